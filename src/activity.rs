@@ -0,0 +1,293 @@
+//! Whether anyone is at the PC, for adaptive polling and perf mode
+//!
+//! The power listener reports the display turning off/on and the idle sensor
+//! reports the user going away (`crate::events::AWAY_THRESHOLD_SECS`) and
+//! coming back. While either holds, the sampled system sensors (CPU, memory,
+//! GPU, network, disk) slow down to `intervals.idle_poll`; they are woken
+//! through [`subscribe`] and poll again right away once the user is back.
+//!
+//! With `perf_mode` on, a running game (or the `perf_mode` switch) pauses
+//! those sensors, the custom sensors and the WMI-heavy ones outright until the
+//! game exits.
+//!
+//! It also collects activity that isn't keyboard/mouse input (controller
+//! input, sound playing) for the idle sensors to fold in per `idle`.
+
+use log::warn;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::time::{Interval, MissedTickBehavior, interval};
+
+/// What polling should do right now
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Activity {
+    #[default]
+    Active,
+    /// User idle or display off: slow down
+    Away,
+    /// Perf mode: poll nothing non-essential
+    Paused,
+}
+
+impl Activity {
+    pub fn is_paused(self) -> bool {
+        self == Self::Paused
+    }
+}
+
+/// Ticks of a paused sensor are skipped; they only keep the interval alive.
+const PAUSED_TICK_SECS: u64 = 3600;
+
+static DISPLAY_OFF: AtomicBool = AtomicBool::new(false);
+static USER_IDLE: AtomicBool = AtomicBool::new(false);
+static GAME_RUNNING: AtomicBool = AtomicBool::new(false);
+/// `features.perf_mode`
+static PERF_MODE: AtomicBool = AtomicBool::new(false);
+/// The `perf_mode` switch
+static PERF_MANUAL: AtomicBool = AtomicBool::new(false);
+
+/// Current activity, changed only on a transition
+static ACTIVITY: LazyLock<watch::Sender<Activity>> =
+    LazyLock::new(|| watch::channel(Activity::Active).0);
+
+/// Apply the config's `perf_mode` flag; turning it off also drops a manual
+/// pause, so the sensors can't stay paused with the switch gone. Likewise a
+/// stopped game or idle sensor no longer holds its last report.
+pub fn configure(config: &crate::config::Config) {
+    let f = &config.features;
+    PERF_MODE.store(f.perf_mode, Ordering::Relaxed);
+    if !f.perf_mode {
+        PERF_MANUAL.store(false, Ordering::Relaxed);
+    }
+    if !f.running_game {
+        GAME_RUNNING.store(false, Ordering::Relaxed);
+    }
+    if !f.idle_tracking {
+        USER_IDLE.store(false, Ordering::Relaxed);
+    }
+    update();
+}
+
+pub fn set_display_off(off: bool) {
+    DISPLAY_OFF.store(off, Ordering::Relaxed);
+    update();
+}
+
+pub fn set_user_idle(idle: bool) {
+    USER_IDLE.store(idle, Ordering::Relaxed);
+    update();
+}
+
+pub fn set_game_running(running: bool) {
+    GAME_RUNNING.store(running, Ordering::Relaxed);
+    update();
+}
+
+/// Whether the game sensor last saw a game running
+pub fn game_running() -> bool {
+    GAME_RUNNING.load(Ordering::Relaxed)
+}
+
+pub fn set_perf_manual(on: bool) {
+    PERF_MANUAL.store(on, Ordering::Relaxed);
+    update();
+}
+
+/// The `perf_mode` switch's `PerfMode` command: `ON` or `OFF`.
+pub fn perf_mode_command(payload: &str) {
+    match payload.trim().to_ascii_uppercase().as_str() {
+        "ON" => set_perf_manual(true),
+        "OFF" => set_perf_manual(false),
+        other => warn!("PerfMode: expected ON or OFF, got {other:?}"),
+    }
+}
+
+/// Why sensors are paused: "manual" (the switch) or "game", None if not
+fn pause_reason() -> Option<&'static str> {
+    if !PERF_MODE.load(Ordering::Relaxed) {
+        None
+    } else if PERF_MANUAL.load(Ordering::Relaxed) {
+        Some("manual")
+    } else if GAME_RUNNING.load(Ordering::Relaxed) {
+        Some("game")
+    } else {
+        None
+    }
+}
+
+/// What the `perf_mode` switch shows: its own state, and why sensors are
+/// paused (if they are)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PerfState {
+    pub manual: bool,
+    pub reason: Option<&'static str>,
+}
+
+/// Current perf mode state; the switch flips without changing the activity
+/// when a game already paused the sensors, so it's watched separately.
+static PERF: LazyLock<watch::Sender<PerfState>> =
+    LazyLock::new(|| watch::channel(PerfState::default()).0);
+
+fn update() {
+    let perf = PerfState {
+        manual: PERF_MANUAL.load(Ordering::Relaxed),
+        reason: pause_reason(),
+    };
+    PERF.send_if_modified(|current| {
+        let changed = *current != perf;
+        *current = perf;
+        changed
+    });
+    let activity = if perf.reason.is_some() {
+        Activity::Paused
+    } else if DISPLAY_OFF.load(Ordering::Relaxed) || USER_IDLE.load(Ordering::Relaxed) {
+        Activity::Away
+    } else {
+        Activity::Active
+    };
+    ACTIVITY.send_if_modified(|current| {
+        let changed = *current != activity;
+        *current = activity;
+        changed
+    });
+}
+
+/// Resolves `changed()` on every transition
+pub fn subscribe() -> watch::Receiver<Activity> {
+    ACTIVITY.subscribe()
+}
+
+/// Resolves `changed()` whenever the switch or the pause reason changes
+pub fn subscribe_perf() -> watch::Receiver<PerfState> {
+    PERF.subscribe()
+}
+
+/// What the signal timestamps count from
+static EPOCH: LazyLock<Instant> = LazyLock::new(Instant::now);
+/// Last controller input, in ms after `EPOCH` plus one (0 = none yet)
+static GAMEPAD_INPUT: AtomicU64 = AtomicU64::new(0);
+static MEDIA_PLAYING: AtomicBool = AtomicBool::new(false);
+
+/// The gamepad sensor saw controller input.
+pub fn note_gamepad_input() {
+    let ms = EPOCH.elapsed().as_millis() as u64 + 1;
+    GAMEPAD_INPUT.store(ms, Ordering::Relaxed);
+}
+
+pub fn set_media_playing(playing: bool) {
+    MEDIA_PLAYING.store(playing, Ordering::Relaxed);
+}
+
+/// Activity besides input in pc-bridge's own session
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Signals {
+    /// Seconds since controller input
+    pub gamepad_idle: Option<i64>,
+    /// Seconds since input in another session (Remote Desktop)
+    pub remote_idle: Option<i64>,
+    pub media_playing: bool,
+}
+
+/// Current signals, with the platform's `remote_idle`
+pub fn signals(remote_idle: Option<i64>) -> Signals {
+    let gamepad = GAMEPAD_INPUT.load(Ordering::Relaxed);
+    Signals {
+        gamepad_idle: (gamepad > 0).then(|| {
+            let since = (EPOCH.elapsed().as_millis() as u64).saturating_sub(gamepad - 1);
+            (since / 1000) as i64
+        }),
+        remote_idle,
+        media_playing: MEDIA_PLAYING.load(Ordering::Relaxed),
+    }
+}
+
+/// Idle seconds once the signals `policy` allows are folded into
+/// `input_idle`: the most recent activity of any kind wins, and sound
+/// playing counts as activity right now.
+pub fn effective_idle_secs(
+    input_idle: i64,
+    signals: Signals,
+    policy: &crate::config::IdleConfig,
+) -> i64 {
+    if policy.media && signals.media_playing {
+        return 0;
+    }
+    let gamepad = signals.gamepad_idle.filter(|_| policy.gamepad);
+    let remote = signals.remote_idle.filter(|_| policy.remote_sessions);
+    [Some(input_idle), gamepad, remote]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(input_idle)
+        .max(0)
+}
+
+/// Poll period of a sensor normally polled every `secs`: `idle_secs` while
+/// away, unless the sensor is already slower or `idle_secs` is 0 (off).
+pub fn poll_secs(secs: u64, idle_secs: u64, activity: Activity) -> u64 {
+    match activity {
+        Activity::Active => secs,
+        Activity::Away if idle_secs > 0 => secs.max(idle_secs),
+        Activity::Away => secs,
+        Activity::Paused => PAUSED_TICK_SECS,
+    }
+}
+
+/// Interval ticking every [`poll_secs`], first tick immediate: swapping it in
+/// on a transition polls right away, so values snap back on activity.
+pub fn ticker(secs: u64, idle_secs: u64, activity: Activity) -> Interval {
+    let period = poll_secs(secs, idle_secs, activity).max(1);
+    let mut tick = interval(Duration::from_secs(period));
+    tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    tick
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poll_secs_slows_only_while_away() {
+        assert_eq!(poll_secs(10, 120, Activity::Active), 10);
+        assert_eq!(poll_secs(10, 120, Activity::Away), 120);
+        // Already slower than the idle rate, or adaptive polling off
+        assert_eq!(poll_secs(600, 120, Activity::Away), 600);
+        assert_eq!(poll_secs(10, 0, Activity::Away), 10);
+        assert_eq!(poll_secs(10, 0, Activity::Paused), PAUSED_TICK_SECS);
+    }
+
+    #[test]
+    fn test_effective_idle_secs_follows_policy() {
+        use crate::config::IdleConfig;
+
+        let policy = IdleConfig::default();
+        let signals = Signals {
+            gamepad_idle: Some(20),
+            remote_idle: Some(600),
+            media_playing: true,
+        };
+        // Controller input 20s ago beats keyboard/mouse 900s ago; media is
+        // off by default.
+        assert_eq!(effective_idle_secs(900, signals, &policy), 20);
+        assert_eq!(
+            effective_idle_secs(
+                900,
+                signals,
+                &IdleConfig {
+                    media: true,
+                    ..IdleConfig::default()
+                }
+            ),
+            0
+        );
+        let input_only = IdleConfig {
+            gamepad: false,
+            media: false,
+            remote_sessions: false,
+        };
+        assert_eq!(effective_idle_secs(900, signals, &input_only), 900);
+        assert_eq!(effective_idle_secs(5, signals, &policy), 5);
+    }
+}
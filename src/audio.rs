@@ -296,6 +296,47 @@ pub fn toggle_mute() -> bool {
     }
 }
 
+/// Peak level (0.0-1.0) of the default output device over the last meter
+/// period, plus `(pid, peak)` of each of its audio sessions while anything
+/// is audible (pid 0 is system sounds). `None` if the meter can't be read.
+#[cfg(windows)]
+pub fn output_peaks() -> Option<(f32, Vec<(u32, f32)>)> {
+    use windows::Win32::Media::Audio::Endpoints::IAudioMeterInformation;
+    use windows::Win32::Media::Audio::{IAudioSessionControl2, IAudioSessionManager2};
+    use windows::core::Interface;
+
+    ensure_com_init();
+    unsafe {
+        let enumerator: IMMDeviceEnumerator =
+            CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL).ok()?;
+        let device = enumerator.GetDefaultAudioEndpoint(eRender, eConsole).ok()?;
+        let meter: IAudioMeterInformation = device.Activate(CLSCTX_ALL, None).ok()?;
+        let peak = meter.GetPeakValue().ok()?;
+        let mut sessions = Vec::new();
+        if peak > 0.0
+            && let Ok(manager) = device.Activate::<IAudioSessionManager2>(CLSCTX_ALL, None)
+            && let Ok(list) = manager.GetSessionEnumerator()
+        {
+            for i in 0..list.GetCount().unwrap_or(0) {
+                let Ok(control) = list.GetSession(i) else {
+                    continue;
+                };
+                // Every session control also exposes the session's own meter.
+                let (Ok(control2), Ok(meter)) = (
+                    control.cast::<IAudioSessionControl2>(),
+                    control.cast::<IAudioMeterInformation>(),
+                ) else {
+                    continue;
+                };
+                if let (Ok(pid), Ok(peak)) = (control2.GetProcessId(), meter.GetPeakValue()) {
+                    sessions.push((pid, peak));
+                }
+            }
+        }
+        Some((peak, sessions))
+    }
+}
+
 /// Send media key press (play/pause, next, previous, stop)
 #[cfg(windows)]
 pub fn send_media_key(key: MediaKey) {
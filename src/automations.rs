@@ -0,0 +1,229 @@
+//! Local automations
+//!
+//! `automations` rules run a command when something happens on the PC - "after
+//! 30 min idle and no game running, turn the display off", "on resume, run my
+//! custom command". They're carried out by the agent itself: events come from
+//! the in-process event stream (`MqttClient::subscribe_events`), idle time and
+//! the running game from the sensors' last published values, and the command
+//! goes through the normal executor. So they keep working while the broker or
+//! HA is unreachable.
+//!
+//! An `idle` rule fires once per idle stretch, when idle time first reaches
+//! `idle_minutes`, and re-arms on the next input.
+
+use log::{debug, info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{MissedTickBehavior, interval};
+
+use crate::AppState;
+use crate::config::{AutomationRule, AutomationTrigger};
+use crate::events::BridgeEvent;
+use crate::mqtt::Command;
+
+/// How often idle rules re-check idle time.
+const IDLE_POLL: Duration = Duration::from_secs(5);
+
+/// Matches events and idle time against the rules and says which fire.
+struct Engine {
+    rules: Vec<AutomationRule>,
+    /// Per rule: idle rule already fired this idle stretch
+    idle_fired: Vec<bool>,
+}
+
+impl Engine {
+    fn new(rules: Vec<AutomationRule>) -> Self {
+        let idle_fired = vec![false; rules.len()];
+        Self { rules, idle_fired }
+    }
+
+    /// Rules set off by `event`.
+    fn on_event(&self, event: &BridgeEvent, gaming: bool) -> Vec<&AutomationRule> {
+        let trigger = match event {
+            BridgeEvent::UserReturned { .. } => AutomationTrigger::UserReturned,
+            BridgeEvent::ResumedFromSleep => AutomationTrigger::ResumedFromSleep,
+            BridgeEvent::GameStarted { .. } => AutomationTrigger::GameStarted,
+            BridgeEvent::GameStopped { .. } => AutomationTrigger::GameStopped,
+            BridgeEvent::ButtonPressed
+            | BridgeEvent::UserIdle
+            | BridgeEvent::UsbConnected { .. }
+            | BridgeEvent::UsbDisconnected { .. }
+            | BridgeEvent::GameLimitReached { .. }
+            | BridgeEvent::TemperatureCritical { .. }
+            | BridgeEvent::RemoteConnected { .. }
+            | BridgeEvent::RemoteDisconnected { .. }
+            | BridgeEvent::ConsoleConnected { .. }
+            | BridgeEvent::ConsoleDisconnected { .. } => return Vec::new(),
+        };
+        self.rules
+            .iter()
+            .filter(|r| r.trigger == trigger && !(r.unless_gaming && gaming))
+            .collect()
+    }
+
+    /// Idle rules whose threshold `idle_secs` just reached.
+    fn on_idle(&mut self, idle_secs: u64, gaming: bool) -> Vec<&AutomationRule> {
+        let mut due = Vec::new();
+        for (rule, fired) in self.rules.iter().zip(self.idle_fired.iter_mut()) {
+            let Some(minutes) = rule.idle_minutes else {
+                continue;
+            };
+            if rule.trigger != AutomationTrigger::Idle {
+                continue;
+            }
+            if idle_secs < minutes * 60 {
+                *fired = false;
+            } else if !*fired {
+                // Counts as fired even when a game suppresses it, so quitting
+                // the game after an hour idle doesn't fire it on the spot.
+                *fired = true;
+                if !(rule.unless_gaming && gaming) {
+                    due.push(rule);
+                }
+            }
+        }
+        due
+    }
+}
+
+/// Whether the game sensor last reported a running game.
+fn gaming(state: &AppState) -> bool {
+    state
+        .mqtt
+        .sensor_value("runninggames")
+        .is_some_and(|v| v != "none")
+}
+
+/// Queue the rule's command on the executor, like a command from HA.
+async fn fire(state: &AppState, rule: &AutomationRule) {
+    let label = if rule.name.is_empty() {
+        &rule.command
+    } else {
+        &rule.name
+    };
+    info!("Automation '{label}': running {}", rule.command);
+    let cmd = Command {
+        name: rule.command.clone(),
+        payload: rule.payload.clone(),
+    };
+    if state.mqtt.command_sender().send(cmd).await.is_err() {
+        warn!("Automation '{label}': command executor is gone");
+    }
+}
+
+/// Supervised while any `automations` rule is configured.
+pub(crate) async fn run(state: Arc<AppState>) {
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut config_rx = state.config_generation.subscribe();
+    let mut events_rx = state.mqtt.subscribe_events();
+    let mut engine = Engine::new(state.config.read().await.automations.clone());
+    let mut tick = interval(IDLE_POLL);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    info!("Automations started ({} rule(s))", engine.rules.len());
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => break,
+            Ok(()) = config_rx.recv() => {
+                engine = Engine::new(state.config.read().await.automations.clone());
+                debug!("Automations reloaded ({} rule(s))", engine.rules.len());
+            }
+            event = events_rx.recv() => match event {
+                Ok(event) => {
+                    for rule in engine.on_event(&event, gaming(&state)) {
+                        fire(&state, rule).await;
+                    }
+                }
+                Err(RecvError::Lagged(n)) => debug!("Automations lagged {n} events"),
+                Err(RecvError::Closed) => break,
+            },
+            _ = tick.tick() => {
+                let Some(idle_secs) = state
+                    .mqtt
+                    .sensor_value("idle_seconds")
+                    .and_then(|v| v.parse::<u64>().ok())
+                else {
+                    continue;
+                };
+                let gaming = gaming(&state);
+                for rule in engine.on_idle(idle_secs, gaming) {
+                    fire(&state, rule).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(trigger: AutomationTrigger, idle_minutes: Option<u64>) -> AutomationRule {
+        AutomationRule {
+            name: String::new(),
+            trigger,
+            idle_minutes,
+            unless_gaming: false,
+            command: "MonitorOff".to_string(),
+            payload: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_idle_fires_once_per_stretch() {
+        let mut engine = Engine::new(vec![rule(AutomationTrigger::Idle, Some(30))]);
+        assert!(engine.on_idle(29 * 60, false).is_empty());
+        assert_eq!(engine.on_idle(30 * 60, false).len(), 1);
+        assert!(engine.on_idle(45 * 60, false).is_empty());
+        // Input re-arms it.
+        assert!(engine.on_idle(3, false).is_empty());
+        assert_eq!(engine.on_idle(31 * 60, false).len(), 1);
+    }
+
+    #[test]
+    fn test_unless_gaming() {
+        let mut idle = rule(AutomationTrigger::Idle, Some(10));
+        idle.unless_gaming = true;
+        let mut engine = Engine::new(vec![idle]);
+        assert!(engine.on_idle(10 * 60, true).is_empty());
+        // Not fired late when the game quits mid-stretch.
+        assert!(engine.on_idle(20 * 60, false).is_empty());
+
+        let mut resumed = rule(AutomationTrigger::ResumedFromSleep, None);
+        resumed.unless_gaming = true;
+        let engine = Engine::new(vec![resumed]);
+        assert!(
+            engine
+                .on_event(&BridgeEvent::ResumedFromSleep, true)
+                .is_empty()
+        );
+        assert_eq!(
+            engine.on_event(&BridgeEvent::ResumedFromSleep, false).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_events_match_their_trigger() {
+        let engine = Engine::new(vec![
+            rule(AutomationTrigger::GameStarted, None),
+            rule(AutomationTrigger::UserReturned, None),
+        ]);
+        let started = BridgeEvent::GameStarted {
+            game_id: "cs2".to_string(),
+            name: "Counter-Strike 2".to_string(),
+        };
+        let due = engine.on_event(&started, true);
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].trigger, AutomationTrigger::GameStarted);
+        assert!(engine.on_event(&BridgeEvent::UserIdle, false).is_empty());
+        assert_eq!(
+            engine
+                .on_event(&BridgeEvent::UserReturned { idle_seconds: 600 }, false)
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Config backup / restore commands
+//!
+//! `BackupConfig` publishes the running config, secrets redacted, to
+//! `pc-bridge/config/<device>`. `RestoreConfig` takes such a backup as its
+//! payload and writes it as the new userConfig.json:
+//! - redacted secrets keep their current values, unless the backup also
+//!   moves their endpoint (`mqtt.broker`, `ha_api.url`, `http_api.bind`),
+//!   which would hand them to whoever wrote it: that restore is refused
+//! - settings that make the agent run programs or install updates (the
+//!   games and their launch commands, `features`, ... - `LOCAL_ONLY`) are
+//!   never taken from MQTT and stay as they are on the PC
+//! - it must be for this `device_name` and pass `Config::validate`
+//! - the replaced file is kept in `backups/` first, and the write is atomic
+//!
+//! The config watcher then hot-reloads it. Both report to the command
+//! results topic.
+
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use log::{info, warn};
+
+use crate::AppState;
+use crate::config::Config;
+
+/// Stands in for secrets in a backup; restored as the current value.
+const REDACTED: &str = "[REDACTED]";
+
+/// Top-level keys a restore leaves as they are on the PC: they decide which
+/// scripts, programs, shell commands and updates the agent runs.
+const LOCAL_ONLY: &[&str] = &[
+    "games",
+    "features",
+    "allow_global_launch",
+    "allow_global_close",
+    "update_channel",
+    "auto_update",
+    "custom_sensors_enabled",
+    "custom_commands_enabled",
+    "custom_command_privileges_allowed",
+    "allow_raw_commands",
+    "scripts_enabled",
+    "custom_sensors",
+    "custom_commands",
+    "plugins",
+    "speedtest_command",
+];
+
+/// The `BackupConfig` command.
+pub(crate) async fn backup(state: &AppState) {
+    let config = state.config.read().await.clone();
+    let result = match serde_json::to_vec_pretty(&redacted(config)) {
+        Ok(json) => {
+            state.mqtt.publish_config_backup(json).await;
+            info!("Published config backup");
+            serde_json::json!({ "command": "BackupConfig", "result": "success" })
+        }
+        Err(e) => {
+            warn!("BackupConfig: {e}");
+            serde_json::json!({ "command": "BackupConfig", "result": "failed", "error": e.to_string() })
+        }
+    };
+    state.mqtt.publish_command_result(&result).await;
+}
+
+/// The `RestoreConfig` command; `payload` is a `BackupConfig` JSON.
+pub(crate) async fn restore(payload: &str, state: &AppState) {
+    let current = state.config.read().await.clone();
+    let result = match restored(payload, &current) {
+        Ok(config) => match tokio::task::spawn_blocking(move || write(&config)).await {
+            Ok(Ok(kept)) => {
+                info!("Config restored; previous one kept as {}", kept.display());
+                Ok(())
+            }
+            Ok(Err(e)) => Err(e),
+            Err(e) => Err(anyhow::anyhow!("restore task failed: {e}")),
+        },
+        Err(e) => Err(e),
+    };
+    let result = match result {
+        Ok(()) => serde_json::json!({ "command": "RestoreConfig", "result": "success" }),
+        Err(e) => {
+            warn!("RestoreConfig: {e:#}");
+            serde_json::json!({ "command": "RestoreConfig", "result": "failed", "error": format!("{e:#}") })
+        }
+    };
+    state.mqtt.publish_command_result(&result).await;
+}
+
+/// `config` as JSON with its secrets replaced by `REDACTED`.
+fn redacted(mut config: Config) -> serde_json::Value {
+    for secret in [
+        &mut config.mqtt.pass,
+        &mut config.ha_api.token,
+        &mut config.http_api.token,
+    ] {
+        if !secret.is_empty() {
+            *secret = REDACTED.to_string();
+        }
+    }
+    serde_json::to_value(&config).unwrap_or_default()
+}
+
+/// Parse a backup into the config to write, or why it can't be restored.
+fn restored(payload: &str, current: &Config) -> Result<Config> {
+    let mut json: serde_json::Value =
+        serde_json::from_str(payload).context("payload is not JSON")?;
+    let obj = json
+        .as_object_mut()
+        .context("payload is not a JSON object")?;
+    // Round-trip the current values so the key list can't drift from Config.
+    let local = serde_json::to_value(current)?;
+    for &key in LOCAL_ONLY {
+        match local.get(key) {
+            Some(value) => obj.insert(key.to_string(), value.clone()),
+            None => obj.remove(key),
+        };
+    }
+    let mut config: Config = serde_json::from_value(json).context("not a pc-bridge config")?;
+    if config.device_name != current.device_name {
+        bail!(
+            "backup is for device '{}', this is '{}'",
+            config.device_name,
+            current.device_name
+        );
+    }
+    // The saved file never holds the MQTT password, so "" means "unchanged" too.
+    let mqtt_pass_kept = config.mqtt.pass.is_empty() || config.mqtt.pass == REDACTED;
+    for (kept, secret, current_secret, endpoint, current_endpoint, names) in [
+        (
+            mqtt_pass_kept,
+            &mut config.mqtt.pass,
+            &current.mqtt.pass,
+            &config.mqtt.broker,
+            &current.mqtt.broker,
+            ("mqtt.broker", "mqtt.pass"),
+        ),
+        (
+            config.ha_api.token == REDACTED,
+            &mut config.ha_api.token,
+            &current.ha_api.token,
+            &config.ha_api.url,
+            &current.ha_api.url,
+            ("ha_api.url", "ha_api.token"),
+        ),
+        (
+            config.http_api.token == REDACTED,
+            &mut config.http_api.token,
+            &current.http_api.token,
+            &config.http_api.bind,
+            &current.http_api.bind,
+            ("http_api.bind", "http_api.token"),
+        ),
+    ] {
+        if !kept {
+            continue;
+        }
+        if endpoint != current_endpoint && !current_secret.is_empty() {
+            bail!(
+                "backup changes {} but leaves {} redacted; put the secret in the backup to restore it",
+                names.0,
+                names.1
+            );
+        }
+        secret.clone_from(current_secret);
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+/// Keep a copy of the current userConfig.json, then save `config` over it.
+/// Returns where the copy went.
+fn write(config: &Config) -> Result<std::path::PathBuf> {
+    let path = Config::config_path()?;
+    let dir = Config::backups_dir()?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("can't create {}", dir.display()))?;
+    let kept = dir.join(backup_name(time::OffsetDateTime::now_utc()));
+    copy(&path, &kept)?;
+    config.save()?;
+    Ok(kept)
+}
+
+fn copy(from: &Path, to: &Path) -> Result<()> {
+    std::fs::copy(from, to).map(drop).with_context(|| {
+        format!(
+            "can't keep a copy of {} in {}",
+            from.display(),
+            to.display()
+        )
+    })
+}
+
+/// `userConfig-20261015-142530.json` (UTC)
+fn backup_name(at: time::OffsetDateTime) -> String {
+    format!(
+        "userConfig-{:04}{:02}{:02}-{:02}{:02}{:02}.json",
+        at.year(),
+        u8::from(at.month()),
+        at.day(),
+        at.hour(),
+        at.minute(),
+        at.second()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current() -> Config {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "device_name": "desk-pc",
+            "mqtt": { "broker": "tcp://broker:1883", "user": "pc" },
+            "http_api": { "enabled": true, "bind": "0.0.0.0:8765", "token": "s3cret" },
+            "custom_commands_enabled": true,
+        }))
+        .unwrap();
+        config.mqtt.pass = "hunter2".to_string();
+        config
+    }
+
+    #[test]
+    fn test_backup_redacts_secrets() {
+        let json = redacted(current());
+        assert_eq!(json["mqtt"]["pass"], REDACTED);
+        assert_eq!(json["http_api"]["token"], REDACTED);
+        // Unset secrets stay visibly unset.
+        assert_eq!(json["ha_api"]["token"], "");
+        assert_eq!(json["mqtt"]["user"], "pc");
+    }
+
+    #[test]
+    fn test_restore_keeps_secrets_and_local_only_settings() {
+        let current = current();
+        let mut backup = redacted(current.clone());
+        backup["intervals"]["cpu"] = 30.into();
+        // None of these may be changed over MQTT.
+        backup["allow_raw_commands"] = true.into();
+        backup["custom_commands_enabled"] = false.into();
+        backup["plugins"] = serde_json::json!([{ "name": "x", "command": ["/tmp/x"] }]);
+
+        let config = restored(&backup.to_string(), &current).unwrap();
+        assert_eq!(config.intervals.cpu, 30);
+        assert_eq!(config.mqtt.pass, "hunter2");
+        assert_eq!(config.http_api.token, "s3cret");
+        assert!(!config.allow_raw_commands);
+        assert!(config.custom_commands_enabled);
+        assert!(config.plugins.is_empty());
+    }
+
+    #[test]
+    fn test_restore_keeps_local_games_and_features() {
+        let current = current();
+        let mut backup = redacted(current.clone());
+        backup["games"] = serde_json::json!({
+            "evil": { "game_id": "evil", "launch_command": "exe:C:\\evil.exe" }
+        });
+        backup["features"]["cmd_file_transfer"] = true.into();
+        backup["allow_global_close"] = true.into();
+        backup["auto_update"] = true.into();
+        backup["update_channel"] = "beta".into();
+
+        let config = restored(&backup.to_string(), &current).unwrap();
+        assert!(config.games.is_empty());
+        assert_eq!(config.features.cmd_file_transfer, current.features.cmd_file_transfer);
+        assert_eq!(config.allow_global_close, current.allow_global_close);
+        assert_eq!(config.auto_update, current.auto_update);
+        assert_eq!(config.update_channel, current.update_channel);
+    }
+
+    #[test]
+    fn test_restore_wont_send_kept_secrets_elsewhere() {
+        let current = current();
+        for (section, key, value) in [
+            ("mqtt", "broker", "tcp://attacker:1883"),
+            ("http_api", "bind", "0.0.0.0:9999"),
+        ] {
+            let mut backup = redacted(current.clone());
+            backup[section][key] = value.into();
+            assert!(restored(&backup.to_string(), &current).is_err(), "{key}");
+        }
+        // With the secret spelled out, the move is the backup writer's call.
+        let mut backup = redacted(current.clone());
+        backup["mqtt"]["broker"] = "tcp://other:1883".into();
+        backup["mqtt"]["pass"] = "other-pass".into();
+        let config = restored(&backup.to_string(), &current).unwrap();
+        assert_eq!(config.mqtt.pass, "other-pass");
+        // No secret to leak (ha_api.token unset)
+        let mut backup = redacted(current.clone());
+        backup["ha_api"]["url"] = "http://ha.local:8123".into();
+        assert!(restored(&backup.to_string(), &current).is_ok());
+    }
+
+    #[test]
+    fn test_restore_rejects_bad_backups() {
+        let current = current();
+        let mut other_pc = redacted(current.clone());
+        other_pc["device_name"] = "other-pc".into();
+        let mut invalid = redacted(current.clone());
+        invalid["mqtt"]["broker"] = "broker:1883".into();
+        for payload in [
+            "not json".to_string(),
+            "[]".to_string(),
+            other_pc.to_string(),
+            invalid.to_string(),
+        ] {
+            assert!(restored(&payload, &current).is_err(), "{payload}");
+        }
+    }
+
+    #[test]
+    fn test_backup_name() {
+        let at = time::OffsetDateTime::from_unix_timestamp(1_760_537_130).unwrap();
+        assert_eq!(backup_name(at), "userConfig-20251015-140530.json");
+    }
+}
@@ -0,0 +1,255 @@
+//! Disk cleanup command
+//!
+//! `DiskCleanup` empties the Recycle Bin / Trash and deletes temp files not
+//! touched in a day, then publishes the space freed to `disk_cleanup_freed`
+//! (MB, with the per-location split and how many in-use files were skipped as
+//! attributes).
+//! - Windows: SHEmptyRecycleBin (sized first with SHQueryRecycleBin) and the
+//!   user's `%TEMP%`. As a service, that's the service account's bin and temp.
+//! - Linux: the XDG Trash (`~/.local/share/Trash`) and `$TMPDIR` / `/tmp`,
+//!   only entries the agent's user owns.
+//!
+//! Links are removed, never followed, and sockets / pipes are left alone.
+
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+
+use log::{info, warn};
+
+use crate::AppState;
+
+/// Temp entries modified more recently than this may belong to something
+/// still running.
+const TEMP_MIN_AGE: Duration = Duration::from_hours(24);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Report {
+    recycle_bin: u64,
+    temp: u64,
+    /// Files that couldn't be deleted (in use, no permission)
+    skipped: u64,
+}
+
+/// Bytes freed and files skipped by one [`clear_dir`] pass.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Cleared {
+    freed: u64,
+    skipped: u64,
+}
+
+/// The `DiskCleanup` command.
+pub(crate) async fn run(state: &AppState) {
+    if RUNNING.swap(true, Ordering::AcqRel) {
+        warn!("DiskCleanup: a cleanup is already running");
+        return;
+    }
+    info!("Disk cleanup started");
+    let report = tokio::task::spawn_blocking(clean).await;
+    RUNNING.store(false, Ordering::Release);
+    let report = match report {
+        Ok(report) => report,
+        Err(e) => {
+            warn!("DiskCleanup task failed: {e}");
+            return;
+        }
+    };
+
+    let mb = |bytes: u64| (bytes as f64 / 1e6 * 10.0).round() / 10.0;
+    info!(
+        "Disk cleanup freed {:.1} MB (recycle bin {:.1}, temp {:.1}; {} files in use)",
+        mb(report.recycle_bin + report.temp),
+        mb(report.recycle_bin),
+        mb(report.temp),
+        report.skipped
+    );
+    state
+        .mqtt
+        .publish_sensor(
+            "disk_cleanup_freed",
+            &format!("{:.1}", mb(report.recycle_bin + report.temp)),
+        )
+        .await;
+    state
+        .mqtt
+        .publish_sensor_attributes(
+            "disk_cleanup_freed",
+            &serde_json::json!({
+                "recycle_bin_mb": mb(report.recycle_bin),
+                "temp_mb": mb(report.temp),
+                "skipped_files": report.skipped,
+            }),
+        )
+        .await;
+}
+
+fn clean() -> Report {
+    let recycle_bin = empty_recycle_bin().unwrap_or_else(|e| {
+        warn!("DiskCleanup: couldn't empty the recycle bin: {e}");
+        0
+    });
+    let cutoff = SystemTime::now() - TEMP_MIN_AGE;
+    let temp = clear_dir(&std::env::temp_dir(), cutoff);
+    Report {
+        recycle_bin,
+        temp: temp.freed,
+        skipped: temp.skipped,
+    }
+}
+
+/// Delete what's under `dir` (not `dir` itself) last modified before
+/// `cutoff`, file by file so one file in use doesn't keep the rest.
+fn clear_dir(dir: &Path, cutoff: SystemTime) -> Cleared {
+    let mut cleared = Cleared::default();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return cleared;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if !owned(&meta) {
+            continue;
+        }
+        let old = meta.modified().is_ok_and(|t| t < cutoff);
+        let kind = meta.file_type();
+        if kind.is_dir() {
+            let inner = clear_dir(&path, cutoff);
+            cleared.freed += inner.freed;
+            cleared.skipped += inner.skipped;
+            // Only goes if now empty.
+            if old {
+                let _ = fs::remove_dir(&path);
+            }
+        } else if (kind.is_file() || kind.is_symlink()) && old {
+            match fs::remove_file(&path) {
+                Ok(()) if kind.is_file() => cleared.freed += meta.len(),
+                Ok(()) => {}
+                Err(_) => cleared.skipped += 1,
+            }
+        }
+    }
+    cleared
+}
+
+/// Only the agent's own files: in a shared `/tmp` the rest can't be deleted
+/// anyway.
+#[cfg(unix)]
+fn owned(meta: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    meta.uid() == unsafe { libc::geteuid() }
+}
+
+#[cfg(windows)]
+fn owned(_meta: &fs::Metadata) -> bool {
+    true
+}
+
+/// Empty the Recycle Bin on every drive; returns the bytes it held.
+#[cfg(windows)]
+fn empty_recycle_bin() -> Result<u64, String> {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::Shell::{
+        SHERB_NOCONFIRMATION, SHERB_NOPROGRESSUI, SHERB_NOSOUND, SHEmptyRecycleBinW, SHQUERYRBINFO,
+        SHQueryRecycleBinW,
+    };
+    use windows::core::PCWSTR;
+
+    let mut info = SHQUERYRBINFO {
+        cbSize: size_of::<SHQUERYRBINFO>() as u32,
+        ..Default::default()
+    };
+    // Null root: all drives.
+    unsafe { SHQueryRecycleBinW(PCWSTR::null(), &raw mut info) }.map_err(|e| e.to_string())?;
+    let (items, size) = (info.i64NumItems, info.i64Size);
+    // Emptying an empty bin is an error.
+    if items == 0 {
+        return Ok(0);
+    }
+    unsafe {
+        SHEmptyRecycleBinW(
+            HWND::default(),
+            PCWSTR::null(),
+            SHERB_NOCONFIRMATION | SHERB_NOPROGRESSUI | SHERB_NOSOUND,
+        )
+    }
+    .map_err(|e| e.to_string())?;
+    Ok(size.max(0) as u64)
+}
+
+/// Empty the XDG Trash; returns the bytes it held.
+#[cfg(unix)]
+fn empty_recycle_bin() -> Result<u64, String> {
+    let trash = trash_dir().ok_or("no home directory")?;
+    // Everything, however recent.
+    let now = SystemTime::now() + Duration::from_secs(1);
+    let files = clear_dir(&trash.join("files"), now);
+    clear_dir(&trash.join("info"), now);
+    clear_dir(&trash.join("expunged"), now);
+    if files.skipped > 0 {
+        warn!(
+            "DiskCleanup: {} trashed files couldn't be deleted",
+            files.skipped
+        );
+    }
+    Ok(files.freed)
+}
+
+#[cfg(unix)]
+fn trash_dir() -> Option<std::path::PathBuf> {
+    use std::path::PathBuf;
+
+    let data = std::env::var_os("XDG_DATA_HOME")
+        .filter(|d| !d.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))?;
+    Some(data.join("Trash"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clear_dir_keeps_recent() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.tmp"), [0u8; 100]).unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/b.tmp"), [0u8; 50]).unwrap();
+
+        // Everything is newer than a day-old cutoff.
+        let kept = clear_dir(dir.path(), SystemTime::now() - TEMP_MIN_AGE);
+        assert_eq!(kept, Cleared::default());
+        assert!(dir.path().join("sub/b.tmp").exists());
+
+        // A cutoff in the future takes it all, sizes summed across levels.
+        let cleared = clear_dir(dir.path(), SystemTime::now() + Duration::from_secs(1));
+        assert_eq!(
+            cleared,
+            Cleared {
+                freed: 150,
+                skipped: 0
+            }
+        );
+        assert!(dir.path().exists());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_clear_dir_does_not_follow_links() {
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("keep"), b"data").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+
+        let cleared = clear_dir(dir.path(), SystemTime::now() + Duration::from_secs(1));
+        assert_eq!(cleared.freed, 0);
+        assert!(!dir.path().join("link").exists());
+        assert!(outside.path().join("keep").exists());
+    }
+}
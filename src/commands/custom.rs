@@ -5,7 +5,7 @@ use log::{debug, error, info};
 use std::sync::Arc;
 
 use crate::AppState;
-use crate::config::{CustomCommand, CustomCommandType};
+use crate::config::{CommandSandbox, CustomCommand, CustomCommandType};
 
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
@@ -47,16 +47,142 @@ pub async fn execute_custom_command(state: &Arc<AppState>, name: &str) -> anyhow
 
     info!("Executing custom command: {} (admin={})", name, cmd.admin);
 
+    if let Some(limits) = &cmd.sandbox {
+        execute_sandboxed(state, &cmd, limits).await?;
+        return Ok(true);
+    }
+
     // Execute based on type
     match cmd.command_type {
         CustomCommandType::Powershell => execute_powershell(&cmd).await,
         CustomCommandType::Executable => execute_executable(&cmd).await,
         CustomCommandType::Shell => execute_shell(&cmd).await,
+        CustomCommandType::ServiceStart => execute_service(&cmd, ServiceAction::Start).await,
+        CustomCommandType::ServiceStop => execute_service(&cmd, ServiceAction::Stop).await,
+        CustomCommandType::ServiceRestart => execute_service(&cmd, ServiceAction::Restart).await,
     }?;
 
     Ok(true)
 }
 
+/// Program and arguments that run `cmd` unelevated
+fn invocation(cmd: &CustomCommand) -> anyhow::Result<(String, Vec<String>)> {
+    let field = |value: &Option<String>, what: &str| {
+        value
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("No {what} for {} command", cmd.name))
+    };
+    Ok(match cmd.command_type {
+        CustomCommandType::Powershell if cfg!(windows) => (
+            "powershell".to_string(),
+            vec![
+                "-NoProfile".to_string(),
+                "-Command".to_string(),
+                field(&cmd.script, "script")?,
+            ],
+        ),
+        CustomCommandType::Powershell => {
+            anyhow::bail!("PowerShell not available on this platform")
+        }
+        CustomCommandType::Executable => (
+            field(&cmd.path, "path")?,
+            cmd.args.clone().unwrap_or_default(),
+        ),
+        CustomCommandType::Shell if cfg!(windows) => (
+            "cmd".to_string(),
+            vec!["/c".to_string(), field(&cmd.command, "command")?],
+        ),
+        CustomCommandType::Shell => (
+            "sh".to_string(),
+            vec!["-c".to_string(), field(&cmd.command, "command")?],
+        ),
+        CustomCommandType::ServiceStart
+        | CustomCommandType::ServiceStop
+        | CustomCommandType::ServiceRestart => {
+            anyhow::bail!("Service commands can't be sandboxed")
+        }
+    })
+}
+
+/// Run `cmd` under its sandbox limits, then supervise it like a shell
+/// command: killed at the time limit, its outcome published.
+async fn execute_sandboxed(
+    state: &Arc<AppState>,
+    cmd: &CustomCommand,
+    limits: &CommandSandbox,
+) -> anyhow::Result<()> {
+    let (program, args) = invocation(cmd)?;
+    let mut child = super::sandbox::command(&program, &args, limits).spawn()?;
+    // Never leave it running unconfined
+    let confinement = match super::sandbox::confine(&child, limits) {
+        Ok(confinement) => confinement,
+        Err(e) => {
+            let _ = child.kill().await;
+            return Err(anyhow::anyhow!("Couldn't sandbox '{}': {e}", cmd.name));
+        }
+    };
+    let state = Arc::clone(state);
+    let name = cmd.name.clone();
+    let limit = super::sandbox::time_limit(limits);
+    tokio::spawn(async move {
+        super::shell::supervise_for(state, name, child, limit).await;
+        confinement.release();
+    });
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum ServiceAction {
+    Start,
+    Stop,
+    Restart,
+}
+
+/// Start, stop or restart a Windows service, as an elevated PowerShell
+/// `*-Service` (`admin` is required for service commands)
+#[cfg(windows)]
+async fn execute_service(cmd: &CustomCommand, action: ServiceAction) -> anyhow::Result<()> {
+    let service = cmd
+        .service
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No service for service command"))?;
+    // -Force: also stop the services that depend on it
+    let verb = match action {
+        ServiceAction::Start => "Start-Service",
+        ServiceAction::Stop => "Stop-Service -Force",
+        ServiceAction::Restart => "Restart-Service -Force",
+    };
+    let script = format!("{verb} -Name '{}'", service.replace('\'', "''"));
+    execute_powershell(&CustomCommand {
+        command_type: CustomCommandType::Powershell,
+        script: Some(script),
+        ..cmd.clone()
+    })
+    .await
+}
+
+/// Start, stop or restart a systemd unit: `systemctl`, under sudo with
+/// `admin`
+#[cfg(unix)]
+async fn execute_service(cmd: &CustomCommand, action: ServiceAction) -> anyhow::Result<()> {
+    let service = cmd
+        .service
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("No service for service command"))?;
+    let verb = match action {
+        ServiceAction::Start => "start",
+        ServiceAction::Stop => "stop",
+        ServiceAction::Restart => "restart",
+    };
+    execute_executable(&CustomCommand {
+        command_type: CustomCommandType::Executable,
+        path: Some("systemctl".to_string()),
+        args: Some(vec![verb.to_string(), service]),
+        ..cmd.clone()
+    })
+    .await
+}
+
 /// Execute PowerShell command
 #[cfg(windows)]
 async fn execute_powershell(cmd: &CustomCommand) -> anyhow::Result<()> {
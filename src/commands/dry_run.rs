@@ -50,8 +50,40 @@ async fn resolve_action(name: &str, payload: &str, state: &Arc<AppState>) -> Str
         "MonitorOff" => "native:monitor_off".to_string(),
         "MonitorOn" => "native:monitor_on".to_string(),
         "CloseGame" => "native:close_game".to_string(),
+        "StopGame" => "native:stop_game".to_string(),
+        // The game select: what its Launch would run
+        "LaunchGame" => {
+            match crate::commands::game_launch_command(&*state.config.read().await, payload) {
+                Some(launch) => format!("launch:{launch}"),
+                None => "not_found".to_string(),
+            }
+        }
         "Screensaver" => "native:screensaver".to_string(),
         "RefreshSteamGames" => "native:refresh_steam_games".to_string(),
+        "CleanupEntities" => "native:cleanup_entities".to_string(),
+        "ConnectionDiagnostics" => "native:connection_diagnostics".to_string(),
+        "WindowsUpdateInstall" => "native:windows_update_install".to_string(),
+        "PowerPlan" => format!("native:power_plan:{payload}"),
+        "DisplayMode" => format!("native:display_mode:{payload}"),
+        "Rgb" => format!("native:openrgb:{payload}"),
+        "PerfMode" => format!("native:perf_mode:{payload}"),
+        "FocusMode" => format!("native:focus_mode:{payload}"),
+        "ReportActiveWindow" => format!("native:report_active_window:{payload}"),
+        "ReportGames" => format!("native:report_games:{payload}"),
+        "Speedtest" => "native:speedtest".to_string(),
+        "DiskCleanup" => "native:disk_cleanup".to_string(),
+        "Print" => format!("native:print:{payload}"),
+        "DockerStart" => format!("native:docker_start:{payload}"),
+        "DockerStop" => format!("native:docker_stop:{payload}"),
+        "InstallUpdate" => "native:install_update".to_string(),
+        "ClearNotificationHistory" => "native:clear_notification_history".to_string(),
+        "BackupConfig" => "native:backup_config".to_string(),
+        "RestoreConfig" => "native:restore_config".to_string(),
+        "FileDownload" => "native:file_download".to_string(),
+        "FileChunk" => "native:file_chunk".to_string(),
+        "SetWallpaper" => format!("native:set_wallpaper:{payload}"),
+        "Theme" => format!("native:theme:{payload}"),
+        "WakeOnLan" => format!("native:wake_on_lan:{payload}"),
         "MediaPlayPause" => "media:play_pause".to_string(),
         "MediaNext" => "media:next".to_string(),
         "MediaPrevious" => "media:previous".to_string(),
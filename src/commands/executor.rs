@@ -1,13 +1,14 @@
 //! Command executor - handles commands from Home Assistant
 
-use log::{debug, error, info, warn};
+use log::{debug, info, warn};
 use std::os::windows::process::CommandExt;
 use std::process::Command;
 use std::sync::Arc;
-use tokio::sync::{Semaphore, broadcast};
+use tokio::sync::broadcast;
 
 use super::custom::execute_custom_command;
 use super::launcher::expand_launcher_shortcut;
+use super::shell::{ShellRunner, shell_runner};
 use crate::AppState;
 use crate::audio::{self, MediaKey};
 use crate::mqtt::CommandReceiver;
@@ -25,66 +26,23 @@ const STEAM_WAIT_TIMEOUT_SECS: u64 = 90;
 /// would need.
 const STEAM_INIT_DELAY_SECS: u64 = 12;
 
-const CREATE_NO_WINDOW: u32 = 0x08000000;
-const MAX_CONCURRENT_COMMANDS: usize = 5;
-
-/// Predefined commands
-fn get_predefined_command(name: &str) -> Option<&'static str> {
-    match name {
-        "Screensaver" => Some(r#"%windir%\System32\scrnsave.scr /s"#),
-        // These are handled natively in execute_command
-        "Wake" | "Lock" | "Hibernate" | "Restart" | "Shutdown" | "Sleep" | "Logoff"
-        | "MonitorOff" | "MonitorOn" | "CloseGame" | "VolumeSet" | "VolumeMute"
-        | "MediaPlayPause" | "MediaNext" | "MediaPrevious" | "MediaStop" => None,
-        _ => None,
-    }
-}
+pub(super) const CREATE_NO_WINDOW: u32 = 0x08000000;
 
 pub struct CommandExecutor {
     state: Arc<AppState>,
     command_rx: CommandReceiver,
-    semaphore: Arc<Semaphore>,
 }
 
 impl CommandExecutor {
     pub fn new(state: Arc<AppState>, command_rx: CommandReceiver) -> Self {
-        Self {
-            state,
-            command_rx,
-            semaphore: Arc::new(Semaphore::new(MAX_CONCURRENT_COMMANDS)),
-        }
+        Self { state, command_rx }
     }
 
-    pub async fn run(mut self) {
-        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
-
-        loop {
-            tokio::select! {
-                biased;
-                _ = shutdown_rx.recv() => {
-                    debug!("Command executor shutting down");
-                    break;
-                }
-                Some(cmd) = self.command_rx.recv() => {
-                    // Rate limit with semaphore
-                    let permit = match self.semaphore.clone().try_acquire_owned() {
-                        Ok(p) => p,
-                        Err(_) => {
-                            warn!("Command rate limited, dropping: {}", cmd.name);
-                            continue;
-                        }
-                    };
-
-                    let state = Arc::clone(&self.state);
-                    tokio::spawn(async move {
-                        let _permit = permit; // Keep permit alive until done
-                        if let Err(e) = Self::execute_command(&cmd.name, &cmd.payload, &state).await {
-                            error!("Command error: {}", e);
-                        }
-                    });
-                }
-            }
-        }
+    pub async fn run(self) {
+        super::queue::run(self.state, self.command_rx, |cmd, state| async move {
+            Self::execute_command(&cmd.name, &cmd.payload, &state).await
+        })
+        .await;
     }
 
     async fn execute_command(
@@ -117,6 +75,24 @@ impl CommandExecutor {
             warn!("Ignoring '{}' - its feature is disabled", name);
             return Ok(());
         }
+        if !super::power_guard::allows(name, payload, state).await {
+            return Ok(());
+        }
+
+        // The game select sends a display name: run that game's launch
+        // command as a Launch, through the same launch gates.
+        let launch;
+        let (name, payload) = if name == "LaunchGame" {
+            let config = state.config.read().await;
+            let Some(command) = crate::commands::game_launch_command(&config, payload) else {
+                warn!("LaunchGame: no launchable game named {payload:?}");
+                return Ok(());
+            };
+            launch = command;
+            ("Launch", launch.as_str())
+        } else {
+            (name, payload)
+        };
 
         match name {
             // Discord: Leave the current voice channel by simulating a keybind
@@ -142,16 +118,16 @@ impl CommandExecutor {
                 tokio::task::spawn_blocking(wake_display);
                 return Ok(());
             }
+            "Screensaver" => {
+                crate::power::screensaver::run(payload, state).await;
+                return Ok(());
+            }
             "notification" => {
                 if !payload.is_empty() {
-                    // WinRT toast does ~10ms of COM work; keep it off the
-                    // single-threaded runtime (matches the Linux path).
-                    let p = payload.to_string();
-                    tokio::task::spawn_blocking(move || {
-                        if let Err(e) = notification::show_toast(&p) {
-                            warn!("Failed to show notification: {e}");
-                        }
-                    });
+                    // Detached: the busy check and the toast itself block.
+                    let state = Arc::clone(state);
+                    let payload = payload.to_string();
+                    tokio::spawn(async move { notification::deliver(&state, payload).await });
                 }
                 return Ok(());
             }
@@ -160,34 +136,51 @@ impl CommandExecutor {
                 return Ok(());
             }
             "Shutdown" => {
+                // Acked before ExitWindowsEx; the WM_ENDSESSION handler in
+                // power/events.rs repeats it over sync TCP as the backstop.
+                state.mqtt.announce_power_down("off").await;
                 shutdown();
                 return Ok(());
             }
-            "Sleep" => {
-                // Pre-publish via async client as first attempt. The sync TCP
-                // publish in wnd_proc's PBT_APMSUSPEND handler is the hard
-                // guarantee, but this async publish often lands too and gives
-                // an extra safety margin.
-                state
-                    .mqtt
-                    .publish_sensor_retained("sleep_state", "sleeping")
-                    .await;
-                tokio::task::yield_now().await;
-                tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+            "Sleep" | "Hibernate" => {
+                let hibernate = name == "Hibernate";
+                if let Some(cause) = crate::power::suspend::unsupported(hibernate) {
+                    crate::power::suspend::report(state, name, Some(cause)).await;
+                    return Ok(());
+                }
+                // Announce via the async client and wait for the broker's ack.
+                // The sync TCP publish in wnd_proc's PBT_APMSUSPEND handler is
+                // the hard guarantee; this one usually lands first.
+                state.mqtt.announce_power_down("sleeping").await;
                 // SetSuspendState blocks until the machine RESUMES, so run it off
                 // the single-threaded runtime or MQTT keepalives/timers freeze for
                 // the whole suspend (and a vetoed/slow suspend would wedge them).
-                let _ = tokio::task::spawn_blocking(sleep).await;
-                return Ok(());
-            }
-            "Hibernate" => {
-                state
-                    .mqtt
-                    .publish_sensor_retained("sleep_state", "sleeping")
-                    .await;
-                tokio::task::yield_now().await;
-                tokio::time::sleep(std::time::Duration::from_millis(250)).await;
-                let _ = tokio::task::spawn_blocking(hibernate).await;
+                let before = crate::power::suspend_count();
+                let result = tokio::task::spawn_blocking(move || suspend(hibernate))
+                    .await
+                    .unwrap_or_else(|e| Err(e.to_string()))
+                    // It can also return TRUE without suspending; the power
+                    // monitor window sees a PBT_APMSUSPEND when it did.
+                    .and_then(|()| match (before, crate::power::suspend_count()) {
+                        (Some(before), Some(after)) if after == before => {
+                            Err("the PC didn't suspend".to_string())
+                        }
+                        _ => Ok(()),
+                    });
+                match result {
+                    Ok(()) => crate::power::suspend::report(state, name, None).await,
+                    Err(error) => {
+                        // Vetoed or failed: nothing will fire a wake event.
+                        warn!("{} failed, announcing awake again", name);
+                        state.mqtt.publish_availability(true).await;
+                        state
+                            .mqtt
+                            .publish_sensor_retained("sleep_state", "awake")
+                            .await;
+                        let cause = crate::power::suspend::failure_cause(error).await;
+                        crate::power::suspend::report(state, name, Some(&cause)).await;
+                    }
+                }
                 return Ok(());
             }
             "Restart" => {
@@ -214,6 +207,10 @@ impl CommandExecutor {
                 close_running_games(state).await;
                 return Ok(());
             }
+            "StopGame" => {
+                super::stop_game::run(state).await;
+                return Ok(());
+            }
             "VolumeSet" => {
                 if let Ok(level) = payload.parse::<f32>() {
                     tokio::task::spawn_blocking(move || audio::set_volume(level));
@@ -280,10 +277,150 @@ impl CommandExecutor {
                 }
                 return Ok(());
             }
+            "CleanupEntities" => {
+                let config = state.config.read().await.clone();
+                let cleaned = state.mqtt.cleanup_stale_devices(&config).await;
+                info!("Entity cleanup: removed {cleaned} previous device name(s)");
+                return Ok(());
+            }
+            "ConnectionDiagnostics" => {
+                let config = state.config.read().await.clone();
+                state.mqtt.connection_diagnostics(&config).await;
+                return Ok(());
+            }
+            "PowerPlan" => {
+                match crate::power::plan::set_active(payload).await {
+                    Ok(plan) => {
+                        info!("Power plan set to '{plan}'");
+                        // Reflect it now rather than on the sensor's next poll.
+                        state.mqtt.publish_sensor("power_plan", &plan).await;
+                    }
+                    Err(e) => warn!("PowerPlan: {e}"),
+                }
+                return Ok(());
+            }
+            "DisplayMode" => {
+                let modes = state.config.read().await.display_modes.clone();
+                match crate::power::display_mode::set_by_name(&modes, payload).await {
+                    Ok(name) => {
+                        info!("Display mode set to '{name}'");
+                        // Reflect it now rather than on the sensor's next poll.
+                        state.mqtt.publish_sensor("display_mode", &name).await;
+                    }
+                    Err(e) => warn!("DisplayMode: {e}"),
+                }
+                return Ok(());
+            }
+            "Rgb" => {
+                crate::openrgb::run(payload, state).await;
+                return Ok(());
+            }
+            "PerfMode" => {
+                crate::activity::perf_mode_command(payload);
+                return Ok(());
+            }
+            "FocusMode" => {
+                let minutes = state.config.read().await.focus.minutes;
+                crate::focus::command(payload, minutes);
+                return Ok(());
+            }
+            "ReportActiveWindow" | "ReportGames" => {
+                if let Some(reported) = crate::reporting::Reported::from_command(name) {
+                    crate::reporting::command(reported, payload);
+                }
+                return Ok(());
+            }
+            "Speedtest" => {
+                // Detached: a test takes tens of seconds.
+                let state = Arc::clone(state);
+                tokio::spawn(async move { crate::speedtest::run(&state).await });
+                return Ok(());
+            }
+            "DiskCleanup" => {
+                let state = Arc::clone(state);
+                tokio::spawn(async move { crate::cleanup::run(&state).await });
+                return Ok(());
+            }
+            "Print" => {
+                crate::sensors::print_payload(payload, state).await;
+                return Ok(());
+            }
+            "DockerStart" | "DockerStop" => {
+                // A stop waits out the container's grace period; don't hold up
+                // other commands meanwhile.
+                let state = Arc::clone(state);
+                let (name, start) = (payload.to_string(), name == "DockerStart");
+                tokio::spawn(
+                    async move { crate::sensors::docker_command(&name, start, &state).await },
+                );
+                return Ok(());
+            }
+            "WakeOnLan" => {
+                crate::wol::run(payload, state).await;
+                return Ok(());
+            }
+            "ClearNotificationHistory" => {
+                notification::clear_history(state).await;
+                return Ok(());
+            }
+            "BackupConfig" => {
+                crate::backup::backup(state).await;
+                return Ok(());
+            }
+            "RestoreConfig" => {
+                crate::backup::restore(payload, state).await;
+                return Ok(());
+            }
+            "FileDownload" => {
+                crate::transfer::download(payload, state).await;
+                return Ok(());
+            }
+            "FileChunk" => {
+                crate::transfer::chunk(payload, state).await;
+                return Ok(());
+            }
+            "SetWallpaper" => {
+                crate::transfer::set_wallpaper(payload, state).await;
+                return Ok(());
+            }
+            "Theme" => {
+                crate::sensors::set_theme(payload).await;
+                return Ok(());
+            }
+            "InstallUpdate" => {
+                // Detached: the download can take a while, and a successful
+                // install restarts the process from inside the task.
+                info!("Installing agent update");
+                let state = Arc::clone(state);
+                tokio::spawn(async move { crate::updater::install_latest(&state).await });
+                return Ok(());
+            }
+            "WindowsUpdateInstall" => {
+                // Download + install can take the better part of an hour, so it
+                // runs detached rather than holding a command slot; the WUA guard
+                // refuses a second press while one is in flight.
+                info!("Starting Windows Update install");
+                let state = Arc::clone(state);
+                tokio::spawn(async move {
+                    match tokio::task::spawn_blocking(crate::sensors::install_windows_updates).await
+                    {
+                        Ok(Ok(outcome)) => info!("Windows Update: {outcome}"),
+                        Ok(Err(e)) => warn!("Windows Update install failed: {e}"),
+                        Err(e) => warn!("Windows Update install task failed: {e}"),
+                    }
+                    crate::sensors::refresh_windows_updates(&state).await;
+                });
+                return Ok(());
+            }
             _ => {}
         }
 
-        // Check for custom command first
+        // Check for a plugin command
+        if crate::plugins::dispatch(name, payload).await {
+            return Ok(());
+        }
+
+        // Then a custom command
         if execute_custom_command(state, name).await? {
             return Ok(());
         }
@@ -340,10 +477,7 @@ impl CommandExecutor {
             return Ok(());
         }
 
-        let cmd_str = match resolve_shell_command(name, &expanded_payload, allow_raw) {
-            // Predefined commands are trusted and may embed env vars (e.g.
-            // Screensaver = %windir%\...), so expand their output here.
-            ShellResolution::Predefined(cmd) => expand_env_vars(&cmd),
+        let cmd_str = match resolve_shell_command(&expanded_payload, allow_raw) {
             // Launcher output was validated post-expansion; raw is the (opt-in)
             // already-expanded payload. Neither needs another expansion pass.
             ShellResolution::LauncherShortcut(cmd) | ShellResolution::RawCommand(cmd) => cmd,
@@ -369,53 +503,41 @@ impl CommandExecutor {
 
         info!("Running: {}", cmd_str);
 
-        // Build PowerShell command
-        let ps_cmd = if needs_ampersand(&cmd_str) {
-            format!("& {}", cmd_str)
-        } else {
-            cmd_str
-        };
-
-        // Execute via PowerShell
-        let mut child = Command::new("powershell")
-            .args(["-NoProfile", "-Command", &ps_cmd])
-            .creation_flags(CREATE_NO_WINDOW)
-            .spawn()?;
-
-        // Capture PID before moving child into spawn_blocking so we can kill
-        // the process tree on timeout (calling .kill() is impossible once the
-        // Child is moved into the blocking closure).
-        let pid = child.id();
-
-        // Wait with timeout in background
-        tokio::spawn(async move {
-            match tokio::time::timeout(
-                std::time::Duration::from_secs(300),
-                tokio::task::spawn_blocking(move || child.wait()),
-            )
-            .await
-            {
-                Ok(Ok(Ok(status))) => {
-                    if !status.success() {
-                        warn!("Command exited with: {}", status);
-                    }
-                }
-                Ok(Ok(Err(e))) => error!("Command wait error: {}", e),
-                Ok(Err(e)) => error!("Task join error: {}", e),
-                Err(_) => {
-                    warn!(
-                        "Command timed out after 5 minutes, killing process tree (PID {})",
-                        pid
-                    );
-                    // Kill the entire process tree. The spawn_blocking task
-                    // blocked on child.wait() will unblock once the process dies.
-                    let _ = Command::new("taskkill")
-                        .args(["/F", "/T", "/PID", &pid.to_string()])
-                        .creation_flags(CREATE_NO_WINDOW)
-                        .spawn();
-                }
+        let child = match shell_runner(&cmd_str) {
+            // URLs, documents and programs go straight to the shell, without
+            // spawning anything to wait on
+            ShellRunner::Open { target, args } => {
+                let result =
+                    tokio::task::spawn_blocking(move || shell_open(&target, args.as_deref()))
+                        .await
+                        .unwrap_or_else(|e| Err(e.to_string()));
+                super::shell::report_open(state, name, result).await;
+                return Ok(());
+            }
+            // Plain command lines skip PowerShell's startup; /S keeps cmd
+            // from second-guessing the quotes inside.
+            ShellRunner::Cmd(cmd) => tokio::process::Command::new("cmd")
+                .raw_arg(format!("/D /S /C \"{cmd}\""))
+                .creation_flags(CREATE_NO_WINDOW)
+                .spawn()?,
+            ShellRunner::PowerShell(cmd) => {
+                let ps_cmd = if needs_ampersand(&cmd) {
+                    format!("& {}", cmd)
+                } else {
+                    cmd
+                };
+                tokio::process::Command::new("powershell")
+                    .args(["-NoProfile", "-Command", &ps_cmd])
+                    .creation_flags(CREATE_NO_WINDOW)
+                    .spawn()?
             }
-        });
+        };
+        // A timeout kills the command's whole process tree
+        tokio::spawn(super::shell::supervise(
+            Arc::clone(state),
+            name.to_string(),
+            child,
+        ));
 
         Ok(())
     }
@@ -609,6 +731,7 @@ pub(crate) fn resolve_command_action(
         "DiscordLeaveChannel" => return CommandAction::Native("DiscordLeaveChannel"),
         "Wake" => return CommandAction::Native("Wake"),
         "Lock" => return CommandAction::Native("Lock"),
+        "Screensaver" => return CommandAction::Native("Screensaver"),
         "Shutdown" => return CommandAction::Native("Shutdown"),
         "Sleep" => return CommandAction::Native("Sleep"),
         "Hibernate" => return CommandAction::Native("Hibernate"),
@@ -643,8 +766,8 @@ pub(crate) fn resolve_command_action(
         _ => {}
     }
 
-    // Shell resolution (predefined → launcher → raw → blocked → not found)
-    let resolution = resolve_shell_command(name, payload, allow_raw_commands);
+    // Shell resolution (launcher → raw → blocked → not found)
+    let resolution = resolve_shell_command(payload, allow_raw_commands);
     match &resolution {
         ShellResolution::Blocked => CommandAction::NoOp("blocked"),
         ShellResolution::NotFound => CommandAction::NoOp("not_found"),
@@ -659,8 +782,6 @@ pub(crate) fn resolve_command_action(
 /// because this logic was inline and untestable without a real Windows env.
 #[derive(Debug, PartialEq)]
 pub(crate) enum ShellResolution {
-    /// Predefined command (Screensaver, Shutdown, sleep)
-    Predefined(String),
     /// Validated launcher shortcut (steam:, exe:, close:, url:, etc.)
     LauncherShortcut(String),
     /// Raw payload, allowed by config
@@ -671,41 +792,30 @@ pub(crate) enum ShellResolution {
     NotFound,
 }
 
-/// Resolve a command name + payload into a shell command string.
+/// Resolve a command payload into a shell command string.
 ///
 /// Called after native commands (Wake, Lock, etc.) and custom commands have
 /// been checked. This function is pure (no I/O) and fully unit-testable.
 ///
 /// Order of resolution:
-/// 1. Predefined commands (matched by name)
-/// 2. Launcher shortcuts (matched by payload prefix like `steam:`, `close:`)
-/// 3. Raw payload (gated by `allow_raw_commands`)
-/// 4. Not found (empty payload, unknown name)
-pub(crate) fn resolve_shell_command(
-    name: &str,
-    payload: &str,
-    allow_raw_commands: bool,
-) -> ShellResolution {
-    // 1. Predefined commands always work regardless of allow_raw_commands
-    if let Some(predefined) = get_predefined_command(name) {
-        return ShellResolution::Predefined(predefined.to_string());
-    }
-
-    // 2. Payload-based resolution
+/// 1. Launcher shortcuts (matched by payload prefix like `steam:`, `close:`)
+/// 2. Raw payload (gated by `allow_raw_commands`)
+/// 3. Not found (empty payload)
+pub(crate) fn resolve_shell_command(payload: &str, allow_raw_commands: bool) -> ShellResolution {
     if !payload.is_empty() {
-        // Launcher shortcuts are always allowed - they're validated and safe
+        // 1. Launcher shortcuts are always allowed - they're validated and safe
         if let Some(expanded) = expand_launcher_shortcut(payload) {
             return ShellResolution::LauncherShortcut(expanded);
         }
 
-        // 3. Raw payload: only if configured
+        // 2. Raw payload: only if configured
         if allow_raw_commands {
             return ShellResolution::RawCommand(payload.to_string());
         }
         return ShellResolution::Blocked;
     }
 
-    // 4. No payload and not a predefined command
+    // 3. No payload
     ShellResolution::NotFound
 }
 
@@ -725,6 +835,40 @@ fn needs_ampersand(cmd: &str) -> bool {
     !ps_cmdlets.iter().any(|prefix| cmd.starts_with(prefix))
 }
 
+/// What `Start-Process` does without PowerShell: `ShellExecuteW` "open" on a
+/// URL, document or program. Blocking; it may wait on the handler's DDE.
+fn shell_open(target: &str, args: Option<&str>) -> Result<(), String> {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::Shell::ShellExecuteW;
+    use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+    use windows::core::{PCWSTR, w};
+
+    let wide = |s: &str| -> Vec<u16> { s.encode_utf16().chain(std::iter::once(0)).collect() };
+    let file = wide(target);
+    let params = args.map(wide);
+    let params_ptr = params
+        .as_ref()
+        .map_or(PCWSTR::null(), |p| PCWSTR::from_raw(p.as_ptr()));
+    // SAFETY: both strings are NUL-terminated and outlive the call.
+    let code = unsafe {
+        ShellExecuteW(
+            HWND::default(),
+            w!("open"),
+            PCWSTR::from_raw(file.as_ptr()),
+            params_ptr,
+            PCWSTR::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // Values above 32 mean success; anything else is an error code.
+    let code = code.0 as isize;
+    if code > 32 {
+        Ok(())
+    } else {
+        Err(format!("ShellExecute failed for {target} (error {code})"))
+    }
+}
+
 /// Expand Windows-style %VAR% environment variables (single-pass)
 fn expand_env_vars(s: &str) -> String {
     if !s.contains('%') {
@@ -934,21 +1078,15 @@ fn logoff() {
     }
 }
 
-/// Sleep (native, no PowerShell)
-fn sleep() {
+/// Sleep or hibernate (native, no PowerShell). Returns once the machine
+/// resumes; the OS error if it never suspended.
+fn suspend(hibernate: bool) -> Result<(), String> {
     use windows::Win32::System::Power::SetSuspendState;
-    unsafe {
-        // SetSuspendState(hibernate=false, force=false, wakeupEventsDisabled=false)
-        let _ = SetSuspendState(false, false, false);
-    }
-}
-
-/// Hibernate (native, no PowerShell)
-fn hibernate() {
-    use windows::Win32::System::Power::SetSuspendState;
-    unsafe {
-        // SetSuspendState(hibernate=true, force=false, wakeupEventsDisabled=false)
-        let _ = SetSuspendState(true, false, false);
+    // SetSuspendState(hibernate, force=false, wakeupEventsDisabled=false)
+    if unsafe { SetSuspendState(hibernate, false, false) }.as_bool() {
+        Ok(())
+    } else {
+        Err(windows::core::Error::from_win32().message())
     }
 }
 
@@ -1005,56 +1143,12 @@ mod tests {
     // allow_raw_commands=false. The extraction of resolve_shell_command
     // ensures this class of bug is caught by unit tests going forward.
 
-    // -- Predefined commands --
-
-    #[test]
-    fn test_resolve_predefined_screensaver() {
-        let result = resolve_shell_command("Screensaver", "", false);
-        assert!(
-            matches!(result, ShellResolution::Predefined(ref cmd) if cmd.contains("scrnsave.scr")),
-            "Screensaver should resolve to predefined command: {result:?}"
-        );
-    }
-
-    #[test]
-    fn test_resolve_shutdown_returns_not_found() {
-        // Shutdown is now handled natively, not via shell
-        let result = resolve_shell_command("Shutdown", "", false);
-        assert_eq!(result, ShellResolution::NotFound);
-    }
-
-    #[test]
-    fn test_resolve_sleep_returns_not_found() {
-        // Sleep is now handled natively, not via shell
-        let result = resolve_shell_command("Sleep", "", false);
-        assert_eq!(result, ShellResolution::NotFound);
-    }
-
-    #[test]
-    fn test_resolve_predefined_ignores_allow_raw_commands() {
-        // Predefined commands must work regardless of allow_raw_commands
-        for allow_raw in [false, true] {
-            let result = resolve_shell_command("Screensaver", "", allow_raw);
-            assert!(
-                matches!(result, ShellResolution::Predefined(_)),
-                "Predefined should resolve with allow_raw={allow_raw}: {result:?}"
-            );
-        }
-    }
-
-    #[test]
-    fn test_resolve_predefined_with_payload_still_predefined() {
-        // If name matches predefined, the predefined wins even if payload exists
-        let result = resolve_shell_command("Screensaver", "some:payload", false);
-        assert!(matches!(result, ShellResolution::Predefined(_)));
-    }
-
     // -- Launcher shortcuts (THE BUG SCENARIO) --
 
     #[test]
     fn test_resolve_steam_shortcut_allowed_when_raw_disabled() {
         // THIS IS THE EXACT BUG: steam: shortcuts must work even with allow_raw_commands=false
-        let result = resolve_shell_command("Launch", "steam:730", false);
+        let result = resolve_shell_command("steam:730", false);
         assert!(
             matches!(result, ShellResolution::LauncherShortcut(ref cmd) if cmd.contains("steam://rungameid/730")),
             "steam: shortcut must work with allow_raw_commands=false: {result:?}"
@@ -1063,7 +1157,7 @@ mod tests {
 
     #[test]
     fn test_resolve_update_shortcut_allowed_when_raw_disabled() {
-        let result = resolve_shell_command("Launch", "update:730", false);
+        let result = resolve_shell_command("update:730", false);
         assert!(
             matches!(result, ShellResolution::LauncherShortcut(ref cmd) if cmd.contains("steam://validate/730")),
             "update: shortcut must work with allow_raw_commands=false: {result:?}"
@@ -1072,7 +1166,7 @@ mod tests {
 
     #[test]
     fn test_resolve_validate_shortcut_allowed_when_raw_disabled() {
-        let result = resolve_shell_command("Launch", "validate:730", false);
+        let result = resolve_shell_command("validate:730", false);
         assert!(
             matches!(result, ShellResolution::LauncherShortcut(ref cmd) if cmd.contains("steam://validate/730")),
             "validate: shortcut must work with allow_raw_commands=false: {result:?}"
@@ -1082,7 +1176,7 @@ mod tests {
     #[test]
     fn test_resolve_close_shortcut_allowed_when_raw_disabled() {
         // close: shortcuts must work even with allow_raw_commands=false
-        let result = resolve_shell_command("Launch", "close:notepad", false);
+        let result = resolve_shell_command("close:notepad", false);
         assert!(
             matches!(result, ShellResolution::LauncherShortcut(ref cmd) if cmd.contains("CloseMainWindow")),
             "close: shortcut must work with allow_raw_commands=false: {result:?}"
@@ -1091,7 +1185,7 @@ mod tests {
 
     #[test]
     fn test_resolve_kill_shortcut_allowed_when_raw_disabled() {
-        let result = resolve_shell_command("Launch", "kill:notepad", false);
+        let result = resolve_shell_command("kill:notepad", false);
         assert!(
             matches!(result, ShellResolution::LauncherShortcut(ref cmd) if cmd.contains("CloseMainWindow")),
             "kill: shortcut must work with allow_raw_commands=false: {result:?}"
@@ -1100,7 +1194,7 @@ mod tests {
 
     #[test]
     fn test_resolve_exe_shortcut_allowed_when_raw_disabled() {
-        let result = resolve_shell_command("Launch", r"exe:C:\Games\Game.exe", false);
+        let result = resolve_shell_command(r"exe:C:\Games\Game.exe", false);
         assert!(
             matches!(result, ShellResolution::LauncherShortcut(ref cmd) if cmd.contains("Start-Process")),
             "exe: shortcut must work with allow_raw_commands=false: {result:?}"
@@ -1109,7 +1203,7 @@ mod tests {
 
     #[test]
     fn test_resolve_lnk_shortcut_allowed_when_raw_disabled() {
-        let result = resolve_shell_command("Launch", r"lnk:C:\Users\user\Desktop\Game.lnk", false);
+        let result = resolve_shell_command(r"lnk:C:\Users\user\Desktop\Game.lnk", false);
         assert!(
             matches!(result, ShellResolution::LauncherShortcut(ref cmd) if cmd.contains("Start-Process")),
             "lnk: shortcut must work with allow_raw_commands=false: {result:?}"
@@ -1118,8 +1212,7 @@ mod tests {
 
     #[test]
     fn test_resolve_url_shortcut_allowed_when_raw_disabled() {
-        let result =
-            resolve_shell_command("Launch", "url:discord://discord.com/channels/1/2", false);
+        let result = resolve_shell_command("url:discord://discord.com/channels/1/2", false);
         assert!(
             matches!(result, ShellResolution::LauncherShortcut(ref cmd) if cmd.contains("Start-Process")),
             "url: shortcut must work with allow_raw_commands=false: {result:?}"
@@ -1128,7 +1221,7 @@ mod tests {
 
     #[test]
     fn test_resolve_epic_shortcut_allowed_when_raw_disabled() {
-        let result = resolve_shell_command("Launch", "epic:Fortnite", false);
+        let result = resolve_shell_command("epic:Fortnite", false);
         assert!(
             matches!(result, ShellResolution::LauncherShortcut(ref cmd) if cmd.contains("epicgames")),
             "epic: shortcut must work with allow_raw_commands=false: {result:?}"
@@ -1147,7 +1240,7 @@ mod tests {
         ];
 
         for (payload, expected_substr) in cases {
-            let result = resolve_shell_command("Launch", payload, true);
+            let result = resolve_shell_command(payload, true);
             assert!(
                 matches!(result, ShellResolution::LauncherShortcut(ref cmd) if cmd.contains(expected_substr)),
                 "Launcher shortcut should work with raw=true: payload={payload}, result={result:?}"
@@ -1159,13 +1252,13 @@ mod tests {
 
     #[test]
     fn test_resolve_raw_payload_blocked_when_disabled() {
-        let result = resolve_shell_command("Launch", "notepad.exe", false);
+        let result = resolve_shell_command("notepad.exe", false);
         assert_eq!(result, ShellResolution::Blocked);
     }
 
     #[test]
     fn test_resolve_raw_payload_allowed_when_enabled() {
-        let result = resolve_shell_command("Launch", "notepad.exe", true);
+        let result = resolve_shell_command("notepad.exe", true);
         assert_eq!(
             result,
             ShellResolution::RawCommand("notepad.exe".to_string())
@@ -1175,158 +1268,73 @@ mod tests {
     #[test]
     fn test_resolve_raw_complex_payload_blocked() {
         // A complex PowerShell command should be blocked without allow_raw_commands
-        let result = resolve_shell_command(
-            "Launch",
-            "Get-Process | Where-Object { $_.CPU -gt 100 }",
-            false,
-        );
+        let result = resolve_shell_command("Get-Process | Where-Object { $_.CPU -gt 100 }", false);
         assert_eq!(result, ShellResolution::Blocked);
     }
 
     #[test]
     fn test_resolve_raw_complex_payload_allowed() {
         let payload = "Get-Process | Where-Object { $_.CPU -gt 100 }";
-        let result = resolve_shell_command("Launch", payload, true);
+        let result = resolve_shell_command(payload, true);
         assert_eq!(result, ShellResolution::RawCommand(payload.to_string()));
     }
 
     // -- Not found --
 
     #[test]
-    fn test_resolve_unknown_name_empty_payload() {
-        let result = resolve_shell_command("unknown_command", "", false);
+    fn test_resolve_empty_payload() {
+        let result = resolve_shell_command("", false);
         assert_eq!(result, ShellResolution::NotFound);
     }
 
     #[test]
-    fn test_resolve_unknown_name_empty_payload_raw_enabled() {
+    fn test_resolve_empty_payload_raw_enabled() {
         // Even with raw commands enabled, empty payload = not found
-        let result = resolve_shell_command("unknown_command", "", true);
+        let result = resolve_shell_command("", true);
         assert_eq!(result, ShellResolution::NotFound);
     }
 
-    // -- Native command names should NOT match predefined --
-
-    #[test]
-    fn test_resolve_native_commands_return_not_found() {
-        // Native commands (Wake, Lock, etc.) are handled before resolve_shell_command
-        // is called. If they somehow reach here, they should be NotFound.
-        let native_names = [
-            "Wake",
-            "Lock",
-            "Hibernate",
-            "Restart",
-            "VolumeSet",
-            "VolumeMute",
-            "MediaPlayPause",
-            "MediaNext",
-            "MediaPrevious",
-            "MediaStop",
-        ];
-
-        for name in native_names {
-            let result = resolve_shell_command(name, "", false);
-            assert_eq!(
-                result,
-                ShellResolution::NotFound,
-                "{name} should be NotFound (handled natively before this function)"
-            );
-        }
-    }
-
     // -- Edge cases --
 
     #[test]
     fn test_resolve_invalid_steam_id_falls_through_to_raw() {
         // steam:abc is not valid (non-numeric), expand_launcher_shortcut returns None
         // So it falls through to raw command check
-        let result = resolve_shell_command("Launch", "steam:abc", false);
+        let result = resolve_shell_command("steam:abc", false);
         assert_eq!(result, ShellResolution::Blocked);
 
-        let result = resolve_shell_command("Launch", "steam:abc", true);
+        let result = resolve_shell_command("steam:abc", true);
         assert_eq!(result, ShellResolution::RawCommand("steam:abc".to_string()));
     }
 
     #[test]
     fn test_resolve_url_without_scheme_falls_through() {
         // url:not-a-url has no :// so expand_launcher_shortcut returns None
-        let result = resolve_shell_command("Launch", "url:not-a-url", false);
+        let result = resolve_shell_command("url:not-a-url", false);
         assert_eq!(result, ShellResolution::Blocked);
     }
 
     #[test]
     fn test_resolve_shell_injection_in_url_falls_through() {
         // Shell metacharacters are rejected by expand_launcher_shortcut
-        let result = resolve_shell_command("Launch", "url:discord://x;rm -rf /", false);
+        let result = resolve_shell_command("url:discord://x;rm -rf /", false);
         assert_eq!(result, ShellResolution::Blocked);
     }
 
     #[test]
     fn test_resolve_close_with_injection_falls_through() {
         // Process name with shell chars rejected by is_safe_identifier
-        let result = resolve_shell_command("Launch", "close:bad;name", false);
+        let result = resolve_shell_command("close:bad;name", false);
         assert_eq!(result, ShellResolution::Blocked);
     }
 
     #[test]
     fn test_resolve_empty_launcher_arg_falls_through() {
         // steam: with no arg -> expand_launcher_shortcut returns None
-        let result = resolve_shell_command("Launch", "steam:", false);
+        let result = resolve_shell_command("steam:", false);
         assert_eq!(result, ShellResolution::Blocked);
     }
 
-    // ===================================================================
-    // get_predefined_command tests
-    // ===================================================================
-
-    #[test]
-    fn test_predefined_screensaver() {
-        let cmd = get_predefined_command("Screensaver");
-        assert!(cmd.is_some());
-        assert!(cmd.unwrap().contains("scrnsave.scr"));
-    }
-
-    #[test]
-    fn test_predefined_shutdown_returns_none() {
-        // Shutdown is now handled natively
-        assert_eq!(get_predefined_command("Shutdown"), None);
-    }
-
-    #[test]
-    fn test_predefined_sleep_returns_none() {
-        // Sleep is now handled natively
-        assert_eq!(get_predefined_command("Sleep"), None);
-    }
-
-    #[test]
-    fn test_predefined_native_commands_return_none() {
-        // These are handled natively, not via shell command
-        for name in &[
-            "Wake",
-            "Lock",
-            "Shutdown",
-            "Sleep",
-            "Hibernate",
-            "Restart",
-            "VolumeSet",
-            "VolumeMute",
-            "MediaPlayPause",
-            "MediaNext",
-            "MediaPrevious",
-            "MediaStop",
-        ] {
-            assert!(
-                get_predefined_command(name).is_none(),
-                "{name} should be None"
-            );
-        }
-    }
-
-    #[test]
-    fn test_predefined_unknown_returns_none() {
-        assert!(get_predefined_command("nonexistent").is_none());
-    }
-
     // ===================================================================
     // needs_ampersand tests
     // ===================================================================
@@ -1606,16 +1614,13 @@ mod tests {
     // -- Shell commands through the full pipeline --
 
     #[test]
-    fn test_action_screensaver_produces_shell() {
-        let action = resolve_command_action("Screensaver", "", false);
-        assert!(
-            matches!(
-                action,
-                CommandAction::Shell(ShellResolution::Predefined(ref cmd))
-                if cmd.contains("scrnsave.scr")
-            ),
-            "Screensaver should produce a shell command: {action:?}"
-        );
+    fn test_action_screensaver_is_native() {
+        for payload in ["", "display_off", "lock"] {
+            assert_eq!(
+                resolve_command_action("Screensaver", payload, false),
+                CommandAction::Native("Screensaver")
+            );
+        }
     }
 
     #[test]
@@ -1,10 +1,8 @@
 //! Command executor for Linux - feature-parity with Windows executor
 
-use log::{debug, error, info, warn};
-use std::os::unix::process::CommandExt;
+use log::{debug, info, warn};
 use std::process::Command;
 use std::sync::Arc;
-use tokio::sync::Semaphore;
 
 use super::custom::execute_custom_command;
 use super::launcher_linux::expand_launcher_shortcut;
@@ -12,11 +10,10 @@ use crate::AppState;
 use crate::audio::{self, MediaKey};
 use crate::mqtt::CommandReceiver;
 use crate::notification;
-use crate::power::sync_mqtt::{SyncMqttConfig, parse_broker_url, sync_mqtt_publish_sleep};
+use crate::power::sync_mqtt::{SyncMqttConfig, sync_mqtt_publish_sleep};
 use crate::power::{monitor_off, wake_display};
 use crate::steam::SteamGameDiscovery;
 
-const MAX_CONCURRENT_COMMANDS: usize = 5;
 /// How long to wait for Steam to come up before launching anyway.
 const STEAM_WAIT_TIMEOUT_SECS: u64 = 90;
 /// Grace period after Steam appears, for it to finish initializing.
@@ -25,8 +22,8 @@ const STEAM_INIT_DELAY_SECS: u64 = 12;
 /// Predefined shell commands for Linux
 fn get_predefined_command(name: &str) -> Option<&'static str> {
     match name {
-        "Screensaver" => Some("xdg-screensaver activate"),
-        "Wake" | "Sleep" | "Hibernate" | "MonitorOff" | "MonitorOn" | "CloseGame" => None, // Handled natively
+        "Screensaver" | "Wake" | "Sleep" | "Hibernate" | "MonitorOff" | "MonitorOn"
+        | "CloseGame" => None, // Handled natively
         "Shutdown" => Some("systemctl poweroff"),
         "Lock" => Some("loginctl lock-session"),
         "Restart" => Some("systemctl reboot"),
@@ -38,48 +35,18 @@ fn get_predefined_command(name: &str) -> Option<&'static str> {
 pub struct CommandExecutor {
     state: Arc<AppState>,
     command_rx: CommandReceiver,
-    semaphore: Arc<Semaphore>,
 }
 
 impl CommandExecutor {
     pub fn new(state: Arc<AppState>, command_rx: CommandReceiver) -> Self {
-        Self {
-            state,
-            command_rx,
-            semaphore: Arc::new(Semaphore::new(MAX_CONCURRENT_COMMANDS)),
-        }
+        Self { state, command_rx }
     }
 
-    pub async fn run(mut self) {
-        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
-
-        loop {
-            tokio::select! {
-                biased;
-                _ = shutdown_rx.recv() => {
-                    debug!("Command executor shutting down");
-                    break;
-                }
-                Some(cmd) = self.command_rx.recv() => {
-                    // Rate limit with semaphore
-                    let permit = match self.semaphore.clone().try_acquire_owned() {
-                        Ok(p) => p,
-                        Err(_) => {
-                            warn!("Command rate limited, dropping: {}", cmd.name);
-                            continue;
-                        }
-                    };
-
-                    let state_clone = self.state.clone();
-                    tokio::spawn(async move {
-                        let _permit = permit;
-                        if let Err(e) = Self::execute_command(&cmd.name, &cmd.payload, &state_clone).await {
-                            error!("Command error: {}", e);
-                        }
-                    });
-                }
-            }
-        }
+    pub async fn run(self) {
+        super::queue::run(self.state, self.command_rx, |cmd, state| async move {
+            Self::execute_command(&cmd.name, &cmd.payload, &state).await
+        })
+        .await;
     }
 
     async fn execute_command(
@@ -111,6 +78,24 @@ impl CommandExecutor {
             warn!("Ignoring '{}' - its feature is disabled", name);
             return Ok(());
         }
+        if !super::power_guard::allows(name, payload, state).await {
+            return Ok(());
+        }
+
+        // The game select sends a display name: run that game's launch
+        // command as a Launch, through the same launch gates.
+        let launch;
+        let (name, payload) = if name == "LaunchGame" {
+            let config = state.config.read().await;
+            let Some(command) = crate::commands::game_launch_command(&config, payload) else {
+                warn!("LaunchGame: no launchable game named {payload:?}");
+                return Ok(());
+            };
+            launch = command;
+            ("Launch", launch.as_str())
+        } else {
+            (name, payload)
+        };
 
         // ── Native commands (no shell needed) ──────────────────────────
         match name {
@@ -134,24 +119,14 @@ impl CommandExecutor {
                 return Ok(());
             }
             "Sleep" | "Hibernate" => {
+                let hibernate = name == "Hibernate";
+                if let Some(cause) = crate::power::suspend::unsupported(hibernate) {
+                    crate::power::suspend::report(state, name, Some(cause)).await;
+                    return Ok(());
+                }
                 // Pre-publish sleep state via sync TCP before the NIC goes down,
                 // matching the Windows behavior in power/events.rs.
-                let cfg = {
-                    let config = state.config.read().await;
-                    let (host, port, use_tls) = parse_broker_url(&config.mqtt.broker);
-                    SyncMqttConfig {
-                        host,
-                        port,
-                        use_tls,
-                        user: config.mqtt.user.clone(),
-                        pass: config.mqtt.pass.clone(),
-                        client_id: format!("{}-sleep", config.client_id()),
-                        sleep_topic: format!(
-                            "homeassistant/sensor/{}/sleep_state/state",
-                            config.device_name
-                        ),
-                    }
-                };
+                let cfg = SyncMqttConfig::for_device(&*state.config.read().await);
                 // Off the runtime: a broker connect timeout would otherwise stall
                 // the single-threaded runtime.
                 match tokio::task::spawn_blocking(move || sync_mqtt_publish_sleep(&cfg)).await {
@@ -159,24 +134,45 @@ impl CommandExecutor {
                     Ok(Err(e)) => warn!("Sync MQTT sleep pre-publish failed: {}", e),
                     Err(e) => warn!("Sync publish task join error: {}", e),
                 }
-                // Also publish via async client as fallback
-                state
-                    .mqtt
-                    .publish_sensor_retained("sleep_state", "sleeping")
-                    .await;
-                let cmd = if name == "Sleep" {
-                    "systemctl suspend"
-                } else {
+                // Also announce via the async client, waiting for the ack
+                state.mqtt.announce_power_down("sleeping").await;
+                let cmd = if hibernate {
                     "systemctl hibernate"
+                } else {
+                    "systemctl suspend"
                 };
-                // .status() reaps the process (no zombie) and normally returns
+                // .output() reaps the process (no zombie) and normally returns
                 // fast (systemd suspends asynchronously), but a polkit prompt or
                 // hung systemd could block, so run it off the single-threaded
                 // runtime.
-                let _ = tokio::task::spawn_blocking(move || {
-                    Command::new("bash").args(["-c", cmd]).status()
+                let error = match tokio::task::spawn_blocking(move || {
+                    Command::new("bash").args(["-c", cmd]).output()
                 })
-                .await;
+                .await
+                {
+                    Ok(Ok(output)) if output.status.success() => None,
+                    // "Operation inhibited by ..." or a polkit refusal
+                    Ok(Ok(output)) => Some(
+                        Some(String::from_utf8_lossy(&output.stderr).trim().to_string())
+                            .filter(|e| !e.is_empty())
+                            .unwrap_or_else(|| format!("{cmd} exited with {}", output.status)),
+                    ),
+                    Ok(Err(e)) => Some(format!("{cmd}: {e}")),
+                    Err(e) => Some(e.to_string()),
+                };
+                match error {
+                    None => crate::power::suspend::report(state, name, None).await,
+                    Some(error) => {
+                        // Refused: nothing will fire a wake event.
+                        state.mqtt.publish_availability(true).await;
+                        state
+                            .mqtt
+                            .publish_sensor_retained("sleep_state", "awake")
+                            .await;
+                        let cause = crate::power::suspend::failure_cause(error).await;
+                        crate::power::suspend::report(state, name, Some(&cause)).await;
+                    }
+                }
                 return Ok(());
             }
             "MonitorOff" => {
@@ -191,15 +187,20 @@ impl CommandExecutor {
                 close_running_games(state).await;
                 return Ok(());
             }
+            "StopGame" => {
+                super::stop_game::run(state).await;
+                return Ok(());
+            }
+            "Screensaver" => {
+                crate::power::screensaver::run(payload, state).await;
+                return Ok(());
+            }
             "notification" => {
                 if !payload.is_empty() {
-                    // notify-send/gdbus .status() block; keep them off the runtime.
-                    let p = payload.to_string();
-                    tokio::task::spawn_blocking(move || {
-                        if let Err(e) = notification::show_toast(&p) {
-                            warn!("Failed to show notification: {e}");
-                        }
-                    });
+                    // Detached: the busy check and the toast itself block.
+                    let state = Arc::clone(state);
+                    let payload = payload.to_string();
+                    tokio::spawn(async move { notification::deliver(&state, payload).await });
                 }
                 return Ok(());
             }
@@ -265,9 +266,136 @@ impl CommandExecutor {
                 }
                 return Ok(());
             }
+            "CleanupEntities" => {
+                let config = state.config.read().await.clone();
+                let cleaned = state.mqtt.cleanup_stale_devices(&config).await;
+                info!("Entity cleanup: removed {cleaned} previous device name(s)");
+                return Ok(());
+            }
+            "ConnectionDiagnostics" => {
+                let config = state.config.read().await.clone();
+                state.mqtt.connection_diagnostics(&config).await;
+                return Ok(());
+            }
+            "PowerPlan" => {
+                match crate::power::plan::set_active(payload).await {
+                    Ok(plan) => {
+                        info!("Power plan set to '{plan}'");
+                        // Reflect it now rather than on the sensor's next poll.
+                        state.mqtt.publish_sensor("power_plan", &plan).await;
+                    }
+                    Err(e) => warn!("PowerPlan: {e}"),
+                }
+                return Ok(());
+            }
+            "DisplayMode" => {
+                let modes = state.config.read().await.display_modes.clone();
+                match crate::power::display_mode::set_by_name(&modes, payload).await {
+                    Ok(name) => {
+                        info!("Display mode set to '{name}'");
+                        // Reflect it now rather than on the sensor's next poll.
+                        state.mqtt.publish_sensor("display_mode", &name).await;
+                    }
+                    Err(e) => warn!("DisplayMode: {e}"),
+                }
+                return Ok(());
+            }
+            "Rgb" => {
+                crate::openrgb::run(payload, state).await;
+                return Ok(());
+            }
+            "PerfMode" => {
+                crate::activity::perf_mode_command(payload);
+                return Ok(());
+            }
+            "FocusMode" => {
+                let minutes = state.config.read().await.focus.minutes;
+                crate::focus::command(payload, minutes);
+                return Ok(());
+            }
+            "ReportActiveWindow" | "ReportGames" => {
+                if let Some(reported) = crate::reporting::Reported::from_command(name) {
+                    crate::reporting::command(reported, payload);
+                }
+                return Ok(());
+            }
+            "Speedtest" => {
+                // Detached: a test takes tens of seconds.
+                let state = Arc::clone(state);
+                tokio::spawn(async move { crate::speedtest::run(&state).await });
+                return Ok(());
+            }
+            "DiskCleanup" => {
+                let state = Arc::clone(state);
+                tokio::spawn(async move { crate::cleanup::run(&state).await });
+                return Ok(());
+            }
+            "Print" => {
+                crate::sensors::print_payload(payload, state).await;
+                return Ok(());
+            }
+            "DockerStart" | "DockerStop" => {
+                // A stop waits out the container's grace period; don't hold up
+                // other commands meanwhile.
+                let state = Arc::clone(state);
+                let (name, start) = (payload.to_string(), name == "DockerStart");
+                tokio::spawn(
+                    async move { crate::sensors::docker_command(&name, start, &state).await },
+                );
+                return Ok(());
+            }
+            "WakeOnLan" => {
+                crate::wol::run(payload, state).await;
+                return Ok(());
+            }
+            "ClearNotificationHistory" => {
+                notification::clear_history(state).await;
+                return Ok(());
+            }
+            "BackupConfig" => {
+                crate::backup::backup(state).await;
+                return Ok(());
+            }
+            "RestoreConfig" => {
+                crate::backup::restore(payload, state).await;
+                return Ok(());
+            }
+            "FileDownload" => {
+                crate::transfer::download(payload, state).await;
+                return Ok(());
+            }
+            "FileChunk" => {
+                crate::transfer::chunk(payload, state).await;
+                return Ok(());
+            }
+            "SetWallpaper" => {
+                crate::transfer::set_wallpaper(payload, state).await;
+                return Ok(());
+            }
+            "Theme" => {
+                crate::sensors::set_theme(payload).await;
+                return Ok(());
+            }
+            "InstallUpdate" => {
+                // Detached: the download can take a while, and a successful
+                // install restarts the process from inside the task.
+                info!("Installing agent update");
+                let state = Arc::clone(state);
+                tokio::spawn(async move { crate::updater::install_latest(&state).await });
+                return Ok(());
+            }
+            "WindowsUpdateInstall" => {
+                warn!("WindowsUpdateInstall is only supported on Windows");
+                return Ok(());
+            }
             _ => {}
         }
 
+        // ── Plugin commands ────────────────────────────────────────────
+        if crate::plugins::dispatch(name, payload).await {
+            return Ok(());
+        }
+
         // ── Custom commands ────────────────────────────────────────────
         if execute_custom_command(state, name).await? {
             return Ok(());
@@ -313,6 +441,11 @@ impl CommandExecutor {
             return Ok(());
         }
 
+        // Announced (and acked) before poweroff tears the network down.
+        if name == "Shutdown" {
+            state.mqtt.announce_power_down("off").await;
+        }
+
         // ── Shell commands (predefined → launcher → raw → not found) ─────────────
         let cmd_str = match get_predefined_command(name) {
             Some(cmd) => cmd.to_string(),
@@ -348,39 +481,15 @@ impl CommandExecutor {
 
         // Execute via bash in its own process group so a timeout can kill the
         // whole tree (equivalent to taskkill /T on Windows), not just bash.
-        let mut child = Command::new("bash")
+        let child = tokio::process::Command::new("bash")
             .args(["-c", &cmd_str])
             .process_group(0)
             .spawn()?;
-        let pid = child.id();
-
-        // Wait with timeout in background
-        tokio::spawn(async move {
-            match tokio::time::timeout(
-                std::time::Duration::from_mins(5),
-                tokio::task::spawn_blocking(move || child.wait()),
-            )
-            .await
-            {
-                Ok(Ok(Ok(status))) => {
-                    if !status.success() {
-                        warn!("Command exited with: {}", status);
-                    }
-                }
-                Ok(Ok(Err(e))) => error!("Command wait error: {}", e),
-                Ok(Err(e)) => error!("Task join error: {}", e),
-                Err(_) => {
-                    warn!(
-                        "Command timed out after 5 minutes, killing process group (PID {})",
-                        pid
-                    );
-                    // Negative PID targets the whole process group.
-                    let _ = Command::new("kill")
-                        .args(["-KILL", &format!("-{pid}")])
-                        .status();
-                }
-            }
-        });
+        tokio::spawn(super::shell::supervise(
+            Arc::clone(state),
+            name.to_string(),
+            child,
+        ));
 
         Ok(())
     }
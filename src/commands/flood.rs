@@ -0,0 +1,183 @@
+//! Per-command rate limits with a circuit breaker, ahead of the queue.
+//!
+//! A command name arriving more than its limit within a minute (a broken
+//! automation sending Wake every second, say) trips its breaker: it is
+//! ignored for the cooldown, then counted afresh. Open breakers are
+//! published as the `command_flood` diagnostic binary sensor.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::config::CommandQueueConfig;
+
+/// Sensor that is on while any command's breaker is open
+pub(super) const SENSOR: &str = "command_flood";
+
+/// Span the rate limits are counted over
+const WINDOW: Duration = Duration::from_mins(1);
+
+/// Limits that apply unless `rate_limits` sets one: a file sent with
+/// `FileChunk` is dozens of messages in a row.
+const BUILT_IN_LIMITS: &[(&str, u32)] = &[("FileChunk", 600)];
+
+/// What to do with an incoming command
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Verdict {
+    Allow,
+    /// Just went over its limit: dropped, and ignored from now on
+    Tripped,
+    /// Breaker open: dropped
+    Ignored,
+}
+
+#[derive(Default)]
+struct Window {
+    arrivals: VecDeque<Instant>,
+    open_until: Option<Instant>,
+}
+
+pub(super) struct FloodGuard {
+    default_limit: u32,
+    limits: HashMap<String, u32>,
+    cooldown: Duration,
+    commands: HashMap<String, Window>,
+    /// Breakers tripped since startup
+    tripped: u64,
+}
+
+impl FloodGuard {
+    pub(super) fn new(config: &CommandQueueConfig) -> Self {
+        Self {
+            default_limit: config.rate_limit_per_min,
+            limits: config.rate_limits.clone(),
+            cooldown: Duration::from_secs(config.flood_cooldown_secs.max(1)),
+            commands: HashMap::new(),
+            tripped: 0,
+        }
+    }
+
+    pub(super) fn check(&mut self, name: &str, now: Instant) -> Verdict {
+        let limit = self
+            .limits
+            .get(name)
+            .copied()
+            .or_else(|| {
+                BUILT_IN_LIMITS
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|&(_, limit)| limit)
+            })
+            .unwrap_or(self.default_limit);
+        if limit == 0 {
+            return Verdict::Allow;
+        }
+        let window = self.commands.entry(name.to_string()).or_default();
+        match window.open_until {
+            Some(until) if now < until => return Verdict::Ignored,
+            Some(_) => window.open_until = None,
+            None => {}
+        }
+        while window
+            .arrivals
+            .front()
+            .is_some_and(|&t| now.duration_since(t) >= WINDOW)
+        {
+            window.arrivals.pop_front();
+        }
+        if window.arrivals.len() >= limit as usize {
+            window.arrivals.clear();
+            window.open_until = Some(now + self.cooldown);
+            self.tripped += 1;
+            return Verdict::Tripped;
+        }
+        window.arrivals.push_back(now);
+        Verdict::Allow
+    }
+
+    /// Commands being ignored right now, sorted
+    pub(super) fn open(&self, now: Instant) -> Vec<&str> {
+        let mut open: Vec<&str> = self
+            .commands
+            .iter()
+            .filter(|(_, w)| w.open_until.is_some_and(|until| now < until))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        open.sort_unstable();
+        open
+    }
+
+    /// When the next open breaker closes, to republish the sensor then
+    pub(super) fn next_close(&self, now: Instant) -> Option<Instant> {
+        self.commands
+            .values()
+            .filter_map(|w| w.open_until)
+            .filter(|&until| now < until)
+            .min()
+    }
+
+    /// (`"on"`/`"off"`, attributes) for the sensor
+    pub(super) fn report(&self, now: Instant) -> (&'static str, serde_json::Value) {
+        let open = self.open(now);
+        let state = if open.is_empty() { "off" } else { "on" };
+        let attributes = serde_json::json!({
+            "commands": open,
+            "tripped": self.tripped,
+            "rate_limit_per_min": self.default_limit,
+            "cooldown_secs": self.cooldown.as_secs(),
+        });
+        (state, attributes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(limit: u32) -> FloodGuard {
+        FloodGuard::new(&CommandQueueConfig {
+            rate_limit_per_min: limit,
+            rate_limits: HashMap::from([("VolumeSet".to_string(), 0)]),
+            flood_cooldown_secs: 300,
+            ..CommandQueueConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_flood_trips_breaker_until_cooldown() {
+        let mut guard = guard(3);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        for s in 0..3 {
+            assert_eq!(guard.check("Wake", at(s)), Verdict::Allow);
+        }
+        assert_eq!(guard.check("Wake", at(3)), Verdict::Tripped);
+        assert_eq!(guard.check("Wake", at(4)), Verdict::Ignored);
+        // Other commands are counted on their own; 0 means unlimited.
+        assert_eq!(guard.check("Sleep", at(4)), Verdict::Allow);
+        for _ in 0..10 {
+            assert_eq!(guard.check("VolumeSet", at(4)), Verdict::Allow);
+        }
+        assert_eq!(guard.open(at(4)), ["Wake"]);
+        assert_eq!(guard.next_close(at(4)), Some(at(303)));
+        let (state, attrs) = guard.report(at(4));
+        assert_eq!(state, "on");
+        assert_eq!(attrs["tripped"], 1);
+
+        // Closed again after the cooldown, with a fresh count
+        assert_eq!(guard.check("Wake", at(303)), Verdict::Allow);
+        assert!(guard.open(at(303)).is_empty());
+        assert_eq!(guard.report(at(303)).0, "off");
+    }
+
+    #[test]
+    fn test_steady_rate_under_limit_never_trips() {
+        let mut guard = guard(3);
+        let start = Instant::now();
+        // One every 25s: never more than 3 within a minute
+        for i in 0..20 {
+            let now = start + Duration::from_secs(i * 25);
+            assert_eq!(guard.check("Wake", now), Verdict::Allow);
+        }
+    }
+}
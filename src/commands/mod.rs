@@ -2,6 +2,12 @@
 
 pub mod custom;
 pub mod dry_run;
+mod flood;
+mod power_guard;
+mod queue;
+mod sandbox;
+mod shell;
+pub(crate) mod stop_game;
 
 use crate::config::FeatureConfig;
 
@@ -21,8 +27,8 @@ pub(crate) fn command_feature_enabled(name: &str, f: &FeatureConfig) -> bool {
         "Lock" => f.cmd_lock,
         "Logoff" => f.cmd_logoff,
         "MonitorOff" | "MonitorOn" => f.cmd_monitor,
-        "Launch" => f.launch_game,
-        "CloseGame" => f.close_game,
+        "Launch" | "LaunchGame" => f.launch_game,
+        "CloseGame" | "StopGame" => f.close_game,
         "RefreshSteamGames" => f.steam_library,
         "Screensaver" | "Wake" => f.idle_tracking,
         "DiscordJoin" | "DiscordLeaveChannel" => f.discord,
@@ -30,6 +36,24 @@ pub(crate) fn command_feature_enabled(name: &str, f: &FeatureConfig) -> bool {
         // volume gates the volume_level sensor, not these commands.
         "MediaPlayPause" | "MediaNext" | "MediaPrevious" | "MediaStop" => f.media_controls,
         "VolumeMute" => f.media_controls,
+        "WindowsUpdateInstall" => f.cmd_windows_update_install,
+        "PowerPlan" => f.power_plan,
+        "DisplayMode" => f.display_mode,
+        "Rgb" => f.openrgb,
+        "PerfMode" => f.perf_mode,
+        "FocusMode" => f.focus_mode,
+        "ReportActiveWindow" => f.active_window,
+        "ReportGames" => f.running_game,
+        "Speedtest" => f.cmd_speedtest,
+        "DiskCleanup" => f.cmd_disk_cleanup,
+        "Print" => f.printers,
+        "DockerStart" | "DockerStop" => f.docker,
+        "InstallUpdate" => f.cmd_install_update,
+        "ClearNotificationHistory" => f.notifications,
+        "BackupConfig" | "RestoreConfig" => f.cmd_config_backup,
+        "FileDownload" | "FileChunk" => f.cmd_file_transfer,
+        "SetWallpaper" => f.cmd_wallpaper,
+        "Theme" => f.theme,
         _ => true,
     }
 }
@@ -50,7 +74,9 @@ pub(crate) fn is_native_command(name: &str) -> bool {
             | "MonitorOff"
             | "MonitorOn"
             | "Launch"
+            | "LaunchGame"
             | "CloseGame"
+            | "StopGame"
             | "RefreshSteamGames"
             | "Screensaver"
             | "Wake"
@@ -61,6 +87,30 @@ pub(crate) fn is_native_command(name: &str) -> bool {
             | "MediaPrevious"
             | "MediaStop"
             | "VolumeMute"
+            | "CleanupEntities"
+            | "ConnectionDiagnostics"
+            | "WindowsUpdateInstall"
+            | "PowerPlan"
+            | "DisplayMode"
+            | "Rgb"
+            | "PerfMode"
+            | "FocusMode"
+            | "ReportActiveWindow"
+            | "ReportGames"
+            | "Speedtest"
+            | "DiskCleanup"
+            | "Print"
+            | "DockerStart"
+            | "DockerStop"
+            | "InstallUpdate"
+            | "ClearNotificationHistory"
+            | "WakeOnLan"
+            | "BackupConfig"
+            | "RestoreConfig"
+            | "FileDownload"
+            | "FileChunk"
+            | "SetWallpaper"
+            | "Theme"
     )
 }
 
@@ -104,6 +154,29 @@ pub(crate) fn is_configured_launch(config: &crate::config::Config, payload: &str
         .any(|lc| lc == payload)
 }
 
+/// Exposed games with a launch command, as (display name, launch command)
+/// sorted by name: the options of the game launch select. When two games
+/// share a display name only the first is offered.
+pub(crate) fn launchable_games(config: &crate::config::Config) -> Vec<(String, String)> {
+    let mut games: Vec<(String, String)> = config
+        .games
+        .values()
+        .filter(|g| g.is_exposed())
+        .filter_map(|g| Some((g.display_name(), g.launch_command()?)))
+        .collect();
+    games.sort();
+    games.dedup_by(|a, b| a.0 == b.0);
+    games
+}
+
+/// Launch command of the game the select offers as `name`.
+pub(crate) fn game_launch_command(config: &crate::config::Config, name: &str) -> Option<String> {
+    launchable_games(config)
+        .into_iter()
+        .find(|(display_name, _)| display_name == name)
+        .map(|(_, launch)| launch)
+}
+
 /// Global launch/close authorization gate. Returns true if this payload should be
 /// BLOCKED because it targets something outside the configured games list and the
 /// corresponding global permission is off:
@@ -183,6 +256,39 @@ mod tests {
         assert!(global_scheme_blocked(&cfg, "kill:notepad"));
     }
 
+    #[test]
+    fn test_launchable_games() {
+        let games = serde_json::from_value(serde_json::json!({
+            "cs2": { "game_id": "counter_strike_2", "app_id": 730 },
+            "Fortnite": {
+                "game_id": "fortnite",
+                "launch_command": "epic:Fortnite"
+            },
+            "hidden": { "game_id": "hidden", "app_id": 1, "exposed": false },
+            "notepad": "notepad",
+        }))
+        .unwrap();
+        let cfg = crate::config::Config {
+            games,
+            ..Default::default()
+        };
+        assert_eq!(
+            super::launchable_games(&cfg),
+            vec![
+                ("Counter Strike 2".to_string(), "steam:730".to_string()),
+                ("Fortnite".to_string(), "epic:Fortnite".to_string()),
+            ]
+        );
+        assert_eq!(
+            super::game_launch_command(&cfg, "Fortnite").as_deref(),
+            Some("epic:Fortnite")
+        );
+        // Hidden, not launchable or unknown: nothing to run.
+        assert_eq!(super::game_launch_command(&cfg, "Hidden"), None);
+        assert_eq!(super::game_launch_command(&cfg, "Notepad"), None);
+        assert_eq!(super::game_launch_command(&cfg, "steam:730"), None);
+    }
+
     #[test]
     fn test_unicode_scheme_cannot_evade_gates() {
         // U+212A KELVIN SIGN lowercases to ASCII 'k' under Unicode to_lowercase
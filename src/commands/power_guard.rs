@@ -0,0 +1,192 @@
+//! Shutdown guard: keep a schedule from powering off a PC that's in use
+//!
+//! With `shutdown_guard.enabled`, Shutdown, Restart, Sleep and Hibernate are
+//! refused while a game runs, Steam is updating, or CPU/network is above its
+//! threshold. The refusal is published as a command result and shown as a
+//! toast. A payload with `force:true` (or `{"force": true}`) goes through.
+
+use log::{info, warn};
+
+use crate::AppState;
+use crate::config::ShutdownGuardConfig;
+use crate::i18n::tr_args;
+
+/// Commands the guard applies to
+pub(super) fn guards(name: &str) -> bool {
+    matches!(name, "Shutdown" | "Restart" | "Sleep" | "Hibernate")
+}
+
+/// What the guard looks at, from the sensors' last readings
+#[derive(Debug, Default)]
+struct Busy {
+    game: bool,
+    steam_updating: bool,
+    cpu_percent: Option<f64>,
+    network_mbps: Option<f64>,
+}
+
+impl Busy {
+    fn read(state: &AppState) -> Self {
+        let network_mbps = state
+            .mqtt
+            .sensor_attributes("network_throughput")
+            .and_then(|attrs| {
+                let rx = attrs["rx_bytes_per_sec"].as_f64()?;
+                let tx = attrs["tx_bytes_per_sec"].as_f64()?;
+                Some((rx + tx) * 8.0 / 1_000_000.0)
+            });
+        Self {
+            game: crate::activity::game_running(),
+            steam_updating: state.mqtt.sensor_value("steam_updating").as_deref() == Some("on"),
+            cpu_percent: state
+                .mqtt
+                .sensor_value("cpu_usage")
+                .and_then(|v| v.parse().ok()),
+            network_mbps,
+        }
+    }
+
+    /// Why the PC counts as in use, if it does
+    fn reason(&self, guard: &ShutdownGuardConfig) -> Option<String> {
+        if self.game {
+            return Some("a game is running".to_string());
+        }
+        if self.steam_updating {
+            return Some("Steam is updating".to_string());
+        }
+        if guard.cpu_percent > 0
+            && let Some(cpu) = self
+                .cpu_percent
+                .filter(|&c| c > f64::from(guard.cpu_percent))
+        {
+            return Some(format!(
+                "CPU is at {cpu:.0}% (limit {}%)",
+                guard.cpu_percent
+            ));
+        }
+        if guard.network_mbps > 0
+            && let Some(mbps) = self
+                .network_mbps
+                .filter(|&m| m > f64::from(guard.network_mbps))
+        {
+            return Some(format!(
+                "network is at {mbps:.1} Mbit/s (limit {})",
+                guard.network_mbps
+            ));
+        }
+        None
+    }
+}
+
+/// `force:true` anywhere in the payload, or a JSON object with `"force": true`
+fn is_forced(payload: &str) -> bool {
+    if let Ok(serde_json::Value::Object(map)) = serde_json::from_str(payload) {
+        return map.get("force").and_then(serde_json::Value::as_bool) == Some(true);
+    }
+    let compact: String = payload
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '"' && *c != '\'')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    compact.contains("force:true")
+}
+
+/// Whether power command `name` may run now. A refusal is reported (command
+/// result and toast) before returning false.
+pub(super) async fn allows(name: &str, payload: &str, state: &AppState) -> bool {
+    let guard = state.config.read().await.shutdown_guard.clone();
+    if !guard.enabled || !guards(name) {
+        return true;
+    }
+    let Some(reason) = Busy::read(state).reason(&guard) else {
+        return true;
+    };
+    if is_forced(payload) {
+        info!("{name}: forced although {reason}");
+        return true;
+    }
+    warn!("{name} refused: {reason} (send force:true to override)");
+    state
+        .mqtt
+        .publish_command_result(&serde_json::json!({
+            "command": name,
+            "result": "refused",
+            "error": reason,
+        }))
+        .await;
+    let toast = serde_json::json!({
+        "title": tr_args("{name} blocked", &[("name", name)]),
+        "message": tr_args(
+            "Home Assistant asked for {name}, but {reason}.",
+            &[("name", name), ("reason", &reason)],
+        ),
+    })
+    .to_string();
+    tokio::spawn(async move {
+        match tokio::task::spawn_blocking(move || crate::notification::show_toast(&toast, "")).await
+        {
+            Ok(Err(e)) => warn!("Shutdown guard toast failed: {e}"),
+            Err(e) => warn!("Shutdown guard toast task failed: {e}"),
+            Ok(Ok(())) => {}
+        }
+    });
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_busy_reasons() {
+        let guard = ShutdownGuardConfig {
+            enabled: true,
+            ..ShutdownGuardConfig::default()
+        };
+        assert_eq!(Busy::default().reason(&guard), None);
+        let quiet = Busy {
+            cpu_percent: Some(12.0),
+            network_mbps: Some(0.4),
+            ..Busy::default()
+        };
+        assert_eq!(quiet.reason(&guard), None);
+
+        let game = Busy {
+            game: true,
+            ..Busy::default()
+        };
+        assert_eq!(game.reason(&guard).as_deref(), Some("a game is running"));
+        let busy_cpu = Busy {
+            cpu_percent: Some(93.4),
+            ..Busy::default()
+        };
+        assert_eq!(
+            busy_cpu.reason(&guard).as_deref(),
+            Some("CPU is at 93% (limit 80%)")
+        );
+        let download = Busy {
+            network_mbps: Some(120.0),
+            ..Busy::default()
+        };
+        assert!(download.reason(&guard).unwrap().starts_with("network"));
+        // 0 turns a threshold off
+        let no_limits = ShutdownGuardConfig {
+            cpu_percent: 0,
+            network_mbps: 0,
+            ..guard
+        };
+        assert_eq!(busy_cpu.reason(&no_limits), None);
+        assert_eq!(download.reason(&no_limits), None);
+    }
+
+    #[test]
+    fn test_is_forced() {
+        assert!(is_forced("force:true"));
+        assert!(is_forced("Force: TRUE"));
+        assert!(is_forced(r#"{"force": true}"#));
+        assert!(!is_forced(r#"{"force": false}"#));
+        assert!(!is_forced(""));
+        assert!(!is_forced("force:false"));
+        assert!(guards("Sleep") && !guards("Lock"));
+    }
+}
@@ -0,0 +1,277 @@
+//! Command dispatch shared by both executors: at most `max_concurrent`
+//! commands run at once, later ones wait their turn in a bounded FIFO
+//! instead of being dropped. Queue depth is published as the
+//! `command_queue` diagnostic sensor. A command flooding in is cut off
+//! before it gets that far (see `super::flood`).
+
+use log::{debug, error, warn};
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, watch};
+
+use super::flood::{self, FloodGuard, Verdict};
+use crate::AppState;
+use crate::config::CommandQueueConfig;
+use crate::mqtt::{Command, CommandReceiver};
+
+/// Sensor carrying the number of commands waiting for a slot
+const SENSOR: &str = "command_queue";
+
+/// What became of an incoming command
+enum Admission {
+    /// A slot was free: run now
+    Run(OwnedSemaphorePermit),
+    /// Queued behind running commands
+    Wait,
+    /// Queue full: dropped
+    Full,
+}
+
+/// Concurrency limit and bounded wait queue. The semaphore is fair, so
+/// queued commands get slots in arrival order.
+struct CommandQueue {
+    slots: Arc<Semaphore>,
+    max_concurrent: usize,
+    queue_size: usize,
+    timeout: Duration,
+    /// Commands waiting for a slot; watched so the depth is published
+    /// whenever it changes
+    waiting: watch::Sender<usize>,
+    running: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+impl CommandQueue {
+    fn new(config: &CommandQueueConfig) -> Self {
+        let max_concurrent = config.max_concurrent.max(1);
+        Self {
+            slots: Arc::new(Semaphore::new(max_concurrent)),
+            max_concurrent,
+            queue_size: config.queue_size,
+            timeout: Duration::from_secs(config.queue_timeout_secs.max(1)),
+            waiting: watch::channel(0).0,
+            running: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    fn admit(&self) -> Admission {
+        // A free permit is never handed past a queued command: released
+        // permits go straight to waiters.
+        if let Ok(permit) = Arc::clone(&self.slots).try_acquire_owned() {
+            return Admission::Run(permit);
+        }
+        let mut queued = false;
+        self.waiting.send_if_modified(|n| {
+            queued = *n < self.queue_size;
+            if queued {
+                *n += 1;
+            }
+            queued
+        });
+        if queued {
+            Admission::Wait
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            Admission::Full
+        }
+    }
+
+    /// Wait in the queue for a slot; None once the command went stale.
+    async fn wait(&self) -> Option<OwnedSemaphorePermit> {
+        let permit = tokio::time::timeout(self.timeout, Arc::clone(&self.slots).acquire_owned())
+            .await
+            .ok()
+            .and_then(Result::ok);
+        self.waiting.send_modify(|n| *n = n.saturating_sub(1));
+        if permit.is_none() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        permit
+    }
+
+    fn attributes(&self) -> serde_json::Value {
+        serde_json::json!({
+            "running": self.running.load(Ordering::Relaxed),
+            "max_concurrent": self.max_concurrent,
+            "queue_size": self.queue_size,
+            "dropped": self.dropped.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// Receive commands until shutdown, running each through `execute` under
+/// the configured concurrency and queueing policy.
+pub(super) async fn run<F, Fut>(state: Arc<AppState>, mut command_rx: CommandReceiver, execute: F)
+where
+    F: Fn(Command, Arc<AppState>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let (queue, mut flood) = {
+        let config = state.config.read().await;
+        (
+            Arc::new(CommandQueue::new(&config.command_queue)),
+            FloodGuard::new(&config.command_queue),
+        )
+    };
+    let execute = Arc::new(execute);
+    let mut depth_rx = queue.waiting.subscribe();
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut reconnect_rx = state.mqtt.subscribe_reconnect();
+
+    publish(&state, &queue).await;
+    publish_flood(&state, &flood).await;
+
+    loop {
+        let breaker_closes = flood.next_close(Instant::now());
+        tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => {
+                debug!("Command executor shutting down");
+                break;
+            }
+            Ok(()) = reconnect_rx.recv() => {
+                publish(&state, &queue).await;
+                publish_flood(&state, &flood).await;
+            }
+            Ok(()) = depth_rx.changed() => {
+                depth_rx.mark_unchanged();
+                publish(&state, &queue).await;
+            }
+            () = sleep_until(breaker_closes), if breaker_closes.is_some() => {
+                publish_flood(&state, &flood).await;
+            }
+            Some(cmd) = command_rx.recv() => {
+                match flood.check(&cmd.name, Instant::now()) {
+                    Verdict::Allow => {}
+                    Verdict::Tripped => {
+                        warn!(
+                            "Command {} is flooding in, ignoring it for {}s",
+                            cmd.name,
+                            state.config.read().await.command_queue.flood_cooldown_secs
+                        );
+                        publish_flood(&state, &flood).await;
+                        continue;
+                    }
+                    Verdict::Ignored => {
+                        debug!("Command {} ignored while its flood breaker is open", cmd.name);
+                        continue;
+                    }
+                }
+                let permit = match queue.admit() {
+                    Admission::Run(permit) => Some(permit),
+                    Admission::Wait => {
+                        debug!("Command queued behind {} running: {}", queue.max_concurrent, cmd.name);
+                        None
+                    }
+                    Admission::Full => {
+                        warn!("Command queue full ({}), dropping: {}", queue.queue_size, cmd.name);
+                        publish(&state, &queue).await;
+                        continue;
+                    }
+                };
+
+                let queue = Arc::clone(&queue);
+                let execute = Arc::clone(&execute);
+                let state = Arc::clone(&state);
+                tokio::spawn(async move {
+                    let Some(_permit) = (match permit {
+                        Some(permit) => Some(permit),
+                        None => queue.wait().await,
+                    }) else {
+                        warn!(
+                            "Command waited over {}s for a slot, dropping: {}",
+                            queue.timeout.as_secs(),
+                            cmd.name
+                        );
+                        return;
+                    };
+                    queue.running.fetch_add(1, Ordering::Relaxed);
+                    if let Err(e) = execute(cmd, state).await {
+                        error!("Command error: {}", e);
+                    }
+                    queue.running.fetch_sub(1, Ordering::Relaxed);
+                });
+            }
+        }
+    }
+}
+
+async fn publish_flood(state: &AppState, flood: &FloodGuard) {
+    let (on, attributes) = flood.report(Instant::now());
+    state.mqtt.publish_sensor(flood::SENSOR, on).await;
+    state
+        .mqtt
+        .publish_sensor_attributes(flood::SENSOR, &attributes)
+        .await;
+}
+
+/// Sleep until `at`, or forever; only polled while a breaker is open
+async fn sleep_until(at: Option<Instant>) {
+    match at {
+        Some(at) => tokio::time::sleep_until(at.into()).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn publish(state: &AppState, queue: &CommandQueue) {
+    let depth = *queue.waiting.borrow();
+    state.mqtt.publish_sensor(SENSOR, &depth.to_string()).await;
+    state
+        .mqtt
+        .publish_sensor_attributes(SENSOR, &queue.attributes())
+        .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_admission_policy() {
+        let queue = Arc::new(CommandQueue::new(&CommandQueueConfig {
+            max_concurrent: 1,
+            queue_size: 1,
+            queue_timeout_secs: 1,
+            ..CommandQueueConfig::default()
+        }));
+
+        let Admission::Run(first) = queue.admit() else {
+            panic!("free slot runs immediately");
+        };
+        assert!(matches!(queue.admit(), Admission::Wait));
+        assert_eq!(*queue.waiting.borrow(), 1);
+        assert!(matches!(queue.admit(), Admission::Full));
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 1);
+
+        // The queued command gets the slot once the running one finishes.
+        let waiter = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move { queue.wait().await.is_some() }
+        });
+        tokio::task::yield_now().await;
+        drop(first);
+        assert!(waiter.await.unwrap());
+        assert_eq!(*queue.waiting.borrow(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stale_command_dropped() {
+        let mut queue = CommandQueue::new(&CommandQueueConfig {
+            max_concurrent: 1,
+            queue_size: 4,
+            queue_timeout_secs: 5,
+            ..CommandQueueConfig::default()
+        });
+        queue.timeout = Duration::from_millis(10);
+        let Admission::Run(_running) = queue.admit() else {
+            panic!("free slot runs immediately");
+        };
+        assert!(matches!(queue.admit(), Admission::Wait));
+        assert!(queue.wait().await.is_none());
+        assert_eq!(*queue.waiting.borrow(), 0);
+        assert_eq!(queue.dropped.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,236 @@
+//! Resource limits for sandboxed custom commands
+//!
+//! Windows puts the command in a Job Object: a hard CPU rate cap, a memory cap
+//! across everything in the job, and closing the job kills what is left of
+//! it. The command starts suspended and only runs once it is in the job, so
+//! nothing it starts can get out. Linux runs it in a transient systemd scope
+//! (`systemd-run --scope`) with `CPUQuota` and `MemoryMax`. The time limit is
+//! enforced by the shell supervisor on both.
+//!
+//! These are resource limits only: the command keeps the agent's user and
+//! privileges (no restricted token on Windows).
+
+use std::time::Duration;
+use tokio::process::{Child, Command};
+
+use super::shell::SHELL_TIMEOUT;
+use crate::config::CommandSandbox;
+
+/// How long a sandboxed command may run
+pub(super) fn time_limit(limits: &CommandSandbox) -> Duration {
+    limits
+        .timeout_secs
+        .map_or(SHELL_TIMEOUT, Duration::from_secs)
+}
+
+/// `systemd-run` arguments up to the program. `cpu_percent` is of the whole
+/// machine, `CPUQuota` of one CPU (200% = two cores). A root agent has no
+/// user manager, so its scopes go to the system one.
+#[cfg(any(unix, test))]
+fn systemd_run_args(limits: &CommandSandbox, cpus: usize, user: bool) -> Vec<String> {
+    let mut args = vec!["--scope".to_string(), "--quiet".to_string()];
+    if user {
+        args.push("--user".to_string());
+    }
+    if let Some(percent) = limits.cpu_percent {
+        args.push(format!(
+            "--property=CPUQuota={}%",
+            usize::from(percent) * cpus
+        ));
+    }
+    if let Some(mb) = limits.memory_mb {
+        args.push(format!("--property=MemoryMax={mb}M"));
+    }
+    args.push("--".to_string());
+    args
+}
+
+/// `program args` wrapped in a systemd scope, as its own process group so a
+/// timeout can kill the lot
+#[cfg(unix)]
+pub(super) fn command(program: &str, args: &[String], limits: &CommandSandbox) -> Command {
+    let cpus = std::thread::available_parallelism().map_or(1, std::num::NonZero::get);
+    // SAFETY: geteuid has no preconditions and can't fail.
+    let user = unsafe { libc::geteuid() } != 0;
+    let mut command = Command::new("systemd-run");
+    command
+        .args(systemd_run_args(limits, cpus, user))
+        .arg(program)
+        .args(args)
+        .process_group(0);
+    command
+}
+
+/// Nothing to hold on Linux: the scope lives as long as the command.
+#[cfg(unix)]
+pub(super) struct Confinement;
+
+impl Confinement {
+    /// Done with the command; on Windows this kills whatever it left running.
+    pub(super) fn release(self) {}
+}
+
+#[cfg(unix)]
+pub(super) fn confine(_child: &Child, _limits: &CommandSandbox) -> std::io::Result<Confinement> {
+    Ok(Confinement)
+}
+
+/// Started suspended: `confine` resumes it once it's in the job.
+#[cfg(windows)]
+pub(super) fn command(program: &str, args: &[String], _limits: &CommandSandbox) -> Command {
+    use windows::Win32::System::Threading::CREATE_SUSPENDED;
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .creation_flags(super::executor::CREATE_NO_WINDOW | CREATE_SUSPENDED.0);
+    command
+}
+
+/// The command's Job Object; dropping it kills whatever still runs in it.
+#[cfg(windows)]
+pub(super) struct Confinement(windows::Win32::Foundation::HANDLE);
+
+// SAFETY: a job handle is a kernel object handle, usable from any thread.
+#[cfg(windows)]
+unsafe impl Send for Confinement {}
+
+#[cfg(windows)]
+impl Drop for Confinement {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from CreateJobObjectW and is closed once.
+        unsafe {
+            let _ = windows::Win32::Foundation::CloseHandle(self.0);
+        }
+    }
+}
+
+/// Move the suspended `child` into a Job Object with `limits`, then let it
+/// run. Everything it starts is in the job too.
+#[cfg(windows)]
+pub(super) fn confine(child: &Child, limits: &CommandSandbox) -> std::io::Result<Confinement> {
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JOB_OBJECT_CPU_RATE_CONTROL_ENABLE,
+        JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP, JOB_OBJECT_LIMIT_JOB_MEMORY,
+        JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, JOBOBJECT_CPU_RATE_CONTROL_INFORMATION,
+        JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JobObjectCpuRateControlInformation,
+        JobObjectExtendedLimitInformation, SetInformationJobObject,
+    };
+    use windows::core::PCWSTR;
+
+    let process = child
+        .raw_handle()
+        .ok_or_else(|| std::io::Error::other("command already exited"))?;
+    // SAFETY: plain Win32 calls on a job we own and a live process handle;
+    // the info structs outlive the calls that read them.
+    unsafe {
+        let job = Confinement(CreateJobObjectW(None, PCWSTR::null())?);
+
+        let mut extended = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        extended.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        if let Some(mb) = limits.memory_mb {
+            extended.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_JOB_MEMORY;
+            extended.JobMemoryLimit =
+                usize::try_from(mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX);
+        }
+        SetInformationJobObject(
+            job.0,
+            JobObjectExtendedLimitInformation,
+            std::ptr::from_ref(&extended).cast(),
+            size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        )?;
+
+        if let Some(percent) = limits.cpu_percent {
+            let mut rate = JOBOBJECT_CPU_RATE_CONTROL_INFORMATION {
+                ControlFlags: JOB_OBJECT_CPU_RATE_CONTROL_ENABLE
+                    | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP,
+                ..Default::default()
+            };
+            // In hundredths of a percent of the whole machine
+            rate.Anonymous.CpuRate = u32::from(percent) * 100;
+            SetInformationJobObject(
+                job.0,
+                JobObjectCpuRateControlInformation,
+                std::ptr::from_ref(&rate).cast(),
+                size_of::<JOBOBJECT_CPU_RATE_CONTROL_INFORMATION>() as u32,
+            )?;
+        }
+
+        AssignProcessToJobObject(job.0, HANDLE(process))?;
+        resume(child.id().unwrap_or_default())?;
+        Ok(job)
+    }
+}
+
+/// Resume the threads of process `pid`, started with `CREATE_SUSPENDED`
+/// (std's `Child` doesn't keep the main thread's handle).
+#[cfg(windows)]
+fn resume(pid: u32) -> std::io::Result<()> {
+    use windows::Win32::Foundation::CloseHandle;
+    use windows::Win32::System::Diagnostics::ToolHelp::{
+        CreateToolhelp32Snapshot, TH32CS_SNAPTHREAD, THREADENTRY32, Thread32First, Thread32Next,
+    };
+    use windows::Win32::System::Threading::{OpenThread, ResumeThread, THREAD_SUSPEND_RESUME};
+
+    let mut resumed = false;
+    // SAFETY: thread enumeration and resume on handles opened and closed
+    // here; `entry` outlives the calls that fill it.
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0)?;
+        let mut entry = THREADENTRY32 {
+            dwSize: size_of::<THREADENTRY32>() as u32,
+            ..Default::default()
+        };
+        let mut more = Thread32First(snapshot, &raw mut entry).is_ok();
+        while more {
+            if entry.th32OwnerProcessID == pid
+                && let Ok(thread) = OpenThread(THREAD_SUSPEND_RESUME, false, entry.th32ThreadID)
+            {
+                resumed |= ResumeThread(thread) != u32::MAX;
+                let _ = CloseHandle(thread);
+            }
+            more = Thread32Next(snapshot, &raw mut entry).is_ok();
+        }
+        let _ = CloseHandle(snapshot);
+    }
+    if resumed {
+        Ok(())
+    } else {
+        Err(std::io::Error::other("couldn't resume the command"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_systemd_run_args() {
+        let limits = CommandSandbox {
+            cpu_percent: Some(25),
+            memory_mb: Some(512),
+            timeout_secs: Some(60),
+        };
+        assert_eq!(
+            systemd_run_args(&limits, 8, true),
+            [
+                "--scope",
+                "--quiet",
+                "--user",
+                "--property=CPUQuota=200%",
+                "--property=MemoryMax=512M",
+                "--",
+            ]
+        );
+        assert_eq!(time_limit(&limits), Duration::from_mins(1));
+
+        // No limits: just the scope, and the default time limit
+        let none = CommandSandbox::default();
+        assert_eq!(
+            systemd_run_args(&none, 8, false),
+            ["--scope", "--quiet", "--"]
+        );
+        assert_eq!(time_limit(&none), SHELL_TIMEOUT);
+    }
+}
@@ -0,0 +1,348 @@
+//! Supervision of the shell commands the executors spawn. A command still
+//! running after `SHELL_TIMEOUT` has its whole process tree killed, and how
+//! each one ended is published to the command result topic.
+//!
+//! On Windows, [`shell_runner`] also picks how a command runs: PowerShell
+//! takes ~200ms+ just to start and can be blocked by policy, so it is kept
+//! for what actually needs it.
+
+use log::{error, warn};
+use std::process::ExitStatus;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Child;
+
+use crate::AppState;
+
+/// How long a shell command may run before it is killed
+pub(super) const SHELL_TIMEOUT: Duration = Duration::from_mins(5);
+
+/// How a supervised command ended
+#[derive(Debug)]
+enum Outcome {
+    Exited(ExitStatus),
+    /// Waiting on the child failed; it may or may not have run to completion
+    WaitFailed(String),
+    /// Timed out and killed along with everything it started
+    Killed,
+    /// Handed to `ShellExecuteW`, which doesn't wait for what it starts
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Opened,
+    #[cfg_attr(not(windows), allow(dead_code))]
+    OpenFailed(String),
+}
+
+/// How the Windows executor runs a resolved command
+#[cfg(any(windows, test))]
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum ShellRunner {
+    /// `Start-Process` of a URL, document or program, which is all
+    /// `ShellExecuteW` needs
+    Open {
+        target: String,
+        args: Option<String>,
+    },
+    /// A plain command line, run by `cmd /D /S /C`
+    Cmd(String),
+    /// Cmdlets, pipelines, variables, script blocks
+    PowerShell(String),
+}
+
+/// PowerShell aliases that cmd would run as something else (or not at all)
+#[cfg(any(windows, test))]
+const POWERSHELL_ALIASES: &[&str] = &[
+    "cat", "cp", "curl", "gci", "gps", "iex", "irm", "iwr", "kill", "ls", "man", "mv", "ps", "rm",
+    "sleep", "spps", "start", "wget",
+];
+
+/// Pick the cheapest runner that behaves like PowerShell would for `cmd`.
+#[cfg(any(windows, test))]
+pub(super) fn shell_runner(cmd: &str) -> ShellRunner {
+    let cmd = cmd.trim();
+    if let Some((target, args)) = parse_start_process(cmd) {
+        return ShellRunner::Open {
+            target: target.to_string(),
+            args: args.map(str::to_string),
+        };
+    }
+    if is_plain_command(cmd) {
+        ShellRunner::Cmd(cmd.to_string())
+    } else {
+        ShellRunner::PowerShell(cmd.to_string())
+    }
+}
+
+/// `Start-Process <target> [-ArgumentList '<args>']`, as the launcher emits
+/// it: target bare or double-quoted, args single-quoted. Anything fancier
+/// (other parameters, expressions) stays with PowerShell.
+#[cfg(any(windows, test))]
+fn parse_start_process(cmd: &str) -> Option<(&str, Option<&str>)> {
+    let prefix = cmd.get(..14)?;
+    if !prefix.eq_ignore_ascii_case("Start-Process ") {
+        return None;
+    }
+    let rest = cmd[14..].trim();
+    let (target, args) = match rest.split_once(" -ArgumentList ") {
+        Some((target, args)) => {
+            let args = args.trim().strip_prefix('\'')?.strip_suffix('\'')?;
+            if args.contains('\'') {
+                return None;
+            }
+            (target.trim(), Some(args))
+        }
+        None => (rest, None),
+    };
+    let target = match target.strip_prefix('"') {
+        Some(quoted) => quoted.strip_suffix('"')?,
+        None if target.contains(char::is_whitespace) => return None,
+        None => target,
+    };
+    let literal = !target.is_empty()
+        && !target.chars().any(|c| {
+            matches!(
+                c,
+                '"' | '\'' | '`' | '$' | '(' | ')' | '{' | '}' | ';' | '|'
+            )
+        });
+    literal.then_some((target, args))
+}
+
+/// A program with arguments and nothing PowerShell or cmd would interpret:
+/// no cmdlet or PowerShell alias first, no `%`, `$`, pipes, redirection,
+/// grouping or escapes anywhere.
+#[cfg(any(windows, test))]
+fn is_plain_command(cmd: &str) -> bool {
+    let Some(first) = cmd.split_whitespace().next() else {
+        return false;
+    };
+    let program = first.trim_matches('"');
+    if POWERSHELL_ALIASES.contains(&program.to_ascii_lowercase().as_str()) {
+        return false;
+    }
+    // Verb-Noun, e.g. Stop-Computer; my-tool.exe or C:\x-y\z are programs.
+    let cmdlet = program.split_once('-').is_some_and(|(verb, noun)| {
+        !verb.is_empty()
+            && !noun.is_empty()
+            && verb.chars().all(|c| c.is_ascii_alphabetic())
+            && noun.chars().all(|c| c.is_ascii_alphanumeric())
+    });
+    !cmdlet
+        && cmd.matches('"').count().is_multiple_of(2)
+        && cmd
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || " \\/:.-_=,+#\"".contains(c))
+}
+
+/// Wait for `child` (spawned for command `name`), killing its process tree
+/// on timeout, then publish the outcome.
+pub(super) async fn supervise(state: Arc<AppState>, name: String, child: Child) {
+    supervise_for(state, name, child, SHELL_TIMEOUT).await;
+}
+
+/// [`supervise`] with its own time limit, e.g. a sandboxed custom command's
+pub(super) async fn supervise_for(
+    state: Arc<AppState>,
+    name: String,
+    mut child: Child,
+    limit: Duration,
+) {
+    let outcome = match tokio::time::timeout(limit, child.wait()).await {
+        Ok(Ok(status)) => {
+            if !status.success() {
+                warn!("Command {} exited with: {}", name, status);
+            }
+            Outcome::Exited(status)
+        }
+        Ok(Err(e)) => {
+            error!("Command wait error: {}", e);
+            Outcome::WaitFailed(e.to_string())
+        }
+        Err(_) => {
+            warn!(
+                "Command {} timed out after {}s, killing its process tree (PID {:?})",
+                name,
+                limit.as_secs(),
+                child.id()
+            );
+            kill_tree(&mut child).await;
+            Outcome::Killed
+        }
+    };
+    state
+        .mqtt
+        .publish_command_result(&result_payload(&name, &outcome, limit))
+        .await;
+}
+
+/// Publish how a `ShellExecuteW` hand-off for command `name` went.
+#[cfg(windows)]
+pub(super) async fn report_open(state: &AppState, name: &str, result: Result<(), String>) {
+    let outcome = match result {
+        Ok(()) => Outcome::Opened,
+        Err(e) => {
+            warn!("Command {} failed to open: {}", name, e);
+            Outcome::OpenFailed(e)
+        }
+    };
+    state
+        .mqtt
+        .publish_command_result(&result_payload(name, &outcome, SHELL_TIMEOUT))
+        .await;
+}
+
+/// The whole group, not just bash: the executor spawns it as a process
+/// group leader. Killing the child itself afterwards also reaps it.
+#[cfg(unix)]
+async fn kill_tree(child: &mut Child) {
+    if let Some(pid) = child.id().and_then(|pid| i32::try_from(pid).ok()) {
+        // SAFETY: kill only sends a signal; a negative PID targets the group.
+        unsafe {
+            libc::kill(-pid, libc::SIGKILL);
+        }
+    }
+    let _ = child.kill().await;
+}
+
+#[cfg(windows)]
+async fn kill_tree(child: &mut Child) {
+    if let Some(pid) = child.id() {
+        let _ = tokio::process::Command::new("taskkill")
+            .args(["/F", "/T", "/PID", &pid.to_string()])
+            .creation_flags(super::executor::CREATE_NO_WINDOW)
+            .status()
+            .await;
+    }
+    let _ = child.kill().await;
+}
+
+/// `{"command", "result": "success"|"failed"|"killed", "exit_code", "error"}`
+fn result_payload(name: &str, outcome: &Outcome, limit: Duration) -> serde_json::Value {
+    let (result, exit_code, error) = match outcome {
+        Outcome::Exited(status) if status.success() => ("success", status.code(), None),
+        Outcome::Exited(status) => ("failed", status.code(), None),
+        Outcome::WaitFailed(e) => ("failed", None, Some(e.as_str())),
+        Outcome::Killed => ("killed", None, None),
+        Outcome::Opened => ("success", None, None),
+        Outcome::OpenFailed(e) => ("failed", None, Some(e.as_str())),
+    };
+    serde_json::json!({
+        "command": name,
+        "result": result,
+        "exit_code": exit_code,
+        "error": error,
+        "timeout_secs": limit.as_secs(),
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+
+    #[test]
+    fn test_result_payload() {
+        let ok = result_payload(
+            "Backup",
+            &Outcome::Exited(ExitStatus::from_raw(0)),
+            SHELL_TIMEOUT,
+        );
+        assert_eq!(ok["command"], "Backup");
+        assert_eq!(ok["result"], "success");
+        assert_eq!(ok["exit_code"], 0);
+
+        // Wait status 2 << 8 is exit code 2.
+        let failed = result_payload(
+            "Backup",
+            &Outcome::Exited(ExitStatus::from_raw(2 << 8)),
+            SHELL_TIMEOUT,
+        );
+        assert_eq!(failed["result"], "failed");
+        assert_eq!(failed["exit_code"], 2);
+
+        let killed = result_payload("Backup", &Outcome::Killed, SHELL_TIMEOUT);
+        assert_eq!(killed["result"], "killed");
+        assert!(killed["exit_code"].is_null());
+        assert_eq!(killed["timeout_secs"], 300);
+
+        let opened = result_payload("Launch", &Outcome::Opened, SHELL_TIMEOUT);
+        assert_eq!(opened["result"], "success");
+        assert!(opened["exit_code"].is_null());
+        let refused = result_payload(
+            "Launch",
+            &Outcome::OpenFailed("not found".into()),
+            SHELL_TIMEOUT,
+        );
+        assert_eq!(refused["result"], "failed");
+        assert_eq!(refused["error"], "not found");
+    }
+
+    #[test]
+    fn test_shell_runner_opens_launcher_output() {
+        let open = |target: &str, args: Option<&str>| ShellRunner::Open {
+            target: target.to_string(),
+            args: args.map(str::to_string),
+        };
+        assert_eq!(
+            shell_runner("Start-Process \"steam://rungameid/570\""),
+            open("steam://rungameid/570", None)
+        );
+        assert_eq!(
+            shell_runner("Start-Process notepad.exe"),
+            open("notepad.exe", None)
+        );
+        assert_eq!(
+            shell_runner(
+                "Start-Process \"C:\\Program Files\\App\\app.exe\" -ArgumentList '--fullscreen -x'"
+            ),
+            open("C:\\Program Files\\App\\app.exe", Some("--fullscreen -x"))
+        );
+        // Other parameters or expressions need the real cmdlet.
+        for cmd in [
+            "Start-Process notepad -Verb RunAs",
+            "Start-Process $env:APP",
+            "Start-Process (Get-Item x).FullName",
+        ] {
+            assert!(
+                matches!(shell_runner(cmd), ShellRunner::PowerShell(_)),
+                "{cmd}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_shell_runner_keeps_powershell_where_needed() {
+        for cmd in [
+            "shutdown /s /t 0",
+            "\"C:\\Tools\\my-tool.exe\" --sync",
+            "rundll32.exe user32.dll,LockWorkStation",
+        ] {
+            assert_eq!(shell_runner(cmd), ShellRunner::Cmd(cmd.to_string()));
+        }
+        for cmd in [
+            "Stop-Computer -Force",
+            "Get-Process steam | Stop-Process",
+            "ls C:\\",
+            "kill 1234",
+            "echo %PATH%",
+            "cmd /c a & b",
+            "app.exe > out.txt",
+            "app.exe $HOME",
+        ] {
+            assert!(
+                matches!(shell_runner(cmd), ShellRunner::PowerShell(_)),
+                "{cmd}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kill_tree_reaps_child() {
+        let mut child = tokio::process::Command::new("sh")
+            .args(["-c", "sleep 30 & sleep 30"])
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        kill_tree(&mut child).await;
+        assert!(child.try_wait().unwrap().is_some());
+    }
+}
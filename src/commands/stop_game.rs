@@ -0,0 +1,238 @@
+//! `StopGame`: end whatever game the game sensor reports
+//!
+//! The game sensor records the pids behind each game it reports ([`track`]),
+//! from the process watcher (and, for games matched by window or Store app,
+//! the window's process). StopGame asks each to close the way its window's
+//! close button would (WM_CLOSE on Windows, SIGTERM on Linux), gives it
+//! [`GRACE`] to save and exit, then terminates whatever is still running. The
+//! outcome goes to the results topic.
+//!
+//! Unlike `CloseGame`, which closes every process whose name matches a
+//! configured game, this only touches the games currently reported - including
+//! emulators and window-matched titles. On Linux, games matched by window
+//! title alone have no pid and can't be stopped.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::AppState;
+
+/// How long a game gets to close on its own before it's terminated
+const GRACE: Duration = Duration::from_secs(15);
+
+/// A process behind a reported game
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GameProcess {
+    pub pid: u32,
+    /// Display name of the game
+    pub game: String,
+}
+
+/// Processes of the games the sensor last reported
+static RUNNING: Mutex<Vec<GameProcess>> = Mutex::new(Vec::new());
+
+/// Record the processes behind the currently reported games.
+pub(crate) fn track(processes: Vec<GameProcess>) {
+    *RUNNING.lock().unwrap_or_else(|e| e.into_inner()) = processes;
+}
+
+/// The `StopGame` command.
+pub(crate) async fn run(state: &AppState) {
+    let Some((games, terminated)) = close(None).await else {
+        info!("StopGame: no running game detected");
+        state
+            .mqtt
+            .publish_command_result(&serde_json::json!({
+                "command": "StopGame",
+                "result": "none",
+            }))
+            .await;
+        return;
+    };
+    let result = if terminated.is_empty() {
+        "closed"
+    } else {
+        "terminated"
+    };
+    state
+        .mqtt
+        .publish_command_result(&serde_json::json!({
+            "command": "StopGame",
+            "result": result,
+            "games": games,
+        }))
+        .await;
+}
+
+/// Close the reported games, or only the one with display name `game`.
+/// Returns the games closed and the pids that had to be terminated; None if
+/// there was nothing to close.
+pub(crate) async fn close(game: Option<&str>) -> Option<(Vec<String>, Vec<u32>)> {
+    let processes = RUNNING.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let own_pid = std::process::id();
+    let processes: Vec<GameProcess> = processes
+        .into_iter()
+        .filter(|p| p.pid > 4 && p.pid != own_pid && game.is_none_or(|g| p.game == g))
+        .collect();
+    if processes.is_empty() {
+        return None;
+    }
+    let pids: Vec<u32> = processes.iter().map(|p| p.pid).collect();
+    let mut games: Vec<String> = processes.into_iter().map(|p| p.game).collect();
+    games.dedup();
+
+    info!("Closing {}", games.join(", "));
+    let terminated = tokio::task::spawn_blocking(move || stop(&pids, GRACE))
+        .await
+        .unwrap_or_default();
+    if !terminated.is_empty() {
+        warn!(
+            "{} game process(es) ignored the close request and were terminated",
+            terminated.len()
+        );
+    }
+    Some((games, terminated))
+}
+
+/// Ask `pids` to close, wait up to `grace`, then terminate the rest.
+/// Returns the pids that had to be terminated. Blocking.
+pub(crate) fn stop(pids: &[u32], grace: Duration) -> Vec<u32> {
+    for &pid in pids {
+        platform::request_close(pid);
+    }
+    let deadline = Instant::now() + grace;
+    loop {
+        let alive: Vec<u32> = pids
+            .iter()
+            .copied()
+            .filter(|&pid| platform::is_running(pid))
+            .collect();
+        if alive.is_empty() {
+            return alive;
+        }
+        if Instant::now() >= deadline {
+            for &pid in &alive {
+                platform::terminate(pid);
+            }
+            return alive;
+        }
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::Win32::Foundation::{BOOL, CloseHandle, HWND, LPARAM, WAIT_TIMEOUT, WPARAM};
+    use windows::Win32::System::Threading::{
+        OpenProcess, PROCESS_SYNCHRONIZE, PROCESS_TERMINATE, TerminateProcess, WaitForSingleObject,
+    };
+    use windows::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetWindowThreadProcessId, IsWindowVisible, PostMessageW, WM_CLOSE,
+    };
+
+    /// Post WM_CLOSE to the process's visible top-level windows, as
+    /// `Process.CloseMainWindow` does.
+    pub fn request_close(pid: u32) {
+        unsafe extern "system" fn close(hwnd: HWND, lparam: LPARAM) -> BOOL {
+            let mut owner = 0u32;
+            // SAFETY: plain queries on a window handle EnumWindows passed in;
+            // PostMessageW doesn't wait on the target.
+            unsafe {
+                GetWindowThreadProcessId(hwnd, Some(&raw mut owner));
+                if owner == lparam.0 as u32 && IsWindowVisible(hwnd).as_bool() {
+                    let _ = PostMessageW(hwnd, WM_CLOSE, WPARAM(0), LPARAM(0));
+                }
+            }
+            true.into()
+        }
+        // SAFETY: `close` only runs during this call and reads the pid by value.
+        unsafe {
+            let _ = EnumWindows(Some(close), LPARAM(pid as isize));
+        }
+    }
+
+    pub fn is_running(pid: u32) -> bool {
+        // SAFETY: the handle is only waited on (zero timeout) and closed here.
+        unsafe {
+            let Ok(handle) = OpenProcess(PROCESS_SYNCHRONIZE, false, pid) else {
+                return false;
+            };
+            let running = WaitForSingleObject(handle, 0) == WAIT_TIMEOUT;
+            let _ = CloseHandle(handle);
+            running
+        }
+    }
+
+    pub fn terminate(pid: u32) {
+        // SAFETY: the handle is only used to terminate and closed here.
+        unsafe {
+            if let Ok(handle) = OpenProcess(PROCESS_TERMINATE, false, pid) {
+                let _ = TerminateProcess(handle, 1);
+                let _ = CloseHandle(handle);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+mod platform {
+    fn signal(pid: u32, signal: libc::c_int) {
+        let Ok(pid) = libc::pid_t::try_from(pid) else {
+            return;
+        };
+        // SAFETY: kill has no memory effects; a stale pid just fails.
+        unsafe {
+            libc::kill(pid, signal);
+        }
+    }
+
+    pub fn request_close(pid: u32) {
+        signal(pid, libc::SIGTERM);
+    }
+
+    /// Running and not a zombie waiting to be reaped by its parent
+    pub fn is_running(pid: u32) -> bool {
+        std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .ok()
+            .and_then(|stat| {
+                // The state follows the parenthesized comm, which may contain spaces
+                let (_, rest) = stat.rsplit_once(')')?;
+                rest.split_whitespace().next().map(|state| state != "Z")
+            })
+            .unwrap_or(false)
+    }
+
+    pub fn terminate(pid: u32) {
+        signal(pid, libc::SIGKILL);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stop_closes_then_terminates() {
+        // sleep exits on SIGTERM: closed within the grace period
+        let mut polite = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let terminated = stop(&[polite.id()], Duration::from_secs(5));
+        assert!(terminated.is_empty());
+        polite.wait().unwrap();
+
+        // A shell ignoring SIGTERM is terminated once the grace period is up
+        let mut stubborn = std::process::Command::new("sh")
+            .args(["-c", "trap '' TERM; exec sleep 30"])
+            .spawn()
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        let pid = stubborn.id();
+        let terminated = stop(&[pid], Duration::from_millis(500));
+        assert_eq!(terminated, vec![pid]);
+        stubborn.wait().unwrap();
+    }
+}
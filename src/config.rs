@@ -5,16 +5,65 @@ use log::{error, info, warn};
 use notify::{Event, EventKind, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use crate::AppState;
 
+/// Previous versions of userConfig.json kept as `userConfig.json.bak1..N`
+const CONFIG_BACKUPS: usize = 5;
+
 /// User configuration structure (matches userConfig.json)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub device_name: String,
+    /// Area HA puts the device in when it first discovers it (e.g. "Office").
+    /// Read at startup.
+    #[serde(default)]
+    pub suggested_area: Option<String>,
+    /// Household group: PCs sharing one are listed under a common HA device
+    /// with an `any_game_running` sensor. Read at startup.
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Language of the entity names, the agent's own toasts and the setup
+    /// wizard: "en", "de", "es" or "fr" (see `crate::i18n`)
+    #[serde(default = "default_language")]
+    pub language: String,
     pub mqtt: MqttConfig,
+    /// Direct Home Assistant API transport, alongside MQTT or - with an empty
+    /// `mqtt.broker` - instead of it.
+    #[serde(default)]
+    pub ha_api: HaApiConfig,
+    /// Local HTTP status/control endpoint (off by default)
+    #[serde(default)]
+    pub http_api: HttpApiConfig,
+    /// OpenRGB SDK server the `openrgb` light talks to
+    #[serde(default)]
+    pub openrgb: OpenRgbConfig,
+    /// How many commands run at once, and how many wait for a slot
+    #[serde(default)]
+    pub command_queue: CommandQueueConfig,
+    /// When (not) to show toasts received on the notify topic
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    /// What besides keyboard/mouse input counts as the user being active
+    #[serde(default)]
+    pub idle: IdleConfig,
+    /// When Shutdown/Restart/Sleep/Hibernate are refused
+    #[serde(default)]
+    pub shutdown_guard: ShutdownGuardConfig,
+    /// Daily playtime limits
+    #[serde(default)]
+    pub game_limits: GameLimitsConfig,
+    /// What happens when the CPU or GPU overheats
+    #[serde(default)]
+    pub thermal_guard: ThermalGuardConfig,
+    /// Focus session length and the apps it blocks
+    #[serde(default)]
+    pub focus: FocusConfig,
+    /// How many active window changes `window_history` keeps
+    #[serde(default = "default_window_history_size")]
+    pub window_history_size: usize,
     #[serde(default)]
     pub intervals: IntervalConfig,
     #[serde(default)]
@@ -23,6 +72,10 @@ pub struct Config {
     /// Can be simple string (game_id) or object with app_id
     #[serde(default)]
     pub games: HashMap<String, GameConfig>,
+    /// Emulators map: process_pattern → how to read the loaded game from the
+    /// emulator's window caption
+    #[serde(default)]
+    pub emulators: HashMap<String, EmulatorConfig>,
 
     /// Allow custom sensor polling via PowerShell/WMI/registry
     #[serde(default)]
@@ -33,6 +86,10 @@ pub struct Config {
     /// Allow custom commands to run with elevated privileges
     #[serde(default)]
     pub custom_command_privileges_allowed: bool,
+    /// Run the Rhai scripts in the `scripts` folder next to this file: event
+    /// handlers and derived sensors that can queue commands
+    #[serde(default)]
+    pub scripts_enabled: bool,
     /// Allow raw MQTT payloads to be executed as shell commands
     /// When false (default), only predefined and custom commands are allowed
     #[serde(default)]
@@ -64,6 +121,16 @@ pub struct Config {
     #[serde(default = "default_update_channel")]
     pub update_channel: String,
 
+    /// Install updates as soon as a check finds one. When false, the update is
+    /// only announced (HA update entity) and waits for `InstallUpdate`.
+    #[serde(default = "default_true")]
+    pub auto_update: bool,
+
+    /// Show a toast on the next start after a crash (the report is always
+    /// written and shown on `bridge_health` regardless).
+    #[serde(default)]
+    pub crash_notify: bool,
+
     /// Paths to check for disk usage (e.g. `C:\`, `D:\` or `/`, `/home`).
     /// If empty, disk sensor reports nothing even when enabled.
     #[serde(default)]
@@ -73,33 +140,130 @@ pub struct Config {
     pub custom_sensors: Vec<CustomSensor>,
     #[serde(default)]
     pub custom_commands: Vec<CustomCommand>,
+
+    /// Process watchdog rules. Each rule publishes an alert event when its
+    /// process starts/stops or crosses a CPU/RAM threshold. Empty = watchdog off.
+    #[serde(default)]
+    pub process_alerts: Vec<ProcessAlertRule>,
+
+    /// Process presence sensors: each is an `<name>_running` binary sensor,
+    /// on while any of its processes runs. Empty = none.
+    #[serde(default)]
+    pub process_sensors: Vec<ProcessSensor>,
+
+    /// Local automations: run a command when the PC goes idle, resumes, etc.
+    /// Carried out by the agent itself, so they work with HA/the broker down.
+    #[serde(default)]
+    pub automations: Vec<AutomationRule>,
+
+    /// Other PCs this (always-on) one wakes with Wake-on-LAN. Read at startup.
+    #[serde(default)]
+    pub wol_relay: Vec<WolTarget>,
+
+    /// Display modes offered by the `display_mode` select, e.g. "4K60" and
+    /// "1440p144". Picking one switches the primary display to it.
+    #[serde(default)]
+    pub display_modes: Vec<DisplayMode>,
+
+    /// Speed test CLI the `Speedtest` command runs instead of the built-in
+    /// HTTP test: program then arguments, no shell. It must print Ookla
+    /// (`speedtest --format=json`) or speedtest-cli (`--json`) JSON.
+    #[serde(default)]
+    pub speedtest_command: Vec<String>,
+
+    /// Printers (spooler / CUPS queue names) with a status sensor; the first
+    /// is where `Print` goes unless its payload names another.
+    #[serde(default)]
+    pub printers: Vec<String>,
+
+    /// USB devices with a presence binary sensor, matched by vendor (and
+    /// optionally product) ID.
+    #[serde(default)]
+    pub usb_devices: Vec<UsbDevice>,
+
+    /// Docker / Podman engine to watch, and the containers with their own
+    /// entities
+    #[serde(default)]
+    pub docker: DockerConfig,
+
+    /// External programs that register their own sensors and commands over a
+    /// JSON-lines stdio protocol. Read at startup.
+    #[serde(default)]
+    pub plugins: Vec<PluginConfig>,
+
+    /// Per-entity display name, icon and enabled overrides, keyed by the
+    /// entity's object ID (`cpu_usage`, `sleep`, ...). Applied at discovery.
+    #[serde(default)]
+    pub entity_overrides: HashMap<String, EntityOverride>,
+
+    /// Per-sensor publish thresholds for numeric sensors, keyed by object ID
+    /// like `entity_overrides` (`cpu_usage`, `custom_<name>`, ...)
+    #[serde(default)]
+    pub deadbands: HashMap<String, Deadband>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             device_name: "pc-bridge".to_string(),
+            suggested_area: None,
+            group: None,
+            language: default_language(),
             mqtt: MqttConfig {
                 broker: String::new(),
                 user: String::new(),
                 pass: String::new(),
                 client_id: None,
+                protocol_version: default_mqtt_protocol_version(),
+                session_expiry_secs: default_session_expiry_secs(),
+                availability_expiry_secs: 0,
+                keep_alive_secs: default_keep_alive_secs(),
+                connect_timeout_secs: default_connect_timeout_secs(),
+                tcp_nodelay: false,
+                bundled_state: false,
             },
+            ha_api: HaApiConfig::default(),
+            http_api: HttpApiConfig::default(),
+            openrgb: OpenRgbConfig::default(),
+            command_queue: CommandQueueConfig::default(),
+            notifications: NotificationConfig::default(),
+            idle: IdleConfig::default(),
+            shutdown_guard: ShutdownGuardConfig::default(),
+            game_limits: GameLimitsConfig::default(),
+            thermal_guard: ThermalGuardConfig::default(),
+            focus: FocusConfig::default(),
+            window_history_size: default_window_history_size(),
             intervals: IntervalConfig::default(),
             features: FeatureConfig::default(),
             games: HashMap::new(),
+            emulators: HashMap::new(),
             custom_sensors_enabled: false,
             custom_commands_enabled: false,
             custom_command_privileges_allowed: false,
+            scripts_enabled: false,
             allow_raw_commands: false,
             allow_global_launch: true,
             allow_global_close: false,
             show_tray_icon: true,
             discord_keybind: None,
             update_channel: default_update_channel(),
+            auto_update: true,
+            crash_notify: false,
             disk_sensor_paths: Vec::new(),
             custom_sensors: Vec::new(),
             custom_commands: Vec::new(),
+            process_alerts: Vec::new(),
+            process_sensors: Vec::new(),
+            automations: Vec::new(),
+            wol_relay: Vec::new(),
+            display_modes: Vec::new(),
+            speedtest_command: Vec::new(),
+            printers: Vec::new(),
+            usb_devices: Vec::new(),
+            docker: DockerConfig::default(),
+            plugins: Vec::new(),
+            entity_overrides: HashMap::new(),
+            deadbands: HashMap::new(),
         }
     }
 }
@@ -108,6 +272,10 @@ fn default_true() -> bool {
     true
 }
 
+pub fn default_language() -> String {
+    "en".to_string()
+}
+
 pub fn default_update_channel() -> String {
     "stable".to_string()
 }
@@ -135,6 +303,14 @@ pub enum GameConfig {
         /// Whether this game is exposed in the game_catalog sensor (default: true)
         #[serde(default = "default_true")]
         exposed: bool,
+        /// Detect by a window title containing this (case-insensitive) instead
+        /// of the process name - for emulators and other shared executables
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        window_title: Option<String>,
+        /// Detect by a running Microsoft Store / Game Pass app whose
+        /// AppUserModelID starts with this instead of the process name (Windows)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        aumid: Option<String>,
     },
 }
 
@@ -219,6 +395,22 @@ impl GameConfig {
         }
     }
 
+    /// Window-title pattern, if this game is detected by its window
+    pub fn window_title(&self) -> Option<&str> {
+        match self {
+            GameConfig::Simple(_) => None,
+            GameConfig::Full { window_title, .. } => window_title.as_deref(),
+        }
+    }
+
+    /// AppUserModelID prefix, if this game is detected as a Store app
+    pub fn aumid(&self) -> Option<&str> {
+        match self {
+            GameConfig::Simple(_) => None,
+            GameConfig::Full { aumid, .. } => aumid.as_deref(),
+        }
+    }
+
     /// Create from Steam discovery
     pub fn from_steam(game_id: String, app_id: u32, name: String) -> Self {
         GameConfig::Full {
@@ -228,7 +420,62 @@ impl GameConfig {
             launch_command: None,
             auto_discovered: true,
             exposed: true,
+            window_title: None,
+            aumid: None,
+        }
+    }
+}
+
+/// Emulator whose loaded game is read from its window caption, e.g.
+/// `Dolphin 5.0 | JIT64 | HLE | Super Mario Sunshine (GMSE01)`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EmulatorConfig {
+    /// Game ID reported while the emulator runs (default: the process pattern)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_id: Option<String>,
+    /// The emulator's window caption starts with this, case-insensitive
+    /// (default: the process pattern)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_prefix: Option<String>,
+    /// Splits the caption into parts (default: `" - "`)
+    #[serde(default = "default_emulator_separator")]
+    pub separator: String,
+    /// Which part is the game, from 0; negative counts from the end
+    /// (default: -1, the last part)
+    #[serde(default = "default_emulator_part")]
+    pub part: i32,
+}
+
+fn default_emulator_separator() -> String {
+    " - ".to_string()
+}
+
+fn default_emulator_part() -> i32 {
+    -1
+}
+
+impl EmulatorConfig {
+    /// The game ID reported for the emulator under `pattern`
+    pub fn game_id(&self, pattern: &str) -> String {
+        self.game_id
+            .clone()
+            .unwrap_or_else(|| pattern.to_lowercase())
+    }
+
+    /// The game named in an emulator window caption, or None while the
+    /// caption has no such part (no game loaded, just the emulator's name).
+    pub fn game_in_title(&self, title: &str) -> Option<String> {
+        let parts: Vec<&str> = title.split(self.separator.as_str()).collect();
+        if parts.len() < 2 {
+            return None;
         }
+        let index = if self.part < 0 {
+            parts.len().checked_sub(self.part.unsigned_abs() as usize)?
+        } else {
+            self.part as usize
+        };
+        let game = parts.get(index)?.trim();
+        (!game.is_empty()).then(|| game.to_string())
     }
 }
 
@@ -245,6 +492,10 @@ pub struct FeatureConfig {
     pub running_game: bool,
     #[serde(default)]
     pub game_catalog: bool,
+    /// `game_art` image: Steam artwork of the last game started (needs
+    /// `running_game`)
+    #[serde(default)]
+    pub game_art: bool,
     #[serde(default)]
     pub steam_library: bool,
     #[serde(default)]
@@ -279,6 +530,9 @@ pub struct FeatureConfig {
     pub active_window: bool,
     #[serde(default)]
     pub session_state: bool,
+    /// `remote_desktop` binary sensor and remote/console connect events
+    #[serde(default)]
+    pub remote_desktop: bool,
     #[serde(default)]
     pub audio_device: bool,
     #[serde(default)]
@@ -305,6 +559,102 @@ pub struct FeatureConfig {
     pub uptime_sensor: bool,
     #[serde(default)]
     pub hwinfo_sensor: bool,
+    /// Pending Windows Update count + reboot-required (Windows only)
+    #[serde(default)]
+    pub windows_update: bool,
+    /// `WindowsUpdateInstall` button: download and install pending updates.
+    /// Separate from the sensor and off by default - it changes the system.
+    #[serde(default)]
+    pub cmd_windows_update_install: bool,
+    /// Next scheduled wake of the PC (wake-to-run tasks / RTC alarm)
+    #[serde(default)]
+    pub wake_timers: bool,
+    /// `sleep_blockers` sensor: active power requests keeping the PC awake
+    #[serde(default)]
+    pub sleep_blockers: bool,
+    /// Power plan select (read + switch) and `ac_connected` binary sensor
+    #[serde(default)]
+    pub power_plan: bool,
+    /// Battery levels of connected controllers, headsets and mice
+    #[serde(default)]
+    pub device_batteries: bool,
+    /// Controller connected / recently used binary sensors
+    #[serde(default)]
+    pub gamepad: bool,
+    /// `vr_active` binary sensor: a VR runtime session is running
+    #[serde(default)]
+    pub vr: bool,
+    /// `transcoding` binary sensor: the GPU's video encoder is in use
+    #[serde(default)]
+    pub transcoding: bool,
+    /// Network name, metered and VPN sensors
+    #[serde(default)]
+    pub network_connection: bool,
+    /// Per-disk SMART health, temperature and reallocated sector sensors
+    #[serde(default)]
+    pub disk_health: bool,
+    /// `latency` sensor: round trip to the MQTT broker (else the HA host)
+    #[serde(default)]
+    pub latency: bool,
+    /// `public_ip` sensor: external IP, looked up from a public service
+    #[serde(default)]
+    pub public_ip: bool,
+    /// Printer status sensors (see `printers`) and the `Print` command
+    #[serde(default)]
+    pub printers: bool,
+    /// USB plug / unplug events and `usb_devices` presence sensors
+    #[serde(default)]
+    pub usb_devices: bool,
+    /// `docker_containers` sensor, plus a running sensor and start / stop
+    /// buttons per `docker.containers` entry
+    #[serde(default)]
+    pub docker: bool,
+    /// `media_playing` binary sensor: the output device is producing sound
+    #[serde(default)]
+    pub media_playing: bool,
+    /// Display mode select: switch the primary display between `display_modes`
+    #[serde(default)]
+    pub display_mode: bool,
+    /// Light / dark theme select (Windows app + system theme, GNOME / KDE
+    /// color scheme)
+    #[serde(default)]
+    pub theme: bool,
+    /// RGB light entity driving OpenRGB (see `openrgb`)
+    #[serde(default)]
+    pub openrgb: bool,
+    /// Pause the system, custom and WMI-heavy sensors while a game runs (or
+    /// the `perf_mode` switch is on)
+    #[serde(default)]
+    pub perf_mode: bool,
+    /// `window_history` sensor: the last `window_history_size` active
+    /// windows with how long each had focus (needs `active_window`)
+    #[serde(default)]
+    pub window_history: bool,
+    /// `focus_mode` switch and `focus_remaining` sensor: timed focus sessions
+    /// that close `focus.blocked_apps` and hold back notifications
+    #[serde(default)]
+    pub focus_mode: bool,
+    /// `Speedtest` button and download / upload / ping result sensors
+    #[serde(default)]
+    pub cmd_speedtest: bool,
+    /// `DiskCleanup` button: empty the recycle bin and old temp files
+    #[serde(default)]
+    pub cmd_disk_cleanup: bool,
+    /// `InstallUpdate` button: install the release the update entity shows.
+    /// Off by default - it replaces the binary and restarts the agent.
+    #[serde(default)]
+    pub cmd_install_update: bool,
+    /// `BackupConfig` button and `RestoreConfig` command: read and replace
+    /// userConfig.json over MQTT
+    #[serde(default)]
+    pub cmd_config_backup: bool,
+    /// `FileDownload` / `FileChunk` commands: receive images and sounds into
+    /// the `files` folder
+    #[serde(default)]
+    pub cmd_file_transfer: bool,
+    /// `SetWallpaper` command: a received image, a local path or a URL
+    #[serde(default)]
+    pub cmd_wallpaper: bool,
 }
 
 impl Default for FeatureConfig {
@@ -329,6 +679,7 @@ impl Default for FeatureConfig {
             memory_sensor: false,
             active_window: false,
             session_state: false,
+            remote_desktop: false,
             audio_device: false,
             mic: false,
             webcam: false,
@@ -342,6 +693,36 @@ impl Default for FeatureConfig {
             disk_sensor: false,
             uptime_sensor: false,
             hwinfo_sensor: false,
+            windows_update: false,
+            cmd_windows_update_install: false,
+            wake_timers: false,
+            sleep_blockers: false,
+            power_plan: false,
+            device_batteries: false,
+            gamepad: false,
+            vr: false,
+            transcoding: false,
+            network_connection: false,
+            disk_health: false,
+            latency: false,
+            public_ip: false,
+            printers: false,
+            usb_devices: false,
+            docker: false,
+            media_playing: false,
+            display_mode: false,
+            game_art: false,
+            theme: false,
+            openrgb: false,
+            perf_mode: false,
+            window_history: false,
+            focus_mode: false,
+            cmd_speedtest: false,
+            cmd_disk_cleanup: false,
+            cmd_install_update: false,
+            cmd_config_backup: false,
+            cmd_file_transfer: false,
+            cmd_wallpaper: false,
         }
     }
 }
@@ -369,6 +750,24 @@ pub struct CustomSensor {
     pub registry_key: Option<String>,
     #[serde(default)]
     pub registry_value: Option<String>,
+    /// Regex a `log_tail` line must match
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Directory a `folder` sensor watches
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// What a `folder` sensor reports
+    #[serde(default)]
+    pub metric: FolderMetric,
+    /// Host (name or IPv4 address) a `ping` sensor pings
+    #[serde(default)]
+    pub host: Option<String>,
+    /// How long a `ping` waits for the reply (default 2000)
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+    /// Windows service name or systemd unit a `service` sensor checks
+    #[serde(default)]
+    pub service: Option<String>,
 }
 
 /// Custom sensor types
@@ -379,6 +778,23 @@ pub enum CustomSensorType {
     ProcessExists,
     FileContents,
     Registry,
+    LogTail,
+    Folder,
+    Ping,
+    Service,
+}
+
+/// What a `folder` custom sensor reports about the files directly in it
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FolderMetric {
+    /// Number of files
+    #[default]
+    Count,
+    /// Total size of the files, in MB
+    Size,
+    /// Name of the most recently modified file
+    Newest,
 }
 
 fn default_sensor_interval() -> u64 {
@@ -404,6 +820,65 @@ pub struct CustomCommand {
     pub args: Option<Vec<String>>,
     #[serde(default)]
     pub command: Option<String>,
+    /// Windows service name or systemd unit of the `service_*` types
+    #[serde(default)]
+    pub service: Option<String>,
+    /// Run under resource limits instead of unrestricted
+    #[serde(default)]
+    pub sandbox: Option<CommandSandbox>,
+}
+
+/// Resource limits for a sandboxed custom command, applied to it and
+/// everything it starts (a Job Object on Windows, a systemd scope on Linux).
+/// It still runs as the agent's user with the agent's privileges.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CommandSandbox {
+    /// Hard CPU cap as a percentage of the whole machine (1-100)
+    #[serde(default)]
+    pub cpu_percent: Option<u8>,
+    /// Memory cap in MiB
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// Killed after this many seconds (default 300)
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// External plugin process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginConfig {
+    /// Prefix of its entities: `plugin_<name>_<entity>`. No `_`, so two
+    /// plugins' entity names can't collide.
+    pub name: String,
+    /// Program then arguments, no shell
+    pub command: Vec<String>,
+}
+
+/// Changes to how one HA entity is presented
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EntityOverride {
+    /// Replaces the built-in display name
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Replaces the built-in icon, e.g. `mdi:chip`
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// `false` removes the entity from HA; the feature behind it keeps running
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+/// When a numeric sensor's new reading is worth publishing
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct Deadband {
+    /// Smallest move from the last published value that goes out at once,
+    /// in the sensor's unit (1.0 on `cpu_usage` = one percentage point)
+    #[serde(default)]
+    pub min_change: f64,
+    /// A smaller change still goes out once this many seconds have passed
+    /// since the last publish; 0 = only `min_change` counts
+    #[serde(default)]
+    pub max_age_secs: u64,
 }
 
 /// Custom command types
@@ -413,6 +888,204 @@ pub enum CustomCommandType {
     Powershell,
     Executable,
     Shell,
+    ServiceStart,
+    ServiceStop,
+    ServiceRestart,
+}
+
+/// Whether `name` can be a `service` sensor's or command's service. Only
+/// the characters of real service and unit names, so it can never break
+/// out of the PowerShell quoting it ends up in.
+fn valid_service_name(name: &str) -> bool {
+    !name.trim().is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '_' | '-' | '.' | '@' | ':'))
+}
+
+/// Process watchdog rule - flag a process you want to know about (a crypto
+/// miner appearing, an updater running away with the CPU, ...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessAlertRule {
+    /// Process name, matched exactly (case- and `.exe`-insensitive), same as
+    /// CloseGame - a short name must not flag an unrelated system process.
+    pub process: String,
+    /// Alert when the first instance starts
+    #[serde(default = "default_true")]
+    pub on_start: bool,
+    /// Alert when the last instance exits
+    #[serde(default = "default_true")]
+    pub on_stop: bool,
+    /// Alert when all instances together use more than this share of total CPU
+    /// (0-100, normalized across cores like Task Manager)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<f64>,
+    /// Alert when all instances together hold more than this much RAM (MB)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_mb: Option<u64>,
+}
+
+/// Process presence sensor - e.g. OBS running, a video editor open. A
+/// binary sensor without a PowerShell custom sensor polling for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessSensor {
+    /// Sensor name in HA, e.g. "OBS"; the entity is `obs_running`
+    pub name: String,
+    /// Process names, case- and `.exe`-insensitive; `*` matches any run of
+    /// characters ("Adobe Premiere*")
+    pub processes: Vec<String>,
+    /// mdi icon (default `mdi:application`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+}
+
+impl ProcessSensor {
+    /// Whether a running process with this name counts.
+    pub fn matches(&self, process: &str) -> bool {
+        let process = exe_stem(process).to_lowercase();
+        self.processes
+            .iter()
+            .any(|p| wildcard_match(&exe_stem(p.trim()).to_lowercase(), &process))
+    }
+}
+
+/// `name` without a trailing `.exe` (any case)
+fn exe_stem(name: &str) -> &str {
+    if name.len() >= 4 && name.as_bytes()[name.len() - 4..].eq_ignore_ascii_case(b".exe") {
+        &name[..name.len() - 4]
+    } else {
+        name
+    }
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any run of characters.
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts: Vec<&str> = parts.collect();
+    // No `*`: the whole name.
+    let Some(last) = parts.pop() else {
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Local automation - e.g. "after 30 min idle and no game running, turn the
+/// display off" or "on resume, run my custom command".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutomationRule {
+    /// Shown in the log when the rule fires
+    #[serde(default)]
+    pub name: String,
+    pub trigger: AutomationTrigger,
+    /// `idle` trigger: minutes without input before the rule fires (once per
+    /// idle stretch)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_minutes: Option<u64>,
+    /// Don't fire while a game is running
+    #[serde(default)]
+    pub unless_gaming: bool,
+    /// Command to run: a built-in (`MonitorOff`, `Lock`, `Screensaver`, ...) or
+    /// a custom command's name
+    pub command: String,
+    #[serde(default)]
+    pub payload: String,
+}
+
+/// What sets off an `AutomationRule`. All but `idle` are the bridge events of
+/// the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutomationTrigger {
+    /// No input for `idle_minutes` (needs the `idle_tracking` feature)
+    Idle,
+    UserReturned,
+    /// Back from sleep/hibernate (needs the `sleep_wake` feature)
+    ResumedFromSleep,
+    GameStarted,
+    GameStopped,
+}
+
+/// A PC the Wake-on-LAN relay can wake.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WolTarget {
+    /// The other PC's pc-bridge `device_name`
+    pub device_name: String,
+    /// MAC address of its wired network card, e.g. `AA:BB:CC:DD:EE:FF`
+    pub mac: String,
+    /// Where the magic packet is sent: the LAN broadcast address, or the
+    /// subnet's directed broadcast (e.g. `192.168.1.255`)
+    #[serde(default = "default_wol_broadcast")]
+    pub broadcast: String,
+    #[serde(default = "default_wol_port")]
+    pub port: u16,
+}
+
+/// A resolution + refresh rate the primary display can be switched to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayMode {
+    /// Option shown in HA, e.g. "4K60"
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Hz. Absent: keep whatever rate the display picks for the resolution.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_rate: Option<u32>,
+}
+
+/// A USB device given a presence binary sensor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsbDevice {
+    /// Sensor name in HA, e.g. "Phone"
+    pub name: String,
+    /// Vendor ID, 4 hex digits ("04e8")
+    pub vid: String,
+    /// Product ID. Absent: any product of the vendor (phones change it
+    /// between charging, MTP and tethering modes).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<String>,
+}
+
+impl UsbDevice {
+    /// Whether a device with these IDs is this one. IDs that don't parse
+    /// (rejected by `validate`) match nothing.
+    pub fn matches(&self, vid: u16, pid: u16) -> bool {
+        parse_usb_id(&self.vid) == Some(vid)
+            && self
+                .pid
+                .as_deref()
+                .is_none_or(|p| parse_usb_id(p) == Some(pid))
+    }
+}
+
+/// A USB vendor / product ID: up to 4 hex digits, optionally `0x`-prefixed.
+pub fn parse_usb_id(id: &str) -> Option<u16> {
+    let id = id.trim();
+    let hex = id
+        .strip_prefix("0x")
+        .or_else(|| id.strip_prefix("0X"))
+        .unwrap_or(id);
+    if hex.is_empty() || hex.len() > 4 {
+        return None;
+    }
+    u16::from_str_radix(hex, 16).ok()
+}
+
+fn default_wol_broadcast() -> String {
+    "255.255.255.255".to_string()
+}
+
+fn default_wol_port() -> u16 {
+    9
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -424,19 +1097,470 @@ pub struct MqttConfig {
     pub pass: String,
     #[serde(default)]
     pub client_id: Option<String>,
+    /// MQTT protocol level: 4 = v3.1.1 (default), 5 = MQTT 5.
+    #[serde(default = "default_mqtt_protocol_version")]
+    pub protocol_version: u8,
+    /// MQTT 5 only: how long the broker keeps our session (subscriptions and
+    /// queued QoS 1 commands) after a disconnect.
+    #[serde(default = "default_session_expiry_secs")]
+    pub session_expiry_secs: u32,
+    /// MQTT 5 only: message expiry on the retained "offline" will, so the
+    /// broker eventually drops availability for a PC that never comes back.
+    /// 0 = never expires.
+    #[serde(default)]
+    pub availability_expiry_secs: u32,
+    /// Seconds between keep-alive pings. Lower it below a firewall's or NAT's
+    /// idle timeout if the connection keeps getting dropped silently.
+    #[serde(default = "default_keep_alive_secs")]
+    pub keep_alive_secs: u16,
+    /// Seconds to wait for the TCP/TLS connection to the broker
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+    /// Send packets at once instead of batching small writes (Nagle off)
+    #[serde(default)]
+    pub tcp_nodelay: bool,
+    /// Publish the system sensors as one JSON document on
+    /// `pc-bridge/<device>/state` every `intervals.bundled_state` instead of
+    /// a message per sensor, for constrained brokers. Read at startup.
+    #[serde(default)]
+    pub bundled_state: bool,
+}
+
+fn default_mqtt_protocol_version() -> u8 {
+    4
+}
+
+fn default_keep_alive_secs() -> u16 {
+    30
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    5
+}
+
+fn default_session_expiry_secs() -> u32 {
+    3600
+}
+
+impl MqttConfig {
+    pub fn is_v5(&self) -> bool {
+        self.protocol_version == 5
+    }
+}
+
+impl std::fmt::Debug for MqttConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MqttConfig")
+            .field("broker", &self.broker)
+            .field("user", &self.user)
+            .field("pass", &"[REDACTED]")
+            .field("client_id", &self.client_id)
+            .field("protocol_version", &self.protocol_version)
+            .field("session_expiry_secs", &self.session_expiry_secs)
+            .field("availability_expiry_secs", &self.availability_expiry_secs)
+            .field("keep_alive_secs", &self.keep_alive_secs)
+            .field("connect_timeout_secs", &self.connect_timeout_secs)
+            .field("tcp_nodelay", &self.tcp_nodelay)
+            .field("bundled_state", &self.bundled_state)
+            .finish()
+    }
+}
+
+/// Home Assistant WebSocket/REST API transport, for setups without a broker.
+/// Sensor states are written with the REST API (`POST /api/states/...`) and
+/// commands arrive as `pc_bridge_command` events over the WebSocket API.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct HaApiConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Base URL of the HA instance, e.g. `http://homeassistant.local:8123`
+    #[serde(default)]
+    pub url: String,
+    /// Long-lived access token (HA profile → Security). Kept in the
+    /// `ha_api_credential` file, not userConfig.json, like `mqtt.pass`.
+    #[serde(default)]
+    pub token: String,
+}
+
+impl std::fmt::Debug for HaApiConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HaApiConfig")
+            .field("enabled", &self.enabled)
+            .field("url", &self.url)
+            .field("token", &"[REDACTED]")
+            .finish()
+    }
+}
+
+/// Embedded HTTP server: `GET /health`, `GET /status` and `POST /command`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HttpApiConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Listen address. Loopback by default; any other address requires `token`.
+    #[serde(default = "default_http_api_bind")]
+    pub bind: String,
+    /// Bearer token for `/status`, `/command` and `/metrics` (`/health`
+    /// stays open). Generated and saved on first start when empty on
+    /// loopback; any other bind requires setting it.
+    #[serde(default)]
+    pub token: String,
+    /// Serve Prometheus metrics at `/metrics`
+    #[serde(default)]
+    pub metrics: bool,
+}
+
+fn default_http_api_bind() -> String {
+    "127.0.0.1:8765".to_string()
+}
+
+impl Default for HttpApiConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind: default_http_api_bind(),
+            token: String::new(),
+            metrics: false,
+        }
+    }
+}
+
+impl std::fmt::Debug for HttpApiConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HttpApiConfig")
+            .field("enabled", &self.enabled)
+            .field("bind", &self.bind)
+            .field("token", &"[REDACTED]")
+            .field("metrics", &self.metrics)
+            .finish()
+    }
+}
+
+/// Local OpenRGB SDK server (OpenRGB's "SDK Server" tab)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenRgbConfig {
+    #[serde(default = "default_openrgb_host")]
+    pub host: String,
+    #[serde(default = "default_openrgb_port")]
+    pub port: u16,
+}
+
+fn default_openrgb_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+fn default_openrgb_port() -> u16 {
+    6742
+}
+
+impl Default for OpenRgbConfig {
+    fn default() -> Self {
+        Self {
+            host: default_openrgb_host(),
+            port: default_openrgb_port(),
+        }
+    }
+}
+
+/// Command executor limits. Commands beyond `max_concurrent` wait in a FIFO
+/// queue of `queue_size`; one that waits longer than `queue_timeout_secs` is
+/// dropped as stale, as is one arriving to a full queue.
+///
+/// A command arriving more than its per-minute rate limit trips a breaker:
+/// it is ignored for `flood_cooldown_secs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandQueueConfig {
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    #[serde(default = "default_queue_timeout_secs")]
+    pub queue_timeout_secs: u64,
+    /// Per command name; 0 = unlimited
+    #[serde(default = "default_rate_limit_per_min")]
+    pub rate_limit_per_min: u32,
+    /// `rate_limit_per_min` for specific commands, e.g. `{"VolumeSet": 120}`
+    #[serde(default)]
+    pub rate_limits: HashMap<String, u32>,
+    #[serde(default = "default_flood_cooldown_secs")]
+    pub flood_cooldown_secs: u64,
+}
+
+fn default_max_concurrent() -> usize {
+    5
+}
+
+fn default_queue_size() -> usize {
+    20
+}
+
+fn default_queue_timeout_secs() -> u64 {
+    60
+}
+
+fn default_rate_limit_per_min() -> u32 {
+    30
+}
+
+fn default_flood_cooldown_secs() -> u64 {
+    300
+}
+
+impl Default for CommandQueueConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent: default_max_concurrent(),
+            queue_size: default_queue_size(),
+            queue_timeout_secs: default_queue_timeout_secs(),
+            rate_limit_per_min: default_rate_limit_per_min(),
+            rate_limits: HashMap::new(),
+            flood_cooldown_secs: default_flood_cooldown_secs(),
+        }
+    }
+}
+
+/// Do-not-disturb handling for the notify topic. "Busy" is Focus Assist, a
+/// fullscreen app or presentation mode on Windows, GNOME's Do Not Disturb on
+/// Linux, and a detected game on both. Payloads with `"critical": true`
+/// always show.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    /// Don't show toasts while busy; they're counted on the
+    /// `notifications_suppressed` sensor instead.
+    #[serde(default)]
+    pub suppress_when_busy: bool,
+    /// Show suppressed toasts once the PC is no longer busy, instead of
+    /// dropping them.
+    #[serde(default)]
+    pub queue_suppressed: bool,
+    /// How many received notifications the `last_notification` sensor keeps
+    /// (0 = no history)
+    #[serde(default = "default_notification_history")]
+    pub history_size: usize,
+    /// Name toasts appear under (a payload's `app_name` overrides it)
+    #[serde(default = "default_notification_app_name")]
+    pub app_name: String,
+}
+
+fn default_notification_history() -> usize {
+    10
+}
+
+fn default_notification_app_name() -> String {
+    "PC Bridge".to_string()
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        Self {
+            suppress_when_busy: false,
+            queue_suppressed: false,
+            history_size: default_notification_history(),
+            app_name: default_notification_app_name(),
+        }
+    }
+}
+
+/// Activity that keeps `idle_seconds` from growing besides keyboard/mouse
+/// input in pc-bridge's own session, so watching a video or playing with a
+/// controller doesn't read as "user is idle".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdleConfig {
+    /// Controller input (needs the `gamepad` feature)
+    #[serde(default = "default_true")]
+    pub gamepad: bool,
+    /// Sound playing (needs the `media_playing` feature). Off by default:
+    /// music left on would keep the PC "in use" all day.
+    #[serde(default)]
+    pub media: bool,
+    /// Input in any other session, e.g. over Remote Desktop (Windows)
+    #[serde(default = "default_true")]
+    pub remote_sessions: bool,
+}
+
+impl Default for IdleConfig {
+    fn default() -> Self {
+        Self {
+            gamepad: true,
+            media: false,
+            remote_sessions: true,
+        }
+    }
+}
+
+/// Refuse power commands while the PC is in use: a game running, Steam
+/// updating, or CPU/network busier than the thresholds. A payload with
+/// `force:true` gets past it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShutdownGuardConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// CPU usage (%) above which power commands are refused (`0` = ignore;
+    /// needs `cpu_sensor`)
+    #[serde(default = "default_guard_cpu_percent")]
+    pub cpu_percent: u8,
+    /// Combined download + upload (Mbit/s) above which power commands are
+    /// refused (`0` = ignore; needs `network_sensor`)
+    #[serde(default = "default_guard_network_mbps")]
+    pub network_mbps: u32,
+}
+
+fn default_guard_cpu_percent() -> u8 {
+    80
+}
+
+fn default_guard_network_mbps() -> u32 {
+    5
+}
+
+impl Default for ShutdownGuardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cpu_percent: default_guard_cpu_percent(),
+            network_mbps: default_guard_network_mbps(),
+        }
+    }
+}
+
+/// Daily playtime limits: a toast `warn_minutes` before a limit, then a toast,
+/// a `game_limit_reached` event and `action` once it's reached. Playtime is
+/// counted per local day from the games the game sensor detects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameLimitsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Minutes per day with any game running (`0` = no total limit)
+    #[serde(default)]
+    pub daily_minutes: u32,
+    /// Minutes per day for single games, by game_id (`0` = not allowed)
+    #[serde(default)]
+    pub games: HashMap<String, u32>,
+    /// How long before a limit the warning toast comes (`0` = no warning)
+    #[serde(default = "default_limit_warn_minutes")]
+    pub warn_minutes: u32,
+    #[serde(default)]
+    pub action: GameLimitAction,
+}
+
+fn default_limit_warn_minutes() -> u32 {
+    5
+}
+
+impl Default for GameLimitsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            daily_minutes: 0,
+            games: HashMap::new(),
+            warn_minutes: default_limit_warn_minutes(),
+            action: GameLimitAction::default(),
+        }
+    }
+}
+
+/// Overheat protection: once the CPU or GPU stays above its threshold for
+/// `seconds`, a toast, a `temperature_critical` event and `action`.
+/// Temperatures come from HWiNFO on Windows and hwmon / nvidia-smi on Linux.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThermalGuardConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// CPU package limit in °C (unset = not watched)
+    #[serde(default)]
+    pub cpu_max_c: Option<u32>,
+    /// GPU core limit in °C (unset = not watched)
+    #[serde(default)]
+    pub gpu_max_c: Option<u32>,
+    /// How long a temperature must stay above its limit
+    #[serde(default = "default_thermal_seconds")]
+    pub seconds: u32,
+    #[serde(default)]
+    pub action: ThermalAction,
+}
+
+fn default_thermal_seconds() -> u32 {
+    30
+}
+
+impl Default for ThermalGuardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cpu_max_c: None,
+            gpu_max_c: None,
+            seconds: default_thermal_seconds(),
+            action: ThermalAction::default(),
+        }
+    }
+}
+
+/// What happens on overheating, besides the toast and event
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThermalAction {
+    #[default]
+    None,
+    /// Close every running game, as `StopGame` does
+    CloseGame,
+    /// Shut the PC down, as a forced `Shutdown` does
+    Shutdown,
+}
+
+/// Docker (or Podman, through its Docker-compatible API) on this machine
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DockerConfig {
+    /// Engine socket: a Unix socket path, or a named pipe on Windows. Unset
+    /// tries Docker's default, then Podman's.
+    #[serde(default)]
+    pub socket: Option<String>,
+    /// Container names with a running sensor and start / stop buttons
+    #[serde(default)]
+    pub containers: Vec<String>,
+}
+
+/// Focus sessions started from the `focus_mode` switch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FocusConfig {
+    /// Session length when the switch is turned on (a `FocusMode` payload of
+    /// minutes overrides it)
+    #[serde(default = "default_focus_minutes")]
+    pub minutes: u32,
+    /// Process names closed while a session runs (case- and
+    /// `.exe`-insensitive)
+    #[serde(default)]
+    pub blocked_apps: Vec<String>,
+}
+
+fn default_window_history_size() -> usize {
+    20
+}
+
+fn default_focus_minutes() -> u32 {
+    25
 }
 
-impl std::fmt::Debug for MqttConfig {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("MqttConfig")
-            .field("broker", &self.broker)
-            .field("user", &self.user)
-            .field("pass", &"[REDACTED]")
-            .field("client_id", &self.client_id)
-            .finish()
+impl Default for FocusConfig {
+    fn default() -> Self {
+        Self {
+            minutes: default_focus_minutes(),
+            blocked_apps: Vec::new(),
+        }
     }
 }
 
+/// What happens once a game time limit is reached, besides the toast and event
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameLimitAction {
+    #[default]
+    None,
+    /// Close the game (every game, for `daily_minutes`), as `StopGame` does
+    Close,
+    /// Lock the workstation, as the `Lock` command does
+    Lock,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IntervalConfig {
     #[serde(default = "default_game_sensor")]
@@ -461,6 +1585,91 @@ pub struct IntervalConfig {
     pub network: u64,
     #[serde(default = "default_disk_sensor")]
     pub disk: u64,
+    /// CPU/RAM sampling interval for `process_alerts` rules. Start/stop alerts
+    /// are push-based on Windows and ride this tick on Linux.
+    #[serde(default = "default_process_alerts")]
+    pub process_alerts: u64,
+    /// Process list re-read for `process_sensors`: the Linux poll, and a
+    /// backstop for the push-based watcher on Windows
+    #[serde(default = "default_process_sensors")]
+    pub process_sensors: u64,
+    /// Seconds between heartbeats (availability + every sensor's last value
+    /// republished). Sensor discovery configs get an `expire_after` derived
+    /// from this and their own poll interval, so HA blanks the values of an
+    /// agent that stopped publishing. 0 disables both.
+    #[serde(default = "default_heartbeat")]
+    pub heartbeat: u64,
+    /// How often the `mqtt.bundled_state` document goes out when a bundled
+    /// sensor changed
+    #[serde(default = "default_bundled_state")]
+    pub bundled_state: u64,
+    /// Windows Update search interval. A search contacts the update service
+    /// and takes seconds to minutes, so keep this long.
+    #[serde(default = "default_windows_update")]
+    pub windows_update: u64,
+    /// Wake timer check interval
+    #[serde(default = "default_wake_timers")]
+    pub wake_timers: u64,
+    /// Power request poll interval
+    #[serde(default = "default_sleep_blockers")]
+    pub sleep_blockers: u64,
+    /// Power plan / AC source poll interval
+    #[serde(default = "default_power_plan")]
+    pub power_plan: u64,
+    /// Peripheral battery poll interval
+    #[serde(default = "default_device_batteries")]
+    pub device_batteries: u64,
+    /// Controller poll interval
+    #[serde(default = "default_gamepad")]
+    pub gamepad: u64,
+    /// VR runtime poll interval
+    #[serde(default = "default_vr")]
+    pub vr: u64,
+    /// Video encoder poll interval
+    #[serde(default = "default_transcoding")]
+    pub transcoding: u64,
+    /// Network connection / VPN poll interval
+    #[serde(default = "default_network_connection")]
+    pub network_connection: u64,
+    /// SMART poll interval
+    #[serde(default = "default_disk_health")]
+    pub disk_health: u64,
+    /// Broker / HA ping interval
+    #[serde(default = "default_latency")]
+    pub latency: u64,
+    /// Public IP lookup interval (at least 300)
+    #[serde(default = "default_public_ip")]
+    pub public_ip: u64,
+    /// Printer status poll interval
+    #[serde(default = "default_printers")]
+    pub printers: u64,
+    /// USB rescan interval, a fallback for missed plug notifications
+    #[serde(default = "default_usb_devices")]
+    pub usb_devices: u64,
+    /// Docker container list poll interval
+    #[serde(default = "default_docker")]
+    pub docker: u64,
+    /// Output peak meter poll interval
+    #[serde(default = "default_media_playing")]
+    pub media_playing: u64,
+    /// Display mode poll interval (catches changes made outside HA)
+    #[serde(default = "default_display_mode")]
+    pub display_mode: u64,
+    /// Theme poll interval (catches switches made outside HA)
+    #[serde(default = "default_theme")]
+    pub theme: u64,
+    /// Seconds between `window_history` publishes
+    #[serde(default = "default_window_history")]
+    pub window_history: u64,
+    /// Seconds between update checks (launch always checks). Each stable
+    /// check is one request to GitHub; beta uses the rate-limited API.
+    #[serde(default = "default_update_check")]
+    pub update_check: u64,
+    /// CPU/memory/GPU/network/disk poll interval while the user is idle or
+    /// the display is off (sensors already slower keep their own). 0 keeps
+    /// the normal rates.
+    #[serde(default = "default_idle_poll")]
+    pub idle_poll: u64,
 }
 
 impl Default for IntervalConfig {
@@ -475,6 +1684,31 @@ impl Default for IntervalConfig {
             gpu: default_system_sensors(),
             network: default_system_sensors(),
             disk: default_disk_sensor(),
+            process_alerts: default_process_alerts(),
+            process_sensors: default_process_sensors(),
+            heartbeat: default_heartbeat(),
+            bundled_state: default_bundled_state(),
+            windows_update: default_windows_update(),
+            wake_timers: default_wake_timers(),
+            sleep_blockers: default_sleep_blockers(),
+            power_plan: default_power_plan(),
+            device_batteries: default_device_batteries(),
+            gamepad: default_gamepad(),
+            vr: default_vr(),
+            transcoding: default_transcoding(),
+            network_connection: default_network_connection(),
+            disk_health: default_disk_health(),
+            latency: default_latency(),
+            public_ip: default_public_ip(),
+            printers: default_printers(),
+            usb_devices: default_usb_devices(),
+            docker: default_docker(),
+            media_playing: default_media_playing(),
+            display_mode: default_display_mode(),
+            theme: default_theme(),
+            window_history: default_window_history(),
+            update_check: default_update_check(),
+            idle_poll: default_idle_poll(),
         }
     }
 }
@@ -494,6 +1728,81 @@ fn default_system_sensors() -> u64 {
 fn default_disk_sensor() -> u64 {
     60
 }
+fn default_process_alerts() -> u64 {
+    10
+}
+fn default_process_sensors() -> u64 {
+    5
+}
+fn default_heartbeat() -> u64 {
+    60
+}
+fn default_bundled_state() -> u64 {
+    10
+}
+fn default_windows_update() -> u64 {
+    3600
+}
+fn default_wake_timers() -> u64 {
+    300
+}
+fn default_sleep_blockers() -> u64 {
+    30
+}
+fn default_power_plan() -> u64 {
+    10
+}
+fn default_device_batteries() -> u64 {
+    300
+}
+fn default_gamepad() -> u64 {
+    2
+}
+fn default_vr() -> u64 {
+    5
+}
+fn default_transcoding() -> u64 {
+    10
+}
+fn default_network_connection() -> u64 {
+    30
+}
+fn default_disk_health() -> u64 {
+    600
+}
+fn default_latency() -> u64 {
+    60
+}
+fn default_public_ip() -> u64 {
+    3600
+}
+fn default_printers() -> u64 {
+    30
+}
+fn default_usb_devices() -> u64 {
+    60
+}
+fn default_docker() -> u64 {
+    30
+}
+fn default_media_playing() -> u64 {
+    2
+}
+fn default_display_mode() -> u64 {
+    10
+}
+fn default_theme() -> u64 {
+    30
+}
+fn default_window_history() -> u64 {
+    60
+}
+fn default_update_check() -> u64 {
+    21600
+}
+fn default_idle_poll() -> u64 {
+    120
+}
 
 impl Config {
     /// Given a live list of running process names, return those that match a
@@ -562,8 +1871,10 @@ impl Config {
         let mut config: Config =
             serde_json::from_str(&content).with_context(|| "Failed to parse userConfig.json")?;
 
-        // Load MQTT password from credential file (or migrate from inline JSON)
+        // Load the MQTT password and HA token from their credential files (or
+        // migrate them from inline JSON)
         Self::load_credential(&mut config, &config_path)?;
+        Self::load_ha_token(&mut config, &config_path);
 
         config.validate()?;
 
@@ -595,6 +1906,7 @@ impl Config {
 
         // Clear any inline password remnant without decrypting
         config.mqtt.pass = String::new();
+        Self::load_ha_token(&mut config, &config_path);
 
         config.validate()?;
         Ok(config)
@@ -603,49 +1915,92 @@ impl Config {
     /// Load the MQTT credential from the separate file, or migrate from inline JSON.
     fn load_credential(config: &mut Config, config_path: &PathBuf) -> Result<()> {
         let inline_pass = std::mem::take(&mut config.mqtt.pass);
-        let cred_path = crate::credential::credential_path()?;
+        config.mqtt.pass = Self::load_secret(
+            inline_pass,
+            crate::credential::MQTT,
+            ("mqtt", "pass"),
+            config_path,
+        )
+        .map_err(|e| {
+            anyhow::Error::new(crate::credential::CredentialDecryptFailed).context(e.message)
+        })?;
+        Ok(())
+    }
+
+    /// Load the HA API token like the MQTT password. One that can't be
+    /// decrypted (config copied from another user) leaves the token empty:
+    /// the HA API transport reports it, the rest of the bridge still starts.
+    fn load_ha_token(config: &mut Config, config_path: &PathBuf) {
+        let inline_token = std::mem::take(&mut config.ha_api.token);
+        match Self::load_secret(
+            inline_token,
+            crate::credential::HA_API,
+            ("ha_api", "token"),
+            config_path,
+        ) {
+            Ok(token) => config.ha_api.token = token,
+            Err(e) => warn!("Failed to load the HA API token: {e}"),
+        }
+    }
 
-        if cred_path.exists() {
+    /// A secret kept in the credential file `file`. The file takes priority;
+    /// a secret still inline in userConfig.json (`inline`, at `field`) is
+    /// moved into it. Either way the inline copy is blanked in the file.
+    fn load_secret(
+        inline: String,
+        file: &str,
+        field: (&str, &str),
+        config_path: &PathBuf,
+    ) -> Result<String, crate::credential::DecryptError> {
+        let exists = crate::credential::credential_path(file)
+            .map_err(|e| crate::credential::DecryptError {
+                message: e.to_string(),
+            })?
+            .exists();
+
+        let secret = if exists {
             // Credential file takes priority
-            config.mqtt.pass = crate::credential::load_from_file().map_err(|e| {
-                anyhow::Error::new(crate::credential::CredentialDecryptFailed).context(e.message)
+            crate::credential::load_from_file(file)?
+        } else if !inline.is_empty() {
+            // Migration: stored inline in JSON - move to the credential file
+            let plaintext = crate::credential::decrypt(&inline)?;
+            crate::credential::save_to_file(file, &plaintext).map_err(|e| {
+                crate::credential::DecryptError {
+                    message: e.to_string(),
+                }
             })?;
+            info!("Migrated {}.{} to credential file", field.0, field.1);
+            plaintext
+        } else {
+            // Not configured (anonymous MQTT, no HA API)
+            return Ok(String::new());
+        };
 
-            // Clean up stale inline password from JSON if present
-            if !inline_pass.is_empty()
-                && let Err(e) = Self::clear_inline_password(config_path)
-            {
-                warn!("Failed to strip inline password from config (will retry on next save): {e}");
-            }
-        } else if !inline_pass.is_empty() {
-            // Migration: password was stored inline in JSON - move to credential file
-            let plaintext = crate::credential::decrypt(&inline_pass).map_err(|e| {
-                anyhow::Error::new(crate::credential::CredentialDecryptFailed).context(e.message)
-            })?;
-            config.mqtt.pass = plaintext;
-            crate::credential::save_to_file(&config.mqtt.pass)?;
-            if let Err(e) = Self::clear_inline_password(config_path) {
-                warn!("Failed to strip inline password from config (will retry on next save): {e}");
-            }
-            info!("Migrated MQTT password to credential file");
+        // Clean up the stale inline copy from JSON if present
+        if !inline.is_empty()
+            && let Err(e) = Self::clear_inline_secret(config_path, field)
+        {
+            warn!(
+                "Failed to strip inline {}.{} from config (will retry on next save): {e}",
+                field.0, field.1
+            );
         }
-        // else: no password configured (anonymous MQTT)
-
-        Ok(())
+        Ok(secret)
     }
 
-    /// Blank out the `mqtt.pass` field in the JSON config file.
-    fn clear_inline_password(config_path: &PathBuf) -> Result<()> {
+    /// Blank out a secret field (`mqtt.pass`, `ha_api.token`) in the JSON
+    /// config file.
+    fn clear_inline_secret(config_path: &PathBuf, (section, key): (&str, &str)) -> Result<()> {
         let content = std::fs::read_to_string(config_path)?;
         let mut json: serde_json::Value = serde_json::from_str(&content)?;
-        if let Some(mqtt) = json.get_mut("mqtt").and_then(|v| v.as_object_mut()) {
-            mqtt.insert("pass".to_string(), serde_json::Value::String(String::new()));
+        if let Some(section) = json.get_mut(section).and_then(|v| v.as_object_mut()) {
+            section.insert(key.to_string(), serde_json::Value::String(String::new()));
         }
         let content = serde_json::to_string_pretty(&json)?;
         // Atomic write like every other userConfig.json writer: a bare write()
         // here could leave a truncated file (and fire the hot-reload watcher on
         // the partial write) if it's interrupted mid-flight.
-        crate::fsutil::write_atomic(config_path, content.as_bytes(), None)?;
+        Self::write_file(config_path, &content)?;
         Ok(())
     }
 
@@ -794,7 +2149,7 @@ impl Config {
             // Write back the migrated config atomically (same guarantee as save()):
             // a crash mid-write must not truncate the user's config.
             let new_content = serde_json::to_string_pretty(&json)?;
-            crate::fsutil::write_atomic(config_path, new_content.as_bytes(), None)
+            Self::write_file(config_path, &new_content)
                 .with_context(|| format!("Failed to write migrated config to {:?}", config_path))?;
             info!("Migrated userConfig.json - moved feature toggles into features section");
             Ok(new_content)
@@ -809,6 +2164,21 @@ impl Config {
         Ok(dir.join("userConfig.json"))
     }
 
+    /// Folder of the user's Rhai scripts, next to userConfig.json
+    pub fn scripts_dir() -> Result<PathBuf> {
+        Ok(Self::config_dir()?.join("scripts"))
+    }
+
+    /// Folder of the copies `RestoreConfig` keeps of the configs it replaced
+    pub fn backups_dir() -> Result<PathBuf> {
+        Ok(Self::config_dir()?.join("backups"))
+    }
+
+    /// Folder of the files received with `FileDownload` / `FileChunk`
+    pub fn files_dir() -> Result<PathBuf> {
+        Ok(Self::config_dir()?.join("files"))
+    }
+
     /// Get the platform-specific config directory
     fn config_dir() -> Result<PathBuf> {
         // Explicit override, used by the integration test kit to point the real
@@ -964,8 +2334,9 @@ impl Config {
 
     /// Save current config to userConfig.json.
     ///
-    /// The MQTT password is stored in a separate credential file (encrypted
-    /// via DPAPI on Windows).  The JSON config always has `pass: ""`.
+    /// The MQTT password and the HA API token are stored in separate
+    /// credential files (encrypted via DPAPI on Windows).  The JSON config
+    /// always has `pass: ""` and `token: ""`.
     pub fn save(&self) -> Result<()> {
         let config_path = Self::config_path()?;
 
@@ -976,19 +2347,35 @@ impl Config {
         }
 
         // Save encrypted password to separate credential file
-        crate::credential::save_to_file(&self.mqtt.pass)
+        crate::credential::save_to_file(crate::credential::MQTT, &self.mqtt.pass)
             .with_context(|| "Failed to save MQTT credential")?;
+        crate::credential::save_to_file(crate::credential::HA_API, &self.ha_api.token)
+            .with_context(|| "Failed to save HA API token")?;
 
-        // Write config JSON without the password
+        // Write config JSON without the secrets
         let mut to_save = self.clone();
         to_save.mqtt.pass = String::new();
+        to_save.ha_api.token = String::new();
 
         let content = serde_json::to_string_pretty(&to_save)?;
-        crate::fsutil::write_atomic(&config_path, content.as_bytes(), None)
+        Self::write_file(&config_path, &content)
             .with_context(|| format!("Failed to write config to {:?}", config_path))?;
         Ok(())
     }
 
+    /// Every userConfig.json write goes through here: the previous version is
+    /// rotated into `userConfig.json.bak1..CONFIG_BACKUPS`, then the new one
+    /// is written atomically, so neither a crash mid-write nor a bad migration
+    /// can lose hand-edited settings.
+    fn write_file(config_path: &Path, content: &str) -> std::io::Result<()> {
+        if let Err(e) =
+            crate::fsutil::rotate_backups(config_path, content.as_bytes(), CONFIG_BACKUPS)
+        {
+            warn!("Failed to back up {config_path:?} before writing it: {e}");
+        }
+        crate::fsutil::write_atomic(config_path, content.as_bytes(), None)
+    }
+
     /// Validate configuration values
     pub(crate) fn validate(&self) -> Result<()> {
         if self.device_name.is_empty() {
@@ -1000,6 +2387,21 @@ impl Config {
         if self.device_name.contains(char::is_whitespace) {
             bail!("device_name cannot contain whitespace");
         }
+        if self
+            .suggested_area
+            .as_deref()
+            .is_some_and(|area| area.trim().is_empty())
+        {
+            bail!("suggested_area cannot be empty; remove it to leave the device unassigned");
+        }
+        if !crate::i18n::is_supported(&self.language) {
+            let supported: Vec<_> = crate::i18n::languages().map(|(code, _)| code).collect();
+            bail!(
+                "language '{}' is not supported ({})",
+                self.language,
+                supported.join(", ")
+            );
+        }
         // device_name flows into MQTT topics; reject characters that would break
         // or wildcard a subscription (# + /) or otherwise malform a topic.
         if !self
@@ -1009,21 +2411,121 @@ impl Config {
         {
             bail!("device_name may only contain letters, digits, '.', '_', and '-'");
         }
+        if let Some(group) = &self.group
+            && (group.is_empty()
+                || !group
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')))
+        {
+            bail!("group may only contain letters, digits, '.', '_', and '-'");
+        }
+        if self.http_api.enabled {
+            let addr: std::net::SocketAddr = self.http_api.bind.parse().map_err(|_| {
+                anyhow::anyhow!(
+                    "http_api.bind '{}' must be an address:port, e.g. 127.0.0.1:8765",
+                    self.http_api.bind
+                )
+            })?;
+            if !addr.ip().is_loopback() && self.http_api.token.trim().is_empty() {
+                bail!("http_api.token is required when http_api.bind is not a loopback address");
+            }
+        }
+        if self.shutdown_guard.cpu_percent > 100 {
+            bail!("shutdown_guard.cpu_percent must be 0-100");
+        }
+        if self.focus.minutes == 0 || self.focus.minutes > crate::focus::MAX_MINUTES {
+            bail!("focus.minutes must be 1-{}", crate::focus::MAX_MINUTES);
+        }
+        if !(1..=100).contains(&self.window_history_size) {
+            bail!("window_history_size must be 1-100");
+        }
+        if self.game_limits.enabled {
+            if !self.features.running_game {
+                bail!("game_limits needs the running_game feature");
+            }
+            if self.game_limits.daily_minutes == 0 && self.game_limits.games.is_empty() {
+                bail!("game_limits: set daily_minutes or at least one game");
+            }
+        }
+        if self.thermal_guard.enabled {
+            let guard = &self.thermal_guard;
+            if guard.cpu_max_c.is_none() && guard.gpu_max_c.is_none() {
+                bail!("thermal_guard: set cpu_max_c and/or gpu_max_c");
+            }
+            if [guard.cpu_max_c, guard.gpu_max_c]
+                .into_iter()
+                .flatten()
+                .any(|c| !(40..=120).contains(&c))
+            {
+                bail!("thermal_guard: limits must be 40-120 °C");
+            }
+            if !(1..=3600).contains(&guard.seconds) {
+                bail!("thermal_guard.seconds must be 1-3600");
+            }
+            if cfg!(windows) && !self.features.hwinfo_sensor {
+                bail!(
+                    "thermal_guard needs the hwinfo_sensor feature (HWiNFO supplies the temperatures)"
+                );
+            }
+            if guard.action == ThermalAction::Shutdown && !self.features.cmd_shutdown {
+                bail!("thermal_guard.action shutdown needs the cmd_shutdown feature");
+            }
+        }
+        if self.command_queue.max_concurrent == 0 {
+            bail!("command_queue.max_concurrent must be at least 1");
+        }
+
+        if self.features.openrgb {
+            if self.openrgb.host.trim().is_empty() {
+                bail!("openrgb.host must not be empty");
+            }
+            if self.openrgb.port == 0 {
+                bail!("openrgb.port must not be 0");
+            }
+        }
+        if self.ha_api.enabled {
+            if !self.ha_api.url.starts_with("http://") && !self.ha_api.url.starts_with("https://") {
+                bail!("ha_api.url must start with http:// or https://");
+            }
+            if self.ha_api.token.trim().is_empty() {
+                bail!("ha_api.token is required (a long-lived access token)");
+            }
+            // No broker: the HA API is the only transport.
+            if self.mqtt.broker.is_empty() {
+                return self.validate_entities();
+            }
+        }
         if self.mqtt.broker.is_empty() {
             bail!("mqtt.broker is required");
         }
-        // ws:// / wss:// parse but were never given a WebSocket transport (they
-        // silently connected as raw TCP/TLS, which the broker rejects). Reject
-        // them explicitly until real WebSocket support is wired and tested.
+        // There's no WebSocket transport; say so instead of "bad scheme".
         if self.mqtt.broker.starts_with("ws://") || self.mqtt.broker.starts_with("wss://") {
             bail!(
-                "mqtt.broker: ws:// and wss:// are not supported yet; use tcp:// or ssl:// (MQTT over TLS)"
+                "mqtt.broker: ws:// and wss:// are not supported; use tcp:// or ssl:// (MQTT over TLS)"
             );
         }
         if !self.mqtt.broker.starts_with("tcp://") && !self.mqtt.broker.starts_with("ssl://") {
             bail!("mqtt.broker must start with tcp:// or ssl://");
         }
+        if let Err(e) = crate::mqtt::BrokerUrl::parse(&self.mqtt.broker) {
+            bail!("mqtt.broker: {e:#}");
+        }
+        if !matches!(self.mqtt.protocol_version, 4 | 5) {
+            bail!("mqtt.protocol_version must be 4 (MQTT 3.1.1) or 5 (MQTT 5)");
+        }
+        // Below 5s the pings are most of the traffic; 0 would turn them (and
+        // the broker's detection of a dead PC) off.
+        if self.mqtt.keep_alive_secs < 5 {
+            bail!("mqtt.keep_alive_secs must be at least 5");
+        }
+        if !(1..=120).contains(&self.mqtt.connect_timeout_secs) {
+            bail!("mqtt.connect_timeout_secs must be 1-120");
+        }
+        self.validate_entities()
+    }
 
+    /// Validate custom sensors/commands and process alert rules
+    fn validate_entities(&self) -> Result<()> {
         // Validate custom sensors
         for sensor in &self.custom_sensors {
             Self::validate_custom_sensor(sensor)?;
@@ -1034,6 +2536,245 @@ impl Config {
             Self::validate_custom_command(cmd, self.custom_command_privileges_allowed)?;
         }
 
+        for rule in &self.process_alerts {
+            Self::validate_process_alert(rule)?;
+        }
+
+        for (i, sensor) in self.process_sensors.iter().enumerate() {
+            let slug = crate::sensors::slug(&sensor.name);
+            if slug.is_empty() {
+                bail!(
+                    "process_sensors: name '{}' needs a letter or digit",
+                    sensor.name
+                );
+            }
+            if sensor.processes.is_empty() || sensor.processes.iter().any(|p| p.trim().is_empty()) {
+                bail!(
+                    "process_sensors '{}': processes must list at least one process name",
+                    sensor.name
+                );
+            }
+            if self.process_sensors[..i]
+                .iter()
+                .any(|s| crate::sensors::slug(&s.name) == slug)
+            {
+                bail!("process_sensors: duplicate name '{}'", sensor.name);
+            }
+        }
+
+        for rule in &self.automations {
+            Self::validate_automation(rule)?;
+        }
+
+        for target in &self.wol_relay {
+            Self::validate_wol_target(target, &self.device_name)?;
+        }
+
+        for (i, mode) in self.display_modes.iter().enumerate() {
+            if mode.name.trim().is_empty() {
+                bail!("display_modes: name cannot be empty");
+            }
+            if mode.width == 0 || mode.height == 0 || mode.refresh_rate == Some(0) {
+                bail!(
+                    "display_modes '{}': width, height and refresh_rate must be > 0",
+                    mode.name
+                );
+            }
+            if self.display_modes[..i]
+                .iter()
+                .any(|m| m.name.eq_ignore_ascii_case(&mode.name))
+            {
+                bail!("display_modes: duplicate name '{}'", mode.name);
+            }
+        }
+
+        if self
+            .speedtest_command
+            .first()
+            .is_some_and(|program| program.trim().is_empty())
+        {
+            bail!("speedtest_command: the program (first element) cannot be empty");
+        }
+
+        for (i, printer) in self.printers.iter().enumerate() {
+            if printer.trim().is_empty() {
+                bail!("printers: printer name cannot be empty");
+            }
+            if self.printers[..i].contains(printer) {
+                bail!("printers: duplicate printer '{printer}'");
+            }
+        }
+
+        for (i, container) in self.docker.containers.iter().enumerate() {
+            // Docker's own rule for names; also keeps them safe in API paths.
+            if !container.starts_with(|c: char| c.is_ascii_alphanumeric())
+                || !container
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+            {
+                bail!(
+                    "docker.containers: '{container}' is not a container name \
+                     (letters, digits and _ . -, starting with a letter or digit)"
+                );
+            }
+            if self.docker.containers[..i].contains(container) {
+                bail!("docker.containers: duplicate container '{container}'");
+            }
+        }
+
+        for (i, device) in self.usb_devices.iter().enumerate() {
+            if device.name.trim().is_empty() {
+                bail!("usb_devices: device name cannot be empty");
+            }
+            if parse_usb_id(&device.vid).is_none() {
+                bail!(
+                    "usb_devices '{}': vid must be a hex ID like \"04e8\"",
+                    device.name
+                );
+            }
+            if device
+                .pid
+                .as_deref()
+                .is_some_and(|p| parse_usb_id(p).is_none())
+            {
+                bail!(
+                    "usb_devices '{}': pid must be a hex ID like \"6860\"",
+                    device.name
+                );
+            }
+            if self.usb_devices[..i]
+                .iter()
+                .any(|d| d.name.eq_ignore_ascii_case(&device.name))
+            {
+                bail!("usb_devices: duplicate name '{}'", device.name);
+            }
+        }
+
+        for (i, plugin) in self.plugins.iter().enumerate() {
+            if plugin.name.is_empty()
+                || plugin.name.contains(char::is_whitespace)
+                || plugin.name.contains(['_', '/', '+', '#'])
+            {
+                bail!(
+                    "plugins: name '{}' must be non-empty, without whitespace or '_', '/', '+', '#'",
+                    plugin.name
+                );
+            }
+            if plugin
+                .command
+                .first()
+                .is_none_or(|program| program.trim().is_empty())
+            {
+                bail!(
+                    "plugins '{}': command needs a program (first element)",
+                    plugin.name
+                );
+            }
+            if self.plugins[..i].iter().any(|p| p.name == plugin.name) {
+                bail!("plugins: duplicate name '{}'", plugin.name);
+            }
+        }
+
+        // A blank pattern would match every window / Store app.
+        for (pattern, game) in &self.games {
+            if game.window_title().is_some_and(|t| t.trim().is_empty()) {
+                bail!("games '{pattern}': window_title cannot be empty");
+            }
+            if game.aumid().is_some_and(|a| a.trim().is_empty()) {
+                bail!("games '{pattern}': aumid cannot be empty");
+            }
+        }
+
+        for (pattern, emulator) in &self.emulators {
+            if pattern.trim().is_empty() {
+                bail!("emulators: process pattern cannot be empty");
+            }
+            if emulator.separator.is_empty() {
+                bail!("emulators '{pattern}': separator cannot be empty");
+            }
+            if [&emulator.game_id, &emulator.title_prefix]
+                .into_iter()
+                .any(|v| v.as_deref().is_some_and(|v| v.trim().is_empty()))
+            {
+                bail!("emulators '{pattern}': game_id and title_prefix cannot be empty");
+            }
+        }
+
+        for (id, entry) in &self.entity_overrides {
+            if id.trim().is_empty() {
+                bail!("entity_overrides: entity ID cannot be empty");
+            }
+            if entry.name.as_deref().is_some_and(|n| n.trim().is_empty()) {
+                bail!("entity_overrides '{id}': name cannot be empty");
+            }
+            // HA rejects the whole config over a malformed icon.
+            if entry
+                .icon
+                .as_deref()
+                .is_some_and(|icon| !icon.contains(':') || icon.contains(char::is_whitespace))
+            {
+                bail!("entity_overrides '{id}': icon must look like \"mdi:chip\"");
+            }
+        }
+
+        for (id, deadband) in &self.deadbands {
+            if id.trim().is_empty() {
+                bail!("deadbands: sensor ID cannot be empty");
+            }
+            if !(deadband.min_change.is_finite() && deadband.min_change >= 0.0) {
+                bail!("deadbands '{id}': min_change must be a number >= 0");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate a process watchdog rule
+    fn validate_process_alert(rule: &ProcessAlertRule) -> Result<()> {
+        if rule.process.trim().is_empty() {
+            bail!("process_alerts: 'process' cannot be empty");
+        }
+        if let Some(cpu) = rule.cpu_percent
+            && !(cpu > 0.0 && cpu <= 100.0)
+        {
+            bail!(
+                "process_alerts '{}': cpu_percent must be between 0 and 100",
+                rule.process
+            );
+        }
+        if rule.memory_mb == Some(0) {
+            bail!(
+                "process_alerts '{}': memory_mb must be greater than 0",
+                rule.process
+            );
+        }
+        Ok(())
+    }
+
+    /// Validate a local automation rule
+    fn validate_automation(rule: &AutomationRule) -> Result<()> {
+        if rule.command.trim().is_empty() {
+            bail!("automations '{}': 'command' cannot be empty", rule.name);
+        }
+        if rule.trigger == AutomationTrigger::Idle && rule.idle_minutes.unwrap_or(0) == 0 {
+            bail!(
+                "automations '{}': the idle trigger needs idle_minutes greater than 0",
+                rule.name
+            );
+        }
+        Ok(())
+    }
+
+    /// Validate a Wake-on-LAN relay target
+    fn validate_wol_target(target: &WolTarget, device_name: &str) -> Result<()> {
+        if target.device_name.trim().is_empty() || target.device_name.contains(['/', '+', '#']) {
+            bail!("wol_relay: invalid device_name '{}'", target.device_name);
+        }
+        if target.device_name == device_name {
+            bail!("wol_relay: '{device_name}' can't wake itself");
+        }
+        crate::wol::parse_mac(&target.mac)
+            .with_context(|| format!("wol_relay '{}'", target.device_name))?;
         Ok(())
     }
 
@@ -1107,15 +2848,70 @@ impl Config {
                         sensor.name
                     );
                 }
-                if sensor.registry_value.is_none()
-                    || sensor
-                        .registry_value
-                        .as_ref()
-                        .map(|s| s.is_empty())
-                        .unwrap_or(true)
-                {
+                if sensor.registry_value.is_none()
+                    || sensor
+                        .registry_value
+                        .as_ref()
+                        .map(|s| s.is_empty())
+                        .unwrap_or(true)
+                {
+                    bail!(
+                        "Custom sensor '{}' (registry) requires 'registry_value' field",
+                        sensor.name
+                    );
+                }
+            }
+            CustomSensorType::LogTail => {
+                if sensor.file_path.as_ref().is_none_or(|s| s.is_empty()) {
+                    bail!(
+                        "Custom sensor '{}' (log_tail) requires 'file_path' field",
+                        sensor.name
+                    );
+                }
+                let Some(pattern) = sensor.pattern.as_ref().filter(|s| !s.is_empty()) else {
+                    bail!(
+                        "Custom sensor '{}' (log_tail) requires 'pattern' field",
+                        sensor.name
+                    );
+                };
+                if let Err(e) = regex::Regex::new(pattern) {
+                    bail!(
+                        "Custom sensor '{}' (log_tail) has an invalid pattern: {}",
+                        sensor.name,
+                        e
+                    );
+                }
+            }
+            CustomSensorType::Folder => {
+                if sensor.folder.as_ref().is_none_or(|s| s.is_empty()) {
+                    bail!(
+                        "Custom sensor '{}' (folder) requires 'folder' field",
+                        sensor.name
+                    );
+                }
+            }
+            CustomSensorType::Ping => {
+                if sensor.host.as_ref().is_none_or(|s| s.trim().is_empty()) {
+                    bail!(
+                        "Custom sensor '{}' (ping) requires 'host' field",
+                        sensor.name
+                    );
+                }
+                if let Some(ms) = sensor.timeout_ms
+                    && !(1..=10_000).contains(&ms)
+                {
+                    bail!(
+                        "Custom sensor '{}' (ping) timeout_ms must be 1-10000, got {}",
+                        sensor.name,
+                        ms
+                    );
+                }
+            }
+            CustomSensorType::Service => {
+                if !sensor.service.as_deref().is_some_and(valid_service_name) {
                     bail!(
-                        "Custom sensor '{}' (registry) requires 'registry_value' field",
+                        "Custom sensor '{}' (service) requires a 'service' name \
+                         (letters, digits, spaces and _ - . @ :)",
                         sensor.name
                     );
                 }
@@ -1186,6 +2982,48 @@ impl Config {
                     );
                 }
             }
+            CustomCommandType::ServiceStart
+            | CustomCommandType::ServiceStop
+            | CustomCommandType::ServiceRestart => {
+                if !cmd.service.as_deref().is_some_and(valid_service_name) {
+                    bail!(
+                        "Custom command '{}' ({:?}) requires a 'service' name \
+                         (letters, digits, spaces and _ - . @ :)",
+                        cmd.name,
+                        cmd.command_type
+                    );
+                }
+                // Starting and stopping services is an administrator's job.
+                if !cmd.admin {
+                    bail!(
+                        "Custom command '{}': service commands need admin=true \
+                         (and custom_command_privileges_allowed=true)",
+                        cmd.name
+                    );
+                }
+            }
+        }
+
+        if let Some(sandbox) = &cmd.sandbox {
+            // The elevated process is started by UAC, outside our reach.
+            if cmd.admin {
+                bail!(
+                    "Custom command '{}': sandbox can't be combined with admin=true",
+                    cmd.name
+                );
+            }
+            if sandbox.cpu_percent.is_some_and(|p| p == 0 || p > 100) {
+                bail!(
+                    "Custom command '{}': sandbox.cpu_percent must be 1-100",
+                    cmd.name
+                );
+            }
+            if sandbox.memory_mb == Some(0) || sandbox.timeout_secs == Some(0) {
+                bail!(
+                    "Custom command '{}': sandbox.memory_mb and sandbox.timeout_secs must be > 0",
+                    cmd.name
+                );
+            }
         }
 
         Ok(())
@@ -1308,7 +3146,7 @@ pub async fn watch_config(state: Arc<AppState>) {
 }
 
 /// Reload hot-reloadable config fields (games, intervals, commands, sensors, security flags)
-async fn reload_hot_config(state: &AppState) {
+pub async fn reload_hot_config(state: &AppState) {
     // Config::load() does synchronous file I/O - run on the blocking pool to
     // avoid stalling the single-threaded tokio runtime.
     let new_config = match tokio::task::spawn_blocking(Config::load).await {
@@ -1327,6 +3165,7 @@ async fn reload_hot_config(state: &AppState) {
         let mut config = state.config.write().await;
         let old_count = config.games.len();
         config.games = new_config.games;
+        config.emulators = new_config.emulators;
 
         // Reload intervals (sensors pick up changes via config_generation)
         config.intervals = new_config.intervals;
@@ -1341,6 +3180,13 @@ async fn reload_hot_config(state: &AppState) {
         // Discord keybind
         config.discord_keybind = new_config.discord_keybind;
 
+        // Updater re-reads these before each check.
+        config.update_channel = new_config.update_channel;
+        config.auto_update = new_config.auto_update;
+
+        // Read per notification.
+        config.notifications = new_config.notifications;
+
         // Built-in feature enable flags. Previously these were NOT hot-reloaded,
         // so disabling a feature in the UI didn't stick (a reconnect re-registered
         // it from the stale in-memory flags). Applying them here + the discovery
@@ -1369,8 +3215,20 @@ async fn reload_hot_config(state: &AppState) {
         config.custom_sensors_enabled = new_config.custom_sensors_enabled;
         config.custom_commands_enabled = new_config.custom_commands_enabled;
         config.custom_command_privileges_allowed = new_config.custom_command_privileges_allowed;
+        // The supervisor starts/stops the scripting task on config_generation.
+        config.scripts_enabled = new_config.scripts_enabled;
         config.custom_sensors = new_config.custom_sensors;
         config.custom_commands = new_config.custom_commands;
+        // Watchdog re-reads its rules on config_generation.
+        config.process_alerts = new_config.process_alerts;
+        // Applied by the discovery re-register below and the sensor task.
+        config.process_sensors = new_config.process_sensors;
+        // Automations re-read their rules on config_generation.
+        config.automations = new_config.automations;
+        // Applied by the discovery re-register below.
+        config.entity_overrides = new_config.entity_overrides;
+        // Sensors re-read these on config_generation.
+        config.deadbands = new_config.deadbands;
 
         let new_game_count = config.games.len();
 
@@ -1378,6 +3236,7 @@ async fn reload_hot_config(state: &AppState) {
         let new_sensors_enabled = config.custom_sensors_enabled;
         let new_commands_enabled = config.custom_commands_enabled;
         let new_privileges_allowed = config.custom_command_privileges_allowed;
+        let heartbeat = config.intervals.heartbeat;
 
         // Snapshot the new custom entities (to re-register) and which were removed
         // (to tear down), while still holding the lock. If a category is now
@@ -1417,7 +3276,10 @@ async fn reload_hot_config(state: &AppState) {
             .clear_custom_entities(&removed_sensors, &removed_commands)
             .await;
         if new_sensors_enabled {
-            state.mqtt.register_custom_sensors(&new_sensors).await;
+            state
+                .mqtt
+                .register_custom_sensors(&new_sensors, heartbeat)
+                .await;
         }
         if new_commands_enabled {
             state.mqtt.register_custom_commands(&new_commands).await;
@@ -1428,6 +3290,11 @@ async fn reload_hot_config(state: &AppState) {
         // (not only at restart), mirroring what the reconnect handler does.
         {
             let config = state.config.read().await;
+            crate::crash::set_context(&config);
+            crate::activity::configure(&config);
+            crate::focus::configure(&config);
+            crate::i18n::set_language(&config.language);
+            crate::notification::set_app_name(&config.notifications.app_name).await;
             state.mqtt.register_discovery(&config).await;
             state.mqtt.clear_disabled_entities(&config).await;
         }
@@ -1501,18 +3368,41 @@ mod tests {
     fn minimal_config() -> Config {
         Config {
             device_name: "test-pc".to_string(),
+            suggested_area: None,
+            group: None,
+            language: default_language(),
             mqtt: MqttConfig {
                 broker: "tcp://localhost:1883".to_string(),
                 user: String::new(),
                 pass: String::new(),
                 client_id: None,
+                protocol_version: 4,
+                session_expiry_secs: 3600,
+                availability_expiry_secs: 0,
+                keep_alive_secs: 30,
+                connect_timeout_secs: 5,
+                tcp_nodelay: false,
+                bundled_state: false,
             },
+            ha_api: HaApiConfig::default(),
+            http_api: HttpApiConfig::default(),
+            openrgb: OpenRgbConfig::default(),
+            command_queue: CommandQueueConfig::default(),
+            notifications: NotificationConfig::default(),
+            idle: IdleConfig::default(),
+            shutdown_guard: ShutdownGuardConfig::default(),
+            game_limits: GameLimitsConfig::default(),
+            thermal_guard: ThermalGuardConfig::default(),
+            focus: FocusConfig::default(),
+            window_history_size: default_window_history_size(),
             intervals: IntervalConfig::default(),
             features: FeatureConfig::default(),
             games: HashMap::new(),
+            emulators: HashMap::new(),
             custom_sensors_enabled: false,
             custom_commands_enabled: false,
             custom_command_privileges_allowed: false,
+            scripts_enabled: false,
             allow_raw_commands: false,
             allow_global_launch: true,
             allow_global_close: false,
@@ -1520,7 +3410,21 @@ mod tests {
             discord_keybind: None,
             custom_sensors: vec![],
             custom_commands: vec![],
+            process_alerts: vec![],
+            process_sensors: vec![],
+            automations: vec![],
+            wol_relay: vec![],
+            display_modes: vec![],
+            speedtest_command: vec![],
+            printers: vec![],
+            usb_devices: vec![],
+            docker: DockerConfig::default(),
+            plugins: vec![],
+            entity_overrides: HashMap::new(),
+            deadbands: HashMap::new(),
             update_channel: default_update_channel(),
+            auto_update: true,
+            crash_notify: false,
             disk_sensor_paths: Vec::new(),
         }
     }
@@ -1607,6 +3511,8 @@ mod tests {
             launch_command: None,
             auto_discovered: true,
             exposed: true,
+            window_title: None,
+            aumid: None,
         };
         assert_eq!(config.game_id(), "counter_strike_2");
         assert_eq!(config.app_id(), Some(730));
@@ -1627,6 +3533,8 @@ mod tests {
             launch_command: None,
             auto_discovered: false,
             exposed: true,
+            window_title: None,
+            aumid: None,
         };
         assert_eq!(config.display_name(), "Counter-Strike 2");
     }
@@ -1667,6 +3575,8 @@ mod tests {
             launch_command: None,
             auto_discovered: false,
             exposed: true,
+            window_title: None,
+            aumid: None,
         };
         assert_eq!(config.launch_command(), Some("steam:730".into()));
     }
@@ -1680,6 +3590,8 @@ mod tests {
             launch_command: Some("lnk:C:\\Users\\danke\\Desktop\\Fortnite.lnk".into()),
             auto_discovered: false,
             exposed: true,
+            window_title: None,
+            aumid: None,
         };
         assert_eq!(
             config.launch_command(),
@@ -1702,6 +3614,8 @@ mod tests {
             launch_command: None,
             auto_discovered: false,
             exposed: true,
+            window_title: None,
+            aumid: None,
         };
         assert_eq!(config.launch_command(), None);
     }
@@ -1788,6 +3702,463 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_validate_ipv6_broker() {
+        let mut config = minimal_config();
+        config.mqtt.broker = "tcp://[fe80::1]:1883".to_string();
+        assert!(config.validate().is_ok());
+        config.mqtt.broker = "tcp://[fe80::1:1883".to_string();
+        assert!(config.validate().is_err());
+        config.mqtt.broker = "ssl://broker.local:8883/mqtt".to_string();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_game_limits() {
+        let mut config = minimal_config();
+        config.features.running_game = true;
+        config.game_limits.enabled = true;
+        assert!(config.validate().is_err());
+        config.game_limits.games.insert("cs2".to_string(), 60);
+        assert!(config.validate().is_ok());
+        config.features.running_game = false;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_thermal_guard() {
+        let mut config = minimal_config();
+        config.features.hwinfo_sensor = true;
+        config.thermal_guard.enabled = true;
+        assert!(config.validate().is_err());
+        config.thermal_guard.gpu_max_c = Some(90);
+        assert!(config.validate().is_ok());
+        config.thermal_guard.cpu_max_c = Some(150);
+        assert!(config.validate().is_err());
+        config.thermal_guard.cpu_max_c = Some(95);
+        config.thermal_guard.action = ThermalAction::Shutdown;
+        config.features.cmd_shutdown = false;
+        assert!(config.validate().is_err());
+        config.features.cmd_shutdown = true;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_http_api() {
+        let mut config = minimal_config();
+        config.http_api.enabled = true;
+        assert!(config.validate().is_ok());
+
+        config.http_api.bind = "localhost".to_string();
+        assert!(config.validate().is_err());
+
+        // LAN exposure needs a token; loopback doesn't.
+        config.http_api.bind = "0.0.0.0:8765".to_string();
+        assert!(config.validate().is_err());
+        config.http_api.token = "secret".to_string();
+        assert!(config.validate().is_ok());
+        config.http_api.bind = "[::1]:8765".to_string();
+        config.http_api.token = String::new();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_mqtt_protocol_version() {
+        let mqtt: MqttConfig =
+            serde_json::from_str(r#"{"broker": "tcp://localhost:1883"}"#).unwrap();
+        assert_eq!(mqtt.protocol_version, 4);
+        assert!(!mqtt.is_v5());
+        assert_eq!(mqtt.session_expiry_secs, 3600);
+        assert_eq!(mqtt.availability_expiry_secs, 0);
+        assert_eq!(mqtt.keep_alive_secs, 30);
+        assert_eq!(mqtt.connect_timeout_secs, 5);
+
+        let mut config = minimal_config();
+        config.mqtt.protocol_version = 5;
+        assert!(config.validate().is_ok());
+        config.mqtt.protocol_version = 3;
+        assert!(config.validate().is_err());
+        config.mqtt.protocol_version = 4;
+        config.mqtt.keep_alive_secs = 0;
+        assert!(config.validate().is_err());
+        config.mqtt.keep_alive_secs = 300;
+        config.mqtt.connect_timeout_secs = 0;
+        assert!(config.validate().is_err());
+    }
+
+    // ===== Process alert validation =====
+
+    #[test]
+    fn test_process_alert_rule_defaults_and_validation() {
+        let rule: ProcessAlertRule = serde_json::from_str(r#"{"process": "xmrig.exe"}"#).unwrap();
+        assert!(rule.on_start && rule.on_stop);
+        assert!(rule.cpu_percent.is_none() && rule.memory_mb.is_none());
+
+        let mut config = minimal_config();
+        config.process_alerts = vec![rule.clone()];
+        assert!(config.validate().is_ok());
+
+        config.process_alerts = vec![ProcessAlertRule {
+            process: "  ".to_string(),
+            ..rule.clone()
+        }];
+        assert!(config.validate().is_err());
+
+        config.process_alerts = vec![ProcessAlertRule {
+            cpu_percent: Some(150.0),
+            ..rule.clone()
+        }];
+        assert!(config.validate().is_err());
+
+        config.process_alerts = vec![ProcessAlertRule {
+            memory_mb: Some(0),
+            ..rule
+        }];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_automation_rule_parsing_and_validation() {
+        let rule: AutomationRule = serde_json::from_str(
+            r#"{"trigger": "idle", "idle_minutes": 30, "unless_gaming": true, "command": "MonitorOff"}"#,
+        )
+        .unwrap();
+        assert_eq!(rule.trigger, AutomationTrigger::Idle);
+        assert!(rule.unless_gaming && rule.payload.is_empty());
+
+        let mut config = minimal_config();
+        config.automations = vec![rule.clone()];
+        assert!(config.validate().is_ok());
+
+        config.automations = vec![AutomationRule {
+            idle_minutes: None,
+            ..rule.clone()
+        }];
+        assert!(config.validate().is_err());
+
+        config.automations = vec![AutomationRule {
+            command: " ".to_string(),
+            ..rule.clone()
+        }];
+        assert!(config.validate().is_err());
+
+        // Only idle needs idle_minutes.
+        config.automations = vec![AutomationRule {
+            trigger: AutomationTrigger::ResumedFromSleep,
+            idle_minutes: None,
+            ..rule
+        }];
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_wol_target_defaults_and_validation() {
+        let target: WolTarget =
+            serde_json::from_str(r#"{"device_name": "gaming-rig", "mac": "AA:BB:CC:DD:EE:FF"}"#)
+                .unwrap();
+        assert_eq!(target.broadcast, "255.255.255.255");
+        assert_eq!(target.port, 9);
+
+        let mut config = minimal_config();
+        config.wol_relay = vec![target.clone()];
+        assert!(config.validate().is_ok());
+
+        config.wol_relay = vec![WolTarget {
+            mac: "not-a-mac".to_string(),
+            ..target.clone()
+        }];
+        assert!(config.validate().is_err());
+
+        config.wol_relay = vec![WolTarget {
+            device_name: config.device_name.clone(),
+            ..target
+        }];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_display_modes() {
+        let mode: DisplayMode =
+            serde_json::from_str(r#"{"name": "4K60", "width": 3840, "height": 2160}"#).unwrap();
+        assert_eq!(mode.refresh_rate, None);
+
+        let mut config = minimal_config();
+        config.display_modes = vec![
+            mode.clone(),
+            DisplayMode {
+                name: "1440p144".to_string(),
+                width: 2560,
+                height: 1440,
+                refresh_rate: Some(144),
+            },
+        ];
+        assert!(config.validate().is_ok());
+
+        config.display_modes.push(DisplayMode {
+            name: "4k60".to_string(),
+            ..mode.clone()
+        });
+        assert!(config.validate().is_err());
+
+        config.display_modes = vec![DisplayMode {
+            refresh_rate: Some(0),
+            ..mode
+        }];
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_usb_devices() {
+        let phone: UsbDevice = serde_json::from_str(r#"{"name": "Phone", "vid": "04e8"}"#).unwrap();
+        assert!(phone.matches(0x04e8, 0x6860));
+        assert!(!phone.matches(0x18d1, 0x6860));
+        let pinned = UsbDevice {
+            pid: Some("0x6860".to_string()),
+            ..phone.clone()
+        };
+        assert!(pinned.matches(0x04E8, 0x6860));
+        assert!(!pinned.matches(0x04e8, 0x685d));
+
+        let mut config = minimal_config();
+        config.usb_devices = vec![phone.clone(), pinned.clone()];
+        // Same name twice.
+        assert!(config.validate().is_err());
+        config.usb_devices[1].name = "Phone (MTP)".to_string();
+        assert!(config.validate().is_ok());
+
+        for (vid, pid) in [
+            ("", None),
+            ("12345", None),
+            ("zz", None),
+            ("04e8", Some("g")),
+        ] {
+            config.usb_devices = vec![UsbDevice {
+                name: "Phone".to_string(),
+                vid: vid.to_string(),
+                pid: pid.map(str::to_string),
+            }];
+            assert!(config.validate().is_err(), "{vid} {pid:?}");
+        }
+    }
+
+    #[test]
+    fn test_process_sensor_matches() {
+        let obs: ProcessSensor =
+            serde_json::from_str(r#"{"name": "OBS", "processes": ["obs64.exe", "obs32"]}"#)
+                .unwrap();
+        assert!(obs.matches("obs64.exe"));
+        assert!(obs.matches("OBS64.EXE"));
+        assert!(obs.matches("obs32.exe"));
+        assert!(obs.matches("obs32"));
+        assert!(!obs.matches("obs64-helper.exe"));
+        assert!(!obs.matches("obs.exe"));
+
+        let editor = ProcessSensor {
+            name: "Video editing".to_string(),
+            processes: vec!["Adobe Premiere*".to_string(), "*resolve*".to_string()],
+            icon: None,
+        };
+        assert!(editor.matches("Adobe Premiere Pro.exe"));
+        assert!(editor.matches("Resolve.exe"));
+        assert!(editor.matches("DaVinci Resolve Helper.exe"));
+        assert!(!editor.matches("Adobe Photoshop.exe"));
+
+        assert!(wildcard_match("a*b*c", "abc"));
+        assert!(wildcard_match("a*b*c", "axxbyyc"));
+        assert!(!wildcard_match("a*b*c", "axxcyyb"));
+        assert!(!wildcard_match("ab*ba", "aba"));
+        assert!(wildcard_match("*", ""));
+    }
+
+    #[test]
+    fn test_validate_process_sensors() {
+        let sensor = |name: &str, processes: &[&str]| ProcessSensor {
+            name: name.to_string(),
+            processes: processes.iter().map(|p| p.to_string()).collect(),
+            icon: None,
+        };
+        let mut config = minimal_config();
+        config.process_sensors = vec![
+            sensor("OBS", &["obs64.exe"]),
+            sensor("Discord", &["Discord"]),
+        ];
+        assert!(config.validate().is_ok());
+        // Same slug twice.
+        config.process_sensors.push(sensor("obs!", &["obs32.exe"]));
+        assert!(config.validate().is_err());
+
+        for bad in [
+            sensor("OBS", &[]),
+            sensor("OBS", &[" "]),
+            sensor("!!", &["obs64.exe"]),
+        ] {
+            config.process_sensors = vec![bad.clone()];
+            assert!(config.validate().is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn test_validate_plugins() {
+        let plugin = |name: &str, command: &[&str]| PluginConfig {
+            name: name.to_string(),
+            command: command.iter().map(|s| s.to_string()).collect(),
+        };
+        let mut config = minimal_config();
+        config.plugins = vec![plugin("weather", &["/opt/weather-plugin", "--metric"])];
+        assert!(config.validate().is_ok());
+
+        config.plugins.push(plugin("weather", &["/opt/other"]));
+        assert!(config.validate().is_err());
+
+        for bad in [
+            plugin("", &["/opt/p"]),
+            plugin("my plugin", &["/opt/p"]),
+            plugin("a/b", &["/opt/p"]),
+            // plugin_my_x_y could be my_x's y or my's x_y
+            plugin("my_x", &["/opt/p"]),
+            plugin("p", &[]),
+            plugin("p", &[" "]),
+        ] {
+            config.plugins = vec![bad];
+            assert!(config.validate().is_err(), "{:?}", config.plugins);
+        }
+    }
+
+    #[test]
+    fn test_validate_game_window_title_and_aumid() {
+        let mut config = minimal_config();
+        config.games = serde_json::from_str(
+            r#"{"snes_mario": {"game_id": "super_mario_world", "window_title": "Super Mario World"},
+                "forza": {"game_id": "forza_horizon_5", "aumid": "Microsoft.624F8B84B80_8wekyb3d8bbwe!"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.games["snes_mario"].window_title(),
+            Some("Super Mario World")
+        );
+        assert!(config.games["forza"].window_title().is_none());
+        assert!(config.validate().is_ok());
+
+        for bad in [
+            r#"{"game_id": "x", "window_title": " "}"#,
+            r#"{"game_id": "x", "aumid": ""}"#,
+        ] {
+            config
+                .games
+                .insert("x".to_string(), serde_json::from_str(bad).unwrap());
+            assert!(config.validate().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_emulator_game_in_title() {
+        let emulators: HashMap<String, EmulatorConfig> = serde_json::from_str(
+            r#"{"retroarch": {},
+                "dolphin": {"separator": " | "},
+                "yuzu": {"game_id": "switch", "separator": " | ", "part": 1}}"#,
+        )
+        .unwrap();
+        let retroarch = &emulators["retroarch"];
+        assert_eq!(retroarch.game_id("RetroArch"), "retroarch");
+        assert_eq!(
+            retroarch
+                .game_in_title("RetroArch Snes9x - Super Mario World (USA)")
+                .as_deref(),
+            Some("Super Mario World (USA)")
+        );
+        // Just the emulator, nothing loaded.
+        assert!(retroarch.game_in_title("RetroArch").is_none());
+        assert!(retroarch.game_in_title("RetroArch - ").is_none());
+
+        assert_eq!(
+            emulators["dolphin"]
+                .game_in_title("Dolphin 5.0 | JIT64 DC | HLE | Super Mario Sunshine (GMSE01)")
+                .as_deref(),
+            Some("Super Mario Sunshine (GMSE01)")
+        );
+        let yuzu = &emulators["yuzu"];
+        assert_eq!(yuzu.game_id("yuzu"), "switch");
+        assert_eq!(
+            yuzu.game_in_title("yuzu 1734 | Super Mario Odyssey (64-bit) | 1.3.0")
+                .as_deref(),
+            Some("Super Mario Odyssey (64-bit)")
+        );
+        // Out of range either way.
+        let far = EmulatorConfig {
+            part: -5,
+            ..yuzu.clone()
+        };
+        assert!(far.game_in_title("a | b").is_none());
+        let far = EmulatorConfig { part: 5, ..far };
+        assert!(far.game_in_title("a | b").is_none());
+    }
+
+    #[test]
+    fn test_validate_emulators() {
+        let mut config = minimal_config();
+        config.emulators = serde_json::from_str(r#"{"retroarch": {}}"#).unwrap();
+        assert!(config.validate().is_ok());
+        for bad in [
+            r#"{"": {}}"#,
+            r#"{"dolphin": {"separator": ""}}"#,
+            r#"{"dolphin": {"game_id": " "}}"#,
+            r#"{"dolphin": {"title_prefix": ""}}"#,
+        ] {
+            config.emulators = serde_json::from_str(bad).unwrap();
+            assert!(config.validate().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_validate_entity_overrides() {
+        let mut config = minimal_config();
+        config.entity_overrides = serde_json::from_str(
+            r#"{"cpu_usage": {"name": "Processor", "icon": "mdi:chip"}, "sleep": {"enabled": false}}"#,
+        )
+        .unwrap();
+        assert!(config.entity_overrides["sleep"].name.is_none());
+        assert!(config.entity_overrides["cpu_usage"].enabled);
+        assert!(!config.entity_overrides["sleep"].enabled);
+        assert!(config.validate().is_ok());
+
+        for (name, icon) in [
+            (Some(" "), None),
+            (None, Some("chip")),
+            (None, Some("mdi: chip")),
+        ] {
+            config.entity_overrides.insert(
+                "cpu_usage".to_string(),
+                EntityOverride {
+                    name: name.map(str::to_string),
+                    icon: icon.map(str::to_string),
+                    enabled: true,
+                },
+            );
+            assert!(config.validate().is_err(), "{name:?} {icon:?}");
+        }
+    }
+
+    #[test]
+    fn test_validate_deadbands() {
+        let mut config = minimal_config();
+        config.deadbands =
+            serde_json::from_str(r#"{"cpu_usage": {"min_change": 1.0, "max_age_secs": 300}}"#)
+                .unwrap();
+        assert_eq!(config.deadbands["cpu_usage"].max_age_secs, 300);
+        assert!(config.validate().is_ok());
+
+        for min_change in [-1.0, f64::NAN] {
+            config.deadbands.insert(
+                "cpu_usage".to_string(),
+                Deadband {
+                    min_change,
+                    max_age_secs: 0,
+                },
+            );
+            assert!(config.validate().is_err(), "{min_change}");
+        }
+    }
+
     // ===== Custom sensor validation =====
 
     #[test]
@@ -1803,6 +4174,12 @@ mod tests {
             file_path: None,
             registry_key: None,
             registry_value: None,
+            pattern: None,
+            folder: None,
+            metric: FolderMetric::Count,
+            host: None,
+            timeout_ms: None,
+            service: None,
         };
         assert!(Config::validate_custom_sensor(&sensor).is_err());
     }
@@ -1820,6 +4197,12 @@ mod tests {
             file_path: None,
             registry_key: None,
             registry_value: None,
+            pattern: None,
+            folder: None,
+            metric: FolderMetric::Count,
+            host: None,
+            timeout_ms: None,
+            service: None,
         };
         assert!(Config::validate_custom_sensor(&sensor).is_err());
     }
@@ -1837,6 +4220,12 @@ mod tests {
             file_path: None,
             registry_key: None,
             registry_value: None,
+            pattern: None,
+            folder: None,
+            metric: FolderMetric::Count,
+            host: None,
+            timeout_ms: None,
+            service: None,
         };
         assert!(Config::validate_custom_sensor(&sensor).is_err());
     }
@@ -1854,6 +4243,12 @@ mod tests {
             file_path: None,
             registry_key: None,
             registry_value: None,
+            pattern: None,
+            folder: None,
+            metric: FolderMetric::Count,
+            host: None,
+            timeout_ms: None,
+            service: None,
         };
         assert!(Config::validate_custom_sensor(&sensor).is_ok());
     }
@@ -1871,8 +4266,68 @@ mod tests {
             file_path: None,
             registry_key: Some("HKLM\\SOFTWARE\\Test".to_string()),
             registry_value: None, // Missing!
+            pattern: None,
+            folder: None,
+            metric: FolderMetric::Count,
+            host: None,
+            timeout_ms: None,
+            service: None,
+        };
+        assert!(Config::validate_custom_sensor(&sensor).is_err());
+    }
+
+    #[test]
+    fn test_validate_custom_sensor_log_tail_pattern() {
+        let mut sensor = CustomSensor {
+            name: "backup_status".to_string(),
+            sensor_type: CustomSensorType::LogTail,
+            interval_seconds: 30,
+            unit: None,
+            icon: None,
+            script: None,
+            process: None,
+            file_path: Some("/var/log/backup.log".to_string()),
+            registry_key: None,
+            registry_value: None,
+            pattern: None,
+            folder: None,
+            metric: FolderMetric::Count,
+            host: None,
+            timeout_ms: None,
+            service: None,
+        };
+        assert!(Config::validate_custom_sensor(&sensor).is_err());
+        sensor.pattern = Some("backup (done|failed".to_string());
+        assert!(Config::validate_custom_sensor(&sensor).is_err());
+        sensor.pattern = Some("backup (done|failed)".to_string());
+        assert!(Config::validate_custom_sensor(&sensor).is_ok());
+    }
+
+    #[test]
+    fn test_validate_custom_sensor_ping() {
+        let mut sensor = CustomSensor {
+            name: "nas_ping".to_string(),
+            sensor_type: CustomSensorType::Ping,
+            interval_seconds: 30,
+            unit: None,
+            icon: None,
+            script: None,
+            process: None,
+            file_path: None,
+            registry_key: None,
+            registry_value: None,
+            pattern: None,
+            folder: None,
+            metric: FolderMetric::Count,
+            host: None,
+            timeout_ms: None,
+            service: None,
         };
         assert!(Config::validate_custom_sensor(&sensor).is_err());
+        sensor.host = Some("10.8.0.1".to_string());
+        assert!(Config::validate_custom_sensor(&sensor).is_ok());
+        sensor.timeout_ms = Some(0);
+        assert!(Config::validate_custom_sensor(&sensor).is_err());
     }
 
     // ===== Custom command validation =====
@@ -1888,6 +4343,8 @@ mod tests {
             path: None,
             args: None,
             command: None,
+            service: None,
+            sandbox: None,
         };
         // privileges_allowed = false
         assert!(Config::validate_custom_command(&cmd, false).is_err());
@@ -1904,6 +4361,8 @@ mod tests {
             path: None,
             args: None,
             command: None,
+            service: None,
+            sandbox: None,
         };
         // privileges_allowed = true
         assert!(Config::validate_custom_command(&cmd, true).is_ok());
@@ -1920,8 +4379,74 @@ mod tests {
             path: None, // Missing!
             args: None,
             command: None,
+            service: None,
+            sandbox: None,
+        };
+        assert!(Config::validate_custom_command(&cmd, false).is_err());
+    }
+
+    #[test]
+    fn test_validate_custom_command_service() {
+        let mut cmd = CustomCommand {
+            name: "restart_plex".to_string(),
+            command_type: CustomCommandType::ServiceRestart,
+            icon: None,
+            admin: true,
+            script: None,
+            path: None,
+            args: None,
+            command: None,
+            service: Some("Plex Media Server".to_string()),
+            sandbox: None,
         };
+        assert!(Config::validate_custom_command(&cmd, true).is_ok());
+        // admin is required, and so gated by custom_command_privileges_allowed
         assert!(Config::validate_custom_command(&cmd, false).is_err());
+        cmd.admin = false;
+        assert!(Config::validate_custom_command(&cmd, true).is_err());
+
+        cmd.admin = true;
+        cmd.service = Some("plex'; Remove-Item C:\\".to_string());
+        assert!(Config::validate_custom_command(&cmd, true).is_err());
+        cmd.service = None;
+        assert!(Config::validate_custom_command(&cmd, true).is_err());
+        cmd.service = Some("minecraft@survival.service".to_string());
+        assert!(Config::validate_custom_command(&cmd, true).is_ok());
+    }
+
+    #[test]
+    fn test_validate_custom_command_sandbox() {
+        let mut cmd = CustomCommand {
+            name: "backup".to_string(),
+            command_type: CustomCommandType::Shell,
+            icon: None,
+            admin: false,
+            script: None,
+            path: None,
+            args: None,
+            command: Some("backup.bat".to_string()),
+            service: None,
+            sandbox: Some(CommandSandbox {
+                cpu_percent: Some(25),
+                memory_mb: Some(512),
+                timeout_secs: None,
+            }),
+        };
+        assert!(Config::validate_custom_command(&cmd, true).is_ok());
+
+        cmd.sandbox.as_mut().unwrap().cpu_percent = Some(101);
+        assert!(Config::validate_custom_command(&cmd, true).is_err());
+
+        cmd.sandbox = Some(CommandSandbox {
+            timeout_secs: Some(0),
+            ..CommandSandbox::default()
+        });
+        assert!(Config::validate_custom_command(&cmd, true).is_err());
+
+        // An elevated command can't be confined.
+        cmd.sandbox = Some(CommandSandbox::default());
+        cmd.admin = true;
+        assert!(Config::validate_custom_command(&cmd, true).is_err());
     }
 
     // ===== Config helper methods =====
@@ -2191,6 +4716,22 @@ mod tests {
         assert_eq!(config["device_name"], "temp-test-pc");
     }
 
+    #[test]
+    fn test_clear_inline_secret() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("userConfig.json");
+        std::fs::write(
+            &path,
+            r#"{"device_name": "pc", "ha_api": {"url": "http://ha:8123", "token": "eyJ"}}"#,
+        )
+        .unwrap();
+        Config::clear_inline_secret(&path, ("ha_api", "token")).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["ha_api"]["token"], "");
+        assert_eq!(json["ha_api"]["url"], "http://ha:8123");
+    }
+
     #[test]
     fn test_invalid_json_fails() {
         let bad_json = r#"{ "device_name": "test, "mqtt": {} }"#;
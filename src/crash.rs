@@ -0,0 +1,304 @@
+//! Crash reports
+//!
+//! A panic hook (release builds abort on panic, so every panic is a crash) and,
+//! on Windows, an unhandled-exception filter write `last_crash.json` to the log
+//! directory: time, version, what happened, a backtrace and a config summary
+//! (feature flags and settings, never credentials). The SEH filter also writes
+//! `last_crash.dmp`, a minidump for a debugger.
+//!
+//! On the next start the report is loaded and moved aside to
+//! `previous_crash.json` (the dump to `previous_crash.dmp`), so only that
+//! start reports it: `bridge_health` carries its summary as the `last_crash`
+//! attribute, and with `crash_notify` a toast says the agent crashed - so a
+//! crash loop under a service manager doesn't go unnoticed.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+const REPORT_FILE: &str = "last_crash.json";
+/// An announced report, kept for a look afterwards
+const PREVIOUS_REPORT_FILE: &str = "previous_crash.json";
+#[cfg(windows)]
+const DUMP_FILE: &str = "last_crash.dmp";
+#[cfg(windows)]
+const PREVIOUS_DUMP_FILE: &str = "previous_crash.dmp";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CrashReport {
+    /// RFC 3339, UTC
+    pub time: String,
+    pub version: String,
+    /// "panic" or "exception"
+    pub kind: String,
+    pub message: String,
+    #[serde(default)]
+    pub backtrace: String,
+    #[serde(default)]
+    pub config: String,
+}
+
+impl CrashReport {
+    fn new(kind: &str, message: String, backtrace: String) -> Self {
+        Self {
+            time: time::OffsetDateTime::now_utc()
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            kind: kind.to_string(),
+            message,
+            backtrace,
+            config: CONTEXT.lock().map(|c| c.clone()).unwrap_or_default(),
+        }
+    }
+
+    /// The `last_crash` attribute: enough to spot a crash loop, without the
+    /// backtrace (that stays in the file).
+    pub(crate) fn summary(&self) -> serde_json::Value {
+        serde_json::json!({
+            "time": self.time,
+            "version": self.version,
+            "kind": self.kind,
+            "message": self.message,
+        })
+    }
+}
+
+/// Config summary captured into reports; set once the config is loaded.
+static CONTEXT: Mutex<String> = Mutex::new(String::new());
+
+/// The previous run's report, if it crashed; loaded at startup.
+static LAST: OnceLock<Option<CrashReport>> = OnceLock::new();
+
+fn report_path() -> PathBuf {
+    crate::logging::log_dir().join(REPORT_FILE)
+}
+
+fn write_report(report: &CrashReport) {
+    let path = report_path();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(report) {
+        let _ = std::fs::write(path, json);
+    }
+}
+
+/// Install the crash handlers. Call right after logging is up.
+pub(crate) fn install() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let thread = std::thread::current()
+            .name()
+            .unwrap_or("unnamed")
+            .to_string();
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| (*s).to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        let location = info
+            .location()
+            .map(|l| format!(" at {}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+        let message = format!("thread '{thread}' panicked{location}: {message}");
+        log::error!("{message}");
+        write_report(&CrashReport::new(
+            "panic",
+            message,
+            std::backtrace::Backtrace::force_capture().to_string(),
+        ));
+        previous(info);
+    }));
+
+    #[cfg(windows)]
+    unsafe {
+        windows::Win32::System::Diagnostics::Debug::SetUnhandledExceptionFilter(Some(
+            seh::on_exception,
+        ));
+    }
+}
+
+/// Record which features and settings are on, for later reports.
+pub(crate) fn set_context(config: &crate::config::Config) {
+    let summary = config_summary(config);
+    if let Ok(mut context) = CONTEXT.lock() {
+        *context = summary;
+    }
+}
+
+/// Enabled feature flags plus the settings that change behavior. Built from
+/// the serialized config so new flags are covered without touching this.
+fn config_summary(config: &crate::config::Config) -> String {
+    let features = serde_json::to_value(&config.features).unwrap_or_default();
+    let enabled: Vec<&str> = features
+        .as_object()
+        .map(|flags| {
+            flags
+                .iter()
+                .filter(|(_, on)| on.as_bool() == Some(true))
+                .map(|(name, _)| name.as_str())
+                .collect()
+        })
+        .unwrap_or_default();
+    format!(
+        "os={} features=[{}] games={} custom_sensors={} custom_commands={} \
+         ha_api={} http_api={} update_channel={}",
+        std::env::consts::OS,
+        enabled.join(","),
+        config.games.len(),
+        config.custom_sensors.len(),
+        config.custom_commands.len(),
+        config.ha_api.enabled,
+        config.http_api.enabled,
+        config.update_channel,
+    )
+}
+
+/// Load the previous run's report (once per process) and return it.
+pub(crate) fn last() -> Option<&'static CrashReport> {
+    LAST.get_or_init(|| take(&crate::logging::log_dir())).as_ref()
+}
+
+/// The report in `dir`, moved aside so the next start doesn't find it again.
+fn take(dir: &Path) -> Option<CrashReport> {
+    let path = dir.join(REPORT_FILE);
+    let content = std::fs::read_to_string(&path).ok()?;
+    if let Err(e) = std::fs::rename(&path, dir.join(PREVIOUS_REPORT_FILE)) {
+        log::warn!("Failed to move {} aside: {e}", path.display());
+    }
+    #[cfg(windows)]
+    let _ = std::fs::rename(dir.join(DUMP_FILE), dir.join(PREVIOUS_DUMP_FILE));
+    serde_json::from_str(&content).ok()
+}
+
+/// Announce the previous run's crash, with a toast if `notify` is on. Called
+/// once at startup.
+pub(crate) async fn announce(notify: bool) {
+    let Some(report) = last() else {
+        return;
+    };
+    log::warn!(
+        "Previous run crashed at {} (v{}): {}",
+        report.time,
+        report.version,
+        report.message
+    );
+    if !notify {
+        return;
+    }
+    let payload = serde_json::json!({
+        "title": crate::i18n::tr("PC Bridge crashed"),
+        "message": format!("v{} at {}: {}", report.version, report.time, report.message),
+    })
+    .to_string();
+    match tokio::task::spawn_blocking(move || crate::notification::show_toast(&payload, "")).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => log::warn!("Crash notification failed: {e}"),
+        Err(e) => log::warn!("Crash notification task failed: {e}"),
+    }
+}
+
+#[cfg(windows)]
+mod seh {
+    use std::os::windows::io::AsRawHandle;
+
+    use windows::Win32::Foundation::HANDLE;
+    use windows::Win32::System::Diagnostics::Debug::{
+        EXCEPTION_POINTERS, MINIDUMP_EXCEPTION_INFORMATION, MiniDumpNormal, MiniDumpWriteDump,
+    };
+    use windows::Win32::System::Threading::{
+        GetCurrentProcess, GetCurrentProcessId, GetCurrentThreadId,
+    };
+
+    use super::{CrashReport, DUMP_FILE, write_report};
+
+    /// `EXCEPTION_CONTINUE_SEARCH`: let Windows Error Reporting run as usual.
+    const CONTINUE_SEARCH: i32 = 0;
+
+    pub(super) unsafe extern "system" fn on_exception(info: *const EXCEPTION_POINTERS) -> i32 {
+        // Best effort: the process is going down, but the heap is usually
+        // still usable for a small report.
+        let (code, address) = unsafe {
+            info.as_ref()
+                .and_then(|p| p.ExceptionRecord.as_ref())
+                .map_or((0, 0), |r| (r.ExceptionCode.0, r.ExceptionAddress as usize))
+        };
+        write_report(&CrashReport::new(
+            "exception",
+            format!("unhandled exception 0x{code:08X} at 0x{address:X}"),
+            std::backtrace::Backtrace::force_capture().to_string(),
+        ));
+
+        let path = crate::logging::log_dir().join(DUMP_FILE);
+        if let Ok(file) = std::fs::File::create(path) {
+            let exception = MINIDUMP_EXCEPTION_INFORMATION {
+                ThreadId: unsafe { GetCurrentThreadId() },
+                ExceptionPointers: info.cast_mut(),
+                ClientPointers: false.into(),
+            };
+            unsafe {
+                let _ = MiniDumpWriteDump(
+                    GetCurrentProcess(),
+                    GetCurrentProcessId(),
+                    HANDLE(file.as_raw_handle()),
+                    MiniDumpNormal,
+                    Some(&raw const exception),
+                    None,
+                    None,
+                );
+            }
+        }
+        CONTINUE_SEARCH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_summary_lists_enabled_flags_only() {
+        let mut config = crate::config::Config::default();
+        config.features.cpu_sensor = true;
+        config.features.power_plan = true;
+        config.mqtt.pass = "hunter2".to_string();
+        let summary = config_summary(&config);
+        let features = summary
+            .split_once("features=[")
+            .and_then(|(_, rest)| rest.split_once(']'))
+            .map(|(list, _)| list.split(',').collect::<Vec<_>>())
+            .unwrap();
+        assert!(features.contains(&"cpu_sensor") && features.contains(&"power_plan"));
+        assert!(!features.contains(&"gpu_sensor"), "{summary}");
+        assert!(summary.contains("update_channel=stable"));
+        assert!(!summary.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_report_roundtrip() {
+        let report = CrashReport::new("panic", "boom".to_string(), String::new());
+        let json = serde_json::to_string(&report).unwrap();
+        let loaded: CrashReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, report);
+        assert_eq!(loaded.summary()["message"], "boom");
+        assert!(loaded.summary().get("backtrace").is_none());
+    }
+
+    #[test]
+    fn test_report_is_taken_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = CrashReport::new("panic", "boom".to_string(), String::new());
+        std::fs::write(
+            dir.path().join(REPORT_FILE),
+            serde_json::to_string(&report).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(take(dir.path()), Some(report));
+        assert!(dir.path().join(PREVIOUS_REPORT_FILE).is_file());
+        // The start after a clean run has nothing to report.
+        assert_eq!(take(dir.path()), None);
+    }
+}
@@ -1,16 +1,22 @@
 //! Credential encryption using Windows DPAPI.
 //!
-//! On Windows, MQTT passwords are encrypted with `CryptProtectData` (tied to
-//! the current Windows user) and stored in a separate `mqtt_credential` file
-//! alongside `userConfig.json`.  The JSON config never contains the password.
+//! On Windows, secrets (the MQTT password, the Home Assistant API token) are
+//! encrypted with `CryptProtectData` (tied to the current Windows user) and
+//! stored in separate credential files (`MQTT`, `HA_API`) alongside
+//! `userConfig.json`.  The JSON config never contains them.
 //!
-//! On other platforms, passwords are stored as plaintext in the credential
-//! file with restrictive permissions (0600).
+//! On other platforms, secrets are stored as plaintext in the credential
+//! files with restrictive permissions (0600).
 //!
 //! Encrypted values use the format `"DPAPI:<base64>"`.  Plain strings
 //! without the prefix are treated as unencrypted (first-run / manual edit)
 //! and are automatically encrypted on the next save (Windows only).
 
+/// Credential file of the MQTT password (`mqtt.pass`)
+pub const MQTT: &str = "mqtt_credential";
+/// Credential file of the Home Assistant long-lived token (`ha_api.token`)
+pub const HA_API: &str = "ha_api_credential";
+
 /// Prefix that marks an encrypted credential in the credential file.
 #[cfg(windows)]
 const DPAPI_PREFIX: &str = "DPAPI:";
@@ -87,18 +93,19 @@ impl std::error::Error for CredentialDecryptFailed {}
 
 // ── Credential file I/O ─────────────────────────────────────────────────
 
-/// Path to the credential file alongside `userConfig.json`.
-pub fn credential_path() -> anyhow::Result<std::path::PathBuf> {
+/// Path to the credential file `file` (`MQTT`, `HA_API`) alongside
+/// `userConfig.json`.
+pub fn credential_path(file: &str) -> anyhow::Result<std::path::PathBuf> {
     let config_path = crate::config::Config::config_path()?;
     let dir = config_path
         .parent()
         .expect("config path always has a parent");
-    Ok(dir.join("mqtt_credential"))
+    Ok(dir.join(file))
 }
 
-/// Encrypt a plaintext password and write it to the credential file.
-pub fn save_to_file(plaintext: &str) -> anyhow::Result<()> {
-    let path = credential_path()?;
+/// Encrypt a plaintext secret and write it to the credential file `file`.
+pub fn save_to_file(file: &str, plaintext: &str) -> anyhow::Result<()> {
+    let path = credential_path(file)?;
     if plaintext.is_empty() {
         // Remove credential file when password is cleared
         if path.exists() {
@@ -117,9 +124,9 @@ pub fn save_to_file(plaintext: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Read the credential file and decrypt its contents.
-pub fn load_from_file() -> Result<String, DecryptError> {
-    let path = credential_path().map_err(|e| DecryptError {
+/// Read the credential file `file` and decrypt its contents.
+pub fn load_from_file(file: &str) -> Result<String, DecryptError> {
+    let path = credential_path(file).map_err(|e| DecryptError {
         message: e.to_string(),
     })?;
     if !path.exists() {
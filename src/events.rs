@@ -0,0 +1,346 @@
+//! Discrete bridge events for automation triggers
+//!
+//! State sensors make poor triggers: their values are retained, so a restart or
+//! reconnect replays "game running" and re-fires automations. Events go out
+//! non-retained on `pc-bridge/events/{device}` as JSON tagged by `event`, e.g.
+//! `{"event":"game_started","game_id":"cs2","name":"Counter-Strike 2","timestamp":"..."}`.
+
+use serde::Serialize;
+
+/// Idle time (seconds) at which the user counts as away (`user_idle`); the next
+/// input after that is `user_returned`.
+/// Short enough to catch a coffee break, long enough that reading a page
+/// without touching the mouse doesn't fire it.
+pub const AWAY_THRESHOLD_SECS: i64 = 300;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BridgeEvent {
+    GameStarted {
+        game_id: String,
+        name: String,
+    },
+    GameStopped {
+        game_id: String,
+        name: String,
+    },
+    ResumedFromSleep,
+    /// The tray menu's "Send Button Press" item (Windows)
+    ButtonPressed,
+    /// Idle time just reached `AWAY_THRESHOLD_SECS`
+    UserIdle,
+    /// Input after at least `AWAY_THRESHOLD_SECS` idle; `idle_seconds` is how
+    /// long the user was away.
+    UserReturned {
+        idle_seconds: i64,
+    },
+    /// A USB device was plugged in; `vid` / `pid` are 4 hex digits.
+    UsbConnected {
+        vid: String,
+        pid: String,
+        name: String,
+    },
+    UsbDisconnected {
+        vid: String,
+        pid: String,
+        name: String,
+    },
+    /// A `game_limits` limit was reached: a single game's (with `game_id` /
+    /// `name`) or the daily total (without).
+    GameLimitReached {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        game_id: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        limit_minutes: u32,
+        played_minutes: u64,
+        action: crate::config::GameLimitAction,
+    },
+    /// A Remote Desktop client connected to session `session_id`; `user` is
+    /// who's logged on in it and `client` the connecting machine (either empty
+    /// when unknown, e.g. before logon).
+    RemoteConnected {
+        session_id: String,
+        user: String,
+        client: String,
+    },
+    RemoteDisconnected {
+        session_id: String,
+        user: String,
+        client: String,
+    },
+    /// A session took over the physical console: fast user switching, or the
+    /// console coming back after a Remote Desktop session took it.
+    ConsoleConnected {
+        session_id: String,
+        user: String,
+    },
+    ConsoleDisconnected {
+        session_id: String,
+        user: String,
+    },
+    /// `thermal_guard`: `sensor` (`cpu` / `gpu`) stayed above `limit_c` for
+    /// `seconds`; `celsius` is the reading that tripped it.
+    TemperatureCritical {
+        sensor: String,
+        celsius: f64,
+        limit_c: u32,
+        seconds: u32,
+        action: crate::config::ThermalAction,
+    },
+}
+
+impl BridgeEvent {
+    /// JSON payload with an RFC 3339 timestamp added alongside the tag.
+    pub fn to_payload(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(obj) = value.as_object_mut() {
+            let now = time::OffsetDateTime::now_utc()
+                .format(&time::format_description::well_known::Rfc3339)
+                .unwrap_or_default();
+            obj.insert("timestamp".to_string(), now.into());
+        }
+        value
+    }
+}
+
+/// Started/stopped events between two running-game lists of `(game_id, name)`.
+pub fn game_transitions(prev: &[(String, String)], now: &[(String, String)]) -> Vec<BridgeEvent> {
+    let stopped = prev
+        .iter()
+        .filter(|(id, _)| !now.iter().any(|(n, _)| n == id))
+        .map(|(game_id, name)| BridgeEvent::GameStopped {
+            game_id: game_id.clone(),
+            name: name.clone(),
+        });
+    let started = now
+        .iter()
+        .filter(|(id, _)| !prev.iter().any(|(p, _)| p == id))
+        .map(|(game_id, name)| BridgeEvent::GameStarted {
+            game_id: game_id.clone(),
+            name: name.clone(),
+        });
+    stopped.chain(started).collect()
+}
+
+/// Connected/disconnected events between two USB device lists of
+/// `(key, vid, pid, name)`, the key telling apart two devices with the same IDs.
+pub fn usb_transitions(
+    prev: &[(String, u16, u16, String)],
+    now: &[(String, u16, u16, String)],
+) -> Vec<BridgeEvent> {
+    let ids = |vid: &u16, pid: &u16| (format!("{vid:04x}"), format!("{pid:04x}"));
+    let disconnected = prev
+        .iter()
+        .filter(|(key, ..)| !now.iter().any(|(n, ..)| n == key))
+        .map(|(_, vid, pid, name)| {
+            let (vid, pid) = ids(vid, pid);
+            BridgeEvent::UsbDisconnected {
+                vid,
+                pid,
+                name: name.clone(),
+            }
+        });
+    let connected = now
+        .iter()
+        .filter(|(key, ..)| !prev.iter().any(|(p, ..)| p == key))
+        .map(|(_, vid, pid, name)| {
+            let (vid, pid) = ids(vid, pid);
+            BridgeEvent::UsbConnected {
+                vid,
+                pid,
+                name: name.clone(),
+            }
+        });
+    disconnected.chain(connected).collect()
+}
+
+/// Remote Desktop and console events between two session snapshots: remote
+/// sessions as `(session_id, user, client)`, the console's as `(session_id, user)`.
+pub fn session_transitions(
+    prev_remote: &[(String, String, String)],
+    now_remote: &[(String, String, String)],
+    prev_console: Option<&(String, String)>,
+    now_console: Option<&(String, String)>,
+) -> Vec<BridgeEvent> {
+    let disconnected = prev_remote
+        .iter()
+        .filter(|(id, ..)| !now_remote.iter().any(|(n, ..)| n == id))
+        .map(
+            |(session_id, user, client)| BridgeEvent::RemoteDisconnected {
+                session_id: session_id.clone(),
+                user: user.clone(),
+                client: client.clone(),
+            },
+        );
+    let connected = now_remote
+        .iter()
+        .filter(|(id, ..)| !prev_remote.iter().any(|(p, ..)| p == id))
+        .map(|(session_id, user, client)| BridgeEvent::RemoteConnected {
+            session_id: session_id.clone(),
+            user: user.clone(),
+            client: client.clone(),
+        });
+    let mut events: Vec<_> = disconnected.chain(connected).collect();
+    if prev_console.map(|(id, _)| id) != now_console.map(|(id, _)| id) {
+        if let Some((session_id, user)) = prev_console {
+            events.push(BridgeEvent::ConsoleDisconnected {
+                session_id: session_id.clone(),
+                user: user.clone(),
+            });
+        }
+        if let Some((session_id, user)) = now_console {
+            events.push(BridgeEvent::ConsoleConnected {
+                session_id: session_id.clone(),
+                user: user.clone(),
+            });
+        }
+    }
+    events
+}
+
+/// `UserIdle` when idle time crosses `AWAY_THRESHOLD_SECS`, `UserReturned` when
+/// it drops after the user had been away. `prev_idle` is the last observed idle
+/// seconds (negative = unknown, e.g. reset after a reconnect - never fires).
+pub fn idle_transition(prev_idle: i64, idle: i64) -> Option<BridgeEvent> {
+    if prev_idle < 0 {
+        return None;
+    }
+    if prev_idle < AWAY_THRESHOLD_SECS && idle >= AWAY_THRESHOLD_SECS {
+        return Some(BridgeEvent::UserIdle);
+    }
+    (prev_idle >= AWAY_THRESHOLD_SECS && idle < prev_idle).then_some(BridgeEvent::UserReturned {
+        idle_seconds: prev_idle,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(id: &str, name: &str) -> (String, String) {
+        (id.to_string(), name.to_string())
+    }
+
+    #[test]
+    fn test_payload_is_tagged_with_timestamp() {
+        let ev = BridgeEvent::GameStarted {
+            game_id: "cs2".into(),
+            name: "Counter-Strike 2".into(),
+        };
+        let payload = ev.to_payload();
+        assert_eq!(payload["event"], "game_started");
+        assert_eq!(payload["game_id"], "cs2");
+        assert_eq!(payload["name"], "Counter-Strike 2");
+        assert!(payload["timestamp"].as_str().is_some_and(|t| !t.is_empty()));
+
+        assert_eq!(
+            BridgeEvent::ResumedFromSleep.to_payload()["event"],
+            "resumed_from_sleep"
+        );
+    }
+
+    #[test]
+    fn test_game_transitions() {
+        let prev = [game("cs2", "CS2"), game("bf6", "BF6")];
+        let now = [game("bf6", "BF6"), game("apex", "Apex")];
+        let events = game_transitions(&prev, &now);
+        assert_eq!(
+            events,
+            vec![
+                BridgeEvent::GameStopped {
+                    game_id: "cs2".into(),
+                    name: "CS2".into()
+                },
+                BridgeEvent::GameStarted {
+                    game_id: "apex".into(),
+                    name: "Apex".into()
+                },
+            ]
+        );
+        assert!(game_transitions(&now, &now).is_empty());
+    }
+
+    #[test]
+    fn test_usb_transitions() {
+        let usb = |key: &str, vid, pid, name: &str| (key.to_string(), vid, pid, name.to_string());
+        let prev = [
+            usb("1-2", 0x046d, 0xc52b, "USB Receiver"),
+            usb("1-3", 0x04e8, 0x6860, "SAMSUNG_Android"),
+        ];
+        // The phone moved ports, a drive appeared, the receiver stayed.
+        let now = [
+            usb("1-2", 0x046d, 0xc52b, "USB Receiver"),
+            usb("1-4", 0x04e8, 0x6860, "SAMSUNG_Android"),
+            usb("2-1", 0x0781, 0x5581, "Ultra"),
+        ];
+        let events = usb_transitions(&prev, &now);
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events[0],
+            BridgeEvent::UsbDisconnected {
+                vid: "04e8".into(),
+                pid: "6860".into(),
+                name: "SAMSUNG_Android".into()
+            }
+        );
+        assert_eq!(events[2].to_payload()["event"], "usb_connected");
+        assert_eq!(events[2].to_payload()["pid"], "5581");
+        assert!(usb_transitions(&now, &now).is_empty());
+    }
+
+    #[test]
+    fn test_session_transitions() {
+        let rdp = |id: &str, user: &str| (id.to_string(), user.to_string(), "LAPTOP".to_string());
+        let console = |id: &str, user: &str| (id.to_string(), user.to_string());
+
+        // Someone RDPs in: their session leaves the console for a fresh one.
+        let events = session_transitions(
+            &[],
+            &[rdp("1", "alice")],
+            Some(&console("1", "alice")),
+            Some(&console("2", "")),
+        );
+        assert_eq!(
+            events,
+            vec![
+                BridgeEvent::RemoteConnected {
+                    session_id: "1".into(),
+                    user: "alice".into(),
+                    client: "LAPTOP".into()
+                },
+                BridgeEvent::ConsoleDisconnected {
+                    session_id: "1".into(),
+                    user: "alice".into()
+                },
+                BridgeEvent::ConsoleConnected {
+                    session_id: "2".into(),
+                    user: String::new()
+                },
+            ]
+        );
+        assert_eq!(events[0].to_payload()["event"], "remote_connected");
+
+        let events = session_transitions(&[rdp("1", "alice")], &[], None, None);
+        assert_eq!(events[0].to_payload()["event"], "remote_disconnected");
+        assert_eq!(events.len(), 1);
+
+        let same = [rdp("1", "alice")];
+        let seat = console("c1", "bob");
+        assert!(session_transitions(&same, &same, Some(&seat), Some(&seat)).is_empty());
+    }
+
+    #[test]
+    fn test_idle_transition() {
+        assert_eq!(
+            idle_transition(600, 2),
+            Some(BridgeEvent::UserReturned { idle_seconds: 600 })
+        );
+        assert_eq!(idle_transition(290, 300), Some(BridgeEvent::UserIdle));
+        // Not away long enough, still idle, or unknown previous value.
+        assert_eq!(idle_transition(120, 1), None);
+        assert_eq!(idle_transition(600, 610), None);
+        assert_eq!(idle_transition(-1, 0), None);
+        assert_eq!(idle_transition(-1, 900), None);
+    }
+}
@@ -0,0 +1,247 @@
+//! Agent-owned bookkeeping persisted next to userConfig.json
+//!
+//! `feature_state.json` holds state the agent needs across restarts but that
+//! isn't user configuration: which one-shot migrations already ran, which
+//! device names this install has published under, and the like. It's never
+//! shown in the settings UI and a missing or corrupt file just means "start
+//! fresh" - every migration recorded here is safe to re-run.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+
+/// Current MQTT state-topic layout: state, attributes and availability under
+/// `pc-bridge/<device>/...`. Layout 0 was `homeassistant/sensor/<device>/...`.
+pub const TOPIC_LAYOUT: u32 = 1;
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct FeatureState {
+    /// Layout the retained state topics were last published under. Behind
+    /// `TOPIC_LAYOUT` = the old retained topics still need clearing.
+    #[serde(default)]
+    pub topic_layout: u32,
+    /// Every `device_name` this install has connected as (current one
+    /// included), so entities left behind by a rename can be found and
+    /// removed by the CleanupEntities command.
+    #[serde(default)]
+    pub device_names: Vec<String>,
+    /// The `device_name` it last connected as; a different one at startup
+    /// is a rename, and the old name's entities are removed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_name: Option<String>,
+    /// `group` this PC last published a member record in, cleared when the
+    /// PC leaves it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Update version that failed to start and was rolled back; auto-update
+    /// skips it (an explicit `InstallUpdate` still installs it).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rolled_back_version: Option<String>,
+    /// Object-id slugs of every peripheral battery sensor registered, so
+    /// they can be removed when `device_batteries` is turned off.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub battery_devices: Vec<String>,
+    /// Slugs of every disk given SMART health entities, removed when
+    /// `disk_health` is turned off.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub smart_disks: Vec<String>,
+    /// Slugs of every printer given a status sensor, removed when `printers`
+    /// is turned off.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub printers: Vec<String>,
+    /// Slugs of every configured USB device given a presence sensor, removed
+    /// when `usb_devices` is turned off.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub usb_devices: Vec<String>,
+    /// Slugs of the configured process sensors, so ones removed from
+    /// `process_sensors` can be cleared.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub process_sensors: Vec<String>,
+    /// Slugs of every configured Docker container given entities, removed
+    /// when `docker` is turned off.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub docker_containers: Vec<String>,
+    /// Game ids given per-game device triggers, so triggers for games
+    /// removed from the config (or all of them, with `running_game` off)
+    /// can be cleared.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub game_triggers: Vec<String>,
+    /// Sensors whose reporting switch is off (`active_window`, `games`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paused_reporting: Vec<String>,
+}
+
+impl FeatureState {
+    pub fn path() -> Result<PathBuf> {
+        Ok(Config::config_path()?.with_file_name("feature_state.json"))
+    }
+
+    /// Load from the platform config directory (default if absent/unreadable).
+    pub fn load() -> Self {
+        match Self::path() {
+            Ok(path) => Self::load_from(&path),
+            Err(e) => {
+                warn!("feature_state.json: no config directory ({e})");
+                Self::default()
+            }
+        }
+    }
+
+    /// Add `name` to `device_names`; false if it was already recorded.
+    pub fn remember_device_name(&mut self, name: &str) -> bool {
+        if self.device_names.iter().any(|n| n == name) {
+            return false;
+        }
+        self.device_names.push(name.to_string());
+        true
+    }
+
+    /// Record connecting as `name`: whether that changed anything (and
+    /// needs saving), and the previous name if this is a rename. A state
+    /// from before `device_name` was kept takes the last name recorded.
+    pub fn record_device_name(&mut self, name: &str) -> (bool, Option<String>) {
+        let previous = self
+            .device_name
+            .take()
+            .or_else(|| self.device_names.last().cloned());
+        let changed = self.remember_device_name(name) | (previous.as_deref() != Some(name));
+        self.device_name = Some(name.to_string());
+        (changed, previous.filter(|p| p != name))
+    }
+
+    /// Add `slug` to `battery_devices`; false if it was already recorded.
+    pub fn remember_battery_device(&mut self, slug: &str) -> bool {
+        if self.battery_devices.iter().any(|s| s == slug) {
+            return false;
+        }
+        self.battery_devices.push(slug.to_string());
+        true
+    }
+
+    /// Add `slug` to `smart_disks`; false if it was already recorded.
+    pub fn remember_smart_disk(&mut self, slug: &str) -> bool {
+        if self.smart_disks.iter().any(|s| s == slug) {
+            return false;
+        }
+        self.smart_disks.push(slug.to_string());
+        true
+    }
+
+    /// Add `slug` to `printers`; false if it was already recorded.
+    pub fn remember_printer(&mut self, slug: &str) -> bool {
+        if self.printers.iter().any(|s| s == slug) {
+            return false;
+        }
+        self.printers.push(slug.to_string());
+        true
+    }
+
+    /// Add `slug` to `usb_devices`; false if it was already recorded.
+    pub fn remember_usb_device(&mut self, slug: &str) -> bool {
+        if self.usb_devices.iter().any(|s| s == slug) {
+            return false;
+        }
+        self.usb_devices.push(slug.to_string());
+        true
+    }
+
+    /// Add `slug` to `docker_containers`; false if it was already recorded.
+    pub fn remember_docker_container(&mut self, slug: &str) -> bool {
+        if self.docker_containers.iter().any(|s| s == slug) {
+            return false;
+        }
+        self.docker_containers.push(slug.to_string());
+        true
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.save_to(&Self::path()?)
+    }
+
+    fn load_from(path: &Path) -> Self {
+        let Ok(content) = std::fs::read_to_string(path) else {
+            return Self::default();
+        };
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable {}: {e}", path.display());
+            Self::default()
+        })
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        crate::fsutil::write_atomic(path, content.as_bytes(), None)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_and_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("feature_state.json");
+        assert_eq!(FeatureState::load_from(&path).topic_layout, 0);
+
+        let mut state = FeatureState {
+            topic_layout: TOPIC_LAYOUT,
+            ..FeatureState::default()
+        };
+        assert!(state.remember_device_name("old-pc"));
+        assert!(state.remember_device_name("new-pc"));
+        assert!(!state.remember_device_name("old-pc"));
+        assert!(state.remember_battery_device("dualsense"));
+        assert!(!state.remember_battery_device("dualsense"));
+        assert!(state.remember_smart_disk("samsung_ssd_870_evo_1tb_456x"));
+        assert!(!state.remember_smart_disk("samsung_ssd_870_evo_1tb_456x"));
+        assert!(state.remember_printer("hp_laserjet"));
+        assert!(!state.remember_printer("hp_laserjet"));
+        assert!(state.remember_usb_device("phone"));
+        state.game_triggers = vec!["cs2".to_string()];
+        state.save_to(&path).unwrap();
+        let loaded = FeatureState::load_from(&path);
+        assert_eq!(loaded.topic_layout, TOPIC_LAYOUT);
+        assert_eq!(loaded.device_names, ["old-pc", "new-pc"]);
+        assert_eq!(loaded.battery_devices, ["dualsense"]);
+        assert_eq!(loaded.smart_disks, ["samsung_ssd_870_evo_1tb_456x"]);
+        assert_eq!(loaded.printers, ["hp_laserjet"]);
+        assert_eq!(loaded.usb_devices, ["phone"]);
+        assert_eq!(loaded.game_triggers, ["cs2"]);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert_eq!(FeatureState::load_from(&path).topic_layout, 0);
+    }
+
+    #[test]
+    fn test_record_device_name_detects_rename() {
+        let mut state = FeatureState::default();
+        assert_eq!(state.record_device_name("old-pc"), (true, None));
+        assert_eq!(state.record_device_name("old-pc"), (false, None));
+        assert_eq!(
+            state.record_device_name("new-pc"),
+            (true, Some("old-pc".to_string()))
+        );
+        assert_eq!(state.device_names, ["old-pc", "new-pc"]);
+        // Renaming back is a rename too
+        assert_eq!(
+            state.record_device_name("old-pc"),
+            (true, Some("new-pc".to_string()))
+        );
+
+        // Saved before `device_name` existed: the last recorded name counts
+        let mut legacy = FeatureState {
+            device_names: vec!["old-pc".to_string()],
+            ..FeatureState::default()
+        };
+        assert_eq!(
+            legacy.record_device_name("new-pc"),
+            (true, Some("old-pc".to_string()))
+        );
+    }
+}
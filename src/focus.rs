@@ -0,0 +1,103 @@
+//! Focus mode
+//!
+//! The `focus_mode` switch starts a focus session of `focus.minutes` (a
+//! `FocusMode` payload of minutes, e.g. from an automation, sets another
+//! length) and turns itself off when it's over. While a session runs, the
+//! apps in `focus.blocked_apps` are closed as soon as they're seen, toasts
+//! from HA are held back as if the user were busy (`notifications`), and
+//! `focus_remaining` counts down the minutes left. Sessions don't survive a
+//! restart.
+
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use tokio::sync::watch;
+
+/// Longest session a payload can ask for: a day
+pub const MAX_MINUTES: u32 = 24 * 60;
+
+/// When the running session ends; None while focus mode is off
+static SESSION: LazyLock<watch::Sender<Option<Instant>>> = LazyLock::new(|| watch::channel(None).0);
+
+/// Changes whenever a session starts or ends
+pub fn subscribe() -> watch::Receiver<Option<Instant>> {
+    SESSION.subscribe()
+}
+
+/// Time left in the running session, None while focus mode is off. A
+/// session past its end is ended here.
+pub fn remaining() -> Option<Duration> {
+    let until = (*SESSION.borrow())?;
+    let left = until.saturating_duration_since(Instant::now());
+    if left.is_zero() {
+        end("over");
+        return None;
+    }
+    Some(left)
+}
+
+pub fn is_active() -> bool {
+    remaining().is_some()
+}
+
+/// End the running session, if any. `why` is for the log.
+pub fn end(why: &str) {
+    if SESSION.send_replace(None).is_some() {
+        info!("Focus session {why}");
+    }
+}
+
+/// The `focus_mode` switch's `FocusMode` command: `ON` starts a session of
+/// `default_minutes`, a number starts one of that many minutes, `OFF` ends it.
+pub fn command(payload: &str, default_minutes: u32) {
+    match parse_command(payload, default_minutes) {
+        Ok(Some(minutes)) => {
+            info!("Focus session started ({minutes} min)");
+            let until = Instant::now() + Duration::from_secs(u64::from(minutes) * 60);
+            SESSION.send_replace(Some(until));
+        }
+        Ok(None) => end("ended"),
+        Err(e) => warn!("FocusMode: {e}"),
+    }
+}
+
+/// Session length a `FocusMode` payload asks for; None to end the session.
+fn parse_command(payload: &str, default_minutes: u32) -> Result<Option<u32>, String> {
+    let payload = payload.trim();
+    match payload.to_ascii_uppercase().as_str() {
+        "ON" => return Ok(Some(default_minutes)),
+        "OFF" => return Ok(None),
+        _ => {}
+    }
+    match payload.parse::<u32>() {
+        Ok(0) => Ok(None),
+        Ok(minutes) if minutes <= MAX_MINUTES => Ok(Some(minutes)),
+        _ => Err(format!(
+            "expected ON, OFF or 1-{MAX_MINUTES} minutes, got {payload:?}"
+        )),
+    }
+}
+
+/// Turning `focus_mode` off ends a running session.
+pub fn configure(config: &crate::config::Config) {
+    if !config.features.focus_mode {
+        end("ended (focus_mode disabled)");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("ON", 25), Ok(Some(25)));
+        assert_eq!(parse_command(" on ", 25), Ok(Some(25)));
+        assert_eq!(parse_command("50", 25), Ok(Some(50)));
+        assert_eq!(parse_command("OFF", 25), Ok(None));
+        assert_eq!(parse_command("0", 25), Ok(None));
+        assert!(parse_command("1441", 25).is_err());
+        assert!(parse_command("soon", 25).is_err());
+    }
+}
@@ -81,3 +81,58 @@ pub(crate) fn write_atomic(path: &Path, bytes: &[u8], mode: Option<u32>) -> io::
         }
     }
 }
+
+/// Before `path` is overwritten: shift `path.bak1..` up by one, dropping the
+/// one past `keep`, and copy `path` to `path.bak1`. Skipped when `path`
+/// doesn't exist yet or already holds `next`, so an unchanged save doesn't
+/// push an older, different version out.
+pub(crate) fn rotate_backups(path: &Path, next: &[u8], keep: usize) -> io::Result<()> {
+    let Ok(current) = std::fs::read(path) else {
+        return Ok(());
+    };
+    if current == next || keep == 0 {
+        return Ok(());
+    }
+    let bak = |n: usize| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".bak{n}"));
+        std::path::PathBuf::from(name)
+    };
+    for n in (1..keep).rev() {
+        match std::fs::rename(bak(n), bak(n + 1)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    // Via write_atomic, so a crash can't leave a torn .bak1 either.
+    write_atomic(&bak(1), &current, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate_backups_keeps_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("userConfig.json");
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).ok();
+
+        // Nothing to keep before the first write.
+        rotate_backups(&path, b"v1", 2).unwrap();
+        write_atomic(&path, b"v1", None).unwrap();
+        assert_eq!(read("userConfig.json.bak1"), None);
+
+        for v in ["v2", "v3", "v4"] {
+            rotate_backups(&path, v.as_bytes(), 2).unwrap();
+            write_atomic(&path, v.as_bytes(), None).unwrap();
+        }
+        assert_eq!(read("userConfig.json.bak1").as_deref(), Some("v3"));
+        assert_eq!(read("userConfig.json.bak2").as_deref(), Some("v2"));
+        assert_eq!(read("userConfig.json.bak3"), None);
+
+        // An unchanged save leaves the backups alone.
+        rotate_backups(&path, b"v4", 2).unwrap();
+        assert_eq!(read("userConfig.json.bak1").as_deref(), Some("v3"));
+    }
+}
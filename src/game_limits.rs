@@ -0,0 +1,461 @@
+//! Game time limits
+//!
+//! `game_limits` caps daily playtime: all games together (`daily_minutes`,
+//! counted while any game runs) and/or single games by game_id. Playtime is
+//! counted here from the games the game sensor detects - also while game
+//! reporting is paused - and kept per local day in `playtime.json` next to the
+//! config, so a restart doesn't reset it.
+//!
+//! `warn_minutes` before a limit a toast warns; once it's reached a toast
+//! says so, a `game_limit_reached` event goes out and `action` runs (close
+//! the game, or lock the PC). A limit is enforced once per session: starting
+//! the game again the same day enforces it again.
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::{MissedTickBehavior, interval};
+
+use crate::AppState;
+use crate::config::{Config, GameLimitAction, GameLimitsConfig};
+use crate::events::BridgeEvent;
+use crate::i18n::{tr, tr_args};
+use crate::mqtt::Command;
+
+/// How often playtime is counted and the limits checked
+const TICK: Duration = Duration::from_secs(30);
+
+/// Key of the daily total in `warned` / `enforced` (game ids never are empty)
+const TOTAL: &str = "";
+
+/// `(game_id, name)` of the games the game sensor last detected
+static RUNNING: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+/// Record the games currently running. Called by the game sensor on every
+/// poll, whether or not reporting is paused.
+pub(crate) fn set_running(games: &[(String, String)]) {
+    games.clone_into(&mut RUNNING.lock().unwrap_or_else(|e| e.into_inner()));
+}
+
+/// Playtime for one local day
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct Playtime {
+    /// YYYY-MM-DD
+    date: String,
+    /// Seconds with any game running
+    total_secs: u64,
+    /// game_id -> seconds
+    games: BTreeMap<String, u64>,
+}
+
+impl Playtime {
+    fn path() -> anyhow::Result<PathBuf> {
+        Ok(Config::config_path()?.with_file_name("playtime.json"))
+    }
+
+    fn load() -> Self {
+        Self::path()
+            .ok()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self) {
+        let result = Self::path().and_then(|path| {
+            let json = serde_json::to_string_pretty(self)?;
+            crate::fsutil::write_atomic(&path, json.as_bytes(), None)?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            warn!("Failed to save playtime: {e:#}");
+        }
+    }
+}
+
+/// What a tick of the [`Policy`] calls for. `game` is `(game_id, name)`, or
+/// None for the daily total.
+#[derive(Debug, Clone, PartialEq)]
+enum Due {
+    Warning {
+        game: Option<(String, String)>,
+        minutes_left: u64,
+    },
+    Reached {
+        game: Option<(String, String)>,
+        limit_minutes: u32,
+        played_minutes: u64,
+    },
+}
+
+/// Counts playtime and says which limits are due a warning or are reached.
+struct Policy {
+    limits: GameLimitsConfig,
+    playtime: Playtime,
+    /// Limits warned about today
+    warned: HashSet<String>,
+    /// Limits enforced this session; re-armed when the game (for the total,
+    /// every game) stops
+    enforced: HashSet<String>,
+}
+
+impl Policy {
+    fn new(limits: GameLimitsConfig, playtime: Playtime) -> Self {
+        Self {
+            limits,
+            playtime,
+            warned: HashSet::new(),
+            enforced: HashSet::new(),
+        }
+    }
+
+    /// Count `elapsed` of play for `running` on `date`, and return the
+    /// warnings and limits now due.
+    fn tick(&mut self, date: &str, elapsed: Duration, running: &[(String, String)]) -> Vec<Due> {
+        if self.playtime.date != date {
+            self.playtime = Playtime {
+                date: date.to_string(),
+                ..Playtime::default()
+            };
+            self.warned.clear();
+            self.enforced.clear();
+        }
+        self.enforced.retain(|key| {
+            if key == TOTAL {
+                !running.is_empty()
+            } else {
+                running.iter().any(|(id, _)| id == key)
+            }
+        });
+        if running.is_empty() {
+            return Vec::new();
+        }
+
+        let secs = elapsed.as_secs();
+        self.playtime.total_secs += secs;
+        let mut counted = HashSet::new();
+        for (id, _) in running {
+            if counted.insert(id) {
+                *self.playtime.games.entry(id.clone()).or_default() += secs;
+            }
+        }
+
+        let mut due = Vec::new();
+        if self.limits.daily_minutes > 0 {
+            let played = self.playtime.total_secs;
+            due.extend(self.check(None, self.limits.daily_minutes, played));
+        }
+        for (id, name) in running {
+            let Some(&limit) = self.limits.games.get(id) else {
+                continue;
+            };
+            let played = self.playtime.games.get(id).copied().unwrap_or_default();
+            due.extend(self.check(Some((id.clone(), name.clone())), limit, played));
+        }
+        due
+    }
+
+    fn check(
+        &mut self,
+        game: Option<(String, String)>,
+        limit_minutes: u32,
+        played_secs: u64,
+    ) -> Option<Due> {
+        let key = game
+            .as_ref()
+            .map_or(TOTAL, |(id, _)| id.as_str())
+            .to_string();
+        let limit_secs = u64::from(limit_minutes) * 60;
+        if played_secs >= limit_secs {
+            self.warned.insert(key.clone());
+            return self.enforced.insert(key).then_some(Due::Reached {
+                game,
+                limit_minutes,
+                played_minutes: played_secs / 60,
+            });
+        }
+        let warn_secs = u64::from(self.limits.warn_minutes) * 60;
+        if played_secs + warn_secs >= limit_secs && self.warned.insert(key) {
+            return Some(Due::Warning {
+                game,
+                minutes_left: (limit_secs - played_secs).div_ceil(60),
+            });
+        }
+        None
+    }
+}
+
+/// Today's local date as YYYY-MM-DD
+#[cfg(windows)]
+fn local_date() -> String {
+    // SAFETY: GetLocalTime only fills in the returned struct.
+    let now = unsafe { windows::Win32::System::SystemInformation::GetLocalTime() };
+    format!("{:04}-{:02}-{:02}", now.wYear, now.wMonth, now.wDay)
+}
+
+/// Today's local date as YYYY-MM-DD
+#[cfg(unix)]
+fn local_date() -> String {
+    // SAFETY: localtime_r writes only into `tm`, which outlives the call.
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&raw const now, &raw mut tm).is_null() {
+            return time::OffsetDateTime::now_utc().date().to_string();
+        }
+        format!(
+            "{:04}-{:02}-{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday
+        )
+    }
+}
+
+fn toast(title: &str, message: String) {
+    let payload = serde_json::json!({ "title": title, "message": message }).to_string();
+    tokio::spawn(async move {
+        match tokio::task::spawn_blocking(move || crate::notification::show_toast(&payload, ""))
+            .await
+        {
+            Ok(Err(e)) => warn!("Game limit toast failed: {e}"),
+            Err(e) => warn!("Game limit toast task failed: {e}"),
+            Ok(Ok(())) => {}
+        }
+    });
+}
+
+/// Toast, publish and act on what the policy says is due.
+async fn handle(state: &AppState, action: GameLimitAction, due: Due) {
+    match due {
+        Due::Warning { game, minutes_left } => {
+            let scope = game.map_or_else(|| tr("Games"), |(_, name)| name);
+            info!("Game limit: {scope} - {minutes_left} min left today");
+            toast(
+                &tr("Game time almost up"),
+                tr_args(
+                    "{scope}: {minutes} minute(s) of play left today.",
+                    &[("scope", &scope), ("minutes", &minutes_left.to_string())],
+                ),
+            );
+        }
+        Due::Reached {
+            game,
+            limit_minutes,
+            played_minutes,
+        } => {
+            let (game_id, name) = game.unzip();
+            let scope = name.clone().unwrap_or_else(|| tr("Games"));
+            info!("Game limit: {scope} reached today's {limit_minutes} min ({action:?})");
+            let then = match action {
+                GameLimitAction::None => None,
+                GameLimitAction::Close if name.is_some() => Some("The game will be closed."),
+                GameLimitAction::Close => Some("Games will be closed."),
+                GameLimitAction::Lock => Some("The PC will be locked."),
+            };
+            let mut message = tr_args(
+                "{scope}: today's {minutes} minutes are used up.",
+                &[("scope", &scope), ("minutes", &limit_minutes.to_string())],
+            );
+            if let Some(then) = then {
+                message = format!("{message} {}", tr(then));
+            }
+            toast(&tr("Game time is up"), message);
+            state
+                .mqtt
+                .publish_event(&BridgeEvent::GameLimitReached {
+                    game_id,
+                    name: name.clone(),
+                    limit_minutes,
+                    played_minutes,
+                    action,
+                })
+                .await;
+            match action {
+                GameLimitAction::None => {}
+                GameLimitAction::Close => {
+                    crate::commands::stop_game::close(name.as_deref()).await;
+                }
+                GameLimitAction::Lock => {
+                    let cmd = Command {
+                        name: "Lock".to_string(),
+                        payload: String::new(),
+                    };
+                    if state.mqtt.command_sender().send(cmd).await.is_err() {
+                        warn!("Game limit: command executor is gone");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Supervised while `game_limits` is enabled.
+pub(crate) async fn run(state: Arc<AppState>) {
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut config_rx = state.config_generation.subscribe();
+    let mut policy = Policy::new(
+        state.config.read().await.game_limits.clone(),
+        Playtime::load(),
+    );
+    let mut tick = interval(TICK);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut last = Instant::now();
+    info!("Game limits started");
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => break,
+            Ok(()) = config_rx.recv() => {
+                policy.limits = state.config.read().await.game_limits.clone();
+                debug!("Game limits reloaded");
+            }
+            _ = tick.tick() => {
+                // Capped, so time asleep doesn't count as play.
+                let elapsed = last.elapsed().min(TICK * 2);
+                last = Instant::now();
+                let running = RUNNING.lock().unwrap_or_else(|e| e.into_inner()).clone();
+                let due = policy.tick(&local_date(), elapsed, &running);
+                if !running.is_empty() {
+                    policy.playtime.save();
+                }
+                let action = policy.limits.action;
+                for due in due {
+                    handle(&state, action, due).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const MIN: Duration = Duration::from_mins(1);
+
+    fn game(id: &str) -> (String, String) {
+        (id.to_string(), id.to_uppercase())
+    }
+
+    fn policy(daily_minutes: u32, games: &[(&str, u32)]) -> Policy {
+        let limits = GameLimitsConfig {
+            enabled: true,
+            daily_minutes,
+            games: games
+                .iter()
+                .map(|(id, minutes)| ((*id).to_string(), *minutes))
+                .collect::<HashMap<_, _>>(),
+            warn_minutes: 5,
+            action: GameLimitAction::Close,
+        };
+        Policy::new(limits, Playtime::default())
+    }
+
+    #[test]
+    fn test_warns_then_reaches_once() {
+        let mut policy = policy(0, &[("cs2", 10)]);
+        let cs2 = [game("cs2")];
+        for _ in 0..4 {
+            assert!(policy.tick("2026-10-15", MIN, &cs2).is_empty());
+        }
+        assert_eq!(
+            policy.tick("2026-10-15", MIN, &cs2),
+            vec![Due::Warning {
+                game: Some(game("cs2")),
+                minutes_left: 5
+            }]
+        );
+        for _ in 0..4 {
+            assert!(policy.tick("2026-10-15", MIN, &cs2).is_empty());
+        }
+        assert_eq!(
+            policy.tick("2026-10-15", MIN, &cs2),
+            vec![Due::Reached {
+                game: Some(game("cs2")),
+                limit_minutes: 10,
+                played_minutes: 10
+            }]
+        );
+        // Enforced once while it keeps running...
+        assert!(policy.tick("2026-10-15", MIN, &cs2).is_empty());
+        // ...and again when it's started again the same day.
+        assert!(policy.tick("2026-10-15", MIN, &[]).is_empty());
+        assert!(matches!(
+            policy.tick("2026-10-15", MIN, &cs2).as_slice(),
+            [Due::Reached { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_total_counts_time_not_games() {
+        let mut policy = policy(30, &[]);
+        let both = [game("cs2"), game("factorio")];
+        for _ in 0..20 {
+            assert!(policy.tick("2026-10-15", MIN, &both).is_empty());
+        }
+        assert_eq!(policy.playtime.total_secs, 20 * 60);
+        assert_eq!(policy.playtime.games["factorio"], 20 * 60);
+        // Other games keep counting towards it
+        for _ in 0..4 {
+            policy.tick("2026-10-15", MIN, &both[1..]);
+        }
+        assert!(matches!(
+            policy.tick("2026-10-15", MIN, &both[1..]).as_slice(),
+            [Due::Warning {
+                game: None,
+                minutes_left: 5
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_zero_minutes_blocks_game() {
+        let mut policy = policy(0, &[("cs2", 0)]);
+        assert!(
+            policy
+                .tick("2026-10-15", MIN, &[game("factorio")])
+                .is_empty()
+        );
+        assert!(matches!(
+            policy.tick("2026-10-15", MIN, &[game("cs2")]).as_slice(),
+            [Due::Reached {
+                limit_minutes: 0,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn test_new_day_resets_playtime() {
+        let mut policy = policy(0, &[("cs2", 10)]);
+        let cs2 = [game("cs2")];
+        for _ in 0..10 {
+            policy.tick("2026-10-15", MIN, &cs2);
+        }
+        assert!(policy.enforced.contains("cs2"));
+        assert!(policy.tick("2026-10-16", MIN, &cs2).is_empty());
+        assert_eq!(policy.playtime.date, "2026-10-16");
+        assert_eq!(policy.playtime.games["cs2"], 60);
+    }
+
+    #[test]
+    fn test_event_payload() {
+        let event = BridgeEvent::GameLimitReached {
+            game_id: None,
+            name: None,
+            limit_minutes: 120,
+            played_minutes: 120,
+            action: GameLimitAction::Lock,
+        };
+        let payload = event.to_payload();
+        assert_eq!(payload["event"], "game_limit_reached");
+        assert_eq!(payload["action"], "lock");
+        assert!(payload.get("game_id").is_none());
+    }
+}
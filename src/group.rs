@@ -0,0 +1,279 @@
+//! Household groups of pc-bridge instances
+//!
+//! PCs sharing a `group` each publish a retained member record,
+//! `pc-bridge/groups/<group>/members/<device>` = `{"games": [...]}`. Every
+//! member folds the records, and each member's availability, into the
+//! group's `any_game_running` binary sensor on a shared "PC Bridge <group>"
+//! device in HA, so "any PC gaming" automations need no template sensor. A PC
+//! that drops offline stops counting even if it couldn't clear its record.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use log::{debug, warn};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::AppState;
+use crate::events::BridgeEvent;
+use crate::feature_state::FeatureState;
+
+/// Object id of the group's aggregate sensor
+pub const ANY_GAME_RUNNING: &str = "any_game_running";
+
+/// Availability of every pc-bridge on the broker, to tell live members
+/// from stale records
+pub const AVAILABILITY_FILTER: &str = "pc-bridge/+/availability";
+
+/// A message on one of the group's subscriptions
+#[derive(Debug, Clone)]
+pub struct GroupMessage {
+    pub topic: String,
+    pub payload: String,
+}
+
+/// Retained record of one member: `pc-bridge/groups/<group>/members/<device>`
+pub fn member_topic(group: &str, device_name: &str) -> String {
+    format!("pc-bridge/groups/{group}/members/{device_name}")
+}
+
+/// Subscription for every member's record
+pub fn members_filter(group: &str) -> String {
+    member_topic(group, "+")
+}
+
+pub fn state_topic(group: &str) -> String {
+    format!("pc-bridge/groups/{group}/{ANY_GAME_RUNNING}/state")
+}
+
+pub fn attributes_topic(group: &str) -> String {
+    format!("pc-bridge/groups/{group}/{ANY_GAME_RUNNING}/attributes")
+}
+
+/// HA identifier of the group's shared device
+pub fn device_id(group: &str) -> String {
+    format!("pc_bridge_group_{}", group.replace(['-', '.'], "_"))
+}
+
+/// Whether `topic` is some pc-bridge's `pc-bridge/<device>/availability`
+pub fn is_availability_topic(topic: &str) -> bool {
+    topic
+        .strip_prefix("pc-bridge/")
+        .and_then(|rest| rest.strip_suffix("/availability"))
+        .is_some_and(|device| !device.is_empty() && !device.contains('/'))
+}
+
+/// What this member knows about the group
+#[derive(Debug, Default)]
+struct GroupView {
+    /// Running game names by member
+    members: BTreeMap<String, Vec<String>>,
+    /// Last availability seen per device, members or not
+    online: HashMap<String, bool>,
+}
+
+impl GroupView {
+    fn apply(&mut self, group: &str, msg: &GroupMessage) {
+        if let Some(device) = msg.topic.strip_prefix(&member_topic(group, "")) {
+            // An empty record is a member leaving the group.
+            if msg.payload.is_empty() {
+                self.members.remove(device);
+                return;
+            }
+            let games = serde_json::from_str::<serde_json::Value>(&msg.payload)
+                .ok()
+                .and_then(|v| serde_json::from_value(v["games"].clone()).ok());
+            match games {
+                Some(games) => {
+                    self.members.insert(device.to_string(), games);
+                }
+                None => debug!("Group: unreadable record for {device}: {}", msg.payload),
+            }
+        } else if let Some(device) = msg
+            .topic
+            .strip_prefix("pc-bridge/")
+            .and_then(|rest| rest.strip_suffix("/availability"))
+        {
+            self.online
+                .insert(device.to_string(), msg.payload == "online");
+        }
+    }
+
+    /// (`"on"`/`"off"`, attributes) of `any_game_running`
+    fn report(&self) -> (&'static str, serde_json::Value) {
+        let online: Vec<&str> = self
+            .members
+            .keys()
+            .filter(|m| self.online.get(*m).copied().unwrap_or(false))
+            .map(String::as_str)
+            .collect();
+        let gaming: BTreeMap<&str, &Vec<String>> = online
+            .iter()
+            .filter_map(|m| {
+                let games = &self.members[*m];
+                (!games.is_empty()).then_some((*m, games))
+            })
+            .collect();
+        let state = if gaming.is_empty() { "off" } else { "on" };
+        let attributes = serde_json::json!({
+            "members": self.members.keys().collect::<Vec<_>>(),
+            "online": online,
+            "gaming": gaming,
+        });
+        (state, attributes)
+    }
+}
+
+/// Keep this PC's member record and the group's aggregate current until
+/// shutdown. Also clears the record left in a group the PC was taken out of.
+pub async fn run(state: Arc<AppState>) {
+    let (group, device_name) = {
+        let config = state.config.read().await;
+        (config.group.clone(), config.device_name.clone())
+    };
+    leave_old_group(&state, group.as_deref(), &device_name).await;
+    let Some(group) = group else {
+        return;
+    };
+
+    let mut group_rx = state.mqtt.subscribe_group();
+    let mut events_rx = state.mqtt.subscribe_events();
+    let mut reconnect_rx = state.mqtt.subscribe_reconnect();
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    // Running games by id
+    let mut games: BTreeMap<String, String> = BTreeMap::new();
+    let mut view = GroupView::default();
+    let mut published = None;
+
+    publish_member(&state, &group, &device_name, &games).await;
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => {
+                games.clear();
+                publish_member(&state, &group, &device_name, &games).await;
+                break;
+            }
+            Ok(()) = reconnect_rx.recv() => {
+                publish_member(&state, &group, &device_name, &games).await;
+                published = None;
+            }
+            event = events_rx.recv() => match event {
+                Ok(BridgeEvent::GameStarted { game_id, name }) => {
+                    games.insert(game_id, name);
+                    publish_member(&state, &group, &device_name, &games).await;
+                }
+                Ok(BridgeEvent::GameStopped { game_id, .. }) => {
+                    games.remove(&game_id);
+                    publish_member(&state, &group, &device_name, &games).await;
+                }
+                Ok(_) | Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            },
+            msg = group_rx.recv() => match msg {
+                Ok(msg) => view.apply(&group, &msg),
+                Err(RecvError::Lagged(n)) => warn!("Group: missed {n} member updates"),
+                Err(RecvError::Closed) => break,
+            },
+        }
+
+        // Every member publishes the aggregate; they agree, as they fold the
+        // same retained records.
+        let report = view.report();
+        if published.as_ref() != Some(&report) {
+            let (on, attributes) = &report;
+            state.mqtt.publish_group_state(&group, on, attributes).await;
+            published = Some(report);
+        }
+    }
+}
+
+async fn publish_member(
+    state: &AppState,
+    group: &str,
+    device_name: &str,
+    games: &BTreeMap<String, String>,
+) {
+    let record = serde_json::json!({ "games": games.values().collect::<Vec<_>>() });
+    state
+        .mqtt
+        .publish_group_member(group, device_name, record.to_string().into_bytes())
+        .await;
+}
+
+/// Clear this PC's record in the group it was last in, if that changed.
+async fn leave_old_group(state: &AppState, group: Option<&str>, device_name: &str) {
+    let mut feature_state = FeatureState::load();
+    if feature_state.group.as_deref() == group {
+        return;
+    }
+    if let Some(old) = &feature_state.group {
+        state
+            .mqtt
+            .publish_group_member(old, device_name, Vec::new())
+            .await;
+    }
+    feature_state.group = group.map(str::to_string);
+    if let Err(e) = feature_state.save() {
+        warn!("Failed to record group in feature_state.json: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(topic: &str, payload: &str) -> GroupMessage {
+        GroupMessage {
+            topic: topic.to_string(),
+            payload: payload.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_any_game_running_counts_online_members_only() {
+        let mut view = GroupView::default();
+        view.apply(
+            "home",
+            &msg(
+                "pc-bridge/groups/home/members/desk",
+                r#"{"games":["Counter-Strike 2"]}"#,
+            ),
+        );
+        view.apply(
+            "home",
+            &msg("pc-bridge/groups/home/members/laptop", r#"{"games":[]}"#),
+        );
+        view.apply("home", &msg("pc-bridge/laptop/availability", "online"));
+        // desk's availability not seen yet: its record doesn't count
+        assert_eq!(view.report().0, "off");
+
+        view.apply("home", &msg("pc-bridge/desk/availability", "online"));
+        let (state, attrs) = view.report();
+        assert_eq!(state, "on");
+        assert_eq!(attrs["gaming"]["desk"][0], "Counter-Strike 2");
+        assert_eq!(attrs["members"], serde_json::json!(["desk", "laptop"]));
+
+        // Crashed with its record still saying it's gaming
+        view.apply("home", &msg("pc-bridge/desk/availability", "offline"));
+        assert_eq!(view.report().0, "off");
+
+        // Left the group
+        view.apply("home", &msg("pc-bridge/groups/home/members/desk", ""));
+        assert_eq!(view.report().1["members"], serde_json::json!(["laptop"]));
+    }
+
+    #[test]
+    fn test_group_topics() {
+        assert_eq!(members_filter("home"), "pc-bridge/groups/home/members/+");
+        assert_eq!(
+            state_topic("home"),
+            "pc-bridge/groups/home/any_game_running/state"
+        );
+        assert_eq!(device_id("up-stairs.1"), "pc_bridge_group_up_stairs_1");
+        assert!(is_availability_topic("pc-bridge/desk/availability"));
+        assert!(!is_availability_topic(
+            "pc-bridge/groups/home/availability/x"
+        ));
+        assert!(!is_availability_topic("pc-bridge/desk/hwinfo_availability"));
+    }
+}
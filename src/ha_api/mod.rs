@@ -0,0 +1,482 @@
+//! Home Assistant API transport (`ha_api`), for setups without an MQTT broker
+//!
+//! Runs alongside or instead of MQTT, fed by the same `MqttClient` publish
+//! calls (see `MqttClient::attach_ha_api`):
+//! - sensor state and attributes are written with the REST API
+//!   (`POST /api/states/sensor.<device_id>_<name>`), coalesced per entity so a
+//!   slow HA only ever gets the newest value;
+//! - bridge events are fired as `pc_bridge_event` (`POST /api/events/...`);
+//! - commands arrive as `pc_bridge_command` events over the WebSocket API,
+//!   with `data: {device, command, payload?}`, and go through the same
+//!   executor as MQTT button presses.
+//!
+//! States written this way aren't backed by an integration, so there's no
+//! entity registry, device page or button entities - HA also forgets them on
+//! restart. The WebSocket reconnect after a restart triggers a resync that
+//! writes every known entity again.
+
+mod ws;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde_json::{Value, json};
+use tokio::sync::{broadcast, mpsc};
+use ureq::tls::{RootCerts, TlsConfig, TlsProvider};
+
+use crate::config::Config;
+use crate::mqtt::{Command, MqttClient};
+use ws::{Endpoint, WebSocket};
+
+/// Event type HA automations/scripts fire to run a command.
+const COMMAND_EVENT: &str = "pc_bridge_command";
+/// Event type bridge events (game started, user idle, ...) are fired as.
+const BRIDGE_EVENT: &str = "pc_bridge_event";
+/// HA rejects states longer than this; longer values go out truncated (the
+/// MQTT path has the same limit, enforced by HA on its side).
+const MAX_STATE_LEN: usize = 255;
+/// Pending updates before `MqttClient::mirror` starts dropping them.
+const UPDATE_QUEUE: usize = 256;
+const MAX_BACKOFF_SECS: u64 = 60;
+
+/// What `MqttClient` forwards to the transport.
+#[derive(Debug, Clone)]
+pub enum Update {
+    State {
+        name: String,
+        value: String,
+    },
+    Attributes {
+        name: String,
+        attributes: Value,
+    },
+    Event(Value),
+    /// Write every known entity again (HA restarted and lost them).
+    Resync,
+}
+
+/// Latest known state of one entity.
+#[derive(Default)]
+struct Entity {
+    state: Option<String>,
+    attributes: serde_json::Map<String, Value>,
+}
+
+/// REST side: blocking ureq calls, run on the blocking pool.
+struct Rest {
+    agent: ureq::Agent,
+    base_url: String,
+    auth: String,
+    device_id: String,
+    device_name: String,
+}
+
+impl Rest {
+    fn new(config: &Config) -> Self {
+        let tls = TlsConfig::builder()
+            .provider(TlsProvider::NativeTls)
+            .root_certs(RootCerts::PlatformVerifier)
+            .build();
+        let agent_config = ureq::Agent::config_builder()
+            .tls_config(tls)
+            .timeout_global(Some(Duration::from_secs(5)))
+            .build();
+        Self {
+            agent: ureq::Agent::new_with_config(agent_config),
+            base_url: config.ha_api.url.trim_end_matches('/').to_string(),
+            auth: format!("Bearer {}", config.ha_api.token),
+            device_id: config.device_id(),
+            device_name: config.device_name.clone(),
+        }
+    }
+
+    fn post(&self, path: &str, body: &Value) -> Result<(), ureq::Error> {
+        self.agent
+            .post(format!("{}{path}", self.base_url))
+            .header("Authorization", &self.auth)
+            .header("Content-Type", "application/json")
+            .send(body.to_string())?;
+        Ok(())
+    }
+
+    fn entity_body(&self, name: &str, entity: &Entity) -> Value {
+        let mut attributes = entity.attributes.clone();
+        attributes.insert(
+            "friendly_name".to_string(),
+            format!("{} {}", self.device_name, name.replace('_', " ")).into(),
+        );
+        let state = entity.state.as_deref().unwrap_or("unknown");
+        json!({ "state": truncate(state, MAX_STATE_LEN), "attributes": attributes })
+    }
+
+    /// Write `entities` and fire `events`; returns the first error, after trying all.
+    fn write(&self, entities: &[(String, Value)], events: &[Value]) -> Result<(), ureq::Error> {
+        let mut result = Ok(());
+        for (name, body) in entities {
+            let path = format!("/api/states/{}", entity_id(&self.device_id, name));
+            if let Err(e) = self.post(&path, body) {
+                result = result.and(Err(e));
+            }
+        }
+        for event in events {
+            let mut data = event.clone();
+            if let Some(obj) = data.as_object_mut() {
+                obj.insert("device".to_string(), self.device_name.clone().into());
+            }
+            if let Err(e) = self.post(&format!("/api/events/{BRIDGE_EVENT}"), &data) {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
+
+/// `sensor.<device_id>_<name>`, reduced to the characters HA allows in an
+/// object id.
+fn entity_id(device_id: &str, name: &str) -> String {
+    let object_id: String = format!("{device_id}_{name}")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("sensor.{object_id}")
+}
+
+fn truncate(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// Start the transport: attaches to `mqtt` and returns the writer task, which
+/// exits (marking every entity unavailable) on shutdown. `None` if the URL
+/// can't be used.
+pub(crate) fn spawn(
+    config: &Config,
+    mqtt: &MqttClient,
+    shutdown_rx: broadcast::Receiver<()>,
+) -> Option<tokio::task::JoinHandle<()>> {
+    let Some(endpoint) = Endpoint::parse(&config.ha_api.url) else {
+        warn!(
+            "ha_api.url '{}' is not a usable http(s) URL - Home Assistant API disabled",
+            config.ha_api.url
+        );
+        return None;
+    };
+    let (tx, rx) = mpsc::channel(UPDATE_QUEUE);
+    mqtt.attach_ha_api(tx.clone());
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let listener = Listener {
+        endpoint,
+        token: config.ha_api.token.clone(),
+        device_name: config.device_name.clone(),
+        commands: mqtt.command_sender(),
+        updates: tx,
+        stop: Arc::clone(&stop),
+    };
+    // Blocking socket on its own thread, like the other long-lived listeners
+    // (sleep/wake, WMI): it only wakes for HA messages and its idle timeout.
+    if let Err(e) = std::thread::Builder::new()
+        .name("ha-api-ws".into())
+        .spawn(move || listener.run())
+    {
+        warn!("Failed to start Home Assistant API listener: {e}");
+    }
+
+    info!(
+        "Home Assistant API transport enabled ({})",
+        config.ha_api.url
+    );
+    Some(tokio::spawn(run_writer(
+        Arc::new(Rest::new(config)),
+        rx,
+        shutdown_rx,
+        stop,
+    )))
+}
+
+async fn run_writer(
+    rest: Arc<Rest>,
+    mut rx: mpsc::Receiver<Update>,
+    mut shutdown_rx: broadcast::Receiver<()>,
+    stop: Arc<AtomicBool>,
+) {
+    let mut entities: HashMap<String, Entity> = HashMap::new();
+    let mut failing = false;
+    loop {
+        let first = tokio::select! {
+            _ = shutdown_rx.recv() => break,
+            update = rx.recv() => match update {
+                Some(update) => update,
+                None => break,
+            },
+        };
+
+        // Coalesce everything already queued into one batch.
+        let mut dirty = HashSet::new();
+        let mut events = Vec::new();
+        let mut next = Some(first);
+        while let Some(update) = next {
+            match update {
+                Update::State { name, value } => {
+                    entities.entry(name.clone()).or_default().state = Some(value);
+                    dirty.insert(name);
+                }
+                Update::Attributes { name, attributes } => {
+                    entities.entry(name.clone()).or_default().attributes =
+                        attributes.as_object().cloned().unwrap_or_default();
+                    dirty.insert(name);
+                }
+                Update::Event(event) => events.push(event),
+                Update::Resync => dirty.extend(entities.keys().cloned()),
+            }
+            next = rx.try_recv().ok();
+        }
+        let bodies: Vec<(String, Value)> = dirty
+            .into_iter()
+            .map(|name| {
+                let body = rest.entity_body(&name, &entities[&name]);
+                (name, body)
+            })
+            .collect();
+
+        let rest = Arc::clone(&rest);
+        let result = tokio::task::spawn_blocking(move || rest.write(&bodies, &events)).await;
+        match result {
+            Ok(Ok(())) if failing => {
+                info!("Home Assistant API writes recovered");
+                failing = false;
+            }
+            Ok(Ok(())) => {}
+            Ok(Err(e)) if !failing => {
+                // Logged once per outage: sensors publish every few seconds.
+                warn!("Home Assistant API write failed: {e}");
+                failing = true;
+            }
+            Ok(Err(e)) => debug!("Home Assistant API write failed: {e}"),
+            Err(e) => warn!("Home Assistant API writer panicked: {e}"),
+        }
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    // REST-written states outlive the agent; mark them unavailable like the
+    // MQTT last will does.
+    let bodies: Vec<(String, Value)> = entities
+        .keys()
+        .map(|name| {
+            let gone = Entity {
+                state: Some("unavailable".to_string()),
+                attributes: serde_json::Map::new(),
+            };
+            (name.clone(), rest.entity_body(name, &gone))
+        })
+        .collect();
+    let _ = tokio::task::spawn_blocking(move || rest.write(&bodies, &[])).await;
+}
+
+/// WebSocket side: authenticates, subscribes to `pc_bridge_command` and
+/// feeds matching events into the command channel.
+struct Listener {
+    endpoint: Endpoint,
+    token: String,
+    device_name: String,
+    commands: mpsc::Sender<Command>,
+    updates: mpsc::Sender<Update>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Listener {
+    fn run(self) {
+        let mut backoff_secs = 1;
+        while !self.stop.load(Ordering::Relaxed) {
+            if let Err(e) = self.listen(&mut backoff_secs) {
+                warn!("Home Assistant WebSocket: {e} - retrying in {backoff_secs}s");
+            }
+            for _ in 0..backoff_secs {
+                if self.stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                std::thread::sleep(Duration::from_secs(1));
+            }
+            backoff_secs = (backoff_secs * 2).min(MAX_BACKOFF_SECS);
+        }
+    }
+
+    /// One connection, until it fails or the agent stops.
+    fn listen(&self, backoff_secs: &mut u64) -> std::io::Result<()> {
+        let mut ws = WebSocket::connect(&self.endpoint, "/api/websocket")?;
+
+        let hello = Self::expect_message(&mut ws)?;
+        if hello["type"] != "auth_required" {
+            return Err(std::io::Error::other(format!(
+                "unexpected greeting: {hello}"
+            )));
+        }
+        ws.send_text(&json!({ "type": "auth", "access_token": self.token }).to_string())?;
+        let reply = Self::expect_message(&mut ws)?;
+        match reply["type"].as_str() {
+            Some("auth_ok") => {}
+            Some("auth_invalid") => {
+                return Err(std::io::Error::other(format!(
+                    "access token rejected ({})",
+                    reply["message"].as_str().unwrap_or("auth_invalid")
+                )));
+            }
+            _ => {
+                return Err(std::io::Error::other(format!(
+                    "unexpected auth reply: {reply}"
+                )));
+            }
+        }
+        ws.send_text(
+            &json!({ "id": 1, "type": "subscribe_events", "event_type": COMMAND_EVENT })
+                .to_string(),
+        )?;
+        info!("Home Assistant WebSocket connected - listening for {COMMAND_EVENT}");
+        *backoff_secs = 1;
+        // A (re)connect usually means HA (re)started and forgot our states.
+        let _ = self.updates.blocking_send(Update::Resync);
+
+        let mut next_id = 2u64;
+        let mut awaiting_pong = false;
+        while !self.stop.load(Ordering::Relaxed) {
+            let Some(text) = ws.read_text()? else {
+                if awaiting_pong {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "no reply to ping",
+                    ));
+                }
+                ws.send_text(&json!({ "id": next_id, "type": "ping" }).to_string())?;
+                next_id += 1;
+                awaiting_pong = true;
+                continue;
+            };
+            awaiting_pong = false;
+            if let Some(cmd) = parse_command(&text, &self.device_name) {
+                info!("Command from Home Assistant API: {}", cmd.name);
+                // Same policy as the MQTT event loop: drop rather than block.
+                if self.commands.try_send(cmd).is_err() {
+                    warn!("Command channel full or closed - dropping command");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Next message, treating an idle timeout during the handshake as an error.
+    fn expect_message(ws: &mut WebSocket) -> std::io::Result<Value> {
+        let text = ws.read_text()?.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::TimedOut, "no reply from Home Assistant")
+        })?;
+        serde_json::from_str(&text).map_err(std::io::Error::other)
+    }
+}
+
+/// Command carried by a `pc_bridge_command` event addressed to this device.
+/// `payload` defaults to "PRESS" (what an MQTT button sends) and may be any
+/// JSON value - non-strings are passed on serialized, e.g. for `notification`.
+fn parse_command(text: &str, device_name: &str) -> Option<Command> {
+    let message: Value = serde_json::from_str(text).ok()?;
+    if message["type"] != "event" || message["event"]["event_type"] != COMMAND_EVENT {
+        return None;
+    }
+    let data = &message["event"]["data"];
+    if data["device"].as_str()? != device_name {
+        return None;
+    }
+    let name = data["command"].as_str()?.to_string();
+    let payload = match &data["payload"] {
+        Value::Null => "PRESS".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    Some(Command { name, payload })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(data: &Value) -> String {
+        json!({
+            "id": 1,
+            "type": "event",
+            "event": { "event_type": COMMAND_EVENT, "data": data, "origin": "LOCAL" }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_parse_command_filters_by_device() {
+        let cmd =
+            parse_command(&event(&json!({ "device": "pc", "command": "Sleep" })), "pc").unwrap();
+        assert_eq!(
+            (cmd.name.as_str(), cmd.payload.as_str()),
+            ("Sleep", "PRESS")
+        );
+
+        let cmd = parse_command(
+            &event(&json!({
+                "device": "pc",
+                "command": "notification",
+                "payload": { "title": "Hi", "message": "there" }
+            })),
+            "pc",
+        )
+        .unwrap();
+        assert_eq!(cmd.name, "notification");
+        let payload: Value = serde_json::from_str(&cmd.payload).unwrap();
+        assert_eq!(payload["title"], "Hi");
+
+        let cmd = parse_command(
+            &event(&json!({ "device": "pc", "command": "Launch", "payload": "steam:570" })),
+            "pc",
+        )
+        .unwrap();
+        assert_eq!(cmd.payload, "steam:570");
+
+        // Other PCs, missing fields and other messages are ignored.
+        assert!(
+            parse_command(
+                &event(&json!({ "device": "laptop", "command": "Sleep" })),
+                "pc"
+            )
+            .is_none()
+        );
+        assert!(parse_command(&event(&json!({ "command": "Sleep" })), "pc").is_none());
+        assert!(parse_command(&event(&json!({ "device": "pc" })), "pc").is_none());
+        assert!(parse_command(r#"{"id":2,"type":"pong"}"#, "pc").is_none());
+        assert!(parse_command("not json", "pc").is_none());
+    }
+
+    #[test]
+    fn test_entity_id_and_truncate() {
+        assert_eq!(
+            entity_id("gaming_pc", "cpu_usage"),
+            "sensor.gaming_pc_cpu_usage"
+        );
+        assert_eq!(
+            entity_id("pc", "HWiNFO CPU-Temp"),
+            "sensor.pc_hwinfo_cpu_temp"
+        );
+        assert_eq!(truncate("short", MAX_STATE_LEN), "short");
+        let long = "é".repeat(200);
+        let cut = truncate(&long, MAX_STATE_LEN);
+        assert!(cut.len() <= MAX_STATE_LEN && long.starts_with(cut));
+    }
+}
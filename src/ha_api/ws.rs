@@ -0,0 +1,467 @@
+//! Minimal blocking WebSocket client (RFC 6455) for Home Assistant's API.
+//!
+//! Just enough for HA's JSON protocol: text messages, client-side masking,
+//! ping/pong and close. Like `power::sync_mqtt`, it's hand-rolled over std +
+//! native-tls instead of pulling a WebSocket stack into the binary for one
+//! long-lived socket.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// How long a read may sit idle before `read_text` returns `Ok(None)`, giving
+/// the caller a chance to ping and to notice shutdown.
+pub(super) const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Largest message we'll buffer. HA's event messages are a few KB.
+const MAX_MESSAGE: u64 = 1024 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Host/port/TLS (and reverse-proxy sub-path) parsed from `ha_api.url`.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Endpoint {
+    pub(super) host: String,
+    pub(super) port: u16,
+    pub(super) tls: bool,
+    /// "" or "/sub/path" when HA is served below the site root
+    pub(super) base_path: String,
+}
+
+impl Endpoint {
+    /// Parse `http(s)://host[:port][/path]`. `[v6]` hosts are supported.
+    pub(super) fn parse(url: &str) -> Option<Self> {
+        let (rest, tls) = match url.strip_prefix("https://") {
+            Some(rest) => (rest, true),
+            None => (url.strip_prefix("http://")?, false),
+        };
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let default_port = if tls { 443 } else { 80 };
+        let (host, port) = if let Some(after_open) = authority.strip_prefix('[') {
+            let (host, tail) = after_open.split_once(']')?;
+            let port = match tail.strip_prefix(':') {
+                Some(p) => p.parse().ok()?,
+                None => default_port,
+            };
+            (host, port)
+        } else if let Some((host, port)) = authority.rsplit_once(':') {
+            (host, port.parse().ok()?)
+        } else {
+            (authority, default_port)
+        };
+        if host.is_empty() {
+            return None;
+        }
+        let path = path.trim_matches('/');
+        Some(Self {
+            host: host.to_string(),
+            port,
+            tls,
+            base_path: if path.is_empty() {
+                String::new()
+            } else {
+                format!("/{path}")
+            },
+        })
+    }
+
+    fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        if self.port == if self.tls { 443 } else { 80 } {
+            host
+        } else {
+            format!("{host}:{}", self.port)
+        }
+    }
+}
+
+trait Stream: Read + Write + Send {}
+impl<T: Read + Write + Send> Stream for T {}
+
+pub(super) struct WebSocket {
+    stream: Box<dyn Stream>,
+    /// xorshift state for frame masks and the handshake nonce. Masking only
+    /// has to be unpredictable to scripts in a browser, not cryptographically
+    /// strong, so this avoids a dependency on an RNG crate.
+    rng: u64,
+}
+
+impl WebSocket {
+    /// Connect and perform the HTTP upgrade for `path` (below the base path).
+    pub(super) fn connect(endpoint: &Endpoint, path: &str) -> io::Result<Self> {
+        let timeout = Duration::from_secs(10);
+        let addr = (endpoint.host.as_str(), endpoint.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::other(format!("no address for {}", endpoint.host)))?;
+        let tcp = TcpStream::connect_timeout(&addr, timeout)?;
+        tcp.set_write_timeout(Some(timeout))?;
+        tcp.set_read_timeout(Some(IDLE_TIMEOUT))?;
+        tcp.set_nodelay(true)?;
+
+        let stream: Box<dyn Stream> = if endpoint.tls {
+            let connector = native_tls::TlsConnector::new()
+                .map_err(|e| io::Error::other(format!("TLS init failed: {e}")))?;
+            let tls = connector
+                .connect(&endpoint.host, tcp)
+                .map_err(|e| io::Error::other(format!("TLS handshake failed: {e}")))?;
+            Box::new(tls)
+        } else {
+            Box::new(tcp)
+        };
+
+        let mut ws = Self::from_stream(stream);
+        ws.handshake(endpoint, path)?;
+        Ok(ws)
+    }
+
+    fn from_stream(stream: Box<dyn Stream>) -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self {
+            stream,
+            // xorshift must never start at 0
+            rng: nanos | 1,
+        }
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    fn handshake(&mut self, endpoint: &Endpoint, path: &str) -> io::Result<()> {
+        let mut nonce = [0u8; 16];
+        nonce[..8].copy_from_slice(&self.next_random().to_le_bytes());
+        nonce[8..].copy_from_slice(&self.next_random().to_le_bytes());
+        let request = format!(
+            "GET {}{path} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            endpoint.base_path,
+            endpoint.host_header(),
+            base64(&nonce)
+        );
+        self.stream.write_all(request.as_bytes())?;
+        self.stream.flush()?;
+
+        // Read the response head byte by byte: anything after the blank line
+        // is already WebSocket framing and must stay in the stream.
+        let mut head = Vec::new();
+        let mut byte = [0u8; 1];
+        while !head.ends_with(b"\r\n\r\n") {
+            self.stream.read_exact(&mut byte)?;
+            head.push(byte[0]);
+            if head.len() > 8192 {
+                return Err(io::Error::other("oversized HTTP upgrade response"));
+            }
+        }
+        let head = String::from_utf8_lossy(&head);
+        let status = head.lines().next().unwrap_or_default();
+        if status.split_whitespace().nth(1) != Some("101") {
+            return Err(io::Error::other(format!("upgrade refused: {status}")));
+        }
+        Ok(())
+    }
+
+    pub(super) fn send_text(&mut self, text: &str) -> io::Result<()> {
+        self.write_frame(OP_TEXT, text.as_bytes())
+    }
+
+    fn write_frame(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+        let mut frame = Vec::with_capacity(payload.len() + 14);
+        frame.push(0x80 | opcode); // FIN: we never fragment
+        let len = payload.len();
+        if len < 126 {
+            frame.push(0x80 | len as u8);
+        } else if let Ok(len) = u16::try_from(len) {
+            frame.push(0x80 | 0x7E); // 16-bit length follows
+            frame.extend_from_slice(&len.to_be_bytes());
+        } else {
+            frame.push(0x80 | 0x7F); // 64-bit length follows
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+        let mask = (self.next_random() as u32).to_be_bytes();
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        self.stream.write_all(&frame)?;
+        self.stream.flush()
+    }
+
+    /// Next complete text message, answering pings on the way. `Ok(None)`
+    /// when nothing arrived within `IDLE_TIMEOUT`; an error once the
+    /// connection is unusable (including a close frame from the server).
+    pub(super) fn read_text(&mut self) -> io::Result<Option<String>> {
+        let mut message = Vec::new();
+        loop {
+            let mut header = [0u8; 2];
+            // Only a timeout before the first byte of a message is benign;
+            // mid-frame the stream position is lost, so that's an error.
+            match self.stream.read(&mut header[..1]) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(_) => {}
+                Err(e)
+                    if message.is_empty()
+                        && matches!(
+                            e.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) =>
+                {
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            }
+            self.stream.read_exact(&mut header[1..])?;
+            let fin = header[0] & 0x80 != 0;
+            let opcode = header[0] & 0x0F;
+            let masked = header[1] & 0x80 != 0;
+            let len = match header[1] & 0x7F {
+                126 => {
+                    let mut b = [0u8; 2];
+                    self.stream.read_exact(&mut b)?;
+                    u64::from(u16::from_be_bytes(b))
+                }
+                127 => {
+                    let mut b = [0u8; 8];
+                    self.stream.read_exact(&mut b)?;
+                    u64::from_be_bytes(b)
+                }
+                n => u64::from(n),
+            };
+            if len + message.len() as u64 > MAX_MESSAGE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "WebSocket message too large",
+                ));
+            }
+            let mut mask = [0u8; 4];
+            if masked {
+                self.stream.read_exact(&mut mask)?;
+            }
+            let mut payload = vec![0u8; len as usize];
+            self.stream.read_exact(&mut payload)?;
+            if masked {
+                for (i, b) in payload.iter_mut().enumerate() {
+                    *b ^= mask[i % 4];
+                }
+            }
+
+            match opcode {
+                OP_TEXT | OP_CONTINUATION => {
+                    message.extend_from_slice(&payload);
+                    if fin {
+                        return String::from_utf8(message)
+                            .map(Some)
+                            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+                    }
+                }
+                OP_PING => self.write_frame(OP_PONG, &payload)?,
+                OP_PONG => {}
+                OP_CLOSE => {
+                    // Echo the close (best effort) before reporting it.
+                    let _ = self.write_frame(OP_CLOSE, &payload);
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionAborted,
+                        "server closed the WebSocket",
+                    ));
+                }
+                other => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unexpected WebSocket opcode {other:#x}"),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Standard base64 with padding (only used for the 16-byte handshake nonce).
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Reads scripted server bytes, records what the client writes.
+    struct Duplex {
+        input: Cursor<Vec<u8>>,
+        output: std::sync::Arc<std::sync::Mutex<Vec<u8>>>,
+    }
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.input.read(buf)?;
+            if n == 0 && !buf.is_empty() {
+                // Script exhausted: behave like an idle socket.
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            Ok(n)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn socket(input: Vec<u8>) -> (WebSocket, std::sync::Arc<std::sync::Mutex<Vec<u8>>>) {
+        let output = std::sync::Arc::default();
+        let duplex = Duplex {
+            input: Cursor::new(input),
+            output: std::sync::Arc::clone(&output),
+        };
+        (WebSocket::from_stream(Box::new(duplex)), output)
+    }
+
+    /// Unmasked server frame.
+    fn server_frame(fin: bool, opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mut f = vec![if fin { 0x80 } else { 0 } | opcode];
+        if payload.len() < 126 {
+            f.push(payload.len() as u8);
+        } else {
+            f.push(126);
+            f.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        }
+        f.extend_from_slice(payload);
+        f
+    }
+
+    /// Decode one masked client frame: (opcode, payload).
+    fn client_frame(bytes: &[u8]) -> (u8, Vec<u8>) {
+        assert_eq!(bytes[0] & 0x80, 0x80, "client frames are never fragmented");
+        assert_eq!(bytes[1] & 0x80, 0x80, "client frames must be masked");
+        let (len, at) = match bytes[1] & 0x7F {
+            126 => (u16::from_be_bytes([bytes[2], bytes[3]]) as usize, 4),
+            n => (n as usize, 2),
+        };
+        let mask = &bytes[at..at + 4];
+        let payload = bytes[at + 4..at + 4 + len]
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b ^ mask[i % 4])
+            .collect();
+        (bytes[0] & 0x0F, payload)
+    }
+
+    #[test]
+    fn test_endpoint_parse() {
+        let e = Endpoint::parse("http://homeassistant.local:8123").unwrap();
+        assert_eq!(
+            (e.host.as_str(), e.port, e.tls, e.base_path.as_str()),
+            ("homeassistant.local", 8123, false, "")
+        );
+        assert_eq!(e.host_header(), "homeassistant.local:8123");
+
+        let e = Endpoint::parse("https://ha.example.com/proxy/ha/").unwrap();
+        assert_eq!(
+            (e.port, e.tls, e.base_path.as_str()),
+            (443, true, "/proxy/ha")
+        );
+        assert_eq!(e.host_header(), "ha.example.com");
+
+        let e = Endpoint::parse("http://[fd00::2]:8123").unwrap();
+        assert_eq!((e.host.as_str(), e.port), ("fd00::2", 8123));
+        assert_eq!(e.host_header(), "[fd00::2]:8123");
+
+        assert!(Endpoint::parse("ws://ha:8123").is_none());
+        assert!(Endpoint::parse("http://:8123").is_none());
+        assert!(Endpoint::parse("http://ha:port").is_none());
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(&[0xff; 16]), "/////////////////////w==");
+    }
+
+    #[test]
+    fn test_send_text_is_masked() {
+        let (mut ws, output) = socket(Vec::new());
+        let long = "x".repeat(300);
+        ws.send_text(&long).unwrap();
+        let (opcode, payload) = client_frame(&output.lock().unwrap());
+        assert_eq!(opcode, OP_TEXT);
+        assert_eq!(payload, long.as_bytes());
+    }
+
+    #[test]
+    fn test_read_fragmented_text_and_answer_ping() {
+        let mut input = server_frame(false, OP_TEXT, b"{\"type\":");
+        input.extend(server_frame(true, OP_PING, b"hi"));
+        input.extend(server_frame(true, OP_CONTINUATION, b"\"auth_ok\"}"));
+        let (mut ws, output) = socket(input);
+
+        assert_eq!(
+            ws.read_text().unwrap().as_deref(),
+            Some("{\"type\":\"auth_ok\"}")
+        );
+        assert_eq!(
+            client_frame(&output.lock().unwrap()),
+            (OP_PONG, b"hi".to_vec())
+        );
+        // Nothing left: idle, not an error.
+        assert_eq!(ws.read_text().unwrap(), None);
+    }
+
+    #[test]
+    fn test_close_frame_is_an_error() {
+        let (mut ws, _) = socket(server_frame(true, OP_CLOSE, &[0x03, 0xE8]));
+        assert_eq!(
+            ws.read_text().unwrap_err().kind(),
+            io::ErrorKind::ConnectionAborted
+        );
+    }
+
+    #[test]
+    fn test_handshake_requires_101() {
+        let endpoint = Endpoint::parse("http://ha:8123").unwrap();
+        let mut input = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\r\n".to_vec();
+        input.extend(server_frame(true, OP_TEXT, b"{}"));
+        let (mut ws, output) = socket(input);
+        ws.handshake(&endpoint, "/api/websocket").unwrap();
+        let request = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert!(request.starts_with("GET /api/websocket HTTP/1.1\r\nHost: ha:8123\r\n"));
+        // The first frame right after the response head is left intact.
+        assert_eq!(ws.read_text().unwrap().as_deref(), Some("{}"));
+
+        let (mut ws, _) = socket(b"HTTP/1.1 404 Not Found\r\n\r\n".to_vec());
+        assert!(ws.handshake(&endpoint, "/api/websocket").is_err());
+    }
+}
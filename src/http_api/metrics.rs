@@ -0,0 +1,163 @@
+//! Prometheus text exposition for `GET /metrics`
+//!
+//! Rendered from the same latest-value cache `/status` serves, so metrics
+//! cost nothing between scrapes and always match what HA was sent.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+pub(super) const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Bridge self-metrics.
+pub(super) struct Bridge {
+    pub(super) device: String,
+    pub(super) uptime_secs: u64,
+    /// `None` when running without a broker
+    pub(super) mqtt_connected: Option<bool>,
+}
+
+/// Numeric value of a sensor state: numbers as-is, binary states as 1/0,
+/// anything else (names, timestamps) has no sample.
+fn sample(value: &str) -> Option<f64> {
+    match value {
+        "on" | "true" | "online" => Some(1.0),
+        "off" | "false" | "offline" => Some(0.0),
+        _ => value.parse::<f64>().ok().filter(|v| v.is_finite()),
+    }
+}
+
+/// Label value escaping per the exposition format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} gauge");
+}
+
+pub(super) fn render(sensors: &BTreeMap<String, String>, bridge: &Bridge) -> String {
+    let mut out = String::with_capacity(2048);
+
+    header(
+        &mut out,
+        "pc_bridge_sensor",
+        "Latest value of each numeric or on/off sensor.",
+    );
+    for (name, value) in sensors {
+        if let Some(v) = sample(value) {
+            let _ = writeln!(out, "pc_bridge_sensor{{sensor=\"{}\"}} {v}", escape(name));
+        }
+    }
+
+    // `runninggames` is "none" or comma-separated game ids.
+    let games: Vec<&str> = sensors
+        .get("runninggames")
+        .map(|s| s.as_str())
+        .filter(|s| *s != "none" && !s.is_empty())
+        .map(|s| s.split(',').collect())
+        .unwrap_or_default();
+    header(
+        &mut out,
+        "pc_bridge_games_running",
+        "Number of running games.",
+    );
+    let _ = writeln!(out, "pc_bridge_games_running {}", games.len());
+    header(
+        &mut out,
+        "pc_bridge_game_running",
+        "Set to 1 for each game currently running.",
+    );
+    for game in games {
+        let _ = writeln!(out, "pc_bridge_game_running{{game=\"{}\"}} 1", escape(game));
+    }
+
+    header(&mut out, "pc_bridge_info", "Agent version and device name.");
+    let _ = writeln!(
+        out,
+        "pc_bridge_info{{version=\"{}\",device=\"{}\"}} 1",
+        env!("CARGO_PKG_VERSION"),
+        escape(&bridge.device)
+    );
+    header(
+        &mut out,
+        "pc_bridge_uptime_seconds",
+        "Seconds since the agent started.",
+    );
+    let _ = writeln!(out, "pc_bridge_uptime_seconds {}", bridge.uptime_secs);
+    if let Some(connected) = bridge.mqtt_connected {
+        header(
+            &mut out,
+            "pc_bridge_mqtt_connected",
+            "Whether the MQTT broker connection is up.",
+        );
+        let _ = writeln!(out, "pc_bridge_mqtt_connected {}", u8::from(connected));
+    }
+    header(
+        &mut out,
+        "pc_bridge_sensors_published",
+        "Sensors that have published a value since startup.",
+    );
+    let _ = writeln!(out, "pc_bridge_sensors_published {}", sensors.len());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let sensors: BTreeMap<String, String> = [
+            ("cpu_usage", "12.5"),
+            ("display", "on"),
+            ("active_window", "Visual \"Studio\" Code"),
+            ("lastactive", "2026-10-15T10:00:00Z"),
+            ("runninggames", "cs2,dota2"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let bridge = Bridge {
+            device: "gaming-pc".to_string(),
+            uptime_secs: 42,
+            mqtt_connected: Some(true),
+        };
+        let text = render(&sensors, &bridge);
+        let lines: Vec<&str> = text.lines().filter(|l| !l.starts_with('#')).collect();
+
+        assert!(lines.contains(&"pc_bridge_sensor{sensor=\"cpu_usage\"} 12.5"));
+        assert!(lines.contains(&"pc_bridge_sensor{sensor=\"display\"} 1"));
+        assert!(!text.contains("sensor=\"active_window\""));
+        assert!(!text.contains("sensor=\"lastactive\""));
+        assert!(lines.contains(&"pc_bridge_games_running 2"));
+        assert!(lines.contains(&"pc_bridge_game_running{game=\"cs2\"} 1"));
+        assert!(lines.contains(&"pc_bridge_game_running{game=\"dota2\"} 1"));
+        assert!(lines.contains(&"pc_bridge_uptime_seconds 42"));
+        assert!(lines.contains(&"pc_bridge_mqtt_connected 1"));
+        assert!(text.contains("device=\"gaming-pc\"} 1\n"));
+        // Every sample line belongs to a declared metric.
+        for line in lines {
+            let name = line.split(['{', ' ']).next().unwrap();
+            assert!(text.contains(&format!("# TYPE {name} gauge")), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_no_games_and_no_broker() {
+        let sensors = BTreeMap::from([("runninggames".to_string(), "none".to_string())]);
+        let bridge = Bridge {
+            device: "pc".to_string(),
+            uptime_secs: 1,
+            mqtt_connected: None,
+        };
+        let text = render(&sensors, &bridge);
+        assert!(text.contains("\npc_bridge_games_running 0\n"));
+        assert!(!text.contains("pc_bridge_game_running{"));
+        assert!(!text.contains("pc_bridge_mqtt_connected"));
+        assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
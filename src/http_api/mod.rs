@@ -0,0 +1,504 @@
+//! Local HTTP status/control endpoint (`http_api`)
+//!
+//! A deliberately tiny HTTP/1.1 server - one request per connection, JSON in
+//! and out - for curl, non-HA systems and debugging without an MQTT client:
+//! - `GET /health`: liveness, version, uptime and broker connection (no auth)
+//! - `GET /status`: the above plus the latest value of every sensor
+//! - `POST /command`: `{"command": "Sleep", "payload": "..."}`, queued on the
+//!   same executor (and through the same allow-lists) as MQTT button presses
+//! - `GET /metrics`: Prometheus exposition (with `http_api.metrics`)
+//!
+//! `/status`, `/command` and `/metrics` always need `Authorization: Bearer
+//! <token>`; a token is generated into userConfig.json on first start when
+//! `http_api.token` is unset. Since any web page can reach a loopback port,
+//! requests carrying an `Origin` header (browser cross-origin fetches) are
+//! refused, a loopback bind only answers a loopback `Host` (DNS rebinding),
+//! and POSTs must be `Content-Type: application/json`, which a page can't
+//! send without a CORS preflight this server never answers.
+
+mod metrics;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde_json::{Value, json};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::AppState;
+use crate::config::Config;
+use crate::mqtt::Command;
+
+/// Request head limit; anything bigger isn't a client we serve.
+const MAX_HEAD: usize = 8 * 1024;
+/// `POST /command` bodies are a few hundred bytes (notification payloads).
+const MAX_BODY: usize = 64 * 1024;
+/// Whole-request deadline, so an idle connection can't pin a task.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, PartialEq)]
+struct Request {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    origin: Option<String>,
+    host: Option<String>,
+    content_type: Option<String>,
+    content_length: usize,
+    /// Bytes of the head, including the blank line
+    head_len: usize,
+}
+
+/// Serve until shutdown. Bind failures are logged, not fatal: the agent is
+/// fully functional without the endpoint.
+pub async fn run(state: Arc<AppState>) {
+    let (bind, token) = {
+        let config = state.config.read().await;
+        (config.http_api.bind.clone(), config.http_api.token.clone())
+    };
+    if token.is_empty() {
+        match tokio::task::spawn_blocking(persist_new_token).await {
+            Ok(Ok(token)) => {
+                info!("HTTP API: generated a bearer token (http_api.token in userConfig.json)");
+                state.config.write().await.http_api.token = token;
+            }
+            Ok(Err(e)) => {
+                warn!("HTTP API: failed to save a generated token, not serving: {e}");
+                return;
+            }
+            Err(e) => {
+                warn!("HTTP API: token generation panicked, not serving: {e}");
+                return;
+            }
+        }
+    }
+    let listener = match TcpListener::bind(&bind).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("HTTP API: failed to listen on {bind}: {e}");
+            return;
+        }
+    };
+    info!("HTTP API listening on http://{bind}");
+
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    loop {
+        let (stream, peer) = tokio::select! {
+            _ = shutdown_rx.recv() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    debug!("HTTP API accept failed: {e}");
+                    continue;
+                }
+            },
+        };
+        let state = Arc::clone(&state);
+        tokio::spawn(async move {
+            if tokio::time::timeout(REQUEST_TIMEOUT, serve(&state, stream))
+                .await
+                .is_err()
+            {
+                debug!("HTTP API: request from {peer} timed out");
+            }
+        });
+    }
+}
+
+async fn serve(state: &AppState, mut stream: TcpStream) {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+    let request = loop {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+        match parse_request(&buf) {
+            Ok(Some(request)) => break request,
+            Ok(None) if buf.len() < MAX_HEAD => {}
+            Ok(None) | Err(()) => {
+                let _ = stream
+                    .write_all(&response(400, &json!({ "error": "bad request" })))
+                    .await;
+                return;
+            }
+        }
+    };
+    if request.content_length > MAX_BODY {
+        let _ = stream
+            .write_all(&response(413, &json!({ "error": "body too large" })))
+            .await;
+        return;
+    }
+    while buf.len() < request.head_len + request.content_length {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+    let body = &buf[request.head_len..request.head_len + request.content_length];
+
+    let reply = route(state, &request, body).await;
+    let _ = stream.write_all(&reply).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Give the config a random `http_api.token`, unless it got one meanwhile,
+/// and save it. Reloaded first so manual edits since startup aren't lost.
+/// Blocking: call via `spawn_blocking`.
+fn persist_new_token() -> anyhow::Result<String> {
+    let mut fresh = Config::load()?;
+    if fresh.http_api.token.is_empty() {
+        fresh.http_api.token = generate_token()?;
+        fresh.save()?;
+    }
+    Ok(fresh.http_api.token)
+}
+
+/// 32 random bytes from the OS, hex-encoded
+fn generate_token() -> anyhow::Result<String> {
+    use std::fmt::Write as _;
+
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).map_err(|e| anyhow::anyhow!("no OS randomness: {e}"))?;
+    Ok(bytes.iter().fold(String::with_capacity(64), |mut hex, b| {
+        let _ = write!(hex, "{b:02x}");
+        hex
+    }))
+}
+
+/// Complete HTTP response for `request`.
+async fn route(state: &AppState, request: &Request, body: &[u8]) -> Vec<u8> {
+    let (token, metrics_enabled, loopback) = {
+        let config = state.config.read().await;
+        let loopback = config
+            .http_api
+            .bind
+            .parse::<std::net::SocketAddr>()
+            .is_ok_and(|addr| addr.ip().is_loopback());
+        (
+            config.http_api.token.clone(),
+            config.http_api.metrics,
+            loopback,
+        )
+    };
+    if let Some(refusal) = refuse(request, loopback) {
+        return refusal;
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/health") => response(200, &health(state).await),
+        ("GET", "/metrics") if !metrics_enabled => response(404, &json!({ "error": "not found" })),
+        ("GET", "/status" | "/metrics") | ("POST", "/command")
+            if !authorized(&token, request.authorization.as_deref()) =>
+        {
+            response(401, &json!({ "error": "missing or invalid bearer token" }))
+        }
+        ("GET", "/status") => {
+            let mut status = health(state).await;
+            status["device"] = state.config.read().await.device_name.clone().into();
+            status["sensors"] = json!(state.mqtt.sensor_values());
+            response(200, &status)
+        }
+        ("GET", "/metrics") => {
+            let bridge = metrics::Bridge {
+                device: state.config.read().await.device_name.clone(),
+                uptime_secs: state.start_time.elapsed().as_secs(),
+                mqtt_connected: mqtt_state(state).await,
+            };
+            let text = metrics::render(&state.mqtt.sensor_values(), &bridge);
+            text_response(200, metrics::CONTENT_TYPE, &text)
+        }
+        ("POST", "/command") => {
+            let Some(cmd) = parse_command_body(body) else {
+                return response(
+                    400,
+                    &json!({ "error": "expected {\"command\": \"...\", \"payload\": ...}" }),
+                );
+            };
+            info!("Command from HTTP API: {}", cmd.name);
+            let name = cmd.name.clone();
+            // Same policy as the MQTT event loop: refuse rather than block.
+            match state.mqtt.command_sender().try_send(cmd) {
+                Ok(()) => response(202, &json!({ "queued": name })),
+                Err(_) => response(503, &json!({ "error": "command queue full" })),
+            }
+        }
+        (_, "/health" | "/status" | "/command" | "/metrics") => {
+            response(405, &json!({ "error": "method not allowed" }))
+        }
+        _ => response(404, &json!({ "error": "not found" })),
+    }
+}
+
+/// The response for a request a web page could have sent, if it's one: see
+/// the module docs.
+fn refuse(request: &Request, loopback: bool) -> Option<Vec<u8>> {
+    if request.origin.is_some() {
+        return Some(response(
+            403,
+            &json!({ "error": "cross-origin requests are refused" }),
+        ));
+    }
+    if loopback && !request.host.as_deref().is_none_or(is_loopback_host) {
+        return Some(response(403, &json!({ "error": "unexpected Host" })));
+    }
+    let json_body = request.content_type.as_deref().is_some_and(|ct| {
+        ct.split(';')
+            .next()
+            .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+    });
+    if request.method == "POST" && !json_body {
+        return Some(response(
+            415,
+            &json!({ "error": "Content-Type must be application/json" }),
+        ));
+    }
+    None
+}
+
+/// `localhost`, `127.0.0.1`, `[::1]` and the like, with or without a port
+fn is_loopback_host(host: &str) -> bool {
+    let name = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or_default(),
+        None => host.rsplit_once(':').map_or(host, |(name, _)| name),
+    };
+    name.eq_ignore_ascii_case("localhost")
+        || name
+            .parse::<std::net::IpAddr>()
+            .is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Broker connection state; `None` when running without a broker.
+async fn mqtt_state(state: &AppState) -> Option<bool> {
+    if state.config.read().await.mqtt.broker.is_empty() {
+        None
+    } else {
+        Some(state.mqtt.is_connected())
+    }
+}
+
+async fn health(state: &AppState) -> Value {
+    let mqtt = match mqtt_state(state).await {
+        None => "disabled",
+        Some(true) => "connected",
+        Some(false) => "disconnected",
+    };
+    json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_secs": state.start_time.elapsed().as_secs(),
+        "mqtt": mqtt,
+    })
+}
+
+/// `Ok(None)` until the head is complete; `Err` on a malformed head.
+fn parse_request(buf: &[u8]) -> Result<Option<Request>, ()> {
+    let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return Ok(None);
+    };
+    let head = std::str::from_utf8(&buf[..end]).map_err(|_| ())?;
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().ok_or(())?.split(' ');
+    let method = request_line.next().ok_or(())?.to_string();
+    let target = request_line.next().ok_or(())?;
+    if !request_line
+        .next()
+        .is_some_and(|v| v.starts_with("HTTP/1."))
+    {
+        return Err(());
+    }
+    let path = target.split('?').next().unwrap_or_default().to_string();
+
+    let mut authorization = None;
+    let mut origin = None;
+    let mut host = None;
+    let mut content_type = None;
+    let mut content_length = 0;
+    for line in lines {
+        let (name, value) = line.split_once(':').ok_or(())?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("origin") {
+            origin = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("host") {
+            host = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value.to_string());
+        } else if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse().map_err(|_| ())?;
+        }
+    }
+    Ok(Some(Request {
+        method,
+        path,
+        authorization,
+        origin,
+        host,
+        content_type,
+        content_length,
+        head_len: end + 4,
+    }))
+}
+
+/// An empty token (never generated) authorizes nothing. Compared without
+/// early exit so response timing doesn't leak a prefix.
+fn authorized(token: &str, authorization: Option<&str>) -> bool {
+    if token.is_empty() {
+        return false;
+    }
+    let Some(given) = authorization.and_then(|a| a.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// `payload` is optional ("PRESS", like an MQTT button) and may be any JSON
+/// value - non-strings are passed on serialized, e.g. for `notification`.
+fn parse_command_body(body: &[u8]) -> Option<Command> {
+    let body: Value = serde_json::from_slice(body).ok()?;
+    let name = body["command"]
+        .as_str()
+        .filter(|s| !s.is_empty())?
+        .to_string();
+    let payload = match &body["payload"] {
+        Value::Null => "PRESS".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    Some(Command { name, payload })
+}
+
+/// JSON response.
+fn response(status: u16, body: &Value) -> Vec<u8> {
+    text_response(status, "application/json", &body.to_string())
+}
+
+fn text_response(status: u16, content_type: &str, body: &str) -> Vec<u8> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Content Too Large",
+        415 => "Unsupported Media Type",
+        _ => "Service Unavailable",
+    };
+    format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_request() {
+        assert_eq!(
+            parse_request(b"GET /status HTTP/1.1\r\nHost: x\r\n"),
+            Ok(None)
+        );
+
+        let raw = b"POST /command?x=1 HTTP/1.1\r\nHost: pc:8765\r\nauthorization: Bearer abc\r\n\
+                    Content-Length: 19\r\n\r\n{\"command\":\"Sleep\"}";
+        let request = parse_request(raw).unwrap().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/command");
+        assert_eq!(request.authorization.as_deref(), Some("Bearer abc"));
+        assert_eq!(request.host.as_deref(), Some("pc:8765"));
+        assert_eq!(request.content_length, 19);
+        assert_eq!(&raw[request.head_len..], b"{\"command\":\"Sleep\"}");
+
+        assert!(parse_request(b"GET /\r\n\r\n").is_err());
+        assert!(parse_request(b"GET / HTTP/1.1\r\nContent-Length: lots\r\n\r\n").is_err());
+    }
+
+    #[test]
+    fn test_authorized() {
+        assert!(!authorized("", None));
+        assert!(!authorized("", Some("Bearer ")));
+        assert!(authorized("s3cret", Some("Bearer s3cret")));
+        assert!(!authorized("s3cret", Some("Bearer s3cre")));
+        assert!(!authorized("s3cret", Some("Bearer s3creT")));
+        assert!(!authorized("s3cret", Some("s3cret")));
+        assert!(!authorized("s3cret", None));
+    }
+
+    #[test]
+    fn test_refuse() {
+        let request = |head: &str| {
+            parse_request(format!("{head}\r\n\r\n").as_bytes())
+                .unwrap()
+                .unwrap()
+        };
+        let post = "POST /command HTTP/1.1\r\nHost: 127.0.0.1:8765";
+        let json = "Content-Type: application/json; charset=utf-8";
+        assert!(refuse(&request(&format!("{post}\r\n{json}")), true).is_none());
+        // A page's fetch/form
+        assert!(refuse(&request(post), true).is_some());
+        assert!(
+            refuse(
+                &request(&format!("{post}\r\nContent-Type: text/plain")),
+                true
+            )
+            .is_some()
+        );
+        assert!(
+            refuse(
+                &request(&format!("{post}\r\n{json}\r\nOrigin: https://evil.example")),
+                true
+            )
+            .is_some()
+        );
+        // DNS rebinding: a loopback socket reached under another name
+        let status = |host: &str| request(&format!("GET /status HTTP/1.1\r\nHost: {host}"));
+        assert!(refuse(&status("evil.example:8765"), true).is_some());
+        assert!(refuse(&status("evil.example:8765"), false).is_none());
+        for host in ["localhost:8765", "127.0.0.1", "[::1]:8765"] {
+            assert!(refuse(&status(host), true).is_none(), "{host}");
+        }
+    }
+
+    #[test]
+    fn test_generate_token() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 64);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(token, generate_token().unwrap());
+    }
+
+    #[test]
+    fn test_parse_command_body() {
+        let cmd = parse_command_body(br#"{"command": "Sleep"}"#).unwrap();
+        assert_eq!(
+            (cmd.name.as_str(), cmd.payload.as_str()),
+            ("Sleep", "PRESS")
+        );
+        let cmd = parse_command_body(br#"{"command": "notification", "payload": {"title": "Hi"}}"#)
+            .unwrap();
+        assert_eq!(cmd.payload, r#"{"title":"Hi"}"#);
+        assert!(parse_command_body(br#"{"command": ""}"#).is_none());
+        assert!(parse_command_body(b"Sleep").is_none());
+    }
+
+    #[test]
+    fn test_response() {
+        let raw = String::from_utf8(response(202, &json!({ "queued": "Sleep" }))).unwrap();
+        assert!(raw.starts_with("HTTP/1.1 202 Accepted\r\n"));
+        assert!(raw.contains("Content-Length: 18\r\n"));
+        assert!(raw.ends_with("\r\n\r\n{\"queued\":\"Sleep\"}"));
+    }
+}
@@ -0,0 +1,136 @@
+//! Translations
+//!
+//! The `language` setting picks the language of the names this agent gives
+//! its HA entities, its own toasts (game limits, thermal guard, shutdown
+//! guard, crash report) and the terminal setup wizard. Each locale is a flat
+//! JSON object in `assets/locales`, embedded at build time and keyed by the
+//! English text, gettext style: anything it doesn't list (names the user
+//! chose, strings not translated yet) stays English. `{name}` placeholders
+//! are filled in by [`tr_args`].
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use log::warn;
+
+/// Languages besides English: (code, native name, locale)
+const LOCALES: &[(&str, &str, &str)] = &[
+    ("de", "Deutsch", include_str!("../assets/locales/de.json")),
+    ("es", "Español", include_str!("../assets/locales/es.json")),
+    ("fr", "Français", include_str!("../assets/locales/fr.json")),
+];
+
+type Table = HashMap<String, String>;
+
+static TABLES: LazyLock<Vec<Table>> = LazyLock::new(|| {
+    LOCALES
+        .iter()
+        .map(|(code, _, json)| {
+            serde_json::from_str(json).unwrap_or_else(|e| {
+                warn!("Locale '{code}' is invalid, falling back to English: {e}");
+                Table::new()
+            })
+        })
+        .collect()
+});
+
+/// Index into `LOCALES` of the active language; None for English
+static ACTIVE: Mutex<Option<usize>> = Mutex::new(None);
+
+/// Every selectable language as (code, native name), English first
+pub fn languages() -> impl Iterator<Item = (&'static str, &'static str)> {
+    std::iter::once(("en", "English")).chain(LOCALES.iter().map(|(code, name, _)| (*code, *name)))
+}
+
+pub fn is_supported(code: &str) -> bool {
+    languages().any(|(c, _)| c == code)
+}
+
+/// Switch to `code`; anything unsupported means English.
+pub fn set_language(code: &str) {
+    let index = LOCALES.iter().position(|(c, _, _)| *c == code);
+    *ACTIVE.lock().unwrap_or_else(|e| e.into_inner()) = index;
+}
+
+/// `text` in the active language
+pub fn tr(text: &str) -> String {
+    translate(*ACTIVE.lock().unwrap_or_else(|e| e.into_inner()), text)
+}
+
+/// `text` in the active language with its `{name}` placeholders filled in
+pub fn tr_args(text: &str, args: &[(&str, &str)]) -> String {
+    fill(tr(text), args)
+}
+
+fn translate(locale: Option<usize>, text: &str) -> String {
+    locale
+        .and_then(|i| TABLES[i].get(text))
+        .map_or_else(|| text.to_string(), Clone::clone)
+}
+
+fn fill(text: String, args: &[(&str, &str)]) -> String {
+    args.iter().fold(text, |out, (name, value)| {
+        out.replace(&format!("{{{name}}}"), value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut found: Vec<&str> = text
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect();
+        found.sort_unstable();
+        found
+    }
+
+    #[test]
+    fn test_locales_parse_and_keep_placeholders() {
+        for ((code, _, json), table) in LOCALES.iter().zip(TABLES.iter()) {
+            let parsed: Table = serde_json::from_str(json)
+                .unwrap_or_else(|e| panic!("locale '{code}' is invalid: {e}"));
+            assert_eq!(&parsed, table);
+            for (english, translated) in table {
+                assert!(!translated.is_empty(), "{code}: '{english}' is empty");
+                assert_eq!(
+                    placeholders(english),
+                    placeholders(translated),
+                    "{code}: '{english}' -> '{translated}'"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_all_locales_cover_the_same_strings() {
+        let mut keys: Vec<Vec<&String>> = TABLES
+            .iter()
+            .map(|t| {
+                let mut k: Vec<_> = t.keys().collect();
+                k.sort();
+                k
+            })
+            .collect();
+        let first = keys.remove(0);
+        for (other, (code, _, _)) in keys.iter().zip(&LOCALES[1..]) {
+            assert_eq!(&first, other, "'{code}' and 'de' differ");
+        }
+    }
+
+    #[test]
+    fn test_translate() {
+        let de = LOCALES.iter().position(|(c, _, _)| *c == "de");
+        assert_eq!(translate(de, "CPU Usage"), "CPU-Auslastung");
+        assert_eq!(translate(de, "My custom sensor"), "My custom sensor");
+        assert_eq!(
+            fill(translate(de, "Printer {name}"), &[("name", "Office")]),
+            "Drucker Office"
+        );
+        assert_eq!(translate(None, "CPU Usage"), "CPU Usage");
+        assert!(is_supported("fr") && is_supported("en") && !is_supported("xx"));
+    }
+}
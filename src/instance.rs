@@ -0,0 +1,280 @@
+//! Single-instance guard, and requests to the agent that holds it
+//!
+//! - Windows: the named mutex `Local\pc-bridge-agent-singleton`, owned for the
+//!   process lifetime. `Local\` is per logon session, so every user on a shared
+//!   PC runs their own agent. Requests are named auto-reset events.
+//! - Linux: `flock` on `agent.pid` next to userConfig.json (per user, and per
+//!   `PC_BRIDGE_CONFIG_DIR`), holding the PID. Requests are SIGTERM / SIGHUP.
+//!
+//! `--replace` (updater relaunch) asks the holder to exit and waits for the
+//! lock; `--reload` asks it to re-read its config. Nothing is killed by name,
+//! so other users' agents and unrelated binaries sharing the name are safe.
+
+use std::time::Duration;
+
+use log::{info, warn};
+use tokio::sync::mpsc;
+
+/// How long `--replace` waits for the old instance to let go of the lock.
+const REPLACE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Request {
+    /// Shut down (a newer instance is taking over)
+    Exit,
+    /// Re-read userConfig.json, same as the file watcher
+    Reload,
+}
+
+/// Held by the running agent; the lock is released when it drops (or the
+/// process exits).
+pub(crate) struct Guard {
+    #[cfg(windows)]
+    mutex: windows::Win32::Foundation::HANDLE,
+    #[cfg(unix)]
+    _file: std::fs::File,
+}
+
+/// Take the single-instance lock. With `replace`, a running instance is asked
+/// to exit first; `None` if the lock is still held afterwards.
+pub(crate) fn acquire(replace: bool) -> Option<Guard> {
+    if let Some(guard) = imp::try_lock(Duration::ZERO) {
+        return Some(guard);
+    }
+    if !replace {
+        return None;
+    }
+    info!("Asking the running instance to exit");
+    signal(Request::Exit);
+    let guard = imp::try_lock(REPLACE_TIMEOUT);
+    if guard.is_none() {
+        warn!(
+            "Previous instance still holds the lock after {}s",
+            REPLACE_TIMEOUT.as_secs()
+        );
+    }
+    guard
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::time::Duration;
+
+    use windows::Win32::Foundation::{CloseHandle, HANDLE, WAIT_ABANDONED, WAIT_OBJECT_0};
+    use windows::Win32::System::Threading::{
+        CreateEventW, CreateMutexW, EVENT_MODIFY_STATE, INFINITE, OpenEventW, OpenMutexW,
+        ReleaseMutex, SYNCHRONIZATION_ACCESS_RIGHTS, SetEvent, WaitForMultipleObjects,
+        WaitForSingleObject,
+    };
+    use windows::core::{PCWSTR, w};
+
+    use super::{Guard, Request};
+
+    const MUTEX: PCWSTR = w!("Local\\pc-bridge-agent-singleton");
+    const EXIT_EVENT: PCWSTR = w!("Local\\pc-bridge-agent-exit");
+    const RELOAD_EVENT: PCWSTR = w!("Local\\pc-bridge-agent-reload");
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            unsafe {
+                let _ = ReleaseMutex(self.mutex);
+                let _ = CloseHandle(self.mutex);
+            }
+        }
+    }
+
+    /// Own the mutex, waiting up to `timeout`. An abandoned mutex (holder
+    /// died without releasing) is ours too. Ownership is per thread, so call
+    /// this from the thread that lives as long as the agent (main).
+    pub(super) fn try_lock(timeout: Duration) -> Option<Guard> {
+        unsafe {
+            let mutex = CreateMutexW(None, false, MUTEX).ok()?;
+            let ms = u32::try_from(timeout.as_millis()).unwrap_or(INFINITE - 1);
+            let result = WaitForSingleObject(mutex, ms);
+            if result == WAIT_OBJECT_0 || result == WAIT_ABANDONED {
+                Some(Guard { mutex })
+            } else {
+                let _ = CloseHandle(mutex);
+                None
+            }
+        }
+    }
+
+    pub(super) fn is_running() -> bool {
+        // SYNCHRONIZE access right (0x0010_0000); enough to probe the named mutex.
+        let access = SYNCHRONIZATION_ACCESS_RIGHTS(0x0010_0000);
+        unsafe {
+            match OpenMutexW(access, false, MUTEX) {
+                Ok(h) => {
+                    let _ = CloseHandle(h);
+                    true
+                }
+                Err(_) => false,
+            }
+        }
+    }
+
+    pub(super) fn signal(request: Request) -> bool {
+        let name = match request {
+            Request::Exit => EXIT_EVENT,
+            Request::Reload => RELOAD_EVENT,
+        };
+        unsafe {
+            let Ok(event) = OpenEventW(EVENT_MODIFY_STATE, false, name) else {
+                return false;
+            };
+            let sent = SetEvent(event).is_ok();
+            let _ = CloseHandle(event);
+            sent
+        }
+    }
+
+    /// Forward exit/reload events to `tx` from a dedicated thread (the wait
+    /// blocks forever; the thread dies with the process).
+    pub(super) fn listen(tx: tokio::sync::mpsc::UnboundedSender<Request>) {
+        let spawned = std::thread::Builder::new()
+            .name("instance-requests".into())
+            .spawn(move || unsafe {
+                let (Ok(exit), Ok(reload)) = (
+                    CreateEventW(None, false, false, EXIT_EVENT),
+                    CreateEventW(None, false, false, RELOAD_EVENT),
+                ) else {
+                    log::warn!("Could not create instance request events");
+                    return;
+                };
+                let events: [HANDLE; 2] = [exit, reload];
+                loop {
+                    let request = match WaitForMultipleObjects(&events, false, INFINITE) {
+                        r if r == WAIT_OBJECT_0 => Request::Exit,
+                        r if r.0 == WAIT_OBJECT_0.0 + 1 => Request::Reload,
+                        _ => return,
+                    };
+                    if tx.send(request).is_err() {
+                        return;
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            log::warn!("Could not start instance request listener: {e}");
+        }
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::io::{Read, Seek, Write};
+    use std::os::fd::AsRawFd;
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    use super::{Guard, Request};
+
+    fn lock_path() -> Option<PathBuf> {
+        Some(
+            crate::config::Config::config_path()
+                .ok()?
+                .with_file_name("agent.pid"),
+        )
+    }
+
+    fn flock(file: &std::fs::File, op: libc::c_int) -> bool {
+        unsafe { libc::flock(file.as_raw_fd(), op | libc::LOCK_NB) == 0 }
+    }
+
+    /// Lock the pidfile (polling until `timeout`) and record our PID in it.
+    pub(super) fn try_lock(timeout: Duration) -> Option<Guard> {
+        let path = lock_path()?;
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .ok()?;
+        let deadline = Instant::now() + timeout;
+        while !flock(&file, libc::LOCK_EX) {
+            if Instant::now() >= deadline {
+                return None;
+            }
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        let _ = file.set_len(0);
+        let _ = file.rewind();
+        let _ = write!(file, "{}", std::process::id());
+        Some(Guard { _file: file })
+    }
+
+    /// PID of the agent holding the lock, if one does.
+    fn holder() -> Option<libc::pid_t> {
+        let mut file = std::fs::File::open(lock_path()?).ok()?;
+        if flock(&file, libc::LOCK_SH) {
+            // We got it, so nobody holds it.
+            unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_UN) };
+            return None;
+        }
+        let mut pid = String::new();
+        file.read_to_string(&mut pid).ok()?;
+        pid.trim().parse().ok().filter(|pid| *pid > 0)
+    }
+
+    pub(super) fn is_running() -> bool {
+        holder().is_some()
+    }
+
+    pub(super) fn signal(request: Request) -> bool {
+        let Some(pid) = holder() else {
+            return false;
+        };
+        let sig = match request {
+            Request::Exit => libc::SIGTERM,
+            Request::Reload => libc::SIGHUP,
+        };
+        unsafe { libc::kill(pid, sig) == 0 }
+    }
+
+    pub(super) fn listen(tx: tokio::sync::mpsc::UnboundedSender<Request>) {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        let (Ok(mut term), Ok(mut hup)) = (
+            signal(SignalKind::terminate()),
+            signal(SignalKind::hangup()),
+        ) else {
+            log::warn!("Could not install SIGTERM/SIGHUP handlers");
+            return;
+        };
+        tokio::spawn(async move {
+            loop {
+                let request = tokio::select! {
+                    Some(()) = term.recv() => Request::Exit,
+                    Some(()) = hup.recv() => Request::Reload,
+                    else => return,
+                };
+                if tx.send(request).is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+/// True if an agent holds the lock. A non-owning probe, so it never affects
+/// the running agent; the settings window uses it for its status line.
+pub(crate) fn is_running() -> bool {
+    imp::is_running()
+}
+
+/// Send `request` to the running agent; false if none is running.
+pub(crate) fn signal(request: Request) -> bool {
+    imp::signal(request)
+}
+
+/// Requests from other instances, for the agent's main loop. Call once,
+/// from inside the runtime.
+pub(crate) fn listen() -> mpsc::UnboundedReceiver<Request> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    imp::listen(tx);
+    rx
+}
@@ -110,6 +110,27 @@ impl Dispatch<ZwlrForeignToplevelHandleV1, ()> for ToplevelState {
 
 /// Title of the activated toplevel on a wlroots compositor, or `None`.
 pub fn active_window_title() -> Option<String> {
+    toplevels()?
+        .into_values()
+        .find(|(_, activated)| *activated)
+        .map(|(title, _)| title)
+        .filter(|t| !t.is_empty())
+}
+
+/// Titles of all toplevels on a wlroots compositor, or `None`. Untitled
+/// toplevels are skipped.
+pub fn window_titles() -> Option<Vec<String>> {
+    Some(
+        toplevels()?
+            .into_values()
+            .map(|(title, _)| title)
+            .filter(|t| !t.is_empty())
+            .collect(),
+    )
+}
+
+/// The current toplevels as object id -> (title, is-activated).
+fn toplevels() -> Option<HashMap<ObjectId, (String, bool)>> {
     let conn = Connection::connect_to_env().ok()?;
     let mut queue = conn.new_event_queue::<ToplevelState>();
     let qh = queue.handle();
@@ -121,13 +142,7 @@ pub fn active_window_title() -> Option<String> {
     for _ in 0..3 {
         queue.roundtrip(&mut state).ok()?;
     }
-
-    state
-        .windows
-        .values()
-        .find(|(_, activated)| *activated)
-        .map(|(title, _)| title.clone())
-        .filter(|t| !t.is_empty())
+    Some(state.windows)
 }
 
 /// Whether this session exposes the wlr-foreign-toplevel protocol (wlroots).
@@ -50,7 +50,36 @@ pub fn active_window_title() -> Option<String> {
     if win == 0 {
         return None;
     }
+    window_title(&conn, win)
+}
+
+/// Titles of all managed top-level windows (`_NET_CLIENT_LIST`), or `None` if
+/// unavailable. Untitled windows are skipped.
+pub fn window_titles() -> Option<Vec<String>> {
+    let (conn, screen_num) = x11rb::connect(None).ok()?;
+    let root = conn.setup().roots.get(screen_num)?.root;
+
+    let client_list = conn
+        .intern_atom(false, b"_NET_CLIENT_LIST")
+        .ok()?
+        .reply()
+        .ok()?
+        .atom;
+    let reply = conn
+        .get_property(false, root, client_list, AtomEnum::WINDOW, 0, 4096)
+        .ok()?
+        .reply()
+        .ok()?;
+    Some(
+        reply
+            .value32()?
+            .filter_map(|win| window_title(&conn, win))
+            .collect(),
+    )
+}
 
+/// `_NET_WM_NAME` of `win`, falling back to the legacy `WM_NAME`.
+fn window_title(conn: &impl Connection, win: u32) -> Option<String> {
     let net_wm_name = conn
         .intern_atom(false, b"_NET_WM_NAME")
         .ok()?
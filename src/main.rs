@@ -9,12 +9,26 @@
 
 #![cfg_attr(windows, windows_subsystem = "windows")]
 
+mod activity;
 mod audio;
+mod automations;
+mod backup;
+mod cleanup;
 mod commands;
 mod config;
+mod crash;
 mod credential;
+mod events;
+mod feature_state;
+mod focus;
 mod fsutil;
+mod game_limits;
+mod group;
+mod ha_api;
+mod http_api;
 mod hwinfo;
+mod i18n;
+mod instance;
 #[cfg(unix)]
 mod linux_dbus;
 #[cfg(unix)]
@@ -26,19 +40,29 @@ mod linux_x11;
 mod logging;
 mod mqtt;
 mod notification;
+mod openrgb;
+mod plugins;
 mod power;
+mod reporting;
+mod scripting;
 mod sensors;
 mod setup;
+mod speedtest;
 mod steam;
 mod supervisor;
+mod textutil;
+mod thermal;
+#[cfg(windows)]
+mod toast_identity;
+mod transfer;
 #[cfg(windows)]
 mod tray;
 mod ui;
 mod updater;
+mod wol;
 
-use log::{error, info};
+use log::{error, info, warn};
 use std::sync::Arc;
-#[cfg(windows)]
 use std::time::Duration;
 use tokio::sync::{RwLock, broadcast};
 
@@ -95,15 +119,30 @@ fn main() -> anyhow::Result<()> {
         return ui::run();
     }
 
+    // `--reload`: have the running agent re-read its config, then exit.
+    if std::env::args().any(|a| a == "--reload") {
+        if instance::signal(instance::Request::Reload) {
+            println!("Asked the running agent to reload its config");
+        } else {
+            eprintln!("pc-bridge is not running");
+        }
+        return Ok(());
+    }
+
     // Single-instance: if the headless agent is already running and this is a plain
-    // launch (the user opened the app again), don't kill + restart it - open the
-    // settings window instead. The updater relaunches with `--replace`, which skips
-    // this so an update still takes over the running instance.
+    // launch (the user opened the app again), don't restart it - open the settings
+    // window instead (Windows) or say so. The updater relaunches with `--replace`,
+    // which skips this so an update still takes over the running instance.
     let is_replace = std::env::args().any(|a| a == "--replace");
-    if !is_replace && instance_already_running() {
+    if !is_replace && instance::is_running() {
+        #[cfg(windows)]
         if let Err(e) = spawn_settings_window() {
             eprintln!("pc-bridge is already running; failed to open settings window: {e}");
         }
+        #[cfg(not(windows))]
+        eprintln!(
+            "pc-bridge is already running (--replace takes over, --reload re-reads the config)"
+        );
         return Ok(());
     }
 
@@ -115,61 +154,13 @@ fn main() -> anyhow::Result<()> {
 
 /// Spawn the settings window as a separate `--ui` process (it runs independently of
 /// the agent and edits the config the agent hot-reloads).
+#[cfg(windows)]
 fn spawn_settings_window() -> std::io::Result<()> {
     let exe = std::env::current_exe()?;
     std::process::Command::new(exe).arg("--ui").spawn()?;
     Ok(())
 }
 
-/// True if a headless agent is already running (it holds a named singleton). A
-/// non-owning probe, so it never affects the running agent. Also used by the
-/// settings window to show whether the background agent is up.
-#[cfg(windows)]
-pub(crate) fn instance_already_running() -> bool {
-    use windows::Win32::Foundation::CloseHandle;
-    use windows::Win32::System::Threading::{OpenMutexW, SYNCHRONIZATION_ACCESS_RIGHTS};
-    use windows::core::w;
-    // SYNCHRONIZE access right (0x0010_0000); enough to probe the named mutex.
-    let access = SYNCHRONIZATION_ACCESS_RIGHTS(0x0010_0000);
-    unsafe {
-        match OpenMutexW(access, false, w!("Local\\pc-bridge-agent-singleton")) {
-            Ok(h) => {
-                let _ = CloseHandle(h);
-                true
-            }
-            Err(_) => false,
-        }
-    }
-}
-
-#[cfg(not(windows))]
-pub(crate) fn instance_already_running() -> bool {
-    // Linux runs headless as a service; the open-settings-on-relaunch behavior is a
-    // desktop nicety we only wire on Windows for now.
-    false
-}
-
-/// Mark this process as the running agent by creating the named singleton mutex and
-/// holding it for the process lifetime (the kernel releases it on exit, so the next
-/// launch's probe sees it gone). No-op off Windows.
-#[cfg(windows)]
-fn hold_singleton() {
-    use windows::Win32::System::Threading::CreateMutexW;
-    use windows::core::w;
-    unsafe {
-        // Keep the handle alive for the whole process; leaking it is intentional (it
-        // is closed by the OS on exit). If another handle to the name exists (an old
-        // instance mid-exit), we still get a valid handle and become the sole owner
-        // once it closes.
-        if let Ok(h) = CreateMutexW(None, false, w!("Local\\pc-bridge-agent-singleton")) {
-            std::mem::forget(h);
-        }
-    }
-}
-
-#[cfg(not(windows))]
-fn hold_singleton() {}
-
 async fn run_agent() -> anyhow::Result<()> {
     // On Windows, attach to parent console if launched from terminal
     // This allows seeing output when run from cmd/powershell
@@ -203,6 +194,7 @@ async fn run_agent() -> anyhow::Result<()> {
 
     // Initialize logging (rotating file sink + stderr mirror)
     logging::init();
+    crash::install();
 
     info!("PC Bridge starting...");
 
@@ -210,20 +202,25 @@ async fn run_agent() -> anyhow::Result<()> {
     let args: Vec<String> = std::env::args().collect();
     let force_setup = args.iter().any(|a| a == "--setup");
     let reset_password = args.iter().any(|a| a == "--reset-password");
+    let cleanup_entities = args.iter().any(|a| a == "--cleanup-entities");
     let dry_run = args.iter().any(|a| a == "--dry-run")
         || matches!(
             std::env::var("PC_BRIDGE_DRY_RUN").as_deref(),
             Ok("1" | "true")
         );
 
-    // Kill any existing instances (an updater --replace takeover, or a stale one),
-    // then claim the singleton so a later plain launch opens settings instead of
-    // killing us.
-    kill_existing_instances();
-    hold_singleton();
+    // Claim the single-instance lock (asking the old instance to exit on an
+    // updater --replace takeover) so a later plain launch opens settings
+    // instead of starting a second agent.
+    let Some(_instance) = instance::acquire(args.iter().any(|a| a == "--replace")) else {
+        error!("Another PC Bridge agent is running - exiting");
+        return Ok(());
+    };
 
-    // Clean up leftover .old files from a previous update
-    updater::cleanup_old_files();
+    // Roll back a fresh update that crashed on its last launch, then clean up
+    // leftovers from previous updates
+    let update_pending = updater::startup_check();
+    tokio::spawn(updater::confirm_update(update_pending));
 
     // --reset-password: prompt for new MQTT password, encrypt, save, and exit
     if reset_password {
@@ -283,9 +280,13 @@ async fn run_agent() -> anyhow::Result<()> {
         Err(e) => return Err(e),
     };
     info!("Loaded config for device: {}", config.device_name);
-
-    // Check for updates (non-blocking, continues after check)
-    tokio::spawn(updater::check_for_updates(config.update_channel.clone()));
+    crash::set_context(&config);
+    activity::configure(&config);
+    focus::configure(&config);
+    i18n::set_language(&config.language);
+    notification::set_app_name(&config.notifications.app_name).await;
+    reporting::load();
+    tokio::spawn(crash::announce(config.crash_notify));
 
     // Log enabled features
     log_enabled_features(&config);
@@ -295,6 +296,25 @@ async fn run_agent() -> anyhow::Result<()> {
 
     // Create MQTT client (conditionally registers discovery based on features)
     let (mqtt, command_rx) = MqttClient::new(&config, shutdown_tx.subscribe()).await?;
+    // One-shot: clear retained state left under the old homeassistant/ layout.
+    mqtt.migrate_state_topics(&config).await;
+    // Remember this device name so a later rename can clean up after it.
+    let mut feature_state = feature_state::FeatureState::load();
+    let (changed, renamed_from) = feature_state.record_device_name(&config.device_name);
+    if changed && let Err(e) = feature_state.save() {
+        warn!("Failed to record device name in feature_state.json: {e}");
+    }
+    // A rename is the new device registering above and the old one's
+    // entities and retained topics going away here.
+    if let Some(old) = &renamed_from {
+        info!(
+            "device_name changed from '{}' to '{}', removing the old device from HA",
+            old, config.device_name
+        );
+    }
+    if cleanup_entities || renamed_from.is_some() {
+        mqtt.cleanup_stale_devices(&config).await;
+    }
 
     // Steam discovery is deferred to the "refresh_steam_games" button in HA
     // (previously ran at startup, causing ~400KB+ heap fragmentation on Windows)
@@ -327,9 +347,13 @@ async fn run_agent() -> anyhow::Result<()> {
     // Collect task handles for cleanup
     let mut handles: Vec<TaskHandle> = Vec::new();
 
-    // Start event-driven process watcher if game detection or idle tracking is enabled
+    // Start event-driven process watcher if game detection, idle tracking or
+    // process sensors need it
     #[cfg(windows)]
-    if config.features.running_game || config.features.idle_tracking {
+    if config.features.running_game
+        || config.features.idle_tracking
+        || !config.process_sensors.is_empty()
+    {
         let poll_interval = Duration::from_secs(config.intervals.game_sensor.max(5));
         state
             .process_watcher
@@ -337,13 +361,33 @@ async fn run_agent() -> anyhow::Result<()> {
         info!("  Process watcher started (WMI events with polling fallback)");
     }
 
+    // Home Assistant API transport, alongside or instead of MQTT
+    if config.ha_api.enabled
+        && let Some(handle) = ha_api::spawn(&config, &state.mqtt, shutdown_tx.subscribe())
+    {
+        handles.push(handle);
+    }
+
+    // Local HTTP status/control endpoint
+    if config.http_api.enabled {
+        handles.push(tokio::spawn(http_api::run(Arc::clone(&state))));
+    }
+
+    // Update checks (on launch, then periodically) + the HA update entity
+    handles.push(tokio::spawn(updater::run(Arc::clone(&state))));
+
+    // Group membership record and the group's any_game_running
+    handles.push(tokio::spawn(group::run(Arc::clone(&state))));
+
     // Command executor always runs (needed for any remote control)
     let command_executor = CommandExecutor::new(Arc::clone(&state), command_rx);
     handles.push(tokio::spawn(command_executor.run()));
 
-    // Re-publish HA discovery on every MQTT reconnect. A broker that restarts
-    // without persistence loses the retained config topics, which would orphan
-    // all entities until the agent restarts; re-registering restores them.
+    // Re-publish HA discovery on every MQTT reconnect and HA birth message. A
+    // broker that restarts without persistence (or a user purging retained
+    // messages before restarting HA) loses the retained config topics, which
+    // would orphan all entities until the agent restarts; re-registering
+    // restores them.
     {
         let state = Arc::clone(&state);
         let mut reconnect_rx = state.mqtt.subscribe_reconnect();
@@ -372,6 +416,15 @@ async fn run_agent() -> anyhow::Result<()> {
                             // repeating the ~3x-per-entity teardown on every reconnect is
                             // pure churn on a flapping broker.
                             state.mqtt.register_discovery(&config).await;
+                            if config.custom_sensors_enabled {
+                                state
+                                    .mqtt
+                                    .register_custom_sensors(&config.custom_sensors, config.intervals.heartbeat)
+                                    .await;
+                            }
+                            if config.custom_commands_enabled {
+                                state.mqtt.register_custom_commands(&config.custom_commands).await;
+                            }
                         }
                         Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
                     },
@@ -380,6 +433,47 @@ async fn run_agent() -> anyhow::Result<()> {
         }));
     }
 
+    // Heartbeat: resend availability + the last sensor values so the
+    // expire_after in discovery only lapses once the agent stops publishing.
+    // The interval is re-read every beat, so a hot-reloaded value applies
+    // without a restart (0 = off; re-checked once a minute).
+    {
+        let state = Arc::clone(&state);
+        let mut shutdown_rx = state.shutdown_tx.subscribe();
+        handles.push(tokio::spawn(async move {
+            loop {
+                let secs = state.config.read().await.intervals.heartbeat;
+                let wait = if secs == 0 { 60 } else { secs };
+                tokio::select! {
+                    _ = shutdown_rx.recv() => break,
+                    () = tokio::time::sleep(Duration::from_secs(wait)) => {}
+                }
+                if secs > 0 {
+                    state.mqtt.publish_heartbeat().await;
+                }
+            }
+        }));
+    }
+
+    // Bundled state: the system sensors' changes go out together, at most
+    // once per `intervals.bundled_state` (re-read every round like the
+    // heartbeat's).
+    if config.mqtt.bundled_state {
+        let state = Arc::clone(&state);
+        let mut shutdown_rx = state.shutdown_tx.subscribe();
+        handles.push(tokio::spawn(async move {
+            loop {
+                let secs = state.config.read().await.intervals.bundled_state.max(1);
+                tokio::select! {
+                    _ = shutdown_rx.recv() => break,
+                    () = tokio::time::sleep(Duration::from_secs(secs)) => {}
+                }
+                state.mqtt.publish_bundle(false).await;
+            }
+        }));
+        info!("  Bundled state enabled (system sensors on one topic)");
+    }
+
     // All sensors except HWiNFO are now started/stopped live by the supervisor
     // (see its spawn below) as their feature flags change - including the
     // thread-holding ones (system, session, now_playing, power), which take a
@@ -400,7 +494,7 @@ async fn run_agent() -> anyhow::Result<()> {
     if config.custom_sensors_enabled && !config.custom_sensors.is_empty() {
         state
             .mqtt
-            .register_custom_sensors(&config.custom_sensors)
+            .register_custom_sensors(&config.custom_sensors, config.intervals.heartbeat)
             .await;
         info!(
             "  Custom sensors enabled ({} defined)",
@@ -465,25 +559,46 @@ async fn run_agent() -> anyhow::Result<()> {
             .await;
     }
 
-    // Wait for shutdown signal (Ctrl+C or broadcast)
+    // Wait for shutdown signal (Ctrl+C, broadcast, or an exit request from a
+    // newer instance); reload requests (`--reload`, SIGHUP) are served here too.
     info!("PC Bridge running. Press Ctrl+C to stop.");
 
-    #[cfg(windows)]
-    {
-        if console_attached {
-            // Terminal mode: wait for Ctrl+C via tokio's signal handler
+    let mut requests = instance::listen();
+    let mut shutdown_rx = shutdown_tx.subscribe();
+    loop {
+        // Terminal mode waits for Ctrl+C; background mode (no console) for the
+        // broadcast shutdown (tray Quit).
+        #[cfg(windows)]
+        let ctrl_c = async {
+            if console_attached {
+                tokio::signal::ctrl_c().await.ok();
+            } else {
+                std::future::pending::<()>().await;
+            }
+        };
+        #[cfg(not(windows))]
+        let ctrl_c = async {
             tokio::signal::ctrl_c().await.ok();
-        } else {
-            // Background mode (no console): wait for broadcast shutdown
-            let mut shutdown_rx = shutdown_tx.subscribe();
-            let _ = shutdown_rx.recv().await;
+        };
+
+        tokio::select! {
+            () = ctrl_c => break,
+            _ = shutdown_rx.recv() => break,
+            Some(request) = requests.recv() => match request {
+                instance::Request::Exit => {
+                    info!("Exit requested by another instance");
+                    break;
+                }
+                instance::Request::Reload => {
+                    info!("Reload requested, re-reading config...");
+                    config::reload_hot_config(&state).await;
+                }
+            },
         }
     }
 
-    #[cfg(not(windows))]
-    tokio::signal::ctrl_c().await?;
-
     info!("Shutting down...");
+    updater::confirm_pending_update();
 
     // Second Ctrl+C force-exits (in case shutdown hangs)
     tokio::spawn(async {
@@ -504,12 +619,9 @@ async fn run_agent() -> anyhow::Result<()> {
     })
     .await;
 
-    // Publish offline status (with timeout to avoid hanging on broken MQTT)
-    let _ = tokio::time::timeout(
-        std::time::Duration::from_secs(2),
-        state.mqtt.publish_availability(false),
-    )
-    .await;
+    // Final states and offline, acked and flushed before exit (with a timeout
+    // to avoid hanging on broken MQTT)
+    let _ = tokio::time::timeout(std::time::Duration::from_secs(5), state.mqtt.close()).await;
 
     info!("PC Bridge stopped");
 
@@ -559,6 +671,10 @@ fn log_enabled_features(config: &Config) {
         f.disk_sensor,
         f.uptime_sensor,
         f.hwinfo_sensor,
+        f.windows_update,
+        f.cmd_windows_update_install,
+        f.power_plan,
+        f.cmd_install_update,
         config.custom_sensors_enabled,
         config.custom_commands_enabled,
     ]
@@ -627,7 +743,7 @@ fn reset_mqtt_password() -> anyhow::Result<()> {
         println!();
         println!("  No changes made.");
     } else {
-        credential::save_to_file(&new_pass)?;
+        credential::save_to_file(credential::MQTT, &new_pass)?;
         println!();
         println!("  Password updated and encrypted.");
         println!("  Restart PC Bridge for the change to take effect.");
@@ -686,7 +802,7 @@ fn handle_credential_failure() -> anyhow::Result<Config> {
         println!("  Press Enter to continue...");
         let _ = io::stdin().read_line(&mut String::new());
     } else {
-        credential::save_to_file(&new_pass)?;
+        credential::save_to_file(credential::MQTT, &new_pass)?;
         println!();
         println!("  Password saved and encrypted for this machine.");
         println!();
@@ -707,98 +823,3 @@ fn handle_credential_failure() -> anyhow::Result<Config> {
         Config::load()
     }
 }
-
-/// Kill any other running instances (platform-specific)
-#[cfg(windows)]
-fn kill_existing_instances() {
-    use windows::Win32::Foundation::CloseHandle;
-    use windows::Win32::System::Diagnostics::ToolHelp::{
-        CreateToolhelp32Snapshot, PROCESSENTRY32W, Process32FirstW, Process32NextW,
-        TH32CS_SNAPPROCESS,
-    };
-    use windows::Win32::System::Threading::{OpenProcess, PROCESS_TERMINATE, TerminateProcess};
-
-    let my_pid = std::process::id();
-
-    // Match any of these exe names (covers renames)
-    let exe_names = ["pc-bridge.exe", "pc bridge.exe", "pc-agent.exe"];
-
-    unsafe {
-        let snapshot = match CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0) {
-            Ok(s) => s,
-            Err(e) => {
-                info!("Failed to create process snapshot: {:?}", e);
-                return;
-            }
-        };
-
-        let mut entry = PROCESSENTRY32W {
-            dwSize: std::mem::size_of::<PROCESSENTRY32W>() as u32,
-            ..Default::default()
-        };
-
-        if Process32FirstW(snapshot, &raw mut entry).is_ok() {
-            loop {
-                let proc_name = String::from_utf16_lossy(&entry.szExeFile)
-                    .trim_end_matches('\0')
-                    .to_lowercase();
-
-                // Check if this process matches any of our exe names
-                let is_match = exe_names.iter().any(|&name| proc_name == name);
-
-                if is_match
-                    && entry.th32ProcessID != my_pid
-                    && let Ok(handle) = OpenProcess(PROCESS_TERMINATE, false, entry.th32ProcessID)
-                {
-                    info!(
-                        "Killing existing instance: {} (PID {})",
-                        proc_name, entry.th32ProcessID
-                    );
-                    let _ = TerminateProcess(handle, 0);
-                    let _ = CloseHandle(handle);
-                }
-
-                if Process32NextW(snapshot, &raw mut entry).is_err() {
-                    break;
-                }
-            }
-        }
-
-        let _ = CloseHandle(snapshot);
-    }
-
-    // Give processes time to exit
-    std::thread::sleep(std::time::Duration::from_millis(500));
-}
-
-/// Kill any other running instances (Linux)
-#[cfg(unix)]
-fn kill_existing_instances() {
-    use std::process::Command;
-
-    let my_pid = std::process::id();
-    let exe_name = std::env::current_exe()
-        .ok()
-        .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
-        .unwrap_or_default();
-
-    if exe_name.is_empty() {
-        return;
-    }
-
-    // Use pgrep to find other instances, then kill them excluding our PID
-    if let Ok(output) = Command::new("pgrep").args(["-x", &exe_name]).output() {
-        let pids = String::from_utf8_lossy(&output.stdout);
-        for line in pids.lines() {
-            if let Ok(pid) = line.trim().parse::<u32>()
-                && pid != my_pid
-            {
-                let _ = Command::new("kill")
-                    .args(["-TERM", &pid.to_string()])
-                    .status();
-            }
-        }
-    }
-
-    std::thread::sleep(std::time::Duration::from_secs(1));
-}
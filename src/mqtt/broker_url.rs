@@ -0,0 +1,153 @@
+//! `mqtt.broker` parsing
+//!
+//! `tcp://host:port` and `ssl://host:port` go through the `url` crate rather
+//! than a split on ':', so IPv6 literals (`tcp://[fe80::1]:1883`) come out
+//! right. A URL without a scheme is taken as `tcp://`. Default ports: 1883 for
+//! `tcp://`, 8883 for `ssl://`. There is no WebSocket transport, so `ws://`
+//! and `wss://` are refused rather than dialed as plain MQTT.
+
+use anyhow::{Context, Result, bail};
+use url::{Host, Url};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BrokerUrl {
+    /// Host name or IP, IPv6 without brackets
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+impl BrokerUrl {
+    pub fn parse(url: &str) -> Result<Self> {
+        let url = url.trim();
+        let parsed = if url.contains("://") {
+            Url::parse(url)
+        } else {
+            Url::parse(&format!("tcp://{url}"))
+        }
+        .with_context(|| format!("invalid broker URL {url:?}"))?;
+
+        let (tls, default_port) = match parsed.scheme() {
+            "tcp" => (false, 1883),
+            "ssl" => (true, 8883),
+            "ws" | "wss" => {
+                bail!("WebSocket brokers (ws://, wss://) are not supported; use tcp:// or ssl://")
+            }
+            other => bail!("unsupported broker URL scheme {other}://"),
+        };
+        let host = match parsed.host() {
+            Some(Host::Domain(name)) if !name.is_empty() => name.to_string(),
+            Some(Host::Ipv4(ip)) => ip.to_string(),
+            Some(Host::Ipv6(ip)) => ip.to_string(),
+            _ => bail!("broker URL {url:?} has no host"),
+        };
+        if !matches!(parsed.path(), "" | "/") {
+            bail!("broker URL {url:?} can't have a path");
+        }
+        Ok(Self {
+            host,
+            port: parsed.port().unwrap_or(default_port),
+            tls,
+        })
+    }
+
+    /// Host for libraries that join it to the port with ':' (rumqttc):
+    /// IPv6 literals bracketed.
+    pub fn connect_host(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        }
+    }
+
+    /// `host:port`, for logs
+    pub fn address(&self) -> String {
+        format!("{}:{}", self.connect_host(), self.port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> (String, u16, bool) {
+        let broker = BrokerUrl::parse(url).unwrap();
+        (broker.host, broker.port, broker.tls)
+    }
+
+    #[test]
+    fn test_parse_broker_url() {
+        assert_eq!(
+            parse("tcp://192.168.1.1:1883"),
+            ("192.168.1.1".into(), 1883, false)
+        );
+        assert_eq!(
+            parse("192.168.1.1:1884"),
+            ("192.168.1.1".into(), 1884, false)
+        );
+        assert_eq!(parse("ssl://broker:8883"), ("broker".into(), 8883, true));
+        assert_eq!(
+            parse("ssl://mqtt.example.com:8883"),
+            ("mqtt.example.com".into(), 8883, true)
+        );
+    }
+
+    #[test]
+    fn test_parse_broker_url_default_ports() {
+        assert_eq!(parse("myhost"), ("myhost".into(), 1883, false));
+        assert_eq!(parse("tcp://mybroker"), ("mybroker".into(), 1883, false));
+        assert_eq!(parse("ssl://mybroker"), ("mybroker".into(), 8883, true));
+        // An explicit default port is still that port.
+        assert_eq!(
+            parse("ssl://mybroker:8883"),
+            ("mybroker".into(), 8883, true)
+        );
+    }
+
+    #[test]
+    fn test_parse_broker_url_ipv6() {
+        assert_eq!(parse("tcp://[::1]:1883"), ("::1".into(), 1883, false));
+        assert_eq!(
+            parse("tcp://[fe80::1]:1883"),
+            ("fe80::1".into(), 1883, false)
+        );
+        assert_eq!(
+            parse("ssl://[fe80::1]:8883"),
+            ("fe80::1".into(), 8883, true)
+        );
+        // No scheme + no port
+        assert_eq!(parse("[::1]"), ("::1".into(), 1883, false));
+        // Bracketed + TLS scheme + missing port → TLS default
+        assert_eq!(parse("ssl://[::1]"), ("::1".into(), 8883, true));
+        // Compressed forms are normalized
+        assert_eq!(
+            parse("tcp://[2001:DB8:0:0::1]:1884"),
+            ("2001:db8::1".into(), 1884, false)
+        );
+
+        let broker = BrokerUrl::parse("tcp://[fe80::1]:1883").unwrap();
+        assert_eq!(broker.connect_host(), "[fe80::1]");
+        assert_eq!(broker.address(), "[fe80::1]:1883");
+        let broker = BrokerUrl::parse("tcp://broker.local").unwrap();
+        assert_eq!(broker.connect_host(), "broker.local");
+        assert_eq!(broker.address(), "broker.local:1883");
+    }
+
+    #[test]
+    fn test_parse_broker_url_rejects_websocket_and_paths() {
+        assert!(BrokerUrl::parse("ws://broker:8083").is_err());
+        assert!(BrokerUrl::parse("wss://mqtt.example.com:8084/mqtt").is_err());
+        assert!(BrokerUrl::parse("tcp://broker:1883/mqtt").is_err());
+        assert!(BrokerUrl::parse("tcp://broker/").is_ok());
+    }
+
+    #[test]
+    fn test_parse_broker_url_rejects_malformed() {
+        assert!(BrokerUrl::parse("").is_err());
+        assert!(BrokerUrl::parse("tcp://").is_err());
+        assert!(BrokerUrl::parse("tcp://[fe80::1:1883").is_err());
+        assert!(BrokerUrl::parse("tcp://broker:99999").is_err());
+        assert!(BrokerUrl::parse("http://broker").is_err());
+    }
+}
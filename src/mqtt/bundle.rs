@@ -0,0 +1,111 @@
+//! Bundled state (`mqtt.bundled_state`).
+//!
+//! For brokers that struggle with message volume: the system sensors stop
+//! publishing their own state topics and ride in one JSON document on
+//! `pc-bridge/<device>/state` instead, `{"cpu_usage": "12.3", ...}`, sent
+//! every `intervals.bundled_state` when any of them changed and with every
+//! heartbeat. Their attributes (the CPU / memory / GPU averages) ride along
+//! under `"attributes"`, `{"attributes": {"cpu_usage": {...}}}`, so they
+//! don't bring back a message per sensor. Their discovery configs point
+//! state and attributes at that topic with `value_json` templates.
+
+use std::collections::BTreeMap;
+
+/// Sensors that go in the bundle instead of their own state topics
+const BUNDLED_SENSORS: &[&str] = &[
+    "cpu_usage",
+    "memory_usage",
+    "gpu_usage",
+    "network_throughput",
+    "disk_usage",
+    "battery_level",
+    "battery_charging",
+    "system_uptime",
+    "bridge_health",
+];
+
+pub(super) fn is_bundled(name: &str) -> bool {
+    BUNDLED_SENSORS.contains(&name)
+}
+
+/// Discovery `value_template` of a bundled sensor. A sensor that hasn't
+/// published yet (no battery) renders "None", which HA shows as unknown
+/// instead of logging a template warning on every bundle.
+pub(super) fn value_template(name: &str) -> String {
+    format!("{{{{ value_json.{name} | default('None') }}}}")
+}
+
+/// Discovery `json_attributes_template` of a bundled sensor
+pub(super) fn attributes_template(name: &str) -> String {
+    format!("{{{{ value_json.attributes.{name} | default({{}}) | tojson }}}}")
+}
+
+/// The bundle: the bundled sensors' latest values and attributes out of
+/// `values` and `attributes` (every sensor's, as `StateStore::values` and
+/// `StateStore::attributes` have them). None before any has published.
+pub(super) fn document(
+    values: BTreeMap<String, String>,
+    attributes: Vec<(String, Vec<u8>)>,
+) -> Option<serde_json::Value> {
+    let mut bundle: serde_json::Map<String, serde_json::Value> = values
+        .into_iter()
+        .filter(|(name, _)| is_bundled(name))
+        .map(|(name, value)| (name, value.into()))
+        .collect();
+    let attributes: serde_json::Map<String, serde_json::Value> = attributes
+        .into_iter()
+        .filter(|(name, _)| is_bundled(name))
+        .filter_map(|(name, payload)| Some((name, serde_json::from_slice(&payload).ok()?)))
+        .collect();
+    if !attributes.is_empty() {
+        bundle.insert("attributes".to_string(), attributes.into());
+    }
+    (!bundle.is_empty()).then_some(bundle.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document() {
+        let values: BTreeMap<String, String> = [
+            ("cpu_usage", "12.3"),
+            ("memory_usage", "41.0"),
+            ("active_window", "Firefox"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let attributes = vec![
+            ("cpu_usage".to_string(), br#"{"peak":90.0}"#.to_vec()),
+            ("active_window".to_string(), br#"{"title":"x"}"#.to_vec()),
+        ];
+        assert_eq!(
+            document(values.clone(), Vec::new()),
+            Some(serde_json::json!({ "cpu_usage": "12.3", "memory_usage": "41.0" }))
+        );
+        assert_eq!(
+            document(values, attributes),
+            Some(serde_json::json!({
+                "cpu_usage": "12.3",
+                "memory_usage": "41.0",
+                "attributes": { "cpu_usage": { "peak": 90.0 } },
+            }))
+        );
+        let unbundled = BTreeMap::from([("active_window".to_string(), "Firefox".to_string())]);
+        assert_eq!(document(unbundled, Vec::new()), None);
+    }
+
+    #[test]
+    fn test_templates() {
+        assert_eq!(
+            value_template("cpu_usage"),
+            "{{ value_json.cpu_usage | default('None') }}"
+        );
+        assert_eq!(
+            attributes_template("cpu_usage"),
+            "{{ value_json.attributes.cpu_usage | default({}) | tojson }}"
+        );
+    }
+}
@@ -0,0 +1,480 @@
+//! Protocol-neutral wrapper over rumqttc's MQTT 3.1.1 and MQTT 5 clients
+//!
+//! rumqttc exposes the two protocols as separate client/event-loop types with
+//! their own packet enums. Everything above this module talks to `Client` and
+//! `EventLoop`, which mirror the v3.1.1 call shapes (so call sites keep using
+//! `rumqttc::QoS`) and normalize incoming packets to `Incoming`. The v5 side
+//! also logs broker reason codes and reason strings, which v3.1.1 can't carry.
+//! With no broker configured (Home Assistant API transport only) both are the
+//! inert `Disabled` variant.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use log::{debug, info, warn};
+use rumqttc::QoS;
+use rumqttc::v5::mqttbytes::v5::{
+    ConnectReturnCode, DisconnectReasonCode, LastWillProperties, Packet as V5Packet, PubAckReason,
+    SubscribeReasonCode,
+};
+
+use crate::config::MqttConfig;
+
+/// Connection settings shared by both protocols.
+pub(super) struct ConnectOptions<'a> {
+    pub(super) client_id: String,
+    pub(super) host: String,
+    pub(super) port: u16,
+    pub(super) use_tls: bool,
+    pub(super) mqtt: &'a MqttConfig,
+    /// Retained "offline" LWT topic.
+    pub(super) will_topic: &'a str,
+}
+
+/// Cap packet size to bound memory, but generously: an incoming payload over
+/// the cap makes the event loop error and the whole connection cycle (dropping
+/// the command). 256 KB comfortably covers notification bodies (which can carry
+/// a longer message / data URI) while still bounding memory.
+const MAX_PACKET_SIZE: usize = 256 * 1024;
+/// Limit in-flight QoS 1 messages - local broker doesn't need aggressive pipelining
+const INFLIGHT: u16 = 5;
+
+#[derive(Clone)]
+pub(super) enum Client {
+    V4(rumqttc::AsyncClient),
+    V5(rumqttc::v5::AsyncClient),
+    /// No broker: every request succeeds without doing anything.
+    Disabled,
+}
+
+pub(super) enum EventLoop {
+    V4(Box<rumqttc::EventLoop>),
+    V5(Box<rumqttc::v5::EventLoop>),
+    /// No broker: never yields an event.
+    Disabled,
+}
+
+pub(super) enum ClientError {
+    V4(rumqttc::ClientError),
+    V5(rumqttc::v5::ClientError),
+}
+
+pub(super) enum ConnectionError {
+    V4(rumqttc::ConnectionError),
+    V5(rumqttc::v5::ConnectionError),
+}
+
+// Debug forwards to the inner error so logs read the same as before the
+// wrapper existed (no `V4(..)` noise).
+impl std::fmt::Debug for ClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::V4(e) => e.fmt(f),
+            Self::V5(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::fmt::Debug for ConnectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::V4(e) => e.fmt(f),
+            Self::V5(e) => e.fmt(f),
+        }
+    }
+}
+
+/// The incoming packets the agent acts on; everything else is `Other`.
+pub(super) enum Incoming {
+    Publish {
+        topic: String,
+        payload: Bytes,
+        retain: bool,
+    },
+    ConnAck,
+    /// The broker acknowledged one of our QoS 1 publishes
+    PubAck,
+    /// Our DISCONNECT went out (see `Client::disconnect`)
+    DisconnectSent,
+    Other,
+}
+
+/// Build the client + event loop for the configured protocol version.
+pub(super) fn connect(opts: &ConnectOptions<'_>, cap: usize) -> (Client, EventLoop) {
+    if opts.mqtt.broker.is_empty() {
+        (Client::Disabled, EventLoop::Disabled)
+    } else if opts.mqtt.is_v5() {
+        let (client, eventloop) = rumqttc::v5::AsyncClient::new(v5_options(opts), cap);
+        (Client::V5(client), EventLoop::V5(Box::new(eventloop)))
+    } else {
+        let (client, mut eventloop) = rumqttc::AsyncClient::new(v4_options(opts), cap);
+        eventloop.set_network_options(network_options(opts.mqtt));
+        (Client::V4(client), EventLoop::V4(Box::new(eventloop)))
+    }
+}
+
+/// Connect timeout and TCP_NODELAY (v3.1.1 sets them on the event loop, v5
+/// on its options)
+fn network_options(mqtt: &MqttConfig) -> rumqttc::NetworkOptions {
+    let mut network = rumqttc::NetworkOptions::new();
+    network.set_connection_timeout(mqtt.connect_timeout_secs);
+    network.set_tcp_nodelay(mqtt.tcp_nodelay);
+    network
+}
+
+fn v4_options(opts: &ConnectOptions<'_>) -> rumqttc::MqttOptions {
+    let mut o = rumqttc::MqttOptions::new(&opts.client_id, &opts.host, opts.port);
+    if !opts.mqtt.user.is_empty() {
+        o.set_credentials(&opts.mqtt.user, &opts.mqtt.pass);
+    }
+    if opts.use_tls {
+        o.set_transport(rumqttc::Transport::tls_with_config(
+            rumqttc::TlsConfiguration::Native,
+        ));
+    }
+    o.set_keep_alive(Duration::from_secs(u64::from(opts.mqtt.keep_alive_secs)));
+    o.set_clean_session(false); // Preserve subscriptions
+    o.set_max_packet_size(MAX_PACKET_SIZE, MAX_PACKET_SIZE);
+    o.set_inflight(INFLIGHT);
+    o.set_last_will(rumqttc::LastWill::new(
+        opts.will_topic,
+        "offline".as_bytes().to_vec(),
+        QoS::AtLeastOnce,
+        true,
+    ));
+    o
+}
+
+fn v5_options(opts: &ConnectOptions<'_>) -> rumqttc::v5::MqttOptions {
+    let mut o = rumqttc::v5::MqttOptions::new(&opts.client_id, &opts.host, opts.port);
+    if !opts.mqtt.user.is_empty() {
+        o.set_credentials(&opts.mqtt.user, &opts.mqtt.pass);
+    }
+    if opts.use_tls {
+        o.set_transport(rumqttc::Transport::tls_with_config(
+            rumqttc::TlsConfiguration::Native,
+        ));
+    }
+    o.set_keep_alive(Duration::from_secs(u64::from(opts.mqtt.keep_alive_secs)));
+    // v5 replaces clean_session with clean_start + a session expiry: without an
+    // expiry the broker discards the session on disconnect, so both are needed
+    // to keep subscriptions and queued commands across a reconnect.
+    o.set_clean_start(false);
+    o.set_session_expiry_interval(Some(opts.mqtt.session_expiry_secs));
+    o.set_connection_timeout(opts.mqtt.connect_timeout_secs);
+    o.set_network_options(network_options(opts.mqtt));
+    o.set_max_packet_size(Some(MAX_PACKET_SIZE as u32));
+    o.set_outgoing_inflight_upper_limit(INFLIGHT);
+    // Ask for reason strings on failures (request_problem_info defaults to 1,
+    // but some brokers only send them when asked explicitly).
+    o.set_request_problem_info(Some(1));
+    let expiry = opts.mqtt.availability_expiry_secs;
+    o.set_last_will(rumqttc::v5::mqttbytes::v5::LastWill::new(
+        opts.will_topic,
+        "offline".as_bytes().to_vec(),
+        v5_qos(QoS::AtLeastOnce),
+        true,
+        (expiry > 0).then(|| LastWillProperties {
+            delay_interval: None,
+            payload_format_indicator: None,
+            message_expiry_interval: Some(expiry),
+            content_type: None,
+            response_topic: None,
+            correlation_data: None,
+            user_properties: Vec::new(),
+        }),
+    ));
+    o
+}
+
+fn v5_qos(qos: QoS) -> rumqttc::v5::mqttbytes::QoS {
+    match qos {
+        QoS::AtMostOnce => rumqttc::v5::mqttbytes::QoS::AtMostOnce,
+        QoS::AtLeastOnce => rumqttc::v5::mqttbytes::QoS::AtLeastOnce,
+        QoS::ExactlyOnce => rumqttc::v5::mqttbytes::QoS::ExactlyOnce,
+    }
+}
+
+impl Client {
+    pub(super) async fn publish(
+        &self,
+        topic: impl Into<String>,
+        qos: QoS,
+        retain: bool,
+        payload: impl Into<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        match self {
+            Self::V4(c) => c
+                .publish(topic, qos, retain, payload)
+                .await
+                .map_err(ClientError::V4),
+            Self::V5(c) => c
+                .publish(topic, v5_qos(qos), retain, payload.into())
+                .await
+                .map_err(ClientError::V5),
+            Self::Disabled => Ok(()),
+        }
+    }
+
+    pub(super) async fn publish_bytes(
+        &self,
+        topic: impl Into<String>,
+        qos: QoS,
+        retain: bool,
+        payload: Bytes,
+    ) -> Result<(), ClientError> {
+        match self {
+            Self::V4(c) => c
+                .publish_bytes(topic, qos, retain, payload)
+                .await
+                .map_err(ClientError::V4),
+            Self::V5(c) => c
+                .publish_bytes(topic, v5_qos(qos), retain, payload)
+                .await
+                .map_err(ClientError::V5),
+            Self::Disabled => Ok(()),
+        }
+    }
+
+    pub(super) async fn subscribe(
+        &self,
+        topic: impl Into<String>,
+        qos: QoS,
+    ) -> Result<(), ClientError> {
+        match self {
+            Self::V4(c) => c.subscribe(topic, qos).await.map_err(ClientError::V4),
+            Self::V5(c) => c
+                .subscribe(topic, v5_qos(qos))
+                .await
+                .map_err(ClientError::V5),
+            Self::Disabled => Ok(()),
+        }
+    }
+
+    /// Queue a clean DISCONNECT behind everything already requested, so the
+    /// broker discards the LWT instead of publishing it.
+    pub(super) async fn disconnect(&self) -> Result<(), ClientError> {
+        match self {
+            Self::V4(c) => c.disconnect().await.map_err(ClientError::V4),
+            Self::V5(c) => c.disconnect().await.map_err(ClientError::V5),
+            Self::Disabled => Ok(()),
+        }
+    }
+
+    pub(super) async fn unsubscribe(&self, topic: impl Into<String>) -> Result<(), ClientError> {
+        match self {
+            Self::V4(c) => c.unsubscribe(topic).await.map_err(ClientError::V4),
+            Self::V5(c) => c.unsubscribe(topic).await.map_err(ClientError::V5),
+            Self::Disabled => Ok(()),
+        }
+    }
+}
+
+impl EventLoop {
+    /// Poll the next event. Reconnection is handled by rumqttc - an `Err` just
+    /// means this attempt failed and the caller should back off and poll again.
+    pub(super) async fn poll(&mut self) -> Result<Incoming, ConnectionError> {
+        match self {
+            Self::V4(el) => match el.poll().await.map_err(ConnectionError::V4)? {
+                rumqttc::Event::Incoming(rumqttc::Packet::Publish(p)) => Ok(Incoming::Publish {
+                    topic: p.topic,
+                    payload: p.payload,
+                    retain: p.retain,
+                }),
+                rumqttc::Event::Incoming(rumqttc::Packet::ConnAck(_)) => Ok(Incoming::ConnAck),
+                rumqttc::Event::Incoming(rumqttc::Packet::PubAck(_)) => Ok(Incoming::PubAck),
+                rumqttc::Event::Outgoing(rumqttc::Outgoing::Disconnect) => {
+                    Ok(Incoming::DisconnectSent)
+                }
+                _ => Ok(Incoming::Other),
+            },
+            Self::V5(el) => {
+                let event = el.poll().await.map_err(|e| {
+                    if let rumqttc::v5::ConnectionError::ConnectionRefused(code) = &e {
+                        warn!("MQTT broker refused connection: {code:?}");
+                    }
+                    ConnectionError::V5(e)
+                })?;
+                match event {
+                    rumqttc::v5::Event::Incoming(packet) => Ok(v5_incoming(packet)),
+                    rumqttc::v5::Event::Outgoing(rumqttc::Outgoing::Disconnect) => {
+                        Ok(Incoming::DisconnectSent)
+                    }
+                    rumqttc::v5::Event::Outgoing(_) => Ok(Incoming::Other),
+                }
+            }
+            Self::Disabled => std::future::pending().await,
+        }
+    }
+}
+
+/// Normalize a v5 packet, logging any broker reason codes/strings on the way.
+fn v5_incoming(packet: V5Packet) -> Incoming {
+    match packet {
+        V5Packet::Publish(p) => Incoming::Publish {
+            topic: String::from_utf8_lossy(&p.topic).into_owned(),
+            payload: p.payload,
+            retain: p.retain,
+        },
+        V5Packet::ConnAck(ack) => {
+            let reason = ack
+                .properties
+                .as_ref()
+                .and_then(|p| p.reason_string.as_deref());
+            if ack.code == ConnectReturnCode::Success {
+                if let Some(reason) = reason {
+                    info!("MQTT broker: {reason}");
+                }
+                debug!("MQTT 5 session present: {}", ack.session_present);
+                Incoming::ConnAck
+            } else {
+                // rumqttc surfaces a refusal as a ConnectionError; this only
+                // catches brokers that send one without closing the socket.
+                warn!("MQTT connect failed: {}", describe(&ack.code, reason));
+                Incoming::Other
+            }
+        }
+        V5Packet::Disconnect(d) => {
+            let reason = d
+                .properties
+                .as_ref()
+                .and_then(|p| p.reason_string.as_deref());
+            if d.reason_code == DisconnectReasonCode::NormalDisconnection {
+                info!(
+                    "MQTT broker closed the connection: {}",
+                    describe(&d.reason_code, reason)
+                );
+            } else {
+                warn!(
+                    "MQTT broker disconnected us: {}",
+                    describe(&d.reason_code, reason)
+                );
+            }
+            Incoming::Other
+        }
+        V5Packet::SubAck(ack) => {
+            let reason = ack
+                .properties
+                .as_ref()
+                .and_then(|p| p.reason_string.as_deref());
+            for code in &ack.return_codes {
+                if !matches!(code, SubscribeReasonCode::Success(_)) {
+                    warn!("MQTT subscribe rejected: {}", describe(code, reason));
+                }
+            }
+            Incoming::Other
+        }
+        V5Packet::PubAck(ack) => {
+            // NoMatchingSubscribers is routine (nobody listening to an event).
+            if !matches!(
+                ack.reason,
+                PubAckReason::Success | PubAckReason::NoMatchingSubscribers
+            ) {
+                let reason = ack
+                    .properties
+                    .as_ref()
+                    .and_then(|p| p.reason_string.as_deref());
+                warn!("MQTT publish rejected: {}", describe(&ack.reason, reason));
+            }
+            Incoming::PubAck
+        }
+        _ => Incoming::Other,
+    }
+}
+
+/// "ReasonCode" or "ReasonCode (broker's reason string)".
+fn describe(code: &impl std::fmt::Debug, reason: Option<&str>) -> String {
+    match reason {
+        Some(r) if !r.is_empty() => format!("{code:?} ({r})"),
+        _ => format!("{code:?}"),
+    }
+}
+
+#[cfg(test)]
+pub(super) fn test_client() -> Client {
+    let opts = rumqttc::MqttOptions::new("test-client", "localhost", 1883);
+    let (client, _eventloop) = rumqttc::AsyncClient::new(opts, 10);
+    Client::V4(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mqtt_config(protocol_version: u8, availability_expiry_secs: u32) -> MqttConfig {
+        MqttConfig {
+            broker: "tcp://localhost:1883".to_string(),
+            user: "u".to_string(),
+            pass: "p".to_string(),
+            client_id: None,
+            protocol_version,
+            session_expiry_secs: 600,
+            availability_expiry_secs,
+            keep_alive_secs: 30,
+            connect_timeout_secs: 5,
+            tcp_nodelay: false,
+            bundled_state: false,
+        }
+    }
+
+    fn options(mqtt: &MqttConfig) -> ConnectOptions<'_> {
+        ConnectOptions {
+            client_id: "pc".to_string(),
+            host: "localhost".to_string(),
+            port: 1883,
+            use_tls: false,
+            mqtt,
+            will_topic: "pc-bridge/pc/availability",
+        }
+    }
+
+    #[test]
+    fn test_v5_options_session_and_will_expiry() {
+        let mqtt = mqtt_config(5, 86400);
+        let o = v5_options(&options(&mqtt));
+        assert!(!o.clean_start());
+        assert_eq!(o.session_expiry_interval(), Some(600));
+        let will = o.last_will().expect("LWT set");
+        assert!(will.retain);
+        assert_eq!(&will.message[..], b"offline");
+        assert_eq!(
+            will.properties.and_then(|p| p.message_expiry_interval),
+            Some(86400)
+        );
+
+        // 0 = the will never expires, so no properties at all.
+        let mqtt = mqtt_config(5, 0);
+        let will = v5_options(&options(&mqtt)).last_will().expect("LWT set");
+        assert!(will.properties.is_none());
+    }
+
+    #[test]
+    fn test_connect_picks_protocol() {
+        let v4 = mqtt_config(4, 0);
+        let v5 = mqtt_config(5, 0);
+        assert!(matches!(connect(&options(&v4), 1).0, Client::V4(_)));
+        assert!(matches!(connect(&options(&v5), 1).0, Client::V5(_)));
+        // No broker (HA API transport only).
+        let none = MqttConfig {
+            broker: String::new(),
+            ..mqtt_config(4, 0)
+        };
+        assert!(matches!(connect(&options(&none), 1).0, Client::Disabled));
+    }
+
+    #[test]
+    fn test_describe_reason() {
+        assert_eq!(
+            describe(&DisconnectReasonCode::NotAuthorized, Some("ACL denied")),
+            "NotAuthorized (ACL denied)"
+        );
+        assert_eq!(
+            describe(&DisconnectReasonCode::NotAuthorized, Some("")),
+            "NotAuthorized"
+        );
+        assert_eq!(
+            describe(&DisconnectReasonCode::NotAuthorized, None),
+            "NotAuthorized"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! `ConnectionDiagnostics`: time each step of reaching the broker
+//!
+//! A one-shot connection on its own client id (`<client_id>-diag`, so the
+//! broker doesn't kick the main one) that resolves the broker, opens TCP,
+//! does the TLS handshake for `ssl://` and waits for the CONNACK, stopping at
+//! the first step that fails. Each step is logged with its duration and the
+//! whole report goes to the results topic, so a connection that never comes
+//! up can be narrowed to DNS, a firewall, certificates or credentials.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+use serde::Serialize;
+
+use super::BrokerUrl;
+use super::MqttClient;
+use crate::config::Config;
+use crate::power::sync_mqtt::build_mqtt_connect;
+
+#[derive(Debug, Default, Serialize)]
+struct Report {
+    broker: String,
+    addresses: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dns_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tcp_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tls_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    connack_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+/// Send CONNECT, wait for the CONNACK, then DISCONNECT.
+fn connack(stream: &mut (impl Read + Write), connect: &[u8]) -> Result<(), String> {
+    stream
+        .write_all(connect)
+        .and_then(|()| stream.flush())
+        .map_err(|e| format!("sending CONNECT: {e}"))?;
+    let mut reply = [0u8; 4];
+    stream
+        .read_exact(&mut reply)
+        .map_err(|e| format!("waiting for CONNACK: {e}"))?;
+    if reply[0] != 0x20 || reply[1] != 0x02 {
+        return Err(format!(
+            "not a CONNACK (type={:#x}, len={})",
+            reply[0], reply[1]
+        ));
+    }
+    let _ = stream.write_all(&[0xE0, 0x00]);
+    match reply[3] {
+        0 => Ok(()),
+        4 => Err("CONNACK refused: bad user name or password".into()),
+        5 => Err("CONNACK refused: not authorized".into()),
+        rc => Err(format!("CONNACK refused (return code {rc})")),
+    }
+}
+
+/// Run every step against `config`'s broker. Blocking.
+fn diagnose(config: &Config) -> Report {
+    let timeout = Duration::from_secs(config.mqtt.connect_timeout_secs);
+    let mut report = Report {
+        broker: config.mqtt.broker.clone(),
+        ..Report::default()
+    };
+    let broker = match BrokerUrl::parse(&config.mqtt.broker) {
+        Ok(broker) => broker,
+        Err(e) => {
+            report.error = Some(format!("{e:#}"));
+            return report;
+        }
+    };
+    report.broker = broker.address();
+    let BrokerUrl {
+        host,
+        port,
+        tls: use_tls,
+        ..
+    } = broker;
+
+    let start = Instant::now();
+    let addrs: Vec<SocketAddr> = match (host.as_str(), port).to_socket_addrs() {
+        Ok(addrs) => addrs.collect(),
+        Err(e) => {
+            report.error = Some(format!("DNS lookup of {host} failed: {e}"));
+            return report;
+        }
+    };
+    report.dns_ms = Some(elapsed_ms(start));
+    report.addresses = addrs.iter().map(ToString::to_string).collect();
+    info!(
+        "Diagnostics: {host} resolved to [{}] in {} ms",
+        report.addresses.join(", "),
+        elapsed_ms(start)
+    );
+    if addrs.is_empty() {
+        report.error = Some(format!("DNS lookup of {host} returned no addresses"));
+        return report;
+    }
+
+    // Each address in turn, as the real connection does.
+    let start = Instant::now();
+    let mut last_err = None;
+    let stream = addrs.iter().find_map(|addr| {
+        TcpStream::connect_timeout(addr, timeout)
+            .inspect_err(|e| {
+                warn!("Diagnostics: TCP connect to {addr} failed: {e}");
+                last_err = Some(format!("TCP connect to {addr} failed: {e}"));
+            })
+            .ok()
+    });
+    let Some(stream) = stream else {
+        report.error = last_err;
+        return report;
+    };
+    report.tcp_ms = Some(elapsed_ms(start));
+    info!("Diagnostics: TCP connected in {} ms", elapsed_ms(start));
+    let _ = stream.set_read_timeout(Some(timeout));
+    let _ = stream.set_write_timeout(Some(timeout));
+    let _ = stream.set_nodelay(config.mqtt.tcp_nodelay);
+
+    let connect = build_mqtt_connect(
+        &format!("{}-diag", config.client_id()),
+        &config.mqtt.user,
+        &config.mqtt.pass,
+    );
+    let start;
+    let result = if use_tls {
+        let tls_start = Instant::now();
+        let tls = native_tls::TlsConnector::new()
+            .map_err(|e| format!("TLS init failed: {e}"))
+            .and_then(|connector| {
+                connector
+                    .connect(&host, stream)
+                    .map_err(|e| format!("TLS handshake failed: {e}"))
+            });
+        let mut tls = match tls {
+            Ok(tls) => tls,
+            Err(e) => {
+                report.error = Some(e);
+                return report;
+            }
+        };
+        report.tls_ms = Some(elapsed_ms(tls_start));
+        info!(
+            "Diagnostics: TLS handshake took {} ms",
+            elapsed_ms(tls_start)
+        );
+        start = Instant::now();
+        connack(&mut tls, &connect)
+    } else {
+        let mut stream = stream;
+        start = Instant::now();
+        connack(&mut stream, &connect)
+    };
+    match result {
+        Ok(()) => {
+            report.connack_ms = Some(elapsed_ms(start));
+            info!("Diagnostics: CONNACK accepted in {} ms", elapsed_ms(start));
+        }
+        Err(e) => report.error = Some(e),
+    }
+    report
+}
+
+impl MqttClient {
+    /// The `ConnectionDiagnostics` command: log and publish the timings.
+    pub async fn connection_diagnostics(&self, config: &Config) {
+        if config.mqtt.broker.is_empty() {
+            warn!("ConnectionDiagnostics: no MQTT broker configured");
+            return;
+        }
+        let config = config.clone();
+        let report = match tokio::task::spawn_blocking(move || diagnose(&config)).await {
+            Ok(report) => report,
+            Err(e) => {
+                warn!("ConnectionDiagnostics: task join error: {e}");
+                return;
+            }
+        };
+        match &report.error {
+            Some(e) => warn!("Diagnostics: {} unreachable: {e}", report.broker),
+            None => info!("Diagnostics: {} reachable", report.broker),
+        }
+        let mut result = serde_json::json!({
+            "command": "ConnectionDiagnostics",
+            "result": if report.error.is_some() { "failed" } else { "ok" },
+        });
+        if let (Some(obj), Ok(serde_json::Value::Object(fields))) =
+            (result.as_object_mut(), serde_json::to_value(&report))
+        {
+            obj.extend(fields);
+        }
+        self.publish_command_result(&result).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn config_for(broker: String) -> Config {
+        let mut config = Config::default();
+        config.mqtt.broker = broker;
+        config.mqtt.connect_timeout_secs = 2;
+        config
+    }
+
+    #[test]
+    fn test_diagnose_reports_each_step() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let broker = std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut header = [0u8; 2];
+            socket.read_exact(&mut header).unwrap();
+            let mut rest = vec![0u8; header[1] as usize];
+            socket.read_exact(&mut rest).unwrap();
+            socket.write_all(&[0x20, 0x02, 0x00, 0x00]).unwrap();
+        });
+        let report = diagnose(&config_for(format!("tcp://127.0.0.1:{port}")));
+        broker.join().unwrap();
+        assert_eq!(report.error, None);
+        assert_eq!(report.addresses, vec![format!("127.0.0.1:{port}")]);
+        assert!(report.dns_ms.is_some() && report.tcp_ms.is_some());
+        assert!(report.connack_ms.is_some());
+        assert_eq!(report.tls_ms, None);
+    }
+
+    #[test]
+    fn test_diagnose_stops_at_refused_connack() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut header = [0u8; 2];
+            socket.read_exact(&mut header).unwrap();
+            let mut rest = vec![0u8; header[1] as usize];
+            socket.read_exact(&mut rest).unwrap();
+            socket.write_all(&[0x20, 0x02, 0x00, 0x05]).unwrap();
+        });
+        let report = diagnose(&config_for(format!("tcp://127.0.0.1:{port}")));
+        assert!(report.tcp_ms.is_some());
+        assert_eq!(report.connack_ms, None);
+        assert_eq!(
+            report.error.as_deref(),
+            Some("CONNACK refused: not authorized")
+        );
+    }
+}
@@ -7,21 +7,39 @@
 
 use std::sync::Arc;
 
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use rumqttc::QoS;
 
-use super::payload::{HADevice, HADiscoveryPayload, derive_state_class};
+use super::payload::{
+    HABinarySensor, HAButton, HADevice, HADeviceTrigger, HADiscoveryPayload, HAImage, HALight,
+    HASelect, HASwitch, HAUpdate, derive_state_class,
+};
 // AvailabilityEntry is only constructed in the Windows-only HWiNFO registration.
 #[cfg(windows)]
 use super::payload::AvailabilityEntry;
-use super::{DISCOVERY_PREFIX, MqttClient};
-use crate::config::{Config, CustomCommand, CustomSensor};
+use super::{MqttClient, bundle};
+use crate::config::{Config, CustomCommand, CustomSensor, EntityOverride};
+use crate::feature_state::{FeatureState, TOPIC_LAYOUT};
+use crate::i18n::{tr, tr_args};
+use crate::plugins::{PluginCommand, PluginSensor};
+use crate::reporting::Reported;
 
 impl MqttClient {
     /// Publish a retained discovery config, logging on failure. A broker
     /// rejection (16 KB packet cap, ACL) mid-registration would otherwise
-    /// silently orphan the entity with no diagnostics.
+    /// silently orphan the entity with no diagnostics. The entity's
+    /// `entity_overrides` entry, if any, is applied first.
     async fn publish_discovery(&self, topic: &str, payload: impl Into<Vec<u8>>) {
+        let mut payload = payload.into();
+        if !payload.is_empty() {
+            let overrides = self
+                .entity_overrides
+                .lock()
+                .unwrap_or_else(|e| e.into_inner());
+            if let Some(entry) = topic.rsplit('/').nth(1).and_then(|id| overrides.get(id)) {
+                payload = apply_entity_override(payload, entry);
+            }
+        }
         if let Err(e) = self
             .client
             .publish(topic, QoS::AtLeastOnce, true, payload)
@@ -32,13 +50,26 @@ impl MqttClient {
     }
 
     pub(crate) async fn register_discovery(&self, config: &Config) {
+        config.entity_overrides.clone_into(
+            &mut self
+                .entity_overrides
+                .lock()
+                .unwrap_or_else(|e| e.into_inner()),
+        );
+
         // Fix #5: Use shared device reference instead of creating new one
         let device = &self.device;
 
+        // First, so the device this one is listed under exists
+        if let Some(group) = &config.group {
+            self.register_group(group).await;
+        }
+
         // Conditionally register sensors based on features
         if config.features.running_game {
             self.register_sensor_with_attributes(
                 device,
+                config,
                 "runninggames",
                 "Running Game",
                 "mdi:gamepad-variant",
@@ -50,6 +81,7 @@ impl MqttClient {
         if config.features.game_catalog {
             self.register_sensor_with_attributes(
                 device,
+                config,
                 "game_catalog",
                 "Game Catalog",
                 "mdi:gamepad-variant-outline",
@@ -62,6 +94,7 @@ impl MqttClient {
         if config.features.idle_tracking {
             self.register_sensor(
                 device,
+                config,
                 "lastactive",
                 "Last Active",
                 "mdi:clock-outline",
@@ -73,6 +106,7 @@ impl MqttClient {
             // Grows while idle, resets to ~0 on activity.
             self.register_sensor(
                 device,
+                config,
                 "idle_seconds",
                 "Idle Time",
                 "mdi:timer-sand",
@@ -80,8 +114,9 @@ impl MqttClient {
                 Some("s"),
             )
             .await;
-            self.register_sensor(
+            self.register_sensor_with_attributes(
                 device,
+                config,
                 "screensaver",
                 "Screensaver",
                 "mdi:monitor-shimmer",
@@ -94,7 +129,7 @@ impl MqttClient {
         if config.features.sleep_wake {
             // sleep_state has no availability (always published)
             let payload = HADiscoveryPayload {
-                name: "Sleep State".to_string(),
+                name: tr("Sleep State"),
                 unique_id: format!("{}_sleep_state", self.device_id),
                 state_topic: Some(self.sensor_topic("sleep_state")),
                 command_topic: None,
@@ -106,7 +141,12 @@ impl MqttClient {
                 device_class: None,
                 unit_of_measurement: None,
                 state_class: None,
-                json_attributes_topic: None,
+                // wake_reason / wake_source after a resume
+                json_attributes_topic: Some(self.sensor_attributes_topic("sleep_state")),
+                expire_after: None,
+                entity_category: None,
+                value_template: None,
+                json_attributes_template: None,
             };
             let topic = self.config_topic("sensor", "sleep_state");
             let Ok(json) = serde_json::to_string(&payload) else {
@@ -114,18 +154,28 @@ impl MqttClient {
                 return;
             };
             self.publish_discovery(&topic, json).await;
+            self.register_pc_state(device).await;
         }
 
         // Display power state sensor
         if config.features.display_state {
-            self.register_sensor(device, "display", "Display", "mdi:monitor", None, None)
-                .await;
+            self.register_sensor(
+                device,
+                config,
+                "display",
+                "Display",
+                "mdi:monitor",
+                None,
+                None,
+            )
+            .await;
         }
 
         // Session lock/unlock sensor (WTS on Windows, logind on Linux).
         if config.features.session_state {
             self.register_sensor(
                 device,
+                config,
                 "session",
                 "Session State",
                 "mdi:account-lock",
@@ -135,10 +185,26 @@ impl MqttClient {
             .await;
         }
 
+        // Remote Desktop connected (same WTS / logind producer as `session`;
+        // the connected sessions as attributes)
+        if config.features.remote_desktop {
+            self.register_binary_sensor(
+                device,
+                config,
+                "remote_desktop",
+                "Remote Desktop",
+                "mdi:remote-desktop",
+                Some("connectivity"),
+                true,
+            )
+            .await;
+        }
+
         // Default audio output device sensor (WASAPI on Windows, pactl on Linux).
         if config.features.audio_device {
             self.register_sensor(
                 device,
+                config,
                 "audio_device",
                 "Default Audio Device",
                 "mdi:speaker",
@@ -152,6 +218,7 @@ impl MqttClient {
         if config.features.mic {
             self.register_sensor(
                 device,
+                config,
                 "mic",
                 "Microphone In Use",
                 "mdi:microphone",
@@ -161,14 +228,23 @@ impl MqttClient {
             .await;
         }
         if config.features.webcam {
-            self.register_sensor(device, "webcam", "Webcam In Use", "mdi:webcam", None, None)
-                .await;
+            self.register_sensor(
+                device,
+                config,
+                "webcam",
+                "Webcam In Use",
+                "mdi:webcam",
+                None,
+                None,
+            )
+            .await;
         }
 
         // Now playing (media session) sensor (GSMTC on Windows, playerctl on Linux).
         if config.features.now_playing {
             self.register_sensor(
                 device,
+                config,
                 "now_playing",
                 "Now Playing",
                 "mdi:music",
@@ -183,9 +259,11 @@ impl MqttClient {
         let system_any = config.features.cpu_sensor
             || config.features.memory_sensor
             || config.features.active_window;
+        // CPU, memory and GPU carry their 1m/5m averages and session peak.
         if config.features.cpu_sensor {
-            self.register_sensor(
+            self.register_sensor_with_attributes(
                 device,
+                config,
                 "cpu_usage",
                 "CPU Usage",
                 "mdi:cpu-64-bit",
@@ -195,8 +273,9 @@ impl MqttClient {
             .await;
         }
         if config.features.memory_sensor {
-            self.register_sensor(
+            self.register_sensor_with_attributes(
                 device,
+                config,
                 "memory_usage",
                 "Memory Usage",
                 "mdi:memory",
@@ -208,6 +287,7 @@ impl MqttClient {
         if config.features.active_window {
             self.register_sensor(
                 device,
+                config,
                 "active_window",
                 "Active Window",
                 "mdi:application",
@@ -215,10 +295,24 @@ impl MqttClient {
                 None,
             )
             .await;
+            if config.features.window_history {
+                self.register_sensor_with_attributes(
+                    device,
+                    config,
+                    "window_history",
+                    "Window History",
+                    "mdi:history",
+                    None,
+                    None,
+                )
+                .await;
+            }
         }
         if system_any {
-            self.register_sensor(
+            // Time remaining, health and power draw ride along as attributes.
+            self.register_sensor_with_attributes(
                 device,
+                config,
                 "battery_level",
                 "Battery Level",
                 "mdi:battery",
@@ -228,6 +322,7 @@ impl MqttClient {
             .await;
             self.register_sensor(
                 device,
+                config,
                 "battery_charging",
                 "Battery Charging",
                 "mdi:battery-charging",
@@ -239,6 +334,7 @@ impl MqttClient {
             // Bridge health diagnostics (uptime in seconds, version in attributes)
             self.register_sensor_with_attributes(
                 device,
+                config,
                 "bridge_health",
                 "Bridge Health",
                 "mdi:heart-pulse",
@@ -248,10 +344,33 @@ impl MqttClient {
             .await;
         }
 
+        // Commands waiting for a slot (running / dropped counts in attributes)
+        self.register_sensor_with_attributes(
+            device,
+            config,
+            "command_queue",
+            "Command Queue",
+            "mdi:tray-full",
+            None,
+            None,
+        )
+        .await;
+        // On while a flooding command is being ignored
+        self.register_binary_sensor(
+            device,
+            config,
+            "command_flood",
+            "Command Flood",
+            "mdi:alert-octagon",
+            Some("problem"),
+            true,
+        )
+        .await;
+
         // Steam update sensor - no availability so updates persist while PC is off/asleep
         if config.features.steam_updates {
             let payload = HADiscoveryPayload {
-                name: "Steam Updating".to_string(),
+                name: tr("Steam Updating"),
                 unique_id: format!("{}_steam_updating", self.device_id),
                 state_topic: Some(self.sensor_topic("steam_updating")),
                 command_topic: None,
@@ -264,6 +383,10 @@ impl MqttClient {
                 device_class: None,
                 unit_of_measurement: None,
                 state_class: None,
+                expire_after: None,
+                entity_category: None,
+                value_template: None,
+                json_attributes_template: None,
             };
             let topic = self.config_topic("sensor", "steam_updating");
             let Ok(json) = serde_json::to_string(&payload) else {
@@ -275,8 +398,9 @@ impl MqttClient {
 
         // GPU sensor
         if config.features.gpu_sensor {
-            self.register_sensor(
+            self.register_sensor_with_attributes(
                 device,
+                config,
                 "gpu_usage",
                 "GPU Usage",
                 "mdi:expansion-card",
@@ -290,6 +414,7 @@ impl MqttClient {
         if config.features.network_sensor {
             self.register_sensor_with_attributes(
                 device,
+                config,
                 "network_throughput",
                 "Network Throughput",
                 "mdi:network",
@@ -299,10 +424,88 @@ impl MqttClient {
             .await;
         }
 
+        // Network name / metered / VPN
+        if config.features.network_connection {
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "network_connection",
+                "Network",
+                "mdi:wifi",
+                None,
+                None,
+            )
+            .await;
+            self.register_binary_sensor(
+                device,
+                config,
+                "network_metered",
+                "Metered Connection",
+                "mdi:cash",
+                None,
+                false,
+            )
+            .await;
+            self.register_binary_sensor(
+                device,
+                config,
+                "vpn_active",
+                "VPN",
+                "mdi:vpn",
+                Some("connectivity"),
+                true,
+            )
+            .await;
+        }
+
+        // Round trip to the broker / HA host
+        if config.features.latency {
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "latency",
+                "Latency",
+                "mdi:lan-pending",
+                Some("duration"),
+                Some("ms"),
+            )
+            .await;
+        }
+
+        // Docker: the per-container entities are registered by the docker
+        // sensor itself.
+        if config.features.docker {
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "docker_containers",
+                "Docker Containers Running",
+                "mdi:docker",
+                None,
+                None,
+            )
+            .await;
+        }
+
+        // External IP
+        if config.features.public_ip {
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "public_ip",
+                "Public IP",
+                "mdi:ip-network",
+                None,
+                None,
+            )
+            .await;
+        }
+
         // Disk usage sensor
         if config.features.disk_sensor {
             self.register_sensor_with_attributes(
                 device,
+                config,
                 "disk_usage",
                 "Disk Usage",
                 "mdi:harddisk",
@@ -316,6 +519,7 @@ impl MqttClient {
         if config.features.uptime_sensor {
             self.register_sensor(
                 device,
+                config,
                 "system_uptime",
                 "System Uptime",
                 "mdi:clock-check",
@@ -323,6 +527,192 @@ impl MqttClient {
                 Some("s"),
             )
             .await;
+            self.register_sensor(
+                device,
+                config,
+                "last_boot",
+                "Last Boot",
+                "mdi:restart",
+                Some("timestamp"),
+                None,
+            )
+            .await;
+        }
+
+        // Next scheduled wake (timestamp; what set it and the upcoming timers
+        // as attributes)
+        if config.features.wake_timers {
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "next_wake",
+                "Next Wake",
+                "mdi:alarm",
+                Some("timestamp"),
+                None,
+            )
+            .await;
+        }
+
+        // Power requests keeping the PC awake (count; who and why as attributes)
+        if config.features.sleep_blockers {
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "sleep_blockers",
+                "Sleep Blockers",
+                "mdi:sleep-off",
+                None,
+                None,
+            )
+            .await;
+        }
+
+        // Power plan select (options known once the sensor has listed the plans;
+        // it registers the select itself the first time) + AC source.
+        if config.features.power_plan {
+            let plans = crate::power::plan::cached_names();
+            if !plans.is_empty() {
+                self.register_power_plan_select(&plans).await;
+            }
+            self.register_binary_sensor(
+                device,
+                config,
+                "ac_connected",
+                "AC Power",
+                "mdi:power-plug",
+                Some("plug"),
+                false,
+            )
+            .await;
+        }
+
+        if config.features.display_mode && !config.display_modes.is_empty() {
+            let names: Vec<String> = config
+                .display_modes
+                .iter()
+                .map(|m| m.name.clone())
+                .collect();
+            self.register_display_mode_select(&names).await;
+        }
+
+        if config.features.game_art {
+            self.register_game_art_image().await;
+        }
+
+        if config.features.theme {
+            self.register_theme_select().await;
+        }
+
+        if config.features.gamepad {
+            self.register_binary_sensor(
+                device,
+                config,
+                "gamepad_connected",
+                "Gamepad Connected",
+                "mdi:controller",
+                Some("connectivity"),
+                false,
+            )
+            .await;
+            self.register_binary_sensor(
+                device,
+                config,
+                "gamepad_active",
+                "Gamepad Active",
+                "mdi:controller-classic",
+                Some("occupancy"),
+                false,
+            )
+            .await;
+        }
+
+        if config.features.media_playing {
+            self.register_binary_sensor(
+                device,
+                config,
+                "media_playing",
+                "Media Playing",
+                "mdi:music",
+                Some("sound"),
+                true,
+            )
+            .await;
+        }
+
+        if config.features.vr {
+            self.register_binary_sensor(
+                device,
+                config,
+                "vr_active",
+                "VR Active",
+                "mdi:virtual-reality",
+                Some("running"),
+                true,
+            )
+            .await;
+        }
+
+        if config.features.transcoding {
+            self.register_binary_sensor(
+                device,
+                config,
+                "transcoding",
+                "Transcoding",
+                "mdi:video-box",
+                Some("running"),
+                true,
+            )
+            .await;
+        }
+
+        if config.features.openrgb {
+            self.register_rgb_light().await;
+        }
+
+        if config.features.perf_mode {
+            self.register_perf_mode_switch().await;
+        }
+        if config.features.focus_mode {
+            self.register_focus_switch().await;
+            self.register_sensor(
+                device,
+                config,
+                "focus_remaining",
+                "Focus Remaining",
+                "mdi:timer-sand",
+                None,
+                Some("min"),
+            )
+            .await;
+        }
+        if config.features.active_window {
+            self.register_reporting_switch(Reported::ActiveWindow).await;
+        }
+        if config.features.running_game {
+            self.register_reporting_switch(Reported::Games).await;
+        }
+
+        // Agent update entity: announced whenever checks are on; Install only
+        // with the opt-in InstallUpdate command.
+        if config.update_channel != "disabled" {
+            self.register_update(config).await;
+        }
+
+        // Pending Windows Updates (count; titles and reboot flag as attributes).
+        // Windows-only producer, so like HWiNFO it's never registered elsewhere.
+        #[cfg(windows)]
+        if config.features.windows_update {
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "windows_updates",
+                "Windows Updates",
+                "mdi:microsoft-windows",
+                None,
+                None,
+            )
+            .await;
         }
 
         // HWiNFO sensors are Windows-only - the producer task is
@@ -335,6 +725,7 @@ impl MqttClient {
             // Temperatures
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "cpu_package_temp",
                 "CPU Package Temperature",
                 "mdi:thermometer",
@@ -344,6 +735,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "gpu_temp",
                 "GPU Temperature",
                 "mdi:thermometer",
@@ -353,6 +745,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "gpu_hotspot_temp",
                 "GPU Hot Spot Temperature",
                 "mdi:thermometer-alert",
@@ -362,6 +755,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "gpu_memory_temp",
                 "GPU Memory Temperature",
                 "mdi:thermometer-lines",
@@ -373,6 +767,7 @@ impl MqttClient {
             // Power
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "cpu_package_power",
                 "CPU Package Power",
                 "mdi:flash",
@@ -382,6 +777,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "cpu_soc_power",
                 "CPU SoC Power",
                 "mdi:flash-outline",
@@ -391,6 +787,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "gpu_power",
                 "GPU Power",
                 "mdi:flash",
@@ -402,6 +799,7 @@ impl MqttClient {
             // Clocks
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "cpu_effective_clock",
                 "CPU Effective Clock",
                 "mdi:speedometer",
@@ -411,6 +809,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "gpu_core_clock",
                 "GPU Core Clock",
                 "mdi:speedometer",
@@ -420,6 +819,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "gpu_memory_clock",
                 "GPU Memory Clock",
                 "mdi:speedometer-medium",
@@ -431,6 +831,7 @@ impl MqttClient {
             // Utilization
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "cpu_total_usage",
                 "CPU Total Usage",
                 "mdi:cpu-64-bit",
@@ -440,6 +841,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "gpu_core_load",
                 "GPU Core Load",
                 "mdi:expansion-card",
@@ -449,6 +851,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "gpu_vram_usage_pct",
                 "GPU VRAM Usage",
                 "mdi:memory",
@@ -460,6 +863,7 @@ impl MqttClient {
             // Fan + framerate
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "gpu_fan_rpm",
                 "GPU Fan",
                 "mdi:fan",
@@ -469,6 +873,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "framerate",
                 "Framerate",
                 "mdi:speedometer",
@@ -480,6 +885,7 @@ impl MqttClient {
             // Motherboard SuperIO sensors: 4 fan headers + VRM temperature.
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "case_fan_cpu",
                 "CPU Fan",
                 "mdi:fan",
@@ -489,6 +895,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "case_fan_cpu_opt",
                 "CPU OPT Fan",
                 "mdi:fan",
@@ -498,6 +905,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "case_fan_system_1",
                 "System Fan 1",
                 "mdi:fan",
@@ -507,6 +915,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "case_fan_system_2",
                 "System Fan 2",
                 "mdi:fan",
@@ -516,6 +925,7 @@ impl MqttClient {
             .await;
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "vrm_temp",
                 "VRM Temperature",
                 "mdi:thermometer",
@@ -531,6 +941,7 @@ impl MqttClient {
             // anything useful.
             self.register_hwinfo_sensor(
                 device,
+                config,
                 "hwinfo_diagnostic",
                 "HWiNFO Diagnostic",
                 "mdi:bug-outline",
@@ -545,6 +956,7 @@ impl MqttClient {
         // are published to the attributes topic; state stays under HA's 255-char cap.
         self.register_sensor_with_attributes(
             device,
+            config,
             "bridge_info",
             "Bridge Info",
             "mdi:information-outline",
@@ -558,10 +970,20 @@ impl MqttClient {
         if config.features.launch_game {
             self.register_button(device, "Launch", "mdi:rocket-launch")
                 .await;
+            // Dropdown of the configured games; refreshed with the config
+            let games: Vec<String> = crate::commands::launchable_games(config)
+                .into_iter()
+                .map(|(name, _)| name)
+                .collect();
+            if !games.is_empty() {
+                self.register_game_launch_select(&games).await;
+            }
         }
         if config.features.close_game {
             self.register_button(device, "CloseGame", "mdi:close-box")
                 .await;
+            self.register_button(device, "StopGame", "mdi:stop-circle")
+                .await;
         }
         if config.features.steam_library {
             self.register_button(device, "RefreshSteamGames", "mdi:steam")
@@ -576,6 +998,11 @@ impl MqttClient {
                 .await;
         }
 
+        // Wake-on-LAN relay: one button per PC this one can wake.
+        for target in &config.wol_relay {
+            self.register_wol_button(device, &target.device_name).await;
+        }
+
         // Power control buttons - each gated by its own feature flag.
         if config.features.cmd_shutdown {
             self.register_button(device, "Shutdown", "mdi:power").await;
@@ -602,6 +1029,67 @@ impl MqttClient {
             self.register_button(device, "MonitorOn", "mdi:monitor")
                 .await;
         }
+        if config.features.cmd_windows_update_install {
+            self.register_button(device, "WindowsUpdateInstall", "mdi:download")
+                .await;
+        }
+        if config.features.cmd_speedtest {
+            self.register_button(device, "Speedtest", "mdi:speedometer")
+                .await;
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "speedtest_download",
+                "Speedtest Download",
+                "mdi:download-network",
+                Some("data_rate"),
+                Some("Mbit/s"),
+            )
+            .await;
+            self.register_sensor(
+                device,
+                config,
+                "speedtest_upload",
+                "Speedtest Upload",
+                "mdi:upload-network",
+                Some("data_rate"),
+                Some("Mbit/s"),
+            )
+            .await;
+            self.register_sensor(
+                device,
+                config,
+                "speedtest_ping",
+                "Speedtest Ping",
+                "mdi:timer-outline",
+                Some("duration"),
+                Some("ms"),
+            )
+            .await;
+        }
+        // RestoreConfig takes a payload, so it's a command topic, not a button.
+        if config.features.cmd_config_backup {
+            self.register_button(device, "BackupConfig", "mdi:content-save-cog")
+                .await;
+        }
+        if config.features.cmd_disk_cleanup {
+            self.register_button(device, "DiskCleanup", "mdi:broom")
+                .await;
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "disk_cleanup_freed",
+                "Disk Cleanup Freed",
+                "mdi:broom",
+                Some("data_size"),
+                Some("MB"),
+            )
+            .await;
+        }
+        // Printer sensors are registered by the printer sensor itself.
+        if config.features.printers {
+            self.register_button(device, "Print", "mdi:printer").await;
+        }
 
         // Discord buttons
         // DiscordJoin: Expects a launcher payload like "url:discord://discord.com/channels/..."
@@ -614,6 +1102,13 @@ impl MqttClient {
                 .await;
         }
 
+        // Always available: removes entities left behind by a device rename
+        self.register_button(device, "CleanupEntities", "mdi:broom")
+            .await;
+        // Always available: times each step of reaching the broker
+        self.register_button(device, "ConnectionDiagnostics", "mdi:lan-check")
+            .await;
+
         // Audio control commands (media keys) if enabled
         if config.features.media_controls {
             for (name, icon) in [
@@ -630,6 +1125,7 @@ impl MqttClient {
             // Register volume sensor
             self.register_sensor(
                 device,
+                config,
                 "volume_level",
                 "Volume Level",
                 "mdi:volume-high",
@@ -642,8 +1138,41 @@ impl MqttClient {
         // Register notify service only if notifications enabled
         if config.features.notifications {
             self.register_notify_service(device).await;
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "notifications_suppressed",
+                "Notifications Suppressed",
+                "mdi:bell-off",
+                None,
+                None,
+            )
+            .await;
+            self.register_sensor_with_attributes(
+                device,
+                config,
+                "last_notification",
+                "Last Notification",
+                "mdi:bell-ring",
+                None,
+                None,
+            )
+            .await;
+            self.register_button(device, "ClearNotificationHistory", "mdi:bell-remove")
+                .await;
         }
 
+        // Device triggers for the discrete events on the event topic, so they can
+        // be picked from HA's automation UI instead of hand-written MQTT triggers.
+        for (event, trigger_type, subtype, enabled) in device_triggers(config) {
+            if enabled {
+                self.register_device_trigger(device, event, trigger_type, subtype)
+                    .await;
+            }
+        }
+        self.sync_game_triggers(device, config).await;
+        self.sync_process_sensors(device, config).await;
+
         info!("Registered HA discovery");
     }
 
@@ -656,7 +1185,11 @@ impl MqttClient {
     pub(crate) async fn clear_disabled_entities(&self, config: &Config) {
         let mut cleared = 0usize;
         for (component, object_id, enabled) in feature_entities(config) {
-            if enabled {
+            let overridden_off = config
+                .entity_overrides
+                .get(object_id)
+                .is_some_and(|o| !o.enabled);
+            if enabled && !overridden_off {
                 continue;
             }
             let topic = self.config_topic(component, object_id);
@@ -669,9 +1202,15 @@ impl MqttClient {
                 cleared += 1;
             }
             // Also clear the retained state + attributes so they don't linger on
-            // the broker after the entity is removed. Only sensors publish state
-            // (buttons don't), so skip the empty-topic churn for those.
-            if component == "sensor" {
+            // the broker after the entity is removed. Only sensors (and the
+            // select/binary sensor/update/light/switch/image entities sharing their
+            // state topics) publish state; buttons don't, so skip the
+            // empty-topic churn for those.
+            if matches!(
+                component,
+                "sensor" | "select" | "binary_sensor" | "update" | "light" | "switch" | "image"
+            ) {
+                self.forget_value(object_id);
                 let _ = self
                     .client
                     .publish(
@@ -692,10 +1231,117 @@ impl MqttClient {
                     .await;
             }
         }
+        // SMART disk entities, likewise per disk at runtime.
+        if !config.features.disk_health {
+            let mut state = FeatureState::load();
+            if !state.smart_disks.is_empty() {
+                for slug in &state.smart_disks {
+                    for id in [
+                        disk_health_id(slug),
+                        disk_temperature_id(slug),
+                        disk_reallocated_id(slug),
+                    ] {
+                        self.forget_value(&id);
+                    }
+                }
+                for topic in disk_health_topics(&self.device_name, &state.smart_disks) {
+                    let _ = self
+                        .client
+                        .publish(&topic, QoS::AtLeastOnce, true, Vec::<u8>::new())
+                        .await;
+                }
+                cleared += state.smart_disks.len();
+                state.smart_disks.clear();
+                if let Err(e) = state.save() {
+                    warn!("Failed to update feature_state.json: {e}");
+                }
+            }
+        }
+        // Printer sensors, likewise per printer at runtime.
+        if !config.features.printers {
+            let mut state = FeatureState::load();
+            if !state.printers.is_empty() {
+                for slug in &state.printers {
+                    self.forget_value(&printer_id(slug));
+                }
+                for topic in printer_topics(&self.device_name, &state.printers) {
+                    let _ = self
+                        .client
+                        .publish(&topic, QoS::AtLeastOnce, true, Vec::<u8>::new())
+                        .await;
+                }
+                cleared += state.printers.len();
+                state.printers.clear();
+                if let Err(e) = state.save() {
+                    warn!("Failed to update feature_state.json: {e}");
+                }
+            }
+        }
+        // USB presence sensors, likewise per configured device at runtime.
+        if !config.features.usb_devices {
+            let mut state = FeatureState::load();
+            if !state.usb_devices.is_empty() {
+                for slug in &state.usb_devices {
+                    self.forget_value(&usb_device_id(slug));
+                }
+                for topic in usb_device_topics(&self.device_name, &state.usb_devices) {
+                    let _ = self
+                        .client
+                        .publish(&topic, QoS::AtLeastOnce, true, Vec::<u8>::new())
+                        .await;
+                }
+                cleared += state.usb_devices.len();
+                state.usb_devices.clear();
+                if let Err(e) = state.save() {
+                    warn!("Failed to update feature_state.json: {e}");
+                }
+            }
+        }
+        // Docker container entities, likewise per configured container.
+        if !config.features.docker {
+            let mut state = FeatureState::load();
+            if !state.docker_containers.is_empty() {
+                for slug in &state.docker_containers {
+                    self.forget_value(&docker_container_id(slug));
+                }
+                for topic in docker_container_topics(&self.device_name, &state.docker_containers) {
+                    let _ = self
+                        .client
+                        .publish(&topic, QoS::AtLeastOnce, true, Vec::<u8>::new())
+                        .await;
+                }
+                cleared += state.docker_containers.len();
+                state.docker_containers.clear();
+                if let Err(e) = state.save() {
+                    warn!("Failed to update feature_state.json: {e}");
+                }
+            }
+        }
+        // Peripheral battery sensors are registered per device at runtime, so
+        // they're found through feature_state.json rather than the table.
+        if !config.features.device_batteries {
+            let mut state = FeatureState::load();
+            if !state.battery_devices.is_empty() {
+                for slug in &state.battery_devices {
+                    self.forget_value(&device_battery_id(slug));
+                }
+                for topic in device_battery_topics(&self.device_name, &state.battery_devices) {
+                    let _ = self
+                        .client
+                        .publish(&topic, QoS::AtLeastOnce, true, Vec::<u8>::new())
+                        .await;
+                }
+                cleared += state.battery_devices.len();
+                state.battery_devices.clear();
+                if let Err(e) = state.save() {
+                    warn!("Failed to update feature_state.json: {e}");
+                }
+            }
+        }
         // The notify service uses a 3-segment device-level config topic, not the
         // per-entity shape, so clear it directly.
         if !config.features.notifications {
-            let topic = format!("{}/notify/{}/config", DISCOVERY_PREFIX, self.device_name);
+            let topic = Self::notify_config_topic_static(&self.device_name);
             self.publish_discovery(&topic, Vec::<u8>::new()).await;
         }
         if cleared > 0 {
@@ -707,24 +1353,125 @@ impl MqttClient {
         }
     }
 
-    /// Helper to register a single sensor
-    async fn register_sensor(
-        &self,
-        device: &Arc<HADevice>,
-        name: &str,
-        display_name: &str,
-        icon: &str,
-        device_class: Option<&str>,
-        unit: Option<&str>,
+    /// One-shot move of state/attributes/availability out of the discovery
+    /// prefix: `register_discovery` has already republished every config
+    /// pointing at the new `pc-bridge/<device>/...` topics, so all that's left
+    /// is clearing the old retained `homeassistant/sensor/<device>/...` values.
+    /// Recorded in `feature_state.json` so it runs once per install. Called by
+    /// the agent right after `new()`.
+    pub(crate) async fn migrate_state_topics(&self, config: &Config) {
+        let mut state = FeatureState::load();
+        if state.topic_layout >= TOPIC_LAYOUT {
+            return;
+        }
+        let mut sensors: Vec<String> = feature_entities(config)
+            .into_iter()
+            .filter(|(component, _, _)| *component == "sensor")
+            .map(|(_, oid, _)| oid.to_string())
+            .collect();
+        sensors.push("bridge_info".to_string());
+        sensors.extend(
+            config
+                .custom_sensors
+                .iter()
+                .map(|s| format!("custom_{}", s.name)),
+        );
+
+        let topics = Self::legacy_state_topics(&self.device_name, &sensors);
+        for topic in &topics {
+            let _ = self
+                .client
+                .publish(topic, QoS::AtLeastOnce, true, Vec::<u8>::new())
+                .await;
+        }
+        info!(
+            "Migrated state topics to {}/{}/... (cleared {} legacy retained topics)",
+            super::STATE_PREFIX,
+            self.device_name,
+            topics.len()
+        );
+
+        state.topic_layout = TOPIC_LAYOUT;
+        if let Err(e) = state.save() {
+            warn!("Failed to record topic migration (will re-run next start): {e}");
+        }
+    }
+
+    /// Remove every entity and retained topic published under a previous
+    /// `device_name` (recorded in `feature_state.json`), so a rename doesn't
+    /// leave a dead duplicate device in HA. Backs the CleanupEntities command
+    /// and `--cleanup-entities`. Returns how many old names were cleaned.
+    pub(crate) async fn cleanup_stale_devices(&self, config: &Config) -> usize {
+        let mut state = FeatureState::load();
+        let old_names: Vec<String> = state
+            .device_names
+            .iter()
+            .filter(|n| **n != self.device_name)
+            .cloned()
+            .collect();
+        if old_names.is_empty() {
+            info!("Entity cleanup: no previous device names recorded");
+            return 0;
+        }
+        for name in &old_names {
+            let mut topics = stale_device_topics(name, config);
+            topics.extend(device_battery_topics(name, &state.battery_devices));
+            topics.extend(disk_health_topics(name, &state.smart_disks));
+            topics.extend(printer_topics(name, &state.printers));
+            topics.extend(usb_device_topics(name, &state.usb_devices));
+            topics.extend(process_sensor_topics(name, &state.process_sensors));
+            topics.extend(docker_container_topics(name, &state.docker_containers));
+            topics.extend(game_trigger_topics(name, &state.game_triggers));
+            if let Some(group) = &config.group {
+                topics.push(crate::group::member_topic(group, name));
+            }
+            for topic in &topics {
+                let _ = self
+                    .client
+                    .publish(topic, QoS::AtLeastOnce, true, Vec::<u8>::new())
+                    .await;
+            }
+            info!(
+                "Entity cleanup: cleared {} retained topics for old device '{}'",
+                topics.len(),
+                name
+            );
+        }
+        state.device_names.retain(|n| *n == self.device_name);
+        if let Err(e) = state.save() {
+            warn!("Entity cleanup: failed to update feature_state.json: {e}");
+        }
+        old_names.len()
+    }
+
+    /// Helper to register a single sensor
+    async fn register_sensor(
+        &self,
+        device: &Arc<HADevice>,
+        config: &Config,
+        name: &str,
+        display_name: &str,
+        icon: &str,
+        device_class: Option<&str>,
+        unit: Option<&str>,
     ) {
-        self.register_sensor_internal(device, name, display_name, icon, device_class, unit, false)
-            .await;
+        self.register_sensor_internal(
+            device,
+            name,
+            display_name,
+            icon,
+            device_class,
+            unit,
+            false,
+            sensor_expire_after(name, config),
+        )
+        .await;
     }
 
     /// Helper to register a button command
     async fn register_button(&self, device: &Arc<HADevice>, name: &str, icon: &str) {
         let payload = HADiscoveryPayload {
-            name: name.to_string(),
+            name: tr(name),
             unique_id: format!("{}_{}", self.device_id, name),
             state_topic: None,
             command_topic: Some(self.command_topic(name)),
@@ -737,6 +1484,10 @@ impl MqttClient {
             unit_of_measurement: None,
             state_class: None,
             json_attributes_topic: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let topic = self.config_topic("button", name);
@@ -747,18 +1498,525 @@ impl MqttClient {
         self.publish_discovery(&topic, json).await;
     }
 
+    /// "Wake <target>" button for a Wake-on-LAN relay target. Its command
+    /// topic is the relay topic, not a command of this device.
+    async fn register_wol_button(&self, device: &Arc<HADevice>, target: &str) {
+        let id = format!("wol_{}", target.replace('-', "_"));
+        let payload = HADiscoveryPayload {
+            name: tr_args("Wake {target}", &[("target", target)]),
+            unique_id: format!("{}_{}", self.device_id, id),
+            state_topic: None,
+            command_topic: Some(crate::wol::relay_topic(target)),
+            availability_topic: Some(self.availability_topic()),
+            availability: None,
+            availability_mode: None,
+            device: Arc::clone(device),
+            icon: Some("mdi:lan-connect".to_string()),
+            device_class: None,
+            unit_of_measurement: None,
+            state_class: None,
+            json_attributes_topic: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
+        };
+
+        let topic = self.config_topic("button", &id);
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA discovery payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Register (or refresh the options of) the power plan select. State is the
+    /// `power_plan` sensor topic; choosing an option sends `PowerPlan` with
+    /// the plan name.
+    pub(crate) async fn register_power_plan_select(&self, options: &[String]) {
+        let payload = HASelect {
+            name: tr("Power Plan"),
+            unique_id: format!("{}_power_plan", self.device_id),
+            state_topic: Some(self.sensor_topic("power_plan")),
+            command_topic: self.command_topic("PowerPlan"),
+            availability_topic: self.availability_topic(),
+            options: options.to_vec(),
+            device: Arc::clone(&self.device),
+            icon: "mdi:speedometer".to_string(),
+            json_attributes_topic: None,
+        };
+        let topic = self.config_topic("select", "power_plan");
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA select payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Register the display mode select. State is the `display_mode` sensor
+    /// topic; choosing an option sends `DisplayMode` with the mode name.
+    async fn register_display_mode_select(&self, options: &[String]) {
+        let payload = HASelect {
+            name: tr("Display Mode"),
+            unique_id: format!("{}_display_mode", self.device_id),
+            state_topic: Some(self.sensor_topic("display_mode")),
+            command_topic: self.command_topic("DisplayMode"),
+            availability_topic: self.availability_topic(),
+            options: options.to_vec(),
+            device: Arc::clone(&self.device),
+            icon: "mdi:monitor-screenshot".to_string(),
+            json_attributes_topic: None,
+        };
+        let topic = self.config_topic("select", "display_mode");
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA select payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Register the theme select. State is the `theme` sensor topic; choosing
+    /// an option sends `Theme` with `light` or `dark`.
+    async fn register_theme_select(&self) {
+        let payload = HASelect {
+            name: tr("Theme"),
+            unique_id: format!("{}_theme", self.device_id),
+            state_topic: Some(self.sensor_topic("theme")),
+            command_topic: self.command_topic("Theme"),
+            availability_topic: self.availability_topic(),
+            options: crate::sensors::THEME_OPTIONS
+                .iter()
+                .map(|o| (*o).to_string())
+                .collect(),
+            device: Arc::clone(&self.device),
+            icon: "mdi:theme-light-dark".to_string(),
+            json_attributes_topic: Some(self.sensor_attributes_topic("theme")),
+        };
+        let topic = self.config_topic("select", "theme");
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA select payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Register the game launch select: one option per launchable game,
+    /// choosing one sends `LaunchGame` with its display name. Stateless, so
+    /// HA keeps showing the game last launched from it.
+    async fn register_game_launch_select(&self, options: &[String]) {
+        let payload = HASelect {
+            name: tr("Launch Game"),
+            unique_id: format!("{}_launch_game", self.device_id),
+            state_topic: None,
+            command_topic: self.command_topic("LaunchGame"),
+            availability_topic: self.availability_topic(),
+            options: options.to_vec(),
+            device: Arc::clone(&self.device),
+            icon: "mdi:gamepad-variant".to_string(),
+            json_attributes_topic: None,
+        };
+        let topic = self.config_topic("select", "launch_game");
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA select payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Register the OpenRGB light. Commands go to `Rgb`; state is the JSON the
+    /// command handler publishes on the `rgb` sensor topic.
+    async fn register_rgb_light(&self) {
+        let payload = HALight {
+            name: tr("RGB Lighting"),
+            unique_id: format!("{}_rgb", self.device_id),
+            schema: "json",
+            state_topic: self.sensor_topic("rgb"),
+            command_topic: self.command_topic("Rgb"),
+            availability_topic: self.availability_topic(),
+            brightness: true,
+            supported_color_modes: vec!["rgb"],
+            device: Arc::clone(&self.device),
+            icon: "mdi:led-strip-variant".to_string(),
+        };
+        let topic = self.config_topic("light", "rgb");
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA light payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Register the game artwork image. The picture is the retained JPEG on
+    /// the `game_art` sensor topic.
+    async fn register_game_art_image(&self) {
+        let payload = HAImage {
+            name: tr("Game Art"),
+            unique_id: format!("{}_game_art", self.device_id),
+            image_topic: self.sensor_topic("game_art"),
+            content_type: "image/jpeg",
+            json_attributes_topic: self.sensor_attributes_topic("game_art"),
+            availability_topic: self.availability_topic(),
+            device: Arc::clone(&self.device),
+            icon: "mdi:image-frame".to_string(),
+        };
+        let topic = self.config_topic("image", "game_art");
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA image payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Register the perf mode switch. Commands go to `PerfMode`; state is the
+    /// `perf_mode` sensor topic.
+    async fn register_perf_mode_switch(&self) {
+        let payload = HASwitch {
+            name: tr("Perf Mode"),
+            unique_id: format!("{}_perf_mode", self.device_id),
+            state_topic: self.sensor_topic("perf_mode"),
+            command_topic: self.command_topic("PerfMode"),
+            availability_topic: self.availability_topic(),
+            device: Arc::clone(&self.device),
+            icon: "mdi:speedometer".to_string(),
+        };
+        let topic = self.config_topic("switch", "perf_mode");
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA switch payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Register the focus mode switch. Commands go to `FocusMode`; state is
+    /// the `focus_mode` sensor topic.
+    async fn register_focus_switch(&self) {
+        let payload = HASwitch {
+            name: tr("Focus Mode"),
+            unique_id: format!("{}_focus_mode", self.device_id),
+            state_topic: self.sensor_topic("focus_mode"),
+            command_topic: self.command_topic("FocusMode"),
+            availability_topic: self.availability_topic(),
+            device: Arc::clone(&self.device),
+            icon: "mdi:target-account".to_string(),
+        };
+        let topic = self.config_topic("switch", "focus_mode");
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA switch payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Register the switch that pauses reporting of `reported`. Commands go
+    /// to its `Report...` command; state is the switch's own sensor topic.
+    async fn register_reporting_switch(&self, reported: Reported) {
+        let (name, icon) = match reported {
+            Reported::ActiveWindow => ("Report Active Window", "mdi:application-cog"),
+            Reported::Games => ("Report Games", "mdi:controller"),
+        };
+        let payload = HASwitch {
+            name: tr(name),
+            unique_id: format!("{}_{}", self.device_id, reported.switch()),
+            state_topic: self.sensor_topic(reported.switch()),
+            command_topic: self.command_topic(reported.command()),
+            availability_topic: self.availability_topic(),
+            device: Arc::clone(&self.device),
+            icon: icon.to_string(),
+        };
+        let topic = self.config_topic("switch", reported.switch());
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA switch payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Register the battery sensor of one peripheral (controller, headset,
+    /// mouse), seen at runtime by the device battery sensor. `slug` comes from
+    /// the device name; `name` is shown as "<name> Battery".
+    pub(crate) async fn register_device_battery(&self, slug: &str, name: &str, config: &Config) {
+        let object_id = device_battery_id(slug);
+        self.register_sensor_internal(
+            &self.device,
+            &object_id,
+            &tr_args("{name} Battery", &[("name", name)]),
+            "mdi:battery",
+            Some("battery"),
+            Some("%"),
+            true,
+            sensor_expire_after(&object_id, config),
+        )
+        .await;
+    }
+
+    /// Register the status sensor of one configured printer; `name` is the
+    /// printer's spooler / CUPS name.
+    pub(crate) async fn register_printer(&self, slug: &str, name: &str, config: &Config) {
+        let object_id = printer_id(slug);
+        self.register_sensor_internal(
+            &self.device,
+            &object_id,
+            &tr_args("Printer {name}", &[("name", name)]),
+            "mdi:printer",
+            None,
+            None,
+            true,
+            sensor_expire_after(&object_id, config),
+        )
+        .await;
+    }
+
+    /// Register the presence binary sensor of one configured USB device;
+    /// `name` is its configured name.
+    pub(crate) async fn register_usb_device(&self, slug: &str, name: &str, config: &Config) {
+        self.register_binary_sensor(
+            &self.device,
+            config,
+            &usb_device_id(slug),
+            name,
+            "mdi:usb",
+            Some("plug"),
+            true,
+        )
+        .await;
+    }
+
+    /// Register one configured Docker container's running binary sensor and
+    /// its start / stop buttons, which send `DockerStart` / `DockerStop` with
+    /// the container's name.
+    pub(crate) async fn register_docker_container(&self, slug: &str, name: &str, config: &Config) {
+        let object_id = docker_container_id(slug);
+        self.register_binary_sensor(
+            &self.device,
+            config,
+            &object_id,
+            &tr_args("Container {name}", &[("name", name)]),
+            "mdi:docker",
+            Some("running"),
+            true,
+        )
+        .await;
+        for (suffix, template, command, icon) in [
+            ("start", "Start container {name}", "DockerStart", "mdi:play"),
+            ("stop", "Stop container {name}", "DockerStop", "mdi:stop"),
+        ] {
+            let button_id = format!("{object_id}_{suffix}");
+            let payload = HAButton {
+                name: tr_args(template, &[("name", name)]),
+                unique_id: format!("{}_{}", self.device_id, button_id),
+                command_topic: self.command_topic(command),
+                payload_press: name.to_string(),
+                availability_topic: self.availability_topic(),
+                device: Arc::clone(&self.device),
+                icon: icon.to_string(),
+            };
+            let Ok(json) = serde_json::to_string(&payload) else {
+                error!("Failed to serialize HA button payload");
+                return;
+            };
+            self.publish_discovery(&self.config_topic("button", &button_id), json)
+                .await;
+        }
+    }
+
+    /// Register one disk's SMART entities, seen at runtime by the disk health
+    /// sensor: the problem binary sensor, plus temperature and reallocated
+    /// sectors when the drive reports them. All diagnostic.
+    pub(crate) async fn register_disk_health(
+        &self,
+        slug: &str,
+        model: &str,
+        temperature: bool,
+        reallocated: bool,
+        config: &Config,
+    ) {
+        let object_id = disk_health_id(slug);
+        let payload = HABinarySensor {
+            name: tr_args("{model} Health", &[("model", model)]),
+            unique_id: format!("{}_{}", self.device_id, object_id),
+            state_topic: self.sensor_topic(&object_id),
+            availability_topic: Some(self.availability_topic()),
+            payload_on: "on",
+            payload_off: "off",
+            device_class: Some("problem".to_string()),
+            expire_after: sensor_expire_after(&object_id, config),
+            json_attributes_topic: Some(self.sensor_attributes_topic(&object_id)),
+            entity_category: Some("diagnostic"),
+            device: Arc::clone(&self.device),
+            icon: "mdi:harddisk".to_string(),
+        };
+        if let Ok(json) = serde_json::to_string(&payload) {
+            self.publish_discovery(&self.config_topic("binary_sensor", &object_id), json)
+                .await;
+        }
+
+        let sensors = [
+            (
+                temperature,
+                disk_temperature_id(slug),
+                format!("{model} Temperature"),
+                "mdi:thermometer",
+                Some("temperature"),
+                Some("°C"),
+            ),
+            (
+                reallocated,
+                disk_reallocated_id(slug),
+                format!("{model} Reallocated Sectors"),
+                "mdi:harddisk-remove",
+                None,
+                None,
+            ),
+        ];
+        for (present, object_id, name, icon, device_class, unit) in sensors {
+            if !present {
+                continue;
+            }
+            let payload = HADiscoveryPayload {
+                name,
+                unique_id: format!("{}_{}", self.device_id, object_id),
+                state_topic: Some(self.sensor_topic(&object_id)),
+                command_topic: None,
+                availability_topic: Some(self.availability_topic()),
+                availability: None,
+                availability_mode: None,
+                json_attributes_topic: None,
+                device: Arc::clone(&self.device),
+                icon: Some(icon.to_string()),
+                device_class: device_class.map(str::to_string),
+                unit_of_measurement: unit.map(str::to_string),
+                // A count that should stay 0: measurement so HA keeps history.
+                state_class: Some("measurement".to_string()),
+                expire_after: sensor_expire_after(&object_id, config),
+                entity_category: Some("diagnostic"),
+                value_template: None,
+                json_attributes_template: None,
+            };
+            if let Ok(json) = serde_json::to_string(&payload) {
+                self.publish_discovery(&self.config_topic("sensor", &object_id), json)
+                    .await;
+            }
+        }
+    }
+
+    /// Register the HA update entity for the agent itself. State comes from
+    /// the updater as JSON; Install sends `InstallUpdate`.
+    async fn register_update(&self, config: &Config) {
+        let payload = HAUpdate {
+            name: tr("PC Bridge Update"),
+            unique_id: format!("{}_update_available", self.device_id),
+            state_topic: self.sensor_topic("update_available"),
+            json_attributes_topic: self.sensor_attributes_topic("update_available"),
+            command_topic: config
+                .features
+                .cmd_install_update
+                .then(|| self.command_topic("InstallUpdate")),
+            payload_install: "PRESS",
+            availability_topic: self.availability_topic(),
+            device_class: "firmware",
+            device: Arc::clone(&self.device),
+            icon: "mdi:update".to_string(),
+        };
+        let topic = self.config_topic("update", "update_available");
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA update payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// The `group`'s shared device and its `any_game_running` sensor. Every
+    /// member publishes the same config, with no availability: the sensor
+    /// outlives any one PC.
+    async fn register_group(&self, group: &str) {
+        let group_id = crate::group::device_id(group);
+        let payload = HABinarySensor {
+            name: "Any PC Gaming".to_string(),
+            unique_id: format!("{group_id}_{}", crate::group::ANY_GAME_RUNNING),
+            state_topic: crate::group::state_topic(group),
+            availability_topic: None,
+            payload_on: "on",
+            payload_off: "off",
+            device_class: Some("running".to_string()),
+            expire_after: None,
+            json_attributes_topic: Some(crate::group::attributes_topic(group)),
+            entity_category: None,
+            device: Arc::new(HADevice {
+                identifiers: vec![group_id.clone()],
+                name: format!("PC Bridge {group}"),
+                model: "PC Bridge group".to_string(),
+                manufacturer: "dank0i".to_string(),
+                sw_version: self.device.sw_version.clone(),
+                suggested_area: None,
+                via_device: None,
+            }),
+            icon: "mdi:gamepad-variant".to_string(),
+        };
+        let topic =
+            Self::config_topic_static(&group_id, "binary_sensor", crate::group::ANY_GAME_RUNNING);
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA group sensor payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
+    /// Helper to register an on/off binary sensor
+    #[allow(clippy::too_many_arguments)]
+    async fn register_binary_sensor(
+        &self,
+        device: &Arc<HADevice>,
+        config: &Config,
+        name: &str,
+        display_name: &str,
+        icon: &str,
+        device_class: Option<&str>,
+        with_attributes: bool,
+    ) {
+        let payload = HABinarySensor {
+            name: tr(display_name),
+            unique_id: format!("{}_{}", self.device_id, name),
+            state_topic: self.sensor_topic(name),
+            availability_topic: Some(self.availability_topic()),
+            payload_on: "on",
+            payload_off: "off",
+            device_class: device_class.map(|s| s.to_string()),
+            expire_after: sensor_expire_after(name, config),
+            json_attributes_topic: with_attributes.then(|| self.sensor_attributes_topic(name)),
+            entity_category: entity_category(name),
+            device: Arc::clone(device),
+            icon: icon.to_string(),
+        };
+        let topic = self.config_topic("binary_sensor", name);
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA binary sensor payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
     /// Helper to register a sensor with JSON attributes support
     async fn register_sensor_with_attributes(
         &self,
         device: &Arc<HADevice>,
+        config: &Config,
         name: &str,
         display_name: &str,
         icon: &str,
         device_class: Option<&str>,
         unit: Option<&str>,
     ) {
-        self.register_sensor_internal(device, name, display_name, icon, device_class, unit, true)
-            .await;
+        self.register_sensor_internal(
+            device,
+            name,
+            display_name,
+            icon,
+            device_class,
+            unit,
+            true,
+            sensor_expire_after(name, config),
+        )
+        .await;
     }
 
     /// Helper to register an HWiNFO-backed sensor.
@@ -775,6 +2033,7 @@ impl MqttClient {
     async fn register_hwinfo_sensor(
         &self,
         device: &Arc<HADevice>,
+        config: &Config,
         name: &str,
         display_name: &str,
         icon: &str,
@@ -791,7 +2050,7 @@ impl MqttClient {
         ];
 
         let payload = HADiscoveryPayload {
-            name: display_name.to_string(),
+            name: tr(display_name),
             unique_id: format!("{}_{}", self.device_id, name),
             state_topic: Some(self.sensor_topic(name)),
             command_topic: None,
@@ -804,6 +2063,10 @@ impl MqttClient {
             device_class: device_class.map(|s| s.to_string()),
             unit_of_measurement: unit.map(|s| s.to_string()),
             state_class: derive_state_class(device_class, unit),
+            expire_after: sensor_expire_after(name, config),
+            entity_category: entity_category(name),
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let topic = self.config_topic("sensor", name);
@@ -825,25 +2088,36 @@ impl MqttClient {
         device_class: Option<&str>,
         unit: Option<&str>,
         with_attributes: bool,
+        expire_after: Option<u64>,
     ) {
+        let bundled = self.bundled && bundle::is_bundled(name);
         let payload = HADiscoveryPayload {
-            name: display_name.to_string(),
+            name: tr(display_name),
             unique_id: format!("{}_{}", self.device_id, name),
-            state_topic: Some(self.sensor_topic(name)),
+            state_topic: Some(if bundled {
+                Self::bundle_topic_static(&self.device_name)
+            } else {
+                self.sensor_topic(name)
+            }),
             command_topic: None,
             availability_topic: Some(self.availability_topic()),
             availability: None,
             availability_mode: None,
-            json_attributes_topic: if with_attributes {
-                Some(self.sensor_attributes_topic(name))
-            } else {
-                None
+            json_attributes_topic: match (with_attributes, bundled) {
+                (false, _) => None,
+                (true, false) => Some(self.sensor_attributes_topic(name)),
+                (true, true) => Some(Self::bundle_topic_static(&self.device_name)),
             },
             device: Arc::clone(device),
             icon: Some(icon.to_string()),
             device_class: device_class.map(|s| s.to_string()),
             unit_of_measurement: unit.map(|s| s.to_string()),
             state_class: derive_state_class(device_class, unit),
+            expire_after,
+            entity_category: entity_category(name),
+            value_template: bundled.then(|| bundle::value_template(name)),
+            json_attributes_template: (with_attributes && bundled)
+                .then(|| bundle::attributes_template(name)),
         };
 
         let topic = self.config_topic("sensor", name);
@@ -854,30 +2128,133 @@ impl MqttClient {
         self.publish_discovery(&topic, json).await;
     }
 
+    /// Register an HA device trigger for one `crate::events` event name. The
+    /// object id is the event name, so each event has one trigger per device.
+    async fn register_device_trigger(
+        &self,
+        device: &Arc<HADevice>,
+        event: &str,
+        trigger_type: &str,
+        subtype: &str,
+    ) {
+        let payload = HADeviceTrigger {
+            automation_type: "trigger",
+            topic: self.event_topic(),
+            trigger_type: trigger_type.to_string(),
+            subtype: subtype.to_string(),
+            payload: event.to_string(),
+            value_template: "{{ value_json.event }}",
+            device: Arc::clone(device),
+        };
+        self.publish_device_trigger(event, &payload).await;
+    }
+
+    /// Register the `game_started` / `game_stopped` triggers of every
+    /// configured game, matching `<event>:<game_id>`, so a per-game scene can
+    /// be attached in HA's automation editor without templating on the
+    /// sensor state. Triggers of games no longer configured (all of them with
+    /// `running_game` off) are cleared through feature_state.json.
+    async fn sync_game_triggers(&self, device: &Arc<HADevice>, config: &Config) {
+        let games = game_trigger_ids(config);
+        let mut state = FeatureState::load();
+        let removed: Vec<String> = state
+            .game_triggers
+            .iter()
+            .filter(|id| !games.contains(id))
+            .cloned()
+            .collect();
+        for topic in game_trigger_topics(&self.device_name, &removed) {
+            self.publish_discovery(&topic, Vec::<u8>::new()).await;
+        }
+        for game_id in &games {
+            for event in GAME_TRIGGER_EVENTS {
+                let payload = HADeviceTrigger {
+                    automation_type: "trigger",
+                    topic: self.event_topic(),
+                    trigger_type: event.to_string(),
+                    subtype: game_id.clone(),
+                    payload: format!("{event}:{game_id}"),
+                    value_template: GAME_TRIGGER_TEMPLATE,
+                    device: Arc::clone(device),
+                };
+                self.publish_device_trigger(&game_trigger_id(event, game_id), &payload)
+                    .await;
+            }
+        }
+        if state.game_triggers != games {
+            state.game_triggers = games;
+            if let Err(e) = state.save() {
+                warn!("Failed to update feature_state.json: {e}");
+            }
+        }
+    }
+
+    /// Register a binary sensor per `process_sensors` entry, and clear the
+    /// ones of entries since removed from the config (all of them when the
+    /// list is emptied), as recorded in feature_state.json.
+    async fn sync_process_sensors(&self, device: &Arc<HADevice>, config: &Config) {
+        let slugs = process_sensor_slugs(config);
+        let mut state = FeatureState::load();
+        let removed: Vec<String> = state
+            .process_sensors
+            .iter()
+            .filter(|slug| !slugs.contains(slug))
+            .cloned()
+            .collect();
+        for slug in &removed {
+            self.forget_value(&process_sensor_id(slug));
+        }
+        for topic in process_sensor_topics(&self.device_name, &removed) {
+            self.publish_discovery(&topic, Vec::<u8>::new()).await;
+        }
+        for (sensor, slug) in config.process_sensors.iter().zip(&slugs) {
+            self.register_binary_sensor(
+                device,
+                config,
+                &process_sensor_id(slug),
+                &sensor.name,
+                sensor.icon.as_deref().unwrap_or("mdi:application"),
+                Some("running"),
+                true,
+            )
+            .await;
+        }
+        if state.process_sensors != slugs {
+            state.process_sensors = slugs;
+            if let Err(e) = state.save() {
+                warn!("Failed to update feature_state.json: {e}");
+            }
+        }
+    }
+
+    /// Publish one device trigger's discovery config under `object_id`.
+    async fn publish_device_trigger(&self, object_id: &str, payload: &HADeviceTrigger) {
+        let topic = self.config_topic("device_automation", object_id);
+        let Ok(json) = serde_json::to_string(payload) else {
+            error!("Failed to serialize HA device trigger payload");
+            return;
+        };
+        self.publish_discovery(&topic, json).await;
+    }
+
     /// Register notify service for MQTT discovery
     async fn register_notify_service(&self, device: &Arc<HADevice>) {
         // The notify platform expects command_topic to receive messages
         let notify_topic = format!("pc-bridge/notifications/{}", self.device_name);
 
         let payload = serde_json::json!({
-            "name": "Notification",
+            "name": tr("Notification"),
             "unique_id": format!("{}_notify", self.device_id),
             "command_topic": notify_topic,
             "availability_topic": self.availability_topic(),
-            "device": {
-                "identifiers": device.identifiers,
-                "name": device.name,
-                "model": device.model,
-                "manufacturer": device.manufacturer,
-                "sw_version": device.sw_version
-            },
+            "device": device,
             "icon": "mdi:message-badge",
             "qos": 1
         });
 
         // notify uses 3-segment device-level config topic, not the 4-segment
         // per-entity shape - single notify service per device.
-        let topic = format!("{}/notify/{}/config", DISCOVERY_PREFIX, self.device_name);
+        let topic = Self::notify_config_topic_static(&self.device_name);
         let Ok(json) = serde_json::to_string(&payload) else {
             error!("Failed to serialize HA discovery payload");
             return;
@@ -888,7 +2265,7 @@ impl MqttClient {
     }
 
     /// Register custom sensors for MQTT discovery
-    pub async fn register_custom_sensors(&self, sensors: &[CustomSensor]) {
+    pub async fn register_custom_sensors(&self, sensors: &[CustomSensor], heartbeat: u64) {
         for sensor in sensors {
             let topic_name = format!("custom_{}", sensor.name);
             let display_name = format!("Custom: {}", sensor.name);
@@ -911,6 +2288,10 @@ impl MqttClient {
                 unit_of_measurement: sensor.unit.clone(),
                 state_class: derive_state_class(None, sensor.unit.as_deref()),
                 json_attributes_topic: None,
+                expire_after: expire_after(Some(sensor.interval_seconds), heartbeat),
+                entity_category: None,
+                value_template: None,
+                json_attributes_template: None,
             };
 
             let topic = self.config_topic("sensor", &topic_name);
@@ -954,6 +2335,10 @@ impl MqttClient {
                 unit_of_measurement: None,
                 state_class: None,
                 json_attributes_topic: None,
+                expire_after: None,
+                entity_category: None,
+                value_template: None,
+                json_attributes_template: None,
             };
 
             let topic = self.config_topic("button", &cmd.name);
@@ -983,6 +2368,193 @@ impl MqttClient {
         }
     }
 
+    /// Register a plugin's sensors and buttons, subscribing to the buttons'
+    /// command topics. Plugins push their states, so no expire_after.
+    pub(crate) async fn register_plugin(
+        &self,
+        plugin: &str,
+        sensors: &[PluginSensor],
+        commands: &[PluginCommand],
+    ) {
+        for sensor in sensors {
+            let topic_name = crate::plugins::entity_name(plugin, &sensor.name);
+            let payload = HADiscoveryPayload {
+                name: format!("{plugin}: {}", sensor.name),
+                unique_id: format!("{}_{}", self.device_id, topic_name),
+                state_topic: Some(self.sensor_topic(&topic_name)),
+                command_topic: None,
+                availability_topic: Some(self.availability_topic()),
+                availability: None,
+                availability_mode: None,
+                device: Arc::clone(&self.device),
+                icon: Some(
+                    sensor
+                        .icon
+                        .clone()
+                        .unwrap_or_else(|| "mdi:puzzle".to_string()),
+                ),
+                device_class: sensor.device_class.clone(),
+                unit_of_measurement: sensor.unit.clone(),
+                state_class: derive_state_class(
+                    sensor.device_class.as_deref(),
+                    sensor.unit.as_deref(),
+                ),
+                json_attributes_topic: Some(self.sensor_attributes_topic(&topic_name)),
+                expire_after: None,
+                entity_category: None,
+                value_template: None,
+                json_attributes_template: None,
+            };
+            let Ok(json) = serde_json::to_string(&payload) else {
+                error!("Failed to serialize HA discovery payload");
+                return;
+            };
+            self.publish_discovery(&self.config_topic("sensor", &topic_name), json)
+                .await;
+        }
+
+        for cmd in commands {
+            let name = crate::plugins::entity_name(plugin, &cmd.name);
+            let payload = HADiscoveryPayload {
+                name: format!("{plugin}: {}", cmd.name),
+                unique_id: format!("{}_{}", self.device_id, name),
+                state_topic: None,
+                command_topic: Some(self.command_topic(&name)),
+                availability_topic: Some(self.availability_topic()),
+                availability: None,
+                availability_mode: None,
+                device: Arc::clone(&self.device),
+                icon: Some(cmd.icon.clone().unwrap_or_else(|| "mdi:puzzle".to_string())),
+                device_class: None,
+                unit_of_measurement: None,
+                state_class: None,
+                json_attributes_topic: None,
+                expire_after: None,
+                entity_category: None,
+                value_template: None,
+                json_attributes_template: None,
+            };
+            let Ok(json) = serde_json::to_string(&payload) else {
+                error!("Failed to serialize HA discovery payload");
+                return;
+            };
+            self.publish_discovery(&self.config_topic("button", &name), json)
+                .await;
+            if let Err(e) = self
+                .client
+                .subscribe(&self.command_topic(&name), QoS::AtLeastOnce)
+                .await
+            {
+                error!("Failed to subscribe to plugin command {name}: {e:?}");
+            }
+        }
+
+        info!(
+            "Registered plugin '{plugin}': {} sensor(s), {} command(s)",
+            sensors.len(),
+            commands.len()
+        );
+    }
+
+    /// Remove a plugin's sensors and buttons from HA (empty retained configs,
+    /// like `clear_custom_entities`) and stop taking the buttons' commands.
+    pub(crate) async fn clear_plugin_entities(
+        &self,
+        plugin: &str,
+        sensors: &[String],
+        commands: &[String],
+    ) {
+        for sensor in sensors {
+            let name = crate::plugins::entity_name(plugin, sensor);
+            self.publish_discovery(&self.config_topic("sensor", &name), Vec::<u8>::new())
+                .await;
+            self.forget_value(&name);
+            for topic in [
+                self.sensor_topic(&name),
+                self.sensor_attributes_topic(&name),
+            ] {
+                let _ = self
+                    .client
+                    .publish(topic, QoS::AtLeastOnce, true, Vec::<u8>::new())
+                    .await;
+            }
+        }
+        for command in commands {
+            let name = crate::plugins::entity_name(plugin, command);
+            self.publish_discovery(&self.config_topic("button", &name), Vec::<u8>::new())
+                .await;
+            let _ = self.client.unsubscribe(self.command_topic(&name)).await;
+        }
+        if !sensors.is_empty() || !commands.is_empty() {
+            debug!(
+                "Cleared plugin '{plugin}': {} sensor(s), {} command(s)",
+                sensors.len(),
+                commands.len()
+            );
+        }
+    }
+
+    /// `pc_state`: on / sleep / off, read from `sleep_state`. Available while
+    /// the bridge is online OR `sleep_state` says it went down on purpose, so
+    /// a sleeping PC reads "sleep" instead of unavailable; only a PC that
+    /// vanished without notice (crash, power cut) is unavailable.
+    async fn register_pc_state(&self, device: &Arc<HADevice>) {
+        let sleep_state_topic = self.sensor_topic("sleep_state");
+        let payload = serde_json::json!({
+            "name": tr("PC State"),
+            "unique_id": format!("{}_pc_state", self.device_id),
+            "state_topic": sleep_state_topic,
+            "value_template": PC_STATE_TEMPLATE,
+            "availability": [
+                { "topic": self.availability_topic() },
+                { "topic": sleep_state_topic, "value_template": PC_STATE_AVAILABILITY_TEMPLATE },
+            ],
+            "availability_mode": "any",
+            "device_class": "enum",
+            "options": ["on", "sleep", "off"],
+            "device": device,
+            "icon": "mdi:desktop-tower-monitor",
+        });
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA discovery payload");
+            return;
+        };
+        self.publish_discovery(&self.config_topic("sensor", "pc_state"), json)
+            .await;
+    }
+
+    /// Register a script's `script_<name>` sensor, first published via
+    /// `set_sensor`. Scripts push their states, so no expire_after.
+    pub(crate) async fn register_script_sensor(&self, name: &str) {
+        let topic_name = format!("script_{name}");
+        let payload = HADiscoveryPayload {
+            name: tr_args("Script: {name}", &[("name", name)]),
+            unique_id: format!("{}_{}", self.device_id, topic_name),
+            state_topic: Some(self.sensor_topic(&topic_name)),
+            command_topic: None,
+            availability_topic: Some(self.availability_topic()),
+            availability: None,
+            availability_mode: None,
+            device: Arc::clone(&self.device),
+            icon: Some("mdi:script-text".to_string()),
+            device_class: None,
+            unit_of_measurement: None,
+            state_class: None,
+            json_attributes_topic: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
+        };
+        let Ok(json) = serde_json::to_string(&payload) else {
+            error!("Failed to serialize HA discovery payload");
+            return;
+        };
+        self.publish_discovery(&self.config_topic("sensor", &topic_name), json)
+            .await;
+        debug!("Registered script sensor: {name}");
+    }
+
     /// Tear down removed custom entities by publishing an empty retained config to
     /// their discovery topics (the HA remove pattern), so a custom sensor/command
     /// deleted from the config actually disappears from HA instead of lingering as
@@ -996,6 +2568,7 @@ impl MqttClient {
             let key = format!("custom_{name}");
             let topic = self.config_topic("sensor", &key);
             self.publish_discovery(&topic, Vec::<u8>::new()).await;
+            self.forget_value(&key);
             // Clear retained state + attributes so they don't outlive the entity.
             let _ = self
                 .client
@@ -1062,6 +2635,433 @@ const HWINFO_ENTITY_IDS: &[&str] = &[
     "hwinfo_diagnostic",
 ];
 
+/// Sensors registered without `expire_after` (and left out of the heartbeat):
+/// `sleep_state` and `steam_updating` deliberately have no availability so
+/// they keep reading while the PC is asleep or off, and `bridge_info` is
+/// static and republished on every connect.
+pub(super) const NON_EXPIRING_SENSORS: &[&str] = &["sleep_state", "steam_updating", "bridge_info"];
+
+/// `expire_after` for a sensor polled every `poll_secs` (None = event-driven):
+/// three heartbeats or three polls, whichever is longer, so one late beat or a
+/// slow poll never blanks a live value. None with the heartbeat off, since
+/// nothing would refresh unchanged values.
+/// `sleep_state` ("awake" / "sleeping" / "off") as `pc_state`
+const PC_STATE_TEMPLATE: &str = "{{ {'sleeping': 'sleep', 'off': 'off'}.get(value, 'on') }}";
+
+/// The second `pc_state` availability source: a power-down the bridge
+/// announced keeps the entity available.
+const PC_STATE_AVAILABILITY_TEMPLATE: &str =
+    "{{ 'online' if value in ('sleeping', 'off') else 'offline' }}";
+
+/// Built-in sensors about the bridge or the machine's plumbing rather than
+/// what the user is doing, filed under the device page's Diagnostic section.
+fn entity_category(name: &str) -> Option<&'static str> {
+    matches!(
+        name,
+        "bridge_health"
+            | "command_queue"
+            | "command_flood"
+            | "cpu_usage"
+            | "memory_usage"
+            | "battery_level"
+            | "battery_charging"
+            | "system_uptime"
+            | "last_boot"
+            | "latency"
+            | "hwinfo_diagnostic"
+    )
+    .then_some("diagnostic")
+}
+
+/// Rename / re-icon an entity's discovery payload, or empty it (removing the
+/// entity) when the override disables it. Device triggers have no name or
+/// icon and pass through untouched.
+fn apply_entity_override(payload: Vec<u8>, entry: &EntityOverride) -> Vec<u8> {
+    let Ok(serde_json::Value::Object(mut obj)) = serde_json::from_slice(&payload) else {
+        return payload;
+    };
+    if !obj.contains_key("unique_id") {
+        return payload;
+    }
+    if !entry.enabled {
+        return Vec::new();
+    }
+    if let Some(name) = &entry.name {
+        obj.insert("name".to_string(), name.clone().into());
+    }
+    if let Some(icon) = &entry.icon {
+        obj.insert("icon".to_string(), icon.clone().into());
+    }
+    serde_json::to_vec(&obj).unwrap_or(payload)
+}
+
+fn expire_after(poll_secs: Option<u64>, heartbeat: u64) -> Option<u64> {
+    (heartbeat > 0).then(|| 3 * heartbeat.max(poll_secs.unwrap_or(0)))
+}
+
+/// `expire_after` for a built-in sensor, from the interval its task polls at.
+fn sensor_expire_after(name: &str, config: &Config) -> Option<u64> {
+    if NON_EXPIRING_SENSORS.contains(&name) {
+        return None;
+    }
+    let iv = &config.intervals;
+    let poll_secs = match name {
+        "runninggames" | "game_catalog" => Some(iv.game_sensor),
+        "lastactive" | "idle_seconds" => Some(iv.last_active),
+        "cpu_usage" => Some(iv.cpu),
+        "memory_usage" => Some(iv.memory),
+        "gpu_usage" => Some(iv.gpu),
+        "network_throughput" => Some(iv.network),
+        "network_connection" | "network_metered" | "vpn_active" => Some(iv.network_connection),
+        "latency" => Some(iv.latency),
+        "public_ip" => Some(iv.public_ip.max(300)),
+        "disk_usage" => Some(iv.disk),
+        "system_uptime" | "last_boot" => Some(60),
+        "windows_updates" => Some(iv.windows_update),
+        "next_wake" => Some(iv.wake_timers.max(60)),
+        "sleep_blockers" => Some(iv.sleep_blockers.max(5)),
+        "power_plan" | "ac_connected" => Some(iv.power_plan),
+        "gamepad_connected" | "gamepad_active" => Some(iv.gamepad),
+        "vr_active" => Some(iv.vr),
+        "transcoding" => Some(iv.transcoding),
+        "media_playing" => Some(iv.media_playing),
+        "theme" => Some(iv.theme),
+        "window_history" => Some(iv.window_history.max(10)),
+        n if n.starts_with("device_battery_") => Some(iv.device_batteries),
+        n if n.starts_with("printer_") => Some(iv.printers.max(5)),
+        n if n.starts_with("usb_") => Some(iv.usb_devices.max(10)),
+        n if n.starts_with("docker_") => Some(iv.docker.max(5)),
+        n if n.starts_with("disk_health_")
+            || n.starts_with("disk_temperature_")
+            || n.starts_with("disk_reallocated_") =>
+        {
+            Some(iv.disk_health.max(60))
+        }
+        _ => None,
+    };
+    // A bundled sensor's changes only go out with the bundle.
+    let poll_secs = if config.mqtt.bundled_state && bundle::is_bundled(name) {
+        poll_secs.map(|secs| secs.max(iv.bundled_state))
+    } else {
+        poll_secs
+    };
+    expire_after(poll_secs, iv.heartbeat)
+}
+
+/// Object id of a peripheral's battery sensor.
+pub(crate) fn device_battery_id(slug: &str) -> String {
+    format!("device_battery_{slug}")
+}
+
+/// Object ids of a disk's SMART entities.
+pub(crate) fn disk_health_id(slug: &str) -> String {
+    format!("disk_health_{slug}")
+}
+
+pub(crate) fn disk_temperature_id(slug: &str) -> String {
+    format!("disk_temperature_{slug}")
+}
+
+pub(crate) fn disk_reallocated_id(slug: &str) -> String {
+    format!("disk_reallocated_{slug}")
+}
+
+/// Object id of a printer's status sensor.
+pub(crate) fn printer_id(slug: &str) -> String {
+    format!("printer_{slug}")
+}
+
+/// Discovery config, state and attributes topics of the printer sensors
+/// recorded in `feature_state.json`.
+fn printer_topics(device_name: &str, slugs: &[String]) -> Vec<String> {
+    let mut topics = Vec::with_capacity(slugs.len() * 3);
+    for slug in slugs {
+        let object_id = printer_id(slug);
+        topics.push(MqttClient::config_topic_static(
+            device_name,
+            "sensor",
+            &object_id,
+        ));
+        topics.push(MqttClient::sensor_topic_static(device_name, &object_id));
+        topics.push(MqttClient::sensor_attributes_topic_static(
+            device_name,
+            &object_id,
+        ));
+    }
+    topics
+}
+
+/// Object id of a configured USB device's presence sensor.
+pub(crate) fn usb_device_id(slug: &str) -> String {
+    format!("usb_{slug}")
+}
+
+/// Object id of a configured process sensor, e.g. `obs_running`.
+pub(crate) fn process_sensor_id(slug: &str) -> String {
+    format!("{slug}_running")
+}
+
+/// Slugs of the configured process sensors, in config order.
+fn process_sensor_slugs(config: &Config) -> Vec<String> {
+    config
+        .process_sensors
+        .iter()
+        .map(|s| crate::sensors::slug(&s.name))
+        .collect()
+}
+
+/// Discovery config, state and attributes topics of the process sensors
+/// recorded in `feature_state.json`.
+fn process_sensor_topics(device_name: &str, slugs: &[String]) -> Vec<String> {
+    let mut topics = Vec::with_capacity(slugs.len() * 3);
+    for slug in slugs {
+        let object_id = process_sensor_id(slug);
+        topics.push(MqttClient::config_topic_static(
+            device_name,
+            "binary_sensor",
+            &object_id,
+        ));
+        topics.push(MqttClient::sensor_topic_static(device_name, &object_id));
+        topics.push(MqttClient::sensor_attributes_topic_static(
+            device_name,
+            &object_id,
+        ));
+    }
+    topics
+}
+
+/// Discovery config, state and attributes topics of the USB presence sensors
+/// recorded in `feature_state.json`.
+fn usb_device_topics(device_name: &str, slugs: &[String]) -> Vec<String> {
+    let mut topics = Vec::with_capacity(slugs.len() * 3);
+    for slug in slugs {
+        let object_id = usb_device_id(slug);
+        topics.push(MqttClient::config_topic_static(
+            device_name,
+            "binary_sensor",
+            &object_id,
+        ));
+        topics.push(MqttClient::sensor_topic_static(device_name, &object_id));
+        topics.push(MqttClient::sensor_attributes_topic_static(
+            device_name,
+            &object_id,
+        ));
+    }
+    topics
+}
+
+/// Object id of a configured Docker container's running sensor; its
+/// buttons add `_start` / `_stop`.
+pub(crate) fn docker_container_id(slug: &str) -> String {
+    format!("docker_{slug}")
+}
+
+/// Discovery config, state and attributes topics of the Docker container
+/// entities recorded in `feature_state.json`.
+fn docker_container_topics(device_name: &str, slugs: &[String]) -> Vec<String> {
+    let mut topics = Vec::with_capacity(slugs.len() * 5);
+    for slug in slugs {
+        let object_id = docker_container_id(slug);
+        topics.push(MqttClient::config_topic_static(
+            device_name,
+            "binary_sensor",
+            &object_id,
+        ));
+        topics.push(MqttClient::sensor_topic_static(device_name, &object_id));
+        topics.push(MqttClient::sensor_attributes_topic_static(
+            device_name,
+            &object_id,
+        ));
+        for suffix in ["start", "stop"] {
+            topics.push(MqttClient::config_topic_static(
+                device_name,
+                "button",
+                &format!("{object_id}_{suffix}"),
+            ));
+        }
+    }
+    topics
+}
+
+/// Discovery config, state and attributes topics of the SMART disk entities
+/// recorded in `feature_state.json`.
+fn disk_health_topics(device_name: &str, slugs: &[String]) -> Vec<String> {
+    let mut topics = Vec::with_capacity(slugs.len() * 7);
+    for slug in slugs {
+        let health = disk_health_id(slug);
+        topics.push(MqttClient::config_topic_static(
+            device_name,
+            "binary_sensor",
+            &health,
+        ));
+        topics.push(MqttClient::sensor_topic_static(device_name, &health));
+        topics.push(MqttClient::sensor_attributes_topic_static(
+            device_name,
+            &health,
+        ));
+        for id in [disk_temperature_id(slug), disk_reallocated_id(slug)] {
+            topics.push(MqttClient::config_topic_static(device_name, "sensor", &id));
+            topics.push(MqttClient::sensor_topic_static(device_name, &id));
+        }
+    }
+    topics
+}
+
+/// Discovery config, state and attributes topics of the peripheral battery
+/// sensors recorded in `feature_state.json`.
+fn device_battery_topics(device_name: &str, slugs: &[String]) -> Vec<String> {
+    let mut topics = Vec::with_capacity(slugs.len() * 3);
+    for slug in slugs {
+        let object_id = device_battery_id(slug);
+        topics.push(MqttClient::config_topic_static(
+            device_name,
+            "sensor",
+            &object_id,
+        ));
+        topics.push(MqttClient::sensor_topic_static(device_name, &object_id));
+        topics.push(MqttClient::sensor_attributes_topic_static(
+            device_name,
+            &object_id,
+        ));
+    }
+    topics
+}
+
+/// Events that get a device trigger per configured game.
+const GAME_TRIGGER_EVENTS: [&str; 2] = ["game_started", "game_stopped"];
+
+/// Renders a game event as `<event>:<game_id>`, the per-game triggers' payload.
+/// Other events have no `game_id` and render as `<event>:`, matching none.
+const GAME_TRIGGER_TEMPLATE: &str = "{{ value_json.event }}:{{ value_json.game_id }}";
+
+/// Distinct ids of the configured games, sorted; empty with `running_game`
+/// off. Several process patterns can share one game id.
+fn game_trigger_ids(config: &Config) -> Vec<String> {
+    if !config.features.running_game {
+        return Vec::new();
+    }
+    let ids: std::collections::BTreeSet<&str> =
+        config.games.values().map(|g| g.game_id()).collect();
+    ids.into_iter().map(str::to_string).collect()
+}
+
+/// Object id of a game's trigger for `event`. Game ids are free-form, so
+/// anything outside `[A-Za-z0-9_-]` becomes `_` to keep the topic valid.
+fn game_trigger_id(event: &str, game_id: &str) -> String {
+    let slug: String = game_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{event}_{slug}")
+}
+
+/// Discovery config topics of the per-game triggers recorded in
+/// `feature_state.json`.
+fn game_trigger_topics(device_name: &str, game_ids: &[String]) -> Vec<String> {
+    game_ids
+        .iter()
+        .flat_map(|id| GAME_TRIGGER_EVENTS.map(|event| game_trigger_id(event, id)))
+        .map(|oid| MqttClient::config_topic_static(device_name, "device_automation", &oid))
+        .collect()
+}
+
+/// Every retained topic pc-bridge may have published under `device_name`:
+/// discovery configs for all built-in entities (enabled or not), `bridge_info`,
+/// the notify service and the current config's custom entities, plus state,
+/// attributes and availability in both the current and the legacy layout.
+fn stale_device_topics(device_name: &str, config: &Config) -> Vec<String> {
+    let mut sensors: Vec<String> = vec!["bridge_info".to_string()];
+    let mut topics = vec![
+        MqttClient::config_topic_static(device_name, "sensor", "bridge_info"),
+        MqttClient::notify_config_topic_static(device_name),
+        MqttClient::availability_topic_static(device_name),
+        MqttClient::hwinfo_availability_topic_static(device_name),
+    ];
+    for (component, oid, _) in feature_entities(config) {
+        topics.push(MqttClient::config_topic_static(device_name, component, oid));
+        if matches!(
+            component,
+            "sensor" | "select" | "binary_sensor" | "update" | "light" | "switch" | "image"
+        ) {
+            sensors.push(oid.to_string());
+        }
+    }
+    for sensor in &config.custom_sensors {
+        let key = format!("custom_{}", sensor.name);
+        topics.push(MqttClient::config_topic_static(device_name, "sensor", &key));
+        sensors.push(key);
+    }
+    for cmd in &config.custom_commands {
+        topics.push(MqttClient::config_topic_static(
+            device_name,
+            "button",
+            &cmd.name,
+        ));
+    }
+    for name in &sensors {
+        topics.push(MqttClient::sensor_topic_static(device_name, name));
+        topics.push(MqttClient::sensor_attributes_topic_static(
+            device_name,
+            name,
+        ));
+    }
+    topics.extend(MqttClient::legacy_state_topics(device_name, &sensors));
+    topics
+}
+
+/// HA device triggers: (event name, trigger type, subtype, enabled). The event
+/// names match `crate::events::BridgeEvent`'s serialized tags; each trigger is
+/// registered only while the feature that publishes its event is on.
+fn device_triggers(config: &Config) -> Vec<(&'static str, &'static str, &'static str, bool)> {
+    let f = &config.features;
+    #[allow(unused_mut)]
+    let mut triggers = vec![
+        (
+            "game_started",
+            "game_started",
+            "running_game",
+            f.running_game,
+        ),
+        (
+            "game_stopped",
+            "game_stopped",
+            "running_game",
+            f.running_game,
+        ),
+        (
+            "resumed_from_sleep",
+            "resumed_from_sleep",
+            "power",
+            f.sleep_wake,
+        ),
+        ("user_idle", "user_idle", "idle", f.idle_tracking),
+        ("user_returned", "user_returned", "idle", f.idle_tracking),
+        ("usb_connected", "usb_connected", "usb", f.usb_devices),
+        ("usb_disconnected", "usb_disconnected", "usb", f.usb_devices),
+        (
+            "game_limit_reached",
+            "game_limit_reached",
+            "game_limits",
+            config.game_limits.enabled,
+        ),
+    ];
+    // The tray (and its "Send Button Press" item) only exists on Windows.
+    #[cfg(windows)]
+    triggers.push((
+        "button_pressed",
+        "button_short_press",
+        "tray_button",
+        config.show_tray_icon,
+    ));
+    triggers
+}
+
 /// Every built-in HA entity the agent can register, paired with whether the
 /// current config enables it. The teardown pass clears the disabled ones.
 ///
@@ -1083,28 +3083,83 @@ fn feature_entities(config: &Config) -> Vec<(&'static str, &'static str, bool)>
         ("sensor", "idle_seconds", f.idle_tracking),
         ("sensor", "screensaver", f.idle_tracking),
         ("sensor", "sleep_state", f.sleep_wake),
+        ("sensor", "pc_state", f.sleep_wake),
         ("sensor", "display", f.display_state),
         ("sensor", "cpu_usage", f.cpu_sensor),
         ("sensor", "memory_usage", f.memory_sensor),
         ("sensor", "active_window", f.active_window),
+        (
+            "sensor",
+            "window_history",
+            f.active_window && f.window_history,
+        ),
         ("sensor", "battery_level", system_any),
         ("sensor", "battery_charging", system_any),
         ("sensor", "bridge_health", system_any),
+        ("sensor", "command_queue", true),
+        ("binary_sensor", "command_flood", true),
         ("sensor", "steam_updating", f.steam_updates),
         ("sensor", "gpu_usage", f.gpu_sensor),
         ("sensor", "network_throughput", f.network_sensor),
+        ("sensor", "network_connection", f.network_connection),
+        ("sensor", "latency", f.latency),
+        ("sensor", "public_ip", f.public_ip),
+        ("sensor", "docker_containers", f.docker),
+        ("sensor", "speedtest_download", f.cmd_speedtest),
+        ("sensor", "speedtest_upload", f.cmd_speedtest),
+        ("sensor", "speedtest_ping", f.cmd_speedtest),
+        ("sensor", "disk_cleanup_freed", f.cmd_disk_cleanup),
         ("sensor", "disk_usage", f.disk_sensor),
         ("sensor", "system_uptime", f.uptime_sensor),
+        ("sensor", "last_boot", f.uptime_sensor),
+        ("sensor", "next_wake", f.wake_timers),
+        ("sensor", "sleep_blockers", f.sleep_blockers),
         ("sensor", "volume_level", f.volume),
         // Cross-platform sensors with per-OS producers.
         ("sensor", "session", f.session_state),
+        ("binary_sensor", "remote_desktop", f.remote_desktop),
         ("sensor", "audio_device", f.audio_device),
         ("sensor", "mic", f.mic),
         ("sensor", "webcam", f.webcam),
         ("sensor", "now_playing", f.now_playing),
+        ("sensor", "notifications_suppressed", f.notifications),
+        ("sensor", "last_notification", f.notifications),
+        ("select", "power_plan", f.power_plan),
+        (
+            "select",
+            "display_mode",
+            f.display_mode && !config.display_modes.is_empty(),
+        ),
+        ("select", "theme", f.theme),
+        ("image", "game_art", f.game_art),
+        (
+            "select",
+            "launch_game",
+            f.launch_game && !crate::commands::launchable_games(config).is_empty(),
+        ),
+        ("binary_sensor", "ac_connected", f.power_plan),
+        ("binary_sensor", "gamepad_connected", f.gamepad),
+        ("binary_sensor", "gamepad_active", f.gamepad),
+        ("binary_sensor", "vr_active", f.vr),
+        ("binary_sensor", "transcoding", f.transcoding),
+        ("binary_sensor", "media_playing", f.media_playing),
+        ("binary_sensor", "network_metered", f.network_connection),
+        ("binary_sensor", "vpn_active", f.network_connection),
+        ("light", "rgb", f.openrgb),
+        ("switch", "perf_mode", f.perf_mode),
+        ("switch", "focus_mode", f.focus_mode),
+        ("sensor", "focus_remaining", f.focus_mode),
+        ("switch", "report_active_window", f.active_window),
+        ("switch", "report_games", f.running_game),
+        (
+            "update",
+            "update_available",
+            config.update_channel != "disabled",
+        ),
         // Buttons
         ("button", "Launch", f.launch_game),
         ("button", "CloseGame", f.close_game),
+        ("button", "StopGame", f.close_game),
         ("button", "RefreshSteamGames", f.steam_library),
         ("button", "Screensaver", f.idle_tracking),
         ("button", "Wake", f.idle_tracking),
@@ -1116,6 +3171,11 @@ fn feature_entities(config: &Config) -> Vec<(&'static str, &'static str, bool)>
         ("button", "Logoff", f.cmd_logoff),
         ("button", "MonitorOff", f.cmd_monitor),
         ("button", "MonitorOn", f.cmd_monitor),
+        (
+            "button",
+            "WindowsUpdateInstall",
+            f.cmd_windows_update_install,
+        ),
         ("button", "DiscordJoin", f.discord),
         ("button", "DiscordLeaveChannel", f.discord),
         ("button", "MediaPlayPause", f.media_controls),
@@ -1123,19 +3183,35 @@ fn feature_entities(config: &Config) -> Vec<(&'static str, &'static str, bool)>
         ("button", "MediaPrevious", f.media_controls),
         ("button", "MediaStop", f.media_controls),
         ("button", "VolumeMute", f.media_controls),
+        ("button", "ClearNotificationHistory", f.notifications),
+        ("button", "Speedtest", f.cmd_speedtest),
+        ("button", "DiskCleanup", f.cmd_disk_cleanup),
+        ("button", "BackupConfig", f.cmd_config_backup),
+        ("button", "Print", f.printers),
+        ("button", "CleanupEntities", true),
+        ("button", "ConnectionDiagnostics", true),
     ];
     // HWiNFO sensors have a Windows-only producer, so they only exist here.
     #[cfg(windows)]
     for oid in HWINFO_ENTITY_IDS {
         entities.push(("sensor", oid, f.hwinfo_sensor));
     }
+    #[cfg(windows)]
+    entities.push(("sensor", "windows_updates", f.windows_update));
+    for (event, _, _, enabled) in device_triggers(config) {
+        entities.push(("device_automation", event, enabled));
+    }
     entities
 }
 
 #[cfg(test)]
 mod tests {
-    use super::feature_entities;
-    use crate::config::Config;
+    use super::{
+        apply_entity_override, disk_temperature_id, entity_category, feature_entities,
+        game_trigger_id, game_trigger_ids, game_trigger_topics, sensor_expire_after,
+        stale_device_topics,
+    };
+    use crate::config::{Config, EntityOverride};
 
     fn enabled_of(config: &Config, component: &str, oid: &str) -> Option<bool> {
         feature_entities(config)
@@ -1186,6 +3262,7 @@ mod tests {
         assert_eq!(enabled_of(&config, "button", "Shutdown"), Some(true));
         assert_eq!(enabled_of(&config, "button", "Logoff"), Some(true));
         assert_eq!(enabled_of(&config, "sensor", "sleep_state"), Some(true));
+        assert_eq!(enabled_of(&config, "sensor", "pc_state"), Some(true));
         assert_eq!(enabled_of(&config, "sensor", "display"), Some(true));
         // Opt-in sensors default off, so teardown clears them.
         assert_eq!(enabled_of(&config, "sensor", "gpu_usage"), Some(false));
@@ -1218,4 +3295,181 @@ mod tests {
         assert_eq!(enabled_of(&config, "sensor", "battery_level"), Some(true));
         assert_eq!(enabled_of(&config, "sensor", "bridge_health"), Some(true));
     }
+
+    #[test]
+    fn expire_after_covers_poll_interval_and_heartbeat() {
+        let mut config = Config::default();
+        config.intervals.heartbeat = 60;
+        config.intervals.disk = 300;
+        assert_eq!(sensor_expire_after("cpu_usage", &config), Some(180));
+        // A poll slower than the heartbeat stretches the window.
+        assert_eq!(sensor_expire_after("disk_usage", &config), Some(900));
+        assert_eq!(
+            sensor_expire_after("device_battery_dualsense", &config),
+            Some(900)
+        );
+        assert_eq!(
+            sensor_expire_after(&disk_temperature_id("wd_black_sn850x_0123"), &config),
+            Some(1800)
+        );
+        // Event-driven sensors are kept fresh by the heartbeat alone.
+        assert_eq!(sensor_expire_after("display", &config), Some(180));
+        // Must keep reading "sleeping" while the agent is suspended.
+        assert_eq!(sensor_expire_after("sleep_state", &config), None);
+
+        // Bundled, a change can wait for the bundle.
+        config.mqtt.bundled_state = true;
+        config.intervals.bundled_state = 120;
+        assert_eq!(sensor_expire_after("cpu_usage", &config), Some(360));
+        assert_eq!(sensor_expire_after("display", &config), Some(180));
+
+        config.intervals.heartbeat = 0;
+        assert_eq!(sensor_expire_after("cpu_usage", &config), None);
+    }
+
+    #[test]
+    fn stale_device_topics_cover_configs_state_and_legacy() {
+        let mut config = Config::default();
+        config
+            .custom_sensors
+            .push(serde_json::from_str(r#"{"name":"disk","type":"powershell"}"#).unwrap());
+        let topics = stale_device_topics("old-pc", &config);
+        for expected in [
+            "homeassistant/sensor/old-pc/bridge_info/config",
+            "homeassistant/button/old-pc/Shutdown/config",
+            // Disabled features are cleared too - the old name may have had them on.
+            "homeassistant/sensor/old-pc/gpu_usage/config",
+            "homeassistant/sensor/old-pc/custom_disk/config",
+            "pc-bridge/old-pc/availability",
+            "pc-bridge/old-pc/sleep_state/state",
+            "pc-bridge/old-pc/bridge_info/attributes",
+            "homeassistant/sensor/old-pc/availability",
+            "homeassistant/sensor/old-pc/sleep_state/state",
+        ] {
+            assert!(topics.iter().any(|t| t == expected), "missing {expected}");
+        }
+        assert!(topics.iter().all(|t| t.contains("/old-pc/")));
+    }
+
+    #[test]
+    fn device_triggers_follow_their_features() {
+        let mut config = Config::default();
+        config.features.running_game = true;
+        config.features.idle_tracking = false;
+        let entity = |oid| enabled_of(&config, "device_automation", oid);
+        assert_eq!(entity("game_started"), Some(true));
+        assert_eq!(entity("game_stopped"), Some(true));
+        assert_eq!(entity("user_idle"), Some(false));
+        assert_eq!(entity("user_returned"), Some(false));
+        assert!(entity("resumed_from_sleep").is_some());
+    }
+
+    #[test]
+    fn device_trigger_payload_matches_event_tag() {
+        use super::super::payload::{HADevice, HADeviceTrigger};
+        use std::sync::Arc;
+
+        let device = Arc::new(HADevice {
+            identifiers: vec!["pc".into()],
+            name: "pc".into(),
+            model: "PC Bridge".into(),
+            manufacturer: "pc-bridge".into(),
+            sw_version: "1.0".into(),
+            suggested_area: None,
+            via_device: None,
+        });
+        let trigger = HADeviceTrigger {
+            automation_type: "trigger",
+            topic: "pc-bridge/events/pc".into(),
+            trigger_type: "game_started".into(),
+            subtype: "running_game".into(),
+            payload: "game_started".into(),
+            value_template: "{{ value_json.event }}",
+            device,
+        };
+        let json = serde_json::to_value(&trigger).unwrap();
+        assert_eq!(json["automation_type"], "trigger");
+        assert_eq!(json["type"], "game_started");
+        assert_eq!(json["subtype"], "running_game");
+        // The payload must equal what `value_template` renders from a real event.
+        let event = crate::events::BridgeEvent::GameStarted {
+            game_id: "cs2".into(),
+            name: "CS2".into(),
+        };
+        assert_eq!(json["payload"], event.to_payload()["event"]);
+    }
+
+    #[test]
+    fn game_triggers_cover_each_configured_game_once() {
+        use crate::config::GameConfig;
+
+        let mut config = Config::default();
+        config
+            .games
+            .insert("cs2".into(), GameConfig::Simple("cs2".into()));
+        config
+            .games
+            .insert("cs2_launcher".into(), GameConfig::Simple("cs2".into()));
+        config.games.insert(
+            "witcher3".into(),
+            GameConfig::Simple("the witcher/3".into()),
+        );
+        config.features.running_game = true;
+        assert_eq!(game_trigger_ids(&config), ["cs2", "the witcher/3"]);
+        assert_eq!(
+            game_trigger_id("game_started", "the witcher/3"),
+            "game_started_the_witcher_3"
+        );
+        assert_eq!(
+            game_trigger_topics("pc", &["cs2".to_string()]),
+            [
+                "homeassistant/device_automation/pc/game_started_cs2/config",
+                "homeassistant/device_automation/pc/game_stopped_cs2/config",
+            ]
+        );
+
+        config.features.running_game = false;
+        assert!(game_trigger_ids(&config).is_empty());
+    }
+
+    #[test]
+    fn internal_sensors_are_diagnostic() {
+        for name in [
+            "bridge_health",
+            "cpu_usage",
+            "battery_level",
+            "command_queue",
+            "command_flood",
+        ] {
+            assert_eq!(entity_category(name), Some("diagnostic"), "{name}");
+        }
+        for name in ["runninggames", "idle_seconds", "now_playing", "custom_foo"] {
+            assert_eq!(entity_category(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn entity_override_renames_reicons_and_removes() {
+        let payload = br#"{"name":"CPU Usage","unique_id":"pc_cpu_usage","icon":"mdi:cpu-64-bit"}"#;
+        let rename = EntityOverride {
+            name: Some("Processor".into()),
+            icon: Some("mdi:chip".into()),
+            enabled: true,
+        };
+        let json: serde_json::Value =
+            serde_json::from_slice(&apply_entity_override(payload.to_vec(), &rename)).unwrap();
+        assert_eq!(json["name"], "Processor");
+        assert_eq!(json["icon"], "mdi:chip");
+        assert_eq!(json["unique_id"], "pc_cpu_usage");
+
+        let off = EntityOverride {
+            name: None,
+            icon: None,
+            enabled: false,
+        };
+        assert!(apply_entity_override(payload.to_vec(), &off).is_empty());
+        // Device triggers aren't entities.
+        let trigger = br#"{"automation_type":"trigger","type":"game_started"}"#;
+        assert_eq!(apply_entity_override(trigger.to_vec(), &off), trigger);
+    }
 }
@@ -0,0 +1,93 @@
+//! Broker round trip, measured by the heartbeat.
+//!
+//! Each beat also publishes a sequence number to the agent's own
+//! `pc-bridge/<device>/heartbeat` topic, which it subscribes to; the time
+//! until the broker delivers it back is the round trip, shown on
+//! `bridge_health` as `broker_rtt_ms`. A beat that never comes back by the
+//! next one clears it, so a broker that accepts publishes but stopped
+//! routing them shows up too. The topic doubles as a deadman signal: one
+//! message per heartbeat interval for as long as the agent is alive.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct Inner {
+    seq: u64,
+    /// Sequence number and send time of the beat in flight
+    pending: Option<(u64, Instant)>,
+    /// Round trip of the last beat to come back
+    rtt: Option<Duration>,
+}
+
+#[derive(Default)]
+pub(super) struct HeartbeatProbe {
+    inner: Mutex<Inner>,
+}
+
+impl HeartbeatProbe {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A beat is going out: its payload. An earlier beat still in flight
+    /// was lost.
+    pub(super) fn start(&self) -> String {
+        let mut inner = self.lock();
+        if inner.pending.is_some() {
+            inner.rtt = None;
+        }
+        inner.seq += 1;
+        inner.pending = Some((inner.seq, Instant::now()));
+        inner.seq.to_string()
+    }
+
+    /// A message arrived on the heartbeat topic. Anything but the beat in
+    /// flight (a late one, another publisher) is ignored.
+    pub(super) fn echo(&self, payload: &[u8]) {
+        let mut inner = self.lock();
+        let seq = std::str::from_utf8(payload)
+            .ok()
+            .and_then(|s| s.trim().parse::<u64>().ok());
+        if let Some((pending, sent)) = inner.pending
+            && seq == Some(pending)
+        {
+            inner.rtt = Some(sent.elapsed());
+            inner.pending = None;
+        }
+    }
+
+    pub(super) fn rtt(&self) -> Option<Duration> {
+        self.lock().rtt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let probe = HeartbeatProbe::default();
+        assert_eq!(probe.rtt(), None);
+
+        let first = probe.start();
+        // Not this beat: ignored.
+        probe.echo(b"41");
+        probe.echo(b"garbage");
+        assert_eq!(probe.rtt(), None);
+        probe.echo(first.as_bytes());
+        let rtt = probe.rtt().expect("echoed beat has a round trip");
+        // A duplicate delivery doesn't remeasure.
+        probe.echo(first.as_bytes());
+        assert_eq!(probe.rtt(), Some(rtt));
+
+        // A beat that never comes back clears it at the next one.
+        probe.start();
+        assert_eq!(probe.rtt(), Some(rtt));
+        let third = probe.start();
+        assert_eq!(probe.rtt(), None);
+        probe.echo(third.as_bytes());
+        assert!(probe.rtt().is_some());
+    }
+}
@@ -1,19 +1,38 @@
 //! MQTT client for Home Assistant communication
 
+use bytes::{BufMut, Bytes, BytesMut};
 use log::{debug, error, info, warn};
-use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
-use std::sync::Arc;
+use rumqttc::QoS;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Duration;
-use tokio::sync::{broadcast, mpsc};
+use tokio::sync::{broadcast, mpsc, watch};
 
-use crate::config::Config;
+use crate::config::{Config, EntityOverride};
 #[cfg(test)]
 use crate::config::{CustomCommand, CustomSensor};
-#[cfg(test)]
-use std::collections::HashMap;
 
 pub(super) const DISCOVERY_PREFIX: &str = "homeassistant";
+/// Home Assistant's birth/last-will topic: "online" when HA (re)starts.
+const HA_STATUS_TOPIC: &str = "homeassistant/status";
+/// Root of entity state/attributes/availability topics (outside the discovery
+/// prefix, per HA's recommendation).
+pub(super) const STATE_PREFIX: &str = "pc-bridge";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Retained states that would otherwise outlive the agent, and what HA
+/// should show once it's gone: no game running, nothing updating or playing,
+/// camera and mic off.
+const FINAL_STATES: &[(&str, &str)] = &[
+    ("runninggames", "none"),
+    ("steam_updating", "off"),
+    ("now_playing", "idle"),
+    ("webcam", "off"),
+    ("mic", "off"),
+];
+/// How long `close` and `announce_power_down` wait for the broker to ack
+/// their publishes
+const FINAL_ACK_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// Command received from Home Assistant
 #[derive(Debug, Clone)]
@@ -24,7 +43,7 @@ pub struct Command {
 
 /// MQTT client wrapper
 pub struct MqttClient {
-    client: AsyncClient,
+    client: Client,
     device_name: String,
     device_id: String,
     /// Fix #3: Cached topic strings to avoid repeated format!() calls
@@ -34,17 +53,199 @@ pub struct MqttClient {
     /// Broadcast channel notifying subscribers when MQTT reconnects (ConnAck).
     /// Sensors listen on this to republish retained state after broker/network recovery.
     reconnect_tx: broadcast::Sender<()>,
+    /// Every event passed to `publish_event`, for in-process consumers (local
+    /// automations), whether or not the broker is reachable.
+    events_tx: broadcast::Sender<crate::events::BridgeEvent>,
+    /// Member records and availability seen on the `group` subscriptions,
+    /// for `crate::group`
+    group_tx: broadcast::Sender<crate::group::GroupMessage>,
+    /// Last state (and retain flag) and attributes published per sensor:
+    /// resent by `publish_heartbeat` so `expire_after` doesn't lapse on
+    /// values that simply haven't changed, replayed by the announcement after
+    /// ConnAck and HA birth, and served by the local HTTP API's `/status`.
+    store: Arc<StateStore>,
+    /// Latest sensor state held while the broker is unreachable; flushed by
+    /// the event loop after ConnAck.
+    offline: Arc<OfflineBuffer>,
+    /// Home Assistant API transport (`ha_api`), when enabled: mirrors sensor
+    /// state and events so they reach HA with or without a broker.
+    ha_api: OnceLock<mpsc::Sender<crate::ha_api::Update>>,
+    /// Sender side of the command channel, shared with the HA API listener so
+    /// its commands go through the same executor as MQTT ones.
+    command_tx: mpsc::Sender<Command>,
+    /// Broker round trip of the heartbeat, shared with the event loop that
+    /// sees each beat come back.
+    heartbeat: Arc<HeartbeatProbe>,
+    /// QoS 1 publishes the broker has acked, so far; `close` waits on it
+    acks: watch::Receiver<u64>,
+    /// Set once the event loop has exited
+    closed: watch::Receiver<bool>,
+    /// `entity_overrides` from the config last passed to `register_discovery`,
+    /// applied by every discovery publish
+    entity_overrides: Mutex<HashMap<String, EntityOverride>>,
+    /// State and attribute payloads are copied (or serialized) into this and
+    /// split off as `Bytes`, so a publish carves a slice out of a chunk that's
+    /// reclaimed once the event loop has sent it, instead of allocating.
+    scratch: Mutex<BytesMut>,
+    /// `mqtt.bundled_state`: the system sensors publish through the bundle
+    /// (see `bundle`) instead of their own state topics
+    bundled: bool,
+    /// A bundled sensor changed since the bundle was last published
+    bundle_dirty: AtomicBool,
 }
 
+mod broker_url;
+mod bundle;
+mod client;
+mod diagnostics;
 mod discovery;
+mod heartbeat;
+mod offline;
 mod payload;
+mod state_store;
 mod topics;
 
+pub(crate) use broker_url::BrokerUrl;
+use client::{Client, ConnectOptions, Incoming};
+pub(crate) use discovery::{
+    device_battery_id, disk_health_id, disk_reallocated_id, disk_temperature_id,
+    docker_container_id, printer_id, process_sensor_id, usb_device_id,
+};
+use heartbeat::HeartbeatProbe;
+use offline::OfflineBuffer;
+
 use payload::HADevice;
 #[cfg(test)]
 use payload::{HADiscoveryPayload, derive_state_class};
+use state_store::StateStore;
 use topics::CachedTopics;
 
+/// Everything the event loop republishes to announce the bridge: retained
+/// availability + `bridge_info`, then the reconnect signal that makes sensors
+/// (and discovery, see main.rs) republish. Sent after every ConnAck and when
+/// Home Assistant itself comes back online.
+struct Announcement {
+    client: Client,
+    availability_topic: String,
+    info_topic: String,
+    info_body: String,
+    info_attrs_topic: String,
+    info_attrs_body: String,
+    /// Replayed in full, with `device_name` for the topics
+    store: Arc<StateStore>,
+    device_name: String,
+    /// Replay the bundled sensors as the bundle (`mqtt.bundled_state`)
+    bundled: bool,
+    reconnect_tx: broadcast::Sender<()>,
+}
+
+impl Announcement {
+    async fn publish(&self) {
+        if let Err(e) = self
+            .client
+            .publish_bytes(
+                &self.availability_topic,
+                QoS::AtLeastOnce,
+                true,
+                bytes::Bytes::from_static(b"online"),
+            )
+            .await
+        {
+            warn!("Failed to publish online availability: {:?}", e);
+        }
+
+        // Birth message: state carries only the version (255-char cap); the
+        // rest goes to the attributes topic.
+        if let Err(e) = self
+            .client
+            .publish(
+                &self.info_topic,
+                QoS::AtLeastOnce,
+                true,
+                self.info_body.as_bytes(),
+            )
+            .await
+        {
+            warn!("Failed to publish bridge_info birth message: {:?}", e);
+        }
+        if let Err(e) = self
+            .client
+            .publish(
+                &self.info_attrs_topic,
+                QoS::AtLeastOnce,
+                true,
+                self.info_attrs_body.as_bytes(),
+            )
+            .await
+        {
+            warn!("Failed to publish bridge_info birth attributes: {:?}", e);
+        }
+
+        // Every sensor's last state and attributes, whether or not it
+        // listens for the reconnect below.
+        let states = self.store.states();
+        let attributes = self.store.attributes();
+        debug!(
+            "Replaying {} sensor state(s) and {} attribute set(s)",
+            states.len(),
+            attributes.len()
+        );
+        for (name, value, retained) in states {
+            if self.bundled && bundle::is_bundled(&name) {
+                continue;
+            }
+            let topic = MqttClient::sensor_topic_static(&self.device_name, &name);
+            if let Err(e) = self
+                .client
+                .publish(topic, QoS::AtLeastOnce, retained, value)
+                .await
+            {
+                warn!("Failed to replay {} state: {:?}", name, e);
+            }
+        }
+        for (name, payload) in attributes {
+            if self.bundled && bundle::is_bundled(&name) {
+                continue;
+            }
+            let topic = MqttClient::sensor_attributes_topic_static(&self.device_name, &name);
+            if let Err(e) = self
+                .client
+                .publish(topic, QoS::AtLeastOnce, true, payload)
+                .await
+            {
+                warn!("Failed to replay {} attributes: {:?}", name, e);
+            }
+        }
+        if self.bundled
+            && let Some(document) = bundle::document(self.store.values(), self.store.attributes())
+        {
+            let topic = MqttClient::bundle_topic_static(&self.device_name);
+            if let Err(e) = self
+                .client
+                .publish(topic, QoS::AtLeastOnce, false, document.to_string())
+                .await
+            {
+                warn!("Failed to replay the state bundle: {:?}", e);
+            }
+        }
+
+        // Notify sensors to republish their retained state.
+        if let Err(e) = self.reconnect_tx.send(()) {
+            debug!(
+                "No reconnect subscribers yet ({}); first connect is fine, later is suspicious",
+                e
+            );
+        }
+    }
+}
+
+/// True for Home Assistant's live birth message. A *retained* "online" is
+/// replayed by the broker on every subscribe, i.e. right after our own ConnAck
+/// announcement, so acting on it would just republish everything twice.
+fn is_ha_birth(topic: &str, payload: &[u8], retain: bool) -> bool {
+    topic == HA_STATUS_TOPIC && payload == b"online" && !retain
+}
+
 /// Receiver for commands from MQTT
 pub struct CommandReceiver {
     rx: mpsc::Receiver<Command>,
@@ -69,52 +270,54 @@ fn parse_incoming_topic<'a>(
     None
 }
 
+/// Topics the Wake-on-LAN relay listens on, paired with the target they wake:
+/// the relay topic and the target's own `Wake` button.
+fn wol_relay_topics(config: &Config) -> Vec<(String, String)> {
+    config
+        .wol_relay
+        .iter()
+        .flat_map(|t| {
+            [
+                crate::wol::relay_topic(&t.device_name),
+                format!("{}/button/{}/Wake/action", DISCOVERY_PREFIX, t.device_name),
+            ]
+            .map(|topic| (topic, t.device_name.clone()))
+        })
+        .collect()
+}
+
 impl MqttClient {
     pub async fn new(
         config: &Config,
         mut shutdown_rx: broadcast::Receiver<()>,
     ) -> anyhow::Result<(Self, CommandReceiver)> {
-        // Parse broker URL
-        let broker = &config.mqtt.broker;
-        let (host, port, use_tls) = Self::parse_broker_url(broker)?;
-
-        let mut opts = MqttOptions::new(config.client_id(), host.clone(), port);
-
-        // Authentication
-        if !config.mqtt.user.is_empty() {
-            opts.set_credentials(&config.mqtt.user, &config.mqtt.pass);
+        let broker = if config.mqtt.broker.is_empty() {
+            // Validated: only allowed with the Home Assistant API transport.
+            info!("No MQTT broker configured - using the Home Assistant API only");
+            BrokerUrl::default()
+        } else {
+            BrokerUrl::parse(&config.mqtt.broker)?
+        };
+        if broker.tls {
+            info!("MQTT TLS enabled for {}", broker.address());
         }
-
-        // TLS transport (ssl:// or wss:// scheme)
-        if use_tls {
-            let tls_config = rumqttc::TlsConfiguration::Native;
-            opts.set_transport(rumqttc::Transport::tls_with_config(tls_config));
-            info!("MQTT TLS enabled for {}:{}", host, port);
+        if config.mqtt.is_v5() {
+            info!("Using MQTT 5");
         }
 
-        // Connection settings
-        opts.set_keep_alive(Duration::from_secs(30));
-        opts.set_clean_session(false); // Preserve subscriptions
-
-        // Cap packet size to bound memory, but generously: an incoming payload
-        // over the cap makes the event loop error and the whole connection cycle
-        // (dropping the command). 256 KB comfortably covers notification bodies
-        // (which can carry a longer message / data URI) while still bounding memory.
-        opts.set_max_packet_size(256 * 1024, 256 * 1024);
-
-        // Limit in-flight QoS 1 messages - local broker doesn't need aggressive pipelining
-        opts.set_inflight(5);
-
-        // Reconnection is handled by rumqttc automatically - just keep polling
-
-        // Last Will and Testament (LWT)
+        // Connection settings, credentials, TLS and the LWT are protocol-specific
+        // and live in client.rs. Reconnection is handled by rumqttc automatically
+        // - just keep polling.
         let availability_topic = Self::availability_topic_static(&config.device_name);
-        opts.set_last_will(rumqttc::LastWill::new(
-            &availability_topic,
-            "offline".as_bytes().to_vec(),
-            QoS::AtLeastOnce,
-            true,
-        ));
+        let connect_opts = ConnectOptions {
+            client_id: config.client_id(),
+            // rumqttc joins host and port with ':', so IPv6 needs its brackets.
+            host: broker.connect_host(),
+            port: broker.port,
+            use_tls: broker.tls,
+            mqtt: &config.mqtt,
+            will_topic: &availability_topic,
+        };
 
         // Buffer must hold ALL messages queued before the event loop starts draining.
         // MQTT spec forbids sending packets before CONNACK, so nothing drains until
@@ -122,16 +325,19 @@ impl MqttClient {
         // subscribe_commands() + clear_disabled_entities(). The teardown dominates:
         // it emits 3 publishes per disabled sensor (config + state + attributes), and
         // on Windows the disabled set includes the ~21 HWiNFO sensors, so an all-off
-        // Windows config reaches ~155-160 requests. If the broker isn't up at startup
-        // the event loop can't drain, so new() blocks on a full channel until it
-        // connects; too small a buffer would wedge that path uninterruptibly. 512
-        // leaves comfortable headroom over the worst case plus custom entities.
+        // Windows config reaches ~155-160 requests. The one-shot legacy state-topic
+        // migration the agent runs right after new() adds 2 per sensor (~100 on
+        // Windows) on the first start after upgrading. If the broker isn't up at
+        // startup the event loop can't drain, so new() blocks on a full channel until
+        // it connects; too small a buffer would wedge that path uninterruptibly. 512
+        // leaves headroom over the worst case plus custom entities.
         // (This path is Windows-heavy and not exercised by the non-Windows CI tests.)
-        let (client, mut eventloop) = AsyncClient::new(opts, 512);
+        let (client, mut eventloop) = client::connect(&connect_opts, 512);
 
         let device_name = config.device_name.clone();
         let device_id = config.device_id();
         let (command_tx, command_rx) = mpsc::channel(16);
+        let command_tx_for_api = command_tx.clone();
 
         // Reconnect notification channel - sensors subscribe to republish state
         let (reconnect_tx, _) = broadcast::channel(4);
@@ -140,92 +346,132 @@ impl MqttClient {
         // Build list of topics to subscribe to (for reconnection)
         let subscribe_topics = Self::build_subscribe_topics(&config.device_name, config);
 
-        // Clone client for event loop to publish availability on reconnect
+        // Clone client for event loop to resubscribe on reconnect
         let client_for_eventloop = client.clone();
-        let availability_topic_for_eventloop = availability_topic.clone();
+        let offline = Arc::new(OfflineBuffer::default());
+        if config.mqtt.broker.is_empty() {
+            // Nothing will ever ConnAck: mark connected so state isn't held.
+            offline.drain_or_connect(offline.epoch());
+        }
+        let offline_for_eventloop = Arc::clone(&offline);
+        let store = Arc::new(StateStore::default());
 
         // Pre-compute prefixes for hot path (avoid format!() per message)
         let button_prefix = format!("{}/button/{}/", DISCOVERY_PREFIX, &device_name);
         let notify_topic_match = format!("pc-bridge/notifications/{}", &device_name);
+        let wol_topics = wol_relay_topics(config);
+        let group_members = config
+            .group
+            .as_deref()
+            .map(|group| crate::group::member_topic(group, ""));
+        // Every member's record plus the availability of all of them
+        let (group_tx, _) = broadcast::channel(64);
+        let group_tx_for_eventloop = group_tx.clone();
+        let heartbeat_topic = Self::heartbeat_topic_static(&device_name);
+        let heartbeat_topic_for_connack = heartbeat_topic.clone();
+        let heartbeat = Arc::new(HeartbeatProbe::default());
+        let heartbeat_for_eventloop = Arc::clone(&heartbeat);
 
         // Pre-compute birth message for ConnAck (Feature H).
         //
         // State carries just the version string (HA caps sensor state at 255
         // chars; the full JSON blob exceeds that and the entity falls back to
         // unknown). Everything else goes to the attributes topic.
-        let birth_topic = format!(
-            "{}/sensor/{}/bridge_info/state",
-            DISCOVERY_PREFIX, &device_name
-        );
-        let birth_attrs_topic = format!(
-            "{}/sensor/{}/bridge_info/attributes",
-            DISCOVERY_PREFIX, &device_name
-        );
-        let birth_payload = VERSION.to_string();
-        let birth_attrs_payload = serde_json::json!({
+        let info_attrs_body = serde_json::json!({
             "version": VERSION,
             "os": std::env::consts::OS,
             "arch": std::env::consts::ARCH,
-            "features": {
-                "running_game": config.features.running_game,
-                "game_catalog": config.features.game_catalog,
-                "steam_library": config.features.steam_library,
-                "launch_game": config.features.launch_game,
-                "close_game": config.features.close_game,
-                "idle_tracking": config.features.idle_tracking,
-                "sleep_wake": config.features.sleep_wake,
-                "display_state": config.features.display_state,
-                "cmd_shutdown": config.features.cmd_shutdown,
-                "cmd_restart": config.features.cmd_restart,
-                "cmd_sleep": config.features.cmd_sleep,
-                "cmd_lock": config.features.cmd_lock,
-                "cmd_logoff": config.features.cmd_logoff,
-                "cmd_monitor": config.features.cmd_monitor,
-                "notifications": config.features.notifications,
-                "cpu_sensor": config.features.cpu_sensor,
-                "memory_sensor": config.features.memory_sensor,
-                "active_window": config.features.active_window,
-                "session_state": config.features.session_state,
-                "audio_device": config.features.audio_device,
-                "mic": config.features.mic,
-                "webcam": config.features.webcam,
-                "now_playing": config.features.now_playing,
-                "volume": config.features.volume,
-                "media_controls": config.features.media_controls,
-                "steam_updates": config.features.steam_updates,
-                "discord": config.features.discord,
-                "gpu_sensor": config.features.gpu_sensor,
-                "network_sensor": config.features.network_sensor,
-                "disk_sensor": config.features.disk_sensor,
-                "uptime_sensor": config.features.uptime_sensor,
-                "hwinfo_sensor": config.features.hwinfo_sensor,
-            }
+            // Every flag, by its config key
+            "features": &config.features,
         })
         .to_string();
+        let announcement = Arc::new(Announcement {
+            client: client.clone(),
+            availability_topic: availability_topic.clone(),
+            info_topic: format!("{}/{}/bridge_info/state", STATE_PREFIX, &device_name),
+            info_body: VERSION.to_string(),
+            info_attrs_topic: format!("{}/{}/bridge_info/attributes", STATE_PREFIX, &device_name),
+            info_attrs_body,
+            store: Arc::clone(&store),
+            device_name: device_name.clone(),
+            bundled: config.mqtt.bundled_state,
+            reconnect_tx: reconnect_tx_for_eventloop,
+        });
 
         // Spawn event loop handler
+        // Shutdown doesn't stop the event loop outright: it keeps polling so
+        // `close` can get its final publishes acked and its DISCONNECT out.
+        let (acks_tx, acks) = watch::channel(0u64);
+        let (closed_tx, closed) = watch::channel(false);
         tokio::spawn(async move {
             let mut backoff_secs: u64 = 1;
+            let mut closing = false;
             loop {
                 tokio::select! {
                     biased;
-                    _ = shutdown_rx.recv() => {
-                        debug!("MQTT event loop shutting down");
-                        break;
+                    _ = shutdown_rx.recv(), if !closing => {
+                        debug!("MQTT event loop draining for shutdown");
+                        closing = true;
                     }
                     poll_result = eventloop.poll() => {
                         match poll_result {
-                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    Ok(Incoming::Publish { topic, payload, retain }) => {
                         debug!(
                             "MQTT message: {} = {}",
-                            publish.topic,
-                            String::from_utf8_lossy(&publish.payload)
+                            topic,
+                            String::from_utf8_lossy(&payload)
                         );
 
+                        // HA restarted: a user who purged retained messages has
+                        // lost our availability, configs and states, so announce
+                        // again exactly like after a reconnect.
+                        if is_ha_birth(&topic, &payload, retain) {
+                            info!("Home Assistant came online - republishing discovery and state");
+                            let announcement = Arc::clone(&announcement);
+                            tokio::spawn(async move { announcement.publish().await });
+                            continue;
+                        }
+
+                        if topic == heartbeat_topic {
+                            heartbeat_for_eventloop.echo(&payload);
+                            continue;
+                        }
+
+                        if let Some(members) = &group_members
+                            && (topic.starts_with(members.as_str())
+                                || crate::group::is_availability_topic(&topic))
+                        {
+                            let _ = group_tx_for_eventloop.send(crate::group::GroupMessage {
+                                payload: String::from_utf8_lossy(&payload).into_owned(),
+                                topic,
+                            });
+                            continue;
+                        }
+
+                        // A wake request for a relay target: the payload is
+                        // the button press, the target is in the topic.
+                        // Retained ones are skipped so a stray retained press
+                        // can't wake the target on every reconnect.
+                        if let Some((_, target)) = wol_topics.iter().find(|(t, _)| *t == topic) {
+                            if retain {
+                                continue;
+                            }
+                            if command_tx
+                                .try_send(Command {
+                                    name: "WakeOnLan".to_string(),
+                                    payload: target.clone(),
+                                })
+                                .is_err()
+                            {
+                                warn!("Command channel full or closed - dropping command");
+                            }
+                            continue;
+                        }
+
                         // Extract command name using the shared parser so a
                         // change here can't drift from the test-only path.
                         let cmd_name = parse_incoming_topic(
-                            &publish.topic,
+                            &topic,
                             &button_prefix,
                             &notify_topic_match,
                         )
@@ -233,9 +479,9 @@ impl MqttClient {
 
                         if let Some(cmd_name) = cmd_name {
                             // Zero-copy when payload is valid UTF-8 (common case)
-                            let payload = match std::str::from_utf8(&publish.payload) {
+                            let payload = match std::str::from_utf8(&payload) {
                                 Ok(s) => s.to_string(),
-                                Err(_) => String::from_utf8_lossy(&publish.payload).into_owned(),
+                                Err(_) => String::from_utf8_lossy(&payload).into_owned(),
                             };
                             // try_send (not .await): blocking here would stop the
                             // poll loop from sending keepalives and the broker
@@ -251,7 +497,17 @@ impl MqttClient {
                             }
                         }
                     }
-                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    Ok(Incoming::PubAck) => {
+                        acks_tx.send_modify(|n| *n += 1);
+                    }
+                    Ok(Incoming::DisconnectSent) => {
+                        debug!("MQTT disconnected cleanly");
+                        break;
+                    }
+                    // Reconnected mid-shutdown: announcing online now would
+                    // undo the offline `close` is about to publish.
+                    Ok(Incoming::ConnAck) if closing => {}
+                    Ok(Incoming::ConnAck) => {
                         info!("MQTT connected - resubscribing then announcing online");
                         // Reset backoff on successful connection.
                         backoff_secs = 1;
@@ -267,12 +523,10 @@ impl MqttClient {
                         // before the availability publish (TCP order), which HA needs.
                         let client = client_for_eventloop.clone();
                         let topics = subscribe_topics.clone();
-                        let avail = availability_topic_for_eventloop.clone();
-                        let state_topic = birth_topic.clone();
-                        let state_body = birth_payload.clone();
-                        let attr_topic = birth_attrs_topic.clone();
-                        let attr_body = birth_attrs_payload.clone();
-                        let rtx = reconnect_tx_for_eventloop.clone();
+                        let announcement = Arc::clone(&announcement);
+                        let offline = Arc::clone(&offline_for_eventloop);
+                        let heartbeat_topic = heartbeat_topic_for_connack.clone();
+                        let epoch = offline.epoch();
                         tokio::spawn(async move {
                             // Subscribe BEFORE publishing "online": HA may fire
                             // commands the instant we appear available, and the broker
@@ -283,42 +537,43 @@ impl MqttClient {
                                 }
                             }
                             info!("Resubscribed to {} command topics", topics.len());
-
-                            if let Err(e) = client
-                                .publish_bytes(
-                                    &avail,
-                                    QoS::AtLeastOnce,
-                                    true,
-                                    bytes::Bytes::from_static(b"online"),
-                                )
-                                .await
-                            {
-                                warn!("Failed to publish online availability after ConnAck: {:?}", e);
-                            }
-
-                            // Birth message: state carries only the version (255-char
-                            // cap); the rest goes to the attributes topic.
-                            if let Err(e) = client
-                                .publish(&state_topic, QoS::AtLeastOnce, true, state_body.as_bytes())
-                                .await
-                            {
-                                warn!("Failed to publish bridge_info birth message: {:?}", e);
+                            // HA's birth topic, so an HA restart re-triggers the
+                            // announcement below (see is_ha_birth).
+                            if let Err(e) = client.subscribe(HA_STATUS_TOPIC, QoS::AtLeastOnce).await {
+                                warn!("Failed to subscribe to {}: {:?}", HA_STATUS_TOPIC, e);
                             }
-                            if let Err(e) = client
-                                .publish(&attr_topic, QoS::AtLeastOnce, true, attr_body.as_bytes())
-                                .await
-                            {
-                                warn!("Failed to publish bridge_info birth attributes: {:?}", e);
+                            // Our own heartbeats, to time the broker round trip.
+                            if let Err(e) = client.subscribe(&heartbeat_topic, QoS::AtLeastOnce).await {
+                                warn!("Failed to subscribe to {}: {:?}", heartbeat_topic, e);
                             }
 
-                            // Notify sensors to republish their retained state.
-                            if let Err(e) = rtx.send(()) {
-                                debug!("No reconnect subscribers yet ({}); first connect is fine, later is suspicious", e);
+                            announcement.publish().await;
+
+                            // Catch HA up on the newest value of everything that
+                            // changed while we were offline.
+                            loop {
+                                let batch = offline.drain_or_connect(epoch);
+                                if batch.is_empty() {
+                                    break;
+                                }
+                                debug!("Flushing {} buffered sensor update(s)", batch.len());
+                                for (topic, payload, retained) in batch {
+                                    if let Err(e) =
+                                        client.publish(&topic, QoS::AtLeastOnce, retained, payload).await
+                                    {
+                                        warn!("Failed to flush buffered {}: {:?}", topic, e);
+                                    }
+                                }
                             }
                         });
                     }
                     Ok(_) => {}
+                    Err(e) if closing => {
+                        debug!("MQTT connection lost during shutdown: {:?}", e);
+                        break;
+                    }
                     Err(e) => {
+                        offline_for_eventloop.disconnected();
                         warn!("MQTT error (retrying in {}s): {:?}", backoff_secs, e);
                         // Race the backoff against shutdown so Ctrl+C isn't stuck
                         // for up to 30s waiting on a reconnect delay.
@@ -336,6 +591,7 @@ impl MqttClient {
                     }
                 }
             }
+            closed_tx.send_replace(true);
         });
 
         // Fix #3: Pre-cache topic strings
@@ -348,6 +604,8 @@ impl MqttClient {
             model: format!("PC Bridge v{}", VERSION),
             manufacturer: "dank0i".to_string(),
             sw_version: VERSION.to_string(),
+            suggested_area: config.suggested_area.clone(),
+            via_device: config.group.as_deref().map(crate::group::device_id),
         });
 
         let mqtt = Self {
@@ -357,6 +615,19 @@ impl MqttClient {
             cached_topics,
             device,
             reconnect_tx,
+            events_tx: broadcast::channel(16).0,
+            group_tx,
+            store,
+            offline,
+            ha_api: OnceLock::new(),
+            command_tx: command_tx_for_api,
+            heartbeat,
+            acks,
+            closed,
+            entity_overrides: Mutex::new(config.entity_overrides.clone()),
+            scratch: Mutex::new(BytesMut::new()),
+            bundled: config.mqtt.bundled_state,
+            bundle_dirty: AtomicBool::new(false),
         };
 
         let cmd_rx = CommandReceiver { rx: command_rx };
@@ -371,12 +642,6 @@ impl MqttClient {
         Ok((mqtt, cmd_rx))
     }
 
-    /// Forwards to the canonical implementation in `power::sync_mqtt` so the
-    /// async client and the sync sleep publisher can't drift out of sync.
-    fn parse_broker_url(url: &str) -> anyhow::Result<(String, u16, bool)> {
-        Ok(crate::power::sync_mqtt::parse_broker_url(url))
-    }
-
     /// Test-only thin shim that builds the prefixes the event-loop already
     /// caches, then calls `parse_incoming_topic`.  Keeps tests honest: a
     /// production-routing regression now fails the unit test too.
@@ -396,7 +661,9 @@ impl MqttClient {
     /// the two can't drift apart.
     const NATIVE_COMMANDS: &[&str] = &[
         "Launch",
+        "LaunchGame",
         "CloseGame",
+        "StopGame",
         "RefreshSteamGames",
         "Screensaver",
         "Wake",
@@ -415,6 +682,29 @@ impl MqttClient {
         "MediaPrevious",
         "MediaStop",
         "VolumeMute",
+        "CleanupEntities",
+        "ConnectionDiagnostics",
+        "WindowsUpdateInstall",
+        "PowerPlan",
+        "DisplayMode",
+        "Rgb",
+        "PerfMode",
+        "FocusMode",
+        "ReportActiveWindow",
+        "ReportGames",
+        "Speedtest",
+        "DiskCleanup",
+        "Print",
+        "DockerStart",
+        "DockerStop",
+        "InstallUpdate",
+        "ClearNotificationHistory",
+        "BackupConfig",
+        "RestoreConfig",
+        "FileDownload",
+        "FileChunk",
+        "SetWallpaper",
+        "Theme",
     ];
 
     fn build_subscribe_topics(device_name: &str, config: &Config) -> Vec<String> {
@@ -442,6 +732,13 @@ impl MqttClient {
             ));
         }
 
+        topics.extend(wol_relay_topics(config).into_iter().map(|(topic, _)| topic));
+
+        if let Some(group) = &config.group {
+            topics.push(crate::group::members_filter(group));
+            topics.push(crate::group::AVAILABILITY_FILTER.to_string());
+        }
+
         topics
     }
 
@@ -464,18 +761,195 @@ impl MqttClient {
         self.reconnect_tx.subscribe()
     }
 
+    /// Subscribe to the events passed to `publish_event`.
+    pub fn subscribe_events(&self) -> broadcast::Receiver<crate::events::BridgeEvent> {
+        self.events_tx.subscribe()
+    }
+
+    /// Subscribe to what the `group` subscriptions receive.
+    pub fn subscribe_group(&self) -> broadcast::Receiver<crate::group::GroupMessage> {
+        self.group_tx.subscribe()
+    }
+
+    /// This PC's retained record in `group`; empty to leave it.
+    pub async fn publish_group_member(&self, group: &str, device_name: &str, record: Vec<u8>) {
+        let topic = crate::group::member_topic(group, device_name);
+        self.publish_state(&topic, true, self.payload(&record))
+            .await;
+    }
+
+    /// The group's `any_game_running` state and attributes (retained)
+    pub async fn publish_group_state(&self, group: &str, on: &str, attributes: &serde_json::Value) {
+        let payload = self.payload(on.as_bytes());
+        self.publish_state(&crate::group::state_topic(group), true, payload)
+            .await;
+        let Some(payload) = self.json_payload(attributes) else {
+            return;
+        };
+        self.publish_state(&crate::group::attributes_topic(group), true, payload)
+            .await;
+    }
+
     /// Publish a sensor value (non-retained)
     pub async fn publish_sensor(&self, name: &str, value: &str) {
-        self.publish_inner(self.sensor_topic(name), false, value.to_owned())
+        self.mirror(|| crate::ha_api::Update::State {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        });
+        self.remember_value(name, value, false);
+        if self.bundles(name) {
+            return;
+        }
+        let payload = self.payload(value.as_bytes());
+        self.publish_state(&self.sensor_topic_ref(name), false, payload)
             .await;
     }
 
     /// Publish a sensor value (retained)
     pub async fn publish_sensor_retained(&self, name: &str, value: &str) {
-        self.publish_inner(self.sensor_topic(name), true, value.to_owned())
+        self.mirror(|| crate::ha_api::Update::State {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        });
+        self.remember_value(name, value, true);
+        if self.bundles(name) {
+            return;
+        }
+        let payload = self.payload(value.as_bytes());
+        self.publish_state(&self.sensor_topic_ref(name), true, payload)
             .await;
     }
 
+    /// Route state and events to the HA API transport as well. Called once at
+    /// startup when `ha_api` is enabled.
+    pub(crate) fn attach_ha_api(&self, tx: mpsc::Sender<crate::ha_api::Update>) {
+        let _ = self.ha_api.set(tx);
+    }
+
+    /// Command channel the HA API listener feeds (see `attach_ha_api`).
+    pub(crate) fn command_sender(&self) -> mpsc::Sender<Command> {
+        self.command_tx.clone()
+    }
+
+    /// Forward an update to the HA API transport, if attached. try_send: the
+    /// writer coalesces by entity, so a full channel only means it's behind on
+    /// a slow HA and the entity's next update carries the newest value.
+    fn mirror(&self, update: impl FnOnce() -> crate::ha_api::Update) {
+        if let Some(tx) = self.ha_api.get() {
+            let _ = tx.try_send(update());
+        }
+    }
+
+    /// Record `value` for the heartbeat, the reconnect replay and
+    /// `sensor_values`.
+    fn remember_value(&self, name: &str, value: &str, retained: bool) {
+        self.store.remember(name, value, retained);
+    }
+
+    /// Drop `name` from the heartbeat once its entity is torn down, so the
+    /// next beat doesn't resurrect the state topic we just cleared.
+    pub(crate) fn forget_value(&self, name: &str) {
+        self.store.forget(name);
+        self.bundles(name);
+    }
+
+    /// Whether `name` goes out in the bundle rather than on its own topic;
+    /// if so, marks the bundle for the next `publish_bundle`.
+    fn bundles(&self, name: &str) -> bool {
+        let bundled = self.bundled && bundle::is_bundled(name);
+        if bundled {
+            self.bundle_dirty.store(true, Ordering::Relaxed);
+        }
+        bundled
+    }
+
+    /// Publish the bundled state document if a bundled sensor changed since
+    /// the last one, or regardless with `force` (heartbeat). A no-op unless
+    /// `mqtt.bundled_state` is on.
+    pub async fn publish_bundle(&self, force: bool) {
+        if !self.bundled || !(self.bundle_dirty.swap(false, Ordering::Relaxed) || force) {
+            return;
+        }
+        let Some(document) = bundle::document(self.store.values(), self.store.attributes()) else {
+            return;
+        };
+        let Some(payload) = self.json_payload(&document) else {
+            return;
+        };
+        let topic = Self::bundle_topic_static(&self.device_name);
+        self.publish_state(&topic, false, payload).await;
+    }
+
+    /// Heartbeat: retained availability plus the last value of every sensor,
+    /// so HA's `expire_after` only marks values unavailable once the agent
+    /// actually stops publishing (crash, hang, lost network) rather than when
+    /// a reading just hasn't changed.
+    pub async fn publish_heartbeat(&self) {
+        // Offline there's nothing to keep alive; the ConnAck flush catches up.
+        if !self.offline.is_connected() {
+            return;
+        }
+        let values = self.heartbeat_values();
+        self.publish_availability(true).await;
+        let beat = self.heartbeat.start();
+        if let Err(e) = self
+            .client
+            .publish(
+                Self::heartbeat_topic_static(&self.device_name),
+                QoS::AtLeastOnce,
+                false,
+                beat.into_bytes(),
+            )
+            .await
+        {
+            debug!("Failed to publish heartbeat: {:?}", e);
+        }
+        for (topic, value, retained) in values {
+            self.publish_inner(topic, retained, value).await;
+        }
+        self.publish_bundle(true).await;
+    }
+
+    /// Broker round trip of the last heartbeat; None before the first one
+    /// comes back or when the last one never did.
+    pub fn broker_rtt(&self) -> Option<Duration> {
+        self.heartbeat.rtt()
+    }
+
+    /// (topic, value, retained) for every sensor the heartbeat resends.
+    /// Sensors without `expire_after` are skipped - resending a stale cached
+    /// `sleep_state` right before suspend would overwrite the "sleeping" the
+    /// power handler just published. Bundled sensors go in the bundle.
+    fn heartbeat_values(&self) -> Vec<(String, String, bool)> {
+        self.store
+            .states()
+            .into_iter()
+            .filter(|(name, _, _)| !discovery::NON_EXPIRING_SENSORS.contains(&name.as_str()))
+            .filter(|(name, _, _)| !(self.bundled && bundle::is_bundled(name)))
+            .map(|(name, value, retained)| (self.sensor_topic(&name), value, retained))
+            .collect()
+    }
+
+    /// Latest value of every sensor published so far, by sensor name.
+    pub fn sensor_values(&self) -> std::collections::BTreeMap<String, String> {
+        self.store.values()
+    }
+
+    /// Latest value of one sensor, if it has been published.
+    pub fn sensor_value(&self, name: &str) -> Option<String> {
+        self.store.value(name)
+    }
+
+    /// Latest attributes of one sensor, if they have been published.
+    pub fn sensor_attributes(&self, name: &str) -> Option<serde_json::Value> {
+        serde_json::from_slice(&self.store.attributes_of(name)?).ok()
+    }
+
+    /// Whether the broker connection is up (always true without a broker).
+    pub fn is_connected(&self) -> bool {
+        self.offline.is_connected()
+    }
+
     /// Publish a dry-run command record to the test topic consumed by the
     /// integration test kit. Not retained. Topic: `pc-bridge/test/executed/<device>`.
     pub async fn publish_test_action(&self, name: &str, payload: &str, action: &str) {
@@ -487,16 +961,133 @@ impl MqttClient {
         self.publish_inner(topic, false, value).await;
     }
 
+    /// Publish a discrete event (non-retained, so a reconnecting subscriber
+    /// never replays it and re-fires an automation).
+    pub async fn publish_event(&self, event: &crate::events::BridgeEvent) {
+        debug!("Event: {:?}", event);
+        let _ = self.events_tx.send(event.clone());
+        let body = event.to_payload();
+        self.mirror(|| crate::ha_api::Update::Event(body.clone()));
+        let Ok(payload) = serde_json::to_vec(&body) else {
+            return;
+        };
+        self.publish_inner(self.event_topic(), false, payload).await;
+    }
+
+    /// Publish a process watchdog alert (non-retained - an alert is a one-off
+    /// event, replaying it to a reconnecting subscriber would re-fire automations).
+    pub async fn publish_process_alert(&self, event: &serde_json::Value) {
+        let Ok(payload) = serde_json::to_vec(event) else {
+            return;
+        };
+        self.publish_inner(self.process_alert_topic(), false, payload)
+            .await;
+    }
+
+    /// Publish how a shell command ended (non-retained, like events).
+    pub async fn publish_command_result(&self, result: &serde_json::Value) {
+        let Ok(payload) = serde_json::to_vec(result) else {
+            return;
+        };
+        self.publish_inner(self.command_result_topic(), false, payload)
+            .await;
+    }
+
+    /// Publish an image entity's picture (retained raw bytes; the last one
+    /// sent while disconnected goes out on reconnect)
+    pub async fn publish_image(&self, name: &str, image: &[u8]) {
+        self.publish_state(
+            &self.sensor_topic_ref(name),
+            true,
+            Bytes::copy_from_slice(image),
+        )
+        .await;
+    }
+
+    /// Publish a `BackupConfig` backup (non-retained: the broker shouldn't
+    /// keep a copy of the config around for every later subscriber).
+    pub async fn publish_config_backup(&self, json: Vec<u8>) {
+        self.publish_inner(self.config_backup_topic(), false, json)
+            .await;
+    }
+
+    /// Last words before exit, once every task has stopped: final values for
+    /// the `FINAL_STATES` this agent published, retained "offline", then a
+    /// clean DISCONNECT after the broker has acked them (or
+    /// `FINAL_ACK_TIMEOUT` passed). Without the wait the process could exit
+    /// with them still queued, leaving HA on a phantom game.
+    pub async fn close(&self) {
+        let mut acks = self.acks.clone();
+        let start = *acks.borrow();
+        let finals = self.final_states();
+        for (name, value) in &finals {
+            self.publish_sensor_retained(name, value).await;
+        }
+        self.publish_availability(false).await;
+
+        // Offline the publishes were only buffered; the LWT covers it.
+        if !self
+            .wait_acked(&mut acks, start + finals.len() as u64 + 1)
+            .await
+        {
+            return;
+        }
+        if let Err(e) = self.client.disconnect().await {
+            debug!("Failed to disconnect: {:?}", e);
+            return;
+        }
+        let mut closed = self.closed.clone();
+        let _ = tokio::time::timeout(Duration::from_secs(1), closed.wait_for(|c| *c)).await;
+    }
+
+    /// The PC is about to sleep or power off: retained `sleep_state` and
+    /// "offline", waited on until the broker acks them (up to
+    /// `FINAL_ACK_TIMEOUT`), so HA sees a planned power-down rather than a
+    /// crash. The heartbeat and reconnect announcement restore "online".
+    pub async fn announce_power_down(&self, sleep_state: &str) {
+        let mut acks = self.acks.clone();
+        let start = *acks.borrow();
+        self.publish_sensor_retained("sleep_state", sleep_state)
+            .await;
+        self.publish_availability(false).await;
+        self.wait_acked(&mut acks, start + 2).await;
+    }
+
+    /// Wait until the broker has acked `expected` publishes in all. False if
+    /// there is no live connection to wait on.
+    async fn wait_acked(&self, acks: &mut watch::Receiver<u64>, expected: u64) -> bool {
+        if matches!(self.client, Client::Disabled) || !self.offline.is_connected() {
+            return false;
+        }
+        if tokio::time::timeout(FINAL_ACK_TIMEOUT, acks.wait_for(|n| *n >= expected))
+            .await
+            .is_err()
+        {
+            warn!("Broker didn't ack the final publishes in time, carrying on");
+        }
+        true
+    }
+
+    /// `FINAL_STATES` entries for sensors this agent has published, skipping
+    /// those already at their final value.
+    fn final_states(&self) -> Vec<(&'static str, &'static str)> {
+        FINAL_STATES
+            .iter()
+            .filter(|(name, value)| self.store.value(name).is_some_and(|v| v != *value))
+            .copied()
+            .collect()
+    }
+
     /// Publish availability status
     pub async fn publish_availability(&self, online: bool) {
         // Zero-copy static payloads - Bytes::from_static avoids the &[u8] → Vec<u8>
         // copy that `publish` would do.
         let payload = if online {
-            bytes::Bytes::from_static(b"online")
+            Bytes::from_static(b"online")
         } else {
-            bytes::Bytes::from_static(b"offline")
+            Bytes::from_static(b"offline")
         };
-        self.publish_bytes_inner(self.availability_topic(), true, payload)
+        self.publish_bytes_inner(&self.cached_topics.availability, true, payload)
             .await;
     }
 
@@ -504,21 +1095,58 @@ impl MqttClient {
     /// `register_hwinfo_sensor` track this in addition to the main LWT.
     pub async fn publish_hwinfo_availability(&self, online: bool) {
         let payload = if online {
-            bytes::Bytes::from_static(b"online")
+            Bytes::from_static(b"online")
         } else {
-            bytes::Bytes::from_static(b"offline")
+            Bytes::from_static(b"offline")
         };
-        self.publish_bytes_inner(self.hwinfo_availability_topic(), true, payload)
+        self.publish_bytes_inner(&self.hwinfo_availability_topic(), true, payload)
             .await;
     }
 
     /// Publish sensor attributes as JSON
     pub async fn publish_sensor_attributes(&self, name: &str, attributes: &serde_json::Value) {
-        let topic = self.sensor_attributes_topic(name);
-        let Ok(payload) = serde_json::to_vec(attributes) else {
+        self.mirror(|| crate::ha_api::Update::Attributes {
+            name: name.to_owned(),
+            attributes: attributes.clone(),
+        });
+        let Some(payload) = self.json_payload(attributes) else {
             return;
         };
-        self.publish_inner(topic, true, payload).await;
+        self.store.remember_attributes(name, &payload);
+        if self.bundles(name) {
+            return;
+        }
+        self.publish_state(&self.sensor_attributes_topic_ref(name), true, payload)
+            .await;
+    }
+
+    /// Publish sensor state or attributes - or, while disconnected, hold just
+    /// the latest value per topic until the ConnAck flush.
+    async fn publish_state(&self, topic: &str, retained: bool, payload: Bytes) {
+        if self.offline.hold(topic, &payload, retained) {
+            return;
+        }
+        self.publish_bytes_inner(topic, retained, payload).await;
+    }
+
+    /// `payload` copied into the scratch buffer
+    fn payload(&self, payload: &[u8]) -> Bytes {
+        let mut scratch = self.scratch.lock().unwrap_or_else(|e| e.into_inner());
+        reserve_scratch(&mut scratch, payload.len());
+        scratch.extend_from_slice(payload);
+        scratch.split().freeze()
+    }
+
+    /// `value` serialized as JSON straight into the scratch buffer
+    fn json_payload(&self, value: &serde_json::Value) -> Option<Bytes> {
+        let mut scratch = self.scratch.lock().unwrap_or_else(|e| e.into_inner());
+        reserve_scratch(&mut scratch, 0);
+        if let Err(e) = serde_json::to_writer((&mut *scratch).writer(), value) {
+            scratch.clear();
+            error!("Failed to serialize sensor attributes: {e}");
+            return None;
+        }
+        Some(scratch.split().freeze())
     }
 
     /// Internal publish helper. Logs failures instead of silently dropping them
@@ -533,11 +1161,12 @@ impl MqttClient {
         }
     }
 
-    /// Zero-copy variant for static byte payloads (LWT, fixed enums).
-    async fn publish_bytes_inner(&self, topic: String, retained: bool, payload: bytes::Bytes) {
+    /// Zero-copy variant for `Bytes` payloads (scratch slices, LWT, fixed
+    /// enums). The topic is copied once, into the packet.
+    async fn publish_bytes_inner(&self, topic: &str, retained: bool, payload: Bytes) {
         if let Err(e) = self
             .client
-            .publish_bytes(&topic, QoS::AtLeastOnce, retained, payload)
+            .publish_bytes(topic, QoS::AtLeastOnce, retained, payload)
             .await
         {
             warn!("MQTT publish_bytes failed for {}: {:?}", topic, e);
@@ -547,6 +1176,17 @@ impl MqttClient {
     // Topic helpers live in mqtt/topics.rs - split impl block.
 }
 
+/// Scratch chunk size: the state and attribute payloads of a few polls
+const SCRATCH_CHUNK: usize = 4096;
+
+/// Make room for at least `len` more bytes, a chunk at a time. `reserve`
+/// takes the chunk back in place once every `Bytes` split off it is gone.
+fn reserve_scratch(scratch: &mut BytesMut, len: usize) {
+    if scratch.capacity() < len.max(SCRATCH_CHUNK / 4) {
+        scratch.reserve(len.max(SCRATCH_CHUNK));
+    }
+}
+
 impl CommandReceiver {
     /// Receive next command (async)
     pub async fn recv(&mut self) -> Option<Command> {
@@ -557,13 +1197,16 @@ impl CommandReceiver {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{FeatureConfig, IntervalConfig, MqttConfig};
+    use crate::config::{
+        CommandQueueConfig, DockerConfig, FeatureConfig, FocusConfig, GameLimitsConfig,
+        HaApiConfig, HttpApiConfig, IdleConfig, IntervalConfig, MqttConfig, NotificationConfig,
+        OpenRgbConfig, ShutdownGuardConfig, ThermalGuardConfig,
+    };
 
     /// Create a minimal MqttClient for testing topics and payload generation.
     /// The event loop is never polled - no real broker connection is made.
     fn test_client(device_name: &str) -> MqttClient {
-        let opts = MqttOptions::new("test-client", "localhost", 1883);
-        let (client, _eventloop) = AsyncClient::new(opts, 10);
+        let client = client::test_client();
         let device_id = device_name.replace('-', "_");
         let (reconnect_tx, _) = broadcast::channel(4);
         MqttClient {
@@ -577,8 +1220,23 @@ mod tests {
                 model: format!("PC Bridge v{}", VERSION),
                 manufacturer: "dank0i".to_string(),
                 sw_version: VERSION.to_string(),
+                suggested_area: None,
+                via_device: None,
             }),
             reconnect_tx,
+            events_tx: broadcast::channel(16).0,
+            group_tx: broadcast::channel(1).0,
+            store: Arc::new(StateStore::default()),
+            offline: Arc::new(OfflineBuffer::default()),
+            ha_api: OnceLock::new(),
+            command_tx: mpsc::channel(1).0,
+            heartbeat: Arc::new(HeartbeatProbe::default()),
+            acks: watch::channel(0).1,
+            closed: watch::channel(false).1,
+            entity_overrides: Mutex::default(),
+            scratch: Mutex::default(),
+            bundled: false,
+            bundle_dirty: AtomicBool::new(false),
         }
     }
 
@@ -586,18 +1244,41 @@ mod tests {
     fn test_config(device_name: &str, features: FeatureConfig) -> Config {
         Config {
             device_name: device_name.to_string(),
+            suggested_area: None,
+            group: None,
+            language: crate::config::default_language(),
             mqtt: MqttConfig {
                 broker: "tcp://localhost:1883".to_string(),
                 user: String::new(),
                 pass: String::new(),
                 client_id: None,
+                protocol_version: 4,
+                session_expiry_secs: 3600,
+                availability_expiry_secs: 0,
+                keep_alive_secs: 30,
+                connect_timeout_secs: 5,
+                tcp_nodelay: false,
+                bundled_state: false,
             },
+            ha_api: HaApiConfig::default(),
+            http_api: HttpApiConfig::default(),
+            openrgb: OpenRgbConfig::default(),
+            command_queue: CommandQueueConfig::default(),
+            notifications: NotificationConfig::default(),
+            idle: IdleConfig::default(),
+            shutdown_guard: ShutdownGuardConfig::default(),
+            game_limits: GameLimitsConfig::default(),
+            thermal_guard: ThermalGuardConfig::default(),
+            focus: FocusConfig::default(),
+            window_history_size: 20,
             intervals: IntervalConfig::default(),
             features,
             games: HashMap::new(),
+            emulators: HashMap::new(),
             custom_sensors_enabled: false,
             custom_commands_enabled: false,
             custom_command_privileges_allowed: false,
+            scripts_enabled: false,
             allow_raw_commands: false,
             allow_global_launch: true,
             allow_global_close: false,
@@ -605,7 +1286,21 @@ mod tests {
             discord_keybind: None,
             custom_sensors: Vec::new(),
             custom_commands: Vec::new(),
+            process_alerts: Vec::new(),
+            process_sensors: Vec::new(),
+            automations: Vec::new(),
+            wol_relay: Vec::new(),
+            display_modes: Vec::new(),
+            speedtest_command: Vec::new(),
+            printers: Vec::new(),
+            usb_devices: Vec::new(),
+            docker: DockerConfig::default(),
+            plugins: Vec::new(),
+            entity_overrides: HashMap::new(),
+            deadbands: HashMap::new(),
             update_channel: crate::config::default_update_channel(),
+            auto_update: true,
+            crash_notify: false,
             disk_sensor_paths: Vec::new(),
         }
     }
@@ -617,7 +1312,7 @@ mod tests {
         let mqtt = test_client("dank0i-pc");
         assert_eq!(
             mqtt.sensor_topic("runninggames"),
-            "homeassistant/sensor/dank0i-pc/runninggames/state"
+            "pc-bridge/dank0i-pc/runninggames/state"
         );
     }
 
@@ -627,7 +1322,7 @@ mod tests {
         // "custom_foo" is not in the cached set, should still produce correct topic
         assert_eq!(
             mqtt.sensor_topic("custom_foo"),
-            "homeassistant/sensor/dank0i-pc/custom_foo/state"
+            "pc-bridge/dank0i-pc/custom_foo/state"
         );
     }
 
@@ -636,7 +1331,7 @@ mod tests {
         let mqtt = test_client("dank0i-pc");
         assert_eq!(
             mqtt.sensor_attributes_topic("runninggames"),
-            "homeassistant/sensor/dank0i-pc/runninggames/attributes"
+            "pc-bridge/dank0i-pc/runninggames/attributes"
         );
     }
 
@@ -649,12 +1344,122 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_event_topic() {
+        let mqtt = test_client("dank0i-pc");
+        assert_eq!(mqtt.event_topic(), "pc-bridge/events/dank0i-pc");
+    }
+
+    #[test]
+    fn test_final_states() {
+        let mqtt = test_client("dank0i-pc");
+        mqtt.remember_value("runninggames", "cs2", true);
+        mqtt.remember_value("steam_updating", "off", true);
+        mqtt.remember_value("cpu_usage", "12", false);
+        // Only published sensors that aren't already at their final value.
+        assert_eq!(mqtt.final_states(), vec![("runninggames", "none")]);
+    }
+
+    #[test]
+    fn test_command_result_topic() {
+        let mqtt = test_client("dank0i-pc");
+        assert_eq!(mqtt.command_result_topic(), "pc-bridge/results/dank0i-pc");
+    }
+
+    #[test]
+    fn test_process_alert_topic() {
+        let mqtt = test_client("dank0i-pc");
+        assert_eq!(mqtt.process_alert_topic(), "pc-bridge/alerts/dank0i-pc");
+    }
+
+    #[test]
+    fn test_ha_birth_detection() {
+        assert!(is_ha_birth("homeassistant/status", b"online", false));
+        // Retained replay on subscribe, HA going down, or another topic.
+        assert!(!is_ha_birth("homeassistant/status", b"online", true));
+        assert!(!is_ha_birth("homeassistant/status", b"offline", false));
+        assert!(!is_ha_birth(
+            "homeassistant/sensor/pc/status",
+            b"online",
+            false
+        ));
+    }
+
+    #[test]
+    fn test_heartbeat_values() {
+        let mqtt = test_client("dank0i-pc");
+        mqtt.remember_value("cpu_usage", "12", false);
+        mqtt.remember_value("cpu_usage", "15", false);
+        mqtt.remember_value("runninggames", "none", true);
+        // Never resent: would clobber "sleeping" right before suspend.
+        mqtt.remember_value("sleep_state", "awake", true);
+        mqtt.forget_value("runninggames");
+
+        assert_eq!(
+            mqtt.heartbeat_values(),
+            vec![(mqtt.sensor_topic("cpu_usage"), "15".to_string(), false)]
+        );
+        // /status still reports it.
+        assert_eq!(mqtt.sensor_values()["sleep_state"], "awake");
+        assert!(!mqtt.sensor_values().contains_key("runninggames"));
+    }
+
+    #[test]
+    fn test_bundled_sensors_skip_their_own_topics() {
+        let mut mqtt = test_client("dank0i-pc");
+        mqtt.bundled = true;
+        mqtt.remember_value("display", "on", false);
+        assert!(!mqtt.bundles("display"));
+        assert!(!mqtt.bundle_dirty.load(Ordering::Relaxed));
+        mqtt.remember_value("cpu_usage", "12.3", false);
+        assert!(mqtt.bundles("cpu_usage"));
+        assert!(mqtt.bundle_dirty.load(Ordering::Relaxed));
+
+        // The bundle carries cpu_usage; the heartbeat only resends display.
+        assert_eq!(
+            mqtt.heartbeat_values(),
+            vec![(mqtt.sensor_topic("display"), "on".to_string(), false)]
+        );
+        assert_eq!(
+            bundle::document(mqtt.sensor_values(), mqtt.store.attributes()),
+            Some(serde_json::json!({ "cpu_usage": "12.3" }))
+        );
+        // Its history attributes ride in the bundle too.
+        mqtt.store
+            .remember_attributes("cpu_usage", br#"{"avg_1m":12.3}"#);
+        assert_eq!(
+            bundle::document(mqtt.sensor_values(), mqtt.store.attributes()),
+            Some(serde_json::json!({
+                "cpu_usage": "12.3",
+                "attributes": { "cpu_usage": { "avg_1m": 12.3 } },
+            }))
+        );
+    }
+
+    #[test]
+    fn test_legacy_state_topics() {
+        let mqtt = test_client("dank0i-pc");
+        let topics = MqttClient::legacy_state_topics("dank0i-pc", &["cpu_usage".to_string()]);
+        assert_eq!(
+            topics,
+            vec![
+                "homeassistant/sensor/dank0i-pc/availability",
+                "homeassistant/sensor/dank0i-pc/hwinfo_availability",
+                "homeassistant/sensor/dank0i-pc/cpu_usage/state",
+                "homeassistant/sensor/dank0i-pc/cpu_usage/attributes",
+            ]
+        );
+        // None of them collide with the current layout.
+        assert!(!topics.contains(&mqtt.sensor_topic("cpu_usage")));
+        assert!(!topics.contains(&mqtt.availability_topic()));
+    }
+
     #[test]
     fn test_availability_topic_instance() {
         let mqtt = test_client("dank0i-pc");
         assert_eq!(
             mqtt.availability_topic(),
-            "homeassistant/sensor/dank0i-pc/availability"
+            "pc-bridge/dank0i-pc/availability"
         );
     }
 
@@ -700,17 +1505,14 @@ mod tests {
     #[test]
     fn test_cached_topics_correct_format() {
         let ct = CachedTopics::new("my-pc");
-        assert_eq!(
-            ct.availability.as_ref(),
-            "homeassistant/sensor/my-pc/availability"
-        );
+        assert_eq!(ct.availability.as_ref(), "pc-bridge/my-pc/availability");
         assert_eq!(
             ct.sensor_state.get("cpu_usage").unwrap().as_ref(),
-            "homeassistant/sensor/my-pc/cpu_usage/state"
+            "pc-bridge/my-pc/cpu_usage/state"
         );
         assert_eq!(
             ct.sensor_attrs.get("cpu_usage").unwrap().as_ref(),
-            "homeassistant/sensor/my-pc/cpu_usage/attributes"
+            "pc-bridge/my-pc/cpu_usage/attributes"
         );
     }
 
@@ -733,6 +1535,10 @@ mod tests {
             device_class: None,
             unit_of_measurement: Some("%".to_string()),
             state_class: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
@@ -740,13 +1546,10 @@ mod tests {
         // Required fields for HA sensor discovery
         assert_eq!(json["name"], "CPU Usage");
         assert_eq!(json["unique_id"], "dank0i_pc_cpu_usage");
-        assert_eq!(
-            json["state_topic"],
-            "homeassistant/sensor/dank0i-pc/cpu_usage/state"
-        );
+        assert_eq!(json["state_topic"], "pc-bridge/dank0i-pc/cpu_usage/state");
         assert_eq!(
             json["availability_topic"],
-            "homeassistant/sensor/dank0i-pc/availability"
+            "pc-bridge/dank0i-pc/availability"
         );
         assert_eq!(json["icon"], "mdi:cpu-64-bit");
         assert_eq!(json["unit_of_measurement"], "%");
@@ -772,6 +1575,10 @@ mod tests {
             device_class: None,
             unit_of_measurement: None,
             state_class: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
@@ -785,7 +1592,7 @@ mod tests {
         );
         assert_eq!(
             json["availability_topic"],
-            "homeassistant/sensor/dank0i-pc/availability"
+            "pc-bridge/dank0i-pc/availability"
         );
         assert_eq!(json["icon"], "mdi:power-sleep");
 
@@ -812,17 +1619,21 @@ mod tests {
             device_class: None,
             unit_of_measurement: None,
             state_class: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
 
         assert_eq!(
             json["json_attributes_topic"],
-            "homeassistant/sensor/dank0i-pc/runninggames/attributes"
+            "pc-bridge/dank0i-pc/runninggames/attributes"
         );
         assert_eq!(
             json["state_topic"],
-            "homeassistant/sensor/dank0i-pc/runninggames/state"
+            "pc-bridge/dank0i-pc/runninggames/state"
         );
     }
 
@@ -843,6 +1654,10 @@ mod tests {
             device_class: None,
             unit_of_measurement: None,
             state_class: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
@@ -852,6 +1667,8 @@ mod tests {
         assert_eq!(device["name"], "dank0i-pc");
         assert_eq!(device["manufacturer"], "dank0i");
         assert_eq!(device["sw_version"], VERSION);
+        // Unset area is left out, not sent as null.
+        assert!(device.get("suggested_area").is_none());
         assert!(
             device["model"].as_str().unwrap().starts_with("PC Bridge v"),
             "model should start with 'PC Bridge v'"
@@ -876,6 +1693,10 @@ mod tests {
             device_class: None,
             unit_of_measurement: None,
             state_class: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
@@ -885,10 +1706,7 @@ mod tests {
             json.get("availability_topic").is_none(),
             "sleep_state should NOT have availability_topic"
         );
-        assert_eq!(
-            json["state_topic"],
-            "homeassistant/sensor/dank0i-pc/sleep_state/state"
-        );
+        assert_eq!(json["state_topic"], "pc-bridge/dank0i-pc/sleep_state/state");
     }
 
     #[test]
@@ -908,11 +1726,17 @@ mod tests {
                 model: "test".to_string(),
                 manufacturer: "test".to_string(),
                 sw_version: "0.0.0".to_string(),
+                suggested_area: None,
+                via_device: None,
             }),
             icon: None,
             device_class: None,
             unit_of_measurement: None,
             state_class: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let json_str = serde_json::to_string(&payload).unwrap();
@@ -946,6 +1770,10 @@ mod tests {
             device_class: Some("timestamp".to_string()),
             unit_of_measurement: None,
             state_class: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
@@ -968,6 +1796,12 @@ mod tests {
             file_path: None,
             registry_key: None,
             registry_value: None,
+            pattern: None,
+            folder: None,
+            metric: crate::config::FolderMetric::Count,
+            host: None,
+            timeout_ms: None,
+            service: None,
         };
 
         let topic_name = format!("custom_{}", sensor.name);
@@ -985,6 +1819,10 @@ mod tests {
             unit_of_measurement: sensor.unit.clone(),
             state_class: derive_state_class(None, sensor.unit.as_deref()),
             json_attributes_topic: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
@@ -993,7 +1831,7 @@ mod tests {
         assert_eq!(json["unique_id"], "dank0i_pc_custom_gpu_power");
         assert_eq!(
             json["state_topic"],
-            "homeassistant/sensor/dank0i-pc/custom_gpu_power/state"
+            "pc-bridge/dank0i-pc/custom_gpu_power/state"
         );
         assert_eq!(json["unit_of_measurement"], "W");
         assert_eq!(json["icon"], "mdi:lightning-bolt");
@@ -1011,6 +1849,8 @@ mod tests {
             path: None,
             args: None,
             command: Some("reboot-router.sh".to_string()),
+            service: None,
+            sandbox: None,
         };
 
         let payload = HADiscoveryPayload {
@@ -1027,6 +1867,10 @@ mod tests {
             unit_of_measurement: None,
             state_class: None,
             json_attributes_topic: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
@@ -1052,13 +1896,7 @@ mod tests {
             "unique_id": format!("{}_notify", mqtt.device_id),
             "command_topic": notify_topic,
             "availability_topic": mqtt.availability_topic(),
-            "device": {
-                "identifiers": mqtt.device.identifiers,
-                "name": mqtt.device.name,
-                "model": mqtt.device.model,
-                "manufacturer": mqtt.device.manufacturer,
-                "sw_version": mqtt.device.sw_version
-            },
+            "device": mqtt.device,
             "icon": "mdi:message-badge",
             "qos": 1
         });
@@ -1165,6 +2003,27 @@ mod tests {
         assert!(topics.contains(&"pc-bridge/notifications/test-pc".to_string()));
     }
 
+    #[test]
+    fn test_subscribe_topics_with_wol_relay() {
+        let mut config = test_config("test-pc", FeatureConfig::default());
+        config.wol_relay = vec![crate::config::WolTarget {
+            device_name: "gaming-rig".to_string(),
+            mac: "AA:BB:CC:DD:EE:FF".to_string(),
+            broadcast: "255.255.255.255".to_string(),
+            port: 9,
+        }];
+        let topics = MqttClient::build_subscribe_topics("test-pc", &config);
+
+        assert!(topics.contains(&"pc-bridge/wol/gaming-rig".to_string()));
+        assert!(topics.contains(&"homeassistant/button/gaming-rig/Wake/action".to_string()));
+        // Both wake the same target.
+        assert!(
+            wol_relay_topics(&config)
+                .iter()
+                .all(|(_, target)| target == "gaming-rig")
+        );
+    }
+
     #[test]
     fn test_subscribe_topics_with_custom_commands() {
         let features = FeatureConfig::default();
@@ -1179,6 +2038,8 @@ mod tests {
                 path: None,
                 args: None,
                 command: Some("echo test".to_string()),
+                service: None,
+                sandbox: None,
             },
             CustomCommand {
                 name: "backup_db".to_string(),
@@ -1189,6 +2050,8 @@ mod tests {
                 path: None,
                 args: None,
                 command: Some("echo backup".to_string()),
+                service: None,
+                sandbox: None,
             },
         ];
 
@@ -1220,6 +2083,7 @@ mod tests {
             memory_sensor: true,
             active_window: true,
             session_state: true,
+            remote_desktop: true,
             audio_device: true,
             mic: true,
             webcam: true,
@@ -1233,6 +2097,36 @@ mod tests {
             disk_sensor: true,
             uptime_sensor: true,
             hwinfo_sensor: true,
+            windows_update: true,
+            cmd_windows_update_install: true,
+            wake_timers: true,
+            sleep_blockers: true,
+            power_plan: true,
+            device_batteries: true,
+            gamepad: true,
+            vr: true,
+            transcoding: true,
+            network_connection: true,
+            disk_health: true,
+            latency: true,
+            public_ip: true,
+            printers: true,
+            usb_devices: true,
+            docker: true,
+            media_playing: true,
+            display_mode: true,
+            game_art: true,
+            theme: true,
+            openrgb: true,
+            perf_mode: true,
+            window_history: true,
+            focus_mode: true,
+            cmd_speedtest: true,
+            cmd_disk_cleanup: true,
+            cmd_install_update: true,
+            cmd_config_backup: true,
+            cmd_file_transfer: true,
+            cmd_wallpaper: true,
         };
         let config = test_config("test-pc", features);
         let topics = MqttClient::build_subscribe_topics("test-pc", &config);
@@ -1291,6 +2185,10 @@ mod tests {
             device_class: Some("battery".to_string()),
             unit_of_measurement: Some("%".to_string()),
             state_class: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
 
         // Serialize → parse back → verify it's a valid JSON object
@@ -1305,7 +2203,7 @@ mod tests {
     #[test]
     fn test_lwt_topic_and_payload() {
         let topic = MqttClient::availability_topic_static("dank0i-pc");
-        assert_eq!(topic, "homeassistant/sensor/dank0i-pc/availability");
+        assert_eq!(topic, "pc-bridge/dank0i-pc/availability");
 
         // LWT payload should be "offline"
         let payload = "offline";
@@ -1336,7 +2234,7 @@ mod tests {
         let value = "awake";
         let mqtt = test_client("dank0i-pc");
         let topic = mqtt.sensor_topic("sleep_state");
-        assert_eq!(topic, "homeassistant/sensor/dank0i-pc/sleep_state/state");
+        assert_eq!(topic, "pc-bridge/dank0i-pc/sleep_state/state");
         assert_eq!(value, "awake");
     }
 
@@ -1346,7 +2244,7 @@ mod tests {
         let value = "sleeping";
         let mqtt = test_client("dank0i-pc");
         let topic = mqtt.sensor_topic("sleep_state");
-        assert_eq!(topic, "homeassistant/sensor/dank0i-pc/sleep_state/state");
+        assert_eq!(topic, "pc-bridge/dank0i-pc/sleep_state/state");
         assert_eq!(value, "sleeping");
     }
 
@@ -1366,7 +2264,7 @@ mod tests {
     fn test_screensaver_topic() {
         let mqtt = test_client("dank0i-pc");
         let topic = mqtt.sensor_topic("screensaver");
-        assert_eq!(topic, "homeassistant/sensor/dank0i-pc/screensaver/state");
+        assert_eq!(topic, "pc-bridge/dank0i-pc/screensaver/state");
     }
 
     #[test]
@@ -1428,10 +2326,7 @@ mod tests {
 
         let mqtt = test_client("dank0i-pc");
         let topic = mqtt.sensor_topic(&topic_name);
-        assert_eq!(
-            topic,
-            "homeassistant/sensor/dank0i-pc/custom_my_sensor/state"
-        );
+        assert_eq!(topic, "pc-bridge/dank0i-pc/custom_my_sensor/state");
     }
 
     #[test]
@@ -1440,7 +2335,7 @@ mod tests {
         let value = "on";
         let mqtt = test_client("dank0i-pc");
         let topic = mqtt.sensor_topic("display");
-        assert_eq!(topic, "homeassistant/sensor/dank0i-pc/display/state");
+        assert_eq!(topic, "pc-bridge/dank0i-pc/display/state");
         assert_eq!(value, "on");
     }
 
@@ -1451,14 +2346,8 @@ mod tests {
         let state_topic = mqtt.sensor_topic("runninggames");
         let attrs_topic = mqtt.sensor_attributes_topic("runninggames");
 
-        assert_eq!(
-            state_topic,
-            "homeassistant/sensor/dank0i-pc/runninggames/state"
-        );
-        assert_eq!(
-            attrs_topic,
-            "homeassistant/sensor/dank0i-pc/runninggames/attributes"
-        );
+        assert_eq!(state_topic, "pc-bridge/dank0i-pc/runninggames/state");
+        assert_eq!(attrs_topic, "pc-bridge/dank0i-pc/runninggames/attributes");
 
         // When no game: payload "none", attrs {"display_name":"None"}
         let no_game_payload = "none";
@@ -1486,14 +2375,8 @@ mod tests {
         let state_topic = mqtt.sensor_topic("steam_updating");
         let attrs_topic = mqtt.sensor_attributes_topic("steam_updating");
 
-        assert_eq!(
-            state_topic,
-            "homeassistant/sensor/dank0i-pc/steam_updating/state"
-        );
-        assert_eq!(
-            attrs_topic,
-            "homeassistant/sensor/dank0i-pc/steam_updating/attributes"
-        );
+        assert_eq!(state_topic, "pc-bridge/dank0i-pc/steam_updating/state");
+        assert_eq!(attrs_topic, "pc-bridge/dank0i-pc/steam_updating/attributes");
 
         // When not updating: "off" + {"updating_games":[],"count":0}
         let idle_payload = "off";
@@ -1532,11 +2415,13 @@ mod tests {
 
     #[test]
     fn test_sensor_attributes_serializes_to_bytes() {
-        // publish_sensor_attributes uses serde_json::to_vec (zero-copy) - verify it produces
-        // identical output to to_string for our attribute shapes
+        // publish_sensor_attributes serializes straight into the scratch buffer -
+        // verify it produces identical output to to_string for our attribute shapes
         let attrs = serde_json::json!({"display_name": "HELLDIVERS 2"});
 
-        let vec_bytes = serde_json::to_vec(&attrs).unwrap();
+        let mut scratch = BytesMut::new();
+        serde_json::to_writer((&mut scratch).writer(), &attrs).unwrap();
+        let vec_bytes = scratch.to_vec();
         let string_bytes = serde_json::to_string(&attrs).unwrap().into_bytes();
 
         assert_eq!(vec_bytes, string_bytes);
@@ -1546,64 +2431,25 @@ mod tests {
         );
     }
 
-    // ===== parse_broker_url tests =====
-
-    #[test]
-    fn test_parse_broker_url_tcp() {
-        let (host, port, tls) = MqttClient::parse_broker_url("tcp://localhost:1883").unwrap();
-        assert_eq!(host, "localhost");
-        assert_eq!(port, 1883);
-        assert!(!tls);
-    }
-
-    #[test]
-    fn test_parse_broker_url_ssl() {
-        let (host, port, tls) =
-            MqttClient::parse_broker_url("ssl://mqtt.example.com:8883").unwrap();
-        assert_eq!(host, "mqtt.example.com");
-        assert_eq!(port, 8883);
-        assert!(tls);
-    }
-
-    #[test]
-    fn test_parse_broker_url_ws() {
-        let (host, port, tls) = MqttClient::parse_broker_url("ws://192.168.1.100:8083").unwrap();
-        assert_eq!(host, "192.168.1.100");
-        assert_eq!(port, 8083);
-        assert!(!tls);
-    }
-
-    #[test]
-    fn test_parse_broker_url_wss() {
-        let (host, port, tls) =
-            MqttClient::parse_broker_url("wss://mqtt.example.com:8084").unwrap();
-        assert_eq!(host, "mqtt.example.com");
-        assert_eq!(port, 8084);
-        assert!(tls);
-    }
-
     #[test]
-    fn test_parse_broker_url_default_port() {
-        let (host, port, tls) = MqttClient::parse_broker_url("tcp://localhost").unwrap();
-        assert_eq!(host, "localhost");
-        assert_eq!(port, 1883);
-        assert!(!tls);
-    }
-
-    #[test]
-    fn test_parse_broker_url_default_port_ssl() {
-        let (host, port, tls) = MqttClient::parse_broker_url("ssl://broker.local").unwrap();
-        assert_eq!(host, "broker.local");
-        assert_eq!(port, 8883);
-        assert!(tls);
-    }
-
-    #[test]
-    fn test_parse_broker_url_ipv4() {
-        let (host, port, tls) = MqttClient::parse_broker_url("tcp://192.168.1.1:1883").unwrap();
-        assert_eq!(host, "192.168.1.1");
-        assert_eq!(port, 1883);
-        assert!(!tls);
+    fn test_scratch_payloads_reuse_the_chunk() {
+        let client = test_client("test-pc");
+        let first = client.payload(b"42.5");
+        let attrs = client
+            .json_payload(&serde_json::json!({"display_name": "HELLDIVERS 2"}))
+            .unwrap();
+        assert_eq!(&first[..], b"42.5");
+        assert_eq!(&attrs[..], br#"{"display_name":"HELLDIVERS 2"}"#);
+        // Split off one chunk, back to back
+        assert_eq!(attrs.as_ptr(), first[first.len()..].as_ptr());
+
+        // Once the event loop has dropped them, the chunk is reused in place.
+        let start = first.as_ptr();
+        drop((first, attrs));
+        for _ in 0..SCRATCH_CHUNK {
+            let value = client.payload(b"17");
+            assert!(value.as_ptr() >= start && value.as_ptr() < start.wrapping_add(SCRATCH_CHUNK));
+        }
     }
 
     // ===== extract_command_name tests =====
@@ -1655,7 +2501,7 @@ mod tests {
     #[test]
     fn test_availability_topic_static() {
         let topic = MqttClient::availability_topic_static("test-pc");
-        assert_eq!(topic, "homeassistant/sensor/test-pc/availability");
+        assert_eq!(topic, "pc-bridge/test-pc/availability");
     }
 
     // ===== Command struct tests =====
@@ -1898,18 +2744,41 @@ mod tests {
         fn broker_config(device_name: &str, port: u16, features: FeatureConfig) -> Config {
             Config {
                 device_name: device_name.to_string(),
+                suggested_area: None,
+                group: None,
+                language: crate::config::default_language(),
                 mqtt: MqttConfig {
                     broker: format!("tcp://127.0.0.1:{port}"),
                     user: String::new(),
                     pass: String::new(),
                     client_id: None,
+                    protocol_version: 4,
+                    session_expiry_secs: 3600,
+                    availability_expiry_secs: 0,
+                    keep_alive_secs: 30,
+                    connect_timeout_secs: 5,
+                    tcp_nodelay: false,
+                    bundled_state: false,
                 },
+                ha_api: HaApiConfig::default(),
+                http_api: HttpApiConfig::default(),
+                openrgb: OpenRgbConfig::default(),
+                command_queue: CommandQueueConfig::default(),
+                notifications: NotificationConfig::default(),
+                idle: IdleConfig::default(),
+                shutdown_guard: ShutdownGuardConfig::default(),
+                game_limits: GameLimitsConfig::default(),
+                thermal_guard: ThermalGuardConfig::default(),
+                focus: FocusConfig::default(),
+                window_history_size: 20,
                 intervals: IntervalConfig::default(),
                 features,
                 games: HashMap::new(),
+                emulators: HashMap::new(),
                 custom_sensors_enabled: false,
                 custom_commands_enabled: false,
                 custom_command_privileges_allowed: false,
+                scripts_enabled: false,
                 allow_raw_commands: false,
                 allow_global_launch: true,
                 allow_global_close: false,
@@ -1917,7 +2786,21 @@ mod tests {
                 discord_keybind: None,
                 custom_sensors: Vec::new(),
                 custom_commands: Vec::new(),
+                process_alerts: Vec::new(),
+                process_sensors: Vec::new(),
+                automations: Vec::new(),
+                wol_relay: Vec::new(),
+                display_modes: Vec::new(),
+                speedtest_command: Vec::new(),
+                printers: Vec::new(),
+                usb_devices: Vec::new(),
+                docker: DockerConfig::default(),
+                plugins: Vec::new(),
+                entity_overrides: HashMap::new(),
+                deadbands: HashMap::new(),
                 update_channel: crate::config::default_update_channel(),
+                auto_update: true,
+                crash_notify: false,
                 disk_sensor_paths: Vec::new(),
             }
         }
@@ -1943,6 +2826,7 @@ mod tests {
                 memory_sensor: true,
                 active_window: true,
                 session_state: true,
+                remote_desktop: true,
                 audio_device: true,
                 mic: true,
                 webcam: true,
@@ -1956,6 +2840,36 @@ mod tests {
                 disk_sensor: true,
                 uptime_sensor: true,
                 hwinfo_sensor: true,
+                windows_update: true,
+                cmd_windows_update_install: true,
+                wake_timers: true,
+                sleep_blockers: true,
+                power_plan: true,
+                device_batteries: true,
+                gamepad: true,
+                vr: true,
+                transcoding: true,
+                network_connection: true,
+                disk_health: true,
+                latency: true,
+                public_ip: true,
+                printers: true,
+                usb_devices: true,
+                docker: true,
+                media_playing: true,
+                display_mode: true,
+                game_art: true,
+                theme: true,
+                openrgb: true,
+                perf_mode: true,
+                window_history: true,
+                focus_mode: true,
+                cmd_speedtest: true,
+                cmd_disk_cleanup: true,
+                cmd_install_update: true,
+                cmd_config_backup: true,
+                cmd_file_transfer: true,
+                cmd_wallpaper: true,
             }
         }
 
@@ -2084,6 +2998,8 @@ mod tests {
                     path: None,
                     args: None,
                     command: Some("echo test".to_string()),
+                    service: None,
+                    sandbox: None,
                 });
             }
 
@@ -2356,12 +3272,107 @@ mod tests {
             let availability = guard
                 .published
                 .iter()
-                .find(|(t, _)| t == "homeassistant/sensor/test-pc/availability");
+                .find(|(t, _)| t == "pc-bridge/test-pc/availability");
 
             assert!(availability.is_some(), "Availability not published");
             let payload = String::from_utf8_lossy(&availability.unwrap().1).to_string();
             assert_eq!(payload, "online");
         }
+
+        #[tokio::test(flavor = "current_thread")]
+        async fn test_ha_birth_republishes_availability() {
+            let (port, state, inject) = start_mini_broker().await;
+            let config = broker_config("test-pc", port, FeatureConfig::default());
+            let (stx, _) = test_shutdown();
+
+            let (_mqtt, _cmd_rx) = MqttClient::new(&config, stx.subscribe()).await.unwrap();
+            wait_for_topics(&state, &["pc-bridge/test-pc/availability".to_string()]).await;
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while !state
+                    .lock()
+                    .unwrap()
+                    .subscribed
+                    .iter()
+                    .any(|t| t == HA_STATUS_TOPIC)
+                {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("never subscribed to homeassistant/status");
+
+            let count = |state: &Arc<Mutex<BrokerState>>| {
+                state
+                    .lock()
+                    .unwrap()
+                    .published
+                    .iter()
+                    .filter(|(t, _)| t == "pc-bridge/test-pc/availability")
+                    .count()
+            };
+            let before = count(&state);
+            inject
+                .send((HA_STATUS_TOPIC.to_string(), "online".to_string()))
+                .await
+                .unwrap();
+
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while count(&state) <= before {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("HA birth did not republish availability");
+        }
+
+        #[tokio::test]
+        async fn test_offline_updates_flush_latest_after_connack() {
+            let (port, state, _inject) = start_mini_broker().await;
+            let config = broker_config("test-pc", port, FeatureConfig::default());
+            let (stx, _) = test_shutdown();
+
+            let (mqtt, _cmd_rx) = MqttClient::new(&config, stx.subscribe()).await.unwrap();
+            // Let the first ConnAck land; its announcement replays every stored
+            // value, which would otherwise double the flushed one below.
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while !mqtt.is_connected() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("never connected");
+            // Simulate an outage: everything published now is held, not queued.
+            mqtt.offline.disconnected();
+            mqtt.publish_sensor("custom_probe", "10").await;
+            mqtt.publish_sensor("custom_probe", "42").await;
+            let topic = "pc-bridge/test-pc/custom_probe/state";
+            assert!(
+                !state
+                    .lock()
+                    .unwrap()
+                    .published
+                    .iter()
+                    .any(|(t, _)| t == topic),
+                "offline update reached the broker"
+            );
+
+            // Reconnect: the flush sends only the latest value.
+            let epoch = mqtt.offline.epoch();
+            for (topic, payload, retained) in mqtt.offline.drain_or_connect(epoch) {
+                mqtt.client
+                    .publish(&topic, QoS::AtLeastOnce, retained, payload)
+                    .await
+                    .unwrap();
+            }
+            wait_for_topics(&state, &[topic.to_string()]).await;
+            let published = state.lock().unwrap().published.clone();
+            let values: Vec<&[u8]> = published
+                .iter()
+                .filter(|(t, _)| t == topic)
+                .map(|(_, p)| p.as_slice())
+                .collect();
+            assert_eq!(values, [b"42".as_slice()]);
+        }
     }
 
     // ===== state_class on a HADiscoveryPayload =====
@@ -2384,6 +3395,10 @@ mod tests {
             device_class: Some("power".to_string()),
             unit_of_measurement: Some("W".to_string()),
             state_class: Some("measurement".to_string()),
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
         let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
         assert_eq!(json["state_class"], "measurement");
@@ -2406,6 +3421,10 @@ mod tests {
             device_class: None,
             unit_of_measurement: None,
             state_class: None,
+            expire_after: None,
+            entity_category: None,
+            value_template: None,
+            json_attributes_template: None,
         };
         let json: serde_json::Value = serde_json::to_value(&payload).unwrap();
         // String enum sensors should NOT have state_class serialized
@@ -0,0 +1,151 @@
+//! Latest-value buffer for sensor state published while the broker is down.
+//!
+//! Without it, publishes made during an outage pile up in rumqttc's bounded
+//! request channel (blocking the publisher once it fills) and replay in order
+//! on reconnect, so HA walks through minutes of stale readings - or, once the
+//! channel overflows, never sees the newest ones. While disconnected the
+//! client instead keeps only the newest payload per topic here, and the event
+//! loop flushes it after ConnAck.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use log::warn;
+
+/// Distinct topics held while offline. Well above the ~60 built-in sensor
+/// state/attribute topics; a topic beyond this is dropped (its next publish
+/// after the reconnect still lands).
+const MAX_PENDING_TOPICS: usize = 256;
+
+#[derive(Default)]
+struct Inner {
+    connected: bool,
+    /// Bumped on every disconnect, so a flush started for an earlier
+    /// connection can't mark a later, dead one connected.
+    epoch: u64,
+    /// topic -> (payload, retained)
+    pending: HashMap<String, (Vec<u8>, bool)>,
+}
+
+/// Starts disconnected: anything published before the first ConnAck is
+/// buffered and flushed along with the initial announcement.
+#[derive(Default)]
+pub(super) struct OfflineBuffer {
+    inner: Mutex<Inner>,
+}
+
+impl OfflineBuffer {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(super) fn is_connected(&self) -> bool {
+        self.lock().connected
+    }
+
+    /// Called on a connection error: subsequent state publishes are buffered.
+    pub(super) fn disconnected(&self) {
+        let mut inner = self.lock();
+        inner.connected = false;
+        inner.epoch += 1;
+    }
+
+    /// Called on ConnAck; pass the result to `drain_or_connect`.
+    pub(super) fn epoch(&self) -> u64 {
+        self.lock().epoch
+    }
+
+    /// Hold `payload` as the latest value for `topic` if offline. Returns
+    /// `false` when connected, i.e. the caller should publish it now.
+    pub(super) fn hold(&self, topic: &str, payload: &[u8], retained: bool) -> bool {
+        let mut inner = self.lock();
+        if inner.connected {
+            return false;
+        }
+        if let Some(entry) = inner.pending.get_mut(topic) {
+            entry.0.clear();
+            entry.0.extend_from_slice(payload);
+            entry.1 = retained;
+        } else if inner.pending.len() < MAX_PENDING_TOPICS {
+            inner
+                .pending
+                .insert(topic.to_owned(), (payload.to_vec(), retained));
+        } else {
+            warn!("Offline buffer full - dropping update for {topic}");
+        }
+        true
+    }
+
+    /// Next batch to flush after ConnAck. An empty batch atomically marks the
+    /// buffer connected, so callers loop until empty: values published while a
+    /// batch is in flight are still buffered and go out in the next one, never
+    /// overtaken by (and then overwritten with) an older buffered value. Also
+    /// empty once the connection from `epoch` has dropped again.
+    pub(super) fn drain_or_connect(&self, epoch: u64) -> Vec<(String, Vec<u8>, bool)> {
+        let mut inner = self.lock();
+        if inner.epoch != epoch {
+            return Vec::new();
+        }
+        if inner.pending.is_empty() {
+            inner.connected = true;
+            return Vec::new();
+        }
+        inner
+            .pending
+            .drain()
+            .map(|(topic, (payload, retained))| (topic, payload, retained))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_latest_value_until_connected() {
+        let buffer = OfflineBuffer::default();
+        assert!(buffer.hold("pc-bridge/pc/cpu_usage/state", b"10", false));
+        assert!(buffer.hold("pc-bridge/pc/cpu_usage/state", b"42", false));
+        assert!(buffer.hold("pc-bridge/pc/runninggames/state", b"none", true));
+
+        let epoch = buffer.epoch();
+        let mut batch = buffer.drain_or_connect(epoch);
+        batch.sort();
+        assert_eq!(
+            batch,
+            vec![
+                (
+                    "pc-bridge/pc/cpu_usage/state".to_string(),
+                    b"42".to_vec(),
+                    false
+                ),
+                (
+                    "pc-bridge/pc/runninggames/state".to_string(),
+                    b"none".to_vec(),
+                    true
+                ),
+            ]
+        );
+        assert!(!buffer.is_connected());
+        assert!(buffer.drain_or_connect(epoch).is_empty());
+        assert!(buffer.is_connected());
+        assert!(!buffer.hold("pc-bridge/pc/cpu_usage/state", b"50", false));
+
+        buffer.disconnected();
+        assert!(buffer.hold("pc-bridge/pc/cpu_usage/state", b"7", false));
+        // A flush for the connection that just dropped neither drains nor
+        // marks the buffer connected.
+        assert!(buffer.drain_or_connect(epoch).is_empty());
+        assert!(!buffer.is_connected());
+    }
+
+    #[test]
+    fn test_bounded() {
+        let buffer = OfflineBuffer::default();
+        for i in 0..MAX_PENDING_TOPICS + 10 {
+            buffer.hold(&format!("t/{i}"), b"1", false);
+        }
+        assert_eq!(buffer.drain_or_connect(0).len(), MAX_PENDING_TOPICS);
+    }
+}
@@ -42,6 +42,153 @@ pub(super) struct HADiscoveryPayload {
     /// `derive_state_class`.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub(super) state_class: Option<String>,
+    /// Seconds without a state message after which HA shows the sensor as
+    /// unavailable - catches a hung or crashed agent whose retained values
+    /// would otherwise look live. See `sensor_expire_after`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) expire_after: Option<u64>,
+    /// "diagnostic" files the entity under the device page's Diagnostic
+    /// section instead of its main sensors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) entity_category: Option<&'static str>,
+    /// Picks the entity's value out of a shared JSON state topic (bundled
+    /// state, see `bundle`); None reads the payload as-is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) value_template: Option<String>,
+    /// Picks the entity's attributes out of a shared JSON topic (bundled
+    /// state); None reads `json_attributes_topic` as-is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) json_attributes_template: Option<String>,
+}
+
+/// HA MQTT device trigger (`device_automation` component). Shows up under the
+/// device in HA's automation editor; fires when a message on `topic` renders
+/// (via `value_template`) to `payload`.
+#[derive(Serialize)]
+pub(super) struct HADeviceTrigger {
+    pub(super) automation_type: &'static str,
+    pub(super) topic: String,
+    #[serde(rename = "type")]
+    pub(super) trigger_type: String,
+    pub(super) subtype: String,
+    pub(super) payload: String,
+    pub(super) value_template: &'static str,
+    pub(super) device: Arc<HADevice>,
+}
+
+/// HA MQTT button that sends a fixed payload (HA's default is "PRESS"), so
+/// several buttons can share one command with different arguments.
+#[derive(Serialize)]
+pub(super) struct HAButton {
+    pub(super) name: String,
+    pub(super) unique_id: String,
+    pub(super) command_topic: String,
+    pub(super) payload_press: String,
+    pub(super) availability_topic: String,
+    pub(super) device: Arc<HADevice>,
+    pub(super) icon: String,
+}
+
+/// HA MQTT select: current option on `state_topic`, the chosen option is
+/// sent as the payload on `command_topic`. Without a state topic HA shows the
+/// last option chosen.
+#[derive(Serialize)]
+pub(super) struct HASelect {
+    pub(super) name: String,
+    pub(super) unique_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) state_topic: Option<String>,
+    pub(super) command_topic: String,
+    pub(super) availability_topic: String,
+    pub(super) options: Vec<String>,
+    pub(super) device: Arc<HADevice>,
+    pub(super) icon: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) json_attributes_topic: Option<String>,
+}
+
+/// HA MQTT image: the raw picture bytes on `image_topic`.
+#[derive(Serialize)]
+pub(super) struct HAImage {
+    pub(super) name: String,
+    pub(super) unique_id: String,
+    pub(super) image_topic: String,
+    pub(super) content_type: &'static str,
+    pub(super) json_attributes_topic: String,
+    pub(super) availability_topic: String,
+    pub(super) device: Arc<HADevice>,
+    pub(super) icon: String,
+}
+
+/// HA MQTT switch: "ON" / "OFF" on `state_topic`, toggled by sending the
+/// same on `command_topic`.
+#[derive(Serialize)]
+pub(super) struct HASwitch {
+    pub(super) name: String,
+    pub(super) unique_id: String,
+    pub(super) state_topic: String,
+    pub(super) command_topic: String,
+    pub(super) availability_topic: String,
+    pub(super) device: Arc<HADevice>,
+    pub(super) icon: String,
+}
+
+/// HA MQTT update entity. State is a JSON object (installed/latest version,
+/// release summary/url, in_progress); without a `command_topic` HA shows the
+/// update but offers no Install button.
+#[derive(Serialize)]
+pub(super) struct HAUpdate {
+    pub(super) name: String,
+    pub(super) unique_id: String,
+    pub(super) state_topic: String,
+    pub(super) json_attributes_topic: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) command_topic: Option<String>,
+    pub(super) payload_install: &'static str,
+    pub(super) availability_topic: String,
+    pub(super) device_class: &'static str,
+    pub(super) device: Arc<HADevice>,
+    pub(super) icon: String,
+}
+
+/// HA MQTT light, JSON schema: commands and state are JSON objects
+/// (`{"state":"ON","brightness":..,"color":{"r":..}}`).
+#[derive(Serialize)]
+pub(super) struct HALight {
+    pub(super) name: String,
+    pub(super) unique_id: String,
+    pub(super) schema: &'static str,
+    pub(super) state_topic: String,
+    pub(super) command_topic: String,
+    pub(super) availability_topic: String,
+    pub(super) brightness: bool,
+    pub(super) supported_color_modes: Vec<&'static str>,
+    pub(super) device: Arc<HADevice>,
+    pub(super) icon: String,
+}
+
+/// HA MQTT binary sensor. pc-bridge states are lowercase "on"/"off", not
+/// HA's default "ON"/"OFF".
+#[derive(Serialize)]
+pub(super) struct HABinarySensor {
+    pub(super) name: String,
+    pub(super) unique_id: String,
+    pub(super) state_topic: String,
+    /// None for group entities, which outlive any one PC
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) availability_topic: Option<String>,
+    pub(super) payload_on: &'static str,
+    pub(super) payload_off: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) device_class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) expire_after: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) json_attributes_topic: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) entity_category: Option<&'static str>,
+    pub(super) device: Arc<HADevice>,
+    pub(super) icon: String,
 }
 
 /// One entry in HA's multi-source `availability` list.
@@ -57,6 +204,12 @@ pub(super) struct HADevice {
     pub(super) model: String,
     pub(super) manufacturer: String,
     pub(super) sw_version: String,
+    /// Only applied by HA when it first creates the device
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) suggested_area: Option<String>,
+    /// The `group`'s shared device, which this PC is listed under
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) via_device: Option<String>,
 }
 
 /// Pick the right HA `state_class` for a numeric sensor so it ends up in the
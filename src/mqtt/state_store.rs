@@ -0,0 +1,143 @@
+//! Last published state and attributes of every sensor.
+//!
+//! Written on every sensor publish, so it's the one place that knows what HA
+//! should currently be showing. The heartbeat resends from it, the HTTP API
+//! serves it, and the announcement replays all of it after every ConnAck and
+//! HA birth message - a broker restarted without persistence, or an HA that
+//! purged retained messages, gets every sensor back without each one having
+//! to watch `subscribe_reconnect` itself.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+#[derive(Default)]
+struct Entry {
+    /// (value, retained), once a state was published
+    state: Option<(String, bool)>,
+    /// Serialized JSON, once attributes were published (always retained)
+    attributes: Option<Vec<u8>>,
+}
+
+/// `name`'s entry, allocating the key only the first time: this runs on
+/// every publish.
+fn entry<'a>(sensors: &'a mut HashMap<String, Entry>, name: &str) -> &'a mut Entry {
+    if !sensors.contains_key(name) {
+        sensors.insert(name.to_owned(), Entry::default());
+    }
+    sensors.get_mut(name).expect("inserted above")
+}
+
+#[derive(Default)]
+pub(super) struct StateStore {
+    sensors: Mutex<HashMap<String, Entry>>,
+}
+
+impl StateStore {
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.sensors.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(super) fn remember(&self, name: &str, value: &str, retained: bool) {
+        let mut sensors = self.lock();
+        let entry = entry(&mut sensors, name);
+        match &mut entry.state {
+            // Reuse the existing buffer: hot sensors (HWiNFO at 500ms) publish
+            // constantly and this runs on every one.
+            Some((v, r)) => {
+                v.clear();
+                v.push_str(value);
+                *r = retained;
+            }
+            None => entry.state = Some((value.to_owned(), retained)),
+        }
+    }
+
+    pub(super) fn remember_attributes(&self, name: &str, payload: &[u8]) {
+        let mut sensors = self.lock();
+        let entry = entry(&mut sensors, name);
+        match &mut entry.attributes {
+            // Reuse the buffer, like `remember`
+            Some(a) => {
+                a.clear();
+                a.extend_from_slice(payload);
+            }
+            None => entry.attributes = Some(payload.to_vec()),
+        }
+    }
+
+    pub(super) fn forget(&self, name: &str) {
+        self.lock().remove(name);
+    }
+
+    pub(super) fn value(&self, name: &str) -> Option<String> {
+        self.lock()
+            .get(name)
+            .and_then(|e| e.state.as_ref())
+            .map(|(value, _)| value.clone())
+    }
+
+    pub(super) fn values(&self) -> BTreeMap<String, String> {
+        self.lock()
+            .iter()
+            .filter_map(|(name, e)| Some((name.clone(), e.state.as_ref()?.0.clone())))
+            .collect()
+    }
+
+    /// (name, value, retained) for every state
+    pub(super) fn states(&self) -> Vec<(String, String, bool)> {
+        self.lock()
+            .iter()
+            .filter_map(|(name, e)| {
+                let (value, retained) = e.state.as_ref()?;
+                Some((name.clone(), value.clone(), *retained))
+            })
+            .collect()
+    }
+
+    pub(super) fn attributes_of(&self, name: &str) -> Option<Vec<u8>> {
+        self.lock().get(name).and_then(|e| e.attributes.clone())
+    }
+
+    /// (name, payload) for every set of attributes
+    pub(super) fn attributes(&self) -> Vec<(String, Vec<u8>)> {
+        self.lock()
+            .iter()
+            .filter_map(|(name, e)| Some((name.clone(), e.attributes.clone()?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remember_and_forget() {
+        let store = StateStore::default();
+        store.remember("cpu_usage", "12", false);
+        store.remember("cpu_usage", "15", false);
+        store.remember_attributes("runninggames", br#"{"count":0}"#);
+        store.remember("runninggames", "none", true);
+
+        assert_eq!(store.value("cpu_usage").as_deref(), Some("15"));
+        let mut states = store.states();
+        states.sort();
+        assert_eq!(
+            states,
+            vec![
+                ("cpu_usage".to_string(), "15".to_string(), false),
+                ("runninggames".to_string(), "none".to_string(), true),
+            ]
+        );
+        assert_eq!(
+            store.attributes(),
+            vec![("runninggames".to_string(), br#"{"count":0}"#.to_vec())]
+        );
+
+        // A torn-down entity is gone from both.
+        store.forget("runninggames");
+        assert_eq!(store.value("runninggames"), None);
+        assert!(store.attributes().is_empty());
+        assert_eq!(store.values().len(), 1);
+    }
+}
@@ -1,15 +1,17 @@
 //! Topic-string construction.
 //!
-//! HA's MQTT discovery uses a `<prefix>/<component>/<device>/<entity>/<role>`
-//! convention.  All of those strings are built here so the rest of `mqtt::*`
-//! never has to think about the format.  Frequently-published sensors have
-//! their state and attribute topics pre-cached at startup to avoid the
-//! per-publish `format!()` cost.
+//! HA's MQTT discovery uses a `<prefix>/<component>/<device>/<entity>/config`
+//! convention; entity state, attributes and availability live outside the
+//! discovery prefix under `pc-bridge/<device>/...`.  All of those strings are
+//! built here so the rest of `mqtt::*` never has to think about the format.
+//! Frequently-published sensors have their state and attribute topics
+//! pre-cached at startup to avoid the per-publish `format!()` cost.
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use super::{DISCOVERY_PREFIX, MqttClient};
+use super::{DISCOVERY_PREFIX, MqttClient, STATE_PREFIX};
 
 /// Pre-computed topic strings for frequently published sensors.
 pub(super) struct CachedTopics {
@@ -52,25 +54,19 @@ impl CachedTopics {
         for name in sensors {
             sensor_state.insert(
                 *name,
-                Arc::from(format!(
-                    "{}/sensor/{}/{}/state",
-                    DISCOVERY_PREFIX, device_name, name
-                )),
+                Arc::from(format!("{}/{}/{}/state", STATE_PREFIX, device_name, name)),
             );
             sensor_attrs.insert(
                 *name,
                 Arc::from(format!(
-                    "{}/sensor/{}/{}/attributes",
-                    DISCOVERY_PREFIX, device_name, name
+                    "{}/{}/{}/attributes",
+                    STATE_PREFIX, device_name, name
                 )),
             );
         }
 
         Self {
-            availability: Arc::from(format!(
-                "{}/sensor/{}/availability",
-                DISCOVERY_PREFIX, device_name
-            )),
+            availability: Arc::from(MqttClient::availability_topic_static(device_name)),
             sensor_state,
             sensor_attrs,
         }
@@ -79,25 +75,41 @@ impl CachedTopics {
 
 // Topic helpers - split-impl block lives here so callers in mod.rs can
 // continue to use `self.sensor_topic(...)` etc. but the format strings are
-// no longer scattered.  All these return owned String because rumqttc's
-// publish methods consume Into<String>.
+// no longer scattered.  Most return owned String for discovery payloads;
+// the publish path borrows the cached ones (`sensor_topic_ref`).
 impl MqttClient {
     pub(super) fn availability_topic(&self) -> String {
         self.cached_topics.availability.to_string()
     }
 
-    pub(super) fn availability_topic_static(device_name: &str) -> String {
-        format!("{}/sensor/{}/availability", DISCOVERY_PREFIX, device_name)
+    pub fn availability_topic_static(device_name: &str) -> String {
+        format!("{}/{}/availability", STATE_PREFIX, device_name)
+    }
+
+    /// Where each heartbeat also goes, to time the broker round trip.
+    pub(super) fn heartbeat_topic_static(device_name: &str) -> String {
+        format!("{}/{}/heartbeat", STATE_PREFIX, device_name)
+    }
+
+    /// The bundled state document's topic (`mqtt.bundled_state`).
+    pub(super) fn bundle_topic_static(device_name: &str) -> String {
+        format!("{}/{}/state", STATE_PREFIX, device_name)
+    }
+
+    /// State topic of a sensor, without the `MqttClient` (sync sleep publisher).
+    pub fn sensor_topic_static(device_name: &str, name: &str) -> String {
+        format!("{}/{}/{}/state", STATE_PREFIX, device_name, name)
     }
 
     /// Topic published by the HWiNFO sensor task to indicate whether HWiNFO is
     /// currently running. Used by the multi-source `availability` list on each
     /// HWiNFO-backed sensor.
     pub fn hwinfo_availability_topic(&self) -> String {
-        format!(
-            "{}/sensor/{}/hwinfo_availability",
-            DISCOVERY_PREFIX, self.device_name
-        )
+        Self::hwinfo_availability_topic_static(&self.device_name)
+    }
+
+    pub(super) fn hwinfo_availability_topic_static(device_name: &str) -> String {
+        format!("{}/{}/hwinfo_availability", STATE_PREFIX, device_name)
     }
 
     pub(super) fn sensor_topic(&self, name: &str) -> String {
@@ -105,10 +117,7 @@ impl MqttClient {
         if let Some(cached) = self.cached_topics.sensor_state.get(name) {
             return cached.to_string();
         }
-        format!(
-            "{}/sensor/{}/{}/state",
-            DISCOVERY_PREFIX, self.device_name, name
-        )
+        Self::sensor_topic_static(&self.device_name, name)
     }
 
     pub(super) fn sensor_attributes_topic(&self, name: &str) -> String {
@@ -116,10 +125,70 @@ impl MqttClient {
         if let Some(cached) = self.cached_topics.sensor_attrs.get(name) {
             return cached.to_string();
         }
-        format!(
-            "{}/sensor/{}/{}/attributes",
-            DISCOVERY_PREFIX, self.device_name, name
-        )
+        Self::sensor_attributes_topic_static(&self.device_name, name)
+    }
+
+    /// `sensor_topic` for the publish path: borrowed from the cache for the
+    /// frequently published sensors, so a publish doesn't allocate it.
+    pub(super) fn sensor_topic_ref(&self, name: &str) -> Cow<'_, str> {
+        match self.cached_topics.sensor_state.get(name) {
+            Some(cached) => Cow::Borrowed(cached),
+            None => Cow::Owned(Self::sensor_topic_static(&self.device_name, name)),
+        }
+    }
+
+    /// `sensor_attributes_topic` for the publish path, like `sensor_topic_ref`.
+    pub(super) fn sensor_attributes_topic_ref(&self, name: &str) -> Cow<'_, str> {
+        match self.cached_topics.sensor_attrs.get(name) {
+            Some(cached) => Cow::Borrowed(cached),
+            None => Cow::Owned(Self::sensor_attributes_topic_static(
+                &self.device_name,
+                name,
+            )),
+        }
+    }
+
+    pub(super) fn sensor_attributes_topic_static(device_name: &str, name: &str) -> String {
+        format!("{}/{}/{}/attributes", STATE_PREFIX, device_name, name)
+    }
+
+    /// Non-retained discrete events (game started, woke up, ...):
+    /// `pc-bridge/events/<device>`. See `crate::events`.
+    pub(super) fn event_topic(&self) -> String {
+        format!("pc-bridge/events/{}", self.device_name)
+    }
+
+    /// Non-retained process watchdog alerts: `pc-bridge/alerts/<device>`.
+    /// Outside the discovery prefix - these are events, not entity state.
+    pub(super) fn process_alert_topic(&self) -> String {
+        format!("pc-bridge/alerts/{}", self.device_name)
+    }
+
+    /// Non-retained outcomes of shell commands (exit code, or killed on
+    /// timeout): `pc-bridge/results/<device>`.
+    pub(super) fn command_result_topic(&self) -> String {
+        format!("pc-bridge/results/{}", self.device_name)
+    }
+
+    /// Non-retained `BackupConfig` output: `pc-bridge/config/<device>`.
+    pub(super) fn config_backup_topic(&self) -> String {
+        format!("pc-bridge/config/{}", self.device_name)
+    }
+
+    /// Pre-migration (`TOPIC_LAYOUT` 0) retained topics under the discovery
+    /// prefix: availability, HWiNFO availability, and the given sensors' state
+    /// and attributes. Only used to clear them once after upgrading.
+    pub(super) fn legacy_state_topics(device_name: &str, sensors: &[String]) -> Vec<String> {
+        let base = format!("{}/sensor/{}", DISCOVERY_PREFIX, device_name);
+        let mut topics = vec![
+            format!("{base}/availability"),
+            format!("{base}/hwinfo_availability"),
+        ];
+        for name in sensors {
+            topics.push(format!("{base}/{name}/state"));
+            topics.push(format!("{base}/{name}/attributes"));
+        }
+        topics
     }
 
     pub(super) fn command_topic(&self, name: &str) -> String {
@@ -134,9 +203,19 @@ impl MqttClient {
     /// `component` is the HA MQTT discovery component (`sensor`, `button`,
     /// `notify`, etc.).  `name` is the entity's discovery name.
     pub(super) fn config_topic(&self, component: &str, name: &str) -> String {
+        Self::config_topic_static(&self.device_name, component, name)
+    }
+
+    pub(super) fn config_topic_static(device_name: &str, component: &str, name: &str) -> String {
         format!(
             "{}/{}/{}/{}/config",
-            DISCOVERY_PREFIX, component, self.device_name, name
+            DISCOVERY_PREFIX, component, device_name, name
         )
     }
+
+    /// The notify service's device-level config topic (3 segments, not the
+    /// per-entity shape).
+    pub(super) fn notify_config_topic_static(device_name: &str) -> String {
+        format!("{}/notify/{}/config", DISCOVERY_PREFIX, device_name)
+    }
 }
@@ -3,11 +3,37 @@
 //! Replaces the old PowerShell approach with native Windows APIs for:
 //! - ~10ms instead of 200-500ms latency
 //! - No PowerShell process spawn overhead
-//! - Proper app identity support
+//! - Proper app identity: toasts show as `notifications.app_name` ("PC
+//!   Bridge"), or a payload's own `app_name` (see `crate::toast_identity`)
+//!
+//! A payload can carry a click target (`action_url`, or an HA dashboard `path`
+//! resolved against `ha_api.url`). Windows uses protocol activation, so the
+//! shell opens the URL even when the toast is clicked later from the Action
+//! Center, after this process has moved on; Linux uses a notify-send action.
+//!
+//! With `notifications.suppress_when_busy`, toasts arriving while the user is
+//! busy (Focus Assist, fullscreen, a game - see `NotificationConfig`) are
+//! counted on the `notifications_suppressed` sensor and, with
+//! `queue_suppressed`, shown once they're no longer busy.
+//!
+//! The last `notifications.history_size` payloads received, and what became of
+//! them, are kept in `notification_history.json` in the log directory and
+//! published as the `history` attribute of the `last_notification` sensor;
+//! the `ClearNotificationHistory` button empties it.
 #![allow(dead_code)] // Used on Windows only
 
-#[cfg(windows)]
-use log::debug;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::AppState;
+use crate::textutil::truncate_chars;
+
 #[cfg(windows)]
 use windows::{
     Data::Xml::Dom::XmlDocument,
@@ -23,22 +49,475 @@ pub struct NotificationPayload {
     pub title: String,
     #[serde(default)]
     pub message: String,
+    /// http(s) URL opened when the notification is clicked
+    #[serde(default, alias = "url", alias = "clickAction")]
+    pub action_url: Option<String>,
+    /// HA dashboard path (e.g. `/lovelace/cameras`), opened under `ha_api.url`
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Show even while the user is busy (Focus Assist, fullscreen, gaming)
+    #[serde(default)]
+    pub critical: bool,
+    /// Name to show the toast under instead of `notifications.app_name`
+    #[serde(default)]
+    pub app_name: Option<String>,
 }
 
 impl NotificationPayload {
     /// Parse notification payload from JSON or plain text
     pub fn from_payload(payload: &str) -> Self {
         serde_json::from_str(payload).unwrap_or_else(|_| Self {
-            title: String::new(),
             message: payload.to_string(),
+            ..Self::default()
         })
     }
+
+    /// URL to open on click. Only http(s) - anyone who can publish to the
+    /// notify topic must not be able to launch arbitrary protocol handlers.
+    pub fn click_target(&self, ha_url: &str) -> Option<String> {
+        let is_web = |u: &str| u.starts_with("https://") || u.starts_with("http://");
+        if let Some(url) = self.action_url.as_deref().map(str::trim)
+            && is_web(url)
+        {
+            return Some(url.to_string());
+        }
+        let path = self.path.as_deref().map(str::trim)?;
+        let base = ha_url.trim().trim_end_matches('/');
+        (path.starts_with('/') && !path.starts_with("//") && is_web(base))
+            .then(|| format!("{base}{path}"))
+    }
+
+    /// Name to show the toast under: its own `app_name`, else `default`
+    pub fn app_name(&self, default: &str) -> String {
+        self.app_name
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .unwrap_or(default)
+            .to_string()
+    }
+}
+
+/// `notifications.app_name`, set by `set_app_name`
+static APP_NAME: Mutex<String> = Mutex::new(String::new());
+
+fn default_app_name() -> String {
+    let name = APP_NAME.lock().unwrap_or_else(|e| e.into_inner());
+    if name.is_empty() {
+        "PC Bridge".to_string()
+    } else {
+        name.clone()
+    }
+}
+
+/// Apply `notifications.app_name`; on Windows that registers it as the app
+/// identity toasts show under (see `crate::toast_identity`).
+pub(crate) async fn set_app_name(name: &str) {
+    let name = match name.trim() {
+        "" => "PC Bridge".to_string(),
+        name => name.to_string(),
+    };
+    {
+        let mut current = APP_NAME.lock().unwrap_or_else(|e| e.into_inner());
+        if *current == name {
+            return;
+        }
+        current.clone_from(&name);
+    }
+    #[cfg(windows)]
+    let _ = tokio::task::spawn_blocking(move || crate::toast_identity::register(&name)).await;
+}
+
+/// Toasts held back while busy; the oldest is dropped past this.
+const MAX_QUEUED: usize = 20;
+
+/// How often a non-empty queue re-checks whether the user is still busy.
+const QUEUE_POLL: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct Suppressed {
+    /// Total since the agent started
+    count: u64,
+    last_reason: &'static str,
+    /// Raw payloads waiting for the user to be free, oldest first
+    queue: VecDeque<String>,
+}
+
+static SUPPRESSED: Mutex<Suppressed> = Mutex::new(Suppressed {
+    count: 0,
+    last_reason: "",
+    queue: VecDeque::new(),
+});
+
+/// Set by the game sensor; a running game counts as busy.
+static GAME_RUNNING: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_game_running(running: bool) {
+    GAME_RUNNING.store(running, Ordering::Relaxed);
+}
+
+/// Why toasts should wait right now, if they should. Blocking (may shell out).
+fn busy_reason() -> Option<&'static str> {
+    if crate::focus::is_active() {
+        return Some("focus");
+    }
+    if GAME_RUNNING.load(Ordering::Relaxed) {
+        return Some("game");
+    }
+    desktop_busy()
+}
+
+#[cfg(windows)]
+fn desktop_busy() -> Option<&'static str> {
+    use windows::Win32::UI::Shell::{
+        QUNS_BUSY, QUNS_PRESENTATION_MODE, QUNS_QUIET_TIME, QUNS_RUNNING_D3D_FULL_SCREEN,
+        SHQueryUserNotificationState,
+    };
+
+    if focus_assist_profile().is_some_and(|profile| profile != 0) {
+        return Some("focus_assist");
+    }
+    match unsafe { SHQueryUserNotificationState() }.ok()? {
+        QUNS_BUSY | QUNS_RUNNING_D3D_FULL_SCREEN => Some("fullscreen"),
+        QUNS_PRESENTATION_MODE => Some("presentation"),
+        QUNS_QUIET_TIME => Some("quiet_time"),
+        _ => None,
+    }
+}
+
+/// Active Focus Assist profile: 0 off, 1 priority only, 2 alarms only. There
+/// is no public API; this is the WNF state the Action Center itself reads.
+#[cfg(windows)]
+fn focus_assist_profile() -> Option<u32> {
+    /// WNF_SHEL_QUIETHOURS_ACTIVE_PROFILE_CHANGED
+    const STATE_NAME: u64 = 0x0D83_063E_A3BF_1C75;
+
+    #[link(name = "ntdll")]
+    unsafe extern "system" {
+        fn NtQueryWnfStateData(
+            state_name: *const u64,
+            type_id: *const core::ffi::c_void,
+            explicit_scope: *const core::ffi::c_void,
+            change_stamp: *mut u32,
+            buffer: *mut core::ffi::c_void,
+            buffer_size: *mut u32,
+        ) -> i32;
+    }
+
+    let state_name = STATE_NAME;
+    let mut profile = 0u32;
+    let mut size = 4u32;
+    let mut stamp = 0u32;
+    let status = unsafe {
+        NtQueryWnfStateData(
+            &raw const state_name,
+            std::ptr::null(),
+            std::ptr::null(),
+            &raw mut stamp,
+            (&raw mut profile).cast(),
+            &raw mut size,
+        )
+    };
+    (status >= 0 && size == 4).then_some(profile)
+}
+
+/// GNOME's Do Not Disturb (other desktops' daemons apply their own DND to
+/// notify-send anyway; this only decides whether to count and queue).
+#[cfg(not(windows))]
+fn desktop_busy() -> Option<&'static str> {
+    let out = std::process::Command::new("gsettings")
+        .args(["get", "org.gnome.desktop.notifications", "show-banners"])
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    (out.status.success() && String::from_utf8_lossy(&out.stdout).trim() == "false")
+        .then_some("do_not_disturb")
+}
+
+/// Show a notify-topic payload, or - with `suppress_when_busy` and a busy
+/// user - count it (and queue it, with `queue_suppressed`) instead.
+pub(crate) async fn deliver(state: &AppState, payload: String) {
+    let (policy, ha_url) = {
+        let config = state.config.read().await;
+        (config.notifications.clone(), config.ha_api.url.clone())
+    };
+    let notif = NotificationPayload::from_payload(&payload);
+    if (policy.suppress_when_busy || crate::focus::is_active())
+        && !notif.critical
+        && let Ok(Some(reason)) = tokio::task::spawn_blocking(busy_reason).await
+    {
+        debug!("Notification suppressed ({reason})");
+        let status = if policy.queue_suppressed {
+            "queued"
+        } else {
+            "suppressed"
+        };
+        record(&notif, &payload, status, policy.history_size);
+        suppress(payload, reason, policy.queue_suppressed);
+        publish_suppressed(state).await;
+        publish_history(state).await;
+        return;
+    }
+    let shown = show_in_background(payload.clone(), ha_url).await;
+    let status = if shown { "shown" } else { "failed" };
+    record(&notif, &payload, status, policy.history_size);
+    publish_history(state).await;
+}
+
+fn suppress(payload: String, reason: &'static str, queue: bool) {
+    let mut suppressed = SUPPRESSED.lock().unwrap_or_else(|e| e.into_inner());
+    suppressed.count += 1;
+    suppressed.last_reason = reason;
+    if queue {
+        if suppressed.queue.len() >= MAX_QUEUED {
+            suppressed.queue.pop_front();
+        }
+        suppressed.queue.push_back(payload);
+    }
+}
+
+/// Toast APIs / notify-send block; keep them off the runtime. False if the
+/// toast couldn't be shown.
+pub(crate) async fn show_in_background(payload: String, ha_url: String) -> bool {
+    match tokio::task::spawn_blocking(move || show_toast(&payload, &ha_url)).await {
+        Ok(Ok(())) => true,
+        Ok(Err(e)) => {
+            warn!("Failed to show notification: {e}");
+            false
+        }
+        Err(_) => false,
+    }
+}
+
+async fn publish_suppressed(state: &AppState) {
+    let (count, attrs) = {
+        let suppressed = SUPPRESSED.lock().unwrap_or_else(|e| e.into_inner());
+        (
+            suppressed.count,
+            serde_json::json!({
+                "queued": suppressed.queue.len(),
+                "last_reason": suppressed.last_reason,
+            }),
+        )
+    };
+    state
+        .mqtt
+        .publish_sensor_retained("notifications_suppressed", &count.to_string())
+        .await;
+    state
+        .mqtt
+        .publish_sensor_attributes("notifications_suppressed", &attrs)
+        .await;
+}
+
+const HISTORY_FILE: &str = "notification_history.json";
+
+/// HA caps sensor state at 255 characters.
+const STATE_MAX: usize = 255;
+
+/// One received notification, as kept in the history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct HistoryEntry {
+    /// RFC 3339, UTC
+    time: String,
+    title: String,
+    message: String,
+    /// "shown", "failed", "suppressed", or "queued" until a queued toast is
+    /// shown
+    status: String,
+}
+
+/// Newest last. `None` until first used, then loaded from disk.
+static HISTORY: Mutex<Option<VecDeque<HistoryEntry>>> = Mutex::new(None);
+
+fn history_path() -> PathBuf {
+    crate::logging::log_dir().join(HISTORY_FILE)
+}
+
+fn load_history() -> VecDeque<HistoryEntry> {
+    std::fs::read_to_string(history_path())
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_history(history: &VecDeque<HistoryEntry>) {
+    let path = history_path();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    match serde_json::to_string_pretty(history) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write(&path, json) {
+                warn!("Failed to write {}: {e}", path.display());
+            }
+        }
+        Err(e) => warn!("Failed to serialize notification history: {e}"),
+    }
+}
+
+/// Run `f` on the (loaded) history and persist it afterwards.
+fn with_history<R>(f: impl FnOnce(&mut VecDeque<HistoryEntry>) -> R) -> R {
+    let mut guard = HISTORY.lock().unwrap_or_else(|e| e.into_inner());
+    let history = guard.get_or_insert_with(load_history);
+    let result = f(history);
+    save_history(history);
+    result
+}
+
+fn push_entry(history: &mut VecDeque<HistoryEntry>, entry: HistoryEntry, limit: usize) {
+    history.push_back(entry);
+    while history.len() > limit {
+        history.pop_front();
+    }
+}
+
+fn record(notif: &NotificationPayload, payload: &str, status: &str, limit: usize) {
+    let message = if notif.message.is_empty() {
+        payload
+    } else {
+        &notif.message
+    };
+    let entry = HistoryEntry {
+        time: time::OffsetDateTime::now_utc()
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap_or_default(),
+        title: notif.title.clone(),
+        message: message.to_string(),
+        status: status.to_string(),
+    };
+    with_history(|history| push_entry(history, entry, limit));
+}
+
+/// Empty the history (the `ClearNotificationHistory` button).
+pub(crate) async fn clear_history(state: &AppState) {
+    with_history(VecDeque::clear);
+    info!("Notification history cleared");
+    publish_history(state).await;
+}
+
+/// Sensor state for the newest entry: its title, else its message.
+fn history_state(history: &VecDeque<HistoryEntry>) -> String {
+    let Some(last) = history.back() else {
+        return "none".to_string();
+    };
+    let text = if last.title.is_empty() {
+        &last.message
+    } else {
+        &last.title
+    };
+    truncate_chars(text, STATE_MAX)
+}
+
+/// Resolve the "queued" history entries once the queue has been shown;
+/// `shown` holds each queued toast's outcome, oldest first. The queue and the
+/// queued entries share an order, but either may have lost its oldest ones
+/// (`MAX_QUEUED`, `history_size`), so they're matched from the newest end.
+/// A queued entry without a toast was dropped from the full queue.
+fn settle_queued(history: &mut VecDeque<HistoryEntry>, shown: &[bool]) {
+    let mut outcomes = shown.iter().rev();
+    for entry in history.iter_mut().rev().filter(|e| e.status == "queued") {
+        entry.status = match outcomes.next() {
+            Some(true) => "shown",
+            Some(false) => "failed",
+            None => "suppressed",
+        }
+        .to_string();
+    }
+}
+
+async fn publish_history(state: &AppState) {
+    let (value, attrs) = {
+        let guard = HISTORY.lock().unwrap_or_else(|e| e.into_inner());
+        let empty = VecDeque::new();
+        let history = guard.as_ref().unwrap_or(&empty);
+        (
+            history_state(history),
+            serde_json::json!({
+                "count": history.len(),
+                // Newest first, as HA shows attribute lists top-down.
+                "history": history.iter().rev().collect::<Vec<_>>(),
+            }),
+        )
+    };
+    state
+        .mqtt
+        .publish_sensor_retained("last_notification", &value)
+        .await;
+    state
+        .mqtt
+        .publish_sensor_attributes("last_notification", &attrs)
+        .await;
 }
 
-/// Show a native Windows toast notification
+/// Publishes the suppressed counter and shows queued toasts once the user is
+/// no longer busy (or suppression has been turned off). Supervised with the
+/// `notifications` feature.
+pub(crate) async fn run(state: Arc<AppState>) {
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut reconnect_rx = state.mqtt.subscribe_reconnect();
+    let mut tick = tokio::time::interval(QUEUE_POLL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let limit = state.config.read().await.notifications.history_size;
+    with_history(|history| {
+        while history.len() > limit {
+            history.pop_front();
+        }
+    });
+    publish_suppressed(&state).await;
+    publish_history(&state).await;
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => break,
+            Ok(()) = reconnect_rx.recv() => {
+                publish_suppressed(&state).await;
+                publish_history(&state).await;
+            }
+            _ = tick.tick() => {
+                if SUPPRESSED.lock().unwrap_or_else(|e| e.into_inner()).queue.is_empty() {
+                    continue;
+                }
+                let (policy, ha_url) = {
+                    let config = state.config.read().await;
+                    (config.notifications.clone(), config.ha_api.url.clone())
+                };
+                if (policy.suppress_when_busy || crate::focus::is_active())
+                    && !matches!(tokio::task::spawn_blocking(busy_reason).await, Ok(None))
+                {
+                    continue;
+                }
+                let queued = std::mem::take(
+                    &mut SUPPRESSED.lock().unwrap_or_else(|e| e.into_inner()).queue,
+                );
+                info!("Showing {} queued notification(s)", queued.len());
+                let mut shown = Vec::with_capacity(queued.len());
+                for payload in queued {
+                    shown.push(show_in_background(payload, ha_url.clone()).await);
+                }
+                with_history(|history| settle_queued(history, &shown));
+                publish_suppressed(&state).await;
+                publish_history(&state).await;
+            }
+        }
+    }
+}
+
+/// Show a native Windows toast notification. `ha_url` resolves dashboard
+/// `path` click targets.
 #[cfg(windows)]
-pub fn show_toast(payload: &str) -> anyhow::Result<()> {
+pub fn show_toast(payload: &str, ha_url: &str) -> anyhow::Result<()> {
     let notif = NotificationPayload::from_payload(payload);
+    let launch = notif
+        .click_target(ha_url)
+        .map(|url| {
+            format!(
+                r#" launch="{}" activationType="protocol""#,
+                escape_xml(&url)
+            )
+        })
+        .unwrap_or_default();
 
     let title = if notif.title.is_empty() {
         "Home Assistant"
@@ -57,7 +536,7 @@ pub fn show_toast(payload: &str) -> anyhow::Result<()> {
 
     // Build toast XML template
     let toast_xml = format!(
-        r#"<toast>
+        r#"<toast{}>
             <visual>
                 <binding template="ToastText02">
                     <text id="1">{}</text>
@@ -65,7 +544,7 @@ pub fn show_toast(payload: &str) -> anyhow::Result<()> {
                 </binding>
             </visual>
         </toast>"#,
-        title, message
+        launch, title, message
     );
 
     // Initialize COM on this thread (STA) - the WinRT calls below (XmlDocument,
@@ -85,10 +564,11 @@ pub fn show_toast(payload: &str) -> anyhow::Result<()> {
     // Create toast notification
     let toast = ToastNotification::CreateToastNotification(&xml_doc)?;
 
-    // Use PowerShell's AUMID as app identity (works without app registration)
-    let app_id = HSTRING::from(
-        "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\WindowsPowerShell\\v1.0\\powershell.exe",
-    );
+    // Registered "PC Bridge" identity (or the payload's own app_name);
+    // PowerShell's until registration has worked.
+    let app_id = HSTRING::from(crate::toast_identity::aumid_for(
+        &notif.app_name(&default_app_name()),
+    ));
     let notifier = ToastNotificationManager::CreateToastNotifierWithId(&app_id)?;
 
     notifier.Show(&toast)?;
@@ -99,7 +579,7 @@ pub fn show_toast(payload: &str) -> anyhow::Result<()> {
 
 /// Show notification on Linux using notify-send
 #[cfg(not(windows))]
-pub fn show_toast(payload: &str) -> anyhow::Result<()> {
+pub fn show_toast(payload: &str, ha_url: &str) -> anyhow::Result<()> {
     use std::process::Command;
 
     let notif = NotificationPayload::from_payload(payload);
@@ -114,15 +594,19 @@ pub fn show_toast(payload: &str) -> anyhow::Result<()> {
         &notif.message
     };
 
+    let app_name = notif.app_name(&default_app_name());
+    let app_name_arg = format!("--app-name={app_name}");
+
+    if let Some(url) = notif.click_target(ha_url)
+        && show_with_action(&app_name_arg, title, message, &url)
+    {
+        return Ok(());
+    }
+
     // Try notify-send (available on most Linux desktops).  .status() waits
     // and reaps the child; .spawn() alone would leak zombies on Linux.
     let result = Command::new("notify-send")
-        .args([
-            "--app-name=PC Bridge",
-            "--icon=dialog-information",
-            title,
-            message,
-        ])
+        .args([&app_name_arg, "--icon=dialog-information", title, message])
         .status();
 
     // A non-zero exit is a failure too, not just a missing binary, so fall
@@ -140,7 +624,7 @@ pub fn show_toast(payload: &str) -> anyhow::Result<()> {
             "--dest=org.freedesktop.Notifications",
             "--object-path=/org/freedesktop/Notifications",
             "--method=org.freedesktop.Notifications.Notify",
-            "PC Bridge",          // app_name
+            &app_name,            // app_name
             "0",                  // replaces_id
             "dialog-information", // icon
             title,
@@ -166,6 +650,48 @@ pub fn show_toast(payload: &str) -> anyhow::Result<()> {
     ))
 }
 
+/// notify-send with a default action (libnotify 0.7.9+). With an action it
+/// waits until the notification is clicked or closed, so a watcher thread
+/// opens the URL on click. False if this notify-send can't do actions (it
+/// exits at once with an error), so the caller falls back to a plain one.
+#[cfg(not(windows))]
+fn show_with_action(app_name_arg: &str, title: &str, message: &str, url: &str) -> bool {
+    use std::process::{Command, Stdio};
+    use std::time::Duration;
+
+    let Ok(mut child) = Command::new("notify-send")
+        .args([
+            app_name_arg,
+            "--icon=dialog-information",
+            "--action=default=Open",
+            title,
+            message,
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return false;
+    };
+    std::thread::sleep(Duration::from_millis(200));
+    if let Ok(Some(status)) = child.try_wait()
+        && !status.success()
+    {
+        return false;
+    }
+    log::debug!("Notification sent via notify-send (click opens {url})");
+    let url = url.to_string();
+    std::thread::spawn(move || {
+        // wait_with_output reaps the child; stdout is the chosen action key.
+        if let Ok(out) = child.wait_with_output()
+            && String::from_utf8_lossy(&out.stdout).trim() == "default"
+        {
+            let _ = Command::new("xdg-open").arg(&url).status();
+        }
+    });
+    true
+}
+
 /// Escape XML special characters and strip control chars
 fn escape_xml(s: &str) -> String {
     let mut result = String::with_capacity(s.len());
@@ -205,6 +731,131 @@ mod tests {
         assert_eq!(payload.message, "Just a plain message");
     }
 
+    #[test]
+    fn test_click_target() {
+        let p = NotificationPayload::from_payload(
+            r#"{"message": "m", "action_url": "https://example.com/cam"}"#,
+        );
+        assert_eq!(
+            p.click_target("").as_deref(),
+            Some("https://example.com/cam")
+        );
+        // HA's `url` key works too; dashboard paths need an HA base URL.
+        let p = NotificationPayload::from_payload(r#"{"url": "http://ha.local:8123/x"}"#);
+        assert_eq!(
+            p.click_target("").as_deref(),
+            Some("http://ha.local:8123/x")
+        );
+        let p = NotificationPayload::from_payload(r#"{"path": "/lovelace/cameras"}"#);
+        assert_eq!(
+            p.click_target("http://homeassistant.local:8123/")
+                .as_deref(),
+            Some("http://homeassistant.local:8123/lovelace/cameras")
+        );
+        assert_eq!(p.click_target(""), None);
+
+        // Non-web schemes and protocol-relative paths are refused.
+        for json in [
+            r#"{"action_url": "file:///C:/Windows/System32/calc.exe"}"#,
+            r#"{"action_url": "ms-settings:"}"#,
+            r#"{"path": "//evil.example"}"#,
+        ] {
+            let p = NotificationPayload::from_payload(json);
+            assert_eq!(p.click_target("http://ha.local"), None, "{json}");
+        }
+        assert_eq!(
+            NotificationPayload::from_payload("plain").click_target("x"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_critical_flag() {
+        assert!(
+            NotificationPayload::from_payload(r#"{"message": "m", "critical": true}"#).critical
+        );
+        assert!(!NotificationPayload::from_payload(r#"{"message": "m"}"#).critical);
+        assert!(!NotificationPayload::from_payload("plain").critical);
+    }
+
+    #[test]
+    fn test_app_name_override() {
+        let p = NotificationPayload::from_payload(r#"{"message": "m", "app_name": "Frigate"}"#);
+        assert_eq!(p.app_name("PC Bridge"), "Frigate");
+        let blank = NotificationPayload::from_payload(r#"{"message": "m", "app_name": " "}"#);
+        assert_eq!(blank.app_name("PC Bridge"), "PC Bridge");
+        assert_eq!(
+            NotificationPayload::from_payload("plain").app_name("PC Bridge"),
+            "PC Bridge"
+        );
+    }
+
+    #[test]
+    fn test_suppress_counts_and_caps_queue() {
+        for i in 0..MAX_QUEUED + 3 {
+            suppress(format!("n{i}"), "game", true);
+        }
+        suppress("dropped".to_string(), "fullscreen", false);
+        let suppressed = SUPPRESSED.lock().unwrap();
+        assert_eq!(suppressed.count, MAX_QUEUED as u64 + 4);
+        assert_eq!(suppressed.last_reason, "fullscreen");
+        // Oldest are dropped; unqueued ones only count.
+        assert_eq!(suppressed.queue.len(), MAX_QUEUED);
+        assert_eq!(suppressed.queue.front().map(String::as_str), Some("n3"));
+        assert!(!suppressed.queue.contains(&"dropped".to_string()));
+    }
+
+    fn entry(title: &str, message: &str) -> HistoryEntry {
+        HistoryEntry {
+            time: String::new(),
+            title: title.to_string(),
+            message: message.to_string(),
+            status: "shown".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_history_keeps_newest() {
+        let mut history = VecDeque::new();
+        for i in 0..5 {
+            push_entry(&mut history, entry(&format!("t{i}"), "m"), 3);
+        }
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.front().map(|e| e.title.as_str()), Some("t2"));
+        push_entry(&mut history, entry("x", "m"), 0);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn test_settle_queued() {
+        let mut history: VecDeque<_> = ["dropped", "a", "b", "c"]
+            .into_iter()
+            .map(|title| HistoryEntry {
+                status: "queued".to_string(),
+                ..entry(title, "m")
+            })
+            .collect();
+        history.push_back(entry("live", "m"));
+        settle_queued(&mut history, &[true, false, true]);
+        let statuses: Vec<_> = history.iter().map(|e| e.status.as_str()).collect();
+        assert_eq!(
+            statuses,
+            ["suppressed", "shown", "failed", "shown", "shown"]
+        );
+    }
+
+    #[test]
+    fn test_history_state() {
+        let mut history = VecDeque::new();
+        assert_eq!(history_state(&history), "none");
+        history.push_back(entry("Doorbell", "Someone is at the door"));
+        assert_eq!(history_state(&history), "Doorbell");
+        history.push_back(entry("", "plain text"));
+        assert_eq!(history_state(&history), "plain text");
+        history.push_back(entry(&"a".repeat(300), ""));
+        assert_eq!(history_state(&history).chars().count(), STATE_MAX);
+    }
+
     #[test]
     fn test_xml_escaping() {
         assert_eq!(escape_xml("Hello & World"), "Hello &amp; World");
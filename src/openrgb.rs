@@ -0,0 +1,371 @@
+//! OpenRGB client: case/keyboard lighting as an HA light
+//!
+//! With `features.openrgb` the agent registers an MQTT JSON-schema light
+//! (`light.<device>_rgb`) whose commands arrive as the `Rgb` command. Each
+//! command connects to the OpenRGB SDK server (`openrgb.host`/`port`, the
+//! "SDK Server" tab in OpenRGB), puts every controller in its direct mode and
+//! sets all LEDs to the color scaled by brightness; off is all black. The
+//! light's state is what was last applied, published retained, since OpenRGB
+//! has no notion of on/off to read back.
+//!
+//! Protocol: OpenRGB's network SDK, version 0 (the server assumes it unless
+//! the client negotiates higher). Every packet is a 16-byte header - `ORGB`,
+//! then device index, packet id and data length as little-endian u32 - and
+//! its data.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::AppState;
+
+const MAGIC: &[u8; 4] = b"ORGB";
+const REQUEST_CONTROLLER_COUNT: u32 = 0;
+const REQUEST_CONTROLLER_DATA: u32 = 1;
+const SET_CLIENT_NAME: u32 = 50;
+const UPDATE_LEDS: u32 = 1050;
+const SET_CUSTOM_MODE: u32 = 1100;
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// What the light was last set to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Light {
+    on: bool,
+    brightness: u8,
+    rgb: [u8; 3],
+}
+
+static LIGHT: Mutex<Light> = Mutex::new(Light {
+    on: false,
+    brightness: 255,
+    rgb: [255, 255, 255],
+});
+
+/// HA's JSON-schema light command. Everything is optional: a brightness or
+/// color change alone also turns the light on.
+#[derive(Deserialize)]
+struct LightCommand {
+    state: Option<String>,
+    brightness: Option<u8>,
+    color: Option<Rgb>,
+}
+
+#[derive(Deserialize)]
+struct Rgb {
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+impl Light {
+    /// The light after `payload` (the JSON command, or a bare ON/OFF).
+    fn apply(self, payload: &str) -> Result<Self> {
+        let payload = payload.trim();
+        let cmd: LightCommand = if payload.starts_with('{') {
+            serde_json::from_str(payload).context("invalid light command")?
+        } else {
+            LightCommand {
+                state: Some(payload.to_string()),
+                brightness: None,
+                color: None,
+            }
+        };
+        let mut next = self;
+        next.on = match cmd.state.as_deref().map(str::to_ascii_uppercase).as_deref() {
+            Some("OFF") => false,
+            Some("ON") | None => true,
+            Some(other) => bail!("unknown light state '{other}'"),
+        };
+        if let Some(brightness) = cmd.brightness {
+            next.brightness = brightness;
+        }
+        if let Some(c) = cmd.color {
+            next.rgb = [c.r, c.g, c.b];
+        }
+        Ok(next)
+    }
+
+    /// The LED color: the color scaled by brightness, black when off.
+    fn led_color(self) -> [u8; 3] {
+        if !self.on {
+            return [0; 3];
+        }
+        self.rgb
+            .map(|c| (u16::from(c) * u16::from(self.brightness) / 255) as u8)
+    }
+
+    /// JSON-schema light state for the state topic.
+    fn state_json(self) -> String {
+        serde_json::json!({
+            "state": if self.on { "ON" } else { "OFF" },
+            "brightness": self.brightness,
+            "color_mode": "rgb",
+            "color": { "r": self.rgb[0], "g": self.rgb[1], "b": self.rgb[2] },
+        })
+        .to_string()
+    }
+}
+
+/// The `Rgb` command: apply an HA light command to every OpenRGB device.
+pub(crate) async fn run(payload: &str, state: &AppState) {
+    let (host, port) = {
+        let config = state.config.read().await;
+        (config.openrgb.host.clone(), config.openrgb.port)
+    };
+    let current = *LIGHT.lock().unwrap_or_else(|e| e.into_inner());
+    let next = match current.apply(payload) {
+        Ok(next) => next,
+        Err(e) => {
+            warn!("Rgb: {e:#}");
+            return;
+        }
+    };
+    let color = next.led_color();
+    match tokio::task::spawn_blocking(move || set_all(&host, port, color)).await {
+        Ok(Ok(_)) => {
+            *LIGHT.lock().unwrap_or_else(|e| e.into_inner()) = next;
+            state
+                .mqtt
+                .publish_sensor_retained("rgb", &next.state_json())
+                .await;
+        }
+        Ok(Err(e)) => warn!("Rgb: {e:#}"),
+        Err(e) => warn!("Rgb: {e}"),
+    }
+}
+
+/// Set every LED of every controller to `color`. Blocking. Returns how many
+/// controllers were set.
+fn set_all(host: &str, port: u16, color: [u8; 3]) -> Result<u32> {
+    let mut client =
+        Client::connect(host, port).with_context(|| format!("OpenRGB at {host}:{port}"))?;
+    let count = client.controller_count()?;
+    for index in 0..count {
+        let leds = client.led_count(index)?;
+        client.send(index, SET_CUSTOM_MODE, &[])?;
+        client.send(index, UPDATE_LEDS, &update_leds(leds, color))?;
+    }
+    if count > 0 {
+        info!("OpenRGB: set {count} device(s) to {color:?}");
+    }
+    Ok(count)
+}
+
+/// UPDATE_LEDS data: total size, LED count, then one R,G,B,pad per LED.
+fn update_leds(leds: u16, color: [u8; 3]) -> Vec<u8> {
+    let size = 4 + 2 + 4 * usize::from(leds);
+    let mut data = Vec::with_capacity(size);
+    data.extend_from_slice(&(size as u32).to_le_bytes());
+    data.extend_from_slice(&leds.to_le_bytes());
+    for _ in 0..leds {
+        data.extend_from_slice(&[color[0], color[1], color[2], 0]);
+    }
+    data
+}
+
+struct Client {
+    stream: TcpStream,
+}
+
+impl Client {
+    fn connect(host: &str, port: u16) -> Result<Self> {
+        let addr = (host, port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| anyhow!("no address for {host}"))?;
+        let stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        let mut client = Self { stream };
+        client.send(0, SET_CLIENT_NAME, b"PC Bridge\0")?;
+        Ok(client)
+    }
+
+    fn send(&mut self, device: u32, packet: u32, data: &[u8]) -> Result<()> {
+        let mut buf = Vec::with_capacity(16 + data.len());
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&device.to_le_bytes());
+        buf.extend_from_slice(&packet.to_le_bytes());
+        buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        buf.extend_from_slice(data);
+        self.stream.write_all(&buf)?;
+        Ok(())
+    }
+
+    /// Data of the next `packet` reply, skipping unrelated notifications.
+    fn recv(&mut self, packet: u32) -> Result<Vec<u8>> {
+        loop {
+            let mut header = [0u8; 16];
+            self.stream.read_exact(&mut header)?;
+            if &header[..4] != MAGIC {
+                bail!("not an OpenRGB SDK server");
+            }
+            let id = u32::from_le_bytes(header[8..12].try_into()?);
+            let len = u32::from_le_bytes(header[12..16].try_into()?) as usize;
+            let mut data = vec![0u8; len];
+            self.stream.read_exact(&mut data)?;
+            if id == packet {
+                return Ok(data);
+            }
+        }
+    }
+
+    fn controller_count(&mut self) -> Result<u32> {
+        self.send(0, REQUEST_CONTROLLER_COUNT, &[])?;
+        let data = self.recv(REQUEST_CONTROLLER_COUNT)?;
+        Reader::new(&data).u32()
+    }
+
+    fn led_count(&mut self, index: u32) -> Result<u16> {
+        self.send(index, REQUEST_CONTROLLER_DATA, &[])?;
+        let data = self.recv(REQUEST_CONTROLLER_DATA)?;
+        led_count(&data).context("malformed controller data")
+    }
+}
+
+/// Little-endian cursor over a packet's data.
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| anyhow!("truncated at byte {}", self.pos))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    /// Length-prefixed (u16) string, skipped.
+    fn skip_string(&mut self) -> Result<()> {
+        let len = self.u16()?;
+        self.take(usize::from(len))?;
+        Ok(())
+    }
+}
+
+/// LED count from a version-0 controller description: walk past the strings,
+/// modes, zones and LEDs to the trailing color list, one entry per LED.
+fn led_count(data: &[u8]) -> Result<u16> {
+    let mut r = Reader::new(data);
+    r.u32()?; // data size
+    r.u32()?; // device type
+    for _ in 0..5 {
+        r.skip_string()?; // name, description, version, serial, location
+    }
+    let modes = r.u16()?;
+    r.u32()?; // active mode
+    for _ in 0..modes {
+        r.skip_string()?;
+        // value, flags, speed min/max, colors min/max, speed, direction, color mode
+        r.take(9 * 4)?;
+        let colors = r.u16()?;
+        r.take(4 * usize::from(colors))?;
+    }
+    let zones = r.u16()?;
+    for _ in 0..zones {
+        r.skip_string()?;
+        r.take(4 * 4)?; // type, leds min/max/count
+        let matrix = r.u16()?;
+        r.take(usize::from(matrix))?;
+    }
+    let leds = r.u16()?;
+    for _ in 0..leds {
+        r.skip_string()?;
+        r.u32()?; // value
+    }
+    r.u16()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_light_command() {
+        let off = Light {
+            on: false,
+            brightness: 255,
+            rgb: [255, 255, 255],
+        };
+        let red = off
+            .apply(r#"{"state":"ON","color":{"r":255,"g":0,"b":0}}"#)
+            .unwrap();
+        assert_eq!((red.on, red.rgb), (true, [255, 0, 0]));
+        // Brightness alone turns it on and keeps the color.
+        let dim = red.apply(r#"{"brightness":51}"#).unwrap();
+        assert_eq!(dim.led_color(), [51, 0, 0]);
+        let dark = dim.apply("OFF").unwrap();
+        assert_eq!(dark.led_color(), [0, 0, 0]);
+        // Back on restores the previous color and brightness.
+        assert_eq!(dark.apply(r#"{"state":"ON"}"#).unwrap(), dim);
+        assert!(off.apply(r#"{"state":"BLINK"}"#).is_err());
+        assert!(off.apply("{not json").is_err());
+    }
+
+    #[test]
+    fn test_led_count_from_controller_data() {
+        fn string(buf: &mut Vec<u8>, s: &str) {
+            buf.extend_from_slice(&(s.len() as u16 + 1).to_le_bytes());
+            buf.extend_from_slice(s.as_bytes());
+            buf.push(0);
+        }
+        let mut d = Vec::new();
+        d.extend_from_slice(&0u32.to_le_bytes()); // size (unchecked)
+        d.extend_from_slice(&5u32.to_le_bytes()); // type: keyboard
+        for s in ["Keyboard", "desc", "1.0", "", "HID: /dev/hidraw0"] {
+            string(&mut d, s);
+        }
+        d.extend_from_slice(&1u16.to_le_bytes()); // modes
+        d.extend_from_slice(&0u32.to_le_bytes()); // active mode
+        string(&mut d, "Direct");
+        d.extend_from_slice(&[0; 36]);
+        d.extend_from_slice(&1u16.to_le_bytes()); // mode colors
+        d.extend_from_slice(&[0; 4]);
+        d.extend_from_slice(&1u16.to_le_bytes()); // zones
+        string(&mut d, "Keys");
+        d.extend_from_slice(&[0; 16]);
+        d.extend_from_slice(&12u16.to_le_bytes()); // matrix: h, w, 1 entry
+        d.extend_from_slice(&[0; 12]);
+        d.extend_from_slice(&3u16.to_le_bytes()); // LEDs
+        for name in ["Esc", "F1", "F2"] {
+            string(&mut d, name);
+            d.extend_from_slice(&0u32.to_le_bytes());
+        }
+        d.extend_from_slice(&3u16.to_le_bytes()); // colors
+        d.extend_from_slice(&[0; 12]);
+
+        assert_eq!(led_count(&d).unwrap(), 3);
+        assert!(led_count(&d[..40]).is_err());
+    }
+
+    #[test]
+    fn test_update_leds_packet() {
+        let data = update_leds(2, [1, 2, 3]);
+        assert_eq!(data.len(), 14);
+        assert_eq!(u32::from_le_bytes(data[..4].try_into().unwrap()), 14);
+        assert_eq!(u16::from_le_bytes(data[4..6].try_into().unwrap()), 2);
+        assert_eq!(&data[6..], &[1, 2, 3, 0, 1, 2, 3, 0]);
+    }
+}
@@ -0,0 +1,501 @@
+//! External plugins: programs that add their own sensors and commands
+//!
+//! Each `plugins` entry is started with piped stdio and speaks JSON lines,
+//! one object per line, tagged by `type`:
+//!
+//! - plugin → bridge `register`: `{"type":"register","sensors":[{"name","unit",
+//!   "icon","device_class"}],"commands":[{"name","icon"}]}`. Re-sending it
+//!   replaces the previous set; entities left out are removed from HA.
+//! - plugin → bridge `state`: `{"type":"state","sensor","value","attributes"}`,
+//!   for a registered sensor. `value` may be any JSON scalar.
+//! - plugin → bridge `result`: `{"type":"result","command","result","error"}`,
+//!   published to the command result topic.
+//! - bridge → plugin `command`: `{"type":"command","command","payload"}` when
+//!   one of its buttons is pressed.
+//!
+//! Entities are named `plugin_<plugin>_<name>` (plugin names have no `_`).
+//! Stderr lines go to the log. A plugin that exits has its sensors marked
+//! unavailable and its buttons removed, and is restarted after
+//! `RESTART_DELAY`; on shutdown it is killed along with the bridge's task.
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+
+use crate::AppState;
+use crate::config::PluginConfig;
+
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// Wait before restarting a plugin that exited
+const RESTART_DELAY: Duration = Duration::from_secs(10);
+
+/// A sensor a plugin registered
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct PluginSensor {
+    pub name: String,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub device_class: Option<String>,
+}
+
+/// A command (HA button) a plugin registered
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct PluginCommand {
+    pub name: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+/// A line from a plugin's stdout
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    Register {
+        #[serde(default)]
+        sensors: Vec<PluginSensor>,
+        #[serde(default)]
+        commands: Vec<PluginCommand>,
+    },
+    State {
+        sensor: String,
+        value: serde_json::Value,
+        #[serde(default)]
+        attributes: Option<serde_json::Value>,
+    },
+    Result {
+        command: String,
+        result: String,
+        #[serde(default)]
+        error: Option<String>,
+    },
+}
+
+/// Where a plugin command goes: its own name and the plugin's stdin queue
+struct Route {
+    plugin: String,
+    command: String,
+    stdin: mpsc::Sender<String>,
+}
+
+/// Command name (`plugin_<plugin>_<command>`) → route, for every running
+/// plugin's registered commands.
+static ROUTES: Mutex<Option<HashMap<String, Route>>> = Mutex::new(None);
+
+fn routes() -> std::sync::MutexGuard<'static, Option<HashMap<String, Route>>> {
+    ROUTES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Entity (and command) name of a plugin's sensor or command
+pub(crate) fn entity_name(plugin: &str, name: &str) -> String {
+    format!("plugin_{plugin}_{name}")
+}
+
+/// Entity names become topic levels, so the same rules as custom sensors.
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(char::is_whitespace) && !name.contains(['/', '+', '#'])
+}
+
+/// Names in `old` but not in `new`
+fn removed<'a>(
+    old: impl Iterator<Item = &'a String>,
+    new: impl Iterator<Item = &'a String> + Clone,
+) -> Vec<String> {
+    old.filter(|name| !new.clone().any(|n| n == *name))
+        .cloned()
+        .collect()
+}
+
+/// A state value as published: strings as-is, other scalars as JSON.
+fn state_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The line sent to a plugin for a button press
+fn command_line(command: &str, payload: &str) -> String {
+    serde_json::json!({
+        "type": "command",
+        "command": command,
+        "payload": payload,
+    })
+    .to_string()
+}
+
+/// Hand a command to the plugin that registered it. False when no running
+/// plugin owns `name`, so the executor keeps resolving it.
+pub(crate) async fn dispatch(name: &str, payload: &str) -> bool {
+    let Some((plugin, line, stdin)) = routes().as_ref().and_then(|routes| {
+        let route = routes.get(name)?;
+        Some((
+            route.plugin.clone(),
+            command_line(&route.command, payload),
+            route.stdin.clone(),
+        ))
+    }) else {
+        return false;
+    };
+    if stdin.send(line).await.is_err() {
+        warn!("Plugin '{plugin}' exited before it could take '{name}'");
+    }
+    true
+}
+
+/// Run every configured plugin until shutdown.
+pub async fn run(state: Arc<AppState>) {
+    let plugins = state.config.read().await.plugins.clone();
+    // Dropping the set (supervisor cancel) aborts the tasks, and with them
+    // their kill_on_drop children.
+    let mut tasks = JoinSet::new();
+    for plugin in plugins {
+        tasks.spawn(supervise(Arc::clone(&state), plugin));
+    }
+    while tasks.join_next().await.is_some() {}
+}
+
+/// Keep one plugin running, restarting it whenever it exits.
+async fn supervise(state: Arc<AppState>, plugin: PluginConfig) {
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    loop {
+        let mut session = Session {
+            state: Arc::clone(&state),
+            plugin: plugin.name.clone(),
+            sensors: Vec::new(),
+            commands: Vec::new(),
+        };
+        tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => {
+                session.close().await;
+                return;
+            }
+            r = session.run(&plugin) => {
+                match r {
+                    Ok(status) => warn!("Plugin '{}' exited ({status})", plugin.name),
+                    Err(e) => warn!("Plugin '{}' failed: {e:#}", plugin.name),
+                }
+            }
+        }
+        session.close().await;
+
+        tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => return,
+            () = tokio::time::sleep(RESTART_DELAY) => {
+                info!("Restarting plugin '{}'", plugin.name);
+            }
+        }
+    }
+}
+
+/// One run of a plugin process and what it registered
+struct Session {
+    state: Arc<AppState>,
+    plugin: String,
+    sensors: Vec<PluginSensor>,
+    commands: Vec<PluginCommand>,
+}
+
+impl Session {
+    /// Run the plugin until it exits or closes stdout.
+    async fn run(&mut self, config: &PluginConfig) -> anyhow::Result<std::process::ExitStatus> {
+        let mut command = Command::new(&config.command[0]);
+        command
+            .args(&config.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(windows)]
+        command.creation_flags(CREATE_NO_WINDOW);
+        let mut child = command.spawn()?;
+        info!("Plugin '{}' started (PID {:?})", self.plugin, child.id());
+
+        let (Some(mut stdin), Some(stdout), Some(stderr)) =
+            (child.stdin.take(), child.stdout.take(), child.stderr.take())
+        else {
+            anyhow::bail!("stdio not piped");
+        };
+        let mut stdout = BufReader::new(stdout).lines();
+        let mut stderr = BufReader::new(stderr).lines();
+        let mut stderr_open = true;
+        let (stdin_tx, mut stdin_rx) = mpsc::channel::<String>(16);
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+
+        loop {
+            tokio::select! {
+                line = stdout.next_line() => match line {
+                    Ok(Some(line)) => self.handle(&line, &stdin_tx).await,
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("Plugin '{}': reading stdout failed: {e}", self.plugin);
+                        break;
+                    }
+                },
+                line = stderr.next_line(), if stderr_open => match line {
+                    Ok(Some(line)) => info!("[plugin {}] {}", self.plugin, line),
+                    _ => stderr_open = false,
+                },
+                Some(line) = stdin_rx.recv() => {
+                    let written = async {
+                        stdin.write_all(line.as_bytes()).await?;
+                        stdin.write_all(b"\n").await?;
+                        stdin.flush().await
+                    };
+                    if let Err(e) = written.await {
+                        warn!("Plugin '{}': writing a command failed: {e}", self.plugin);
+                    }
+                }
+                // A broker that lost its retained discovery configs must get
+                // the plugin's entities back; states come from the store.
+                Ok(()) = reconnect_rx.recv() => self.register().await,
+            }
+        }
+
+        // Stdout closed: it is exiting (or no longer talking to us).
+        drop(stdin);
+        match tokio::time::timeout(Duration::from_secs(5), child.wait()).await {
+            Ok(status) => Ok(status?),
+            Err(_) => {
+                child.kill().await?;
+                Ok(child.wait().await?)
+            }
+        }
+    }
+
+    async fn handle(&mut self, line: &str, stdin: &mpsc::Sender<String>) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        let message = match serde_json::from_str::<Message>(line) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!(
+                    "Plugin '{}': ignoring invalid line ({e}): {line}",
+                    self.plugin
+                );
+                return;
+            }
+        };
+        match message {
+            Message::Register { sensors, commands } => {
+                self.unroute();
+                let plugin = &self.plugin;
+                let sensors: Vec<PluginSensor> = sensors
+                    .into_iter()
+                    .filter(|s| {
+                        valid_name(&s.name) || {
+                            warn!("Plugin '{plugin}': invalid sensor name '{}'", s.name);
+                            false
+                        }
+                    })
+                    .collect();
+                let commands: Vec<PluginCommand> = commands
+                    .into_iter()
+                    .filter(|c| {
+                        valid_name(&c.name) || {
+                            warn!("Plugin '{plugin}': invalid command name '{}'", c.name);
+                            false
+                        }
+                    })
+                    .collect();
+                let removed_sensors = removed(
+                    self.sensors.iter().map(|s| &s.name),
+                    sensors.iter().map(|s| &s.name),
+                );
+                let removed_commands = removed(
+                    self.commands.iter().map(|c| &c.name),
+                    commands.iter().map(|c| &c.name),
+                );
+                self.state
+                    .mqtt
+                    .clear_plugin_entities(&self.plugin, &removed_sensors, &removed_commands)
+                    .await;
+                self.sensors = sensors;
+                self.commands = commands;
+                self.register().await;
+                let mut routes = routes();
+                let routes = routes.get_or_insert_with(HashMap::new);
+                for command in &self.commands {
+                    routes.insert(
+                        entity_name(&self.plugin, &command.name),
+                        Route {
+                            plugin: self.plugin.clone(),
+                            command: command.name.clone(),
+                            stdin: stdin.clone(),
+                        },
+                    );
+                }
+            }
+            Message::State {
+                sensor,
+                value,
+                attributes,
+            } => {
+                if !self.sensors.iter().any(|s| s.name == sensor) {
+                    warn!(
+                        "Plugin '{}': state for unregistered sensor '{sensor}'",
+                        self.plugin
+                    );
+                    return;
+                }
+                let name = entity_name(&self.plugin, &sensor);
+                self.state
+                    .mqtt
+                    .publish_sensor(&name, &state_value(&value))
+                    .await;
+                if let Some(attributes) = attributes {
+                    self.state
+                        .mqtt
+                        .publish_sensor_attributes(&name, &attributes)
+                        .await;
+                }
+            }
+            Message::Result {
+                command,
+                result,
+                error,
+            } => {
+                self.state
+                    .mqtt
+                    .publish_command_result(&serde_json::json!({
+                        "command": entity_name(&self.plugin, &command),
+                        "result": result,
+                        "error": error,
+                    }))
+                    .await;
+            }
+        }
+    }
+
+    async fn register(&self) {
+        if self.sensors.is_empty() && self.commands.is_empty() {
+            return;
+        }
+        debug!(
+            "Plugin '{}': registering {} sensor(s), {} command(s)",
+            self.plugin,
+            self.sensors.len(),
+            self.commands.len()
+        );
+        self.state
+            .mqtt
+            .register_plugin(&self.plugin, &self.sensors, &self.commands)
+            .await;
+    }
+
+    /// Stop routing this plugin's commands.
+    fn unroute(&self) {
+        if let Some(routes) = routes().as_mut() {
+            routes.retain(|_, route| route.plugin != self.plugin);
+        }
+    }
+
+    /// The process is gone: drop its routes and buttons, and mark its
+    /// sensors unavailable.
+    async fn close(&mut self) {
+        self.unroute();
+        for sensor in std::mem::take(&mut self.sensors) {
+            self.state
+                .mqtt
+                .publish_sensor(&entity_name(&self.plugin, &sensor.name), "unavailable")
+                .await;
+        }
+        let commands: Vec<String> = std::mem::take(&mut self.commands)
+            .into_iter()
+            .map(|c| c.name)
+            .collect();
+        self.state
+            .mqtt
+            .clear_plugin_entities(&self.plugin, &[], &commands)
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_messages() {
+        let register: Message = serde_json::from_str(
+            r#"{"type":"register","sensors":[{"name":"temp","unit":"°C"}],"commands":[{"name":"refresh"}]}"#,
+        )
+        .unwrap();
+        let Message::Register { sensors, commands } = register else {
+            panic!("expected register");
+        };
+        assert_eq!(sensors[0].name, "temp");
+        assert_eq!(sensors[0].unit.as_deref(), Some("°C"));
+        assert_eq!(commands[0].name, "refresh");
+
+        let state: Message =
+            serde_json::from_str(r#"{"type":"state","sensor":"temp","value":21.5}"#).unwrap();
+        let Message::State {
+            value, attributes, ..
+        } = state
+        else {
+            panic!("expected state");
+        };
+        assert_eq!(state_value(&value), "21.5");
+        assert!(attributes.is_none());
+        assert_eq!(state_value(&serde_json::json!("on")), "on");
+
+        assert!(serde_json::from_str::<Message>(r#"{"type":"bogus"}"#).is_err());
+    }
+
+    #[test]
+    fn test_removed() {
+        let names = |list: &[&str]| list.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let (old, new) = (names(&["temp", "rain", "wind"]), names(&["wind", "uv"]));
+        assert_eq!(removed(old.iter(), new.iter()), ["temp", "rain"]);
+        assert!(removed(new.iter(), new.iter()).is_empty());
+    }
+
+    #[test]
+    fn test_names_and_command_line() {
+        assert_eq!(entity_name("weather", "temp"), "plugin_weather_temp");
+        assert!(valid_name("temp_2"));
+        assert!(!valid_name(""));
+        assert!(!valid_name("a b"));
+        assert!(!valid_name("a/b"));
+
+        let line: serde_json::Value =
+            serde_json::from_str(&command_line("refresh", "now")).unwrap();
+        assert_eq!(
+            line,
+            serde_json::json!({"type": "command", "command": "refresh", "payload": "now"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_routes_registered_commands() {
+        let (tx, mut rx) = mpsc::channel(1);
+        routes().get_or_insert_with(HashMap::new).insert(
+            entity_name("test", "ping"),
+            Route {
+                plugin: "test".to_string(),
+                command: "ping".to_string(),
+                stdin: tx,
+            },
+        );
+        assert!(dispatch("plugin_test_ping", "").await);
+        assert_eq!(rx.recv().await.unwrap(), command_line("ping", ""));
+        assert!(!dispatch("plugin_test_pong", "").await);
+    }
+}
@@ -0,0 +1,281 @@
+//! Display mode (resolution + refresh rate) read + switch
+//!
+//! - Windows: EnumDisplaySettings / ChangeDisplaySettingsEx on the primary
+//!   display; the requested rate is matched to the closest one the display
+//!   supports at that resolution (59 vs 60, 143 vs 144).
+//! - Linux: `xrandr` on the primary output (X11; under Wayland xrandr only
+//!   sees XWayland and can't switch).
+//!
+//! Modes are addressed by their configured name (`display_modes`, the
+//! select's options) and resolved to width/height/rate here.
+
+use crate::config::DisplayMode;
+
+/// What the primary display currently runs at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Resolution {
+    pub width: u32,
+    pub height: u32,
+    /// Hz, rounded
+    pub refresh_rate: u32,
+}
+
+impl std::fmt::Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}@{}", self.width, self.height, self.refresh_rate)
+    }
+}
+
+/// The configured mode the display is in. A mode without a rate matches any
+/// rate; otherwise within 1 Hz, since drivers round 59.94/143.97 differently.
+pub(crate) fn matching(modes: &[DisplayMode], current: Resolution) -> Option<&DisplayMode> {
+    modes.iter().find(|m| {
+        m.width == current.width
+            && m.height == current.height
+            && m.refresh_rate
+                .is_none_or(|hz| hz.abs_diff(current.refresh_rate) <= 1)
+    })
+}
+
+/// Switch to the configured mode named `name` (case-insensitive); returns
+/// its name as configured.
+pub(crate) async fn set_by_name(modes: &[DisplayMode], name: &str) -> Result<String, String> {
+    let mode = modes
+        .iter()
+        .find(|m| m.name.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| format!("unknown display mode '{}'", name.trim()))?;
+    set(mode).await?;
+    Ok(mode.name.clone())
+}
+
+/// Current mode of the primary display, or `None` if it can't be read.
+#[cfg(windows)]
+pub(crate) async fn current() -> Option<Resolution> {
+    tokio::task::spawn_blocking(win::current)
+        .await
+        .ok()
+        .flatten()
+}
+
+#[cfg(windows)]
+async fn set(mode: &DisplayMode) -> Result<(), String> {
+    let mode = mode.clone();
+    tokio::task::spawn_blocking(move || win::set(&mode))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(unix)]
+pub(crate) async fn current() -> Option<Resolution> {
+    let out = super::plan::run("xrandr", &["--current"]).await.ok()?;
+    parse_xrandr(&out).map(|(_, current)| current)
+}
+
+#[cfg(unix)]
+async fn set(mode: &DisplayMode) -> Result<(), String> {
+    let out = super::plan::run("xrandr", &["--current"]).await?;
+    let (output, _) = parse_xrandr(&out).ok_or("no connected display found by xrandr")?;
+    let size = format!("{}x{}", mode.width, mode.height);
+    let mut args = vec!["--output", output.as_str(), "--mode", size.as_str()];
+    // xrandr picks the mode whose rate is closest to the one asked for.
+    let rate = mode.refresh_rate.map(|hz| hz.to_string());
+    if let Some(rate) = &rate {
+        args.extend(["--rate", rate.as_str()]);
+    }
+    super::plan::run("xrandr", &args).await.map(drop)
+}
+
+/// Of the rates a display offers at a resolution, the one closest to
+/// `wanted`.
+#[cfg_attr(unix, allow(dead_code))]
+fn closest_rate(rates: impl IntoIterator<Item = u32>, wanted: u32) -> Option<u32> {
+    rates.into_iter().min_by_key(|hz| hz.abs_diff(wanted))
+}
+
+/// The primary (else first) connected output in `xrandr --current` output,
+/// and its current mode: the mode line whose rate is starred, e.g.
+/// `   2560x1440    143.97*+  59.95`.
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_xrandr(output: &str) -> Option<(String, Resolution)> {
+    let mut found: Option<(String, Resolution)> = None;
+    let mut output_name: Option<(String, bool)> = None;
+    for line in output.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            // Output header: "DP-1 connected primary 2560x1440+0+0 (...)"
+            let mut words = line.split_whitespace();
+            output_name = match (words.next(), words.next()) {
+                (Some(name), Some("connected")) => {
+                    Some((name.to_string(), words.next() == Some("primary")))
+                }
+                _ => None,
+            };
+            continue;
+        }
+        let Some((name, primary)) = &output_name else {
+            continue;
+        };
+        let mut words = line.split_whitespace();
+        let Some((width, height)) = words.next().and_then(|size| {
+            let (w, h) = size.split_once('x')?;
+            Some((w.parse().ok()?, h.trim_end_matches('i').parse().ok()?))
+        }) else {
+            continue;
+        };
+        let Some(rate) = words.find(|w| w.contains('*')) else {
+            continue;
+        };
+        let Ok(rate) = rate.trim_end_matches(['*', '+']).parse::<f64>() else {
+            continue;
+        };
+        let current = Resolution {
+            width,
+            height,
+            refresh_rate: rate.round() as u32,
+        };
+        if *primary {
+            return Some((name.clone(), current));
+        }
+        found.get_or_insert((name.clone(), current));
+    }
+    found
+}
+
+#[cfg(windows)]
+mod win {
+    use super::{DisplayMode, Resolution, closest_rate};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::Graphics::Gdi::{
+        CDS_UPDATEREGISTRY, ChangeDisplaySettingsExW, DEVMODEW, DISP_CHANGE_SUCCESSFUL,
+        DM_DISPLAYFREQUENCY, DM_PELSHEIGHT, DM_PELSWIDTH, ENUM_CURRENT_SETTINGS,
+        ENUM_DISPLAY_SETTINGS_MODE, EnumDisplaySettingsW,
+    };
+    use windows::core::PCWSTR;
+
+    fn enum_settings(mode: ENUM_DISPLAY_SETTINGS_MODE) -> Option<DEVMODEW> {
+        let mut dm = DEVMODEW {
+            dmSize: size_of::<DEVMODEW>() as u16,
+            ..Default::default()
+        };
+        // Null device name: the primary display.
+        unsafe { EnumDisplaySettingsW(PCWSTR::null(), mode, &raw mut dm) }
+            .as_bool()
+            .then_some(dm)
+    }
+
+    pub(super) fn current() -> Option<Resolution> {
+        let dm = enum_settings(ENUM_CURRENT_SETTINGS)?;
+        Some(Resolution {
+            width: dm.dmPelsWidth,
+            height: dm.dmPelsHeight,
+            refresh_rate: dm.dmDisplayFrequency,
+        })
+    }
+
+    pub(super) fn set(mode: &DisplayMode) -> Result<(), String> {
+        let rates: Vec<u32> = (0..)
+            .map_while(|i| enum_settings(ENUM_DISPLAY_SETTINGS_MODE(i)))
+            .filter(|dm| dm.dmPelsWidth == mode.width && dm.dmPelsHeight == mode.height)
+            .map(|dm| dm.dmDisplayFrequency)
+            .collect();
+        if rates.is_empty() {
+            return Err(format!(
+                "the display doesn't support {}x{}",
+                mode.width, mode.height
+            ));
+        }
+        let mut dm = DEVMODEW {
+            dmSize: size_of::<DEVMODEW>() as u16,
+            dmPelsWidth: mode.width,
+            dmPelsHeight: mode.height,
+            dmFields: DM_PELSWIDTH | DM_PELSHEIGHT,
+            ..Default::default()
+        };
+        if let Some(hz) = mode.refresh_rate.and_then(|hz| closest_rate(rates, hz)) {
+            dm.dmDisplayFrequency = hz;
+            dm.dmFields |= DM_DISPLAYFREQUENCY;
+        }
+        let result = unsafe {
+            ChangeDisplaySettingsExW(
+                PCWSTR::null(),
+                Some(&raw const dm),
+                HWND::default(),
+                CDS_UPDATEREGISTRY,
+                None,
+            )
+        };
+        if result == DISP_CHANGE_SUCCESSFUL {
+            Ok(())
+        } else {
+            Err(format!("ChangeDisplaySettingsEx failed ({})", result.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mode(name: &str, width: u32, height: u32, refresh_rate: Option<u32>) -> DisplayMode {
+        DisplayMode {
+            name: name.to_string(),
+            width,
+            height,
+            refresh_rate,
+        }
+    }
+
+    #[test]
+    fn test_matching_mode() {
+        let modes = [
+            mode("4K60", 3840, 2160, Some(60)),
+            mode("1440p144", 2560, 1440, Some(144)),
+            mode("1080p", 1920, 1080, None),
+        ];
+        let res = |width, height, refresh_rate| Resolution {
+            width,
+            height,
+            refresh_rate,
+        };
+        assert_eq!(matching(&modes, res(3840, 2160, 59)).unwrap().name, "4K60");
+        assert_eq!(
+            matching(&modes, res(2560, 1440, 143)).unwrap().name,
+            "1440p144"
+        );
+        assert!(matching(&modes, res(2560, 1440, 60)).is_none());
+        assert_eq!(
+            matching(&modes, res(1920, 1080, 240)).unwrap().name,
+            "1080p"
+        );
+        assert_eq!(res(2560, 1440, 60).to_string(), "2560x1440@60");
+    }
+
+    #[test]
+    fn test_closest_rate() {
+        assert_eq!(closest_rate([60, 59, 144, 120], 143), Some(144));
+        assert_eq!(closest_rate([60, 59], 60), Some(60));
+        assert_eq!(closest_rate([], 60), None);
+    }
+
+    #[test]
+    fn test_parse_xrandr() {
+        let out = "Screen 0: minimum 8 x 8, current 6400 x 2160, maximum 32767 x 32767\n\
+                   HDMI-1 connected 2560x1440+3840+0 (normal left inverted right x axis y axis) 597mm x 336mm\n   \
+                   2560x1440     59.95*+\n   \
+                   1920x1080     60.00    50.00\n\
+                   DP-1 connected primary 3840x2160+0+0 (normal left inverted right x axis y axis) 600mm x 340mm\n   \
+                   3840x2160     60.00 +  30.00\n   \
+                   2560x1440    143.97*   59.95\n\
+                   DP-2 disconnected (normal left inverted right x axis y axis)\n";
+        let (output, current) = parse_xrandr(out).unwrap();
+        assert_eq!(output, "DP-1");
+        assert_eq!(current.to_string(), "2560x1440@144");
+
+        // No primary: the first connected output.
+        let no_primary = out.replace(" primary", "");
+        let (output, current) = parse_xrandr(&no_primary).unwrap();
+        assert_eq!(output, "HDMI-1");
+        assert_eq!(current.to_string(), "2560x1440@60");
+
+        assert!(parse_xrandr("Screen 0: minimum 8 x 8\nDP-1 disconnected\n").is_none());
+    }
+}
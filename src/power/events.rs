@@ -11,13 +11,18 @@
 //! broker before `wnd_proc` returns. The async event loop cannot provide
 //! this guarantee because Windows may suspend the NIC before the tokio
 //! runtime flushes the message to TCP.
+//!
+//! The same path covers an OS shutdown or restart: on `WM_ENDSESSION` the
+//! window holds a shutdown block reason while it publishes `sleep_state`
+//! "off" and availability "offline", so HA sees a planned power-down.
 
 use log::{debug, error, info, warn};
 use std::sync::Arc;
-use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
 use tokio::sync::mpsc;
 use windows::Win32::Foundation::{HANDLE, HWND, LPARAM, LRESULT, WPARAM};
 use windows::Win32::System::Power::RegisterPowerSettingNotification;
+use windows::Win32::System::Shutdown::{ShutdownBlockReasonCreate, ShutdownBlockReasonDestroy};
 use windows::Win32::UI::WindowsAndMessaging::{
     CreateWindowExW, DEVICE_NOTIFY_WINDOW_HANDLE, DefWindowProcW, DestroyWindow, DispatchMessageW,
     GWLP_USERDATA, GetMessageW, GetWindowLongPtrW, MSG, PostMessageW, RegisterClassExW,
@@ -25,14 +30,17 @@ use windows::Win32::UI::WindowsAndMessaging::{
 };
 
 use super::display::wake_display_with_retry;
-use super::sync_mqtt::{SyncMqttConfig, parse_broker_url, sync_mqtt_publish_sleep};
+use super::sync_mqtt::{SyncMqttConfig, sync_mqtt_publish_power_down, sync_mqtt_publish_sleep};
 use crate::AppState;
+use crate::events::BridgeEvent;
 
 const WM_POWERBROADCAST: u32 = 0x218;
 const PBT_APMSUSPEND: usize = 4;
 const PBT_APMRESUMEAUTO: usize = 0x12;
 const PBT_APMRESUMESUSPEND: usize = 7;
 const PBT_POWERSETTINGCHANGE: usize = 0x8013;
+const WM_QUERYENDSESSION: u32 = 0x11;
+const WM_ENDSESSION: u32 = 0x16;
 
 /// GUID_CONSOLE_DISPLAY_STATE: {6FE69556-704A-47A0-8F24-C28D936FDA47}
 /// Data values: 0 = off, 1 = on, 2 = dimmed
@@ -85,6 +93,19 @@ fn try_transition_to_awake() -> bool {
         .is_ok()
 }
 
+/// PBT_APMSUSPENDs seen, so a Sleep command can tell a suspend from a
+/// SetSuspendState that returned without one.
+static SUSPENDS: AtomicU64 = AtomicU64::new(0);
+/// Whether the power monitor window is up to count them
+static LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// Suspends seen so far; None while no power monitor window is listening.
+pub(crate) fn suspend_count() -> Option<u64> {
+    LISTENING
+        .load(Ordering::Acquire)
+        .then(|| SUSPENDS.load(Ordering::Acquire))
+}
+
 pub struct PowerEventListener {
     state: Arc<AppState>,
 }
@@ -101,24 +122,7 @@ impl PowerEventListener {
         // Build sync MQTT config for the power-events thread.
         // This lets wnd_proc publish the sleep message over a dedicated TCP
         // connection, independent of the async event loop.
-        let sync_mqtt = {
-            let config = self.state.config.read().await;
-            let broker = &config.mqtt.broker;
-            let (host, port, use_tls) = parse_broker_url(broker);
-            SyncMqttConfig {
-                host,
-                port,
-                use_tls,
-                user: config.mqtt.user.clone(),
-                pass: config.mqtt.pass.clone(),
-                // Use a distinct client_id so the broker doesn't kick our main connection
-                client_id: format!("{}-sleep", config.client_id()),
-                sleep_topic: format!(
-                    "homeassistant/sensor/{}/sleep_state/state",
-                    config.device_name
-                ),
-            }
-        };
+        let sync_mqtt = SyncMqttConfig::for_device(&*self.state.config.read().await);
 
         // Spawn blocking thread for Windows message pump
         // Store hwnd so we can post WM_QUIT on shutdown
@@ -176,7 +180,7 @@ impl PowerEventListener {
                             // Fallback publish via the async client. Harmless if the
                             // sync TCP publish already landed (retained = last-write-wins).
                             // Catches cases where sync fails (TLS broker, Modern Standby, etc.).
-                            self.state.mqtt.publish_sensor_retained("sleep_state", "sleeping").await;
+                            self.state.mqtt.announce_power_down("sleeping").await;
                         }
                         PowerEvent::Wake => {
                             info!("Power event: WAKE");
@@ -188,8 +192,20 @@ impl PowerEventListener {
                             // Publish wake state with retries in background task
                             // so the event handler stays responsive to new events
                             let mqtt = &self.state.mqtt;
+                            // The suspend published "offline" (see announce_power_down)
+                            mqtt.publish_availability(true).await;
                             mqtt.publish_sensor_retained("sleep_state", "awake").await;
                             info!("Published awake state");
+                            mqtt.publish_event(&BridgeEvent::ResumedFromSleep).await;
+                            let state = Arc::clone(&self.state);
+                            tokio::spawn(async move {
+                                let source = super::wake_source::last_wake().await;
+                                info!("Woken by {source:?}");
+                                state
+                                    .mqtt
+                                    .publish_sensor_attributes("sleep_state", &source.attributes())
+                                    .await;
+                            });
                             let state = Arc::clone(&self.state);
                             tokio::spawn(async move {
                                 for delay_secs in [2, 5, 10] {
@@ -200,10 +216,12 @@ impl PowerEventListener {
                         }
                         PowerEvent::DisplayOff => {
                             info!("Power event: DISPLAY OFF");
+                            crate::activity::set_display_off(true);
                             self.state.mqtt.publish_sensor_retained("display", "off").await;
                         }
                         PowerEvent::DisplayOn => {
                             info!("Power event: DISPLAY ON");
+                            crate::activity::set_display_off(false);
                             self.state.mqtt.publish_sensor_retained("display", "on").await;
                         }
                     }
@@ -270,6 +288,7 @@ impl PowerEventListener {
             let ctx_ptr = Box::into_raw(ctx);
             SetWindowLongPtrW(hwnd, GWLP_USERDATA, ctx_ptr as isize);
 
+            LISTENING.store(true, Ordering::Release);
             info!("Power event listener started (hwnd: {:?})", hwnd);
 
             // Send hwnd back so async side can post WM_USER to unblock GetMessageW
@@ -292,6 +311,7 @@ impl PowerEventListener {
             }
 
             // Cleanup
+            LISTENING.store(false, Ordering::Release);
             let _ = Box::from_raw(ctx_ptr);
             let _ = DestroyWindow(hwnd);
         }
@@ -313,6 +333,7 @@ impl PowerEventListener {
                     match wparam.0 {
                         PBT_APMSUSPEND => {
                             debug!("Received PBT_APMSUSPEND");
+                            SUSPENDS.fetch_add(1, Ordering::AcqRel);
                             // Only fire if transitioning from awake to sleeping
                             if try_transition_to_sleep() {
                                 info!("State transition: awake -> sleeping");
@@ -382,6 +403,33 @@ impl PowerEventListener {
                 }
             }
 
+            if msg == WM_QUERYENDSESSION {
+                // Ask Windows to wait for us once the session really ends.
+                let _ = ShutdownBlockReasonCreate(
+                    hwnd,
+                    windows::core::w!("Telling Home Assistant the PC is shutting down"),
+                );
+                return LRESULT(1);
+            }
+
+            if msg == WM_ENDSESSION {
+                let ctx_ptr = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const WndProcContext;
+                // wparam is zero when the shutdown was cancelled.
+                if wparam.0 != 0 {
+                    // Windows may end the process once this returns.
+                    crate::updater::confirm_pending_update();
+                }
+                if wparam.0 != 0 && !ctx_ptr.is_null() {
+                    info!("Session ending, publishing power-down");
+                    match sync_mqtt_publish_power_down(&(*ctx_ptr).sync_mqtt, "off") {
+                        Ok(()) => info!("Power-down published via sync TCP"),
+                        Err(e) => warn!("Sync MQTT publish failed: {}", e),
+                    }
+                }
+                let _ = ShutdownBlockReasonDestroy(hwnd);
+                return LRESULT(0);
+            }
+
             DefWindowProcW(hwnd, msg, wparam, lparam)
         }
     }
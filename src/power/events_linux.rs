@@ -15,7 +15,8 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
 use crate::AppState;
-use crate::power::sync_mqtt::{SyncMqttConfig, parse_broker_url, sync_mqtt_publish_sleep};
+use crate::events::BridgeEvent;
+use crate::power::sync_mqtt::{SyncMqttConfig, sync_mqtt_publish_sleep};
 
 /// Power-related events from D-Bus monitor threads
 enum PowerEvent {
@@ -82,6 +83,9 @@ impl PowerEventListener {
         if sleep_inhibitor.is_some() {
             info!("Holding systemd sleep delay-inhibitor for pre-suspend publish");
         }
+        // Wakeup source counts from before the last suspend, to tell on resume
+        // which one woke the PC.
+        let mut wakeup_counts = std::collections::HashMap::new();
 
         // Channel for events from blocking D-Bus reader threads
         let (event_tx, mut event_rx) = tokio::sync::mpsc::channel::<PowerEvent>(8);
@@ -155,22 +159,7 @@ impl PowerEventListener {
                             // Guaranteed-delivery sync publish (fresh TCP) before we
                             // release the inhibitor and the system suspends. Offloaded
                             // so the blocking connect doesn't stall the runtime.
-                            let cfg = {
-                                let config = self.state.config.read().await;
-                                let (host, port, use_tls) = parse_broker_url(&config.mqtt.broker);
-                                SyncMqttConfig {
-                                    host,
-                                    port,
-                                    use_tls,
-                                    user: config.mqtt.user.clone(),
-                                    pass: config.mqtt.pass.clone(),
-                                    client_id: format!("{}-sleep", config.client_id()),
-                                    sleep_topic: format!(
-                                        "homeassistant/sensor/{}/sleep_state/state",
-                                        config.device_name
-                                    ),
-                                }
-                            };
+                            let cfg = SyncMqttConfig::for_device(&*self.state.config.read().await);
                             match tokio::task::spawn_blocking(move || sync_mqtt_publish_sleep(&cfg))
                                 .await
                             {
@@ -178,14 +167,31 @@ impl PowerEventListener {
                                 Ok(Err(e)) => warn!("Sync MQTT sleep pre-publish failed: {}", e),
                                 Err(e) => warn!("Sync publish task join error: {}", e),
                             }
-                            self.state.mqtt.publish_sensor_retained("sleep_state", "sleeping").await;
+                            self.state.mqtt.announce_power_down("sleeping").await;
+                            wakeup_counts = tokio::task::spawn_blocking(super::wake_source::wakeup_counts)
+                                .await
+                                .unwrap_or_default();
                             // Drop the fd to release the delay-inhibitor: logind now
                             // proceeds to suspend.
                             drop(sleep_inhibitor.take());
                         }
                         PowerEvent::Wake => {
                             info!("Power event: WAKE");
+                            self.state.mqtt.publish_availability(true).await;
                             self.state.mqtt.publish_sensor_retained("sleep_state", "awake").await;
+                            self.state.mqtt.publish_event(&BridgeEvent::ResumedFromSleep).await;
+                            let before = std::mem::take(&mut wakeup_counts);
+                            if let Ok(source) = tokio::task::spawn_blocking(move || {
+                                super::wake_source::woken_by(&before)
+                            })
+                            .await
+                            {
+                                info!("Woken by {source:?}");
+                                self.state
+                                    .mqtt
+                                    .publish_sensor_attributes("sleep_state", &source.attributes())
+                                    .await;
+                            }
                             // Re-arm the inhibitor for the next suspend, off the
                             // runtime (the D-Bus connect+call is blocking).
                             sleep_inhibitor = tokio::task::spawn_blocking(Self::take_sleep_inhibitor)
@@ -195,10 +201,12 @@ impl PowerEventListener {
                         }
                         PowerEvent::DisplayOff => {
                             info!("Power event: DISPLAY OFF");
+                            crate::activity::set_display_off(true);
                             self.state.mqtt.publish_sensor_retained("display", "off").await;
                         }
                         PowerEvent::DisplayOn => {
                             info!("Power event: DISPLAY ON");
+                            crate::activity::set_display_off(false);
                             self.state.mqtt.publish_sensor_retained("display", "on").await;
                         }
                     }
@@ -4,6 +4,13 @@
 #[cfg_attr(not(windows), allow(dead_code))]
 pub mod sync_mqtt;
 
+pub(crate) mod display_mode;
+pub(crate) mod plan;
+pub(crate) mod requests;
+pub(crate) mod screensaver;
+pub(crate) mod suspend;
+mod wake_source;
+
 #[cfg(windows)]
 mod display;
 #[cfg(windows)]
@@ -18,6 +25,8 @@ mod events_linux;
 pub use display::{monitor_off, wake_display};
 #[cfg(windows)]
 pub use events::PowerEventListener;
+#[cfg(windows)]
+pub(crate) use events::suspend_count;
 
 #[cfg(unix)]
 pub use display_linux::{monitor_off, wake_display};
@@ -0,0 +1,225 @@
+//! Power plan (Windows) / power profile (Linux) read + switch, and AC source
+//!
+//! - Windows: `powercfg /list` and `/setactive <GUID>`; AC from GetSystemPowerStatus
+//! - Linux: `powerprofilesctl list` / `set` (power-profiles-daemon); AC from
+//!   the `Mains` supply in /sys/class/power_supply
+//!
+//! Plans are addressed by display name from HA (the select's options) and
+//! mapped back to the GUID/profile id here.
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct PowerPlan {
+    /// GUID (Windows) or profile name (Linux)
+    pub id: String,
+    pub name: String,
+    pub active: bool,
+}
+
+/// Plan names last seen by the sensor, so discovery can (re)publish the
+/// select's options on reconnect without shelling out from the MQTT task.
+static PLAN_NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) fn cached_names() -> Vec<String> {
+    PLAN_NAMES.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Store the current plan names; true if they differ from the cached ones.
+pub(crate) fn cache_names(plans: &[PowerPlan]) -> bool {
+    let names: Vec<String> = plans.iter().map(|p| p.name.clone()).collect();
+    let mut cached = PLAN_NAMES.lock().unwrap_or_else(|e| e.into_inner());
+    if *cached == names {
+        return false;
+    }
+    *cached = names;
+    true
+}
+
+/// Run `program` (with a timeout) and return its stdout.
+pub(super) async fn run(program: &str, args: &[&str]) -> Result<String, String> {
+    let mut cmd = tokio::process::Command::new(program);
+    cmd.args(args).kill_on_drop(true);
+    #[cfg(windows)]
+    cmd.creation_flags(0x0800_0000); // CREATE_NO_WINDOW
+    match tokio::time::timeout(COMMAND_TIMEOUT, cmd.output()).await {
+        Ok(Ok(out)) if out.status.success() => {
+            Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+        }
+        Ok(Ok(out)) => Err(format!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&out.stderr).trim()
+        )),
+        Ok(Err(e)) => Err(format!("{program}: {e}")),
+        Err(_) => Err(format!("{program} timed out")),
+    }
+}
+
+/// Installed plans, or `None` if they can't be read (e.g. no
+/// power-profiles-daemon).
+#[cfg(windows)]
+pub(crate) async fn list() -> Option<Vec<PowerPlan>> {
+    let out = run("powercfg", &["/list"]).await.ok()?;
+    Some(parse_powercfg_list(&out)).filter(|plans| !plans.is_empty())
+}
+
+#[cfg(unix)]
+pub(crate) async fn list() -> Option<Vec<PowerPlan>> {
+    let out = run("powerprofilesctl", &["list"]).await.ok()?;
+    Some(parse_powerprofilesctl_list(&out)).filter(|plans| !plans.is_empty())
+}
+
+/// Activate the plan with display name `name` (case-insensitive); returns
+/// its name as listed.
+pub(crate) async fn set_active(name: &str) -> Result<String, String> {
+    let plans = list().await.ok_or("power plans unavailable")?;
+    let plan = plans
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| format!("unknown power plan '{}'", name.trim()))?;
+    #[cfg(windows)]
+    run("powercfg", &["/setactive", &plan.id]).await?;
+    #[cfg(unix)]
+    run("powerprofilesctl", &["set", &plan.id]).await?;
+    Ok(plan.name.clone())
+}
+
+/// Whether the machine runs on mains power; `None` if unknown.
+#[cfg(windows)]
+pub(crate) fn ac_connected() -> Option<bool> {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status = SYSTEM_POWER_STATUS::default();
+    unsafe { GetSystemPowerStatus(&raw mut status) }.ok()?;
+    // ACLineStatus: 0 = offline, 1 = online, 255 = unknown
+    match status.ACLineStatus {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn ac_connected() -> Option<bool> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+        // No power_supply class at all: a desktop (or container) on mains.
+        return Some(true);
+    };
+    let mut saw_battery = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+        match kind.trim() {
+            "Mains" => {
+                let online = std::fs::read_to_string(path.join("online")).ok()?;
+                return Some(online.trim() == "1");
+            }
+            "Battery" => saw_battery = true,
+            _ => {}
+        }
+    }
+    // A battery but no Mains supply: can't tell. No battery: desktop.
+    (!saw_battery).then_some(true)
+}
+
+/// `powercfg /list` lines look like
+/// `Power Scheme GUID: 381b4222-...-ff5bb260df2e  (Balanced) *`; the labels
+/// are localized, so only the GUID, the parenthesised name and the trailing
+/// `*` (active) are relied on.
+#[cfg_attr(unix, allow(dead_code))]
+fn parse_powercfg_list(output: &str) -> Vec<PowerPlan> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let id = line
+                .split_whitespace()
+                .find(|w| w.len() == 36 && w.bytes().filter(|b| *b == b'-').count() == 4)?;
+            let open = line.find('(')?;
+            let close = line.rfind(')')?;
+            let name = line.get(open + 1..close)?.trim();
+            (!name.is_empty()).then(|| PowerPlan {
+                id: id.to_string(),
+                name: name.to_string(),
+                active: line.ends_with('*'),
+            })
+        })
+        .collect()
+}
+
+/// `powerprofilesctl list` prints one `name:` header per profile (prefixed
+/// `* ` when active, two spaces otherwise) followed by indented details.
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_powerprofilesctl_list(output: &str) -> Vec<PowerPlan> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (active, rest) = if let Some(rest) = line.strip_prefix("* ") {
+                (true, rest)
+            } else {
+                (false, line.strip_prefix("  ")?)
+            };
+            let name = rest.strip_suffix(':')?;
+            (!name.is_empty() && !name.starts_with(char::is_whitespace) && !name.contains(' '))
+                .then(|| PowerPlan {
+                    id: name.to_string(),
+                    name: name.to_string(),
+                    active,
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_powercfg_list() {
+        let out = "\r\nExisting Power Schemes (* Active)\r\n\
+                   -----------------------------------\r\n\
+                   Power Scheme GUID: 381b4222-f694-41f0-9685-ff5bb260df2e  (Balanced) *\r\n\
+                   Power Scheme GUID: 8c5e7fda-e8bf-4a96-9a85-a6e23a8c635c  (High performance)\r\n\
+                   Power Scheme GUID: e9a42b02-d5df-448d-aa00-03f14749eb61  (Ultimate Performance (custom))\r\n";
+        let plans = parse_powercfg_list(out);
+        assert_eq!(plans.len(), 3);
+        assert_eq!(plans[0].id, "381b4222-f694-41f0-9685-ff5bb260df2e");
+        assert_eq!(plans[0].name, "Balanced");
+        assert!(plans[0].active);
+        assert_eq!(plans[1].name, "High performance");
+        assert!(!plans[1].active);
+        assert_eq!(plans[2].name, "Ultimate Performance (custom)");
+
+        // Localized labels still parse.
+        let de = "Energieschema-GUID: 381b4222-f694-41f0-9685-ff5bb260df2e  (Ausbalanciert) *";
+        assert_eq!(parse_powercfg_list(de)[0].name, "Ausbalanciert");
+    }
+
+    #[test]
+    fn test_parse_powerprofilesctl_list() {
+        let out = "  performance:\n    CpuDriver:\tamd_pstate\n    Degraded:   no\n\n\
+                   * balanced:\n    CpuDriver:\tamd_pstate\n\n\
+                   \x20 power-saver:\n    CpuDriver:\tamd_pstate\n";
+        let plans = parse_powerprofilesctl_list(out);
+        let names: Vec<&str> = plans.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["performance", "balanced", "power-saver"]);
+        assert!(plans[1].active && !plans[0].active && !plans[2].active);
+    }
+
+    #[test]
+    fn test_cache_names_reports_changes() {
+        let plan = |name: &str| PowerPlan {
+            id: name.to_string(),
+            name: name.to_string(),
+            active: false,
+        };
+        cache_names(&[]);
+        assert!(cache_names(&[plan("Balanced")]));
+        assert!(!cache_names(&[plan("Balanced")]));
+        assert_eq!(cached_names(), ["Balanced"]);
+        assert!(cache_names(&[plan("Balanced"), plan("High performance")]));
+    }
+}
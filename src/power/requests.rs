@@ -0,0 +1,239 @@
+//! Active power requests: what is keeping the PC awake
+//!
+//! - Windows: `powercfg /requests`, the display / system / away mode /
+//!   execution requests apps, services and drivers hold. powercfg only
+//!   answers an elevated caller, so this is None when the agent isn't.
+//! - Linux: systemd-logind's inhibitor locks (`ListInhibitors` on the system
+//!   bus); `block` locks on `sleep` or `idle` keep it awake, `delay` locks
+//!   only hold a suspend off for a few seconds.
+
+/// One request (Windows) or inhibitor lock (Linux)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PowerRequest {
+    /// What it holds off: `display`, `system`, `awaymode`, `execution`, ...
+    /// on Windows; logind's `what` (`sleep:idle`, `shutdown`, ...) on Linux
+    pub kind: String,
+    /// Process, service or driver behind it (`chrome.exe`, `wuauserv`)
+    pub requester: String,
+    /// What the requester says it's for (`Audio Stream`)
+    pub reason: Option<String>,
+    /// Keeps the PC from sleeping (vs. only the display on)
+    pub blocks_sleep: bool,
+}
+
+impl PowerRequest {
+    /// `chrome.exe (Audio Stream)`
+    pub fn describe(&self) -> String {
+        match &self.reason {
+            Some(reason) => format!("{} ({reason})", self.requester),
+            None => self.requester.clone(),
+        }
+    }
+}
+
+/// The active requests; None if they can't be read.
+#[cfg(windows)]
+pub(crate) async fn active() -> Option<Vec<PowerRequest>> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    match tokio::process::Command::new("powercfg")
+        .arg("/requests")
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .await
+    {
+        Ok(output) if output.status.success() => {
+            Some(parse_powercfg(&String::from_utf8_lossy(&output.stdout)))
+        }
+        Ok(output) => {
+            // Not elevated: "This command requires administrator privileges"
+            log::debug!("powercfg /requests exited with {}", output.status);
+            None
+        }
+        Err(e) => {
+            log::debug!("powercfg /requests failed: {e}");
+            None
+        }
+    }
+}
+
+/// The active inhibitor locks; None if logind can't be asked.
+#[cfg(unix)]
+pub(crate) async fn active() -> Option<Vec<PowerRequest>> {
+    tokio::task::spawn_blocking(inhibitors).await.ok().flatten()
+}
+
+/// (what, who, why, mode, uid, pid) as logind lists them
+#[cfg(unix)]
+type Inhibitor = (String, String, String, String, u32, u32);
+
+#[cfg(unix)]
+fn inhibitors() -> Option<Vec<PowerRequest>> {
+    let bus = zbus::blocking::Connection::system().ok()?;
+    let reply = bus
+        .call_method(
+            Some("org.freedesktop.login1"),
+            "/org/freedesktop/login1",
+            Some("org.freedesktop.login1.Manager"),
+            "ListInhibitors",
+            &(),
+        )
+        .ok()?;
+    let locks: Vec<Inhibitor> = reply.body().deserialize().ok()?;
+    Some(
+        locks
+            .into_iter()
+            .map(|(what, who, why, mode, _, _)| inhibitor(what, who, &why, &mode))
+            .collect(),
+    )
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+fn inhibitor(what: String, who: String, why: &str, mode: &str) -> PowerRequest {
+    let blocks_sleep = mode == "block" && what.split(':').any(|w| w == "sleep" || w == "idle");
+    PowerRequest {
+        kind: what,
+        requester: who,
+        reason: (!why.is_empty()).then(|| why.to_string()),
+        blocks_sleep,
+    }
+}
+
+/// Requests in `powercfg /requests` output:
+///
+/// ```text
+/// DISPLAY:
+/// [PROCESS] \Device\HarddiskVolume3\Program Files\Google\Chrome\Application\chrome.exe
+/// Video Wake Lock
+///
+/// SYSTEM:
+/// [DRIVER] Realtek High Definition Audio (HDAUDIO\FUNC_01&VEN_10EC&...)
+/// An audio stream is currently in use.
+///
+/// AWAYMODE:
+/// None.
+/// ```
+///
+/// System, away mode and execution requests keep the PC from sleeping.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_powercfg(output: &str) -> Vec<PowerRequest> {
+    let mut requests: Vec<PowerRequest> = Vec::new();
+    let mut kind = String::new();
+    // Whether the last request still takes reason lines
+    let mut open = false;
+    for line in output.lines().map(str::trim) {
+        if line.is_empty() || line == "None." {
+            open = false;
+        } else if let Some(header) = line.strip_suffix(':')
+            && !header.contains(' ')
+            && header.chars().all(|c| c.is_ascii_uppercase())
+        {
+            kind = header.to_ascii_lowercase();
+            open = false;
+        } else if line.starts_with('[')
+            && let Some((tag, rest)) = line.split_once(']')
+        {
+            requests.push(PowerRequest {
+                kind: kind.clone(),
+                requester: requester(&tag[1..], rest.trim()),
+                reason: None,
+                blocks_sleep: matches!(kind.as_str(), "system" | "awaymode" | "execution"),
+            });
+            open = true;
+        } else if open && let Some(last) = requests.last_mut() {
+            last.reason = Some(match last.reason.take() {
+                Some(reason) => format!("{reason} {line}"),
+                None => line.to_string(),
+            });
+        }
+    }
+    requests
+}
+
+/// Short name of a `[PROCESS]` / `[SERVICE]` / `[DRIVER]` requester:
+/// the executable, the service name, the driver's description.
+fn requester(tag: &str, detail: &str) -> String {
+    let file_name = |path: &str| path.rsplit('\\').next().unwrap_or(path).trim().to_string();
+    match tag {
+        "PROCESS" => file_name(detail),
+        "SERVICE" => match detail.rsplit_once('(') {
+            Some((_, service)) => service.trim_end_matches(')').trim().to_string(),
+            None => file_name(detail),
+        },
+        "DRIVER" => detail
+            .split_once(" (")
+            .map_or(detail, |(name, _)| name)
+            .to_string(),
+        _ => detail.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POWERCFG: &str = "DISPLAY:\r\n\
+        [PROCESS] \\Device\\HarddiskVolume3\\Program Files\\Google\\Chrome\\Application\\chrome.exe\r\n\
+        Video Wake Lock\r\n\
+        \r\n\
+        SYSTEM:\r\n\
+        [DRIVER] Realtek High Definition Audio (HDAUDIO\\FUNC_01&VEN_10EC&DEV_0892)\r\n\
+        An audio stream is currently in use.\r\n\
+        [PROCESS] \\Device\\HarddiskVolume3\\Program Files\\Google\\Chrome\\Application\\chrome.exe\r\n\
+        Audio Stream\r\n\
+        \r\n\
+        AWAYMODE:\r\n\
+        None.\r\n\
+        \r\n\
+        EXECUTION:\r\n\
+        [SERVICE] \\Device\\HarddiskVolume3\\Windows\\System32\\svchost.exe (wuauserv)\r\n\
+        \r\n\
+        PERFBOOST:\r\n\
+        None.\r\n";
+
+    #[test]
+    fn test_parse_powercfg() {
+        let requests = parse_powercfg(POWERCFG);
+        let described: Vec<(String, String, bool)> = requests
+            .iter()
+            .map(|r| (r.kind.clone(), r.describe(), r.blocks_sleep))
+            .collect();
+        assert_eq!(
+            described,
+            [
+                ("display", "chrome.exe (Video Wake Lock)", false),
+                (
+                    "system",
+                    "Realtek High Definition Audio (An audio stream is currently in use.)",
+                    true
+                ),
+                ("system", "chrome.exe (Audio Stream)", true),
+                ("execution", "wuauserv", true),
+            ]
+            .map(|(k, d, b)| (k.to_string(), d.to_string(), b))
+        );
+        assert!(parse_powercfg("DISPLAY:\nNone.\n\nSYSTEM:\nNone.\n").is_empty());
+    }
+
+    #[test]
+    fn test_inhibitor() {
+        let steam = inhibitor(
+            "sleep:idle".to_string(),
+            "Steam".to_string(),
+            "Downloading",
+            "block",
+        );
+        assert!(steam.blocks_sleep);
+        assert_eq!(steam.describe(), "Steam (Downloading)");
+        // A delay lock only holds a suspend off briefly.
+        let delay = inhibitor(
+            "sleep".to_string(),
+            "NetworkManager".to_string(),
+            "",
+            "delay",
+        );
+        assert!(!delay.blocks_sleep);
+        assert_eq!(delay.describe(), "NetworkManager");
+        assert!(!inhibitor("shutdown".to_string(), "gdm".to_string(), "", "block").blocks_sleep);
+    }
+}
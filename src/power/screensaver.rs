@@ -0,0 +1,284 @@
+//! Screensaver command
+//!
+//! `Screensaver` used to shell out to `scrnsave.scr`, which many Windows
+//! installs no longer ship. The payload now picks what "screensaver" means:
+//! start the configured screensaver (default), the photo slideshow, blank the
+//! display, or lock. Starting a screensaver falls back to blanking when it
+//! can't run (none configured, or the `.scr` is missing), and
+//! the outcome goes out on the command results topic.
+
+use log::{info, warn};
+
+use crate::AppState;
+
+/// What the `Screensaver` payload asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreensaverMode {
+    /// The screensaver set in the OS (empty payload / `PRESS`)
+    Screensaver,
+    /// The Windows photo slideshow screensaver
+    Slideshow,
+    /// Turn the display off, like `MonitorOff`
+    DisplayOff,
+    /// Lock the session
+    Lock,
+}
+
+impl ScreensaverMode {
+    /// `None` for a payload that isn't a mode.
+    pub fn parse(payload: &str) -> Option<Self> {
+        match payload.trim().to_ascii_lowercase().as_str() {
+            "" | "press" | "screensaver" | "start" => Some(Self::Screensaver),
+            "slideshow" | "photos" => Some(Self::Slideshow),
+            "display_off" | "blank" | "off" => Some(Self::DisplayOff),
+            "lock" => Some(Self::Lock),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Screensaver => "screensaver",
+            Self::Slideshow => "slideshow",
+            Self::DisplayOff => "display_off",
+            Self::Lock => "lock",
+        }
+    }
+}
+
+/// What `activate` ended up doing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreensaverOutcome {
+    pub requested: ScreensaverMode,
+    /// The mode actually carried out; differs from `requested` on fallback.
+    pub performed: Option<ScreensaverMode>,
+    /// Why the requested mode wasn't (fully) carried out
+    pub error: Option<String>,
+}
+
+impl ScreensaverOutcome {
+    fn done(requested: ScreensaverMode) -> Self {
+        Self {
+            requested,
+            performed: Some(requested),
+            error: None,
+        }
+    }
+
+    /// The command result: "success", "fallback" (another mode was carried
+    /// out) or "failed".
+    pub fn result(&self) -> serde_json::Value {
+        let result = match self.performed {
+            Some(mode) if mode == self.requested => "success",
+            Some(_) => "fallback",
+            None => "failed",
+        };
+        serde_json::json!({
+            "command": "Screensaver",
+            "result": result,
+            "requested": self.requested.as_str(),
+            "performed": self.performed.map(ScreensaverMode::as_str),
+            "error": self.error,
+        })
+    }
+}
+
+/// The `Screensaver` command: carry out the payload's mode off the runtime
+/// and publish the outcome.
+pub(crate) async fn run(payload: &str, state: &AppState) {
+    let Some(mode) = ScreensaverMode::parse(payload) else {
+        warn!(
+            "Screensaver: unknown mode {payload:?} (use screensaver, slideshow, display_off or lock)"
+        );
+        state
+            .mqtt
+            .publish_command_result(&serde_json::json!({
+                "command": "Screensaver",
+                "result": "failed",
+                "error": format!("unknown mode {payload:?}"),
+            }))
+            .await;
+        return;
+    };
+    let Ok(outcome) = tokio::task::spawn_blocking(move || activate(mode)).await else {
+        return;
+    };
+    state.mqtt.publish_command_result(&outcome.result()).await;
+}
+
+/// Carry out `mode`. Blocking (window messages, child processes).
+pub fn activate(mode: ScreensaverMode) -> ScreensaverOutcome {
+    info!("Screensaver: {}", mode.as_str());
+    let result = match mode {
+        ScreensaverMode::DisplayOff => {
+            super::monitor_off();
+            Ok(())
+        }
+        ScreensaverMode::Lock => lock(),
+        ScreensaverMode::Screensaver => start_screensaver(),
+        ScreensaverMode::Slideshow => start_slideshow(),
+    };
+    match result {
+        Ok(()) => ScreensaverOutcome::done(mode),
+        Err(e)
+            if matches!(
+                mode,
+                ScreensaverMode::Screensaver | ScreensaverMode::Slideshow
+            ) =>
+        {
+            warn!("Screensaver: {e}; turning the display off instead");
+            super::monitor_off();
+            ScreensaverOutcome {
+                requested: mode,
+                performed: Some(ScreensaverMode::DisplayOff),
+                error: Some(e),
+            }
+        }
+        Err(e) => {
+            warn!("Screensaver: {e}");
+            ScreensaverOutcome {
+                requested: mode,
+                performed: None,
+                error: Some(e),
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+fn lock() -> Result<(), String> {
+    use windows::Win32::System::Shutdown::LockWorkStation;
+    unsafe { LockWorkStation() }.map_err(|e| format!("LockWorkStation failed: {e}"))
+}
+
+/// Run the screensaver chosen in Settings (`SCRNSAVE.EXE` under
+/// `HKCU\Control Panel\Desktop`) with `/s`, if screensavers are enabled.
+#[cfg(windows)]
+fn start_screensaver() -> Result<(), String> {
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SPI_GETSCREENSAVEACTIVE, SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS, SystemParametersInfoW,
+    };
+    use winreg::RegKey;
+    use winreg::enums::HKEY_CURRENT_USER;
+
+    let mut active = windows::Win32::Foundation::BOOL(0);
+    unsafe {
+        SystemParametersInfoW(
+            SPI_GETSCREENSAVEACTIVE,
+            0,
+            Some((&raw mut active).cast()),
+            SYSTEM_PARAMETERS_INFO_UPDATE_FLAGS(0),
+        )
+    }
+    .map_err(|e| format!("SystemParametersInfo failed: {e}"))?;
+    if !active.as_bool() {
+        return Err("no screensaver is enabled".to_string());
+    }
+
+    let scr: String = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(r"Control Panel\Desktop")
+        .and_then(|desktop| desktop.get_value("SCRNSAVE.EXE"))
+        .map_err(|_| "no screensaver is configured".to_string())?;
+    let scr = scr.trim();
+    if scr.is_empty() {
+        return Err("no screensaver is configured".to_string());
+    }
+    run_scr(scr)
+}
+
+#[cfg(windows)]
+fn start_slideshow() -> Result<(), String> {
+    let windir = std::env::var("windir").unwrap_or_else(|_| r"C:\Windows".to_string());
+    run_scr(&format!(r"{windir}\System32\PhotoScreensaver.scr"))
+}
+
+#[cfg(windows)]
+fn run_scr(scr: &str) -> Result<(), String> {
+    if !std::path::Path::new(scr).exists() {
+        return Err(format!("screensaver {scr} is missing"));
+    }
+    std::process::Command::new(scr)
+        .arg("/s")
+        .spawn()
+        .map(drop)
+        .map_err(|e| format!("failed to start {scr}: {e}"))
+}
+
+#[cfg(unix)]
+fn lock() -> Result<(), String> {
+    run_tool("loginctl", &["lock-session"])
+}
+
+#[cfg(unix)]
+fn start_screensaver() -> Result<(), String> {
+    run_tool("xdg-screensaver", &["activate"])
+}
+
+#[cfg(unix)]
+fn start_slideshow() -> Result<(), String> {
+    Err("the slideshow screensaver is Windows-only".to_string())
+}
+
+#[cfg(unix)]
+fn run_tool(program: &str, args: &[&str]) -> Result<(), String> {
+    match std::process::Command::new(program).args(args).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("{program} exited with {status}")),
+        Err(e) => Err(format!("{program} not available: {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_modes() {
+        for payload in ["", "PRESS", "screensaver", "Start"] {
+            assert_eq!(
+                ScreensaverMode::parse(payload),
+                Some(ScreensaverMode::Screensaver)
+            );
+        }
+        for payload in ["display_off", "blank", "OFF"] {
+            assert_eq!(
+                ScreensaverMode::parse(payload),
+                Some(ScreensaverMode::DisplayOff)
+            );
+        }
+        assert_eq!(
+            ScreensaverMode::parse(" lock "),
+            Some(ScreensaverMode::Lock)
+        );
+        assert_eq!(
+            ScreensaverMode::parse("Slideshow"),
+            Some(ScreensaverMode::Slideshow)
+        );
+        assert_eq!(ScreensaverMode::parse("bubbles"), None);
+    }
+
+    #[test]
+    fn test_outcome_result() {
+        let fell_back = ScreensaverOutcome {
+            requested: ScreensaverMode::Screensaver,
+            performed: Some(ScreensaverMode::DisplayOff),
+            error: Some("no screensaver is configured".to_string()),
+        };
+        let result = fell_back.result();
+        assert_eq!(result["command"], "Screensaver");
+        assert_eq!(result["result"], "fallback");
+        assert_eq!(result["requested"], "screensaver");
+        assert_eq!(result["performed"], "display_off");
+
+        let result = ScreensaverOutcome::done(ScreensaverMode::Lock).result();
+        assert_eq!(result["result"], "success");
+        assert!(result["error"].is_null());
+
+        let failed = ScreensaverOutcome {
+            requested: ScreensaverMode::Lock,
+            performed: None,
+            error: Some("loginctl not available".to_string()),
+        };
+        assert_eq!(failed.result()["result"], "failed");
+    }
+}
@@ -0,0 +1,109 @@
+//! Why a Sleep / Hibernate command didn't suspend the PC
+//!
+//! Both executors check that the PC can do the requested sleep state before
+//! announcing it, and after a suspend that didn't happen work out why: the
+//! first of unsupported by the hardware, blocked by a power request
+//! (`requests`), or the OS error. The outcome goes to the command result
+//! topic as `{"command", "result": "ok" | "failed", "error"}`.
+
+use log::warn;
+
+use super::requests;
+use crate::AppState;
+
+/// Why this PC can't enter the sleep state at all, if it can't.
+#[cfg(windows)]
+pub(crate) fn unsupported(hibernate: bool) -> Option<&'static str> {
+    use windows::Win32::System::Power::{GetPwrCapabilities, SYSTEM_POWER_CAPABILITIES};
+
+    let mut caps = SYSTEM_POWER_CAPABILITIES::default();
+    if !unsafe { GetPwrCapabilities(&raw mut caps) }.as_bool() {
+        return None;
+    }
+    if hibernate {
+        return (!caps.SystemS4.as_bool() || !caps.HiberFilePresent.as_bool())
+            .then_some("hibernation is turned off (powercfg /hibernate on)");
+    }
+    let sleeps = caps.SystemS1.as_bool() || caps.SystemS2.as_bool() || caps.SystemS3.as_bool();
+    if sleeps {
+        None
+    } else if caps.AoAc.as_bool() {
+        Some("this PC uses Modern Standby, which has no S3 sleep")
+    } else {
+        Some("sleep isn't supported by this PC's firmware or drivers")
+    }
+}
+
+/// Why this PC can't enter the sleep state at all, if it can't: the kernel
+/// lists the states it supports in `/sys/power/state`.
+#[cfg(unix)]
+pub(crate) fn unsupported(hibernate: bool) -> Option<&'static str> {
+    let states = std::fs::read_to_string("/sys/power/state").ok()?;
+    unsupported_states(&states, hibernate)
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+fn unsupported_states(states: &str, hibernate: bool) -> Option<&'static str> {
+    let has = |state: &str| states.split_whitespace().any(|s| s == state);
+    if hibernate {
+        (!has("disk")).then_some("hibernation isn't supported by this kernel")
+    } else {
+        (!has("mem") && !has("freeze")).then_some("sleep isn't supported by this kernel")
+    }
+}
+
+/// The cause to report for a suspend that didn't happen: the power
+/// requests keeping the PC awake if there are any, else `error`.
+pub(crate) async fn failure_cause(error: String) -> String {
+    let blockers: Vec<String> = requests::active()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter(|r| r.blocks_sleep)
+        .map(requests::PowerRequest::describe)
+        .collect();
+    blocked_by(&blockers).unwrap_or(error)
+}
+
+fn blocked_by(blockers: &[String]) -> Option<String> {
+    (!blockers.is_empty()).then(|| format!("blocked by {}", blockers.join(", ")))
+}
+
+/// Publish the outcome of `command` to the command result topic.
+pub(crate) async fn report(state: &AppState, command: &str, error: Option<&str>) {
+    let result = match error {
+        Some(error) => {
+            warn!("{command} failed: {error}");
+            serde_json::json!({ "command": command, "result": "failed", "error": error })
+        }
+        None => serde_json::json!({ "command": command, "result": "ok" }),
+    };
+    state.mqtt.publish_command_result(&result).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported_states() {
+        assert_eq!(unsupported_states("freeze mem disk\n", false), None);
+        assert_eq!(unsupported_states("freeze mem disk\n", true), None);
+        // Modern Standby laptops only offer s2idle
+        assert_eq!(unsupported_states("freeze\n", false), None);
+        assert!(unsupported_states("freeze mem\n", true).is_some());
+        assert!(unsupported_states("disk\n", false).is_some());
+    }
+
+    #[test]
+    fn test_blocked_by() {
+        assert_eq!(blocked_by(&[]), None);
+        assert_eq!(
+            blocked_by(&[
+                "chrome.exe (Audio Stream)".to_string(),
+                "wuauserv".to_string()
+            ]),
+            Some("blocked by chrome.exe (Audio Stream), wuauserv".to_string())
+        );
+    }
+}
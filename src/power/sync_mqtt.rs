@@ -1,9 +1,11 @@
 //! Synchronous MQTT publish for sleep state notifications.
 //!
 //! Provides a one-shot raw TCP MQTT 3.1.1 publish that bypasses the async
-//! rumqttc event loop. This guarantees the "sleeping" message reaches the
-//! broker before Windows/Linux powers down the NIC, since the call blocks
-//! until the packet is on the wire.
+//! rumqttc event loop. This guarantees the "sleeping" message (and the
+//! retained "offline" availability that goes with it, so HA sees a planned
+//! power-down rather than a crash) reaches the broker before Windows/Linux
+//! powers down the NIC, since the call blocks until the packets are on the
+//! wire.
 //!
 //! All functions are platform-independent and compiled on every target so
 //! that the full test suite runs on macOS/Linux CI as well as Windows.
@@ -23,51 +25,31 @@ pub struct SyncMqttConfig {
     pub pass: String,
     pub client_id: String,
     pub sleep_topic: String,
+    pub availability_topic: String,
 }
 
-/// Parse a broker URL like "tcp://host:port" into (host, port, use_tls).
-///
-/// Schemes recognised: `tcp://`, `ssl://`, `ws://`, `wss://`.  Missing scheme
-/// defaults to non-TLS.  Missing port defaults to 1883 (non-TLS) / 8883 (TLS).
-///
-/// Handles IPv6 bracketed addresses (`[::1]:1883`).  Without the brackets
-/// the host:port split-on-`:` would misparse a bare IPv6 address.
-pub fn parse_broker_url(url: &str) -> (String, u16, bool) {
-    let (without_scheme, use_tls) = if let Some(rest) = url.strip_prefix("ssl://") {
-        (rest, true)
-    } else if let Some(rest) = url.strip_prefix("wss://") {
-        (rest, true)
-    } else if let Some(rest) = url.strip_prefix("tcp://") {
-        (rest, false)
-    } else if let Some(rest) = url.strip_prefix("ws://") {
-        (rest, false)
-    } else {
-        (url, false)
-    };
-
-    let default_port = if use_tls { 8883 } else { 1883 };
-
-    // IPv6 bracketed address - `[host]:port` or just `[host]`.
-    if let Some(after_open) = without_scheme.strip_prefix('[')
-        && let Some(close) = after_open.find(']')
-    {
-        let host = after_open[..close].to_string();
-        let rest = &after_open[close + 1..];
-        let port = rest
-            .strip_prefix(':')
-            .and_then(|p| p.parse().ok())
-            .unwrap_or(default_port);
-        return (host, port, use_tls);
+impl SyncMqttConfig {
+    /// The device's broker and topics, on a distinct client id so the broker
+    /// doesn't kick the main connection.
+    pub fn for_device(config: &crate::config::Config) -> Self {
+        // Validated at load; a broker that doesn't parse just fails to connect.
+        let broker = crate::mqtt::BrokerUrl::parse(&config.mqtt.broker).unwrap_or_default();
+        Self {
+            host: broker.host,
+            port: broker.port,
+            use_tls: broker.tls,
+            user: config.mqtt.user.clone(),
+            pass: config.mqtt.pass.clone(),
+            client_id: format!("{}-sleep", config.client_id()),
+            sleep_topic: crate::mqtt::MqttClient::sensor_topic_static(
+                &config.device_name,
+                "sleep_state",
+            ),
+            availability_topic: crate::mqtt::MqttClient::availability_topic_static(
+                &config.device_name,
+            ),
+        }
     }
-
-    // Plain host[:port]
-    let parts: Vec<&str> = without_scheme.split(':').collect();
-    let host = parts.first().unwrap_or(&"localhost").to_string();
-    let port = parts
-        .get(1)
-        .and_then(|p| p.parse().ok())
-        .unwrap_or(default_port);
-    (host, port, use_tls)
 }
 
 /// Publish "sleeping" to the sleep_state topic using a one-shot synchronous
@@ -75,16 +57,21 @@ pub fn parse_broker_url(url: &str) -> (String, u16, bool) {
 /// that the PUBLISH packet is guaranteed to be on the wire before `wnd_proc`
 /// returns and the OS powers down the NIC.
 pub fn sync_mqtt_publish_sleep(cfg: &SyncMqttConfig) -> std::io::Result<()> {
-    let addr = format!("{}:{}", cfg.host, cfg.port);
+    sync_mqtt_publish_power_down(cfg, "sleeping")
+}
+
+/// Like `sync_mqtt_publish_sleep`, with `sleep_state` set to `state` ("off"
+/// when the session is ending).
+pub fn sync_mqtt_publish_power_down(cfg: &SyncMqttConfig, state: &str) -> std::io::Result<()> {
     let timeout = Duration::from_secs(2);
 
     // Resolve hostname to IP, hard-bounded. This runs inside the Windows suspend
     // handler (wnd_proc during PBT_APMSUSPEND), so an unbounded DNS lookup against a
     // slow resolver could push suspend past the OS grace window and get the process
     // force-killed before the publish lands. An IP host resolves instantly.
-    let socket_addr = resolve_timeout(&addr, timeout)?;
+    let addrs = resolve_timeout(&cfg.host, cfg.port, timeout)?;
 
-    let stream = TcpStream::connect_timeout(&socket_addr, timeout)?;
+    let stream = connect_any(&addrs, timeout)?;
     stream.set_write_timeout(Some(timeout))?;
     stream.set_read_timeout(Some(timeout))?;
     stream.set_nodelay(true)?;
@@ -98,40 +85,70 @@ pub fn sync_mqtt_publish_sleep(cfg: &SyncMqttConfig) -> std::io::Result<()> {
                 format!("TLS handshake failed: {e}"),
             )
         })?;
-        do_mqtt_exchange(&mut tls_stream, cfg)
+        do_mqtt_exchange(&mut tls_stream, cfg, state)
     } else {
         let mut stream = stream;
-        do_mqtt_exchange(&mut stream, cfg)
+        do_mqtt_exchange(&mut stream, cfg, state)
     }
 }
 
-/// Resolve `addr` to a `SocketAddr` but never block longer than `timeout`.
+/// Resolve `host` to its addresses but never block longer than `timeout`.
 /// `to_socket_addrs` has no timeout of its own, so we run it on a worker thread
 /// and give up if it doesn't answer in time (a detached slow lookup will finish
 /// and drop harmlessly). An IP literal returns immediately.
-fn resolve_timeout(addr: &str, timeout: Duration) -> std::io::Result<SocketAddr> {
-    let owned = addr.to_string();
+fn resolve_timeout(host: &str, port: u16, timeout: Duration) -> std::io::Result<Vec<SocketAddr>> {
+    let owned = host.to_string();
     let (tx, rx) = std::sync::mpsc::channel();
     std::thread::spawn(move || {
-        let _ = tx.send(owned.to_socket_addrs().map(|mut it| it.next()));
+        let _ = tx.send(
+            (owned.as_str(), port)
+                .to_socket_addrs()
+                .map(Iterator::collect),
+        );
     });
     match rx.recv_timeout(timeout) {
-        Ok(Ok(Some(sa))) => Ok(sa),
-        Ok(Ok(None)) => Err(std::io::Error::new(
+        Ok(Ok(addrs)) if Vec::is_empty(&addrs) => Err(std::io::Error::new(
             std::io::ErrorKind::InvalidInput,
-            format!("DNS resolution returned no addresses for {addr}"),
+            format!("DNS resolution returned no addresses for {host}"),
         )),
-        Ok(Err(e)) => Err(e),
+        Ok(result) => result,
         Err(_) => Err(std::io::Error::new(
             std::io::ErrorKind::TimedOut,
-            format!("DNS resolution timed out for {addr}"),
+            format!("DNS resolution timed out for {host}"),
         )),
     }
 }
 
+/// Connect to the first of `addrs` that answers, within `timeout` overall. A
+/// broker with several addresses (IPv4 + IPv6, or a multi-homed host) may
+/// only be reachable on some, so each gets an even share of what's left
+/// rather than the first dead one using it all up.
+fn connect_any(addrs: &[SocketAddr], timeout: Duration) -> std::io::Result<TcpStream> {
+    let deadline = std::time::Instant::now() + timeout;
+    let mut last_err = None;
+    for (i, addr) in addrs.iter().enumerate() {
+        let left = deadline.saturating_duration_since(std::time::Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        let share = left / (addrs.len() - i) as u32;
+        match TcpStream::connect_timeout(addr, share) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::TimedOut, "broker connect timed out")
+    }))
+}
+
 /// Perform the MQTT CONNECT/CONNACK/PUBLISH/DISCONNECT exchange over any
 /// Read+Write stream (plain TCP or TLS-wrapped).
-fn do_mqtt_exchange(stream: &mut (impl Read + Write), cfg: &SyncMqttConfig) -> std::io::Result<()> {
+fn do_mqtt_exchange(
+    stream: &mut (impl Read + Write),
+    cfg: &SyncMqttConfig,
+    state: &str,
+) -> std::io::Result<()> {
     // --- CONNECT ---
     let connect = build_mqtt_connect(&cfg.client_id, &cfg.user, &cfg.pass);
     stream.write_all(&connect)?;
@@ -153,8 +170,13 @@ fn do_mqtt_exchange(stream: &mut (impl Read + Write), cfg: &SyncMqttConfig) -> s
         ));
     }
 
-    // --- PUBLISH (QoS 0, retained) ---
-    let publish = build_mqtt_publish(&cfg.sleep_topic, b"sleeping", true);
+    // --- PUBLISH (QoS 0, retained): sleep state, then availability ---
+    let mut publish = build_mqtt_publish(&cfg.sleep_topic, state.as_bytes(), true);
+    publish.extend(build_mqtt_publish(
+        &cfg.availability_topic,
+        b"offline",
+        true,
+    ));
     stream.write_all(&publish)?;
     stream.flush()?;
 
@@ -166,7 +188,7 @@ fn do_mqtt_exchange(stream: &mut (impl Read + Write), cfg: &SyncMqttConfig) -> s
 }
 
 /// Build an MQTT 3.1.1 CONNECT packet.
-fn build_mqtt_connect(client_id: &str, user: &str, pass: &str) -> Vec<u8> {
+pub(crate) fn build_mqtt_connect(client_id: &str, user: &str, pass: &str) -> Vec<u8> {
     let mut payload = Vec::with_capacity(64);
 
     // Variable header: Protocol Name
@@ -341,70 +363,6 @@ mod tests {
         assert_eq!(packet[9], 0x82);
     }
 
-    #[test]
-    fn test_parse_broker_url() {
-        assert_eq!(
-            parse_broker_url("tcp://192.168.1.1:1883"),
-            ("192.168.1.1".into(), 1883, false)
-        );
-        assert_eq!(
-            parse_broker_url("192.168.1.1:1884"),
-            ("192.168.1.1".into(), 1884, false)
-        );
-        assert_eq!(
-            parse_broker_url("ssl://broker:8883"),
-            ("broker".into(), 8883, true)
-        );
-    }
-
-    #[test]
-    fn test_parse_broker_url_defaults() {
-        assert_eq!(parse_broker_url("myhost"), ("myhost".into(), 1883, false));
-        assert_eq!(
-            parse_broker_url("ws://broker:9001"),
-            ("broker".into(), 9001, false)
-        );
-        assert_eq!(
-            parse_broker_url("wss://broker:9002"),
-            ("broker".into(), 9002, true)
-        );
-    }
-
-    #[test]
-    fn test_parse_broker_url_ipv6_bracketed() {
-        assert_eq!(
-            parse_broker_url("tcp://[::1]:1883"),
-            ("::1".into(), 1883, false)
-        );
-        assert_eq!(
-            parse_broker_url("ssl://[fe80::1]:8883"),
-            ("fe80::1".into(), 8883, true)
-        );
-        // No scheme + no port
-        assert_eq!(parse_broker_url("[::1]"), ("::1".into(), 1883, false));
-        // Bracketed + TLS scheme + missing port → TLS default
-        assert_eq!(parse_broker_url("ssl://[::1]"), ("::1".into(), 8883, true));
-    }
-
-    #[test]
-    fn test_parse_broker_url_tls_default_ports() {
-        // ssl:// without port should default to 8883
-        assert_eq!(
-            parse_broker_url("ssl://mybroker"),
-            ("mybroker".into(), 8883, true)
-        );
-        // wss:// without port should default to 8883
-        assert_eq!(
-            parse_broker_url("wss://mybroker"),
-            ("mybroker".into(), 8883, true)
-        );
-        // tcp:// without port should default to 1883
-        assert_eq!(
-            parse_broker_url("tcp://mybroker"),
-            ("mybroker".into(), 1883, false)
-        );
-    }
-
     // -----------------------------------------------------------------------
     // Integration tests - sync MQTT publish against a mini-broker
     // -----------------------------------------------------------------------
@@ -530,7 +488,8 @@ mod tests {
                 user: String::new(),
                 pass: String::new(),
                 client_id: "test-sleep".into(),
-                sleep_topic: "homeassistant/sensor/test-pc/sleep_state/state".into(),
+                sleep_topic: "pc-bridge/test-pc/sleep_state/state".into(),
+                availability_topic: "test/availability".into(),
             };
 
             let broker_handle = std::thread::spawn(move || run_mini_broker(listener));
@@ -539,13 +498,41 @@ mod tests {
             assert!(result.is_ok(), "sync publish failed: {:?}", result.err());
 
             let received = broker_handle.join().unwrap();
-            assert_eq!(received.len(), 1, "Expected 1 publish, got {received:?}");
-            assert_eq!(
-                received[0].topic,
-                "homeassistant/sensor/test-pc/sleep_state/state"
-            );
+            assert_eq!(received.len(), 2, "Expected 2 publishes, got {received:?}");
+            assert_eq!(received[0].topic, "pc-bridge/test-pc/sleep_state/state");
             assert_eq!(received[0].payload, b"sleeping");
             assert!(received[0].retain, "Sleep message must be retained");
+            // Planned power-down, not a crash: availability goes offline too.
+            assert_eq!(received[1].topic, "test/availability");
+            assert_eq!(received[1].payload, b"offline");
+            assert!(received[1].retain, "Availability must be retained");
+        }
+
+        #[test]
+        fn sync_publish_power_down_off() {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+
+            let cfg = SyncMqttConfig {
+                host: "127.0.0.1".into(),
+                port,
+                use_tls: false,
+                user: String::new(),
+                pass: String::new(),
+                client_id: "test-off".into(),
+                sleep_topic: "test/sleep".into(),
+                availability_topic: "test/availability".into(),
+            };
+
+            let broker_handle = std::thread::spawn(move || run_mini_broker(listener));
+
+            let result = sync_mqtt_publish_power_down(&cfg, "off");
+            assert!(result.is_ok(), "sync publish failed: {:?}", result.err());
+
+            let received = broker_handle.join().unwrap();
+            assert_eq!(received.len(), 2);
+            assert_eq!(received[0].payload, b"off");
+            assert_eq!(received[1].payload, b"offline");
         }
 
         #[test]
@@ -561,6 +548,7 @@ mod tests {
                 pass: "testpass".into(),
                 client_id: "test-auth".into(),
                 sleep_topic: "test/sleep".into(),
+                availability_topic: "test/availability".into(),
             };
 
             let broker_handle = std::thread::spawn(move || run_mini_broker(listener));
@@ -573,7 +561,7 @@ mod tests {
             );
 
             let received = broker_handle.join().unwrap();
-            assert_eq!(received.len(), 1);
+            assert_eq!(received.len(), 2);
             assert_eq!(received[0].payload, b"sleeping");
         }
 
@@ -591,12 +579,28 @@ mod tests {
                 pass: String::new(),
                 client_id: "test-fail".into(),
                 sleep_topic: "test/sleep".into(),
+                availability_topic: "test/availability".into(),
             };
 
             let result = sync_mqtt_publish_sleep(&cfg);
             assert!(result.is_err(), "Should fail when broker is down");
         }
 
+        /// A host with several addresses: a dead one first mustn't stop the
+        /// connect reaching the live one.
+        #[test]
+        fn connect_any_skips_unreachable_address() {
+            let dead = TcpListener::bind("127.0.0.1:0").unwrap();
+            let dead_addr = dead.local_addr().unwrap();
+            drop(dead);
+            let live = TcpListener::bind("127.0.0.1:0").unwrap();
+            let live_addr = live.local_addr().unwrap();
+
+            let stream = connect_any(&[dead_addr, live_addr], Duration::from_secs(2)).unwrap();
+            assert_eq!(stream.peer_addr().unwrap(), live_addr);
+            assert!(connect_any(&[dead_addr], Duration::from_secs(2)).is_err());
+        }
+
         /// The entire sync publish (connect + connack + publish + disconnect)
         /// must complete fast enough to finish inside the wnd_proc handler
         /// before Windows proceeds with suspend. On loopback this should be
@@ -616,6 +620,7 @@ mod tests {
                 pass: String::new(),
                 client_id: "test-timing".into(),
                 sleep_topic: "test/timing".into(),
+                availability_topic: "test/availability".into(),
             };
 
             let start = std::time::Instant::now();
@@ -630,7 +635,7 @@ mod tests {
             );
 
             let received = broker_handle.join().unwrap();
-            assert_eq!(received.len(), 1);
+            assert_eq!(received.len(), 2);
             assert_eq!(received[0].payload, b"sleeping");
         }
 
@@ -688,6 +693,7 @@ mod tests {
                 pass: String::new(),
                 client_id: "test-nic-death".into(),
                 sleep_topic: "test/nic-death".into(),
+                availability_topic: "test/availability".into(),
             };
 
             let result = sync_mqtt_publish_sleep(&cfg);
@@ -747,6 +753,7 @@ mod tests {
                 pass: String::new(),
                 client_id: "test-reject".into(),
                 sleep_topic: "test/sleep".into(),
+                availability_topic: "test/availability".into(),
             };
 
             let result = sync_mqtt_publish_sleep(&cfg);
@@ -785,6 +792,7 @@ mod tests {
                 pass: String::new(),
                 client_id: "test-bad-connack".into(),
                 sleep_topic: "test/sleep".into(),
+                availability_topic: "test/availability".into(),
             };
 
             let result = sync_mqtt_publish_sleep(&cfg);
@@ -822,6 +830,7 @@ mod tests {
                 pass: String::new(),
                 client_id: "test-wrong-type".into(),
                 sleep_topic: "test/sleep".into(),
+                availability_topic: "test/availability".into(),
             };
 
             let result = sync_mqtt_publish_sleep(&cfg);
@@ -0,0 +1,267 @@
+//! What woke the PC: published after resume as attributes of `sleep_state`
+//!
+//! - `wake_reason`: `timer` (a scheduled task or update wake timer),
+//!   `network` (the network adapter, usually a Wake-on-LAN magic packet),
+//!   `power_button`, `device` (keyboard, mouse, other USB) or `unknown`
+//! - `wake_source`: the device or timer owner, when known
+//! - `woke_at`: RFC 3339 time of the resume
+//!
+//! Windows reads `powercfg /lastwake` (the Power Troubleshooter's record of
+//! the last wake). Linux compares the `wakeup_count` of each source under
+//! `/sys/class/wakeup` from before the suspend with after it.
+
+#[cfg(unix)]
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum WakeReason {
+    Timer,
+    Network,
+    PowerButton,
+    Device,
+    Unknown,
+}
+
+impl WakeReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Timer => "timer",
+            Self::Network => "network",
+            Self::PowerButton => "power_button",
+            Self::Device => "device",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct WakeSource {
+    pub reason: WakeReason,
+    pub source: Option<String>,
+}
+
+impl WakeSource {
+    const UNKNOWN: Self = Self {
+        reason: WakeReason::Unknown,
+        source: None,
+    };
+
+    /// `sleep_state` attributes, stamped with the current time.
+    pub fn attributes(&self) -> serde_json::Value {
+        let now = time::OffsetDateTime::now_utc()
+            .format(&time::format_description::well_known::Rfc3339)
+            .unwrap_or_default();
+        serde_json::json!({
+            "wake_reason": self.reason.as_str(),
+            "wake_source": self.source,
+            "woke_at": now,
+        })
+    }
+}
+
+/// Whether a device name looks like a network adapter.
+fn is_network_adapter(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    [
+        "ethernet", "network", "wi-fi", "wifi", "wireless", "wlan", "gbe",
+    ]
+    .iter()
+    .any(|n| name.contains(n))
+}
+
+/// Last wake, from `powercfg /lastwake`.
+#[cfg(windows)]
+pub(super) async fn last_wake() -> WakeSource {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    match tokio::process::Command::new("powercfg")
+        .arg("/lastwake")
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .await
+    {
+        Ok(output) if output.status.success() => {
+            parse_lastwake(&String::from_utf8_lossy(&output.stdout))
+        }
+        Ok(output) => {
+            log::debug!("powercfg /lastwake exited with {}", output.status);
+            WakeSource::UNKNOWN
+        }
+        Err(e) => {
+            log::debug!("powercfg /lastwake failed: {e}");
+            WakeSource::UNKNOWN
+        }
+    }
+}
+
+/// The first wake source in `powercfg /lastwake` output:
+///
+/// ```text
+/// Wake History Count - 1
+/// Wake History [0]
+///   Wake Source Count - 1
+///   Wake Source [0]
+///     Type: Device
+///     Instance Path: PCI\VEN_8086&DEV_15B8&...
+///     Friendly Name: Intel(R) Ethernet Connection (2) I219-V
+/// ```
+///
+/// Timers come as `Type: Wake Timer` with an `Owner:`, the power button as
+/// `Type: Fixed Feature`.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn parse_lastwake(output: &str) -> WakeSource {
+    let mut kind = None;
+    let mut name = None;
+    let mut owner = None;
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "Type" if kind.is_none() => kind = Some(value.to_string()),
+            "Friendly Name" | "Description" if name.is_none() && !value.is_empty() => {
+                name = Some(value.to_string());
+            }
+            "Owner" if owner.is_none() && !value.is_empty() => owner = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    let Some(kind) = kind else {
+        return WakeSource::UNKNOWN;
+    };
+    let kind = kind.to_ascii_lowercase();
+    if kind.contains("timer") {
+        return WakeSource {
+            reason: WakeReason::Timer,
+            source: owner.or(name),
+        };
+    }
+    if kind.contains("fixed feature") {
+        return WakeSource {
+            reason: WakeReason::PowerButton,
+            source: name,
+        };
+    }
+    if kind.contains("device") {
+        let reason = if name.as_deref().is_some_and(is_network_adapter) {
+            WakeReason::Network
+        } else {
+            WakeReason::Device
+        };
+        return WakeSource {
+            reason,
+            source: name,
+        };
+    }
+    WakeSource {
+        reason: WakeReason::Unknown,
+        source: name.or(owner),
+    }
+}
+
+/// `wakeup_count` per wakeup source directory, taken before a suspend.
+#[cfg(unix)]
+pub(super) fn wakeup_counts() -> HashMap<std::path::PathBuf, u64> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/wakeup") else {
+        return HashMap::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let dir = entry.path();
+            let count = std::fs::read_to_string(dir.join("wakeup_count")).ok()?;
+            Some((dir, count.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+/// The source whose `wakeup_count` grew the most since `before`.
+#[cfg(unix)]
+pub(super) fn woken_by(before: &HashMap<std::path::PathBuf, u64>) -> WakeSource {
+    let after = wakeup_counts();
+    let Some(dir) = after
+        .iter()
+        .filter_map(|(dir, &count)| {
+            let grew = count.checked_sub(before.get(dir).copied().unwrap_or_default())?;
+            (grew > 0).then_some((dir, grew))
+        })
+        .max_by_key(|(_, grew)| *grew)
+        .map(|(dir, _)| dir)
+    else {
+        return WakeSource::UNKNOWN;
+    };
+    let name = std::fs::read_to_string(dir.join("name"))
+        .map(|n| n.trim().to_string())
+        .unwrap_or_default();
+    let network = dir.join("device").join("net").is_dir();
+    WakeSource {
+        reason: classify_wakeup(&name, network),
+        source: (!name.is_empty()).then_some(name),
+    }
+}
+
+/// Reason for a Linux wakeup source by name, `network` when its device has a
+/// network interface.
+#[cfg(unix)]
+fn classify_wakeup(name: &str, network: bool) -> WakeReason {
+    let lower = name.to_ascii_lowercase();
+    if lower.contains("rtc") || lower.contains("alarmtimer") {
+        WakeReason::Timer
+    } else if lower.starts_with("pnp0c0c") || lower.starts_with("lnxpwrbn") {
+        WakeReason::PowerButton
+    } else if network || is_network_adapter(name) {
+        WakeReason::Network
+    } else {
+        WakeReason::Device
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lastwake() {
+        let nic = "Wake History Count - 1\r\nWake History [0]\r\n  Wake Source Count - 1\r\n  Wake Source [0]\r\n    Type: Device\r\n    Instance Path: PCI\\VEN_8086&DEV_15B8&SUBSYS_86721043\r\n    Friendly Name: Intel(R) Ethernet Connection (2) I219-V\r\n    Description: Intel(R) Ethernet Connection (2) I219-V\r\n    Manufacturer: Intel\r\n";
+        assert_eq!(
+            parse_lastwake(nic),
+            WakeSource {
+                reason: WakeReason::Network,
+                source: Some("Intel(R) Ethernet Connection (2) I219-V".to_string()),
+            }
+        );
+
+        let timer = "Wake History Count - 1\nWake History [0]\n  Wake Source Count - 1\n  Wake Source [0]\n    Type: Wake Timer\n    Owner: [PROCESS] \\Device\\HarddiskVolume3\\Windows\\System32\\svchost.exe\n    Owner Supplied Reason: Windows will execute 'NT TASK\\Microsoft\\Windows\\UpdateOrchestrator\\Reboot' scheduled task\n";
+        let source = parse_lastwake(timer);
+        assert_eq!(source.reason, WakeReason::Timer);
+        assert!(source.source.unwrap().ends_with("svchost.exe"));
+
+        let button = "Wake History Count - 1\nWake History [0]\n  Wake Source Count - 1\n  Wake Source [0]\n    Type: Fixed Feature\n    Power Button\n";
+        assert_eq!(parse_lastwake(button).reason, WakeReason::PowerButton);
+
+        let mouse = "  Wake Source [0]\n    Type: Device\n    Friendly Name: HID-compliant mouse\n";
+        assert_eq!(parse_lastwake(mouse).reason, WakeReason::Device);
+
+        assert_eq!(
+            parse_lastwake("Wake History Count - 0\n"),
+            WakeSource::UNKNOWN
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_classify_wakeup() {
+        assert_eq!(
+            classify_wakeup("alarmtimer.0.auto", false),
+            WakeReason::Timer
+        );
+        assert_eq!(classify_wakeup("rtc0", false), WakeReason::Timer);
+        assert_eq!(
+            classify_wakeup("PNP0C0C:00", false),
+            WakeReason::PowerButton
+        );
+        assert_eq!(classify_wakeup("0000:03:00.0", true), WakeReason::Network);
+        assert_eq!(classify_wakeup("1-4", false), WakeReason::Device);
+    }
+}
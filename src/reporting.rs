@@ -0,0 +1,139 @@
+//! Reporting switches for privacy-sensitive sensors
+//!
+//! `report_active_window` and `report_games` are HA switches that stop the
+//! foreground window title and the running games from leaving the PC, e.g.
+//! while screen sharing. Off, `active_window` and `runninggames` read
+//! "paused" and no game_started/game_stopped events are sent; game detection
+//! keeps driving perf mode and notification suppression locally. Which
+//! switches are off survives restarts (`feature_state.json`).
+
+use std::collections::BTreeSet;
+use std::sync::LazyLock;
+
+use log::{info, warn};
+use tokio::sync::watch;
+
+use crate::feature_state::FeatureState;
+
+/// State published in place of a paused sensor's value
+pub const PAUSED: &str = "paused";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reported {
+    ActiveWindow,
+    Games,
+}
+
+impl Reported {
+    pub const ALL: [Self; 2] = [Self::ActiveWindow, Self::Games];
+
+    /// Name in `feature_state.json`
+    pub const fn key(self) -> &'static str {
+        match self {
+            Self::ActiveWindow => "active_window",
+            Self::Games => "games",
+        }
+    }
+
+    /// Object id of the switch, also its state topic
+    pub const fn switch(self) -> &'static str {
+        match self {
+            Self::ActiveWindow => "report_active_window",
+            Self::Games => "report_games",
+        }
+    }
+
+    /// Command the switch sends `ON`/`OFF` to
+    pub const fn command(self) -> &'static str {
+        match self {
+            Self::ActiveWindow => "ReportActiveWindow",
+            Self::Games => "ReportGames",
+        }
+    }
+
+    pub fn from_command(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.command() == name)
+    }
+
+    fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.key() == key)
+    }
+}
+
+/// Sensors whose reporting is switched off
+static PAUSED_SET: LazyLock<watch::Sender<BTreeSet<Reported>>> =
+    LazyLock::new(|| watch::channel(BTreeSet::new()).0);
+
+/// Restore the switches from `feature_state.json`.
+pub fn load() {
+    let paused: BTreeSet<Reported> = FeatureState::load()
+        .paused_reporting
+        .iter()
+        .filter_map(|key| Reported::from_key(key))
+        .collect();
+    if !paused.is_empty() {
+        let keys: Vec<&str> = paused.iter().map(|r| r.key()).collect();
+        info!("Reporting paused for: {}", keys.join(", "));
+    }
+    PAUSED_SET.send_replace(paused);
+}
+
+pub fn is_paused(reported: Reported) -> bool {
+    PAUSED_SET.borrow().contains(&reported)
+}
+
+/// Changes whenever a switch flips
+pub fn subscribe() -> watch::Receiver<BTreeSet<Reported>> {
+    PAUSED_SET.subscribe()
+}
+
+/// A reporting switch's command: `ON` reports, `OFF` pauses.
+pub fn command(reported: Reported, payload: &str) {
+    let paused = match payload.trim().to_ascii_uppercase().as_str() {
+        "ON" => false,
+        "OFF" => true,
+        other => {
+            warn!("{}: expected ON or OFF, got {other:?}", reported.command());
+            return;
+        }
+    };
+    let changed = PAUSED_SET.send_if_modified(|set| {
+        if paused {
+            set.insert(reported)
+        } else {
+            set.remove(&reported)
+        }
+    });
+    if !changed {
+        return;
+    }
+    info!(
+        "Reporting of {} {}",
+        reported.key(),
+        if paused { "paused" } else { "resumed" }
+    );
+    let mut feature_state = FeatureState::load();
+    feature_state.paused_reporting = PAUSED_SET
+        .borrow()
+        .iter()
+        .map(|r| r.key().to_string())
+        .collect();
+    if let Err(e) = feature_state.save() {
+        warn!("Failed to record paused reporting in feature_state.json: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reported_names_round_trip() {
+        for reported in Reported::ALL {
+            assert_eq!(Reported::from_command(reported.command()), Some(reported));
+            assert_eq!(Reported::from_key(reported.key()), Some(reported));
+            assert!(crate::commands::is_native_command(reported.command()));
+        }
+        assert_eq!(Reported::from_command("PerfMode"), None);
+    }
+}
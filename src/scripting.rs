@@ -0,0 +1,492 @@
+//! Rhai scripting hooks
+//!
+//! With `scripts_enabled`, every `*.rhai` file in the `scripts` folder next to
+//! userConfig.json is compiled and may define:
+//!
+//! - `on_event(e)`: called for every bridge event (`crate::events`), `e` being
+//!   its JSON payload as a map (`e.event`, `e.game_id`, ...)
+//! - `on_tick()`: called every `TICK`, for sensors derived from others
+//!
+//! and call `command(name, payload)`, `run_after(seconds, name, payload)`,
+//! `cancel(name)`, `sensor(name)` and `set_sensor(name, value)`. Commands go
+//! through the executor like automations' do; `set_sensor` publishes the
+//! `script_<name>` sensor. Scripts are recompiled when a file in the folder
+//! changes. Each call is capped at `MAX_OPERATIONS`, so a runaway loop is an
+//! error rather than a hung agent.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use rhai::{AST, CallFnOptions, Dynamic, Engine, Scope};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{Instant, MissedTickBehavior, interval};
+
+use crate::AppState;
+use crate::config::Config;
+use crate::mqtt::Command;
+
+/// How often `on_tick` runs
+const TICK: Duration = Duration::from_secs(10);
+
+/// Rhai operations one call may take
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// What a script asked for during a call, carried out once it returns
+#[derive(Debug, PartialEq)]
+enum Action {
+    Command {
+        name: String,
+        payload: String,
+        delay: Duration,
+    },
+    /// Drop pending `run_after`s of this command
+    Cancel(String),
+    SetSensor {
+        name: String,
+        value: String,
+    },
+}
+
+type Actions = Arc<Mutex<Vec<Action>>>;
+
+fn push(actions: &Actions, action: Action) {
+    actions
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(action);
+}
+
+/// The loaded scripts, compiled once, and the engine that runs them.
+struct Scripts {
+    engine: Engine,
+    /// (file name, compiled script), in file name order
+    loaded: Vec<(String, AST)>,
+    actions: Actions,
+}
+
+impl Scripts {
+    /// `lookup` backs `sensor(name)`: the sensor's last published value.
+    fn new(lookup: impl Fn(&str) -> Option<String> + Send + Sync + 'static) -> Self {
+        let actions = Actions::default();
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.on_print(|s| info!("[script] {s}"));
+        engine.on_debug(|s, source, _| debug!("[script {}] {s}", source.unwrap_or("?")));
+
+        let a = Arc::clone(&actions);
+        engine.register_fn("command", move |name: &str, payload: &str| {
+            push(
+                &a,
+                Action::Command {
+                    name: name.to_string(),
+                    payload: payload.to_string(),
+                    delay: Duration::ZERO,
+                },
+            );
+        });
+        let a = Arc::clone(&actions);
+        engine.register_fn("command", move |name: &str| {
+            push(
+                &a,
+                Action::Command {
+                    name: name.to_string(),
+                    payload: String::new(),
+                    delay: Duration::ZERO,
+                },
+            );
+        });
+        let a = Arc::clone(&actions);
+        engine.register_fn("run_after", move |secs: i64, name: &str, payload: &str| {
+            push(
+                &a,
+                Action::Command {
+                    name: name.to_string(),
+                    payload: payload.to_string(),
+                    delay: Duration::from_secs(secs.max(0).unsigned_abs()),
+                },
+            );
+        });
+        let a = Arc::clone(&actions);
+        engine.register_fn("run_after", move |secs: i64, name: &str| {
+            push(
+                &a,
+                Action::Command {
+                    name: name.to_string(),
+                    payload: String::new(),
+                    delay: Duration::from_secs(secs.max(0).unsigned_abs()),
+                },
+            );
+        });
+        let a = Arc::clone(&actions);
+        engine.register_fn("cancel", move |name: &str| {
+            push(&a, Action::Cancel(name.to_string()));
+        });
+        let a = Arc::clone(&actions);
+        engine.register_fn("set_sensor", move |name: &str, value: Dynamic| {
+            push(
+                &a,
+                Action::SetSensor {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                },
+            );
+        });
+        // "" for a sensor that never published, so scripts can compare freely.
+        engine.register_fn("sensor", move |name: &str| lookup(name).unwrap_or_default());
+
+        Self {
+            engine,
+            loaded: Vec::new(),
+            actions,
+        }
+    }
+
+    /// Compile `source` as script `name`, replacing any same-named one.
+    fn compile(&mut self, name: &str, source: &str) -> Result<(), String> {
+        let mut ast = self.engine.compile(source).map_err(|e| e.to_string())?;
+        ast.set_source(name);
+        self.loaded.retain(|(n, _)| n != name);
+        self.loaded.push((name.to_string(), ast));
+        self.loaded.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(())
+    }
+
+    /// Replace every script with the `*.rhai` files in `dir` (blocking).
+    fn load(&mut self, dir: &Path) {
+        self.loaded.clear();
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.extension().is_none_or(|ext| ext != "rhai") {
+                continue;
+            }
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let result = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|source| self.compile(&name, &source));
+            if let Err(e) = result {
+                warn!("Script {name} not loaded: {e}");
+            }
+        }
+        info!(
+            "Loaded {} script(s) from {}",
+            self.loaded.len(),
+            dir.display()
+        );
+    }
+
+    /// Call `func` in every script defining it, returning what they asked for.
+    fn call(&self, func: &str, args: Vec<Dynamic>) -> Vec<Action> {
+        let arity = args.len();
+        for (name, ast) in &self.loaded {
+            if !ast
+                .iter_functions()
+                .any(|f| f.name == func && f.params.len() == arity)
+            {
+                continue;
+            }
+            // Only the hook runs; top-level statements aren't evaluated per call.
+            let options = CallFnOptions::new().eval_ast(false);
+            let result = self.engine.call_fn_with_options::<Dynamic>(
+                options,
+                &mut Scope::new(),
+                ast,
+                func,
+                args.clone(),
+            );
+            if let Err(e) = result {
+                warn!("Script {name}: {func} failed: {e}");
+            }
+        }
+        std::mem::take(&mut *self.actions.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn on_event(&self, event: &crate::events::BridgeEvent) -> Vec<Action> {
+        match rhai::serde::to_dynamic(event.to_payload()) {
+            Ok(event) => self.call("on_event", vec![event]),
+            Err(e) => {
+                warn!("Scripts: event not convertible: {e}");
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Sensor names become topic levels.
+fn valid_sensor_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Carries out script actions: runs commands (now or when due) and publishes
+/// the `script_*` sensors.
+struct Runner {
+    state: Arc<AppState>,
+    /// `run_after` commands not yet due
+    pending: Vec<(Instant, Command)>,
+    /// Last value of each script sensor; registered with HA on first publish
+    sensors: HashMap<String, String>,
+}
+
+impl Runner {
+    async fn apply(&mut self, actions: Vec<Action>) {
+        for action in actions {
+            match action {
+                Action::Command {
+                    name,
+                    payload,
+                    delay,
+                } => {
+                    let cmd = Command { name, payload };
+                    if delay.is_zero() {
+                        self.send(cmd).await;
+                    } else {
+                        debug!("Script: {} in {}s", cmd.name, delay.as_secs());
+                        self.pending.push((Instant::now() + delay, cmd));
+                    }
+                }
+                Action::Cancel(name) => self.pending.retain(|(_, cmd)| cmd.name != name),
+                Action::SetSensor { name, value } => {
+                    if !valid_sensor_name(&name) {
+                        warn!("Script: invalid sensor name '{name}' (letters, digits, _)");
+                        continue;
+                    }
+                    let first = !self.sensors.contains_key(&name);
+                    if self.sensors.get(&name) == Some(&value) {
+                        continue;
+                    }
+                    if first {
+                        self.state.mqtt.register_script_sensor(&name).await;
+                    }
+                    self.state
+                        .mqtt
+                        .publish_sensor(&format!("script_{name}"), &value)
+                        .await;
+                    self.sensors.insert(name, value);
+                }
+            }
+        }
+    }
+
+    /// Queue `cmd` on the executor, like a command from HA.
+    async fn send(&self, cmd: Command) {
+        info!("Script: running {}", cmd.name);
+        if self.state.mqtt.command_sender().send(cmd).await.is_err() {
+            warn!("Script: command executor is gone");
+        }
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.pending.iter().map(|(at, _)| *at).min()
+    }
+
+    async fn run_due(&mut self) {
+        let now = Instant::now();
+        let (due, later) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(at, _)| *at <= now);
+        self.pending = later;
+        for (_, cmd) in due {
+            self.send(cmd).await;
+        }
+    }
+}
+
+/// Supervised while `scripts_enabled`.
+pub(crate) async fn run(state: Arc<AppState>) {
+    let dir = match Config::scripts_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            error!("Scripts: {e}");
+            return;
+        }
+    };
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        error!("Scripts: can't create {}: {e}", dir.display());
+        return;
+    }
+
+    let lookup_state = Arc::clone(&state);
+    let mut scripts = Scripts::new(move |name| lookup_state.mqtt.sensor_value(name));
+    scripts.load(&dir);
+
+    // notify isn't async: the watcher lives on a blocking thread until stop_tx drops.
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Event>(10);
+    let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+    let watch_dir = dir.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut watcher = match notify::recommended_watcher(move |res: Result<Event, _>| {
+            if let Ok(event) = res {
+                let _ = tx.blocking_send(event);
+            }
+        }) {
+            Ok(w) => w,
+            Err(e) => {
+                error!("Scripts: failed to create file watcher: {e}");
+                return;
+            }
+        };
+        if let Err(e) = watcher.watch(&watch_dir, RecursiveMode::NonRecursive) {
+            error!("Scripts: failed to watch {}: {e}", watch_dir.display());
+            return;
+        }
+        let _ = stop_rx.recv();
+    });
+
+    let mut runner = Runner {
+        state: Arc::clone(&state),
+        pending: Vec::new(),
+        sensors: HashMap::new(),
+    };
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut events_rx = state.mqtt.subscribe_events();
+    let mut reconnect_rx = state.mqtt.subscribe_reconnect();
+    let mut tick = interval(TICK);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    // Editors emit several events per save; reload 500ms after the last.
+    let mut reload_at: Option<Instant> = None;
+
+    loop {
+        let next_due = runner.next_due();
+        tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => break,
+            () = tokio::time::sleep_until(reload_at.unwrap_or_else(Instant::now)), if reload_at.is_some() => {
+                reload_at = None;
+                scripts.load(&dir);
+            }
+            Some(event) = rx.recv() => {
+                let is_script = event
+                    .paths
+                    .iter()
+                    .any(|p| p.extension().is_some_and(|ext| ext == "rhai"));
+                if is_script && matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+                    reload_at = Some(Instant::now() + Duration::from_millis(500));
+                }
+            }
+            () = tokio::time::sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {
+                runner.run_due().await;
+            }
+            event = events_rx.recv() => match event {
+                Ok(event) => {
+                    let actions = scripts.on_event(&event);
+                    runner.apply(actions).await;
+                }
+                Err(RecvError::Lagged(n)) => debug!("Scripts lagged {n} events"),
+                Err(RecvError::Closed) => break,
+            },
+            // The broker may have lost the script sensors' discovery configs.
+            Ok(()) = reconnect_rx.recv() => {
+                for name in runner.sensors.keys() {
+                    state.mqtt.register_script_sensor(name).await;
+                }
+            }
+            _ = tick.tick() => {
+                let actions = scripts.call("on_tick", Vec::new());
+                runner.apply(actions).await;
+            }
+        }
+    }
+    drop(stop_tx);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::BridgeEvent;
+
+    fn scripts(source: &str) -> Scripts {
+        let mut scripts = Scripts::new(|name| (name == "cpu_usage").then(|| "91.5".to_string()));
+        scripts.compile("test.rhai", source).unwrap();
+        scripts
+    }
+
+    #[test]
+    fn test_event_handler_queues_delayed_command() {
+        let scripts = scripts(
+            r#"
+            fn on_event(e) {
+                if e.event == "game_started" && e.game_id == "cs2" {
+                    run_after(300, "MonitorOff");
+                }
+                if e.event == "game_stopped" {
+                    cancel("MonitorOff");
+                    command("Lock", "now");
+                }
+            }
+            "#,
+        );
+        let started = BridgeEvent::GameStarted {
+            game_id: "cs2".to_string(),
+            name: "Counter-Strike 2".to_string(),
+        };
+        assert_eq!(
+            scripts.on_event(&started),
+            vec![Action::Command {
+                name: "MonitorOff".to_string(),
+                payload: String::new(),
+                delay: Duration::from_mins(5),
+            }]
+        );
+        let stopped = BridgeEvent::GameStopped {
+            game_id: "cs2".to_string(),
+            name: "Counter-Strike 2".to_string(),
+        };
+        assert_eq!(
+            scripts.on_event(&stopped),
+            vec![
+                Action::Cancel("MonitorOff".to_string()),
+                Action::Command {
+                    name: "Lock".to_string(),
+                    payload: "now".to_string(),
+                    delay: Duration::ZERO,
+                },
+            ]
+        );
+        assert!(scripts.on_event(&BridgeEvent::UserIdle).is_empty());
+    }
+
+    #[test]
+    fn test_tick_derives_sensor() {
+        let scripts = scripts(
+            r#"
+            fn on_tick() {
+                let cpu = parse_float(sensor("cpu_usage"));
+                set_sensor("cpu_busy", if cpu > 80.0 { "on" } else { "off" });
+                set_sensor("gpu_seen", sensor("gpu_usage") != "");
+            }
+            "#,
+        );
+        assert_eq!(
+            scripts.call("on_tick", Vec::new()),
+            vec![
+                Action::SetSensor {
+                    name: "cpu_busy".to_string(),
+                    value: "on".to_string(),
+                },
+                Action::SetSensor {
+                    name: "gpu_seen".to_string(),
+                    value: "false".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_runaway_and_broken_scripts() {
+        let mut scripts = scripts("fn on_tick() { loop { } }");
+        // Capped, not hung; nothing queued.
+        assert!(scripts.call("on_tick", Vec::new()).is_empty());
+        assert!(scripts.compile("bad.rhai", "fn on_tick( {").is_err());
+        assert_eq!(scripts.loaded.len(), 1);
+        assert!(valid_sensor_name("cpu_busy"));
+        assert!(!valid_sensor_name("cpu/busy"));
+    }
+}
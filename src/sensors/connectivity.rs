@@ -0,0 +1,354 @@
+//! Connectivity diagnostics
+//!
+//! - `latency`: round trip in ms to the HA host (`ha_api.url`, when the HA
+//!   API is enabled) else the MQTT broker, every `intervals.latency`. An ICMP
+//!   echo where the OS allows it unprivileged (always on Windows; Linux needs
+//!   `net.ipv4.ping_group_range` to cover the user, the default on most
+//!   distros), else the time to open a TCP connection to the port. The
+//!   `target` and `method` attributes say which.
+//! - `public_ip`: external address from api.ipify.org, every
+//!   `intervals.public_ip` (at least 5 min). A failed lookup keeps the last
+//!   address rather than going unavailable.
+
+use log::{debug, info, warn};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::AppState;
+use crate::config::Config;
+
+/// Per-probe timeout, for the echo and the TCP fallback each.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+const PUBLIC_IP_URL: &str = "https://api.ipify.org";
+
+pub struct LatencySensor {
+    state: Arc<AppState>,
+}
+
+impl LatencySensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.latency {
+            return;
+        }
+        let poll_secs = config.intervals.latency.max(5);
+        let target = latency_target(&config);
+        drop(config);
+
+        let Some((host, port)) = target else {
+            warn!("Latency sensor: no broker or HA host to ping");
+            return;
+        };
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut prev_method: Option<&str> = None;
+
+        info!("Latency sensor started ({host}:{port}, every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Latency sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => prev_method = None,
+                _ = tick.tick() => {
+                    let (h, p) = (host.clone(), port);
+                    let result = tokio::task::spawn_blocking(move || measure(&h, p))
+                        .await
+                        .ok()
+                        .flatten();
+                    let (value, method) = match result {
+                        Some((rtt, method)) => {
+                            (format!("{:.1}", rtt.as_secs_f64() * 1000.0), method)
+                        }
+                        None => ("unavailable".to_string(), "none"),
+                    };
+                    // The round trip changes every poll; only the method
+                    // attribute is worth deduplicating.
+                    self.state.mqtt.publish_sensor("latency", &value).await;
+                    if prev_method != Some(method) {
+                        self.state
+                            .mqtt
+                            .publish_sensor_attributes(
+                                "latency",
+                                &serde_json::json!({ "target": host, "method": method }),
+                            )
+                            .await;
+                        prev_method = Some(method);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Host and port to measure against: the HA host when the HA API is
+/// configured (it's where automations run), else the broker.
+fn latency_target(config: &Config) -> Option<(String, u16)> {
+    if config.ha_api.enabled
+        && let Ok(uri) = config.ha_api.url.parse::<ureq::http::Uri>()
+        && let Some(host) = uri.host()
+    {
+        let default_port = if uri.scheme_str() == Some("https") {
+            443
+        } else {
+            80
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        return Some((host.to_string(), uri.port_u16().unwrap_or(default_port)));
+    }
+    let broker = crate::mqtt::BrokerUrl::parse(&config.mqtt.broker).ok()?;
+    Some((broker.host, broker.port))
+}
+
+/// Round trip to `host`, and how it was measured ("icmp" / "tcp").
+fn measure(host: &str, port: u16) -> Option<(Duration, &'static str)> {
+    let addrs: Vec<SocketAddr> = (host, port).to_socket_addrs().ok()?.collect();
+    let v4 = addrs.iter().find_map(|a| match a.ip() {
+        IpAddr::V4(ip) => Some(ip),
+        IpAddr::V6(_) => None,
+    });
+    if let Some(ip) = v4 {
+        match icmp_echo(ip, PROBE_TIMEOUT) {
+            Ok(rtt) => return Some((rtt, "icmp")),
+            Err(e) => debug!("ICMP echo to {ip} failed ({e}), timing a TCP connect"),
+        }
+    }
+    let addr = addrs.first()?;
+    let start = Instant::now();
+    TcpStream::connect_timeout(addr, PROBE_TIMEOUT).ok()?;
+    Some((start.elapsed(), "tcp"))
+}
+
+/// Round trip of one ICMP echo to `host` (a name is resolved first; IPv4
+/// only). Backs `ping` custom sensors.
+pub(crate) fn ping(host: &str, timeout: Duration) -> Result<Duration, String> {
+    let ip = (host, 0)
+        .to_socket_addrs()
+        .map_err(|e| format!("can't resolve {host}: {e}"))?
+        .find_map(|a| match a.ip() {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .ok_or_else(|| format!("{host} has no IPv4 address"))?;
+    icmp_echo(ip, timeout).map_err(|e| format!("no reply from {ip}: {e}"))
+}
+
+/// One echo request over an unprivileged ICMP socket. The kernel fills in
+/// the identifier and checksum.
+#[cfg(unix)]
+fn icmp_echo(ip: Ipv4Addr, timeout: Duration) -> std::io::Result<Duration> {
+    use std::os::fd::{FromRawFd, OwnedFd};
+
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_INET,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::IPPROTO_ICMP,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // Datagram socket semantics are all std needs for connect/send/recv.
+    let sock = std::net::UdpSocket::from(unsafe { OwnedFd::from_raw_fd(fd) });
+    sock.connect((ip, 0))?;
+    sock.set_read_timeout(Some(timeout))?;
+    // Type 8 (echo request), sequence 1.
+    let request = [8u8, 0, 0, 0, 0, 0, 0, 1];
+    let start = Instant::now();
+    sock.send(&request)?;
+    let mut reply = [0u8; 64];
+    loop {
+        let n = sock.recv(&mut reply)?;
+        // Type 0: echo reply. Anything else (unreachable, ...) is skipped.
+        if n >= 8 && reply[0] == 0 {
+            return Ok(start.elapsed());
+        }
+        if start.elapsed() >= timeout {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+    }
+}
+
+/// One echo request via IcmpSendEcho (no admin needed). Its round trip is
+/// whole milliseconds.
+#[cfg(windows)]
+fn icmp_echo(ip: Ipv4Addr, timeout: Duration) -> std::io::Result<Duration> {
+    use windows::Win32::NetworkManagement::IpHelper::{
+        ICMP_ECHO_REPLY, IcmpCloseHandle, IcmpCreateFile, IcmpSendEcho,
+    };
+
+    let data = [0u8; 32];
+    // Reply struct + echoed data + room for an ICMP error.
+    let mut reply = vec![0u8; size_of::<ICMP_ECHO_REPLY>() + data.len() + 8];
+    let handle = unsafe { IcmpCreateFile() }.map_err(std::io::Error::other)?;
+    let replies = unsafe {
+        IcmpSendEcho(
+            handle,
+            u32::from_ne_bytes(ip.octets()),
+            data.as_ptr().cast(),
+            data.len() as u16,
+            None,
+            reply.as_mut_ptr().cast(),
+            reply.len() as u32,
+            timeout.as_millis() as u32,
+        )
+    };
+    let err = std::io::Error::last_os_error();
+    let _ = unsafe { IcmpCloseHandle(handle) };
+    if replies == 0 {
+        return Err(err);
+    }
+    let reply = unsafe { std::ptr::read_unaligned(reply.as_ptr().cast::<ICMP_ECHO_REPLY>()) };
+    // IP_SUCCESS
+    if reply.Status != 0 {
+        return Err(std::io::Error::other(format!(
+            "ICMP status {}",
+            reply.Status
+        )));
+    }
+    Ok(Duration::from_millis(reply.RoundTripTime.into()))
+}
+
+pub struct PublicIpSensor {
+    state: Arc<AppState>,
+}
+
+impl PublicIpSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.public_ip {
+            return;
+        }
+        // A third-party service: keep it rare.
+        let poll_secs = config.intervals.public_ip.max(300);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut last: Option<IpAddr> = None;
+        let mut published = false;
+
+        info!("Public IP sensor started (checked every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Public IP sensor shutting down");
+                    break;
+                }
+                // Republish the cached address rather than looking it up again.
+                Ok(()) = reconnect_rx.recv() => published = false,
+                _ = tick.tick() => {
+                    match tokio::task::spawn_blocking(fetch_public_ip).await {
+                        Ok(Ok(ip)) => {
+                            if last != Some(ip) {
+                                info!("Public IP: {ip}");
+                                last = Some(ip);
+                                published = false;
+                            }
+                        }
+                        Ok(Err(e)) => debug!("Public IP lookup failed: {e}"),
+                        Err(e) => debug!("Public IP lookup panicked: {e}"),
+                    }
+                }
+            }
+            if !published && let Some(ip) = last {
+                self.state
+                    .mqtt
+                    .publish_sensor("public_ip", &ip.to_string())
+                    .await;
+                self.state
+                    .mqtt
+                    .publish_sensor_attributes(
+                        "public_ip",
+                        &serde_json::json!({ "ipv6": ip.is_ipv6() }),
+                    )
+                    .await;
+                published = true;
+            }
+        }
+    }
+}
+
+fn fetch_public_ip() -> Result<IpAddr, String> {
+    use ureq::tls::{RootCerts, TlsConfig, TlsProvider};
+
+    let tls = TlsConfig::builder()
+        .provider(TlsProvider::NativeTls)
+        .root_certs(RootCerts::PlatformVerifier)
+        .build();
+    let config = ureq::Agent::config_builder()
+        .tls_config(tls)
+        .timeout_global(Some(std::time::Duration::from_secs(10)))
+        .build();
+    let body = ureq::Agent::new_with_config(config)
+        .get(PUBLIC_IP_URL)
+        .call()
+        .map_err(|e| e.to_string())?
+        .body_mut()
+        .read_to_string()
+        .map_err(|e| e.to_string())?;
+    parse_ip(&body).ok_or_else(|| format!("unexpected response {:?}", body.trim()))
+}
+
+/// The service answers with the bare address; anything else (a captive
+/// portal page, an error body) is rejected.
+fn parse_ip(body: &str) -> Option<IpAddr> {
+    body.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ip() {
+        assert_eq!(
+            parse_ip("203.0.113.7\n"),
+            Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)))
+        );
+        assert!(parse_ip("2001:db8::1").is_some_and(|ip| ip.is_ipv6()));
+        assert!(parse_ip("<html>Log in to Wi-Fi</html>").is_none());
+        assert!(parse_ip("").is_none());
+    }
+
+    #[test]
+    fn test_latency_target() {
+        let mut config = Config::default();
+        config.mqtt.broker = "tcp://192.168.1.5:1883".to_string();
+        assert_eq!(
+            latency_target(&config),
+            Some(("192.168.1.5".to_string(), 1883))
+        );
+
+        config.ha_api.enabled = true;
+        config.ha_api.url = "https://ha.example.com".to_string();
+        assert_eq!(
+            latency_target(&config),
+            Some(("ha.example.com".to_string(), 443))
+        );
+        config.ha_api.url = "http://[fd00::2]:8123".to_string();
+        assert_eq!(latency_target(&config), Some(("fd00::2".to_string(), 8123)));
+    }
+}
@@ -1,12 +1,16 @@
 //! Custom sensor polling - user-defined sensors from config
 
 use log::{debug, info, warn};
+use regex::Regex;
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
 use std::sync::Arc;
 use std::time::Duration;
 
+use super::runner::{Published, Reading};
 use crate::AppState;
-use crate::config::{CustomSensor, CustomSensorType};
+use crate::config::{CustomSensor, CustomSensorType, FolderMetric};
 
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
@@ -27,23 +31,237 @@ fn contains_ignore_ascii_case(haystack: &str, needle: &str) -> bool {
         .any(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
 }
 
+/// Most a `log_tail` sensor reads per poll; a log that grew by more since
+/// the last poll is read from this far before its end.
+const LOG_TAIL_MAX_READ: u64 = 1024 * 1024;
+
+/// HA caps sensor state at 255 characters.
+const STATE_MAX: usize = 255;
+
+/// Which file a `log_tail` path points at. A rotated log (renamed away and
+/// recreated) is a different file under the same path.
+#[cfg(unix)]
+type FileIdentity = (u64, u64);
+
+/// Windows has no stable file index in std, so the creation time stands in;
+/// a truncated log is caught by its size either way.
+#[cfg(windows)]
+type FileIdentity = std::time::SystemTime;
+
+#[cfg(unix)]
+fn file_identity(meta: &std::fs::Metadata) -> Option<FileIdentity> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(windows)]
+fn file_identity(meta: &std::fs::Metadata) -> Option<FileIdentity> {
+    meta.created().ok()
+}
+
+/// Read position in one `log_tail` file, kept between polls
+struct LogTail {
+    path: String,
+    regex: Regex,
+    /// Whether the file was read before
+    opened: bool,
+    identity: Option<FileIdentity>,
+    offset: u64,
+    /// Bytes after the last newline, waiting for the rest of their line.
+    /// None while inside a line that's being skipped.
+    partial: Option<Vec<u8>>,
+    last_match: Option<String>,
+}
+
+impl LogTail {
+    fn new(path: &str, pattern: &str) -> Result<Self, String> {
+        Ok(Self {
+            path: path.to_string(),
+            regex: Regex::new(pattern).map_err(|e| e.to_string())?,
+            opened: false,
+            identity: None,
+            offset: 0,
+            partial: None,
+            last_match: None,
+        })
+    }
+
+    fn same_sensor(&self, path: &str, pattern: &str) -> bool {
+        self.path == path && self.regex.as_str() == pattern
+    }
+
+    /// Read what was appended since the last poll and return the last line
+    /// matching the pattern so far. The first read only looks at the end of
+    /// the file; a rotated or truncated log is read again from its start.
+    fn poll(&mut self) -> std::io::Result<Option<String>> {
+        let mut file = File::open(&self.path)?;
+        let meta = file.metadata()?;
+        let identity = file_identity(&meta);
+        let len = meta.len();
+
+        let mut from = self.offset;
+        if !self.opened {
+            from = len.saturating_sub(LOG_TAIL_MAX_READ);
+            self.partial = (from == 0).then(Vec::new);
+        } else if self.identity != identity || len < self.offset {
+            debug!(
+                "log_tail: {} was rotated, reading from the start",
+                self.path
+            );
+            from = 0;
+            self.partial = Some(Vec::new());
+        } else if len - from > LOG_TAIL_MAX_READ {
+            // Fell far behind: skip ahead, dropping the line it lands in.
+            from = len - LOG_TAIL_MAX_READ;
+            self.partial = None;
+        }
+        self.opened = true;
+        self.identity = identity;
+
+        file.seek(SeekFrom::Start(from))?;
+        let mut buf = Vec::new();
+        file.take(len - from).read_to_end(&mut buf)?;
+        self.offset = from + buf.len() as u64;
+
+        let mut lines = buf.split(|&b| b == b'\n');
+        let rest = lines.next_back().unwrap_or_default();
+        for line in lines {
+            if let Some(mut head) = self.partial.replace(Vec::new()) {
+                head.extend_from_slice(line);
+                self.check(&head);
+            }
+        }
+        if let Some(head) = &mut self.partial {
+            head.extend_from_slice(rest);
+            // Without a newline in this long, it's not a log line.
+            if head.len() as u64 > LOG_TAIL_MAX_READ {
+                self.partial = None;
+            }
+        }
+        Ok(self.last_match.clone())
+    }
+
+    fn check(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim_end_matches('\r');
+        if self.regex.is_match(line) {
+            self.last_match = Some(line.trim().chars().take(STATE_MAX).collect());
+        }
+    }
+}
+
+/// A change in a watched folder polls its sensor this long after, so a burst
+/// of events (a file being copied in) reads the folder once.
+const FOLDER_SETTLE: Duration = Duration::from_secs(2);
+
+/// A `folder` sensor's value: its metric over the files directly in `path`
+/// (subfolders aren't counted or entered). Err on an empty folder for `Newest`.
+fn folder_metric(path: &str, metric: FolderMetric) -> Result<String, String> {
+    let mut count = 0u64;
+    let mut size = 0u64;
+    let mut newest: Option<(std::time::SystemTime, String)> = None;
+    for entry in std::fs::read_dir(path)
+        .map_err(|e| e.to_string())?
+        .flatten()
+    {
+        let Ok(meta) = entry.metadata() else {
+            continue; // deleted since read_dir listed it
+        };
+        if !meta.is_file() {
+            continue;
+        }
+        count += 1;
+        size += meta.len();
+        if let Ok(modified) = meta.modified()
+            && newest.as_ref().is_none_or(|(time, _)| modified > *time)
+        {
+            newest = Some((modified, entry.file_name().to_string_lossy().into_owned()));
+        }
+    }
+    match metric {
+        FolderMetric::Count => Ok(count.to_string()),
+        FolderMetric::Size => Ok(format!("{:.1}", size as f64 / (1024.0 * 1024.0))),
+        FolderMetric::Newest => newest
+            .map(|(_, name)| name.chars().take(STATE_MAX).collect())
+            .ok_or_else(|| "folder is empty".to_string()),
+    }
+}
+
 /// Custom sensor manager - polls user-defined sensors
 pub struct CustomSensorManager {
     state: Arc<AppState>,
+    /// `log_tail` read positions by sensor name
+    tails: HashMap<String, LogTail>,
+    /// Watchers on `folder` sensors' directories (kept alive while in use)
+    watchers: Vec<notify::RecommendedWatcher>,
 }
 
 impl CustomSensorManager {
     pub fn new(state: Arc<AppState>) -> Self {
-        Self { state }
+        Self {
+            state,
+            tails: HashMap::new(),
+            watchers: Vec::new(),
+        }
     }
 
-    pub async fn run(self) {
+    /// Watch every `folder` sensor's directory; a change sends the sensor's
+    /// name on `tx`. A folder that can't be watched (e.g. not there yet) is
+    /// still polled every interval.
+    fn watch_folders(&mut self, sensors: &[CustomSensor], tx: &tokio::sync::mpsc::Sender<String>) {
+        use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+        self.watchers.clear();
+        for sensor in sensors {
+            let (CustomSensorType::Folder, Some(folder)) = (&sensor.sensor_type, &sensor.folder)
+            else {
+                continue;
+            };
+            let tx = tx.clone();
+            let name = sensor.name.clone();
+            let watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
+                if let Ok(event) = res
+                    && matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                    )
+                {
+                    // Full channel: a poll for this folder is already on its way.
+                    let _ = tx.try_send(name.clone());
+                }
+            });
+            let mut watcher = match watcher {
+                Ok(w) => w,
+                Err(e) => {
+                    warn!(
+                        "Custom sensor '{}': can't create watcher: {}",
+                        sensor.name, e
+                    );
+                    continue;
+                }
+            };
+            match watcher.watch(std::path::Path::new(folder), RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    debug!("Custom sensor '{}': watching {}", sensor.name, folder);
+                    self.watchers.push(watcher);
+                }
+                Err(e) => warn!(
+                    "Custom sensor '{}': can't watch {}: {}",
+                    sensor.name, folder, e
+                ),
+            }
+        }
+    }
+
+    pub async fn run(mut self) {
         let mut shutdown_rx = self.state.shutdown_tx.subscribe();
         let mut config_rx = self.state.config_generation.subscribe();
 
         // Snapshot config at startup
+        let mut published = Published::default();
         let (mut sensors, mut enabled) = {
             let config = self.state.config.read().await;
+            published.configure(&config);
             (config.custom_sensors.clone(), config.custom_sensors_enabled)
         };
 
@@ -71,6 +289,11 @@ impl CustomSensorManager {
         let now = tokio::time::Instant::now();
         let mut next_due: HashMap<String, tokio::time::Instant> =
             sensors.iter().map(|s| (s.name.clone(), now)).collect();
+        // Perf mode: no polling at all until the game exits.
+        let mut activity_rx = crate::activity::subscribe();
+        let mut paused = activity_rx.borrow_and_update().is_paused();
+        let (folder_tx, mut folder_rx) = tokio::sync::mpsc::channel::<String>(16);
+        self.watch_folders(&sensors, &folder_tx);
 
         loop {
             // Find the earliest next-due sensor to calculate sleep time
@@ -95,20 +318,40 @@ impl CustomSensorManager {
                     let config = self.state.config.read().await;
                     sensors.clone_from(&config.custom_sensors);
                     enabled = config.custom_sensors_enabled;
+                    published.configure(&config);
+                    drop(config);
+                    // Removed entities were torn down; anything re-added must
+                    // publish again.
+                    published.clear();
                     // Reset schedules for new/changed sensors. Only when enabled:
                     // seeding next_due while disabled leaves next_wake permanently
                     // in the past (the tick body `continue`s without advancing it),
                     // busy-looping the whole single-threaded runtime.
                     let reload_now = tokio::time::Instant::now();
                     next_due.clear();
+                    self.tails.retain(|name, _| sensors.iter().any(|s| &s.name == name));
                     if enabled {
                         for s in &sensors {
                             next_due.insert(s.name.clone(), reload_now);
                         }
+                        self.watch_folders(&sensors, &folder_tx);
+                    } else {
+                        self.watchers.clear();
                     }
                     info!("Custom sensors config reloaded ({} sensors, enabled={})", sensors.len(), enabled);
                 }
-                () = tokio::time::sleep_until(next_wake) => {
+                // A watched folder changed: poll it soon, unless it's due sooner.
+                Some(name) = folder_rx.recv() => {
+                    let settled = tokio::time::Instant::now() + FOLDER_SETTLE;
+                    if let Some(due) = next_due.get_mut(&name) {
+                        *due = (*due).min(settled);
+                    }
+                }
+                // Whatever fell due while paused polls as soon as it resumes.
+                Ok(()) = activity_rx.changed() => {
+                    paused = activity_rx.borrow_and_update().is_paused();
+                }
+                () = tokio::time::sleep_until(next_wake), if !paused => {
                     if !enabled {
                         continue;
                     }
@@ -119,21 +362,21 @@ impl CustomSensorManager {
                         let due = next_due.get(&sensor.name).copied().unwrap_or(poll_now);
                         if poll_now >= due {
                             let topic_name = format!("custom_{}", sensor.name);
-                            match self.poll_sensor(sensor).await {
+                            let value = match self.poll_sensor(sensor).await {
                                 Ok(value) => {
-                                    self.state.mqtt.publish_sensor(&topic_name, &value).await;
                                     debug!("Custom sensor '{}' = {}", sensor.name, value);
+                                    value
                                 }
                                 Err(reason) => {
                                     // Mark the sensor unavailable in HA rather than
                                     // publishing the error text as its value.
                                     debug!("Custom sensor '{}' failed: {}", sensor.name, reason);
-                                    self.state
-                                        .mqtt
-                                        .publish_sensor(&topic_name, "unavailable")
-                                        .await;
+                                    "unavailable".to_string()
                                 }
-                            }
+                            };
+                            published
+                                .publish(&self.state, Reading::new(topic_name, value))
+                                .await;
 
                             // Schedule next poll one interval out, but never in the
                             // past: after a long suspend/resume, clamp forward to now
@@ -149,12 +392,16 @@ impl CustomSensorManager {
 
     /// Poll a single custom sensor. `Ok` is the value; `Err` is a failure reason
     /// (published as HA "unavailable", not as the sensor value).
-    async fn poll_sensor(&self, sensor: &CustomSensor) -> Result<String, String> {
+    async fn poll_sensor(&mut self, sensor: &CustomSensor) -> Result<String, String> {
         match sensor.sensor_type {
             CustomSensorType::Powershell => self.poll_powershell(sensor).await,
             CustomSensorType::ProcessExists => self.poll_process_exists(sensor).await,
             CustomSensorType::FileContents => self.poll_file_contents(sensor).await,
             CustomSensorType::Registry => self.poll_registry(sensor).await,
+            CustomSensorType::LogTail => self.poll_log_tail(sensor).await,
+            CustomSensorType::Folder => self.poll_folder(sensor).await,
+            CustomSensorType::Ping => self.poll_ping(sensor).await,
+            CustomSensorType::Service => self.poll_service(sensor).await,
         }
     }
 
@@ -254,6 +501,116 @@ impl CustomSensorManager {
         }
     }
 
+    /// Last line of a log file matching the sensor's pattern
+    async fn poll_log_tail(&mut self, sensor: &CustomSensor) -> Result<String, String> {
+        let path = sensor
+            .file_path
+            .as_deref()
+            .ok_or_else(|| "no file_path".to_string())?;
+        let pattern = sensor
+            .pattern
+            .as_deref()
+            .ok_or_else(|| "no pattern".to_string())?;
+
+        let mut tail = match self.tails.remove(&sensor.name) {
+            Some(tail) if tail.same_sensor(path, pattern) => tail,
+            _ => LogTail::new(path, pattern)?,
+        };
+        let (tail, result) = tokio::task::spawn_blocking(move || {
+            let result = tail.poll();
+            (tail, result)
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+        self.tails.insert(sensor.name.clone(), tail);
+
+        result
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "no matching line yet".to_string())
+    }
+
+    /// File count, total size or newest file of a folder
+    async fn poll_folder(&self, sensor: &CustomSensor) -> Result<String, String> {
+        let folder = sensor
+            .folder
+            .clone()
+            .ok_or_else(|| "no folder".to_string())?;
+        let metric = sensor.metric;
+
+        tokio::task::spawn_blocking(move || folder_metric(&folder, metric))
+            .await
+            .map_err(|e| e.to_string())?
+    }
+
+    /// Round trip to a host in ms; unavailable when it doesn't answer
+    async fn poll_ping(&self, sensor: &CustomSensor) -> Result<String, String> {
+        let host = sensor
+            .host
+            .as_deref()
+            .map(str::trim)
+            .ok_or_else(|| "no host".to_string())?
+            .to_string();
+        let timeout = Duration::from_millis(sensor.timeout_ms.unwrap_or(2000));
+
+        let rtt = tokio::task::spawn_blocking(move || super::connectivity::ping(&host, timeout))
+            .await
+            .map_err(|e| e.to_string())??;
+        Ok(format!("{:.1}", rtt.as_secs_f64() * 1000.0))
+    }
+
+    /// Whether a Windows service is running, from the service control manager
+    #[cfg(windows)]
+    async fn poll_service(&self, sensor: &CustomSensor) -> Result<String, String> {
+        let service = sensor
+            .service
+            .clone()
+            .ok_or_else(|| "no service".to_string())?;
+
+        let running = tokio::task::spawn_blocking(move || {
+            use windows::Win32::System::Services::{
+                CloseServiceHandle, OpenSCManagerW, OpenServiceW, QueryServiceStatus,
+                SC_MANAGER_CONNECT, SERVICE_QUERY_STATUS, SERVICE_RUNNING, SERVICE_STATUS,
+            };
+            use windows::core::{HSTRING, PCWSTR};
+
+            let name = HSTRING::from(service.as_str());
+            unsafe {
+                let scm = OpenSCManagerW(PCWSTR::null(), PCWSTR::null(), SC_MANAGER_CONNECT)
+                    .map_err(|e| e.to_string())?;
+                let result = OpenServiceW(scm, &name, SERVICE_QUERY_STATUS).and_then(|svc| {
+                    let mut status = SERVICE_STATUS::default();
+                    let queried = QueryServiceStatus(svc, &mut status);
+                    let _ = CloseServiceHandle(svc);
+                    queried.map(|()| status.dwCurrentState == SERVICE_RUNNING)
+                });
+                let _ = CloseServiceHandle(scm);
+                result.map_err(|e| format!("{service}: {e}"))
+            }
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        Ok(if running { "on" } else { "off" }.to_string())
+    }
+
+    /// Whether a systemd unit is active (`systemctl is-active`)
+    #[cfg(unix)]
+    async fn poll_service(&self, sensor: &CustomSensor) -> Result<String, String> {
+        let service = sensor
+            .service
+            .clone()
+            .ok_or_else(|| "no service".to_string())?;
+
+        let mut cmd = tokio::process::Command::new("systemctl");
+        cmd.args(["is-active", "--quiet", &service])
+            .kill_on_drop(true);
+        match tokio::time::timeout(Duration::from_secs(5), cmd.status()).await {
+            Ok(Ok(status)) => Ok(if status.success() { "on" } else { "off" }.to_string()),
+            Ok(Err(e)) => Err(e.to_string()),
+            Err(_) => Err("systemctl timed out".to_string()),
+        }
+    }
+
     /// Read registry value (Windows only)
     #[cfg(windows)]
     async fn poll_registry(&self, sensor: &CustomSensor) -> Result<String, String> {
@@ -306,3 +663,72 @@ impl CustomSensorManager {
         Err("registry not available on this platform".to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &std::path::Path, text: &str) {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_folder_metric() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_str().unwrap();
+        assert_eq!(folder_metric(path, FolderMetric::Count).unwrap(), "0");
+        assert!(folder_metric(path, FolderMetric::Newest).is_err());
+
+        std::fs::write(dir.path().join("a.jpg"), vec![0u8; 1024 * 1024]).unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub").join("b.jpg"), "x").unwrap();
+        let newer = dir.path().join("c.jpg");
+        std::fs::write(&newer, vec![0u8; 512 * 1024]).unwrap();
+        let later = std::time::SystemTime::now() + Duration::from_mins(1);
+        File::options()
+            .write(true)
+            .open(&newer)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        assert_eq!(folder_metric(path, FolderMetric::Count).unwrap(), "2");
+        assert_eq!(folder_metric(path, FolderMetric::Size).unwrap(), "1.5");
+        assert_eq!(folder_metric(path, FolderMetric::Newest).unwrap(), "c.jpg");
+    }
+
+    #[test]
+    fn test_log_tail_follows_appends_and_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("server.log");
+        append(&path, "backup done: old\nidle\n");
+        let mut tail = LogTail::new(path.to_str().unwrap(), r"backup (done|failed)").unwrap();
+
+        assert_eq!(tail.poll().unwrap().as_deref(), Some("backup done: old"));
+
+        // A line arrives in two writes; it counts once it's complete.
+        append(&path, "idle\nbackup fai");
+        assert_eq!(tail.poll().unwrap().as_deref(), Some("backup done: old"));
+        append(&path, "led: disk full\r\n");
+        assert_eq!(
+            tail.poll().unwrap().as_deref(),
+            Some("backup failed: disk full")
+        );
+
+        // Rotated: renamed away and recreated, shorter than the read offset.
+        std::fs::rename(&path, dir.path().join("server.log.1")).unwrap();
+        append(&path, "backup done: new\n");
+        assert_eq!(tail.poll().unwrap().as_deref(), Some("backup done: new"));
+
+        // Truncated in place
+        std::fs::write(&path, "").unwrap();
+        append(&path, "backup done\n");
+        assert_eq!(tail.poll().unwrap().as_deref(), Some("backup done"));
+    }
+}
@@ -0,0 +1,480 @@
+//! Peripheral battery sensors - controllers, headsets, mice
+//!
+//! One `device_battery_<slug>` sensor (battery device class, %) per connected
+//! device that reports a battery level, registered with HA the first time it
+//! shows up and polled every `intervals.device_batteries`. Sources:
+//! - Windows: the Bluetooth battery level PnP keeps on paired devices
+//!   (`DEVPKEY_Bluetooth_Battery`, what Settings > Bluetooth shows)
+//! - Linux: `/sys/class/power_supply` entries with `scope` = `Device`, which
+//!   the HID drivers create (hid-playstation, xpadneo, hid-logitech-hidpp,
+//!   hid-steelseries, ...)
+//!
+//! A device that goes away is no longer published, so HA marks it unavailable
+//! once `expire_after` passes. Every slug registered is recorded in
+//! `feature_state.json` so turning the feature off removes them all.
+
+use log::{debug, info, warn};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::AppState;
+use crate::feature_state::FeatureState;
+use crate::mqtt::device_battery_id;
+
+/// A peripheral's battery reading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DeviceBattery {
+    name: String,
+    /// 0-100
+    level: u8,
+    /// Where the reading came from, for the sensor's attributes
+    source: &'static str,
+}
+
+/// Object-id slug for a device name: lowercase ASCII alphanumerics, runs of
+/// anything else collapsed to one `_`. Empty when nothing usable is left.
+pub(crate) fn slug(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            out.push(c.to_ascii_lowercase());
+        } else if !out.is_empty() && !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    while out.ends_with('_') {
+        out.pop();
+    }
+    out
+}
+
+pub struct DeviceBatterySensor {
+    state: Arc<AppState>,
+}
+
+impl DeviceBatterySensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.device_batteries {
+            return;
+        }
+        let poll_secs = config.intervals.device_batteries.max(10);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        // Registered with HA since the last (re)connect
+        let mut registered: HashSet<String> = HashSet::new();
+        // slug -> last published level
+        let mut published: HashMap<String, u8> = HashMap::new();
+
+        info!("Device battery sensor started (polled every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Device battery sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => {
+                    registered.clear();
+                    published.clear();
+                }
+                _ = tick.tick() => {
+                    let devices = tokio::task::spawn_blocking(read_batteries)
+                        .await
+                        .unwrap_or_default();
+                    self.publish(devices, &mut registered, &mut published).await;
+                }
+            }
+        }
+    }
+
+    async fn publish(
+        &self,
+        devices: Vec<DeviceBattery>,
+        registered: &mut HashSet<String>,
+        published: &mut HashMap<String, u8>,
+    ) {
+        let mut present = HashSet::new();
+        for device in devices {
+            let slug = slug(&device.name);
+            // One device can surface on several PnP nodes / power supplies.
+            if slug.is_empty() || !present.insert(slug.clone()) {
+                continue;
+            }
+            if !registered.contains(&slug) {
+                let config = self.state.config.read().await;
+                self.state
+                    .mqtt
+                    .register_device_battery(&slug, &device.name, &config)
+                    .await;
+                drop(config);
+                remember(&slug);
+                registered.insert(slug.clone());
+                info!("Device battery: {} at {}%", device.name, device.level);
+            }
+            if published.get(&slug) == Some(&device.level) {
+                continue;
+            }
+            let object_id = device_battery_id(&slug);
+            self.state
+                .mqtt
+                .publish_sensor(&object_id, &device.level.to_string())
+                .await;
+            let attrs = serde_json::json!({
+                "device": device.name,
+                "source": device.source,
+            });
+            self.state
+                .mqtt
+                .publish_sensor_attributes(&object_id, &attrs)
+                .await;
+            published.insert(slug, device.level);
+        }
+
+        // Gone devices: stop the heartbeat republishing their last level so
+        // the sensor expires in HA.
+        published.retain(|slug, _| {
+            let keep = present.contains(slug);
+            if !keep {
+                debug!("Device battery: {slug} disconnected");
+                self.state.mqtt.forget_value(&device_battery_id(slug));
+            }
+            keep
+        });
+    }
+}
+
+/// Record `slug` in feature_state.json so the entity can be cleaned up later.
+fn remember(slug: &str) {
+    let mut state = FeatureState::load();
+    if state.remember_battery_device(slug)
+        && let Err(e) = state.save()
+    {
+        warn!("Device battery: failed to record {slug} in feature_state.json: {e}");
+    }
+}
+
+/// Battery readings of the connected peripherals. Blocking.
+#[cfg(windows)]
+fn read_batteries() -> Vec<DeviceBattery> {
+    pnp::read()
+}
+
+/// Battery readings of the connected peripherals. Blocking.
+#[cfg(unix)]
+fn read_batteries() -> Vec<DeviceBattery> {
+    read_power_supplies(std::path::Path::new("/sys/class/power_supply"))
+}
+
+/// Peripheral power supplies under `root` (`/sys/class/power_supply`). The
+/// laptop's own battery and the AC adapter have no `scope` or `System`, so
+/// they're skipped.
+#[cfg(unix)]
+fn read_power_supplies(root: &std::path::Path) -> Vec<DeviceBattery> {
+    let Ok(entries) = std::fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut devices = Vec::new();
+    for entry in entries.flatten() {
+        let dir = entry.path();
+        let read = |file: &str| {
+            std::fs::read_to_string(dir.join(file))
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        if read("scope").as_deref() != Some("Device") {
+            continue;
+        }
+        // Some drivers (Logitech HID++ 1.0) only report a coarse level.
+        let level = match read("capacity").and_then(|c| c.parse::<u8>().ok()) {
+            Some(level) => level.min(100),
+            None => match read("capacity_level").as_deref() {
+                Some("Full") => 100,
+                Some("High") => 75,
+                Some("Normal") => 50,
+                Some("Low") => 20,
+                Some("Critical") => 5,
+                _ => continue,
+            },
+        };
+        let name = match (read("manufacturer"), read("model_name")) {
+            (Some(maker), Some(model)) if !model.starts_with(&maker) => {
+                format!("{maker} {model}")
+            }
+            (_, Some(model)) => model,
+            _ => entry.file_name().to_string_lossy().into_owned(),
+        };
+        devices.push(DeviceBattery {
+            name,
+            level,
+            source: "power_supply",
+        });
+    }
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+    devices
+}
+
+/// SetupAPI walk over present devices for the Bluetooth battery property.
+/// Declared locally: the windows crate's SetupAPI bindings aren't enabled and
+/// this needs four calls.
+#[cfg(windows)]
+mod pnp {
+    use super::DeviceBattery;
+    use core::ffi::c_void;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct Guid {
+        data1: u32,
+        data2: u16,
+        data3: u16,
+        data4: [u8; 8],
+    }
+
+    #[repr(C)]
+    struct DevPropKey {
+        fmtid: Guid,
+        pid: u32,
+    }
+
+    #[repr(C)]
+    struct SpDevinfoData {
+        cb_size: u32,
+        class_guid: Guid,
+        dev_inst: u32,
+        reserved: usize,
+    }
+
+    #[link(name = "setupapi")]
+    unsafe extern "system" {
+        fn SetupDiGetClassDevsW(
+            class_guid: *const Guid,
+            enumerator: *const u16,
+            hwnd_parent: *mut c_void,
+            flags: u32,
+        ) -> *mut c_void;
+        fn SetupDiEnumDeviceInfo(
+            device_info_set: *mut c_void,
+            member_index: u32,
+            device_info_data: *mut SpDevinfoData,
+        ) -> i32;
+        fn SetupDiGetDevicePropertyW(
+            device_info_set: *mut c_void,
+            device_info_data: *const SpDevinfoData,
+            property_key: *const DevPropKey,
+            property_type: *mut u32,
+            property_buffer: *mut u8,
+            property_buffer_size: u32,
+            required_size: *mut u32,
+            flags: u32,
+        ) -> i32;
+        fn SetupDiDestroyDeviceInfoList(device_info_set: *mut c_void) -> i32;
+    }
+
+    const DIGCF_PRESENT: u32 = 0x2;
+    const DIGCF_ALLCLASSES: u32 = 0x4;
+    const DEVPROP_TYPE_BYTE: u32 = 0x3;
+    const DEVPROP_TYPE_STRING: u32 = 0x12;
+
+    /// DEVPKEY_Bluetooth_Battery
+    const BLUETOOTH_BATTERY: DevPropKey = DevPropKey {
+        fmtid: Guid {
+            data1: 0x104E_A319,
+            data2: 0x6EE2,
+            data3: 0x4701,
+            data4: [0xBD, 0x47, 0x8D, 0xDB, 0xF4, 0x25, 0xBB, 0xE5],
+        },
+        pid: 2,
+    };
+    const DEVICE_PROPERTIES: Guid = Guid {
+        data1: 0xA45C_254E,
+        data2: 0xDF1C,
+        data3: 0x4EFD,
+        data4: [0x80, 0x20, 0x67, 0xD1, 0x46, 0xA8, 0x50, 0xE0],
+    };
+    /// DEVPKEY_Device_FriendlyName
+    const FRIENDLY_NAME: DevPropKey = DevPropKey {
+        fmtid: DEVICE_PROPERTIES,
+        pid: 14,
+    };
+    /// DEVPKEY_Device_DeviceDesc
+    const DEVICE_DESC: DevPropKey = DevPropKey {
+        fmtid: DEVICE_PROPERTIES,
+        pid: 2,
+    };
+
+    pub(super) fn read() -> Vec<DeviceBattery> {
+        let set = unsafe {
+            SetupDiGetClassDevsW(
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                DIGCF_PRESENT | DIGCF_ALLCLASSES,
+            )
+        };
+        // INVALID_HANDLE_VALUE
+        if set as isize == -1 {
+            return Vec::new();
+        }
+        let mut devices = Vec::new();
+        for index in 0u32.. {
+            let mut info = SpDevinfoData {
+                cb_size: size_of::<SpDevinfoData>() as u32,
+                class_guid: Guid {
+                    data1: 0,
+                    data2: 0,
+                    data3: 0,
+                    data4: [0; 8],
+                },
+                dev_inst: 0,
+                reserved: 0,
+            };
+            if unsafe { SetupDiEnumDeviceInfo(set, index, &raw mut info) } == 0 {
+                break; // ERROR_NO_MORE_ITEMS
+            }
+            let mut level = [0u8; 1];
+            if property(
+                set,
+                &info,
+                &BLUETOOTH_BATTERY,
+                DEVPROP_TYPE_BYTE,
+                &mut level,
+            )
+            .is_none()
+            {
+                continue;
+            }
+            let Some(name) = string_property(set, &info, &FRIENDLY_NAME)
+                .or_else(|| string_property(set, &info, &DEVICE_DESC))
+            else {
+                continue;
+            };
+            devices.push(DeviceBattery {
+                name,
+                level: level[0].min(100),
+                source: "bluetooth",
+            });
+        }
+        unsafe { SetupDiDestroyDeviceInfoList(set) };
+        devices
+    }
+
+    /// Read a property of type `expected` into `buf`; bytes written.
+    fn property(
+        set: *mut c_void,
+        info: &SpDevinfoData,
+        key: &DevPropKey,
+        expected: u32,
+        buf: &mut [u8],
+    ) -> Option<usize> {
+        let mut prop_type = 0u32;
+        let mut size = 0u32;
+        let ok = unsafe {
+            SetupDiGetDevicePropertyW(
+                set,
+                info,
+                key,
+                &raw mut prop_type,
+                buf.as_mut_ptr(),
+                buf.len() as u32,
+                &raw mut size,
+                0,
+            )
+        };
+        (ok != 0 && prop_type == expected).then_some(size as usize)
+    }
+
+    fn string_property(set: *mut c_void, info: &SpDevinfoData, key: &DevPropKey) -> Option<String> {
+        let mut buf = [0u8; 512];
+        let len = property(set, info, key, DEVPROP_TYPE_STRING, &mut buf)?.min(buf.len());
+        let wide: Vec<u16> = buf[..len]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        let name = String::from_utf16_lossy(&wide).trim().to_string();
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(
+            slug("DualSense Wireless Controller"),
+            "dualsense_wireless_controller"
+        );
+        assert_eq!(
+            slug("Xbox Wireless Controller (2)"),
+            "xbox_wireless_controller_2"
+        );
+        assert_eq!(slug("  -- "), "");
+        assert_eq!(slug("Arctis Nova 7 "), "arctis_nova_7");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_power_supplies() {
+        let root = tempfile::tempdir().unwrap();
+        let supply = |dir: &str, files: &[(&str, &str)]| {
+            let path = root.path().join(dir);
+            std::fs::create_dir(&path).unwrap();
+            for (file, content) in files {
+                std::fs::write(path.join(file), format!("{content}\n")).unwrap();
+            }
+        };
+        // Laptop battery: no scope, skipped.
+        supply("BAT0", &[("capacity", "80"), ("model_name", "5B10W51867")]);
+        supply(
+            "ps-controller-battery-aa:bb",
+            &[
+                ("scope", "Device"),
+                ("capacity", "15"),
+                ("manufacturer", "Sony"),
+                ("model_name", "DualSense Wireless Controller"),
+            ],
+        );
+        supply(
+            "hidpp_battery_0",
+            &[
+                ("scope", "Device"),
+                ("capacity_level", "Low"),
+                ("manufacturer", "Logitech"),
+                ("model_name", "Logitech G502"),
+            ],
+        );
+        // Device scope but no level: skipped.
+        supply("hid-unknown-battery", &[("scope", "Device")]);
+
+        let devices = read_power_supplies(root.path());
+        assert_eq!(
+            devices,
+            vec![
+                DeviceBattery {
+                    name: "Logitech G502".to_string(),
+                    level: 20,
+                    source: "power_supply",
+                },
+                DeviceBattery {
+                    name: "Sony DualSense Wireless Controller".to_string(),
+                    level: 15,
+                    source: "power_supply",
+                },
+            ]
+        );
+    }
+}
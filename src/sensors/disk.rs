@@ -6,7 +6,6 @@
 
 use log::{debug, info};
 use std::sync::Arc;
-use tokio::time::{Duration, MissedTickBehavior, interval};
 
 use crate::AppState;
 
@@ -27,6 +26,7 @@ impl DiskSensor {
         let paths = config.disk_sensor_paths.clone();
         // Disk has its own interval now (defaults to 60s; disk changes slowly).
         let poll_secs = config.intervals.disk.max(1);
+        let idle_secs = config.intervals.idle_poll;
         drop(config);
 
         if paths.is_empty() {
@@ -34,8 +34,9 @@ impl DiskSensor {
             return;
         }
 
-        let mut tick = interval(Duration::from_secs(poll_secs));
-        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut activity_rx = crate::activity::subscribe();
+        let activity = *activity_rx.borrow_and_update();
+        let mut tick = crate::activity::ticker(poll_secs, idle_secs, activity);
         let mut shutdown_rx = self.state.shutdown_tx.subscribe();
         let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
         let mut prev_state = String::new();
@@ -56,7 +57,16 @@ impl DiskSensor {
                 Ok(()) = reconnect_rx.recv() => {
                     prev_state.clear();
                 }
+                Ok(()) = activity_rx.changed() => {
+                    // Idle rate while away, nothing while paused; back to
+                    // normal (with an immediate poll) on activity.
+                    let activity = *activity_rx.borrow_and_update();
+                    tick = crate::activity::ticker(poll_secs, idle_secs, activity);
+                }
                 _ = tick.tick() => {
+                    if activity_rx.borrow().is_paused() {
+                        continue;
+                    }
                     // GetDiskFreeSpaceExW/statvfs can block for seconds on a
                     // network or spun-down drive; keep it off the runtime.
                     let paths_snapshot = paths.clone();
@@ -0,0 +1,415 @@
+//! Disk SMART health sensors
+//!
+//! Per physical disk, registered the first time it's seen and polled every
+//! `intervals.disk_health`, all as diagnostic entities:
+//! - `disk_health_<slug>`: problem binary sensor, "on" when the drive's own
+//!   SMART verdict is failing; attributes carry model, serial and power-on
+//!   hours
+//! - `disk_temperature_<slug>`: °C, when the drive reports it
+//! - `disk_reallocated_<slug>`: reallocated sector count (SATA attribute 5;
+//!   NVMe drives have none)
+//!
+//! Sources: `smartctl` (smartmontools) JSON when it's installed - the only
+//! option on Linux, where it needs root - else on Windows the Storage
+//! Management WMI classes (health status always, temperature and power-on
+//! hours from the reliability counters when the agent is elevated). smartctl
+//! runs with `-n standby`, so a sleeping hard drive isn't spun up; it keeps
+//! its last values until it wakes.
+//!
+//! A disk that goes away stops publishing and expires in HA. Every slug is
+//! recorded in `feature_state.json` so turning the feature off removes them.
+
+use log::{debug, info, warn};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use super::device_battery::slug;
+use crate::AppState;
+use crate::feature_state::FeatureState;
+use crate::mqtt::{disk_health_id, disk_reallocated_id, disk_temperature_id};
+
+/// One disk's SMART summary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DiskHealth {
+    model: String,
+    serial: Option<String>,
+    /// `/dev/sda`, `\\.\PhysicalDrive0`, ...
+    device: String,
+    /// SMART overall assessment; `None` when the drive didn't give one
+    passed: Option<bool>,
+    /// °C
+    temperature: Option<i64>,
+    reallocated: Option<u64>,
+    power_on_hours: Option<u64>,
+    source: &'static str,
+}
+
+impl DiskHealth {
+    /// Model plus the serial's last four characters, so two drives of the
+    /// same model get separate entities.
+    fn slug(&self) -> String {
+        let tail = self.serial.as_deref().map_or("", |s| {
+            let start = s.char_indices().rev().nth(3).map_or(0, |(i, _)| i);
+            &s[start..]
+        });
+        slug(&format!("{} {tail}", self.model))
+    }
+}
+
+pub struct DiskHealthSensor {
+    state: Arc<AppState>,
+}
+
+impl DiskHealthSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.disk_health {
+            return;
+        }
+        let poll_secs = config.intervals.disk_health.max(60);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let activity_rx = crate::activity::subscribe();
+        let mut registered: HashSet<String> = HashSet::new();
+        let mut published: HashMap<String, DiskHealth> = HashMap::new();
+        let mut warned = false;
+
+        info!("Disk health sensor started (polled every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Disk health sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => {
+                    registered.clear();
+                    published.clear();
+                }
+                _ = tick.tick() => {
+                    // SMART reads (WMI / smartctl) wait out perf mode.
+                    if activity_rx.borrow().is_paused() {
+                        continue;
+                    }
+                    let (disks, asleep) = read_disks().await;
+                    if disks.is_empty() && asleep.is_empty() && !warned {
+                        warn!(
+                            "Disk health: no SMART data (install smartmontools; on Linux the \
+                             agent must run as root)"
+                        );
+                        warned = true;
+                    }
+                    self.publish(disks, &asleep, &mut registered, &mut published)
+                        .await;
+                }
+            }
+        }
+    }
+
+    async fn publish(
+        &self,
+        disks: Vec<DiskHealth>,
+        asleep: &[String],
+        registered: &mut HashSet<String>,
+        published: &mut HashMap<String, DiskHealth>,
+    ) {
+        let mqtt = &self.state.mqtt;
+        let mut present = HashSet::new();
+        for disk in disks {
+            let slug = disk.slug();
+            if slug.is_empty() || !present.insert(slug.clone()) {
+                continue;
+            }
+            if !registered.contains(&slug) {
+                let config = self.state.config.read().await;
+                mqtt.register_disk_health(
+                    &slug,
+                    &disk.model,
+                    disk.temperature.is_some(),
+                    disk.reallocated.is_some(),
+                    &config,
+                )
+                .await;
+                drop(config);
+                remember(&slug);
+                registered.insert(slug.clone());
+                info!(
+                    "Disk health: {} ({}) {}",
+                    disk.model,
+                    disk.device,
+                    match disk.passed {
+                        Some(true) => "passed",
+                        Some(false) => "FAILING",
+                        None => "no verdict",
+                    }
+                );
+            }
+            if published.get(&slug) == Some(&disk) {
+                continue;
+            }
+            if disk.passed == Some(false) {
+                warn!("Disk health: {} reports SMART failure", disk.model);
+            }
+            let problem = match disk.passed {
+                Some(true) => "off",
+                Some(false) => "on",
+                None => "unavailable",
+            };
+            mqtt.publish_sensor(&disk_health_id(&slug), problem).await;
+            mqtt.publish_sensor_attributes(
+                &disk_health_id(&slug),
+                &serde_json::json!({
+                    "model": disk.model,
+                    "serial": disk.serial,
+                    "device": disk.device,
+                    "power_on_hours": disk.power_on_hours,
+                    "source": disk.source,
+                }),
+            )
+            .await;
+            if let Some(temp) = disk.temperature {
+                mqtt.publish_sensor(&disk_temperature_id(&slug), &temp.to_string())
+                    .await;
+            }
+            if let Some(count) = disk.reallocated {
+                mqtt.publish_sensor(&disk_reallocated_id(&slug), &count.to_string())
+                    .await;
+            }
+            published.insert(slug, disk);
+        }
+
+        // A drive in standby keeps its last values (the heartbeat republishes
+        // them); one that's gone expires.
+        published.retain(|slug, disk| {
+            let keep = present.contains(slug) || asleep.contains(&disk.device);
+            if !keep {
+                debug!("Disk health: {slug} gone");
+                for id in [
+                    disk_health_id(slug),
+                    disk_temperature_id(slug),
+                    disk_reallocated_id(slug),
+                ] {
+                    mqtt.forget_value(&id);
+                }
+            }
+            keep
+        });
+    }
+}
+
+fn remember(slug: &str) {
+    let mut state = FeatureState::load();
+    if state.remember_smart_disk(slug)
+        && let Err(e) = state.save()
+    {
+        warn!("Disk health: failed to record {slug} in feature_state.json: {e}");
+    }
+}
+
+/// Readable disks, and the devices smartctl found but couldn't read
+/// (standby, mostly).
+async fn read_disks() -> (Vec<DiskHealth>, Vec<String>) {
+    let (disks, asleep) = smartctl::read().await;
+    #[cfg(windows)]
+    if disks.is_empty() && asleep.is_empty() {
+        let disks = tokio::task::spawn_blocking(storage_wmi::read)
+            .await
+            .unwrap_or_default();
+        return (disks, asleep);
+    }
+    (disks, asleep)
+}
+
+mod smartctl {
+    use super::{DiskHealth, parse_smartctl};
+    use serde_json::Value;
+    use tokio::time::Duration;
+
+    async fn run(args: &[&str]) -> Option<Value> {
+        let mut cmd = tokio::process::Command::new("smartctl");
+        cmd.args(args).kill_on_drop(true);
+        #[cfg(windows)]
+        {
+            const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+            cmd.creation_flags(CREATE_NO_WINDOW);
+        }
+        let out = tokio::time::timeout(Duration::from_secs(30), cmd.output())
+            .await
+            .ok()?
+            .ok()?;
+        // The exit status is a bitmask that's non-zero for a failing disk
+        // too, so judge by the JSON instead.
+        serde_json::from_slice(&out.stdout).ok()
+    }
+
+    pub(super) async fn read() -> (Vec<DiskHealth>, Vec<String>) {
+        let Some(scan) = run(&["--scan-open", "--json"]).await else {
+            return (Vec::new(), Vec::new());
+        };
+        let mut disks = Vec::new();
+        let mut asleep = Vec::new();
+        for dev in scan["devices"].as_array().into_iter().flatten() {
+            let (Some(name), Some(kind)) = (dev["name"].as_str(), dev["type"].as_str()) else {
+                continue;
+            };
+            let info = run(&["--all", "--json", "--nocheck=standby", "-d", kind, name]).await;
+            match info.as_ref().and_then(|info| parse_smartctl(name, info)) {
+                Some(disk) => disks.push(disk),
+                None => asleep.push(name.to_string()),
+            }
+        }
+        (disks, asleep)
+    }
+}
+
+/// A disk from `smartctl --all --json`. `None` when smartctl couldn't read
+/// it (standby, no permission, no SMART support).
+fn parse_smartctl(device: &str, info: &Value) -> Option<DiskHealth> {
+    let model = info["model_name"]
+        .as_str()
+        .or_else(|| info["scsi_model_name"].as_str())?
+        .trim()
+        .to_string();
+    let passed = info["smart_status"]["passed"].as_bool();
+    let reallocated = info["ata_smart_attributes"]["table"]
+        .as_array()
+        .and_then(|table| table.iter().find(|a| a["id"] == 5))
+        .and_then(|a| a["raw"]["value"].as_u64());
+    Some(DiskHealth {
+        model,
+        serial: info["serial_number"].as_str().map(|s| s.trim().to_string()),
+        device: device.to_string(),
+        passed,
+        temperature: info["temperature"]["current"].as_i64(),
+        reallocated,
+        power_on_hours: info["power_on_time"]["hours"].as_u64(),
+        source: "smartctl",
+    })
+}
+
+/// Storage Management API (`ROOT\Microsoft\Windows\Storage`), what
+/// Get-PhysicalDisk / Get-StorageReliabilityCounter read.
+#[cfg(windows)]
+mod storage_wmi {
+    use super::DiskHealth;
+    use serde::Deserialize;
+    use wmi::{COMLibrary, WMIConnection};
+
+    #[derive(Deserialize)]
+    #[serde(rename = "MSFT_PhysicalDisk", rename_all = "PascalCase")]
+    struct PhysicalDisk {
+        device_id: String,
+        friendly_name: Option<String>,
+        serial_number: Option<String>,
+        /// 0 healthy, 1 warning, 2 unhealthy, 5 unknown
+        health_status: u16,
+    }
+
+    /// Only filled in for an elevated caller.
+    #[derive(Deserialize)]
+    #[serde(rename = "MSFT_StorageReliabilityCounter", rename_all = "PascalCase")]
+    struct ReliabilityCounter {
+        device_id: String,
+        temperature: Option<u8>,
+        power_on_hours: Option<u32>,
+    }
+
+    pub(super) fn read() -> Vec<DiskHealth> {
+        let Ok(com) = COMLibrary::new() else {
+            return Vec::new();
+        };
+        let Ok(wmi) = WMIConnection::with_namespace_path("ROOT\\Microsoft\\Windows\\Storage", com)
+        else {
+            return Vec::new();
+        };
+        let Ok(disks) = wmi.query::<PhysicalDisk>() else {
+            return Vec::new();
+        };
+        let counters = wmi.query::<ReliabilityCounter>().unwrap_or_default();
+        disks
+            .into_iter()
+            .map(|disk| {
+                let counter = counters.iter().find(|c| c.device_id == disk.device_id);
+                DiskHealth {
+                    model: disk
+                        .friendly_name
+                        .map(|n| n.trim().to_string())
+                        .unwrap_or_else(|| format!("Disk {}", disk.device_id)),
+                    serial: disk.serial_number.map(|s| s.trim().to_string()),
+                    device: format!("\\\\.\\PhysicalDrive{}", disk.device_id),
+                    passed: match disk.health_status {
+                        0 => Some(true),
+                        1 | 2 => Some(false),
+                        _ => None,
+                    },
+                    // 0 = not reported
+                    temperature: counter
+                        .and_then(|c| c.temperature)
+                        .filter(|&t| t > 0)
+                        .map(i64::from),
+                    reallocated: None,
+                    power_on_hours: counter.and_then(|c| c.power_on_hours).map(u64::from),
+                    source: "storage",
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_smartctl_sata() {
+        let info = serde_json::json!({
+            "model_name": "Samsung SSD 870 EVO 1TB",
+            "serial_number": "S6PTNM0T123456X",
+            "smart_status": { "passed": true },
+            "temperature": { "current": 34 },
+            "power_on_time": { "hours": 12034 },
+            "ata_smart_attributes": { "table": [
+                { "id": 9, "name": "Power_On_Hours", "raw": { "value": 12034 } },
+                { "id": 5, "name": "Reallocated_Sector_Ct", "raw": { "value": 2 } }
+            ]}
+        });
+        let disk = parse_smartctl("/dev/sda", &info).unwrap();
+        assert_eq!(disk.passed, Some(true));
+        assert_eq!(disk.temperature, Some(34));
+        assert_eq!(disk.reallocated, Some(2));
+        assert_eq!(disk.power_on_hours, Some(12034));
+        assert_eq!(disk.slug(), "samsung_ssd_870_evo_1tb_456x");
+    }
+
+    #[test]
+    fn test_parse_smartctl_nvme_and_unreadable() {
+        let info = serde_json::json!({
+            "model_name": "WD_BLACK SN850X 2000GB",
+            "serial_number": "23123A800123",
+            "smart_status": { "passed": false },
+            "temperature": { "current": 51 },
+            "nvme_smart_health_information_log": { "media_errors": 0 }
+        });
+        let disk = parse_smartctl("/dev/nvme0", &info).unwrap();
+        assert_eq!(disk.passed, Some(false));
+        assert_eq!(disk.reallocated, None);
+
+        // Standby / permission denied: no device info in the JSON.
+        let standby = serde_json::json!({
+            "smartctl": { "exit_status": 2 },
+            "device": { "name": "/dev/sdb" }
+        });
+        assert!(parse_smartctl("/dev/sdb", &standby).is_none());
+    }
+}
@@ -0,0 +1,67 @@
+//! Display mode sensor
+//!
+//! Publishes the primary display's mode as the state of the `display_mode`
+//! select (options = the configured `display_modes`), polled every
+//! `intervals.display_mode`: the configured name when the display is in one
+//! of them, else the raw `WxH@Hz`. Switching is the `DisplayMode` command,
+//! which the select sends with the chosen name.
+
+use log::{debug, info};
+use std::sync::Arc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::AppState;
+use crate::power::display_mode;
+
+pub struct DisplayModeSensor {
+    state: Arc<AppState>,
+}
+
+impl DisplayModeSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.display_mode {
+            return;
+        }
+        let poll_secs = config.intervals.display_mode.max(1);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut prev = String::new();
+
+        info!("Display mode sensor started (polled every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Display mode sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => prev.clear(),
+                _ = tick.tick() => {
+                    let value = match display_mode::current().await {
+                        Some(current) => {
+                            // Re-read each tick: modes are hot-reloadable.
+                            let modes = self.state.config.read().await.display_modes.clone();
+                            display_mode::matching(&modes, current)
+                                .map_or_else(|| current.to_string(), |m| m.name.clone())
+                        }
+                        None => "unavailable".to_string(),
+                    };
+                    if value != prev {
+                        self.state.mqtt.publish_sensor("display_mode", &value).await;
+                        prev = value;
+                    }
+                }
+            }
+        }
+    }
+}
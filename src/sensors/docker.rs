@@ -0,0 +1,496 @@
+//! Docker / Podman containers and the `DockerStart` / `DockerStop` commands
+//!
+//! Reads the engine's container list over its local API socket every
+//! `intervals.docker` (and right after a start / stop):
+//! - `docker_containers`: how many containers are running; attributes carry
+//!   the total and the running names
+//! - `docker_<slug>` per `docker.containers` entry: on while the container
+//!   runs, with its state, status text and image as attributes
+//!
+//! The socket is `docker.socket`, else the first of Docker's and Podman's
+//! defaults that answers: `/var/run/docker.sock`, the rootless sockets under
+//! `$XDG_RUNTIME_DIR`, `/run/podman/podman.sock` on Linux; the
+//! `docker_engine` named pipe (Docker Desktop, WSL backend included), then
+//! Podman machine's on Windows. Podman serves the same Docker API.
+//!
+//! `DockerStart` / `DockerStop` take a container name as payload (the
+//! per-container buttons send theirs); only names in `docker.containers` are
+//! accepted.
+
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use super::device_battery::slug;
+use crate::AppState;
+use crate::feature_state::FeatureState;
+use crate::mqtt::docker_container_id;
+
+/// Limit on one API call. A stop waits out the container's own grace period
+/// (10s by default) before answering, so commands get longer.
+const LIST_TIMEOUT: Duration = Duration::from_secs(10);
+const COMMAND_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// Poked after a start / stop so the sensors catch up right away.
+static REFRESH: Notify = Notify::const_new();
+
+/// One entry of `GET /containers/json`
+#[derive(Debug, Clone, Deserialize)]
+struct Container {
+    #[serde(rename = "Names", default)]
+    names: Vec<String>,
+    #[serde(rename = "State", default)]
+    state: String,
+    #[serde(rename = "Status", default)]
+    status: String,
+    #[serde(rename = "Image", default)]
+    image: String,
+}
+
+impl Container {
+    /// Names come with a leading `/`.
+    fn name(&self) -> &str {
+        self.names.first().map_or("", |n| n.trim_start_matches('/'))
+    }
+
+    fn running(&self) -> bool {
+        self.state == "running"
+    }
+}
+
+pub struct DockerSensor {
+    state: Arc<AppState>,
+}
+
+impl DockerSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.docker {
+            return;
+        }
+        let poll_secs = config.intervals.docker.max(5);
+        let configured = config.docker.socket.clone();
+        let watched: Vec<(String, String)> = config
+            .docker
+            .containers
+            .iter()
+            .map(|name| (name.clone(), slug(name)))
+            .collect();
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut registered = false;
+        let mut socket: Option<String> = None;
+        let mut published: HashMap<String, (String, serde_json::Value)> = HashMap::new();
+
+        info!(
+            "Docker sensor started ({} watched containers, polled every {poll_secs}s)",
+            watched.len()
+        );
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Docker sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => {
+                    registered = false;
+                    published.clear();
+                }
+                () = REFRESH.notified() => {}
+                _ = tick.tick() => {}
+            }
+
+            if !registered {
+                self.register(&watched).await;
+                registered = true;
+            }
+
+            let found = socket.clone();
+            let configured = configured.clone();
+            let listed = tokio::time::timeout(
+                LIST_TIMEOUT,
+                tokio::task::spawn_blocking(move || {
+                    let socket = match found {
+                        Some(socket) => socket,
+                        None => find_socket(configured.as_deref())?,
+                    };
+                    let containers = list_containers(&socket)?;
+                    Ok::<_, String>((socket, containers))
+                }),
+            )
+            .await;
+            let containers = match listed {
+                Ok(Ok(Ok((found, containers)))) => {
+                    if socket.as_ref() != Some(&found) {
+                        info!("Docker: using {found}");
+                        socket = Some(found);
+                    }
+                    Some(containers)
+                }
+                Ok(Ok(Err(e))) => {
+                    debug!("Docker: {e}");
+                    socket = None;
+                    None
+                }
+                Ok(Err(e)) => {
+                    debug!("Docker: list task failed: {e}");
+                    None
+                }
+                Err(_) => {
+                    debug!("Docker: engine didn't answer in time");
+                    socket = None;
+                    None
+                }
+            };
+
+            for (id, value, attributes) in readings(containers.as_deref(), &watched) {
+                let entry = (value, attributes);
+                if published.get(&id) == Some(&entry) {
+                    continue;
+                }
+                let mqtt = &self.state.mqtt;
+                mqtt.publish_sensor(&id, &entry.0).await;
+                mqtt.publish_sensor_attributes(&id, &entry.1).await;
+                published.insert(id, entry);
+            }
+        }
+    }
+
+    /// Register each watched container's entities and record them in
+    /// feature_state.json, so they're removed when `docker` is turned off.
+    async fn register(&self, watched: &[(String, String)]) {
+        let config = self.state.config.read().await;
+        for (name, slug) in watched {
+            self.state
+                .mqtt
+                .register_docker_container(slug, name, &config)
+                .await;
+        }
+        drop(config);
+        let mut state = FeatureState::load();
+        let mut changed = false;
+        for (_, slug) in watched {
+            changed |= state.remember_docker_container(slug);
+        }
+        if changed && let Err(e) = state.save() {
+            warn!("Docker: failed to record containers in feature_state.json: {e}");
+        }
+    }
+}
+
+/// `(object id, state, attributes)` to publish. `containers` is None while
+/// the engine can't be reached.
+fn readings(
+    containers: Option<&[Container]>,
+    watched: &[(String, String)],
+) -> Vec<(String, String, serde_json::Value)> {
+    let mut out = Vec::with_capacity(watched.len() + 1);
+    let Some(containers) = containers else {
+        out.push((
+            "docker_containers".to_string(),
+            "unavailable".to_string(),
+            serde_json::json!({}),
+        ));
+        for (_, slug) in watched {
+            out.push((
+                docker_container_id(slug),
+                "off".to_string(),
+                serde_json::json!({ "state": "engine_unreachable" }),
+            ));
+        }
+        return out;
+    };
+
+    let running: Vec<&str> = containers
+        .iter()
+        .filter(|c| c.running())
+        .map(Container::name)
+        .collect();
+    out.push((
+        "docker_containers".to_string(),
+        running.len().to_string(),
+        serde_json::json!({ "total": containers.len(), "running": running }),
+    ));
+    for (name, slug) in watched {
+        let (value, attributes) = match containers.iter().find(|c| c.name() == name) {
+            Some(c) => (
+                if c.running() { "on" } else { "off" },
+                serde_json::json!({ "state": c.state, "status": c.status, "image": c.image }),
+            ),
+            None => ("off", serde_json::json!({ "state": "missing" })),
+        };
+        out.push((docker_container_id(slug), value.to_string(), attributes));
+    }
+    out
+}
+
+/// The `DockerStart` / `DockerStop` command: start or stop the configured
+/// container named by `payload`.
+pub(crate) async fn docker_command(payload: &str, start: bool, state: &AppState) {
+    let name = payload.trim().to_string();
+    let config = state.config.read().await;
+    if !config.docker.containers.contains(&name) {
+        warn!("Docker: '{name}' is not in docker.containers, ignoring");
+        return;
+    }
+    let configured = config.docker.socket.clone();
+    drop(config);
+
+    let action = if start { "start" } else { "stop" };
+    let path = format!("/containers/{name}/{action}");
+    let result = tokio::time::timeout(
+        COMMAND_TIMEOUT,
+        tokio::task::spawn_blocking(move || {
+            let socket = find_socket(configured.as_deref())?;
+            request(&socket, "POST", &path, COMMAND_TIMEOUT).map_err(|e| e.to_string())
+        }),
+    )
+    .await;
+    match result {
+        // 304: already in that state
+        Ok(Ok(Ok((204 | 304, _)))) => info!("Docker: {action} {name}"),
+        Ok(Ok(Ok((status, body)))) => warn!(
+            "Docker: {action} {name} failed ({status}): {}",
+            api_message(&body)
+        ),
+        Ok(Ok(Err(e))) => warn!("Docker: {action} {name} failed: {e}"),
+        Ok(Err(e)) => warn!("Docker: {action} task failed: {e}"),
+        Err(_) => warn!("Docker: {action} {name} timed out"),
+    }
+    REFRESH.notify_one();
+}
+
+/// The `message` of an API error body, else the body itself.
+fn api_message(body: &[u8]) -> String {
+    #[derive(Deserialize)]
+    struct ApiError {
+        message: String,
+    }
+    serde_json::from_slice::<ApiError>(body).map_or_else(
+        |_| String::from_utf8_lossy(body).trim().to_string(),
+        |e| e.message,
+    )
+}
+
+fn list_containers(socket: &str) -> Result<Vec<Container>, String> {
+    let (status, body) = request(socket, "GET", "/containers/json?all=1", LIST_TIMEOUT)
+        .map_err(|e| format!("{socket}: {e}"))?;
+    if status != 200 {
+        return Err(format!(
+            "listing containers: {status} {}",
+            api_message(&body)
+        ));
+    }
+    serde_json::from_slice(&body).map_err(|e| format!("unexpected container list: {e}"))
+}
+
+/// `docker.socket` (a `unix://` / `npipe://` URL or a bare path), else the
+/// first default socket whose engine answers a ping.
+fn find_socket(configured: Option<&str>) -> Result<String, String> {
+    if let Some(socket) = configured.map(str::trim).filter(|s| !s.is_empty()) {
+        return Ok(socket_path(socket));
+    }
+    default_sockets()
+        .into_iter()
+        .find(|socket| {
+            request(socket, "GET", "/_ping", Duration::from_secs(2))
+                .is_ok_and(|(status, _)| status == 200)
+        })
+        .ok_or_else(|| "no Docker or Podman engine found".to_string())
+}
+
+/// Path of a socket given as a Docker-style URL.
+fn socket_path(socket: &str) -> String {
+    if let Some(path) = socket.strip_prefix("unix://") {
+        path.to_string()
+    } else if let Some(path) = socket.strip_prefix("npipe://") {
+        path.replace('/', "\\")
+    } else {
+        socket.to_string()
+    }
+}
+
+#[cfg(unix)]
+fn default_sockets() -> Vec<String> {
+    let mut sockets = vec!["/var/run/docker.sock".to_string()];
+    if let Ok(runtime) = std::env::var("XDG_RUNTIME_DIR") {
+        sockets.push(format!("{runtime}/docker.sock"));
+        sockets.push(format!("{runtime}/podman/podman.sock"));
+    }
+    sockets.push("/run/podman/podman.sock".to_string());
+    sockets
+}
+
+#[cfg(windows)]
+fn default_sockets() -> Vec<String> {
+    vec![
+        r"\\.\pipe\docker_engine".to_string(),
+        r"\\.\pipe\podman-machine-default".to_string(),
+    ]
+}
+
+#[cfg(unix)]
+fn connect(socket: &str, timeout: Duration) -> std::io::Result<std::os::unix::net::UnixStream> {
+    let stream = std::os::unix::net::UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    Ok(stream)
+}
+
+/// Named pipes have no read timeout; callers bound the wait with a
+/// `tokio::time::timeout` around the blocking task instead.
+#[cfg(windows)]
+fn connect(socket: &str, _timeout: Duration) -> std::io::Result<std::fs::File> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(socket)
+}
+
+/// One HTTP/1.0 request (the engine closes the connection after answering,
+/// so the response is everything up to EOF). Returns status and body.
+fn request(
+    socket: &str,
+    method: &str,
+    path: &str,
+    timeout: Duration,
+) -> std::io::Result<(u16, Vec<u8>)> {
+    let mut stream = connect(socket, timeout)?;
+    write!(
+        stream,
+        "{method} {path} HTTP/1.0\r\nHost: docker\r\nContent-Length: 0\r\n\r\n"
+    )?;
+    stream.flush()?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    parse_response(&response)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "bad HTTP response"))
+}
+
+fn parse_response(raw: &[u8]) -> Option<(u16, Vec<u8>)> {
+    let end = raw.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&raw[..end]);
+    let body = &raw[end + 4..];
+    let mut lines = head.lines();
+    let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+    let chunked = lines.any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    Some((
+        status,
+        if chunked {
+            dechunk(body)
+        } else {
+            body.to_vec()
+        },
+    ))
+}
+
+/// Body of a chunked transfer encoding.
+fn dechunk(mut body: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(body.len());
+    while let Some(eol) = body.windows(2).position(|w| w == b"\r\n") {
+        let size = String::from_utf8_lossy(&body[..eol]);
+        let size = size.split(';').next().unwrap_or_default().trim();
+        let Ok(size) = usize::from_str_radix(size, 16) else {
+            break;
+        };
+        if size == 0 {
+            break;
+        }
+        let start = eol + 2;
+        let end = (start + size).min(body.len());
+        out.extend_from_slice(&body[start..end]);
+        body = body.get(end + 2..).unwrap_or_default();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let plain = b"HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n[]";
+        assert_eq!(parse_response(plain), Some((200, b"[]".to_vec())));
+
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            4\r\n[{\"N\r\n6\r\names\":\r\n3\r\n[]}\r\n1\r\n]\r\n0\r\n\r\n";
+        assert_eq!(
+            parse_response(chunked),
+            Some((200, b"[{\"Names\":[]}]".to_vec()))
+        );
+
+        assert_eq!(
+            parse_response(b"HTTP/1.0 204 No Content\r\n\r\n"),
+            Some((204, vec![]))
+        );
+        assert_eq!(parse_response(b"garbage"), None);
+    }
+
+    #[test]
+    fn test_readings() {
+        let containers: Vec<Container> = serde_json::from_str(
+            r#"[
+                {"Names": ["/plex"], "State": "running", "Status": "Up 2 hours", "Image": "plexinc/pms-docker"},
+                {"Names": ["/minecraft"], "State": "exited", "Status": "Exited (0) 1 day ago", "Image": "itzg/minecraft-server"},
+                {"Names": ["/db"], "State": "running", "Status": "Up 5 minutes", "Image": "postgres"}
+            ]"#,
+        )
+        .unwrap();
+        let watched = vec![
+            ("plex".to_string(), "plex".to_string()),
+            ("minecraft".to_string(), "minecraft".to_string()),
+            ("gone".to_string(), "gone".to_string()),
+        ];
+
+        let out = readings(Some(&containers), &watched);
+        assert_eq!(out[0].0, "docker_containers");
+        assert_eq!(out[0].1, "2");
+        assert_eq!(
+            out[0].2,
+            serde_json::json!({ "total": 3, "running": ["plex", "db"] })
+        );
+        assert_eq!(
+            (out[1].0.as_str(), out[1].1.as_str()),
+            ("docker_plex", "on")
+        );
+        assert_eq!(out[1].2["image"], "plexinc/pms-docker");
+        assert_eq!(out[2].1, "off");
+        assert_eq!(out[2].2["state"], "exited");
+        assert_eq!(out[3].2["state"], "missing");
+
+        let down = readings(None, &watched);
+        assert_eq!(down[0].1, "unavailable");
+        assert!(down[1..].iter().all(|(_, value, _)| value == "off"));
+    }
+
+    #[test]
+    fn test_socket_path() {
+        assert_eq!(
+            socket_path("unix:///run/user/1000/podman/podman.sock"),
+            "/run/user/1000/podman/podman.sock"
+        );
+        assert_eq!(
+            socket_path("npipe:////./pipe/docker_engine"),
+            r"\\.\pipe\docker_engine"
+        );
+        assert_eq!(socket_path("/var/run/docker.sock"), "/var/run/docker.sock");
+    }
+}
@@ -0,0 +1,196 @@
+//! Focus mode switch state and app blocking
+//!
+//! `focus_mode` is the HA switch's state, `focus_remaining` the whole minutes
+//! left in the session (0 while off), with the end time as an attribute.
+//! While a session runs, every poll closes the processes named in
+//! `focus.blocked_apps`; on Windows the process watcher's push notifications
+//! poll right when one starts, on Linux the 2s poll catches it.
+
+use log::{debug, info};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tokio::sync::watch;
+
+use super::process_alerts::process_key;
+#[cfg(windows)]
+use super::process_watcher::ProcessChangeNotification;
+use super::runner::{Reading, Sensor};
+use crate::AppState;
+use crate::config::Config;
+#[cfg(windows)]
+use tokio::sync::broadcast;
+
+/// How long a blocked app gets to close on its own before it's terminated
+const CLOSE_GRACE: Duration = Duration::from_secs(3);
+
+pub struct FocusSensor {
+    state: Arc<AppState>,
+    session_rx: watch::Receiver<Option<Instant>>,
+    #[cfg(windows)]
+    process_rx: broadcast::Receiver<ProcessChangeNotification>,
+    /// Lowered, `.exe`-stripped `focus.blocked_apps`
+    blocked: Vec<String>,
+    /// Whether the last poll saw a session, to toast its end
+    was_active: bool,
+}
+
+impl FocusSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self {
+            #[cfg(windows)]
+            process_rx: state.process_watcher.subscribe(),
+            state,
+            session_rx: crate::focus::subscribe(),
+            blocked: Vec::new(),
+            was_active: false,
+        }
+    }
+
+    pub async fn run(self) {
+        super::runner::run(Arc::clone(&self.state), self).await;
+    }
+
+    /// `(pid, name)` of every running process
+    #[cfg(windows)]
+    async fn processes(&self) -> Vec<(u32, String)> {
+        let proc_state = self.state.process_watcher.state();
+        let guard = proc_state.read().await;
+        guard
+            .processes()
+            .map(|(pid, name)| (pid, name.to_string()))
+            .collect()
+    }
+
+    /// `(pid, name)` of every running process
+    #[cfg(unix)]
+    async fn processes(&self) -> Vec<(u32, String)> {
+        tokio::task::spawn_blocking(super::current_processes)
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Close whatever blocked app is running.
+    async fn close_blocked(&self) {
+        if self.blocked.is_empty() {
+            return;
+        }
+        let own_pid = std::process::id();
+        let found: Vec<(u32, String)> = self
+            .processes()
+            .await
+            .into_iter()
+            .filter(|(pid, name)| {
+                *pid > 4 && *pid != own_pid && self.blocked.contains(&process_key(name))
+            })
+            .collect();
+        if found.is_empty() {
+            return;
+        }
+        let names: Vec<&str> = found.iter().map(|(_, name)| name.as_str()).collect();
+        info!("Focus mode: closing {}", names.join(", "));
+        let pids: Vec<u32> = found.iter().map(|(pid, _)| *pid).collect();
+        let _ = tokio::task::spawn_blocking(move || {
+            crate::commands::stop_game::stop(&pids, CLOSE_GRACE)
+        })
+        .await;
+    }
+}
+
+fn readings(remaining: Option<Duration>, now: OffsetDateTime) -> Vec<Reading> {
+    let ends_at = remaining.and_then(|left| {
+        let end = now.replace_nanosecond(0).ok()? + left;
+        end.format(&Rfc3339).ok()
+    });
+    let minutes = remaining.map_or(0, |left| left.as_secs().div_ceil(60));
+    vec![
+        Reading::new("focus_mode", if remaining.is_some() { "ON" } else { "OFF" }).retained(),
+        Reading::new("focus_remaining", minutes.to_string())
+            .retained()
+            .with_attributes(serde_json::json!({ "ends_at": ends_at })),
+    ]
+}
+
+impl Sensor for FocusSensor {
+    const NAME: &'static str = "Focus";
+
+    fn interval(_config: &Config) -> Option<Duration> {
+        Some(Duration::from_secs(2))
+    }
+
+    async fn configure(&mut self) {
+        let config = self.state.config.read().await;
+        self.blocked = config
+            .focus
+            .blocked_apps
+            .iter()
+            .map(|app| process_key(app.trim()))
+            .collect();
+    }
+
+    #[cfg(windows)]
+    async fn event(&mut self) -> Option<()> {
+        tokio::select! {
+            r = self.session_rx.changed() => r.ok(),
+            r = self.process_rx.recv() => match r {
+                Err(broadcast::error::RecvError::Closed) => None,
+                _ => Some(()),
+            },
+        }
+    }
+
+    #[cfg(unix)]
+    async fn event(&mut self) -> Option<()> {
+        self.session_rx.changed().await.ok()
+    }
+
+    async fn poll(&mut self) -> Vec<Reading> {
+        self.session_rx.borrow_and_update();
+        let remaining = crate::focus::remaining();
+        if remaining.is_some() {
+            self.close_blocked().await;
+        } else if self.was_active {
+            debug!("Focus session over");
+            crate::notification::show_in_background(
+                serde_json::json!({
+                    "title": "Focus session over",
+                    "message": "Time for a break.",
+                })
+                .to_string(),
+                String::new(),
+            )
+            .await;
+        }
+        self.was_active = remaining.is_some();
+        readings(remaining, OffsetDateTime::now_utc())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readings() {
+        let now = OffsetDateTime::parse("2026-10-15T12:00:00.400Z", &Rfc3339).unwrap();
+        assert_eq!(
+            readings(None, now),
+            [
+                Reading::new("focus_mode", "OFF").retained(),
+                Reading::new("focus_remaining", "0")
+                    .retained()
+                    .with_attributes(serde_json::json!({ "ends_at": null })),
+            ]
+        );
+        assert_eq!(
+            readings(Some(Duration::from_secs(24 * 60 + 1)), now),
+            [
+                Reading::new("focus_mode", "ON").retained(),
+                Reading::new("focus_remaining", "25")
+                    .retained()
+                    .with_attributes(serde_json::json!({ "ends_at": "2026-10-15T12:24:01Z" })),
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Game artwork image
+//!
+//! When a game starts, publishes its Steam artwork to the `game_art` image
+//! entity for HA picture cards: the store header (else the library capsule)
+//! from Steam's local `appcache/librarycache`, nothing downloaded. The app id
+//! comes from the game's `games` entry (Steam auto-discovery fills it in),
+//! else Steam's `RunningAppID`. Retained, so the image stays on the last game
+//! started; the `game_id` / `name` / `app_id` attributes say which.
+//!
+//! Driven by the `game_started` events, so games the `report_games` switch
+//! hides don't show up here either.
+
+use log::{debug, info};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::AppState;
+use crate::events::BridgeEvent;
+
+/// Largest image published; it has to fit in one MQTT packet (256 KB). Steam's
+/// headers are tens of KB.
+const MAX_BYTES: u64 = 200 * 1024;
+
+pub struct GameArtSensor {
+    state: Arc<AppState>,
+}
+
+impl GameArtSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        if !self.state.config.read().await.features.game_art {
+            return;
+        }
+        let mut events_rx = self.state.mqtt.subscribe_events();
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        // App id of the image last published
+        let mut shown = None;
+
+        info!("Game art sensor started");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Game art sensor shutting down");
+                    break;
+                }
+                event = events_rx.recv() => match event {
+                    Ok(BridgeEvent::GameStarted { game_id, name }) => {
+                        self.game_started(&game_id, &name, &mut shown).await;
+                    }
+                    Ok(_) | Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                },
+            }
+        }
+    }
+
+    async fn game_started(&self, game_id: &str, name: &str, shown: &mut Option<u32>) {
+        let configured = self
+            .state
+            .config
+            .read()
+            .await
+            .games
+            .values()
+            .find(|gc| gc.game_id() == game_id)
+            .and_then(crate::config::GameConfig::app_id);
+        let art = tokio::task::spawn_blocking(move || {
+            let app_id = configured.or_else(crate::steam::running_app_id)?;
+            let path = crate::steam::library_art(app_id)?;
+            Some((app_id, read_image(&path)?))
+        })
+        .await
+        .ok()
+        .flatten();
+        let Some((app_id, image)) = art else {
+            debug!("Game art: no Steam artwork for '{game_id}'");
+            return;
+        };
+        if *shown == Some(app_id) {
+            return;
+        }
+        info!("Game art: {name} (app {app_id})");
+        self.state.mqtt.publish_image("game_art", &image).await;
+        self.state
+            .mqtt
+            .publish_sensor_attributes(
+                "game_art",
+                &serde_json::json!({ "game_id": game_id, "name": name, "app_id": app_id }),
+            )
+            .await;
+        *shown = Some(app_id);
+    }
+}
+
+/// The JPEG at `path`, if it is one and small enough to publish.
+fn read_image(path: &Path) -> Option<Vec<u8>> {
+    if std::fs::metadata(path).ok()?.len() > MAX_BYTES {
+        debug!("Game art: {} is too large to publish", path.display());
+        return None;
+    }
+    let bytes = std::fs::read(path).ok()?;
+    bytes.starts_with(&[0xFF, 0xD8, 0xFF]).then_some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_image_only_takes_small_jpegs() {
+        let dir = tempfile::tempdir().unwrap();
+        let jpeg = dir.path().join("header.jpg");
+        std::fs::write(&jpeg, [0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10]).unwrap();
+        assert!(read_image(&jpeg).is_some());
+
+        let png = dir.path().join("header.png");
+        std::fs::write(&png, b"\x89PNG\r\n\x1a\n").unwrap();
+        assert!(read_image(&png).is_none());
+
+        let large = dir.path().join("large.jpg");
+        let mut bytes = vec![0xFF, 0xD8, 0xFF];
+        bytes.resize(MAX_BYTES as usize + 1, 0);
+        std::fs::write(&large, bytes).unwrap();
+        assert!(read_image(&large).is_none());
+
+        assert!(read_image(&dir.path().join("missing.jpg")).is_none());
+    }
+}
@@ -0,0 +1,405 @@
+//! Gamepad presence sensor
+//!
+//! `gamepad_connected` ("on" while any game controller is attached) and
+//! `gamepad_active` ("on" while one has produced input in the last
+//! `ACTIVE_FOR`), polled every `intervals.gamepad`. A couch-gaming signal
+//! separate from keyboard/mouse idle tracking: the PC can be "idle" for an
+//! hour while someone plays with a controller.
+//! - Windows: XInput slots 0-3 (Xbox and XInput-compatible pads, incl. anything
+//!   Steam Input remaps)
+//! - Linux: evdev nodes advertising gamepad/joystick buttons. Reading input
+//!   needs access to `/dev/input/event*` (granted to the seat user by udev's
+//!   uaccess rule for joysticks); without it the pad still counts as connected.
+
+use log::{debug, info};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::AppState;
+
+/// Input within this long keeps `gamepad_active` on.
+const ACTIVE_FOR: Duration = Duration::from_mins(1);
+
+/// One poll of the attached controllers.
+#[derive(Debug, Default, Clone, Copy)]
+struct Poll {
+    connected: usize,
+    /// Any button / stick / trigger input since the previous poll
+    input: bool,
+}
+
+pub struct GamepadSensor {
+    state: Arc<AppState>,
+}
+
+impl GamepadSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.gamepad {
+            return;
+        }
+        let poll_secs = config.intervals.gamepad.max(1);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut pads = Some(backend::Gamepads::default());
+        let mut last_input: Option<Instant> = None;
+        let mut prev_connected = "";
+        let mut prev_active = "";
+
+        info!("Gamepad sensor started (polled every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Gamepad sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => {
+                    prev_connected = "";
+                    prev_active = "";
+                }
+                _ = tick.tick() => {
+                    // The backend holds open device handles, so it's moved
+                    // into the blocking task and back.
+                    let Some(mut backend) = pads.take() else { break };
+                    let Ok((backend, poll)) = tokio::task::spawn_blocking(move || {
+                        let poll = backend.poll();
+                        (backend, poll)
+                    })
+                    .await
+                    else {
+                        break;
+                    };
+                    pads = Some(backend);
+
+                    if poll.input {
+                        last_input = Some(Instant::now());
+                        crate::activity::note_gamepad_input();
+                    }
+                    let connected = if poll.connected > 0 { "on" } else { "off" };
+                    let active = if poll.connected > 0
+                        && last_input.is_some_and(|t| t.elapsed() < ACTIVE_FOR)
+                    {
+                        "on"
+                    } else {
+                        "off"
+                    };
+                    if connected != prev_connected {
+                        self.state.mqtt.publish_sensor("gamepad_connected", connected).await;
+                        prev_connected = connected;
+                    }
+                    if active != prev_active {
+                        self.state.mqtt.publish_sensor("gamepad_active", active).await;
+                        prev_active = active;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod backend {
+    //! XInput polling. Declared locally: the windows crate's XInput bindings
+    //! aren't enabled and this needs one call.
+    use super::Poll;
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct XInputGamepad {
+        buttons: u16,
+        left_trigger: u8,
+        right_trigger: u8,
+        thumb_lx: i16,
+        thumb_ly: i16,
+        thumb_rx: i16,
+        thumb_ry: i16,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct XInputState {
+        _packet_number: u32,
+        gamepad: XInputGamepad,
+    }
+
+    #[link(name = "xinput")]
+    unsafe extern "system" {
+        fn XInputGetState(user_index: u32, state: *mut XInputState) -> u32;
+    }
+
+    const SLOTS: usize = 4;
+    /// XINPUT_GAMEPAD_LEFT_THUMB_DEADZONE (the larger of the two)
+    const THUMB_DEADZONE: i16 = 7849;
+    /// XINPUT_GAMEPAD_TRIGGER_THRESHOLD
+    const TRIGGER_THRESHOLD: u8 = 30;
+
+    /// What counts as input, with stick drift inside the deadzone and
+    /// trigger noise below the threshold filtered out. `dwPacketNumber`
+    /// alone changes on that noise too.
+    #[derive(PartialEq, Eq, Clone, Copy)]
+    struct Signature {
+        buttons: u16,
+        triggers: (bool, bool),
+        thumbs: [i8; 4],
+    }
+
+    impl Signature {
+        fn of(pad: &XInputGamepad) -> Self {
+            let axis = |v: i16| {
+                if v.unsigned_abs() < THUMB_DEADZONE.unsigned_abs() {
+                    0
+                } else {
+                    (v / 8192) as i8
+                }
+            };
+            Self {
+                buttons: pad.buttons,
+                triggers: (
+                    pad.left_trigger > TRIGGER_THRESHOLD,
+                    pad.right_trigger > TRIGGER_THRESHOLD,
+                ),
+                thumbs: [
+                    axis(pad.thumb_lx),
+                    axis(pad.thumb_ly),
+                    axis(pad.thumb_rx),
+                    axis(pad.thumb_ry),
+                ],
+            }
+        }
+    }
+
+    #[derive(Default)]
+    pub(super) struct Gamepads {
+        /// Per slot: signature at the previous poll (None = not connected)
+        prev: [Option<Signature>; SLOTS],
+    }
+
+    impl Gamepads {
+        pub(super) fn poll(&mut self) -> Poll {
+            let mut poll = Poll::default();
+            for (slot, prev) in self.prev.iter_mut().enumerate() {
+                let mut state = XInputState::default();
+                // ERROR_SUCCESS; ERROR_DEVICE_NOT_CONNECTED otherwise
+                if unsafe { XInputGetState(slot as u32, &raw mut state) } != 0 {
+                    *prev = None;
+                    continue;
+                }
+                poll.connected += 1;
+                let sig = Signature::of(&state.gamepad);
+                // Plugging in isn't input: the first sighting only records it.
+                if prev.is_some_and(|p| p != sig) {
+                    poll.input = true;
+                }
+                *prev = Some(sig);
+            }
+            poll
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod backend {
+    //! evdev: every `/sys/class/input/event*` whose key capabilities include
+    //! BTN_JOYSTICK or BTN_GAMEPAD, read non-blocking.
+    use super::Poll;
+    use log::debug;
+    use std::collections::{HashMap, HashSet};
+    use std::fs::File;
+    use std::io::Read;
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::path::Path;
+
+    const EV_KEY: u16 = 0x01;
+    const EV_ABS: u16 = 0x03;
+    const BTN_JOYSTICK: usize = 0x120;
+    const BTN_GAMEPAD: usize = 0x130;
+    const ABS_CNT: usize = 0x40;
+
+    /// Whether a sysfs `capabilities/key` bitmap (hex words, most significant
+    /// first, each an unsigned long) has bit `bit` set.
+    pub(super) fn has_key_bit(caps: &str, bit: usize) -> bool {
+        let word_bits = usize::BITS as usize;
+        caps.split_whitespace()
+            .rev()
+            .nth(bit / word_bits)
+            .and_then(|w| u64::from_str_radix(w, 16).ok())
+            .is_some_and(|w| w >> (bit % word_bits) & 1 == 1)
+    }
+
+    fn is_gamepad(event_dir: &Path) -> bool {
+        std::fs::read_to_string(event_dir.join("device/capabilities/key"))
+            .is_ok_and(|caps| has_key_bit(&caps, BTN_GAMEPAD) || has_key_bit(&caps, BTN_JOYSTICK))
+    }
+
+    /// EVIOCGABS(code): _IOR('E', 0x40 + code, struct input_absinfo)
+    fn eviocgabs(code: usize) -> u64 {
+        (2 << 30)
+            | ((std::mem::size_of::<libc::input_absinfo>() as u64) << 16)
+            | (u64::from(b'E') << 8)
+            | (0x40 + code as u64)
+    }
+
+    struct Device {
+        file: File,
+        /// Per ABS axis: last value counted and the movement that counts as
+        /// input (an eighth of the range; 0 = axis absent)
+        abs: [(i32, i32); ABS_CNT],
+    }
+
+    impl Device {
+        fn open(node: &Path) -> std::io::Result<Self> {
+            let file = std::fs::OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(node)?;
+            let mut abs = [(0, 0); ABS_CNT];
+            for (code, axis) in abs.iter_mut().enumerate() {
+                let mut info: libc::input_absinfo = unsafe { std::mem::zeroed() };
+                let ok =
+                    unsafe { libc::ioctl(file.as_raw_fd(), eviocgabs(code) as _, &raw mut info) }
+                        == 0;
+                if ok && info.maximum > info.minimum {
+                    *axis = (info.value, (info.maximum - info.minimum) / 8);
+                }
+            }
+            Ok(Self { file, abs })
+        }
+
+        /// Drain pending events. Err = the device went away.
+        fn drain(&mut self) -> std::io::Result<bool> {
+            const EVENT: usize = std::mem::size_of::<libc::input_event>();
+            let mut buf = [0u8; EVENT * 64];
+            let mut input = false;
+            loop {
+                let n = match self.file.read(&mut buf) {
+                    Ok(0) => return Ok(input),
+                    Ok(n) => n,
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(input),
+                    Err(e) => return Err(e),
+                };
+                for chunk in buf[..n].chunks_exact(EVENT) {
+                    let event: libc::input_event =
+                        unsafe { std::ptr::read_unaligned(chunk.as_ptr().cast()) };
+                    match event.type_ {
+                        // Presses, not releases or autorepeat
+                        EV_KEY if event.value == 1 => input = true,
+                        EV_ABS => {
+                            if let Some((last, step)) = self.abs.get_mut(event.code as usize)
+                                && *step > 0
+                                && (event.value - *last).abs() >= *step
+                            {
+                                *last = event.value;
+                                input = true;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    #[derive(Default)]
+    pub(super) struct Gamepads {
+        /// event node name -> open device (None = no read access)
+        open: HashMap<String, Option<Device>>,
+        /// Event nodes checked and found not to be gamepads
+        ignored: HashSet<String>,
+    }
+
+    impl Gamepads {
+        pub(super) fn poll(&mut self) -> Poll {
+            let mut present = HashSet::new();
+            if let Ok(entries) = std::fs::read_dir("/sys/class/input") {
+                for entry in entries.flatten() {
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    if !name.starts_with("event") {
+                        continue;
+                    }
+                    present.insert(name.clone());
+                    if self.open.contains_key(&name) || self.ignored.contains(&name) {
+                        continue;
+                    }
+                    if !is_gamepad(&entry.path()) {
+                        self.ignored.insert(name);
+                        continue;
+                    }
+                    let device = match Device::open(&Path::new("/dev/input").join(&name)) {
+                        Ok(device) => Some(device),
+                        Err(e) => {
+                            debug!("Gamepad {name}: can't read input ({e})");
+                            None
+                        }
+                    };
+                    self.open.insert(name, device);
+                }
+            }
+            // Node numbers are reused after an unplug, so forget vanished ones.
+            self.open.retain(|name, _| present.contains(name));
+            self.ignored.retain(|name| present.contains(name));
+
+            let mut poll = Poll::default();
+            let mut gone = Vec::new();
+            for (name, device) in &mut self.open {
+                poll.connected += 1;
+                if let Some(device) = device {
+                    match device.drain() {
+                        Ok(input) => poll.input |= input,
+                        Err(_) => gone.push(name.clone()),
+                    }
+                }
+            }
+            for name in gone {
+                self.open.remove(&name);
+                poll.connected -= 1;
+            }
+            poll
+        }
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+mod backend {
+    use super::Poll;
+
+    #[derive(Default)]
+    pub(super) struct Gamepads;
+
+    impl Gamepads {
+        pub(super) fn poll(&mut self) -> Poll {
+            Poll::default()
+        }
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::backend::has_key_bit;
+
+    #[test]
+    fn test_has_key_bit() {
+        // BTN_SOUTH..BTN_THUMBR (0x130-0x13e): bits 48-62 of word 4.
+        let gamepad = "7fff000000000000 0 0 0 0";
+        assert!(has_key_bit(gamepad, 0x130));
+        assert!(!has_key_bit(gamepad, 0x120));
+        // BTN_TRIGGER..BTN_BASE6 (0x120-0x12f): bits 32-47 of word 4.
+        let joystick = "ffff00000000 0 0 0 0";
+        assert!(has_key_bit(joystick, 0x120));
+        assert!(!has_key_bit(joystick, 0x130));
+        // Keyboard: only low key codes.
+        assert!(!has_key_bit("ffffffffffffffff", 0x130));
+        assert!(!has_key_bit("", 0x130));
+    }
+}
@@ -4,16 +4,31 @@
 //! 1. Steam auto-discovery (if Steam installed) - uses process name → app_id lookup
 //! 2. Manual config `games` map (pattern → game_id)
 //!
-//! Uses push notifications from ProcessWatcher for instant detection.
+//! Games with a `window_title` or `aumid` are matched against the visible
+//! top-level windows (title / owning Store app) instead of process names, for
+//! emulators and Game Pass titles whose exe name is generic. Running
+//! `emulators` are reported with the game named in their window caption as
+//! the display name.
+//!
+//! Uses push notifications from ProcessWatcher for instant detection, plus a
+//! `game_sensor` interval poll while any game is matched by window (a title
+//! changes without a process starting).
 //!
 //! Also publishes a `game_catalog` sensor listing all exposed games from config.
 
 use log::{debug, info};
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{broadcast, watch};
 
+use super::process_watcher::ProcessChangeNotification;
+use super::runner::{Reading, Sensor};
 use crate::AppState;
+use crate::commands::stop_game::GameProcess;
+use crate::config::{Config, EmulatorConfig, GameConfig};
+use crate::reporting::Reported;
 
 #[derive(Serialize)]
 struct CatalogEntry {
@@ -29,227 +44,455 @@ struct CatalogEntry {
 }
 
 /// Cached lowered game patterns to avoid recomputing on every WMI event
+#[derive(Default)]
 struct CachedGamePatterns {
     /// (lowered_pattern, game_id, display_name)
     patterns: Vec<(String, String, String)>,
+    /// Games matched by window title: (lowered_title_part, game_id, display_name)
+    window_titles: Vec<(String, String, String)>,
+    /// Games matched by Store app: (lowered_aumid_prefix, game_id, display_name)
+    aumids: Vec<(String, String, String)>,
+    emulators: Vec<CachedEmulator>,
+}
+
+/// An `emulators` entry, lowered for matching
+struct CachedEmulator {
+    /// Lowered process pattern
+    pattern: String,
+    /// Lowered window caption prefix
+    title_prefix: String,
+    game_id: String,
+    /// Display name while no game is loaded
+    name: String,
+    config: EmulatorConfig,
 }
 
 impl CachedGamePatterns {
-    fn build(games: &std::collections::HashMap<String, crate::config::GameConfig>) -> Self {
-        let patterns = games
-            .iter()
-            .map(|(pattern, gc)| {
+    fn build(
+        games: &std::collections::HashMap<String, GameConfig>,
+        emulators: &std::collections::HashMap<String, EmulatorConfig>,
+    ) -> Self {
+        let mut cached = Self::default();
+        for (pattern, gc) in games {
+            let entry = |key: &str| {
                 (
-                    pattern.to_lowercase(),
+                    key.to_lowercase(),
                     gc.game_id().to_string(),
                     gc.display_name(),
                 )
+            };
+            if gc.window_title().is_none() && gc.aumid().is_none() {
+                cached.patterns.push(entry(pattern));
+                continue;
+            }
+            if let Some(title) = gc.window_title() {
+                cached.window_titles.push(entry(title));
+            }
+            if let Some(aumid) = gc.aumid() {
+                cached.aumids.push(entry(aumid));
+            }
+        }
+        cached.emulators = emulators
+            .iter()
+            .map(|(pattern, config)| {
+                let game_id = config.game_id(pattern);
+                CachedEmulator {
+                    pattern: pattern.to_lowercase(),
+                    title_prefix: config
+                        .title_prefix
+                        .as_deref()
+                        .unwrap_or(pattern)
+                        .to_lowercase(),
+                    name: GameConfig::Simple(game_id.clone()).display_name(),
+                    game_id,
+                    config: config.clone(),
+                }
             })
             .collect();
-        Self { patterns }
+        cached
+    }
+
+    /// Whether detection needs the window list (see [`game_windows`])
+    fn needs_windows(&self) -> bool {
+        !self.window_titles.is_empty() || !self.aumids.is_empty() || !self.emulators.is_empty()
     }
 }
 
+/// A visible top-level window, with the AppUserModelID of its process when
+/// that is a packaged (Microsoft Store / Game Pass) app
+#[derive(Debug, Default)]
+struct GameWindow {
+    title: String,
+    aumid: Option<String>,
+    /// Owning process, for `StopGame`
+    pid: u32,
+}
+
 pub struct GameSensor {
     state: Arc<AppState>,
+    process_rx: broadcast::Receiver<ProcessChangeNotification>,
+    cached: CachedGamePatterns,
+    /// Retained `game_catalog` reading, rebuilt with the patterns
+    catalog: Option<Reading>,
+    /// Games seen by the last poll, diffed into game_started/stopped events
+    last_games: Vec<(String, String)>,
+    /// The report_games switch, re-polled when it flips
+    reporting_rx: watch::Receiver<BTreeSet<Reported>>,
 }
 
 impl GameSensor {
     pub fn new(state: Arc<AppState>) -> Self {
-        Self { state }
+        let process_rx = state.process_watcher.subscribe();
+        Self {
+            state,
+            process_rx,
+            cached: CachedGamePatterns::default(),
+            catalog: None,
+            last_games: Vec::new(),
+            reporting_rx: crate::reporting::subscribe(),
+        }
     }
 
     pub async fn run(self) {
-        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
-        let mut process_rx = self.state.process_watcher.subscribe();
-        let mut config_rx = self.state.config_generation.subscribe();
-        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
-
-        // Build cached patterns once at startup
-        // Clone the games map and drop the read lock before any async
-        // work (MQTT publish) to avoid holding the lock across await points.
-        let games = self.state.config.read().await.games.clone();
-        let mut cached = CachedGamePatterns::build(&games);
-        self.publish_game_catalog(&games).await;
+        info!("Game sensor started (push-based)");
+        super::runner::run(Arc::clone(&self.state), self).await;
+    }
 
-        // Publish initial state
-        let games = self.detect_game(&cached).await;
-        self.publish_game(&games).await;
+    /// The running games, and the processes behind them for `StopGame`
+    async fn detect_game(&self) -> (Vec<(String, String)>, Vec<GameProcess>) {
+        let windows = if self.cached.needs_windows() {
+            tokio::task::spawn_blocking(game_windows)
+                .await
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        // Access process list by reference - no HashSet clone
+        let proc_state = self.state.process_watcher.state();
+        let proc_guard = proc_state.read().await;
+        let running = match_games_pairs(proc_guard.names(), &windows, &self.cached);
+        let processes = game_processes(
+            proc_guard.processes().map(|(pid, name)| (pid, &**name)),
+            &windows,
+            &self.cached,
+            &running,
+        );
+        (running, processes)
+    }
+}
 
-        // Track last published state (joined ids) to avoid duplicate messages
-        let mut last_game_id = running_state(&games).0;
+impl Sensor for GameSensor {
+    const NAME: &'static str = "Game";
+
+    /// Push-based: re-detected on every process list change, and also polled
+    /// while a game is matched by window title or Store app, or an emulator's
+    /// caption is read.
+    fn interval(config: &Config) -> Option<Duration> {
+        let by_window = !config.emulators.is_empty()
+            || config
+                .games
+                .values()
+                .any(|gc| gc.window_title().is_some() || gc.aumid().is_some());
+        by_window.then(|| Duration::from_secs(config.intervals.game_sensor))
+    }
 
-        info!("Game sensor started (push-based)");
+    /// Rebuild the cached patterns and the catalog from the games map.
+    async fn configure(&mut self) {
+        // Clone the maps and drop the read lock before building anything.
+        let (games, emulators) = {
+            let config = self.state.config.read().await;
+            (config.games.clone(), config.emulators.clone())
+        };
+        self.cached = CachedGamePatterns::build(&games, &emulators);
+        self.catalog = Some(game_catalog(&games));
+        debug!("Game sensor: rebuilt cached patterns");
+    }
 
-        loop {
-            tokio::select! {
-                biased;
-                _ = shutdown_rx.recv() => {
-                    debug!("Game sensor shutting down");
-                    break;
-                }
-                // Rebuild cached patterns when config changes
-                r = config_rx.recv() => {
-                    // Handle Lagged too so a burst of generations can't leave the
-                    // detector on a stale game pattern set.
-                    if !matches!(r, Ok(()) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_))) {
-                        continue;
-                    }
-                    let games = self.state.config.read().await.games.clone();
-                    cached = CachedGamePatterns::build(&games);
-                    self.publish_game_catalog(&games).await;
-                    debug!("Game sensor: rebuilt cached patterns");
-                    // Re-detect with new patterns
-                    let games = self.detect_game(&cached).await;
-                    let key = running_state(&games).0;
-                    if key != last_game_id {
-                        self.publish_game(&games).await;
-                        last_game_id = key;
-                    }
-                }
-                // MQTT reconnected - force republish retained state
-                Ok(()) = reconnect_rx.recv() => {
-                    info!("Game sensor: MQTT reconnected, republishing current state");
-                    let games = self.state.config.read().await.games.clone();
-                    self.publish_game_catalog(&games).await;
-                    let games = self.detect_game(&cached).await;
-                    self.publish_game(&games).await;
-                    last_game_id = running_state(&games).0;
-                }
-                result = process_rx.recv() => {
-                    match result {
-                        Ok(_notification) => {
-                            // Process list changed - re-detect and publish if different
-                            let games = self.detect_game(&cached).await;
-                            let key = running_state(&games).0;
-                            if key != last_game_id {
-                                self.publish_game(&games).await;
-                                last_game_id = key;
-                            }
-                        }
-                        Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
-                            // Missed some notifications, just re-detect
-                            debug!("Game sensor lagged {} notifications, re-detecting", n);
-                            let games = self.detect_game(&cached).await;
-                            let key = running_state(&games).0;
-                            if key != last_game_id {
-                                self.publish_game(&games).await;
-                                last_game_id = key;
-                            }
-                        }
-                        Err(tokio::sync::broadcast::error::RecvError::Closed) => {
-                            debug!("Process watcher channel closed");
-                            break;
-                        }
-                    }
-                }
+    async fn event(&mut self) -> Option<()> {
+        let notification = tokio::select! {
+            r = self.process_rx.recv() => r,
+            r = self.reporting_rx.changed() => return r.ok(),
+        };
+        match notification {
+            Ok(_notification) => Some(()),
+            // Missed some notifications, just re-detect
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                debug!("Game sensor lagged {} notifications, re-detecting", n);
+                Some(())
             }
+            Err(broadcast::error::RecvError::Closed) => None,
         }
     }
 
-    async fn publish_game(&self, games: &[(String, String)]) {
-        let (state, display_names) = running_state(games);
-        self.state
-            .mqtt
-            .publish_sensor_retained("runninggames", &state)
-            .await;
-
-        // Structured game list, built directly from the pairs (no re-split).
-        let games_array: Vec<serde_json::Value> = games
-            .iter()
-            .map(|(id, name)| serde_json::json!({ "id": id, "name": name }))
-            .collect();
+    async fn poll(&mut self) -> Vec<Reading> {
+        let (running, processes) = self.detect_game().await;
+        crate::commands::stop_game::track(processes);
+        // With the report_games switch off the games stay on the PC: no
+        // events, and the sensor reads "paused".
+        let paused = crate::reporting::is_paused(Reported::Games);
+        if !paused {
+            for event in crate::events::game_transitions(&self.last_games, &running) {
+                self.state.mqtt.publish_event(&event).await;
+            }
+        }
+        crate::notification::set_game_running(!running.is_empty());
+        crate::activity::set_game_running(!running.is_empty());
+        crate::game_limits::set_running(&running);
+        let reading = if paused {
+            Reading::new("runninggames", crate::reporting::PAUSED).retained()
+        } else {
+            running_games(&running)
+        };
+        self.last_games = running;
+        self.catalog.iter().cloned().chain([reading]).collect()
+    }
+}
 
-        let attrs = serde_json::json!({
+/// Retained `runninggames` reading (comma-joined ids) with the structured
+/// game list as attributes.
+fn running_games(games: &[(String, String)]) -> Reading {
+    let (state, display_names) = running_state(games);
+    // Structured game list, built directly from the pairs (no re-split).
+    let games_array: Vec<serde_json::Value> = games
+        .iter()
+        .map(|(id, name)| serde_json::json!({ "id": id, "name": name }))
+        .collect();
+    Reading::new("runninggames", state)
+        .retained()
+        .with_attributes(serde_json::json!({
             "display_name": display_names,
             "games": games_array,
             "count": games_array.len(),
-        });
-        self.state
-            .mqtt
-            .publish_sensor_attributes("runninggames", &attrs)
-            .await;
-    }
-
-    /// Publish the game catalog sensor - a retained list of all exposed games from config.
-    async fn publish_game_catalog(
-        &self,
-        games: &std::collections::HashMap<String, crate::config::GameConfig>,
-    ) {
-        // Collect into typed structs, sort by name, then serialize once
-        let mut entries: Vec<CatalogEntry> = games
-            .iter()
-            .filter(|(_, gc)| gc.is_exposed())
-            .map(|(process_pattern, gc)| CatalogEntry {
-                game_id: gc.game_id().to_owned(),
-                name: gc.display_name(),
-                app_id: gc.app_id(),
-                process_name: process_pattern.clone(),
-                launch_command: gc.launch_command(),
-            })
-            .collect();
+        }))
+}
 
-        entries.sort_by(|a, b| a.name.cmp(&b.name));
+/// The game catalog sensor - a retained list of all exposed games from config.
+fn game_catalog(games: &std::collections::HashMap<String, GameConfig>) -> Reading {
+    // Collect into typed structs, sort by name, then serialize once
+    let mut entries: Vec<CatalogEntry> = games
+        .iter()
+        .filter(|(_, gc)| gc.is_exposed())
+        .map(|(process_pattern, gc)| CatalogEntry {
+            game_id: gc.game_id().to_owned(),
+            name: gc.display_name(),
+            app_id: gc.app_id(),
+            process_name: process_pattern.clone(),
+            launch_command: gc.launch_command(),
+        })
+        .collect();
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
 
-        let count = entries.len();
-        self.state
-            .mqtt
-            .publish_sensor_retained("game_catalog", &count.to_string())
-            .await;
-        let attrs = serde_json::json!({
+    let count = entries.len();
+    Reading::new("game_catalog", count.to_string())
+        .retained()
+        .with_attributes(serde_json::json!({
             "games": entries,
             "count": count,
-        });
-        self.state
-            .mqtt
-            .publish_sensor_attributes("game_catalog", &attrs)
-            .await;
-        debug!("Published game catalog with {} exposed games", count);
-    }
-
-    async fn detect_game(&self, cached: &CachedGamePatterns) -> Vec<(String, String)> {
-        // Access process list by reference - no HashSet clone
-        let proc_state = self.state.process_watcher.state();
-        let proc_guard = proc_state.read().await;
-        match_games_pairs(proc_guard.names(), cached)
-    }
+        }))
 }
 
 /// Pure matching function - testable without AppState. Returns the running
 /// games as structured `(game_id, display_name)` pairs (empty = none). Kept
-/// structured all the way to `publish_game` so a display name containing ", "
+/// structured all the way to `running_games` so a display name containing ", "
 /// can't misalign the id/name pairing (the old join-then-resplit bug).
 fn match_games_pairs(
     process_names: &HashSet<Arc<str>>,
+    windows: &[GameWindow],
     cached: &CachedGamePatterns,
 ) -> Vec<(String, String)> {
     let mut found_games: Vec<(String, String)> = Vec::with_capacity(2);
     let mut seen_ids: HashSet<&str> = HashSet::with_capacity(cached.patterns.len());
 
     for proc_name in process_names {
-        // Strip .exe suffix without allocating (case-insensitive for all casings)
-        let base_name = if proc_name.len() > 4
-            && proc_name.as_bytes()[proc_name.len() - 4..].eq_ignore_ascii_case(b".exe")
-        {
-            &proc_name[..proc_name.len() - 4]
-        } else {
-            proc_name
-        };
-
         for (pattern_lower, game_id, display_name) in &cached.patterns {
-            // Case-insensitive comparison without allocation
-            let matches = starts_with_ignore_ascii_case(proc_name, pattern_lower)
-                || base_name.eq_ignore_ascii_case(pattern_lower);
-            if matches && seen_ids.insert(game_id.as_str()) {
+            if process_matches(proc_name, pattern_lower) && seen_ids.insert(game_id.as_str()) {
                 found_games.push((game_id.clone(), display_name.clone()));
                 break; // This process matched - no need to check remaining patterns
             }
         }
     }
 
+    for window in windows {
+        let title = window.title.to_lowercase();
+        // An empty part never matches, like an empty process pattern.
+        let title_matches = cached
+            .window_titles
+            .iter()
+            .filter(|(part, _, _)| !part.is_empty() && title.contains(part.as_str()));
+        let aumid_matches = cached.aumids.iter().filter(|(prefix, _, _)| {
+            window
+                .aumid
+                .as_deref()
+                .is_some_and(|aumid| starts_with_ignore_ascii_case(aumid, prefix))
+        });
+        for (_, game_id, display_name) in title_matches.chain(aumid_matches) {
+            if seen_ids.insert(game_id.as_str()) {
+                found_games.push((game_id.clone(), display_name.clone()));
+            }
+        }
+    }
+
+    for emulator in &cached.emulators {
+        if !process_names
+            .iter()
+            .any(|proc_name| process_matches(proc_name, &emulator.pattern))
+        {
+            continue;
+        }
+        let game = windows
+            .iter()
+            .filter(|w| starts_with_ignore_ascii_case(&w.title, &emulator.title_prefix))
+            .find_map(|w| emulator.config.game_in_title(&w.title));
+        if seen_ids.insert(emulator.game_id.as_str()) {
+            let name = game.unwrap_or_else(|| emulator.name.clone());
+            found_games.push((emulator.game_id.clone(), name));
+        }
+    }
+
     found_games
 }
 
+/// The processes behind `running` (from `match_games_pairs`): those matching
+/// the game's process pattern, the windows a title or Store match came from,
+/// and running emulators.
+fn game_processes<'a>(
+    processes: impl Iterator<Item = (u32, &'a str)>,
+    windows: &[GameWindow],
+    cached: &CachedGamePatterns,
+    running: &[(String, String)],
+) -> Vec<GameProcess> {
+    if running.is_empty() {
+        return Vec::new();
+    }
+    let processes: Vec<(u32, &str)> = processes.collect();
+    let mut found: Vec<GameProcess> = Vec::new();
+    for (game_id, display_name) in running {
+        let mut pids: Vec<u32> = Vec::new();
+        for (pattern_lower, id, _) in &cached.patterns {
+            if id == game_id {
+                pids.extend(
+                    processes
+                        .iter()
+                        .filter(|(_, name)| process_matches(name, pattern_lower))
+                        .map(|(pid, _)| *pid),
+                );
+            }
+        }
+        for window in windows {
+            let title = window.title.to_lowercase();
+            let by_title = cached.window_titles.iter().any(|(part, id, _)| {
+                id == game_id && !part.is_empty() && title.contains(part.as_str())
+            });
+            let by_aumid = cached.aumids.iter().any(|(prefix, id, _)| {
+                id == game_id
+                    && window
+                        .aumid
+                        .as_deref()
+                        .is_some_and(|aumid| starts_with_ignore_ascii_case(aumid, prefix))
+            });
+            if by_title || by_aumid {
+                pids.push(window.pid);
+            }
+        }
+        for emulator in cached.emulators.iter().filter(|e| &e.game_id == game_id) {
+            pids.extend(
+                processes
+                    .iter()
+                    .filter(|(_, name)| process_matches(name, &emulator.pattern))
+                    .map(|(pid, _)| *pid),
+            );
+        }
+        pids.sort_unstable();
+        pids.dedup();
+        found.extend(pids.into_iter().map(|pid| GameProcess {
+            pid,
+            game: display_name.clone(),
+        }));
+    }
+    found
+}
+
+/// Whether a process name matches a lowered game pattern: a case-insensitive
+/// prefix, or the whole name without `.exe`.
+fn process_matches(proc_name: &str, pattern_lower: &str) -> bool {
+    // Strip .exe suffix without allocating (case-insensitive for all casings)
+    let base_name = if proc_name.len() > 4
+        && proc_name.as_bytes()[proc_name.len() - 4..].eq_ignore_ascii_case(b".exe")
+    {
+        &proc_name[..proc_name.len() - 4]
+    } else {
+        proc_name
+    };
+    // Case-insensitive comparison without allocation
+    starts_with_ignore_ascii_case(proc_name, pattern_lower)
+        || base_name.eq_ignore_ascii_case(pattern_lower)
+}
+
+/// The visible, titled top-level windows (blocking).
+fn game_windows() -> Vec<GameWindow> {
+    use windows::Win32::Foundation::{BOOL, HWND, LPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        EnumWindows, GetWindowTextW, GetWindowThreadProcessId, IsWindowVisible,
+    };
+
+    unsafe extern "system" fn collect(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        // SAFETY: lparam is the &mut Vec passed to EnumWindows below, which
+        // outlives the (synchronous) enumeration.
+        let windows = unsafe { &mut *(lparam.0 as *mut Vec<GameWindow>) };
+        unsafe {
+            if !IsWindowVisible(hwnd).as_bool() {
+                return true.into();
+            }
+            let mut buffer = [0u16; 512];
+            let len = GetWindowTextW(hwnd, &mut buffer);
+            if len <= 0 {
+                return true.into();
+            }
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, Some(&raw mut pid));
+            windows.push(GameWindow {
+                title: String::from_utf16_lossy(&buffer[..len as usize]),
+                aumid: process_aumid(pid),
+                pid,
+            });
+        }
+        true.into()
+    }
+
+    let mut windows: Vec<GameWindow> = Vec::new();
+    // SAFETY: `collect` only runs during this call and only touches `windows`.
+    unsafe {
+        let _ = EnumWindows(Some(collect), LPARAM(&raw mut windows as isize));
+    }
+    windows
+}
+
+/// AppUserModelID of a packaged app's process; None for ordinary Win32
+/// processes (APPMODEL_ERROR_NO_APPLICATION) or when it can't be opened.
+fn process_aumid(pid: u32) -> Option<String> {
+    use windows::Win32::Foundation::{CloseHandle, ERROR_SUCCESS};
+    use windows::Win32::Storage::Packaging::Appx::GetApplicationUserModelId;
+    use windows::Win32::System::Threading::{OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION};
+    use windows::core::PWSTR;
+
+    // SAFETY: the handle is only used by the query below and closed before
+    // returning; the buffer and its length are valid stack locals.
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        // APPLICATION_USER_MODEL_ID_MAX_LENGTH
+        let mut buffer = [0u16; 130];
+        let mut len = buffer.len() as u32;
+        let result = GetApplicationUserModelId(handle, &raw mut len, PWSTR(buffer.as_mut_ptr()));
+        let _ = CloseHandle(handle);
+        if result != ERROR_SUCCESS || len == 0 {
+            return None;
+        }
+        // len includes the terminating NUL
+        Some(String::from_utf16_lossy(&buffer[..len as usize - 1]))
+    }
+}
+
 /// The retained sensor value (comma-joined ids) and display string (", "-joined
 /// names) for a set of running games. "none"/"None" when empty.
 fn running_state(games: &[(String, String)]) -> (String, String) {
@@ -262,13 +505,13 @@ fn running_state(games: &[(String, String)]) -> (String, String) {
 }
 
 /// Joined `(ids, names)` for a process set. Test-only: production carries the
-/// structured pairs from [`match_games_pairs`] through to `publish_game`.
+/// structured pairs from [`match_games_pairs`] through to `running_games`.
 #[cfg(test)]
 fn match_games_in_processes(
     process_names: &HashSet<Arc<str>>,
     cached: &CachedGamePatterns,
 ) -> (String, String) {
-    running_state(&match_games_pairs(process_names, cached))
+    running_state(&match_games_pairs(process_names, &[], cached))
 }
 
 /// Case-insensitive ASCII prefix check without allocation. An empty prefix
@@ -283,7 +526,6 @@ fn starts_with_ignore_ascii_case(haystack: &str, prefix: &str) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::GameConfig;
     use std::collections::HashMap;
 
     /// Helper: build CachedGamePatterns from (pattern, GameConfig) pairs
@@ -292,7 +534,7 @@ mod tests {
             .iter()
             .map(|(k, v)| (k.to_string(), v.clone()))
             .collect();
-        CachedGamePatterns::build(&map)
+        CachedGamePatterns::build(&map, &HashMap::new())
     }
 
     /// Helper: build a process set from string slices
@@ -405,6 +647,8 @@ mod tests {
                 launch_command: None,
                 auto_discovered: true,
                 exposed: true,
+                window_title: None,
+                aumid: None,
             },
         )]);
         let processes = procs(&["helldivers2.exe"]);
@@ -424,6 +668,8 @@ mod tests {
                 launch_command: None,
                 auto_discovered: false,
                 exposed: true,
+                window_title: None,
+                aumid: None,
             },
         )]);
         let processes = procs(&["cod_mw.exe"]);
@@ -483,6 +729,8 @@ mod tests {
                     launch_command: None,
                     auto_discovered: false,
                     exposed: true,
+                    window_title: None,
+                    aumid: None,
                 },
             ),
             ("cod_mw", GameConfig::Simple("call_of_duty_mw".into())),
@@ -516,15 +764,182 @@ mod tests {
 
     #[test]
     fn test_cached_patterns_empty_map() {
-        let cached = CachedGamePatterns::build(&HashMap::new());
+        let cached = CachedGamePatterns::build(&HashMap::new(), &HashMap::new());
         assert!(cached.patterns.is_empty());
     }
 
+    // ===== Window title / Store app detection =====
+
+    fn window_game(game_id: &str, window_title: Option<&str>, aumid: Option<&str>) -> GameConfig {
+        GameConfig::Full {
+            game_id: game_id.into(),
+            app_id: None,
+            name: None,
+            launch_command: None,
+            auto_discovered: false,
+            exposed: true,
+            window_title: window_title.map(Into::into),
+            aumid: aumid.map(Into::into),
+        }
+    }
+
+    fn window(title: &str, aumid: Option<&str>) -> GameWindow {
+        GameWindow {
+            title: title.into(),
+            aumid: aumid.map(Into::into),
+            pid: 0,
+        }
+    }
+
+    #[test]
+    fn test_cached_patterns_split_window_games() {
+        let cached = make_patterns(&[
+            ("retroarch", window_game("mario", Some("Super Mario"), None)),
+            (
+                "forza",
+                window_game("forza_5", None, Some("Microsoft.Forza")),
+            ),
+            ("bf2042", GameConfig::Simple("battlefield_6".into())),
+        ]);
+        assert_eq!(cached.patterns.len(), 1);
+        assert_eq!(cached.window_titles[0].0, "super mario");
+        assert_eq!(cached.aumids[0].0, "microsoft.forza");
+        assert!(cached.needs_windows());
+        assert!(!make_patterns(&[("bf2042", GameConfig::Simple("x".into()))]).needs_windows());
+    }
+
+    #[test]
+    fn test_window_title_match() {
+        let cached = make_patterns(&[(
+            "retroarch",
+            window_game("super_mario_world", Some("Super Mario World"), None),
+        )]);
+        let windows = [window("RetroArch - SUPER MARIO WORLD (USA)", None)];
+        let pairs = match_games_pairs(&procs(&["retroarch.exe"]), &windows, &cached);
+        assert_eq!(pairs[0].0, "super_mario_world");
+
+        // The process alone no longer identifies it.
+        assert!(match_games_pairs(&procs(&["retroarch.exe"]), &[], &cached).is_empty());
+        let other = [window("RetroArch", None)];
+        assert!(match_games_pairs(&procs(&["retroarch.exe"]), &other, &cached).is_empty());
+    }
+
+    #[test]
+    fn test_aumid_prefix_match() {
+        let cached = make_patterns(&[(
+            "forza",
+            window_game(
+                "forza_5",
+                None,
+                Some("Microsoft.624F8B84B80_8wekyb3d8bbwe!"),
+            ),
+        )]);
+        let windows = [
+            window(
+                "Forza Horizon 5",
+                Some("microsoft.624f8b84b80_8wekyb3d8bbwe!ForzaHorizon5"),
+            ),
+            window("Notepad", None),
+        ];
+        let pairs = match_games_pairs(&procs(&["gamelaunchhelper.exe"]), &windows, &cached);
+        assert_eq!(pairs, vec![("forza_5".to_string(), "Forza 5".to_string())]);
+
+        let unpackaged = [window("Forza Horizon 5", None)];
+        assert!(match_games_pairs(&procs(&[]), &unpackaged, &cached).is_empty());
+    }
+
+    #[test]
+    fn test_emulator_reports_loaded_game() {
+        let emulators: HashMap<String, EmulatorConfig> =
+            serde_json::from_str(r#"{"dolphin": {"separator": " | "}}"#).unwrap();
+        let cached = CachedGamePatterns::build(&HashMap::new(), &emulators);
+        let processes = procs(&["Dolphin.exe"]);
+
+        let windows = [
+            window("Notepad | notes.txt", None),
+            window(
+                "Dolphin 5.0 | JIT64 DC | HLE | Super Mario Sunshine (GMSE01)",
+                None,
+            ),
+        ];
+        assert_eq!(
+            match_games_pairs(&processes, &windows, &cached),
+            vec![(
+                "dolphin".to_string(),
+                "Super Mario Sunshine (GMSE01)".to_string()
+            )]
+        );
+
+        // No game loaded yet: the emulator itself is reported.
+        let menu = [window("Dolphin 5.0", None)];
+        assert_eq!(
+            match_games_pairs(&processes, &menu, &cached),
+            vec![("dolphin".to_string(), "Dolphin".to_string())]
+        );
+        // Not running: nothing, whatever the windows say.
+        assert!(match_games_pairs(&procs(&[]), &windows, &cached).is_empty());
+    }
+
+    #[test]
+    fn test_window_game_reported_once() {
+        let cached = make_patterns(&[(
+            "emu",
+            window_game("zelda", Some("Zelda"), Some("Nintendo.Zelda")),
+        )]);
+        let windows = [
+            window("Zelda - main", Some("Nintendo.Zelda!App")),
+            window("Zelda - map", None),
+        ];
+        assert_eq!(match_games_pairs(&procs(&[]), &windows, &cached).len(), 1);
+    }
+
+    #[test]
+    fn test_game_processes_for_stop_game() {
+        let cached = make_patterns(&[
+            ("cs2", GameConfig::Simple("counter_strike_2".into())),
+            (
+                "forza",
+                window_game("forza_5", None, Some("Microsoft.Forza")),
+            ),
+            ("notepad", GameConfig::Simple("notepad".into())),
+        ]);
+        let processes = [
+            (100, "cs2.exe"),
+            (101, "cs2.exe"),
+            (200, "gamelaunchhelper.exe"),
+            (300, "notepad.exe"),
+        ];
+        let windows = [GameWindow {
+            title: "Forza Horizon 5".into(),
+            aumid: Some("Microsoft.Forza!App".into()),
+            pid: 200,
+        }];
+        let running = vec![
+            (
+                "counter_strike_2".to_string(),
+                "Counter Strike 2".to_string(),
+            ),
+            ("forza_5".to_string(), "Forza 5".to_string()),
+        ];
+        let found = game_processes(processes.into_iter(), &windows, &cached, &running);
+        let pids: Vec<(u32, &str)> = found.iter().map(|p| (p.pid, p.game.as_str())).collect();
+        // notepad matches a pattern but isn't reported: left alone
+        assert_eq!(
+            pids,
+            vec![
+                (100, "Counter Strike 2"),
+                (101, "Counter Strike 2"),
+                (200, "Forza 5")
+            ]
+        );
+        assert!(game_processes(processes.into_iter(), &windows, &cached, &[]).is_empty());
+    }
+
     // ===== End-to-end: exact MQTT content verification =====
 
     #[test]
     fn test_mqtt_content_no_games() {
-        // Simulates what publish_game sends when nothing is running
+        // Simulates what running_games sends when nothing is running
         let cached = make_patterns(&[("bf2042", GameConfig::Simple("battlefield_6".into()))]);
         let (ids, names) = match_games_in_processes(&procs(&[]), &cached);
 
@@ -552,15 +967,17 @@ mod tests {
                 launch_command: None,
                 auto_discovered: true,
                 exposed: true,
+                window_title: None,
+                aumid: None,
             },
         )]);
         let processes = procs(&["bf2042.exe", "chrome.exe", "explorer.exe"]);
         let (ids, names) = match_games_in_processes(&processes, &cached);
 
-        // This is what goes to: homeassistant/sensor/{device}/runninggames/state
+        // This is what goes to: pc-bridge/{device}/runninggames/state
         assert_eq!(ids, "battlefield_6");
 
-        // This is what goes to: homeassistant/sensor/{device}/runninggames/attributes
+        // This is what goes to: pc-bridge/{device}/runninggames/attributes
         let attrs = serde_json::json!({ "display_name": names });
         assert_eq!(
             serde_json::to_string(&attrs).unwrap(),
@@ -664,6 +1081,8 @@ mod tests {
             launch_command: None,
             auto_discovered: false,
             exposed: false,
+            window_title: None,
+            aumid: None,
         };
         assert!(!gc.is_exposed());
 
@@ -686,6 +1105,8 @@ mod tests {
                     launch_command: None,
                     auto_discovered: false,
                     exposed: false,
+                    window_title: None,
+                    aumid: None,
                 },
             ),
             (
@@ -697,6 +1118,8 @@ mod tests {
                     launch_command: None,
                     auto_discovered: true,
                     exposed: true,
+                    window_title: None,
+                    aumid: None,
                 },
             ),
         ]
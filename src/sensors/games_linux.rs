@@ -4,16 +4,26 @@
 //! 1. Steam auto-discovery (if Steam installed) - uses process name → app_id lookup
 //! 2. Manual config `games` map (pattern → game_id)
 //!
+//! Games with a `window_title` are matched against the open window titles (X11
+//! or wlroots Wayland) instead of process names. `aumid` is Windows-only; such
+//! games are never detected here. Running `emulators` are reported with the
+//! game named in their window caption as the display name.
+//!
 //! Also publishes a `game_catalog` sensor listing all exposed games from config.
 
-use log::{debug, error, info};
+use log::{debug, error};
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::fs;
 use std::sync::Arc;
-use tokio::time::{Duration, interval};
+use std::time::Duration;
+use tokio::sync::watch;
 
+use super::runner::{Reading, Sensor};
 use crate::AppState;
+use crate::commands::stop_game::GameProcess;
+use crate::config::{Config, EmulatorConfig, GameConfig};
+use crate::reporting::Reported;
 
 #[derive(Serialize)]
 struct CatalogEntry {
@@ -27,197 +37,176 @@ struct CatalogEntry {
 }
 
 /// Cached lowered game patterns - rebuilt only when config changes via config_generation.
+#[derive(Default)]
 struct CachedGamePatterns {
     /// (lowered_pattern, game_id, display_name)
     patterns: Vec<(String, String, String)>,
+    /// Games matched by window title: (lowered_title_part, game_id, display_name)
+    window_titles: Vec<(String, String, String)>,
+    emulators: Vec<CachedEmulator>,
+}
+
+/// An `emulators` entry, lowered for matching
+struct CachedEmulator {
+    /// Lowered process pattern
+    pattern: String,
+    /// Lowered window caption prefix
+    title_prefix: String,
+    game_id: String,
+    /// Display name while no game is loaded
+    name: String,
+    config: EmulatorConfig,
 }
 
 impl CachedGamePatterns {
-    fn build(games: &std::collections::HashMap<String, crate::config::GameConfig>) -> Self {
-        let patterns = games
-            .iter()
-            .map(|(pattern, gc)| {
+    fn build(
+        games: &std::collections::HashMap<String, GameConfig>,
+        emulators: &std::collections::HashMap<String, EmulatorConfig>,
+    ) -> Self {
+        let mut cached = Self::default();
+        for (pattern, gc) in games {
+            let entry = |key: &str| {
                 (
-                    pattern.to_lowercase(),
+                    key.to_lowercase(),
                     gc.game_id().to_string(),
                     gc.display_name(),
                 )
+            };
+            match gc.window_title() {
+                Some(title) => cached.window_titles.push(entry(title)),
+                None if gc.aumid().is_none() => cached.patterns.push(entry(pattern)),
+                None => {}
+            }
+        }
+        cached.emulators = emulators
+            .iter()
+            .map(|(pattern, config)| {
+                let game_id = config.game_id(pattern);
+                CachedEmulator {
+                    pattern: pattern.to_lowercase(),
+                    title_prefix: config
+                        .title_prefix
+                        .as_deref()
+                        .unwrap_or(pattern)
+                        .to_lowercase(),
+                    name: GameConfig::Simple(game_id.clone()).display_name(),
+                    game_id,
+                    config: config.clone(),
+                }
             })
             .collect();
-        Self { patterns }
+        cached
     }
 }
 
 pub struct GameSensor {
     state: Arc<AppState>,
+    cached: CachedGamePatterns,
+    /// Retained `game_catalog` reading, rebuilt with the patterns
+    catalog: Option<Reading>,
+    /// Games seen by the last poll, diffed into game_started/stopped events
+    last_games: Vec<(String, String)>,
+    /// The report_games switch, re-polled when it flips
+    reporting_rx: watch::Receiver<BTreeSet<Reported>>,
 }
 
 impl GameSensor {
     pub fn new(state: Arc<AppState>) -> Self {
-        Self { state }
-    }
-
-    pub async fn run(self) {
-        let config = self.state.config.read().await;
-        let interval_secs = config.intervals.game_sensor.max(1); // Prevent panic on 0
-        let games = config.games.clone();
-        let mut cached = CachedGamePatterns::build(&games);
-        drop(config);
-        self.publish_game_catalog(&games).await;
-
-        let mut tick = interval(Duration::from_secs(interval_secs));
-        // Skip missed ticks so a suspend/resume doesn't fire a burst of catch-up
-        // game scans.
-        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
-        let mut config_rx = self.state.config_generation.subscribe();
-        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
-
-        // Publish initial state
-        let running = self.detect_game(&cached).await;
-        self.publish_game(&running).await;
-
-        // Track last published state (joined ids) to avoid duplicate messages
-        let mut last_game_id = running_state(&running).0;
-
-        loop {
-            tokio::select! {
-                biased;
-                _ = shutdown_rx.recv() => {
-                    debug!("Game sensor shutting down");
-                    break;
-                }
-                // Rebuild cached patterns when config changes
-                Ok(()) = config_rx.recv() => {
-                    let games = self.state.config.read().await.games.clone();
-                    cached = CachedGamePatterns::build(&games);
-                    self.publish_game_catalog(&games).await;
-                    debug!("Game sensor: rebuilt cached patterns");
-                    let running = self.detect_game(&cached).await;
-                    let key = running_state(&running).0;
-                    if key != last_game_id {
-                        self.publish_game(&running).await;
-                        last_game_id = key;
-                    }
-                }
-                // MQTT reconnected - force republish retained state
-                Ok(()) = reconnect_rx.recv() => {
-                    info!("Game sensor: MQTT reconnected, republishing current state");
-                    let games = self.state.config.read().await.games.clone();
-                    self.publish_game_catalog(&games).await;
-                    let running = self.detect_game(&cached).await;
-                    self.publish_game(&running).await;
-                    last_game_id = running_state(&running).0;
-                }
-                _ = tick.tick() => {
-                    let running = self.detect_game(&cached).await;
-                    let key = running_state(&running).0;
-                    if key != last_game_id {
-                        self.publish_game(&running).await;
-                        last_game_id = key;
-                    }
-                }
-            }
+        Self {
+            state,
+            cached: CachedGamePatterns::default(),
+            catalog: None,
+            last_games: Vec::new(),
+            reporting_rx: crate::reporting::subscribe(),
         }
     }
 
-    async fn publish_game(&self, games: &[(String, String)]) {
-        let (state, display_names) = running_state(games);
-        self.state
-            .mqtt
-            .publish_sensor_retained("runninggames", &state)
-            .await;
-
-        // Structured game list, built directly from the pairs (no re-split).
-        let games_array: Vec<serde_json::Value> = games
-            .iter()
-            .map(|(id, name)| serde_json::json!({ "id": id, "name": name }))
-            .collect();
-
-        let attrs = serde_json::json!({
-            "display_name": display_names,
-            "games": games_array,
-            "count": games_array.len(),
-        });
-        self.state
-            .mqtt
-            .publish_sensor_attributes("runninggames", &attrs)
-            .await;
-    }
-
-    /// Publish the game catalog sensor - a retained list of all exposed games from config.
-    async fn publish_game_catalog(
-        &self,
-        games: &std::collections::HashMap<String, crate::config::GameConfig>,
-    ) {
-        let mut entries: Vec<CatalogEntry> = games
-            .iter()
-            .filter(|(_, gc)| gc.is_exposed())
-            .map(|(process_pattern, gc)| CatalogEntry {
-                game_id: gc.game_id().to_owned(),
-                name: gc.display_name(),
-                app_id: gc.app_id(),
-                process_name: process_pattern.clone(),
-                launch_command: gc.launch_command(),
-            })
-            .collect();
-
-        entries.sort_by(|a, b| a.name.cmp(&b.name));
-
-        let count = entries.len();
-        self.state
-            .mqtt
-            .publish_sensor_retained("game_catalog", &count.to_string())
-            .await;
-        let attrs = serde_json::json!({
-            "games": entries,
-            "count": count,
-        });
-        self.state
-            .mqtt
-            .publish_sensor_attributes("game_catalog", &attrs)
-            .await;
-        debug!("Published game catalog with {} exposed games", count);
+    pub async fn run(self) {
+        super::runner::run(Arc::clone(&self.state), self).await;
     }
 
-    async fn detect_game(&self, cached: &CachedGamePatterns) -> Vec<(String, String)> {
+    /// The running games, and the processes behind them for `StopGame`
+    async fn detect_game(cached: &CachedGamePatterns) -> (Vec<(String, String)>, Vec<GameProcess>) {
         // Enumerate processes via /proc
-        let processes = match self.get_process_names().await {
+        let pids_and_names = match Self::get_processes().await {
             Ok(p) => p,
             Err(e) => {
                 error!("Failed to enumerate processes: {}", e);
-                return Vec::new();
+                return (Vec::new(), Vec::new());
             }
         };
+        let running = Self::match_games(cached, &pids_and_names).await;
+        let processes = game_processes(&pids_and_names, cached, &running);
+        (running, processes)
+    }
+
+    async fn match_games(
+        cached: &CachedGamePatterns,
+        pids_and_names: &[(u32, String)],
+    ) -> Vec<(String, String)> {
+        let processes: Vec<&str> = pids_and_names.iter().map(|(_, n)| n.as_str()).collect();
 
         let mut found_games: Vec<(String, String)> = Vec::with_capacity(2);
         let mut seen_ids: HashSet<&str> = HashSet::with_capacity(cached.patterns.len());
 
-        for proc_name in &processes {
+        for proc_name in processes.iter().copied() {
             for (pattern_lower, game_id, display_name) in &cached.patterns {
-                // Case-insensitive prefix match OR exact match (matches Windows behavior)
-                let matches = starts_with_ignore_ascii_case(proc_name, pattern_lower)
-                    || proc_name.eq_ignore_ascii_case(pattern_lower);
-                if matches && seen_ids.insert(game_id.as_str()) {
+                if process_matches(proc_name, pattern_lower) && seen_ids.insert(game_id.as_str()) {
                     found_games.push((game_id.clone(), display_name.clone()));
                     break; // This process matched - no need to check remaining patterns
                 }
             }
         }
 
+        if cached.window_titles.is_empty() && cached.emulators.is_empty() {
+            return found_games;
+        }
+        let titles = tokio::task::spawn_blocking(window_titles)
+            .await
+            .unwrap_or_default();
+        for title in titles.iter().map(|t| t.to_lowercase()) {
+            for (part, game_id, display_name) in &cached.window_titles {
+                // An empty part never matches, like an empty process pattern.
+                if !part.is_empty()
+                    && title.contains(part.as_str())
+                    && seen_ids.insert(game_id.as_str())
+                {
+                    found_games.push((game_id.clone(), display_name.clone()));
+                }
+            }
+        }
+
+        for emulator in &cached.emulators {
+            if !processes
+                .iter()
+                .any(|proc_name| process_matches(proc_name, &emulator.pattern))
+            {
+                continue;
+            }
+            let game = titles
+                .iter()
+                .filter(|t| starts_with_ignore_ascii_case(t, &emulator.title_prefix))
+                .find_map(|t| emulator.config.game_in_title(t));
+            if seen_ids.insert(emulator.game_id.as_str()) {
+                let name = game.unwrap_or_else(|| emulator.name.clone());
+                found_games.push((emulator.game_id.clone(), name));
+            }
+        }
+
         found_games
     }
 
-    async fn get_process_names(&self) -> anyhow::Result<Vec<String>> {
+    async fn get_processes() -> anyhow::Result<Vec<(u32, String)>> {
         // /proc enumeration is hundreds of blocking fs::read_to_string calls -
         // run off the single-threaded runtime.
-        tokio::task::spawn_blocking(Self::get_process_names_blocking)
+        tokio::task::spawn_blocking(Self::get_processes_blocking)
             .await
             .map_err(|e| anyhow::anyhow!("spawn_blocking join error: {}", e))?
     }
 
-    fn get_process_names_blocking() -> anyhow::Result<Vec<String>> {
-        let mut names = Vec::new();
+    /// `(pid, name)` of every process in `/proc`
+    fn get_processes_blocking() -> anyhow::Result<Vec<(u32, String)>> {
+        let mut processes = Vec::new();
 
         // Read /proc to enumerate processes
         for entry in fs::read_dir("/proc")? {
@@ -227,7 +216,7 @@ impl GameSensor {
             // Only process numeric directories (PIDs)
             if let Some(name) = path.file_name()
                 && let Some(name_str) = name.to_str()
-                && name_str.chars().all(|c| c.is_ascii_digit())
+                && let Ok(pid) = name_str.parse::<u32>()
             {
                 // /proc/<pid>/comm is truncated to 15 bytes (TASK_COMM_LEN-1), so a
                 // game with a longer executable name (e.g. MarvelRivals_Shipping)
@@ -251,15 +240,149 @@ impl GameSensor {
                     comm
                 };
                 if !name.is_empty() {
-                    names.push(name);
+                    processes.push((pid, name));
                 }
             }
         }
 
-        Ok(names)
+        Ok(processes)
+    }
+}
+
+impl Sensor for GameSensor {
+    const NAME: &'static str = "Game";
+
+    fn interval(config: &Config) -> Option<Duration> {
+        Some(Duration::from_secs(config.intervals.game_sensor))
+    }
+
+    async fn event(&mut self) -> Option<()> {
+        self.reporting_rx.changed().await.ok()
+    }
+
+    /// Rebuild the cached patterns and the catalog from the games map.
+    async fn configure(&mut self) {
+        // Clone the maps and drop the read lock before building anything.
+        let (games, emulators) = {
+            let config = self.state.config.read().await;
+            (config.games.clone(), config.emulators.clone())
+        };
+        self.cached = CachedGamePatterns::build(&games, &emulators);
+        self.catalog = Some(game_catalog(&games));
+        debug!("Game sensor: rebuilt cached patterns");
+    }
+
+    async fn poll(&mut self) -> Vec<Reading> {
+        let (running, processes) = Self::detect_game(&self.cached).await;
+        crate::commands::stop_game::track(processes);
+        // With the report_games switch off the games stay on the PC: no
+        // events, and the sensor reads "paused".
+        let paused = crate::reporting::is_paused(Reported::Games);
+        if !paused {
+            for event in crate::events::game_transitions(&self.last_games, &running) {
+                self.state.mqtt.publish_event(&event).await;
+            }
+        }
+        crate::notification::set_game_running(!running.is_empty());
+        crate::activity::set_game_running(!running.is_empty());
+        crate::game_limits::set_running(&running);
+        let reading = if paused {
+            Reading::new("runninggames", crate::reporting::PAUSED).retained()
+        } else {
+            running_games(&running)
+        };
+        self.last_games = running;
+        self.catalog.iter().cloned().chain([reading]).collect()
     }
 }
 
+/// Retained `runninggames` reading (comma-joined ids) with the structured
+/// game list as attributes.
+fn running_games(games: &[(String, String)]) -> Reading {
+    let (state, display_names) = running_state(games);
+    // Structured game list, built directly from the pairs (no re-split).
+    let games_array: Vec<serde_json::Value> = games
+        .iter()
+        .map(|(id, name)| serde_json::json!({ "id": id, "name": name }))
+        .collect();
+    Reading::new("runninggames", state)
+        .retained()
+        .with_attributes(serde_json::json!({
+            "display_name": display_names,
+            "games": games_array,
+            "count": games_array.len(),
+        }))
+}
+
+/// The game catalog sensor - a retained list of all exposed games from config.
+fn game_catalog(games: &std::collections::HashMap<String, GameConfig>) -> Reading {
+    let mut entries: Vec<CatalogEntry> = games
+        .iter()
+        .filter(|(_, gc)| gc.is_exposed())
+        .map(|(process_pattern, gc)| CatalogEntry {
+            game_id: gc.game_id().to_owned(),
+            name: gc.display_name(),
+            app_id: gc.app_id(),
+            process_name: process_pattern.clone(),
+            launch_command: gc.launch_command(),
+        })
+        .collect();
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let count = entries.len();
+    Reading::new("game_catalog", count.to_string())
+        .retained()
+        .with_attributes(serde_json::json!({
+            "games": entries,
+            "count": count,
+        }))
+}
+
+/// The processes behind `running`: those matching the game's process
+/// pattern, and running emulators. Window titles carry no pid here, so games
+/// matched by title alone have none.
+fn game_processes(
+    processes: &[(u32, String)],
+    cached: &CachedGamePatterns,
+    running: &[(String, String)],
+) -> Vec<GameProcess> {
+    let mut found: Vec<GameProcess> = Vec::new();
+    for (game_id, display_name) in running {
+        let patterns = cached
+            .patterns
+            .iter()
+            .filter(|(_, id, _)| id == game_id)
+            .map(|(pattern, _, _)| pattern)
+            .chain(
+                cached
+                    .emulators
+                    .iter()
+                    .filter(|e| &e.game_id == game_id)
+                    .map(|e| &e.pattern),
+            )
+            .collect::<Vec<_>>();
+        let mut pids: Vec<u32> = processes
+            .iter()
+            .filter(|(_, name)| patterns.iter().any(|p| process_matches(name, p)))
+            .map(|(pid, _)| *pid)
+            .collect();
+        pids.sort_unstable();
+        pids.dedup();
+        found.extend(pids.into_iter().map(|pid| GameProcess {
+            pid,
+            game: display_name.clone(),
+        }));
+    }
+    found
+}
+
+/// Case-insensitive prefix match OR exact match (matches Windows behavior)
+fn process_matches(proc_name: &str, pattern_lower: &str) -> bool {
+    starts_with_ignore_ascii_case(proc_name, pattern_lower)
+        || proc_name.eq_ignore_ascii_case(pattern_lower)
+}
+
 /// Case-insensitive ASCII prefix check without allocation. An empty prefix never
 /// matches - otherwise a blank/misconfigured game pattern reports every process.
 fn starts_with_ignore_ascii_case(haystack: &str, prefix: &str) -> bool {
@@ -279,8 +402,27 @@ fn running_state(games: &[(String, String)]) -> (String, String) {
     (ids.join(","), names.join(", "))
 }
 
+/// Open window titles (blocking): X11 `_NET_CLIENT_LIST`, or wlr-foreign-toplevel
+/// on a Wayland session, where XWayland would only list its own clients.
+fn window_titles() -> Vec<String> {
+    if !crate::linux_wayland::is_wayland_session()
+        && let Some(titles) = crate::linux_x11::window_titles()
+    {
+        return titles;
+    }
+    crate::linux_wayland::window_titles().unwrap_or_default()
+}
+
 /// Read currently-running process names from `/proc` (blocking). Exposed for the
 /// `CloseGame` command, which has no process watcher on Linux.
 pub(crate) fn current_process_names() -> Vec<String> {
-    GameSensor::get_process_names_blocking().unwrap_or_default()
+    current_processes()
+        .into_iter()
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// `(pid, name)` of every running process. Blocking.
+pub(crate) fn current_processes() -> Vec<(u32, String)> {
+    GameSensor::get_processes_blocking().unwrap_or_default()
 }
@@ -7,8 +7,10 @@
 use log::warn;
 use log::{debug, info};
 use std::sync::Arc;
-use tokio::time::{Duration, MissedTickBehavior, interval};
 
+use super::history::History;
+use super::runner::Published;
+use super::system::with_history;
 use crate::AppState;
 
 pub struct GpuSensor {
@@ -26,14 +28,18 @@ impl GpuSensor {
             return;
         }
         let interval_secs = config.intervals.gpu.max(1);
+        let idle_secs = config.intervals.idle_poll;
+        let mut published = Published::default();
+        published.configure(&config);
         drop(config);
 
-        let mut tick = interval(Duration::from_secs(interval_secs));
-
-        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut activity_rx = crate::activity::subscribe();
+        let activity = *activity_rx.borrow_and_update();
+        let mut tick = crate::activity::ticker(interval_secs, idle_secs, activity);
         let mut shutdown_rx = self.state.shutdown_tx.subscribe();
         let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
-        let mut prev_gpu = String::new();
+        let mut config_rx = self.state.config_generation.subscribe();
+        let mut history = History::default();
 
         info!("GPU sensor started (polled every {}s)", interval_secs);
 
@@ -46,18 +52,32 @@ impl GpuSensor {
                 }
                 Ok(()) = reconnect_rx.recv() => {
                     // Force republish on reconnect
-                    prev_gpu.clear();
+                    published.clear();
+                }
+                // deadbands are hot-reloaded; the interval is read at startup.
+                r = config_rx.recv() => {
+                    if matches!(r, Ok(()) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_))) {
+                        published.configure(&*self.state.config.read().await);
+                    }
+                }
+                Ok(()) = activity_rx.changed() => {
+                    // Idle rate while away, nothing while paused; back to
+                    // normal (with an immediate poll) on activity.
+                    let activity = *activity_rx.borrow_and_update();
+                    tick = crate::activity::ticker(interval_secs, idle_secs, activity);
                 }
                 _ = tick.tick() => {
+                    if activity_rx.borrow().is_paused() {
+                        continue;
+                    }
                     // get_gpu_usage blocks (PDH collection on Windows, nvidia-smi
                     // fork+exec on Linux); keep it off the single-threaded runtime.
                     let Ok(gpu_str) = tokio::task::spawn_blocking(get_gpu_usage).await else {
                         continue;
                     };
-                    if gpu_str != prev_gpu {
-                        self.state.mqtt.publish_sensor("gpu_usage", &gpu_str).await;
-                        prev_gpu = gpu_str;
-                    }
+                    published
+                        .publish(&self.state, with_history(&mut history, "gpu_usage", gpu_str))
+                        .await;
                 }
             }
         }
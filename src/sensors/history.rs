@@ -0,0 +1,103 @@
+//! Recent readings of a numeric sensor
+//!
+//! `cpu_usage`, `memory_usage` and `gpu_usage` keep their last five minutes
+//! of readings here and publish `avg_1m`, `avg_5m` and `peak` (the highest
+//! since the bridge started) as attributes, so a dashboard gets basic
+//! smoothing without statistics helpers.
+
+use std::collections::VecDeque;
+
+use tokio::time::{Duration, Instant};
+
+/// Oldest reading kept
+const WINDOW: Duration = Duration::from_mins(5);
+/// Readings kept at most, whatever the poll interval (1s polls fill 300)
+const MAX_SAMPLES: usize = 320;
+
+#[derive(Default)]
+pub(super) struct History {
+    samples: VecDeque<(Instant, f64)>,
+    peak: Option<f64>,
+}
+
+impl History {
+    /// Record a reading; a non-numeric one ("unavailable") is skipped.
+    pub fn record(&mut self, now: Instant, value: &str) {
+        let Ok(value) = value.parse::<f64>() else {
+            return;
+        };
+        while self
+            .samples
+            .front()
+            .is_some_and(|(at, _)| now.duration_since(*at) > WINDOW)
+            || self.samples.len() >= MAX_SAMPLES
+        {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((now, value));
+        self.peak = Some(self.peak.map_or(value, |peak| peak.max(value)));
+    }
+
+    /// `{"avg_1m", "avg_5m", "peak"}`, each null until there's a reading
+    pub fn attributes(&self, now: Instant) -> serde_json::Value {
+        serde_json::json!({
+            "avg_1m": self.average(now, Duration::from_mins(1)),
+            "avg_5m": self.average(now, WINDOW),
+            "peak": self.peak,
+        })
+    }
+
+    /// Mean of the readings in the last `span`, to one decimal
+    fn average(&self, now: Instant, span: Duration) -> Option<f64> {
+        let (sum, count) = self
+            .samples
+            .iter()
+            .rev()
+            .take_while(|(at, _)| now.duration_since(*at) <= span)
+            .fold((0.0, 0u32), |(sum, count), (_, value)| {
+                (sum + value, count + 1)
+            });
+        (count > 0).then(|| (sum / f64::from(count) * 10.0).round() / 10.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut history = History::default();
+        assert_eq!(
+            history.attributes(at(0)),
+            serde_json::json!({ "avg_1m": null, "avg_5m": null, "peak": null })
+        );
+
+        history.record(at(0), "90.0");
+        history.record(at(200), "unavailable");
+        history.record(at(250), "20.0");
+        history.record(at(280), "30.0");
+        assert_eq!(
+            history.attributes(at(280)),
+            serde_json::json!({ "avg_1m": 25.0, "avg_5m": 46.7, "peak": 90.0 })
+        );
+        // The 90% reading ages out of both averages, not out of the peak.
+        history.record(at(320), "10.0");
+        assert_eq!(
+            history.attributes(at(320)),
+            serde_json::json!({ "avg_1m": 20.0, "avg_5m": 20.0, "peak": 90.0 })
+        );
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let start = Instant::now();
+        let mut history = History::default();
+        for i in 0..1000 {
+            history.record(start + Duration::from_millis(i * 100), "1");
+        }
+        assert_eq!(history.samples.len(), MAX_SAMPLES);
+    }
+}
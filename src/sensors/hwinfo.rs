@@ -45,7 +45,7 @@ pub struct DiagnosticPayload {
 }
 
 /// Build the diagnostic payload published to
-/// `homeassistant/sensor/<device>/hwinfo_diagnostic/state` (and matching
+/// `pc-bridge/<device>/hwinfo_diagnostic/state` (and matching
 /// attributes topic). Pure function - exercised by unit tests below.
 ///
 /// `matched_keys`/`unmatched_keys` describe how the `MATCH_RULES` table mapped
@@ -726,6 +726,7 @@ mod win {
                                             continue;
                                         };
                                         matched.push(rule.key);
+                                        crate::thermal::record(rule.key, reading.value);
 
                                         if !self.should_publish(
                                             rule.key,
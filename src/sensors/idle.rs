@@ -16,11 +16,14 @@ use std::sync::Arc;
 use std::time::Duration;
 use time::OffsetDateTime;
 use time::format_description::well_known::Rfc3339;
-use tokio::time::{MissedTickBehavior, interval};
+use tokio::sync::broadcast;
 use windows::Win32::System::SystemInformation::GetTickCount64;
 use windows::Win32::UI::Input::KeyboardAndMouse::{GetLastInputInfo, LASTINPUTINFO};
 
+use super::process_watcher::ProcessChangeNotification;
+use super::runner::{Reading, Sensor};
 use crate::AppState;
+use crate::config::Config;
 
 /// Format an OffsetDateTime as RFC 3339 string
 fn format_rfc3339(dt: OffsetDateTime) -> String {
@@ -29,163 +32,88 @@ fn format_rfc3339(dt: OffsetDateTime) -> String {
 
 pub struct IdleSensor {
     state: Arc<AppState>,
+    process_rx: broadcast::Receiver<ProcessChangeNotification>,
+    /// Last idle time read, for idle/active transition events
+    prev_idle_secs: i64,
+    query_failed: bool,
 }
 
 impl IdleSensor {
     pub fn new(state: Arc<AppState>) -> Self {
-        Self { state }
+        let process_rx = state.process_watcher.subscribe();
+        Self {
+            state,
+            process_rx,
+            prev_idle_secs: -1,
+            query_failed: false,
+        }
     }
 
     pub async fn run(self) {
-        let config = self.state.config.read().await;
-        let interval_secs = config.intervals.last_active.max(1); // Prevent panic on 0
-        drop(config);
-
-        let mut tick = interval(Duration::from_secs(interval_secs));
-
-        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
-        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
-        let mut process_rx = self.state.process_watcher.subscribe();
-        let mut config_rx = self.state.config_generation.subscribe();
-        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
-
-        // Dedup trackers: only publish when the whole-second value changes.
-        // `idle_seconds` grows each tick while idle (so it keeps publishing);
-        // `lastactive` freezes while idle (so it stops publishing) - both correct.
-        let mut prev_idle_secs: i64 = -1;
-        let mut prev_lastactive_secs: i64 = i64::MIN;
-        let mut query_failed = false;
-
-        // Publish initial idle state
-        self.publish_idle(
-            &mut prev_idle_secs,
-            &mut prev_lastactive_secs,
-            &mut query_failed,
-        )
-        .await;
-
-        // Publish initial screensaver state (retained so HA picks it up)
-        let screensaver_active = self.state.process_watcher.has_screensaver_running().await;
-        let screensaver_state = if screensaver_active { "on" } else { "off" };
-        debug!("Initial screensaver state: {}", screensaver_state);
-        self.state
-            .mqtt
-            .publish_sensor_retained("screensaver", screensaver_state)
-            .await;
-        let mut prev_screensaver_state = screensaver_active;
-
         info!("Idle sensor started (screensaver: push-based, idle: polled via GetLastInputInfo)");
-
-        loop {
-            tokio::select! {
-                biased;
-                _ = shutdown_rx.recv() => {
-                    debug!("Idle sensor shutting down");
-                    break;
-                }
-                // Hot-reload: pick up new interval from config changes
-                Ok(()) = config_rx.recv() => {
-                    let config = self.state.config.read().await;
-                    let new_interval = config.intervals.last_active.max(1);
-                    drop(config);
-                    tick = interval(Duration::from_secs(new_interval));
-                    tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
-                    debug!("Idle sensor: interval updated to {}s", new_interval);
-                }
-                // Re-publish (non-retained) idle values after a broker reconnect
-                // so they don't stay stale until the value next changes.
-                Ok(()) = reconnect_rx.recv() => {
-                    prev_idle_secs = -1;
-                    prev_lastactive_secs = i64::MIN;
-                }
-                _ = tick.tick() => {
-                    self.publish_idle(&mut prev_idle_secs, &mut prev_lastactive_secs, &mut query_failed).await;
-                }
-                result = process_rx.recv() => {
-                    // Process list changed - check screensaver state immediately
-                    match result {
-                        Ok(_notification) => {
-                            let screensaver_active = self.state.process_watcher.has_screensaver_running().await;
-                            if screensaver_active != prev_screensaver_state {
-                                let state_str = if screensaver_active { "on" } else { "off" };
-                                debug!("Screensaver state changed: {}", state_str);
-                                self.state.mqtt.publish_sensor_retained("screensaver", state_str).await;
-                                prev_screensaver_state = screensaver_active;
-                            }
-                        }
-                        Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
-                            debug!("Idle sensor lagged {} notifications, re-checking screensaver", n);
-                            let screensaver_active = self.state.process_watcher.has_screensaver_running().await;
-                            if screensaver_active != prev_screensaver_state {
-                                let state_str = if screensaver_active { "on" } else { "off" };
-                                debug!("Screensaver state changed (post-lag): {}", state_str);
-                                self.state.mqtt.publish_sensor_retained("screensaver", state_str).await;
-                                prev_screensaver_state = screensaver_active;
-                            }
-                        }
-                        Err(tokio::sync::broadcast::error::RecvError::Closed) => {
-                            debug!("Process watcher channel closed");
-                            break;
-                        }
-                    }
-                }
-            }
-        }
+        super::runner::run(Arc::clone(&self.state), self).await;
     }
 
-    /// Poll idle time and publish `idle_seconds` + `lastactive`.
+    /// Poll idle time into `idle_seconds` + `lastactive`.
     ///
     /// On query failure we keep the last published value rather than fabricating
     /// "active now" - the old behaviour made the PC look perpetually busy, which
     /// broke any "PC idle for N minutes" automation downstream.
-    async fn publish_idle(
-        &self,
-        prev_idle_secs: &mut i64,
-        prev_lastactive_secs: &mut i64,
-        query_failed: &mut bool,
-    ) {
-        let Some(idle_ms) = self.get_idle_ms() else {
-            if !*query_failed {
+    async fn read_idle(&mut self, readings: &mut Vec<Reading>) {
+        let Some(idle_ms) = Self::get_idle_ms() else {
+            if !self.query_failed {
                 warn!(
                     "GetLastInputInfo failed - pausing idle updates (last values retained). \
                      The bridge must run in the interactive user session for idle tracking to work."
                 );
-                *query_failed = true;
+                self.query_failed = true;
             }
             return;
         };
-        if *query_failed {
+        if self.query_failed {
             info!("GetLastInputInfo recovered; resuming idle updates");
-            *query_failed = false;
+            self.query_failed = false;
         }
 
-        let idle_secs = (idle_ms / 1000).max(0);
-        debug!("Idle: {idle_secs}s since last input");
+        let input_idle = (idle_ms / 1000).max(0);
+        let policy = self.state.config.read().await.idle.clone();
+        let remote_idle = if policy.remote_sessions {
+            Self::remote_session_idle_secs()
+        } else {
+            None
+        };
+        let idle_secs = crate::activity::effective_idle_secs(
+            input_idle,
+            crate::activity::signals(remote_idle),
+            &policy,
+        );
+        debug!("Idle: {idle_secs}s ({input_idle}s since input in this session)");
+        // Another signal was more recent than the last input here
+        let idle_ms = if idle_secs < input_idle {
+            idle_secs * 1000
+        } else {
+            idle_ms
+        };
+
+        if let Some(event) = crate::events::idle_transition(self.prev_idle_secs, idle_secs) {
+            self.state.mqtt.publish_event(&event).await;
+        }
+        self.prev_idle_secs = idle_secs;
+        crate::activity::set_user_idle(idle_secs >= crate::events::AWAY_THRESHOLD_SECS);
 
         // idle_seconds - numeric, grows while idle, resets to ~0 on input.
-        if idle_secs != *prev_idle_secs {
-            self.state
-                .mqtt
-                .publish_sensor("idle_seconds", &idle_secs.to_string())
-                .await;
-            *prev_idle_secs = idle_secs;
-        }
+        readings.push(Reading::new("idle_seconds", idle_secs.to_string()));
 
-        // lastactive - timestamp of last input; stays frozen (no republish) while idle.
+        // lastactive - timestamp of last input, in whole seconds so it stays
+        // frozen (no republish) while idle.
         let last_active = OffsetDateTime::now_utc() - time::Duration::milliseconds(idle_ms);
-        let la_secs = last_active.unix_timestamp();
-        if la_secs != *prev_lastactive_secs {
-            self.state
-                .mqtt
-                .publish_sensor("lastactive", &format_rfc3339(last_active))
-                .await;
-            *prev_lastactive_secs = la_secs;
-        }
+        let last_active = last_active.replace_nanosecond(0).unwrap_or(last_active);
+        readings.push(Reading::new("lastactive", format_rfc3339(last_active)));
     }
 
     /// Milliseconds since the last keyboard/mouse input, or `None` if the query
     /// failed (e.g. no access to the interactive input desktop).
-    fn get_idle_ms(&self) -> Option<i64> {
+    fn get_idle_ms() -> Option<i64> {
         unsafe {
             let mut lii = LASTINPUTINFO {
                 cbSize: std::mem::size_of::<LASTINPUTINFO>() as u32,
@@ -202,3 +130,93 @@ impl IdleSensor {
         }
     }
 }
+
+impl IdleSensor {
+    /// Seconds since input in the most recently used active session that
+    /// reports it (Remote Desktop sessions do; the console reports none).
+    fn remote_session_idle_secs() -> Option<i64> {
+        use windows::Win32::System::RemoteDesktop::{
+            WTS_CURRENT_SERVER_HANDLE, WTS_SESSION_INFOW, WTSActive, WTSEnumerateSessionsW,
+            WTSFreeMemory, WTSINFOW, WTSQuerySessionInformationW, WTSSessionInfo,
+        };
+        use windows::core::PWSTR;
+
+        // SAFETY: buffers come from WTS and are freed once, after their last use;
+        // WTSSessionInfo's buffer is a WTSINFOW when it is at least that big.
+        unsafe {
+            let mut sessions: *mut WTS_SESSION_INFOW = std::ptr::null_mut();
+            let mut count = 0u32;
+            WTSEnumerateSessionsW(
+                WTS_CURRENT_SERVER_HANDLE,
+                0,
+                1,
+                &raw mut sessions,
+                &raw mut count,
+            )
+            .ok()?;
+            let mut idle: Option<i64> = None;
+            for session in std::slice::from_raw_parts(sessions, count as usize) {
+                if session.State != WTSActive {
+                    continue;
+                }
+                let mut buffer = PWSTR::null();
+                let mut bytes = 0u32;
+                if WTSQuerySessionInformationW(
+                    WTS_CURRENT_SERVER_HANDLE,
+                    session.SessionId,
+                    WTSSessionInfo,
+                    &raw mut buffer,
+                    &raw mut bytes,
+                )
+                .is_err()
+                {
+                    continue;
+                }
+                if bytes as usize >= std::mem::size_of::<WTSINFOW>() {
+                    let info = &*buffer.0.cast::<WTSINFOW>();
+                    // FILETIMEs, in 100ns units
+                    if info.LastInputTime > 0 && info.CurrentTime >= info.LastInputTime {
+                        let secs = (info.CurrentTime - info.LastInputTime) / 10_000_000;
+                        idle = Some(idle.map_or(secs, |i| i.min(secs)));
+                    }
+                }
+                WTSFreeMemory(buffer.0.cast());
+            }
+            WTSFreeMemory(sessions.cast());
+            idle
+        }
+    }
+}
+
+impl Sensor for IdleSensor {
+    const NAME: &'static str = "Idle";
+
+    fn interval(config: &Config) -> Option<Duration> {
+        Some(Duration::from_secs(config.intervals.last_active))
+    }
+
+    /// Process list changed - check the screensaver state immediately.
+    async fn event(&mut self) -> Option<()> {
+        match self.process_rx.recv().await {
+            Ok(_notification) => Some(()),
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                debug!(
+                    "Idle sensor lagged {} notifications, re-checking screensaver",
+                    n
+                );
+                Some(())
+            }
+            Err(broadcast::error::RecvError::Closed) => None,
+        }
+    }
+
+    async fn poll(&mut self) -> Vec<Reading> {
+        let mut readings = Vec::with_capacity(3);
+        self.read_idle(&mut readings).await;
+        let screensaver_active = self.state.process_watcher.has_screensaver_running().await;
+        readings.push(
+            Reading::new("screensaver", if screensaver_active { "on" } else { "off" }).retained(),
+        );
+        readings
+    }
+}
@@ -4,14 +4,16 @@
 //! Last-active time uses bundled backends (x11rb on X11, D-Bus via zbus on
 //! GNOME/KDE Wayland), falling back to xprintidle/qdbus if those don't answer.
 
-use log::{debug, info, warn};
+use log::warn;
 use std::process::Command;
 use std::sync::Arc;
+use std::time::Duration;
 use time::OffsetDateTime;
 use time::format_description::well_known::Rfc3339;
-use tokio::time::{Duration, MissedTickBehavior, interval};
 
+use super::runner::{Reading, Sensor};
 use crate::AppState;
+use crate::config::Config;
 
 /// Format an OffsetDateTime as RFC 3339 string
 fn format_rfc3339(dt: OffsetDateTime) -> String {
@@ -20,131 +22,32 @@ fn format_rfc3339(dt: OffsetDateTime) -> String {
 
 pub struct IdleSensor {
     state: Arc<AppState>,
+    /// Last idle time read, for idle/active transition events
+    prev_idle_secs: i64,
 }
 
 impl IdleSensor {
     pub fn new(state: Arc<AppState>) -> Self {
-        Self { state }
+        Self {
+            state,
+            prev_idle_secs: -1,
+        }
     }
 
     pub async fn run(self) {
-        let config = self.state.config.read().await;
-        let mut interval_secs = config.intervals.last_active.max(1); // Prevent panic on 0
-        drop(config);
-
         // On wlroots Wayland (no D-Bus idle), start the ext-idle-notify listener
         // that backs the D-Bus-less idle path. Idempotent; the runtime
         // is_wayland_session() guard means it's a no-op off Wayland.
         if crate::linux_wayland::is_wayland_session() {
             crate::linux_idle::ensure_started();
         }
-
-        let mut tick = interval(Duration::from_secs(interval_secs));
-        // Skip missed ticks so a stall (e.g. after resume) doesn't fire a burst
-        // of catch-up xprintidle subprocesses.
-        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
-        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
-        let mut config_rx = self.state.config_generation.subscribe();
-        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
-
-        // Track previous values to skip duplicate publishes. idle_seconds grows
-        // each tick while idle (keeps publishing); lastactive freezes while idle.
-        let mut prev_last_active = String::new();
-        let mut prev_idle_secs: i64 = -1;
-
-        // Publish initial state
-        self.publish_idle(&mut prev_last_active, &mut prev_idle_secs)
-            .await;
-
-        // Publish initial screensaver state (retained so HA picks it up).
-        // Off the runtime: is_screensaver_active spawns dbus-send subprocesses.
-        let screensaver_active = tokio::task::spawn_blocking(is_screensaver_active)
-            .await
-            .unwrap_or(false);
-        let screensaver_state = if screensaver_active { "on" } else { "off" };
-        debug!("Initial screensaver state: {}", screensaver_state);
-        self.state
-            .mqtt
-            .publish_sensor_retained("screensaver", screensaver_state)
-            .await;
-        let mut prev_screensaver_state = screensaver_active;
-
-        loop {
-            tokio::select! {
-                biased;
-                _ = shutdown_rx.recv() => {
-                    debug!("Idle sensor shutting down");
-                    break;
-                }
-                // Config hot-reload: update poll interval
-                Ok(()) = config_rx.recv() => {
-                    let config = self.state.config.read().await;
-                    let new_interval = config.intervals.last_active.max(1);
-                    if new_interval != interval_secs {
-                        interval_secs = new_interval;
-                        tick = interval(Duration::from_secs(interval_secs));
-                        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
-                        info!("Idle sensor: interval changed to {}s", interval_secs);
-                    }
-                }
-                // MQTT reconnected: force republish current state
-                Ok(()) = reconnect_rx.recv() => {
-                    info!("Idle sensor: MQTT reconnected, republishing current state");
-                    prev_last_active.clear();
-                    prev_idle_secs = -1;
-                    self.publish_idle(&mut prev_last_active, &mut prev_idle_secs).await;
-                }
-                _ = tick.tick() => {
-                    self.publish_idle(&mut prev_last_active, &mut prev_idle_secs).await;
-
-                    // Check screensaver state (polled alongside idle time), off
-                    // the runtime: is_screensaver_active spawns dbus-send.
-                    let screensaver_active = tokio::task::spawn_blocking(is_screensaver_active)
-                        .await
-                        .unwrap_or(false);
-                    if screensaver_active != prev_screensaver_state {
-                        let state_str = if screensaver_active { "on" } else { "off" };
-                        debug!("Screensaver state changed: {}", state_str);
-                        self.state.mqtt.publish_sensor_retained("screensaver", state_str).await;
-                        prev_screensaver_state = screensaver_active;
-                    }
-                }
-            }
-        }
-    }
-
-    /// Publish `lastactive` + `idle_seconds` (parity with the Windows sensor).
-    /// Skips publishing entirely when idle detection is unavailable, so we never
-    /// fabricate "active now" (which would make the PC look perpetually busy and
-    /// break "idle for N minutes" automations).
-    async fn publish_idle(&self, prev_last: &mut String, prev_idle: &mut i64) {
-        let Some(idle_secs) = self.get_idle_seconds().await else {
-            return;
-        };
-        // idle_seconds grows while idle → publish on change (each tick).
-        if idle_secs != *prev_idle {
-            self.state
-                .mqtt
-                .publish_sensor("idle_seconds", &idle_secs.to_string())
-                .await;
-            *prev_idle = idle_secs;
-        }
-        // lastactive = now - idle → freezes while idle (stops publishing).
-        let last_active = OffsetDateTime::now_utc() - time::Duration::seconds(idle_secs);
-        let formatted = format_rfc3339(last_active);
-        if formatted != *prev_last {
-            self.state
-                .mqtt
-                .publish_sensor("lastactive", &formatted)
-                .await;
-            *prev_last = formatted;
-        }
+        super::runner::run(Arc::clone(&self.state), self).await;
     }
 
     /// Seconds since the last user input, or `None` if no detection method is
     /// available. Tries bundled x11rb (X11) then D-Bus (Wayland), then the
     /// external `xprintidle`/`qdbus` fallbacks.
-    async fn get_idle_seconds(&self) -> Option<i64> {
+    async fn get_idle_seconds() -> Option<i64> {
         tokio::task::spawn_blocking(Self::get_idle_seconds_blocking)
             .await
             .ok()
@@ -206,6 +109,53 @@ impl IdleSensor {
     }
 }
 
+impl Sensor for IdleSensor {
+    const NAME: &'static str = "Idle";
+
+    fn interval(config: &Config) -> Option<Duration> {
+        Some(Duration::from_secs(config.intervals.last_active))
+    }
+
+    /// `lastactive` + `idle_seconds` (parity with the Windows sensor) and the
+    /// screensaver state. Idle readings are skipped entirely when idle
+    /// detection is unavailable, so we never fabricate "active now" (which
+    /// would make the PC look perpetually busy and break "idle for N minutes"
+    /// automations).
+    async fn poll(&mut self) -> Vec<Reading> {
+        let mut readings = Vec::with_capacity(3);
+        if let Some(input_idle) = Self::get_idle_seconds().await {
+            // Remote sessions aren't tracked on Linux.
+            let policy = self.state.config.read().await.idle.clone();
+            let idle_secs = crate::activity::effective_idle_secs(
+                input_idle,
+                crate::activity::signals(None),
+                &policy,
+            );
+            if let Some(event) = crate::events::idle_transition(self.prev_idle_secs, idle_secs) {
+                self.state.mqtt.publish_event(&event).await;
+            }
+            self.prev_idle_secs = idle_secs;
+            crate::activity::set_user_idle(idle_secs >= crate::events::AWAY_THRESHOLD_SECS);
+            // idle_seconds grows while idle; lastactive = now - idle freezes.
+            // Whole seconds, so lastactive doesn't change on every tick.
+            let now = OffsetDateTime::now_utc();
+            let last_active =
+                now.replace_nanosecond(0).unwrap_or(now) - time::Duration::seconds(idle_secs);
+            readings.push(Reading::new("idle_seconds", idle_secs.to_string()));
+            readings.push(Reading::new("lastactive", format_rfc3339(last_active)));
+        }
+
+        // Off the runtime: is_screensaver_active spawns dbus-send subprocesses.
+        let screensaver_active = tokio::task::spawn_blocking(is_screensaver_active)
+            .await
+            .unwrap_or(false);
+        readings.push(
+            Reading::new("screensaver", if screensaver_active { "on" } else { "off" }).retained(),
+        );
+        readings
+    }
+}
+
 /// Check if a screensaver is currently active on Linux.
 ///
 /// Tries multiple detection methods:
@@ -0,0 +1,247 @@
+//! Audio playing sensor
+//!
+//! `media_playing` is "on" while the default output device is actually
+//! producing sound, from its peak meter, polled every
+//! `intervals.media_playing`. Unlike `now_playing` it needs no media session:
+//! a browser tab, a game or a video call all count, and a paused player
+//! doesn't. It stays on through `HOLD_FOR` of silence so gaps between tracks
+//! don't flap it. The `process` attribute names the loudest source.
+//! - Windows: IAudioMeterInformation on the endpoint and each audio session
+//! - Linux: a short `parec` capture of the default sink's monitor; the
+//!   process is the first uncorked stream in `pactl list sink-inputs`
+
+use log::{debug, info};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::AppState;
+
+/// Peak (0.0-1.0) at or below this is silence, about -60 dBFS.
+const SILENCE: f32 = 0.001;
+
+/// Silence shorter than this keeps `media_playing` on.
+const HOLD_FOR: Duration = Duration::from_secs(10);
+
+pub struct MediaPlayingSensor {
+    state: Arc<AppState>,
+}
+
+impl MediaPlayingSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.media_playing {
+            return;
+        }
+        let poll_secs = config.intervals.media_playing.max(1);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut last_sound: Option<Instant> = None;
+        let mut process: Option<String> = None;
+        let mut prev: Option<(&str, Option<String>)> = None;
+
+        info!("Media playing sensor started (polled every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Media playing sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => prev = None,
+                _ = tick.tick() => {
+                    let Some((peak, loudest)) = self.sample().await else {
+                        crate::activity::set_media_playing(false);
+                        if prev.as_ref().is_none_or(|(state, _)| *state != "unavailable") {
+                            self.state.mqtt.publish_sensor("media_playing", "unavailable").await;
+                            prev = Some(("unavailable", None));
+                        }
+                        continue;
+                    };
+                    if peak > SILENCE {
+                        last_sound = Some(Instant::now());
+                        if loudest.is_some() {
+                            process = loudest;
+                        }
+                    }
+                    let playing = last_sound.is_some_and(|t| t.elapsed() < HOLD_FOR);
+                    crate::activity::set_media_playing(playing);
+                    if !playing {
+                        process = None;
+                    }
+                    let current = (if playing { "on" } else { "off" }, process.clone());
+                    if prev.as_ref() == Some(&current) {
+                        continue;
+                    }
+                    if prev.as_ref().map(|(state, _)| *state) != Some(current.0) {
+                        debug!("Media playing: {} ({:?})", current.0, current.1);
+                        self.state.mqtt.publish_sensor("media_playing", current.0).await;
+                    }
+                    self.state
+                        .mqtt
+                        .publish_sensor_attributes(
+                            "media_playing",
+                            &serde_json::json!({ "process": current.1 }),
+                        )
+                        .await;
+                    prev = Some(current);
+                }
+            }
+        }
+    }
+
+    /// Output peak and the loudest source's process name.
+    #[cfg(windows)]
+    async fn sample(&self) -> Option<(f32, Option<String>)> {
+        let (peak, sessions) = tokio::task::spawn_blocking(crate::audio::output_peaks)
+            .await
+            .ok()
+            .flatten()?;
+        // Session 0 is system sounds: not a media source.
+        let loudest = sessions
+            .into_iter()
+            .filter(|&(pid, peak)| pid != 0 && peak > SILENCE)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(pid, _)| pid);
+        let process = match loudest {
+            Some(pid) => {
+                let procs = self.state.process_watcher.state();
+                let procs = procs.read().await;
+                procs
+                    .processes()
+                    .find(|&(p, _)| p == pid)
+                    .map(|(_, name)| name.to_string())
+            }
+            None => None,
+        };
+        Some((peak, process))
+    }
+
+    #[cfg(unix)]
+    async fn sample(&self) -> Option<(f32, Option<String>)> {
+        let peak = monitor_peak().await?;
+        let process = if peak > SILENCE {
+            playing_stream().await
+        } else {
+            None
+        };
+        Some((peak, process))
+    }
+}
+
+/// Quarter of a second of 8 kHz mono s16le.
+#[cfg(unix)]
+const CAPTURE_BYTES: usize = 4000;
+
+/// Peak of a short capture of the default sink's monitor source.
+#[cfg(unix)]
+async fn monitor_peak() -> Option<f32> {
+    use tokio::io::AsyncReadExt;
+
+    let mut child = tokio::process::Command::new("parec")
+        .args([
+            "--device=@DEFAULT_MONITOR@",
+            "--raw",
+            "--format=s16le",
+            "--channels=1",
+            "--rate=8000",
+        ])
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .ok()?;
+    let mut stdout = child.stdout.take()?;
+    let mut buf = vec![0u8; CAPTURE_BYTES];
+    let read = tokio::time::timeout(Duration::from_secs(2), stdout.read_exact(&mut buf)).await;
+    let _ = child.kill().await;
+    read.ok()?.ok()?;
+    Some(peak_s16le(&buf))
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+fn peak_s16le(samples: &[u8]) -> f32 {
+    let peak = samples
+        .chunks_exact(2)
+        .map(|s| i16::from_le_bytes([s[0], s[1]]).unsigned_abs())
+        .max()
+        .unwrap_or(0);
+    f32::from(peak) / 32768.0
+}
+
+/// Process of the first playing (uncorked) stream on any sink.
+#[cfg(unix)]
+async fn playing_stream() -> Option<String> {
+    let out = tokio::process::Command::new("pactl")
+        .args(["list", "sink-inputs"])
+        .env("LC_ALL", "C")
+        .kill_on_drop(true)
+        .output();
+    let out = tokio::time::timeout(Duration::from_secs(2), out)
+        .await
+        .ok()?
+        .ok()?;
+    parse_sink_inputs(&String::from_utf8_lossy(&out.stdout))
+        .into_iter()
+        .next()
+}
+
+/// Uncorked streams in `pactl list sink-inputs` output, by their
+/// `application.process.binary` (else `application.name`).
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_sink_inputs(output: &str) -> Vec<String> {
+    fn property(block: &str, key: &str) -> Option<String> {
+        block.lines().find_map(|line| {
+            let (k, v) = line.trim().split_once(" = ")?;
+            (k == key).then(|| v.trim_matches('"').to_string())
+        })
+    }
+    output
+        .split("Sink Input #")
+        .skip(1)
+        .filter(|block| block.lines().any(|l| l.trim() == "Corked: no"))
+        .filter_map(|block| {
+            property(block, "application.process.binary")
+                .or_else(|| property(block, "application.name"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_s16le() {
+        assert!(peak_s16le(&[0, 0, 0, 0]) < f32::EPSILON);
+        let samples: Vec<u8> = [100i16, -16384, 2000]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        assert!((peak_s16le(&samples) - 0.5).abs() < f32::EPSILON);
+        assert!((peak_s16le(&i16::MIN.to_le_bytes()) - 1.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_parse_sink_inputs() {
+        let out = "Sink Input #41\n\tDriver: protocol-native.c\n\tCorked: yes\n\
+                   \tProperties:\n\t\tapplication.name = \"Firefox\"\n\
+                   \t\tapplication.process.binary = \"firefox\"\n\n\
+                   Sink Input #57\n\tDriver: protocol-native.c\n\tCorked: no\n\
+                   \tProperties:\n\t\tapplication.name = \"Spotify\"\n\
+                   \t\tapplication.process.binary = \"spotify\"\n\n\
+                   Sink Input #60\n\tCorked: no\n\tProperties:\n\
+                   \t\tapplication.name = \"ALSA plug-in [mpv]\"\n";
+        assert_eq!(parse_sink_inputs(out), ["spotify", "ALSA plug-in [mpv]"]);
+        assert!(parse_sink_inputs("").is_empty());
+    }
+}
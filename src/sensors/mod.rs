@@ -2,14 +2,41 @@
 
 mod audio_device;
 mod capture;
+mod connectivity;
 mod custom;
+mod device_battery;
 mod disk;
+mod disk_health;
+mod display_mode;
+mod docker;
+mod focus;
+mod game_art;
+mod gamepad;
 mod gpu;
+mod history;
+mod media_playing;
 mod network;
+mod network_connection;
 mod now_playing;
+mod perf_mode;
+mod power_plan;
+mod printer;
+mod process_alerts;
+mod process_sensors;
+mod remote_desktop;
+mod reporting;
+mod runner;
+mod sleep_blockers;
 mod system;
+mod theme;
+mod transcoding;
 mod uptime;
+mod usb;
 mod volume;
+mod vr;
+mod wake_timers;
+mod window_history;
+mod windows_update;
 
 pub mod hwinfo;
 
@@ -32,14 +59,41 @@ mod session_linux;
 
 pub use audio_device::AudioDeviceSensor;
 pub use capture::CaptureSensor;
+pub use connectivity::{LatencySensor, PublicIpSensor};
 pub use custom::CustomSensorManager;
+pub use device_battery::DeviceBatterySensor;
+pub(crate) use device_battery::slug;
 pub use disk::DiskSensor;
+pub use disk_health::DiskHealthSensor;
+pub use display_mode::DisplayModeSensor;
+pub use docker::DockerSensor;
+pub(crate) use docker::docker_command;
+pub use focus::FocusSensor;
+pub use game_art::GameArtSensor;
+pub use gamepad::GamepadSensor;
 pub use gpu::GpuSensor;
+pub use media_playing::MediaPlayingSensor;
 pub use network::NetworkSensor;
+pub use network_connection::NetworkConnectionSensor;
 pub use now_playing::NowPlayingSensor;
+pub use perf_mode::PerfModeSensor;
+pub use power_plan::PowerPlanSensor;
+pub use printer::PrinterSensor;
+pub(crate) use printer::print_payload;
+pub use process_alerts::ProcessAlertSensor;
+pub use process_sensors::ProcessPresenceSensor;
+pub use reporting::ReportingSensor;
+pub use sleep_blockers::SleepBlockersSensor;
 pub use system::{ActiveWindowSensor, SystemSensor};
+pub use theme::ThemeSensor;
+pub(crate) use theme::{OPTIONS as THEME_OPTIONS, set_theme};
+pub use transcoding::TranscodingSensor;
 pub use uptime::UptimeSensor;
+pub use usb::UsbSensor;
 pub use volume::VolumeSensor;
+pub use vr::VrSensor;
+pub use wake_timers::WakeTimerSensor;
+pub use windows_update::WindowsUpdateSensor;
 
 #[cfg(windows)]
 pub use games::GameSensor;
@@ -50,11 +104,15 @@ pub use process_watcher::ProcessWatcher;
 #[cfg(windows)]
 pub use session::SessionSensor;
 pub use steam::SteamSensor;
+#[cfg(windows)]
+pub(crate) use windows_update::{
+    refresh as refresh_windows_updates, wua::install as install_windows_updates,
+};
 
 #[cfg(unix)]
 pub use games_linux::GameSensor;
 #[cfg(unix)]
-pub(crate) use games_linux::current_process_names;
+pub(crate) use games_linux::{current_process_names, current_processes};
 #[cfg(unix)]
 pub use idle_linux::IdleSensor;
 #[cfg(unix)]
@@ -6,7 +6,6 @@
 
 use log::{debug, info};
 use std::sync::Arc;
-use tokio::time::{Duration, MissedTickBehavior, interval};
 
 use crate::AppState;
 
@@ -25,11 +24,12 @@ impl NetworkSensor {
             return;
         }
         let interval_secs = config.intervals.network.max(1);
+        let idle_secs = config.intervals.idle_poll;
         drop(config);
 
-        let mut tick = interval(Duration::from_secs(interval_secs));
-
-        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut activity_rx = crate::activity::subscribe();
+        let activity = *activity_rx.borrow_and_update();
+        let mut tick = crate::activity::ticker(interval_secs, idle_secs, activity);
         let mut shutdown_rx = self.state.shutdown_tx.subscribe();
         let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
         // Seed off the runtime: GetIfTable2 enumeration can be slow, and this is
@@ -57,7 +57,16 @@ impl NetworkSensor {
                     prev_rx.clear();
                     prev_tx.clear();
                 }
+                Ok(()) = activity_rx.changed() => {
+                    // Idle rate while away, nothing while paused; back to
+                    // normal (with an immediate poll) on activity.
+                    let activity = *activity_rx.borrow_and_update();
+                    tick = crate::activity::ticker(interval_secs, idle_secs, activity);
+                }
                 _ = tick.tick() => {
+                    if activity_rx.borrow().is_paused() {
+                        continue;
+                    }
                     // GetIfTable2 (Windows) enumerates the interface table; keep
                     // it off the single-threaded runtime.
                     let Ok(Some(curr)) = tokio::task::spawn_blocking(get_network_totals).await else {
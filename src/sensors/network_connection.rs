@@ -0,0 +1,395 @@
+//! Network connection / VPN sensor
+//!
+//! Polled every `intervals.network_connection`:
+//! - `network_connection`: name of the network the PC reaches the internet
+//!   through (Wi-Fi SSID / network profile), "disconnected" without one; the
+//!   `type` attribute is wifi / wired / cellular
+//! - `network_metered`: whether that connection is metered
+//! - `vpn_active`: whether a VPN is up; `adapters` lists them
+//!
+//! Windows reads the internet connection profile (WinRT NetworkInformation)
+//! and spots VPNs among the up interfaces by type (PPP for the built-in
+//! client) or name. Linux asks NetworkManager over D-Bus, falling back to
+//! VPN-named interfaces (`wg*`, `tun*`, `tailscale0`, ...) for tunnels NM
+//! doesn't manage.
+
+use log::{debug, info};
+use std::sync::Arc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::AppState;
+
+/// Adapter / interface name fragments (lowercase) that mark a VPN.
+const VPN_NAMES: &[&str] = &[
+    "vpn",
+    "wireguard",
+    "openvpn",
+    "tap-windows",
+    "wintun",
+    "tailscale",
+    "zerotier",
+    "anyconnect",
+    "globalprotect",
+    "pangp",
+    "fortinet",
+    "nordlynx",
+    "mullvad",
+];
+
+fn looks_like_vpn(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    VPN_NAMES.iter().any(|k| name.contains(k))
+}
+
+/// What's published.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Connection {
+    name: Option<String>,
+    kind: Option<&'static str>,
+    metered: Option<bool>,
+    vpn: Vec<String>,
+}
+
+fn on_off(value: Option<bool>) -> &'static str {
+    match value {
+        Some(true) => "on",
+        Some(false) => "off",
+        None => "unavailable",
+    }
+}
+
+pub struct NetworkConnectionSensor {
+    state: Arc<AppState>,
+}
+
+impl NetworkConnectionSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.network_connection {
+            return;
+        }
+        let poll_secs = config.intervals.network_connection.max(1);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut prev: Option<Connection> = None;
+
+        info!("Network connection sensor started (polled every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Network connection sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => prev = None,
+                _ = tick.tick() => {
+                    let Ok(conn) = tokio::task::spawn_blocking(read_connection).await else {
+                        continue;
+                    };
+                    if prev.as_ref() == Some(&conn) {
+                        continue;
+                    }
+                    let mqtt = &self.state.mqtt;
+                    if prev.as_ref().is_none_or(|p| (&p.name, p.kind) != (&conn.name, conn.kind)) {
+                        info!("Network connection: {:?} ({:?})", conn.name, conn.kind);
+                        let name = conn.name.as_deref().unwrap_or("disconnected");
+                        mqtt.publish_sensor("network_connection", name).await;
+                        mqtt.publish_sensor_attributes(
+                            "network_connection",
+                            &serde_json::json!({ "type": conn.kind }),
+                        )
+                        .await;
+                    }
+                    if prev.as_ref().is_none_or(|p| p.metered != conn.metered) {
+                        mqtt.publish_sensor("network_metered", on_off(conn.metered)).await;
+                    }
+                    if prev.as_ref().is_none_or(|p| p.vpn != conn.vpn) {
+                        mqtt.publish_sensor("vpn_active", on_off(Some(!conn.vpn.is_empty()))).await;
+                        mqtt.publish_sensor_attributes(
+                            "vpn_active",
+                            &serde_json::json!({ "adapters": conn.vpn }),
+                        )
+                        .await;
+                    }
+                    prev = Some(conn);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+fn read_connection() -> Connection {
+    use windows::Win32::System::Com::{COINIT_MULTITHREADED, CoInitializeEx, CoUninitialize};
+
+    // WinRT activation needs COM on this (pool) thread.
+    let com = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+    let mut conn = internet_profile();
+    if com {
+        unsafe { CoUninitialize() };
+    }
+    conn.vpn = vpn_adapters();
+    conn
+}
+
+/// Name, type and cost of the profile Windows routes internet traffic over.
+#[cfg(windows)]
+fn internet_profile() -> Connection {
+    use windows::Networking::Connectivity::{NetworkCostType, NetworkInformation};
+
+    let Ok(profile) = NetworkInformation::GetInternetConnectionProfile() else {
+        return Connection::default();
+    };
+    let kind = if profile.IsWlanConnectionProfile().unwrap_or(false) {
+        "wifi"
+    } else if profile.IsWwanConnectionProfile().unwrap_or(false) {
+        "cellular"
+    } else {
+        "wired"
+    };
+    // Metered as Settings shows it: a capped/per-byte plan, roaming, or over
+    // the data limit.
+    let metered = profile.GetConnectionCost().ok().map(|cost| {
+        matches!(
+            cost.NetworkCostType(),
+            Ok(NetworkCostType::Fixed | NetworkCostType::Variable)
+        ) || cost.Roaming().unwrap_or(false)
+            || cost.OverDataLimit().unwrap_or(false)
+    });
+    Connection {
+        name: profile
+            .ProfileName()
+            .ok()
+            .map(|n| n.to_string())
+            .filter(|n| !n.is_empty()),
+        kind: Some(kind),
+        metered,
+        vpn: Vec::new(),
+    }
+}
+
+/// Up interfaces that are a VPN: PPP (the built-in VPN client's connections)
+/// or a VPN-named adapter (WireGuard, OpenVPN's TAP/wintun, vendor clients).
+#[cfg(windows)]
+fn vpn_adapters() -> Vec<String> {
+    use windows::Win32::Foundation::WIN32_ERROR;
+    use windows::Win32::NetworkManagement::IpHelper::{FreeMibTable, GetIfTable2};
+    use windows::Win32::NetworkManagement::Ndis::IfOperStatusUp;
+
+    // IF_TYPE_PPP
+    const PPP_TYPE: u32 = 23;
+    // NDIS_INTERFACE_AND_OPER_STATUS_FLAGS.FilterInterface: the per-adapter
+    // lightweight filter rows, which repeat their adapter's name.
+    const FILTER_INTERFACE: u8 = 0b10;
+
+    fn wide(s: &[u16]) -> String {
+        let len = s.iter().position(|&c| c == 0).unwrap_or(s.len());
+        String::from_utf16_lossy(&s[..len])
+    }
+
+    let mut names = Vec::new();
+    unsafe {
+        let mut table = std::ptr::null_mut();
+        if GetIfTable2(&raw mut table) != WIN32_ERROR(0) || table.is_null() {
+            return names;
+        }
+        let entries =
+            std::slice::from_raw_parts((*table).Table.as_ptr(), (*table).NumEntries as usize);
+        for entry in entries {
+            if entry.OperStatus != IfOperStatusUp
+                || entry.InterfaceAndOperStatusFlags._bitfield & FILTER_INTERFACE != 0
+            {
+                continue;
+            }
+            let alias = wide(&entry.Alias);
+            let description = wide(&entry.Description);
+            let ppp = entry.Type == PPP_TYPE && !description.to_ascii_lowercase().contains("pppoe");
+            if (ppp || looks_like_vpn(&alias) || looks_like_vpn(&description))
+                && !names.contains(&alias)
+            {
+                names.push(alias);
+            }
+        }
+        FreeMibTable(table as *const _);
+    }
+    names
+}
+
+#[cfg(unix)]
+fn read_connection() -> Connection {
+    let mut conn = network_manager::connection().unwrap_or_default();
+    if conn.vpn.is_empty() {
+        conn.vpn = vpn_interfaces();
+    }
+    conn
+}
+
+/// Up network interfaces named like a VPN tunnel.
+#[cfg(unix)]
+fn vpn_interfaces() -> Vec<String> {
+    const IFF_UP: u32 = 0x1;
+
+    let Ok(entries) = std::fs::read_dir("/sys/class/net") else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .flatten()
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| is_vpn_interface(name))
+        .filter(|name| {
+            std::fs::read_to_string(format!("/sys/class/net/{name}/flags"))
+                .ok()
+                .and_then(|f| u32::from_str_radix(f.trim().trim_start_matches("0x"), 16).ok())
+                .is_some_and(|flags| flags & IFF_UP != 0)
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[cfg_attr(windows, allow(dead_code))]
+fn is_vpn_interface(name: &str) -> bool {
+    // wg0 / wg-home, tun0 (OpenVPN, most userspace clients), ppp0; "tunl0"
+    // is the kernel's IPIP device, not a VPN.
+    let prefixed = ["wg", "tun", "ppp"].iter().any(|p| {
+        name.strip_prefix(p).is_some_and(|rest| {
+            rest.chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_digit() || c == '-')
+        })
+    });
+    // ZeroTier: "zt" + 8 alphanumerics
+    let zerotier = name.len() == 10
+        && name.starts_with("zt")
+        && name.bytes().all(|b| b.is_ascii_alphanumeric());
+    (prefixed && name != "tunl0") || zerotier || looks_like_vpn(name)
+}
+
+/// NetworkManager's view over the system bus: the primary connection, the
+/// global metered state and the active VPN / WireGuard connections.
+#[cfg(unix)]
+mod network_manager {
+    use super::Connection;
+    use zbus::blocking::Connection as Bus;
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+    const SERVICE: &str = "org.freedesktop.NetworkManager";
+    const ROOT: &str = "/org/freedesktop/NetworkManager";
+    const ACTIVE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+    /// NM_ACTIVE_CONNECTION_STATE_ACTIVATED
+    const ACTIVATED: u32 = 2;
+
+    fn property(bus: &Bus, path: &str, interface: &str, name: &str) -> Option<OwnedValue> {
+        bus.call_method(
+            Some(SERVICE),
+            path,
+            Some("org.freedesktop.DBus.Properties"),
+            "Get",
+            &(interface, name),
+        )
+        .ok()?
+        .body()
+        .deserialize()
+        .ok()
+    }
+
+    fn string(bus: &Bus, path: &str, name: &str) -> Option<String> {
+        property(bus, path, ACTIVE, name)?.try_into().ok()
+    }
+
+    pub(super) fn connection() -> Option<Connection> {
+        let bus = Bus::system().ok()?;
+        // Fails when NetworkManager isn't running: fall back to interfaces.
+        let primary: OwnedObjectPath = property(&bus, ROOT, SERVICE, "PrimaryConnection")?
+            .try_into()
+            .ok()?;
+        let (name, kind) = if primary.as_str() == "/" {
+            (None, None)
+        } else {
+            (
+                string(&bus, primary.as_str(), "Id"),
+                string(&bus, primary.as_str(), "Type").map(|t| super::nm_kind(&t)),
+            )
+        };
+        // NMMetered: 1 yes, 2 no, 3 guess-yes, 4 guess-no, 0 unknown
+        let metered = property(&bus, ROOT, SERVICE, "Metered")
+            .and_then(|v| u32::try_from(v).ok())
+            .and_then(|m| match m {
+                1 | 3 => Some(true),
+                2 | 4 => Some(false),
+                _ => None,
+            });
+        let active: Vec<OwnedObjectPath> = property(&bus, ROOT, SERVICE, "ActiveConnections")
+            .and_then(|v| v.try_into().ok())
+            .unwrap_or_default();
+        let vpn = active
+            .iter()
+            .filter(|path| {
+                string(&bus, path.as_str(), "Type").is_some_and(|t| t == "vpn" || t == "wireguard")
+                    && property(&bus, path.as_str(), ACTIVE, "State")
+                        .and_then(|v| u32::try_from(v).ok())
+                        == Some(ACTIVATED)
+            })
+            .filter_map(|path| string(&bus, path.as_str(), "Id"))
+            .collect();
+        Some(Connection {
+            name,
+            kind,
+            metered,
+            vpn,
+        })
+    }
+}
+
+/// NetworkManager connection type -> the `type` attribute.
+#[cfg_attr(windows, allow(dead_code))]
+fn nm_kind(nm_type: &str) -> &'static str {
+    match nm_type {
+        "802-11-wireless" => "wifi",
+        "802-3-ethernet" => "wired",
+        "gsm" | "cdma" => "cellular",
+        "vpn" | "wireguard" => "vpn",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_looks_like_vpn() {
+        assert!(looks_like_vpn("WireGuard Tunnel"));
+        assert!(looks_like_vpn("TAP-Windows Adapter V9"));
+        assert!(looks_like_vpn("Cisco AnyConnect Secure Mobility Client"));
+        assert!(looks_like_vpn("Work VPN"));
+        assert!(!looks_like_vpn("Intel(R) Ethernet Controller I225-V"));
+        assert!(!looks_like_vpn("vEthernet (WSL)"));
+    }
+
+    #[test]
+    fn test_is_vpn_interface() {
+        for name in ["wg0", "wg-home", "tun0", "ppp0", "tailscale0", "zt3jnojnvw"] {
+            assert!(is_vpn_interface(name), "{name}");
+        }
+        for name in ["eth0", "enp5s0", "wlan0", "lo", "tunl0", "docker0", "ztmp"] {
+            assert!(!is_vpn_interface(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_nm_kind() {
+        assert_eq!(nm_kind("802-11-wireless"), "wifi");
+        assert_eq!(nm_kind("802-3-ethernet"), "wired");
+        assert_eq!(nm_kind("bridge"), "other");
+    }
+}
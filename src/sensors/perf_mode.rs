@@ -0,0 +1,85 @@
+//! Perf mode switch state
+//!
+//! `perf_mode` is the HA switch's state: "ON" while it pauses the
+//! non-essential sensors by hand. Attributes say whether they are paused
+//! right now and why ("manual", or "game" while a game runs). Event-driven
+//! off `crate::activity`, no polling.
+
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use super::runner::{Reading, Sensor};
+use crate::AppState;
+use crate::activity::PerfState;
+use crate::config::Config;
+
+pub struct PerfModeSensor {
+    state: Arc<AppState>,
+    perf_rx: watch::Receiver<PerfState>,
+}
+
+impl PerfModeSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self {
+            state,
+            perf_rx: crate::activity::subscribe_perf(),
+        }
+    }
+
+    pub async fn run(self) {
+        super::runner::run(Arc::clone(&self.state), self).await;
+    }
+}
+
+fn reading(perf: PerfState) -> Reading {
+    Reading::new("perf_mode", if perf.manual { "ON" } else { "OFF" })
+        .retained()
+        .with_attributes(serde_json::json!({
+            "paused": perf.reason.is_some(),
+            "reason": perf.reason,
+        }))
+}
+
+impl Sensor for PerfModeSensor {
+    const NAME: &'static str = "Perf mode";
+
+    fn interval(_config: &Config) -> Option<Duration> {
+        None
+    }
+
+    async fn event(&mut self) -> Option<()> {
+        self.perf_rx.changed().await.ok()
+    }
+
+    async fn poll(&mut self) -> Vec<Reading> {
+        vec![reading(*self.perf_rx.borrow_and_update())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reading_reports_switch_and_pause_reason() {
+        let off = reading(PerfState::default());
+        assert_eq!(
+            off,
+            Reading::new("perf_mode", "OFF")
+                .retained()
+                .with_attributes(serde_json::json!({"paused": false, "reason": null}))
+        );
+        // A game pauses the sensors with the switch still off.
+        let game = reading(PerfState {
+            manual: false,
+            reason: Some("game"),
+        });
+        assert_eq!(
+            game,
+            Reading::new("perf_mode", "OFF")
+                .retained()
+                .with_attributes(serde_json::json!({"paused": true, "reason": "game"}))
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Power plan + AC source sensor
+//!
+//! Publishes the active power plan as the state of the `power_plan` select
+//! (options = installed plans) and `ac_connected` ("on"/"off") for the binary
+//! sensor, polled every `intervals.power_plan`. Switching is the `PowerPlan`
+//! command, which the select sends with the chosen plan name.
+
+use log::{debug, info};
+use std::sync::Arc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::AppState;
+use crate::power::plan;
+
+pub struct PowerPlanSensor {
+    state: Arc<AppState>,
+}
+
+impl PowerPlanSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.power_plan {
+            return;
+        }
+        let poll_secs = config.intervals.power_plan.max(1);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut prev_plan = String::new();
+        let mut prev_ac = String::new();
+
+        info!("Power plan sensor started (polled every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Power plan sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => {
+                    prev_plan.clear();
+                    prev_ac.clear();
+                }
+                _ = tick.tick() => {
+                    let plans = plan::list().await;
+                    if let Some(plans) = &plans
+                        && plan::cache_names(plans)
+                    {
+                        // New or renamed plan: refresh the select's options.
+                        self.state.mqtt.register_power_plan_select(&plan::cached_names()).await;
+                    }
+                    let active = plans
+                        .as_deref()
+                        .and_then(|plans| plans.iter().find(|p| p.active))
+                        .map_or_else(|| "unavailable".to_string(), |p| p.name.clone());
+                    if active != prev_plan {
+                        self.state.mqtt.publish_sensor("power_plan", &active).await;
+                        prev_plan = active;
+                    }
+
+                    let ac = match plan::ac_connected() {
+                        Some(true) => "on",
+                        Some(false) => "off",
+                        None => "unavailable",
+                    };
+                    if ac != prev_ac {
+                        self.state.mqtt.publish_sensor("ac_connected", ac).await;
+                        prev_ac = ac.to_string();
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,587 @@
+//! Printer status sensors and the `Print` command
+//!
+//! One `printer_<slug>` sensor per name in `printers`, polled every
+//! `intervals.printers`. State is the most pressing of offline / paper_jam /
+//! out_of_paper / error / paused / printing / idle ("unavailable" when the
+//! printer doesn't exist); attributes carry the queued job count and every
+//! problem flag (toner_low, door_open, ...).
+//! - Windows: the spooler's PRINTER_INFO_2 status bits and job count
+//! - Linux: CUPS via `lpstat -l -p` (state and alerts) and `lpstat -o`
+//!
+//! `Print` prints its payload as plain text, or a test page for an empty
+//! payload / the button's PRESS, on the first configured printer (else the
+//! system default). A JSON payload `{"printer": ..., "text": ...}` picks the
+//! printer.
+
+use log::{debug, info, warn};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use super::device_battery::slug;
+use crate::AppState;
+use crate::feature_state::FeatureState;
+use crate::mqtt::printer_id;
+
+/// What a printer is doing, as published.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct PrinterStatus {
+    state: &'static str,
+    jobs: u32,
+    problems: Vec<&'static str>,
+}
+
+impl PrinterStatus {
+    /// Pick the state from the problem flags, most pressing first.
+    fn from_problems(problems: Vec<&'static str>, printing: bool, jobs: u32) -> Self {
+        let state = ["offline", "paper_jam", "out_of_paper", "error", "paused"]
+            .into_iter()
+            .find(|s| problems.contains(s))
+            .unwrap_or(if printing { "printing" } else { "idle" });
+        Self {
+            state,
+            jobs,
+            problems,
+        }
+    }
+}
+
+pub struct PrinterSensor {
+    state: Arc<AppState>,
+}
+
+impl PrinterSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.printers || config.printers.is_empty() {
+            return;
+        }
+        let poll_secs = config.intervals.printers.max(5);
+        let printers: Vec<(String, String)> = config
+            .printers
+            .iter()
+            .map(|name| (name.clone(), slug(name)))
+            .collect();
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut registered = false;
+        let mut prev: HashMap<String, Option<PrinterStatus>> = HashMap::new();
+
+        info!(
+            "Printer sensor started ({} printers, polled every {poll_secs}s)",
+            printers.len()
+        );
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Printer sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => {
+                    registered = false;
+                    prev.clear();
+                }
+                _ = tick.tick() => {
+                    if !registered {
+                        self.register(&printers).await;
+                        registered = true;
+                    }
+                    for (name, slug) in &printers {
+                        let status = read_status(name).await;
+                        if prev.get(slug) == Some(&status) {
+                            continue;
+                        }
+                        let id = printer_id(slug);
+                        match &status {
+                            Some(status) => {
+                                debug!("Printer {name}: {} ({} jobs)", status.state, status.jobs);
+                                self.state.mqtt.publish_sensor(&id, status.state).await;
+                                self.state
+                                    .mqtt
+                                    .publish_sensor_attributes(
+                                        &id,
+                                        &serde_json::json!({
+                                            "printer": name,
+                                            "jobs": status.jobs,
+                                            "problems": status.problems,
+                                        }),
+                                    )
+                                    .await;
+                            }
+                            None => self.state.mqtt.publish_sensor(&id, "unavailable").await,
+                        }
+                        prev.insert(slug.clone(), status);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Register each printer's sensor and record it in feature_state.json,
+    /// so it's removed when `printers` is turned off.
+    async fn register(&self, printers: &[(String, String)]) {
+        let config = self.state.config.read().await;
+        for (name, slug) in printers {
+            self.state.mqtt.register_printer(slug, name, &config).await;
+        }
+        drop(config);
+        let mut state = FeatureState::load();
+        let mut changed = false;
+        for (_, slug) in printers {
+            changed |= state.remember_printer(slug);
+        }
+        if changed && let Err(e) = state.save() {
+            warn!("Printer: failed to record printers in feature_state.json: {e}");
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PrintRequest {
+    printer: Option<String>,
+    text: Option<String>,
+}
+
+/// The `Print` command.
+pub(crate) async fn print_payload(payload: &str, state: &AppState) {
+    let request = serde_json::from_str::<PrintRequest>(payload).unwrap_or(PrintRequest {
+        printer: None,
+        text: Some(payload.to_string()),
+    });
+    let printer = match request.printer.filter(|p| !p.trim().is_empty()) {
+        Some(printer) => Some(printer),
+        None => state.config.read().await.printers.first().cloned(),
+    };
+    let text = request
+        .text
+        .filter(|t| !t.trim().is_empty() && !t.eq_ignore_ascii_case("press"));
+    let target = printer.as_deref().unwrap_or("the default printer");
+    let result = match &text {
+        Some(text) => print_text(printer.as_deref(), text).await,
+        None => print_test_page(printer.as_deref()).await,
+    };
+    match result {
+        Ok(()) if text.is_some() => info!("Print: sent text to {target}"),
+        Ok(()) => info!("Print: test page sent to {target}"),
+        Err(e) => warn!("Print to {target} failed: {e}"),
+    }
+}
+
+#[cfg(windows)]
+async fn read_status(name: &str) -> Option<PrinterStatus> {
+    let name = name.to_string();
+    let (status, attributes, jobs) = tokio::task::spawn_blocking(move || spooler::info(&name))
+        .await
+        .ok()
+        .flatten()?;
+    Some(status_from_spooler(status, attributes, jobs))
+}
+
+#[cfg(windows)]
+async fn print_text(printer: Option<&str>, text: &str) -> Result<(), String> {
+    let printer = printer.map(str::to_string);
+    let text = text.to_string();
+    tokio::task::spawn_blocking(move || spooler::print_text(printer, &text))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+#[cfg(windows)]
+async fn print_test_page(printer: Option<&str>) -> Result<(), String> {
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    let printer = match printer {
+        Some(p) => p.to_string(),
+        None => tokio::task::spawn_blocking(spooler::default_printer)
+            .await
+            .ok()
+            .flatten()
+            .ok_or("no default printer")?,
+    };
+    let status = tokio::process::Command::new("rundll32.exe")
+        .args(["printui.dll,PrintUIEntry", "/k", "/n", &printer])
+        .creation_flags(CREATE_NO_WINDOW)
+        .status()
+        .await
+        .map_err(|e| e.to_string())?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("PrintUIEntry exited with {status}"))
+    }
+}
+
+#[cfg(unix)]
+async fn read_status(name: &str) -> Option<PrinterStatus> {
+    let detail = lpstat(&["-l", "-p", name]).await?;
+    let jobs = lpstat(&["-o", name]).await.unwrap_or_default();
+    parse_lpstat(
+        &detail,
+        jobs.lines().filter(|l| !l.trim().is_empty()).count() as u32,
+    )
+}
+
+#[cfg(unix)]
+async fn lpstat(args: &[&str]) -> Option<String> {
+    let out = tokio::process::Command::new("lpstat")
+        .args(args)
+        .env("LC_ALL", "C")
+        .kill_on_drop(true)
+        .output();
+    let out = tokio::time::timeout(Duration::from_secs(10), out)
+        .await
+        .ok()?
+        .ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+#[cfg(unix)]
+async fn print_text(printer: Option<&str>, text: &str) -> Result<(), String> {
+    lp(printer, &["-t", "pc-bridge"], Some(text)).await
+}
+
+#[cfg(unix)]
+async fn print_test_page(printer: Option<&str>) -> Result<(), String> {
+    lp(
+        printer,
+        &[
+            "-t",
+            "pc-bridge test page",
+            "/usr/share/cups/data/testprint",
+        ],
+        None,
+    )
+    .await
+}
+
+/// Submit a job with `lp`, `stdin` as the document when given.
+#[cfg(unix)]
+async fn lp(printer: Option<&str>, args: &[&str], stdin: Option<&str>) -> Result<(), String> {
+    use std::process::Stdio;
+    use tokio::io::AsyncWriteExt;
+
+    let mut cmd = tokio::process::Command::new("lp");
+    if let Some(printer) = printer {
+        cmd.args(["-d", printer]);
+    }
+    cmd.args(args)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = cmd.spawn().map_err(|e| format!("lp: {e}"))?;
+    if let (Some(text), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(text.as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    let out = tokio::time::timeout(Duration::from_secs(30), child.wait_with_output())
+        .await
+        .map_err(|_| "lp timed out".to_string())?
+        .map_err(|e| e.to_string())?;
+    if out.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&out.stderr).trim().to_string())
+    }
+}
+
+/// State and flags from PRINTER_INFO_2's `Status` bits and `Attributes`.
+#[cfg_attr(unix, allow(dead_code))]
+fn status_from_spooler(status: u32, attributes: u32, jobs: u32) -> PrinterStatus {
+    const FLAGS: &[(u32, &str)] = &[
+        (0x0000_0001, "paused"),
+        (0x0000_0002, "error"),
+        (0x0000_0008, "paper_jam"),
+        (0x0000_0010, "out_of_paper"),
+        (0x0000_0040, "paper_problem"),
+        (0x0000_0080, "offline"),
+        (0x0000_1000, "offline"), // not available
+        (0x0002_0000, "toner_low"),
+        (0x0004_0000, "no_toner"),
+        (0x0010_0000, "user_intervention"),
+        (0x0040_0000, "door_open"),
+    ];
+    const PRINTER_STATUS_PRINTING: u32 = 0x0000_0400;
+    const PRINTER_ATTRIBUTE_WORK_OFFLINE: u32 = 0x0000_0400;
+
+    let mut problems: Vec<&'static str> = Vec::new();
+    for &(bit, flag) in FLAGS {
+        if status & bit != 0 && !problems.contains(&flag) {
+            problems.push(flag);
+        }
+    }
+    if attributes & PRINTER_ATTRIBUTE_WORK_OFFLINE != 0 && !problems.contains(&"offline") {
+        problems.push("offline");
+    }
+    PrinterStatus::from_problems(problems, status & PRINTER_STATUS_PRINTING != 0, jobs)
+}
+
+/// `lpstat -l -p <name>` output: the state line ("is idle", "now printing",
+/// "disabled") and the `Alerts:` line of IPP printer-state-reasons.
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_lpstat(output: &str, jobs: u32) -> Option<PrinterStatus> {
+    let header = output.lines().find(|l| l.starts_with("printer "))?;
+    let mut problems: Vec<&'static str> = Vec::new();
+    if header.contains(" disabled") {
+        problems.push("paused");
+    }
+    let alerts = output
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("Alerts:"))
+        .unwrap_or_default();
+    for reason in alerts.split_whitespace() {
+        // Reasons carry -report / -warning / -error suffixes.
+        let flag = match reason {
+            r if r.starts_with("offline") || r.starts_with("connecting-to-device") => "offline",
+            r if r.starts_with("media-jam") => "paper_jam",
+            r if r.starts_with("media-empty") || r.starts_with("media-needed") => "out_of_paper",
+            r if r.starts_with("toner-low") || r.starts_with("marker-supply-low") => "toner_low",
+            r if r.starts_with("toner-empty") || r.starts_with("marker-supply-empty") => "no_toner",
+            r if r.starts_with("door-open") || r.starts_with("cover-open") => "door_open",
+            r if r.ends_with("-error") => "error",
+            _ => continue,
+        };
+        if !problems.contains(&flag) {
+            problems.push(flag);
+        }
+    }
+    Some(PrinterStatus::from_problems(
+        problems,
+        header.contains("now printing"),
+        jobs,
+    ))
+}
+
+/// winspool calls, declared locally like the SetupAPI ones in
+/// device_battery: the windows crate's printing bindings aren't enabled.
+#[cfg(windows)]
+mod spooler {
+    use core::ffi::c_void;
+
+    /// PRINTER_INFO_2W
+    #[repr(C)]
+    struct PrinterInfo2 {
+        server_name: *mut u16,
+        printer_name: *mut u16,
+        share_name: *mut u16,
+        port_name: *mut u16,
+        driver_name: *mut u16,
+        comment: *mut u16,
+        location: *mut u16,
+        dev_mode: *mut c_void,
+        sep_file: *mut u16,
+        print_processor: *mut u16,
+        datatype: *mut u16,
+        parameters: *mut u16,
+        security_descriptor: *mut c_void,
+        attributes: u32,
+        priority: u32,
+        default_priority: u32,
+        start_time: u32,
+        until_time: u32,
+        status: u32,
+        jobs: u32,
+        average_ppm: u32,
+    }
+
+    /// DOC_INFO_1W
+    #[repr(C)]
+    struct DocInfo1 {
+        doc_name: *const u16,
+        output_file: *const u16,
+        datatype: *const u16,
+    }
+
+    #[link(name = "winspool")]
+    unsafe extern "system" {
+        fn OpenPrinterW(name: *const u16, handle: *mut *mut c_void, defaults: *const c_void)
+        -> i32;
+        fn GetPrinterW(
+            handle: *mut c_void,
+            level: u32,
+            buf: *mut u8,
+            size: u32,
+            needed: *mut u32,
+        ) -> i32;
+        fn ClosePrinter(handle: *mut c_void) -> i32;
+        fn StartDocPrinterW(handle: *mut c_void, level: u32, doc: *const DocInfo1) -> u32;
+        fn StartPagePrinter(handle: *mut c_void) -> i32;
+        fn WritePrinter(
+            handle: *mut c_void,
+            buf: *const c_void,
+            len: u32,
+            written: *mut u32,
+        ) -> i32;
+        fn EndPagePrinter(handle: *mut c_void) -> i32;
+        fn EndDocPrinter(handle: *mut c_void) -> i32;
+        fn GetDefaultPrinterW(buf: *mut u16, size: *mut u32) -> i32;
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        s.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// An open printer handle, closed on drop.
+    struct Printer(*mut c_void);
+
+    impl Printer {
+        fn open(name: &str) -> Option<Self> {
+            let name = wide(name);
+            let mut handle = std::ptr::null_mut();
+            (unsafe { OpenPrinterW(name.as_ptr(), &raw mut handle, std::ptr::null()) } != 0)
+                .then_some(Self(handle))
+        }
+    }
+
+    impl Drop for Printer {
+        fn drop(&mut self) {
+            unsafe { ClosePrinter(self.0) };
+        }
+    }
+
+    /// `Status`, `Attributes` and `cJobs` of a printer.
+    pub(super) fn info(name: &str) -> Option<(u32, u32, u32)> {
+        let printer = Printer::open(name)?;
+        let mut needed = 0u32;
+        unsafe { GetPrinterW(printer.0, 2, std::ptr::null_mut(), 0, &raw mut needed) };
+        if needed == 0 {
+            return None;
+        }
+        // u64s keep the pointer fields aligned.
+        let mut buf = vec![0u64; (needed as usize).div_ceil(8)];
+        let ok = unsafe {
+            GetPrinterW(
+                printer.0,
+                2,
+                buf.as_mut_ptr().cast(),
+                (buf.len() * 8) as u32,
+                &raw mut needed,
+            )
+        };
+        if ok == 0 {
+            return None;
+        }
+        let info = unsafe { &*buf.as_ptr().cast::<PrinterInfo2>() };
+        Some((info.status, info.attributes, info.jobs))
+    }
+
+    pub(super) fn default_printer() -> Option<String> {
+        let mut len = 0u32;
+        unsafe { GetDefaultPrinterW(std::ptr::null_mut(), &raw mut len) };
+        if len == 0 {
+            return None;
+        }
+        let mut buf = vec![0u16; len as usize];
+        if unsafe { GetDefaultPrinterW(buf.as_mut_ptr(), &raw mut len) } == 0 {
+            return None;
+        }
+        let end = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+        Some(String::from_utf16_lossy(&buf[..end]))
+    }
+
+    /// One job of the TEXT datatype: the print processor lays the text out
+    /// in the printer's default font, so it works on GDI-only printers too.
+    pub(super) fn print_text(printer: Option<String>, text: &str) -> Result<(), String> {
+        let name = printer
+            .or_else(default_printer)
+            .ok_or("no default printer")?;
+        let printer = Printer::open(&name).ok_or_else(|| format!("can't open printer '{name}'"))?;
+        let doc_name = wide("pc-bridge");
+        let datatype = wide("TEXT");
+        let doc = DocInfo1 {
+            doc_name: doc_name.as_ptr(),
+            output_file: std::ptr::null(),
+            datatype: datatype.as_ptr(),
+        };
+        // CRLF line ends; the TEXT processor wants ANSI.
+        let body: Vec<u8> = text
+            .replace("\r\n", "\n")
+            .replace('\n', "\r\n")
+            .chars()
+            .map(|c| if c.is_ascii() { c as u8 } else { b'?' })
+            .collect();
+        unsafe {
+            if StartDocPrinterW(printer.0, 1, &raw const doc) == 0 {
+                return Err("StartDocPrinter failed".to_string());
+            }
+            StartPagePrinter(printer.0);
+            let mut written = 0u32;
+            let ok = WritePrinter(
+                printer.0,
+                body.as_ptr().cast(),
+                body.len() as u32,
+                &raw mut written,
+            );
+            EndPagePrinter(printer.0);
+            EndDocPrinter(printer.0);
+            if ok == 0 {
+                return Err("WritePrinter failed".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_from_spooler() {
+        let idle = status_from_spooler(0, 0, 0);
+        assert_eq!(idle.state, "idle");
+        assert!(idle.problems.is_empty());
+
+        let printing = status_from_spooler(0x400, 0, 2);
+        assert_eq!((printing.state, printing.jobs), ("printing", 2));
+
+        // Out of paper while printing: the problem wins.
+        let paper = status_from_spooler(0x400 | 0x10 | 0x2_0000, 0, 1);
+        assert_eq!(paper.state, "out_of_paper");
+        assert_eq!(paper.problems, ["out_of_paper", "toner_low"]);
+
+        // "Use printer offline" attribute, and not-available status.
+        assert_eq!(status_from_spooler(0, 0x400, 0).state, "offline");
+        assert_eq!(status_from_spooler(0x1000, 0, 0).problems, ["offline"]);
+    }
+
+    #[test]
+    fn test_parse_lpstat() {
+        let idle = "printer HP_LaserJet is idle.  enabled since Mon 12 Oct 2026 10:00:00\n\
+                    \tForm mounted:\n\tContent types: any\n\tAlerts: none\n";
+        let status = parse_lpstat(idle, 0).unwrap();
+        assert_eq!(status.state, "idle");
+        assert!(status.problems.is_empty());
+
+        let printing = "printer HP_LaserJet now printing HP_LaserJet-42.  enabled since Mon\n\
+                        \tAlerts: media-empty-error media-needed toner-low-report\n";
+        let status = parse_lpstat(printing, 3).unwrap();
+        assert_eq!((status.state, status.jobs), ("out_of_paper", 3));
+        assert_eq!(status.problems, ["out_of_paper", "toner_low"]);
+
+        let disabled = "printer Brother disabled since Mon 12 Oct 2026 -\n\
+                        \tThe printer is not connected.\n\tAlerts: offline-report\n";
+        let status = parse_lpstat(disabled, 0).unwrap();
+        assert_eq!(status.state, "offline");
+        assert_eq!(status.problems, ["paused", "offline"]);
+
+        assert!(parse_lpstat("lpstat: Invalid destination name\n", 0).is_none());
+    }
+}
@@ -0,0 +1,638 @@
+//! Process watchdog - flags configured processes
+//!
+//! Each `process_alerts` rule names a process (exact match, case- and
+//! `.exe`-insensitive). A non-retained JSON event is published on
+//! `pc-bridge/alerts/{device}` when:
+//! - `started`: the first instance of the process appears
+//! - `stopped`: the last instance exits
+//! - `cpu_exceeded` / `memory_exceeded`: all instances together cross the
+//!   rule's threshold. Edge-triggered - fires once on the crossing and re-arms
+//!   when usage drops back under, so a pegged miner doesn't alert every tick.
+//!
+//! On Windows start/stop rides the ProcessWatcher's push notifications; CPU/RAM
+//! sampling (and start/stop on Linux, which has no watcher) runs on
+//! `intervals.process_alerts`.
+
+use log::{debug, info};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tokio::sync::broadcast;
+use tokio::time::{MissedTickBehavior, interval};
+
+use crate::AppState;
+use crate::config::ProcessAlertRule;
+
+/// One running instance of a watched process
+#[derive(Debug, Clone)]
+struct ProcSample {
+    pid: u32,
+    name: String,
+    /// Cumulative user + kernel CPU time since the process started
+    cpu_time: Duration,
+    /// Resident set (Linux) / working set (Windows)
+    memory_bytes: u64,
+}
+
+/// Per-rule edge state, so each condition alerts once per crossing
+#[derive(Default, Clone)]
+struct RuleState {
+    running: bool,
+    cpu_over: bool,
+    memory_over: bool,
+}
+
+/// Per-rule alert state, fed one process snapshot per tick.
+struct Watchdog {
+    rules: Vec<ProcessAlertRule>,
+    /// Lowered, `.exe`-stripped rule names (parallel to `rules`)
+    keys: Vec<String>,
+    states: Vec<RuleState>,
+    /// pid -> CPU time at the previous resource sample, for per-tick deltas
+    prev_cpu: HashMap<u32, Duration>,
+    last_sample: Option<Instant>,
+}
+
+/// Strip a trailing `.exe` (any case) and lowercase, so `XMRig.EXE` and `xmrig`
+/// compare equal.
+pub(crate) fn process_key(name: &str) -> String {
+    let base = if name.len() >= 4 && name.as_bytes()[name.len() - 4..].eq_ignore_ascii_case(b".exe")
+    {
+        &name[..name.len() - 4]
+    } else {
+        name
+    };
+    base.to_lowercase()
+}
+
+impl Watchdog {
+    fn new(rules: Vec<ProcessAlertRule>) -> Self {
+        let keys = rules
+            .iter()
+            .map(|r| process_key(r.process.trim()))
+            .collect();
+        let states = vec![RuleState::default(); rules.len()];
+        Self {
+            rules,
+            keys,
+            states,
+            prev_cpu: HashMap::new(),
+            last_sample: None,
+        }
+    }
+
+    /// Whether any rule watches this process name (filters what gets sampled).
+    #[cfg(any(windows, test))]
+    fn watches(&self, name: &str) -> bool {
+        let key = process_key(name);
+        self.keys.contains(&key)
+    }
+
+    /// Record which rules are currently running without emitting anything. Used
+    /// after a rules hot-reload so already-running processes don't re-alert.
+    fn prime(&mut self, samples: &[ProcSample]) {
+        for (i, key) in self.keys.iter().enumerate() {
+            self.states[i].running = samples.iter().any(|s| process_key(&s.name) == *key);
+        }
+    }
+
+    /// Evaluate the rules against a fresh sample. `now` is `Some` on a resource
+    /// tick (CPU/RAM thresholds are checked and the CPU baseline advances) and
+    /// `None` for a presence-only pass triggered by a process start/exit.
+    fn evaluate(
+        &mut self,
+        samples: &[ProcSample],
+        now: Option<Instant>,
+        cpus: usize,
+    ) -> Vec<serde_json::Value> {
+        let mut events = Vec::new();
+        // The first resource tick only seeds the CPU baseline - there is no
+        // previous sample to diff against yet.
+        let elapsed = match (now, self.last_sample) {
+            (Some(now), Some(last)) => Some(now.saturating_duration_since(last)),
+            _ => None,
+        };
+
+        for (i, rule) in self.rules.iter().enumerate() {
+            let key = &self.keys[i];
+            let instances: Vec<&ProcSample> = samples
+                .iter()
+                .filter(|s| process_key(&s.name) == *key)
+                .collect();
+            let state = &mut self.states[i];
+            let running = !instances.is_empty();
+
+            if running != state.running {
+                state.running = running;
+                if running && rule.on_start {
+                    events.push(alert_event("started", rule, instances.len()));
+                } else if !running && rule.on_stop {
+                    events.push(alert_event("stopped", rule, 0));
+                }
+                if !running {
+                    // Re-arm thresholds so the next run alerts on its own crossing.
+                    state.cpu_over = false;
+                    state.memory_over = false;
+                }
+            }
+
+            if now.is_none() || !running {
+                continue;
+            }
+
+            if let Some(limit) = rule.memory_mb {
+                let used_mb = instances.iter().map(|s| s.memory_bytes).sum::<u64>() / (1024 * 1024);
+                let over = used_mb > limit;
+                if over && !state.memory_over {
+                    let mut ev = alert_event("memory_exceeded", rule, instances.len());
+                    ev["memory_mb"] = used_mb.into();
+                    ev["threshold"] = limit.into();
+                    events.push(ev);
+                }
+                state.memory_over = over;
+            }
+
+            if let (Some(limit), Some(elapsed)) = (rule.cpu_percent, elapsed)
+                && !elapsed.is_zero()
+            {
+                // A pid new since the last tick started after it, so its whole
+                // CPU time falls inside this window.
+                let used: Duration = instances
+                    .iter()
+                    .map(|s| {
+                        let prev = self.prev_cpu.get(&s.pid).copied().unwrap_or_default();
+                        s.cpu_time.saturating_sub(prev)
+                    })
+                    .sum();
+                let pct = cpu_percent(used, elapsed, cpus);
+                let over = pct > limit;
+                if over && !state.cpu_over {
+                    let mut ev = alert_event("cpu_exceeded", rule, instances.len());
+                    ev["cpu_percent"] = ((pct * 10.0).round() / 10.0).into();
+                    ev["threshold"] = limit.into();
+                    events.push(ev);
+                }
+                state.cpu_over = over;
+            }
+        }
+
+        if let Some(now) = now {
+            self.prev_cpu = samples.iter().map(|s| (s.pid, s.cpu_time)).collect();
+            self.last_sample = Some(now);
+        }
+        events
+    }
+}
+
+/// CPU share of the whole machine (0-100) for `used` CPU time over `elapsed`
+/// wall time, normalized across `cpus` logical processors like Task Manager.
+fn cpu_percent(used: Duration, elapsed: Duration, cpus: usize) -> f64 {
+    let cpus = cpus.max(1) as f64;
+    (used.as_secs_f64() / elapsed.as_secs_f64() / cpus * 100.0).min(100.0)
+}
+
+fn alert_event(kind: &str, rule: &ProcessAlertRule, instances: usize) -> serde_json::Value {
+    let timestamp = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .unwrap_or_default();
+    serde_json::json!({
+        "event": kind,
+        "process": rule.process,
+        "instances": instances,
+        "timestamp": timestamp,
+    })
+}
+
+pub struct ProcessAlertSensor {
+    state: Arc<AppState>,
+}
+
+impl ProcessAlertSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        let mut watchdog = Watchdog::new(config.process_alerts.clone());
+        let mut interval_secs = config.intervals.process_alerts.max(1);
+        drop(config);
+
+        let cpus = std::thread::available_parallelism().map_or(1, std::num::NonZero::get);
+        let mut tick = interval(Duration::from_secs(interval_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut config_rx = self.state.config_generation.subscribe();
+        #[cfg(windows)]
+        let mut process_rx = self.state.process_watcher.subscribe();
+        // Linux has no push-based process watcher: start/stop rides the sampling
+        // tick. A channel that never fires keeps the select! below uniform.
+        #[cfg(unix)]
+        let (_no_watcher, mut process_rx) = broadcast::channel::<()>(1);
+
+        info!(
+            "Process watchdog started ({} rules, sampled every {}s)",
+            watchdog.rules.len(),
+            interval_secs
+        );
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Process watchdog shutting down");
+                    break;
+                }
+                r = config_rx.recv() => {
+                    if !matches!(r, Ok(()) | Err(broadcast::error::RecvError::Lagged(_))) {
+                        continue;
+                    }
+                    let config = self.state.config.read().await;
+                    let rules = config.process_alerts.clone();
+                    let new_secs = config.intervals.process_alerts.max(1);
+                    drop(config);
+                    if !same_rules(&rules, &watchdog.rules) {
+                        // Prime the new rule set so processes already running
+                        // don't all re-alert "started" on every config edit.
+                        watchdog = Watchdog::new(rules);
+                        let samples = self.sample(&watchdog).await;
+                        watchdog.prime(&samples);
+                        debug!("Process watchdog: reloaded {} rules", watchdog.rules.len());
+                    }
+                    if new_secs != interval_secs {
+                        interval_secs = new_secs;
+                        tick = interval(Duration::from_secs(interval_secs));
+                        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                    }
+                }
+                r = process_rx.recv() => {
+                    if matches!(r, Err(broadcast::error::RecvError::Closed)) {
+                        continue;
+                    }
+                    let samples = self.sample(&watchdog).await;
+                    let events = watchdog.evaluate(&samples, None, cpus);
+                    self.publish(&events).await;
+                }
+                _ = tick.tick() => {
+                    let samples = self.sample(&watchdog).await;
+                    let events = watchdog.evaluate(&samples, Some(Instant::now()), cpus);
+                    self.publish(&events).await;
+                }
+            }
+        }
+    }
+
+    async fn publish(&self, events: &[serde_json::Value]) {
+        for event in events {
+            info!("Process alert: {}", event);
+            self.state.mqtt.publish_process_alert(event).await;
+        }
+    }
+
+    /// Sample every running instance of a watched process. The name filter runs
+    /// against the watcher's live set; only matches are opened for CPU/RAM.
+    #[cfg(windows)]
+    async fn sample(&self, watchdog: &Watchdog) -> Vec<ProcSample> {
+        let targets: Vec<(u32, String)> = {
+            let proc_state = self.state.process_watcher.state();
+            let guard = proc_state.read().await;
+            guard
+                .processes()
+                .filter(|(_, name)| watchdog.watches(name))
+                .map(|(pid, name)| (pid, name.to_string()))
+                .collect()
+        };
+        if targets.is_empty() {
+            return Vec::new();
+        }
+        tokio::task::spawn_blocking(move || {
+            targets
+                .into_iter()
+                .map(|(pid, name)| sample_process(pid, name))
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Sample every running instance of a watched process from `/proc`.
+    #[cfg(unix)]
+    async fn sample(&self, watchdog: &Watchdog) -> Vec<ProcSample> {
+        let keys = watchdog.keys.clone();
+        // Hundreds of blocking /proc reads - keep them off the runtime thread.
+        tokio::task::spawn_blocking(move || sample_proc(&keys))
+            .await
+            .unwrap_or_default()
+    }
+}
+
+/// Rule sets compare by their serialized form (ProcessAlertRule carries an f64,
+/// so no derived Eq).
+fn same_rules(a: &[ProcessAlertRule], b: &[ProcessAlertRule]) -> bool {
+    serde_json::to_string(a).ok() == serde_json::to_string(b).ok()
+}
+
+/// Read CPU time and working set for one pid. A process we can't open (elevated,
+/// or exited since the snapshot) still counts as running, with zero usage.
+#[cfg(windows)]
+fn sample_process(pid: u32, name: String) -> ProcSample {
+    use windows::Win32::Foundation::{CloseHandle, FILETIME};
+    use windows::Win32::System::ProcessStatus::{K32GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS};
+    use windows::Win32::System::Threading::{
+        GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    fn filetime_100ns(ft: FILETIME) -> u64 {
+        (u64::from(ft.dwHighDateTime) << 32) | u64::from(ft.dwLowDateTime)
+    }
+
+    let mut sample = ProcSample {
+        pid,
+        name,
+        cpu_time: Duration::ZERO,
+        memory_bytes: 0,
+    };
+    // SAFETY: the handle is only used by the two query calls below and closed
+    // before returning; out-params are valid stack locals of the right size.
+    unsafe {
+        let Ok(handle) = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid) else {
+            return sample;
+        };
+        let (mut created, mut exited, mut kernel, mut user) = (
+            FILETIME::default(),
+            FILETIME::default(),
+            FILETIME::default(),
+            FILETIME::default(),
+        );
+        if GetProcessTimes(
+            handle,
+            &raw mut created,
+            &raw mut exited,
+            &raw mut kernel,
+            &raw mut user,
+        )
+        .is_ok()
+        {
+            let ticks = filetime_100ns(kernel) + filetime_100ns(user);
+            sample.cpu_time = Duration::from_nanos(ticks.saturating_mul(100));
+        }
+        let mut counters = PROCESS_MEMORY_COUNTERS {
+            cb: std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32,
+            ..Default::default()
+        };
+        if K32GetProcessMemoryInfo(handle, &raw mut counters, counters.cb).as_bool() {
+            sample.memory_bytes = counters.WorkingSetSize as u64;
+        }
+        let _ = CloseHandle(handle);
+    }
+    sample
+}
+
+/// Enumerate `/proc` for processes whose name matches one of `keys`.
+#[cfg(unix)]
+fn sample_proc(keys: &[String]) -> Vec<ProcSample> {
+    // SAFETY: sysconf only reads process-wide constants.
+    let (clk_tck, page_size) = unsafe {
+        (
+            libc::sysconf(libc::_SC_CLK_TCK),
+            libc::sysconf(libc::_SC_PAGESIZE),
+        )
+    };
+    let clk_tck = u64::try_from(clk_tck).unwrap_or(100).max(1);
+    let page_size = u64::try_from(page_size).unwrap_or(4096);
+
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    let mut samples = Vec::new();
+    for entry in entries.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        let path = entry.path();
+        let Ok(stat) = std::fs::read_to_string(path.join("stat")) else {
+            continue;
+        };
+        let Some((comm, ticks, rss_pages)) = parse_proc_stat(&stat) else {
+            continue;
+        };
+        // comm is truncated to 15 bytes; fall back to the cmdline basename so
+        // long executable names still match (same as the Linux game sensor).
+        let name = if comm.len() >= 15 {
+            std::fs::read_to_string(path.join("cmdline"))
+                .ok()
+                .and_then(|cl| {
+                    cl.split('\0')
+                        .next()
+                        .filter(|a| !a.is_empty())
+                        .map(|a0| a0.rsplit(['/', '\\']).next().unwrap_or(a0).to_string())
+                })
+                .filter(|b| !b.is_empty())
+                .unwrap_or_else(|| comm.to_string())
+        } else {
+            comm.to_string()
+        };
+        if !keys.contains(&process_key(&name)) {
+            continue;
+        }
+        samples.push(ProcSample {
+            pid,
+            name,
+            cpu_time: Duration::from_secs_f64(ticks as f64 / clk_tck as f64),
+            memory_bytes: rss_pages.saturating_mul(page_size),
+        });
+    }
+    samples
+}
+
+/// Parse `/proc/<pid>/stat` into `(comm, utime + stime ticks, rss pages)`.
+/// comm is parenthesized and may itself contain spaces or ')', so split on the
+/// LAST ')' before counting fields.
+#[cfg(unix)]
+fn parse_proc_stat(stat: &str) -> Option<(&str, u64, u64)> {
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let comm = stat.get(open + 1..close)?;
+    // Fields after comm start at field 3 (state); utime/stime are fields 14/15
+    // and rss is field 24.
+    let rest: Vec<&str> = stat.get(close + 1..)?.split_whitespace().collect();
+    let utime: u64 = rest.get(11)?.parse().ok()?;
+    let stime: u64 = rest.get(12)?.parse().ok()?;
+    let rss: u64 = rest.get(21)?.parse().ok()?;
+    Some((comm, utime + stime, rss))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(process: &str) -> ProcessAlertRule {
+        ProcessAlertRule {
+            process: process.to_string(),
+            on_start: true,
+            on_stop: true,
+            cpu_percent: None,
+            memory_mb: None,
+        }
+    }
+
+    fn proc(pid: u32, name: &str, cpu_secs: u64, memory_mb: u64) -> ProcSample {
+        ProcSample {
+            pid,
+            name: name.to_string(),
+            cpu_time: Duration::from_secs(cpu_secs),
+            memory_bytes: memory_mb * 1024 * 1024,
+        }
+    }
+
+    fn kinds(events: &[serde_json::Value]) -> Vec<&str> {
+        events
+            .iter()
+            .map(|e| e["event"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_process_key_strips_exe_case_insensitively() {
+        assert_eq!(process_key("XMRig.EXE"), "xmrig");
+        assert_eq!(process_key("xmrig"), "xmrig");
+        assert_eq!(process_key(".exe"), "");
+    }
+
+    #[test]
+    fn test_start_and_stop_alert_once() {
+        let mut wd = Watchdog::new(vec![rule("xmrig.exe")]);
+        assert!(wd.evaluate(&[], None, 4).is_empty());
+
+        let running = [proc(10, "XMRIG.exe", 0, 10)];
+        let ev = wd.evaluate(&running, None, 4);
+        assert_eq!(kinds(&ev), ["started"]);
+        assert_eq!(ev[0]["process"], "xmrig.exe");
+        assert_eq!(ev[0]["instances"], 1);
+
+        // Still running - no repeat.
+        assert!(wd.evaluate(&running, None, 4).is_empty());
+        assert_eq!(kinds(&wd.evaluate(&[], None, 4)), ["stopped"]);
+    }
+
+    #[test]
+    fn test_exact_match_only() {
+        // A short rule must not flag a longer, unrelated process name.
+        let mut wd = Watchdog::new(vec![rule("cs")]);
+        assert!(wd.watches("cs.exe"));
+        assert!(!wd.watches("csrss.exe"));
+        assert!(
+            wd.evaluate(&[proc(4, "csrss.exe", 0, 0)], None, 1)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_start_stop_can_be_disabled() {
+        let mut r = rule("updater");
+        r.on_start = false;
+        r.on_stop = false;
+        let mut wd = Watchdog::new(vec![r]);
+        assert!(wd.evaluate(&[proc(1, "updater", 0, 0)], None, 1).is_empty());
+        assert!(wd.evaluate(&[], None, 1).is_empty());
+    }
+
+    #[test]
+    fn test_memory_threshold_is_edge_triggered() {
+        let mut r = rule("updater");
+        r.on_start = false;
+        r.memory_mb = Some(500);
+        let mut wd = Watchdog::new(vec![r]);
+        let t0 = Instant::now();
+
+        // Instances are summed: 300 + 300 MB > 500 MB.
+        let heavy = [proc(1, "updater", 0, 300), proc(2, "updater", 0, 300)];
+        let ev = wd.evaluate(&heavy, Some(t0), 1);
+        assert_eq!(kinds(&ev), ["memory_exceeded"]);
+        assert_eq!(ev[0]["memory_mb"], 600);
+        assert_eq!(ev[0]["threshold"], 500);
+
+        // Still over - no repeat until it drops back under.
+        assert!(
+            wd.evaluate(&heavy, Some(t0 + Duration::from_secs(10)), 1)
+                .is_empty()
+        );
+        let light = [proc(1, "updater", 0, 100)];
+        assert!(
+            wd.evaluate(&light, Some(t0 + Duration::from_secs(20)), 1)
+                .is_empty()
+        );
+        assert_eq!(
+            kinds(&wd.evaluate(&heavy, Some(t0 + Duration::from_secs(30)), 1)),
+            ["memory_exceeded"]
+        );
+    }
+
+    #[test]
+    fn test_cpu_threshold_uses_delta_over_window() {
+        let mut r = rule("miner");
+        r.on_start = false;
+        r.cpu_percent = Some(50.0);
+        let mut wd = Watchdog::new(vec![r]);
+        let t0 = Instant::now();
+
+        // First tick only seeds the baseline, even with a large cumulative time.
+        assert!(
+            wd.evaluate(&[proc(7, "miner", 1000, 0)], Some(t0), 2)
+                .is_empty()
+        );
+
+        // 15s of CPU in a 10s window on 2 cores = 75% of the machine.
+        let ev = wd.evaluate(
+            &[proc(7, "miner", 1015, 0)],
+            Some(t0 + Duration::from_secs(10)),
+            2,
+        );
+        assert_eq!(kinds(&ev), ["cpu_exceeded"]);
+        assert_eq!(ev[0]["cpu_percent"], 75.0);
+
+        // Idle in the next window - re-arms without alerting.
+        assert!(
+            wd.evaluate(
+                &[proc(7, "miner", 1015, 0)],
+                Some(t0 + Duration::from_secs(20)),
+                2
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_prime_suppresses_started_for_running_processes() {
+        let mut wd = Watchdog::new(vec![rule("xmrig")]);
+        let running = [proc(1, "xmrig", 0, 0)];
+        wd.prime(&running);
+        assert!(wd.evaluate(&running, None, 1).is_empty());
+        assert_eq!(kinds(&wd.evaluate(&[], None, 1)), ["stopped"]);
+    }
+
+    #[test]
+    fn test_cpu_percent_is_clamped() {
+        let pct = cpu_percent(Duration::from_secs(30), Duration::from_secs(10), 1);
+        assert!((pct - 100.0).abs() < f64::EPSILON);
+        assert!(cpu_percent(Duration::ZERO, Duration::from_secs(10), 0).abs() < f64::EPSILON);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_proc_stat() {
+        let stat = "1234 (my (odd) proc) S 1 1234 1234 0 -1 4194560 500 0 0 0 \
+                    250 50 0 0 20 0 4 0 100 123456789 2048 18446744073709551615";
+        let (comm, ticks, rss) = parse_proc_stat(stat).unwrap();
+        assert_eq!(comm, "my (odd) proc");
+        assert_eq!(ticks, 300);
+        assert_eq!(rss, 2048);
+        assert!(parse_proc_stat("garbage").is_none());
+    }
+}
@@ -0,0 +1,165 @@
+//! Process presence sensors
+//!
+//! Each `process_sensors` entry is an `<slug>_running` binary sensor (OBS ->
+//! `obs_running`), on while any process matching its `processes` patterns
+//! runs, with the matching names in a `processes` attribute. Discovery
+//! registers the entities from the config (`MqttClient::sync_process_sensors`);
+//! this task only publishes their state.
+//!
+//! On Windows the ProcessWatcher's push notifications drive it, with
+//! `intervals.process_sensors` as a backstop; Linux has no watcher and polls
+//! on that interval.
+
+use log::{debug, info};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use super::device_battery::slug;
+use crate::AppState;
+use crate::config::ProcessSensor;
+use crate::mqtt::process_sensor_id;
+
+pub struct ProcessPresenceSensor {
+    state: Arc<AppState>,
+}
+
+impl ProcessPresenceSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        let mut sensors = config.process_sensors.clone();
+        let mut interval_secs = config.intervals.process_sensors.max(1);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(interval_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut config_rx = self.state.config_generation.subscribe();
+        #[cfg(windows)]
+        let mut process_rx = self.state.process_watcher.subscribe();
+        // Linux has no push-based process watcher: the tick does it all. A
+        // channel that never fires keeps the select! below uniform.
+        #[cfg(unix)]
+        let (_no_watcher, mut process_rx) = broadcast::channel::<()>(1);
+        // Object id -> matching processes last published
+        let mut published: HashMap<String, Vec<String>> = HashMap::new();
+
+        info!(
+            "Process sensors started ({} sensors, rescanned every {interval_secs}s)",
+            sensors.len()
+        );
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Process sensors shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => {
+                    published.clear();
+                }
+                r = config_rx.recv() => {
+                    if !matches!(r, Ok(()) | Err(broadcast::error::RecvError::Lagged(_))) {
+                        continue;
+                    }
+                    let config = self.state.config.read().await;
+                    let new_sensors = config.process_sensors.clone();
+                    let new_secs = config.intervals.process_sensors.max(1);
+                    drop(config);
+                    if new_sensors != sensors {
+                        sensors = new_sensors;
+                        published.clear();
+                        debug!("Process sensors: reloaded {} sensors", sensors.len());
+                    }
+                    if new_secs != interval_secs {
+                        interval_secs = new_secs;
+                        tick = interval(Duration::from_secs(interval_secs));
+                        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                    }
+                }
+                r = process_rx.recv() => {
+                    if matches!(r, Err(broadcast::error::RecvError::Closed)) {
+                        continue;
+                    }
+                }
+                _ = tick.tick() => {}
+            }
+
+            let running = self.running().await;
+            for sensor in &sensors {
+                let id = process_sensor_id(&slug(&sensor.name));
+                let found = matching(sensor, running.iter().map(String::as_str));
+                if published.get(&id) == Some(&found) {
+                    continue;
+                }
+                let mqtt = &self.state.mqtt;
+                mqtt.publish_sensor(&id, if found.is_empty() { "off" } else { "on" })
+                    .await;
+                mqtt.publish_sensor_attributes(&id, &serde_json::json!({ "processes": found }))
+                    .await;
+                published.insert(id, found);
+            }
+        }
+    }
+
+    /// Names of the running processes, from the watcher's live set.
+    #[cfg(windows)]
+    async fn running(&self) -> Vec<String> {
+        let proc_state = self.state.process_watcher.state();
+        let guard = proc_state.read().await;
+        guard.names().iter().map(|n| n.to_string()).collect()
+    }
+
+    /// Names of the running processes, from `/proc`.
+    #[cfg(unix)]
+    async fn running(&self) -> Vec<String> {
+        tokio::task::spawn_blocking(super::current_process_names)
+            .await
+            .unwrap_or_default()
+    }
+}
+
+/// The running processes `sensor` matches, sorted and without duplicates;
+/// empty when it's off.
+fn matching<'a>(sensor: &ProcessSensor, running: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut found: Vec<String> = running
+        .filter(|name| sensor.matches(name))
+        .map(str::to_string)
+        .collect();
+    found.sort_unstable_by_key(|name| name.to_lowercase());
+    found.dedup();
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matching() {
+        let obs = ProcessSensor {
+            name: "OBS".to_string(),
+            processes: vec!["obs64.exe".to_string(), "obs-*".to_string()],
+            icon: None,
+        };
+        let running = [
+            "explorer.exe",
+            "obs64.exe",
+            "obs-browser-page.exe",
+            "obs-browser-page.exe",
+            "obs-ffmpeg-mux.exe",
+        ];
+        assert_eq!(
+            matching(&obs, running.into_iter()),
+            ["obs-browser-page.exe", "obs-ffmpeg-mux.exe", "obs64.exe"]
+        );
+        assert!(matching(&obs, ["explorer.exe", "obs.exe"].into_iter()).is_empty());
+    }
+}
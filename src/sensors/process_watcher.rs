@@ -7,12 +7,16 @@
 //! - Always up-to-date process list
 //! - Push notifications to subscribers when processes change
 //!
-//! Falls back to polling if WMI subscription fails.
+//! Falls back to polling if the WMI subscription fails or dies (WMI service
+//! restarted, quota exceeded - a stream can also stall without an error,
+//! which reconciliation notices), re-subscribing with backoff. `mode()` says
+//! which is active, for the `bridge_health` diagnostics.
 
 use log::{debug, error, info, warn};
 use serde::Deserialize;
 use std::collections::HashSet;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, AtomicU32, Ordering};
 use std::time::{Duration, Instant};
 use tokio::sync::{RwLock, broadcast, mpsc};
 use windows::Win32::Foundation::CloseHandle;
@@ -124,6 +128,12 @@ impl ProcessState {
     pub fn names(&self) -> &HashSet<Arc<str>> {
         &self.names
     }
+
+    /// Tracked `(pid, name)` pairs. The process watchdog uses these to sample
+    /// per-process CPU/RAM for its configured names.
+    pub fn processes(&self) -> impl Iterator<Item = (u32, &Arc<str>)> {
+        self.pid_to_name.iter().map(|(pid, name)| (*pid, name))
+    }
 }
 
 /// Event sent from WMI threads to the async event processor
@@ -132,10 +142,71 @@ enum ProcessEvent {
     Deleted(u32),         // pid
 }
 
+/// First and last wait between WMI re-subscribe attempts; doubles per failure
+const RETRY_MIN: Duration = Duration::from_secs(30);
+const RETRY_MAX: Duration = Duration::from_secs(30 * 60);
+
+fn next_retry_delay(delay: Duration) -> Duration {
+    (delay * 2).min(RETRY_MAX)
+}
+
+const MODE_EVENTS: u8 = 1;
+const MODE_POLLING: u8 = 2;
+
+/// How the watcher currently learns about process changes, for diagnostics
+#[derive(Default)]
+struct WatcherHealth {
+    /// 0 until started, then `MODE_EVENTS` or `MODE_POLLING`
+    mode: AtomicU8,
+    /// Times the event subscription was restored after it died or failed
+    resubscribes: AtomicU32,
+}
+
+impl WatcherHealth {
+    fn set_events(&self, events: bool) {
+        let mode = if events { MODE_EVENTS } else { MODE_POLLING };
+        self.mode.store(mode, Ordering::Relaxed);
+    }
+}
+
+/// Health check of a WMI event stream, fed at each reconciliation
+#[derive(Default)]
+struct StreamCheck {
+    /// Events received since the last reconciliation
+    events: usize,
+    /// Reconciliations in a row that found drift with no event in between
+    quiet_drifts: u32,
+}
+
+impl StreamCheck {
+    /// Record a reconciliation; true if the stream looks dead. One quiet
+    /// drift can be a process that started a moment before the snapshot,
+    /// its event still in WMI's 1s batch - two in a row can't.
+    fn after_reconcile(&mut self, drift: bool) -> bool {
+        if drift && self.events == 0 {
+            self.quiet_drifts += 1;
+        } else {
+            self.quiet_drifts = 0;
+        }
+        self.events = 0;
+        self.quiet_drifts >= 2
+    }
+}
+
+/// The next WMI event; never resolves while unsubscribed.
+async fn next_event(rx: &mut Option<mpsc::Receiver<ProcessEvent>>) -> Option<ProcessEvent> {
+    match rx {
+        Some(rx) => rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
 /// Event-driven process watcher
 pub struct ProcessWatcher {
     /// Shared process state
     state: Arc<RwLock<ProcessState>>,
+    /// Events vs polling, shared with the background task
+    health: Arc<WatcherHealth>,
     /// Channel for notifying subscribers of process changes
     change_tx: broadcast::Sender<ProcessChangeNotification>,
 }
@@ -150,7 +221,11 @@ impl ProcessWatcher {
         // Initial enumeration using ToolHelp (fast, reliable)
         Self::initial_enumeration(&state).await;
 
-        Self { state, change_tx }
+        Self {
+            state,
+            health: Arc::new(WatcherHealth::default()),
+            change_tx,
+        }
     }
 
     /// Subscribe to process change notifications
@@ -162,30 +237,41 @@ impl ProcessWatcher {
     ///
     /// This should be called once after creating the ProcessWatcher.
     /// Spawns background threads for WMI event subscription.
-    /// Falls back to polling if WMI fails.
+    /// Polls if WMI fails, re-subscribing with backoff until it works again.
     pub fn start_background(&self, shutdown_rx: broadcast::Receiver<()>, poll_interval: Duration) {
         let state = Arc::clone(&self.state);
+        let health = Arc::clone(&self.health);
         let change_tx = self.change_tx.clone();
         // WMI fires events for ALL processes. The WMI thread uses blocking_send(),
         // so a full channel stalls it rather than dropping events. WMI's WITHIN 1
         // batching means events arrive in ~1s bursts - 64 slots is ample.
         let (event_tx, event_rx) = mpsc::channel::<ProcessEvent>(64);
 
-        // Try WMI events first, fall back to polling
+        // Try WMI events first, poll until they can be re-subscribed
         tokio::spawn(async move {
-            match Self::setup_wmi_events(event_tx).await {
+            let event_rx = match Self::setup_wmi_events(event_tx).await {
                 Ok(()) => {
                     info!("Process watcher using WMI events");
-                    Self::run_event_processor(&state, shutdown_rx, change_tx, event_rx).await;
+                    Some(event_rx)
                 }
                 Err(e) => {
                     warn!(
-                        "WMI event subscription failed, using polling fallback: {}",
+                        "WMI event subscription failed, polling until it can be re-subscribed: {}",
                         e
                     );
-                    Self::run_polling_fallback(&state, shutdown_rx, poll_interval, change_tx).await;
+                    None
                 }
-            }
+            };
+            health.set_events(event_rx.is_some());
+            Self::run(
+                &state,
+                &health,
+                shutdown_rx,
+                poll_interval,
+                change_tx,
+                event_rx,
+            )
+            .await;
         });
     }
 
@@ -328,12 +414,22 @@ impl ProcessWatcher {
         }
     }
 
-    /// Process WMI events and run periodic reconciliation
-    async fn run_event_processor(
+    /// Apply WMI events and reconcile periodically while subscribed; poll
+    /// and retry the subscription with backoff while not.
+    ///
+    /// A subscription can die silently (WMI service restarted, quota
+    /// exceeded): the thread stays blocked with no events and no error. The
+    /// health check catches that - reconciliation finding missed processes
+    /// twice in a row with no event in between means the stream is dead, so
+    /// it is dropped and re-subscribed. The old thread stays parked in WMI's
+    /// iterator; it exits on its next event, or with the process.
+    async fn run(
         state: &Arc<RwLock<ProcessState>>,
+        health: &WatcherHealth,
         mut shutdown_rx: broadcast::Receiver<()>,
+        poll_interval: Duration,
         change_tx: broadcast::Sender<ProcessChangeNotification>,
-        mut event_rx: mpsc::Receiver<ProcessEvent>,
+        mut event_rx: Option<mpsc::Receiver<ProcessEvent>>,
     ) {
         // Adaptive reconciliation: start at 60s, extend to 5min when WMI is healthy,
         // shrink back to 60s if reconciliation detects drift (missed events).
@@ -341,25 +437,35 @@ impl ProcessWatcher {
         const MAX_RECONCILE_SECS: u64 = 300;
         let mut reconcile_secs = MIN_RECONCILE_SECS;
         let mut consecutive_clean = 0u32;
-        let mut next_reconcile = tokio::time::Instant::now() + Duration::from_secs(reconcile_secs);
-        let mut wmi_alive = true;
+        let mut next_reconcile = tokio::time::Instant::now()
+            + if event_rx.is_some() {
+                Duration::from_secs(reconcile_secs)
+            } else {
+                poll_interval
+            };
+        let mut retry_delay = RETRY_MIN;
+        let mut next_retry = tokio::time::Instant::now() + retry_delay;
+        let mut check = StreamCheck::default();
 
         loop {
             tokio::select! {
                 biased;
                 _ = shutdown_rx.recv() => {
-                    debug!("Event processor shutting down");
+                    debug!("Process watcher shutting down");
                     break;
                 }
-                result = event_rx.recv(), if wmi_alive => {
+                result = next_event(&mut event_rx) => {
                     match result {
                         Some(event) => {
                             // Batch: drain all pending events before acquiring write lock
                             let mut batch = Vec::new();
                             batch.push(event);
-                            while let Ok(e) = event_rx.try_recv() {
-                                batch.push(e);
+                            if let Some(rx) = &mut event_rx {
+                                while let Ok(e) = rx.try_recv() {
+                                    batch.push(e);
+                                }
                             }
+                            check.events += batch.len();
 
                             let mut guard = state.write().await;
                             for ev in batch {
@@ -379,45 +485,70 @@ impl ProcessWatcher {
                         }
                         None => {
                             // WMI event channel closed - thread died due to error.
-                            // Throttle first so a persistent fault (setup succeeds
-                            // but the stream dies immediately) can't become a tight
+                            // The backoff only resets once a stream proves healthy,
+                            // so a persistent fault (setup succeeds but the stream
+                            // dies immediately) can't become a tight
                             // restart+snapshot+fan-out loop that pegs a core.
-                            tokio::time::sleep(Duration::from_secs(2)).await;
-                            // Attempt to restart the WMI thread before falling back.
-                            warn!("WMI event stream lost, attempting restart...");
-                            let (new_tx, new_rx) = mpsc::channel::<ProcessEvent>(64);
-                            match Self::setup_wmi_events(new_tx).await {
-                                Ok(()) => {
-                                    info!("WMI event thread restarted successfully");
-                                    event_rx = new_rx;
-                                    // Reconcile immediately to catch anything missed
-                                    let (pruned, added) = Self::reconcile(state).await;
-                                    if pruned > 0 || added > 0 {
-                                        let _ = change_tx.send(ProcessChangeNotification);
-                                    }
-                                    reconcile_secs = MIN_RECONCILE_SECS;
-                                    consecutive_clean = 0;
-                                }
-                                Err(e) => {
-                                    // Restart failed - fall back to reconciliation-only mode
-                                    warn!("WMI restart failed: {}, falling back to reconciliation every {}s", e, MIN_RECONCILE_SECS);
-                                    wmi_alive = false;
-                                    reconcile_secs = MIN_RECONCILE_SECS;
-                                    consecutive_clean = 0;
-                                }
+                            warn!(
+                                "WMI event stream lost, polling; re-subscribing in {}s",
+                                retry_delay.as_secs()
+                            );
+                            event_rx = None;
+                            health.set_events(false);
+                            next_retry = tokio::time::Instant::now() + retry_delay;
+                            next_reconcile = tokio::time::Instant::now() + poll_interval;
+                        }
+                    }
+                }
+                () = tokio::time::sleep_until(next_retry), if event_rx.is_none() => {
+                    let (new_tx, new_rx) = mpsc::channel::<ProcessEvent>(64);
+                    match Self::setup_wmi_events(new_tx).await {
+                        Ok(()) => {
+                            info!("WMI event subscription restored");
+                            event_rx = Some(new_rx);
+                            health.set_events(true);
+                            health.resubscribes.fetch_add(1, Ordering::Relaxed);
+                            check = StreamCheck::default();
+                            // Reconcile immediately to catch anything missed
+                            let (pruned, added) = Self::reconcile(state).await;
+                            if pruned > 0 || added > 0 {
+                                let _ = change_tx.send(ProcessChangeNotification);
                             }
+                            reconcile_secs = MIN_RECONCILE_SECS;
+                            consecutive_clean = 0;
                             next_reconcile = tokio::time::Instant::now() + Duration::from_secs(reconcile_secs);
                         }
+                        Err(e) => {
+                            warn!("WMI re-subscribe failed: {}, retrying in {}s", e, retry_delay.as_secs());
+                            next_retry = tokio::time::Instant::now() + retry_delay;
+                            retry_delay = next_retry_delay(retry_delay);
+                        }
                     }
                 }
                 () = tokio::time::sleep_until(next_reconcile) => {
                     let (pruned, added) = Self::reconcile(state).await;
-                    if pruned > 0 || added > 0 {
+                    let drift = pruned > 0 || added > 0;
+                    if drift {
+                        let _ = change_tx.send(ProcessChangeNotification);
+                    }
+                    if event_rx.is_none() {
+                        next_reconcile = tokio::time::Instant::now() + poll_interval;
+                        continue;
+                    }
+                    if check.after_reconcile(drift) {
+                        warn!("WMI event stream stalled (missed processes, no events); re-subscribing");
+                        event_rx = None;
+                        health.set_events(false);
+                        next_retry = tokio::time::Instant::now();
+                        next_reconcile = tokio::time::Instant::now() + poll_interval;
+                        continue;
+                    }
+                    retry_delay = RETRY_MIN;
+                    if drift {
                         debug!("Reconciliation: pruned {} stale, added {} new entries, resetting interval to {}s", pruned, added, MIN_RECONCILE_SECS);
                         // Drift detected - shrink interval back to minimum
                         reconcile_secs = MIN_RECONCILE_SECS;
                         consecutive_clean = 0;
-                        let _ = change_tx.send(ProcessChangeNotification);
                     } else {
                         consecutive_clean += 1;
                         // After 3 consecutive clean reconciliations, double the interval
@@ -518,38 +649,6 @@ impl ProcessWatcher {
         pids
     }
 
-    /// Polling fallback if WMI events aren't available.
-    /// Uses reconcile (snapshot + diff) to both add new and remove stale processes.
-    async fn run_polling_fallback(
-        state: &Arc<RwLock<ProcessState>>,
-        mut shutdown_rx: broadcast::Receiver<()>,
-        poll_interval: Duration,
-        change_tx: broadcast::Sender<ProcessChangeNotification>,
-    ) {
-        let mut interval = tokio::time::interval(poll_interval);
-        // Skip missed ticks so a long reconcile (or resume from sleep) doesn't
-        // fire a burst of catch-up polls, consistent with the other sensors.
-        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
-        let state = Arc::clone(state);
-
-        loop {
-            tokio::select! {
-                biased;
-                _ = shutdown_rx.recv() => {
-                    debug!("Process watcher (polling) shutting down");
-                    break;
-                }
-                _ = interval.tick() => {
-                    let (pruned, added) = Self::reconcile(&state).await;
-                    // Notify subscribers after each poll if anything changed
-                    if pruned > 0 || added > 0 {
-                        let _ = change_tx.send(ProcessChangeNotification);
-                    }
-                }
-            }
-        }
-    }
-
     /// Get a snapshot of current process names
     pub async fn get_names(&self) -> HashSet<Arc<str>> {
         self.state.read().await.names.clone()
@@ -564,6 +663,21 @@ impl ProcessWatcher {
     pub fn state(&self) -> Arc<RwLock<ProcessState>> {
         Arc::clone(&self.state)
     }
+
+    /// `events` (WMI subscription) or `polling` (WMI down, re-subscribing
+    /// with backoff); None if the watcher wasn't started.
+    pub fn mode(&self) -> Option<&'static str> {
+        match self.health.mode.load(Ordering::Relaxed) {
+            MODE_EVENTS => Some("events"),
+            MODE_POLLING => Some("polling"),
+            _ => None,
+        }
+    }
+
+    /// Times the WMI subscription was restored after dying or failing
+    pub fn resubscribes(&self) -> u32 {
+        self.health.resubscribes.load(Ordering::Relaxed)
+    }
 }
 
 #[cfg(test)]
@@ -644,4 +758,24 @@ mod tests {
         state.add_process("chrome.exe".to_string(), 200);
         assert_eq!(state.scr_count, 0);
     }
+
+    #[test]
+    fn test_stream_check() {
+        let mut check = StreamCheck::default();
+        // A missed process right after a quiet spell: not yet dead
+        assert!(!check.after_reconcile(true));
+        // Events arrived in between: alive
+        check.events = 3;
+        assert!(!check.after_reconcile(true));
+        assert!(!check.after_reconcile(false));
+        assert!(!check.after_reconcile(true));
+        assert!(check.after_reconcile(true));
+    }
+
+    #[test]
+    fn test_next_retry_delay() {
+        assert_eq!(next_retry_delay(RETRY_MIN), Duration::from_secs(60));
+        assert_eq!(next_retry_delay(Duration::from_secs(20 * 60)), RETRY_MAX);
+        assert_eq!(next_retry_delay(RETRY_MAX), RETRY_MAX);
+    }
 }
@@ -0,0 +1,311 @@
+//! Remote Desktop and console session tracking
+//!
+//! `remote_desktop` is "on" while a Remote Desktop client is connected, with
+//! the connected sessions (`session_id`, `user`, `client`) as attributes.
+//! `remote_connected` / `remote_disconnected` and `console_connected` /
+//! `console_disconnected` events go out when that, or the session on the
+//! physical console (fast user switching), changes.
+//! - Windows: active sessions using the RDP protocol, and the active console
+//!   session; re-read on every WTS connect/disconnect notification
+//! - Linux: logind graphical sessions that are remote or have no seat (xrdp,
+//!   VNC), and the active session on `seat0`; polled with the session sensor
+//!
+//! Driven by `SessionSensor`, which owns the notifications / poll loop.
+
+use log::info;
+
+use crate::AppState;
+use crate::events::session_transitions;
+
+/// Sessions at one point in time: remote as `(session_id, user, client)`, the
+/// console's as `(session_id, user)`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct Sessions {
+    remote: Vec<(String, String, String)>,
+    console: Option<(String, String)>,
+}
+
+impl Sessions {
+    fn attributes(&self) -> serde_json::Value {
+        let sessions: Vec<_> = self
+            .remote
+            .iter()
+            .map(|(session_id, user, client)| {
+                serde_json::json!({
+                    "session_id": session_id,
+                    "user": user,
+                    "client": client,
+                })
+            })
+            .collect();
+        serde_json::json!({ "sessions": sessions })
+    }
+}
+
+/// Last snapshot, to diff the next one against.
+#[derive(Default)]
+pub(super) struct RemoteDesktopTracker {
+    prev: Option<Sessions>,
+    /// Publish the state even if unchanged (first read, after a reconnect).
+    stale: bool,
+}
+
+impl RemoteDesktopTracker {
+    /// Republish the state on the next refresh, without replaying events.
+    pub fn reset(&mut self) {
+        self.stale = true;
+    }
+
+    /// Re-read the sessions; publish events for what changed and the state.
+    /// The first read only publishes the state.
+    pub async fn refresh(&mut self, state: &AppState) {
+        let Ok(now) = tokio::task::spawn_blocking(snapshot).await else {
+            return;
+        };
+        if let Some(prev) = &self.prev {
+            for event in session_transitions(
+                &prev.remote,
+                &now.remote,
+                prev.console.as_ref(),
+                now.console.as_ref(),
+            ) {
+                info!("Session event: {event:?}");
+                state.mqtt.publish_event(&event).await;
+            }
+        }
+        let changed = self
+            .prev
+            .as_ref()
+            .is_none_or(|prev| prev.remote != now.remote);
+        if changed || self.stale {
+            let value = if now.remote.is_empty() { "off" } else { "on" };
+            state
+                .mqtt
+                .publish_sensor_retained("remote_desktop", value)
+                .await;
+            state
+                .mqtt
+                .publish_sensor_attributes("remote_desktop", &now.attributes())
+                .await;
+            self.stale = false;
+        }
+        self.prev = Some(now);
+    }
+}
+
+/// Connected Remote Desktop sessions and the console session, from WTS.
+#[cfg(windows)]
+fn snapshot() -> Sessions {
+    use windows::Win32::System::RemoteDesktop::{
+        WTS_CURRENT_SERVER_HANDLE, WTS_SESSION_INFOW, WTSActive, WTSClientName,
+        WTSClientProtocolType, WTSEnumerateSessionsW, WTSFreeMemory, WTSGetActiveConsoleSessionId,
+        WTSUserName,
+    };
+
+    /// WTSClientProtocolType value for RDP
+    const WTS_PROTOCOL_TYPE_RDP: u16 = 2;
+
+    let mut sessions = Sessions::default();
+    // SAFETY: the session list comes from WTS and is freed once, after its last
+    // use; the queries free their own buffers.
+    unsafe {
+        let mut list: *mut WTS_SESSION_INFOW = std::ptr::null_mut();
+        let mut count = 0u32;
+        if WTSEnumerateSessionsW(
+            WTS_CURRENT_SERVER_HANDLE,
+            0,
+            1,
+            &raw mut list,
+            &raw mut count,
+        )
+        .is_ok()
+        {
+            for session in std::slice::from_raw_parts(list, count as usize) {
+                let id = session.SessionId;
+                if session.State == WTSActive
+                    && query_u16(id, WTSClientProtocolType) == Some(WTS_PROTOCOL_TYPE_RDP)
+                {
+                    sessions.remote.push((
+                        id.to_string(),
+                        query_string(id, WTSUserName),
+                        query_string(id, WTSClientName),
+                    ));
+                }
+            }
+            WTSFreeMemory(list.cast());
+        }
+        // 0xFFFFFFFF while the console is being switched
+        let console = WTSGetActiveConsoleSessionId();
+        if console != u32::MAX {
+            sessions.console = Some((console.to_string(), query_string(console, WTSUserName)));
+        }
+    }
+    sessions
+}
+
+#[cfg(windows)]
+fn query_string(
+    session_id: u32,
+    class: windows::Win32::System::RemoteDesktop::WTS_INFO_CLASS,
+) -> String {
+    use windows::Win32::System::RemoteDesktop::{
+        WTS_CURRENT_SERVER_HANDLE, WTSFreeMemory, WTSQuerySessionInformationW,
+    };
+    use windows::core::PWSTR;
+
+    // SAFETY: the buffer comes from WTS and is freed once, after it's copied.
+    unsafe {
+        let mut buffer = PWSTR::null();
+        let mut bytes = 0u32;
+        if WTSQuerySessionInformationW(
+            WTS_CURRENT_SERVER_HANDLE,
+            session_id,
+            class,
+            &raw mut buffer,
+            &raw mut bytes,
+        )
+        .is_err()
+        {
+            return String::new();
+        }
+        let value = buffer.to_string().unwrap_or_default();
+        WTSFreeMemory(buffer.0.cast());
+        value
+    }
+}
+
+#[cfg(windows)]
+fn query_u16(
+    session_id: u32,
+    class: windows::Win32::System::RemoteDesktop::WTS_INFO_CLASS,
+) -> Option<u16> {
+    use windows::Win32::System::RemoteDesktop::{
+        WTS_CURRENT_SERVER_HANDLE, WTSFreeMemory, WTSQuerySessionInformationW,
+    };
+    use windows::core::PWSTR;
+
+    // SAFETY: as in query_string; the buffer is read only when it holds a u16.
+    unsafe {
+        let mut buffer = PWSTR::null();
+        let mut bytes = 0u32;
+        WTSQuerySessionInformationW(
+            WTS_CURRENT_SERVER_HANDLE,
+            session_id,
+            class,
+            &raw mut buffer,
+            &raw mut bytes,
+        )
+        .ok()?;
+        let value = (bytes as usize >= std::mem::size_of::<u16>())
+            .then(|| buffer.0.cast::<u16>().read_unaligned());
+        WTSFreeMemory(buffer.0.cast());
+        value
+    }
+}
+
+/// Remote graphical sessions and the `seat0` session, from logind. Empty when
+/// loginctl is missing (the session sensor already warns about that).
+#[cfg(unix)]
+fn snapshot() -> Sessions {
+    use std::process::Command;
+
+    let Ok(list) = Command::new("loginctl")
+        .args(["list-sessions", "--no-legend"])
+        .output()
+    else {
+        return Sessions::default();
+    };
+    let ids: Vec<String> = String::from_utf8_lossy(&list.stdout)
+        .lines()
+        .filter_map(|line| line.split_whitespace().next().map(str::to_string))
+        .collect();
+    if ids.is_empty() {
+        return Sessions::default();
+    }
+    let Ok(out) = Command::new("loginctl")
+        .arg("show-session")
+        .args(&ids)
+        .args([
+            "-p",
+            "Id",
+            "-p",
+            "Name",
+            "-p",
+            "Remote",
+            "-p",
+            "RemoteHost",
+            "-p",
+            "Type",
+            "-p",
+            "Seat",
+            "-p",
+            "Active",
+            "-p",
+            "State",
+        ])
+        .output()
+    else {
+        return Sessions::default();
+    };
+    parse_logind_sessions(&String::from_utf8_lossy(&out.stdout))
+}
+
+/// `loginctl show-session` output for several sessions: `Key=value` blocks
+/// separated by blank lines. A graphical session (x11 / wayland) is remote
+/// when logind says so or it has no seat, which is how xrdp and VNC sessions
+/// show up.
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_logind_sessions(output: &str) -> Sessions {
+    let mut sessions = Sessions::default();
+    for block in output.split("\n\n") {
+        let field = |key: &str| {
+            block
+                .lines()
+                .find_map(|line| line.trim().strip_prefix(key)?.strip_prefix('='))
+                .unwrap_or_default()
+        };
+        let id = field("Id");
+        if id.is_empty() || field("State") == "closing" {
+            continue;
+        }
+        let seat = field("Seat");
+        if seat == "seat0" && field("Active") == "yes" {
+            sessions.console = Some((id.to_string(), field("Name").to_string()));
+        }
+        let graphical = matches!(field("Type"), "x11" | "wayland");
+        if graphical && (field("Remote") == "yes" || seat.is_empty()) {
+            sessions.remote.push((
+                id.to_string(),
+                field("Name").to_string(),
+                field("RemoteHost").to_string(),
+            ));
+        }
+    }
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_logind_sessions() {
+        let output = "Id=2\nName=alice\nRemote=no\nRemoteHost=\nType=wayland\nSeat=seat0\nActive=yes\nState=active\n\n\
+                      Id=c4\nName=bob\nRemote=yes\nRemoteHost=192.168.1.20\nType=x11\nSeat=\nActive=yes\nState=active\n\n\
+                      Id=7\nName=alice\nRemote=yes\nRemoteHost=laptop\nType=tty\nSeat=\nActive=yes\nState=active\n\n\
+                      Id=c2\nName=carol\nRemote=no\nType=x11\nSeat=\nActive=no\nState=closing\n";
+        let sessions = parse_logind_sessions(output);
+        assert_eq!(sessions.console, Some(("2".into(), "alice".into())));
+        // The ssh login (tty) and the closing session don't count.
+        assert_eq!(
+            sessions.remote,
+            vec![("c4".into(), "bob".into(), "192.168.1.20".into())]
+        );
+        assert_eq!(
+            sessions.attributes()["sessions"][0]["client"],
+            "192.168.1.20"
+        );
+
+        assert_eq!(parse_logind_sessions(""), Sessions::default());
+    }
+}
@@ -0,0 +1,92 @@
+//! Reporting switch states
+//!
+//! `report_active_window` and `report_games` are the HA switches' states:
+//! "ON" while the sensor is reported, "OFF" while `crate::reporting` has it
+//! paused. Event-driven, no polling.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use super::runner::{Reading, Sensor};
+use crate::AppState;
+use crate::config::Config;
+use crate::reporting::Reported;
+
+pub struct ReportingSensor {
+    state: Arc<AppState>,
+    paused_rx: watch::Receiver<BTreeSet<Reported>>,
+}
+
+impl ReportingSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self {
+            state,
+            paused_rx: crate::reporting::subscribe(),
+        }
+    }
+
+    pub async fn run(self) {
+        super::runner::run(Arc::clone(&self.state), self).await;
+    }
+}
+
+/// Switch states of the reported sensors enabled in `config`
+fn readings(paused: &BTreeSet<Reported>, config: &Config) -> Vec<Reading> {
+    let f = &config.features;
+    Reported::ALL
+        .into_iter()
+        .filter(|r| match r {
+            Reported::ActiveWindow => f.active_window,
+            Reported::Games => f.running_game,
+        })
+        .map(|r| {
+            let on = if paused.contains(&r) { "OFF" } else { "ON" };
+            Reading::new(r.switch(), on).retained()
+        })
+        .collect()
+}
+
+impl Sensor for ReportingSensor {
+    const NAME: &'static str = "Reporting";
+
+    fn interval(_config: &Config) -> Option<Duration> {
+        None
+    }
+
+    async fn event(&mut self) -> Option<()> {
+        self.paused_rx.changed().await.ok()
+    }
+
+    async fn poll(&mut self) -> Vec<Reading> {
+        let paused = self.paused_rx.borrow_and_update().clone();
+        let config = self.state.config.read().await;
+        readings(&paused, &config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readings_follow_switches_and_features() {
+        let mut config = Config::default();
+        config.features.active_window = true;
+        config.features.running_game = false;
+        let paused = BTreeSet::from([Reported::ActiveWindow, Reported::Games]);
+        assert_eq!(
+            readings(&paused, &config),
+            [Reading::new("report_active_window", "OFF").retained()]
+        );
+        config.features.running_game = true;
+        assert_eq!(
+            readings(&BTreeSet::new(), &config),
+            [
+                Reading::new("report_active_window", "ON").retained(),
+                Reading::new("report_games", "ON").retained(),
+            ]
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//! Shared sensor loop. A [`Sensor`] only says how often to poll, what wakes
+//! it early and what it currently reads; [`run`] owns the shutdown, config
+//! hot-reload and reconnect handling every sensor used to repeat, and
+//! [`Published`] only sends the readings that changed since the last publish
+//! (by more than their `deadbands` entry, for numeric ones).
+//!
+//! Sensors that hold OS threads or juggle several intervals (SystemSensor,
+//! custom sensors) keep their own loop but still publish through
+//! [`Published`].
+
+use log::debug;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{Instant, Interval, MissedTickBehavior, interval_at};
+
+use crate::AppState;
+use crate::config::{Config, Deadband};
+
+/// One sensor value as last read, published only when it changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reading {
+    name: Cow<'static, str>,
+    value: String,
+    retained: bool,
+    /// Published alongside the value; None leaves the last attributes alone
+    attributes: Option<serde_json::Value>,
+    /// The attributes only go out with a published value, see
+    /// [`Reading::attributes_with_value`]
+    attributes_follow_value: bool,
+}
+
+impl Reading {
+    pub fn new(name: impl Into<Cow<'static, str>>, value: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            value: value.into(),
+            retained: false,
+            attributes: None,
+            attributes_follow_value: false,
+        }
+    }
+
+    /// Publish retained, for states HA must see right after it (re)starts
+    pub fn retained(mut self) -> Self {
+        self.retained = true;
+        self
+    }
+
+    pub fn with_attributes(mut self, attributes: serde_json::Value) -> Self {
+        self.attributes = Some(attributes);
+        self
+    }
+
+    /// Hold the attributes back while the value is unchanged or inside its
+    /// deadband: for attributes that move on every poll (rolling averages),
+    /// which would otherwise defeat both.
+    pub fn attributes_with_value(mut self) -> Self {
+        self.attributes_follow_value = true;
+        self
+    }
+}
+
+/// A polled and/or event-driven sensor driven by [`run`]
+pub trait Sensor: Send {
+    /// For logs
+    const NAME: &'static str;
+
+    /// Poll period, re-read on every config change. None polls only on
+    /// [`Sensor::event`].
+    fn interval(config: &Config) -> Option<Duration>;
+
+    /// Called at startup and after every config change, before the next
+    /// poll: rebuild anything derived from the config here.
+    fn configure(&mut self) -> impl Future<Output = ()> + Send {
+        async {}
+    }
+
+    /// Resolves when the sensor should be polled early (a push notification
+    /// from an OS watcher). None means the source is gone and ends the
+    /// sensor. Never resolves by default.
+    fn event(&mut self) -> impl Future<Output = Option<()>> + Send {
+        std::future::pending()
+    }
+
+    /// Read the sensor's current values. One-off side effects (events,
+    /// notifications) belong here too; the runner dedupes only the readings.
+    fn poll(&mut self) -> impl Future<Output = Vec<Reading>> + Send;
+}
+
+/// What was last published for one reading
+struct Last {
+    value: String,
+    at: Instant,
+    attributes: Option<serde_json::Value>,
+}
+
+/// What was last published for each reading
+#[derive(Default)]
+pub struct Published {
+    last: HashMap<Cow<'static, str>, Last>,
+    /// `deadbands` from the config, see [`Published::configure`]
+    deadbands: HashMap<String, Deadband>,
+}
+
+impl Published {
+    /// Pick up the config's `deadbands`; call at startup and on every
+    /// config change.
+    pub fn configure(&mut self, config: &Config) {
+        self.deadbands.clone_from(&config.deadbands);
+    }
+
+    /// Publish whichever of `reading`'s value and attributes changed
+    pub async fn publish(&mut self, state: &AppState, reading: Reading) {
+        let now = Instant::now();
+        let (value, attributes) = self.diff(&reading, now);
+        if value {
+            if reading.retained {
+                state
+                    .mqtt
+                    .publish_sensor_retained(&reading.name, &reading.value)
+                    .await;
+            } else {
+                state
+                    .mqtt
+                    .publish_sensor(&reading.name, &reading.value)
+                    .await;
+            }
+        }
+        if attributes && let Some(attrs) = &reading.attributes {
+            state
+                .mqtt
+                .publish_sensor_attributes(&reading.name, attrs)
+                .await;
+        }
+        self.record(reading, value, now);
+    }
+
+    pub async fn publish_all(&mut self, state: &AppState, readings: Vec<Reading>) {
+        for reading in readings {
+            self.publish(state, reading).await;
+        }
+    }
+
+    /// Forget everything, so the next readings are all published
+    pub fn clear(&mut self) {
+        self.last.clear();
+    }
+
+    /// Note `reading` as published. A value held back by its deadband isn't:
+    /// the next reading is measured against the one HA shows.
+    fn record(&mut self, reading: Reading, value_published: bool, now: Instant) {
+        let attributes = reading
+            .attributes
+            .filter(|_| value_published || !reading.attributes_follow_value);
+        match self.last.get_mut(&reading.name) {
+            Some(last) => {
+                if value_published {
+                    last.value = reading.value;
+                    last.at = now;
+                }
+                if attributes.is_some() {
+                    last.attributes = attributes;
+                }
+            }
+            None => {
+                self.last.insert(
+                    reading.name,
+                    Last {
+                        value: reading.value,
+                        at: now,
+                        attributes,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Whether the (value, attributes) differ from what was last published
+    fn diff(&self, reading: &Reading, now: Instant) -> (bool, bool) {
+        match self.last.get(&reading.name) {
+            None => (true, reading.attributes.is_some()),
+            Some(last) => {
+                let value =
+                    last.value != reading.value && self.outside_deadband(reading, last, now);
+                let attributes = reading.attributes.is_some()
+                    && last.attributes != reading.attributes
+                    && (value || !reading.attributes_follow_value);
+                (value, attributes)
+            }
+        }
+    }
+
+    /// Whether a changed value is worth publishing under the reading's
+    /// deadband. Anything non-numeric ("unavailable") always is.
+    fn outside_deadband(&self, reading: &Reading, last: &Last, now: Instant) -> bool {
+        let Some(deadband) = self.deadbands.get(reading.name.as_ref()) else {
+            return true;
+        };
+        let (Ok(new), Ok(old)) = (reading.value.parse::<f64>(), last.value.parse::<f64>()) else {
+            return true;
+        };
+        (new - old).abs() >= deadband.min_change
+            || (deadband.max_age_secs > 0
+                && now.duration_since(last.at) >= Duration::from_secs(deadband.max_age_secs))
+    }
+}
+
+fn ticker(period: Option<Duration>) -> Option<Interval> {
+    // Starts one period out: the initial readings are published up front.
+    let period = period?.max(Duration::from_secs(1));
+    let mut tick = interval_at(tokio::time::Instant::now() + period, period);
+    // A suspend/resume must not fire a burst of catch-up polls.
+    tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    Some(tick)
+}
+
+async fn next_tick(tick: &mut Option<Interval>) {
+    match tick {
+        Some(tick) => {
+            tick.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Drive `sensor` until shutdown (or its event source closes), publishing
+/// its changed readings on every tick, event and config change, and all of
+/// them again after a broker reconnect.
+pub async fn run<S: Sensor>(state: Arc<AppState>, mut sensor: S) {
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut config_rx = state.config_generation.subscribe();
+    let mut reconnect_rx = state.mqtt.subscribe_reconnect();
+    let mut published = Published::default();
+
+    sensor.configure().await;
+    let config = state.config.read().await;
+    published.configure(&config);
+    let mut period = S::interval(&config);
+    drop(config);
+    let mut tick = ticker(period);
+    published.publish_all(&state, sensor.poll().await).await;
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => {
+                debug!("{} sensor shutting down", S::NAME);
+                break;
+            }
+            // Lagged too: a burst of generations must not leave the sensor on
+            // a stale interval or config.
+            r = config_rx.recv() => {
+                if !matches!(r, Ok(()) | Err(RecvError::Lagged(_))) {
+                    continue;
+                }
+                sensor.configure().await;
+                let config = state.config.read().await;
+                published.configure(&config);
+                let new_period = S::interval(&config);
+                drop(config);
+                if new_period != period {
+                    period = new_period;
+                    tick = ticker(period);
+                    debug!("{} sensor: interval changed to {:?}", S::NAME, period);
+                }
+                published.publish_all(&state, sensor.poll().await).await;
+            }
+            Ok(()) = reconnect_rx.recv() => {
+                debug!("{} sensor: MQTT reconnected, republishing current state", S::NAME);
+                published.clear();
+                published.publish_all(&state, sensor.poll().await).await;
+            }
+            () = next_tick(&mut tick) => {
+                published.publish_all(&state, sensor.poll().await).await;
+            }
+            event = sensor.event() => {
+                if event.is_none() {
+                    debug!("{} sensor: event source closed", S::NAME);
+                    break;
+                }
+                published.publish_all(&state, sensor.poll().await).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(published: &mut Published, reading: Reading) -> (bool, bool) {
+        record_at(published, reading, Instant::now())
+    }
+
+    fn record_at(published: &mut Published, reading: Reading, now: Instant) -> (bool, bool) {
+        let changed = published.diff(&reading, now);
+        published.record(reading, changed.0, now);
+        changed
+    }
+
+    #[test]
+    fn test_published_diffs_value_and_attributes_separately() {
+        let mut published = Published::default();
+        let level = |v: &str, cycles: u32| {
+            Reading::new("battery_level", v)
+                .with_attributes(serde_json::json!({ "cycles": cycles }))
+        };
+
+        assert_eq!(record(&mut published, level("80", 10)), (true, true));
+        assert_eq!(record(&mut published, level("80", 10)), (false, false));
+        assert_eq!(record(&mut published, level("79", 10)), (true, false));
+        assert_eq!(record(&mut published, level("79", 11)), (false, true));
+
+        // No attributes this time: only the value is compared, and the last
+        // attributes still count for the next reading that has some.
+        assert_eq!(
+            record(&mut published, Reading::new("battery_level", "79")),
+            (false, false)
+        );
+        assert_eq!(record(&mut published, level("79", 11)), (false, false));
+
+        published.clear();
+        assert_eq!(record(&mut published, level("79", 11)), (true, true));
+    }
+
+    #[test]
+    fn test_deadband_holds_back_small_changes() {
+        let mut config = Config::default();
+        config.deadbands.insert(
+            "cpu_usage".to_string(),
+            Deadband {
+                min_change: 1.0,
+                max_age_secs: 300,
+            },
+        );
+        let mut published = Published::default();
+        published.configure(&config);
+        let cpu = |v: &str| Reading::new("cpu_usage", v);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(record_at(&mut published, cpu("12.3"), at(0)), (true, false));
+        assert_eq!(
+            record_at(&mut published, cpu("12.4"), at(10)),
+            (false, false)
+        );
+        // Measured against the published 12.3, not the held-back 12.4
+        assert_eq!(
+            record_at(&mut published, cpu("13.2"), at(20)),
+            (false, false)
+        );
+        assert_eq!(
+            record_at(&mut published, cpu("13.3"), at(30)),
+            (true, false)
+        );
+        // A small change goes out once max_age_secs have passed
+        assert_eq!(
+            record_at(&mut published, cpu("13.5"), at(100)),
+            (false, false)
+        );
+        assert_eq!(
+            record_at(&mut published, cpu("13.5"), at(330)),
+            (true, false)
+        );
+        // Non-numeric values always go out
+        assert_eq!(
+            record_at(&mut published, cpu("unavailable"), at(340)),
+            (true, false)
+        );
+        // Rolling attributes wait for the value
+        let rolling = |v: &str, avg: f64| {
+            cpu(v)
+                .with_attributes(serde_json::json!({ "avg_1m": avg }))
+                .attributes_with_value()
+        };
+        assert_eq!(
+            record_at(&mut published, rolling("20.0", 20.0), at(350)),
+            (true, true)
+        );
+        assert_eq!(
+            record_at(&mut published, rolling("20.5", 20.2), at(360)),
+            (false, false)
+        );
+        assert_eq!(
+            record_at(&mut published, rolling("21.0", 20.4), at(370)),
+            (true, true)
+        );
+        // Sensors without a deadband publish every change
+        assert_eq!(
+            record_at(&mut published, Reading::new("memory_usage", "41.0"), at(0)),
+            (true, false)
+        );
+        assert_eq!(
+            record_at(&mut published, Reading::new("memory_usage", "41.1"), at(1)),
+            (true, false)
+        );
+    }
+}
@@ -4,37 +4,53 @@
 //! publishes "locked"/"unlocked" to the `session` sensor. Uses its own hidden
 //! message-pump window so it is fully isolated from the power-events listener
 //! (which handles sleep/wake) - a bug here can never affect sleep detection.
+//!
+//! The same notifications, for all sessions, drive the `remote_desktop`
+//! tracker: any connect, disconnect, logon or logoff re-reads the sessions.
 
 use log::{debug, error, info};
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
 use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
 use windows::Win32::System::RemoteDesktop::{
-    WTSRegisterSessionNotification, WTSUnRegisterSessionNotification,
+    ProcessIdToSessionId, WTSRegisterSessionNotification, WTSUnRegisterSessionNotification,
 };
+use windows::Win32::System::Threading::GetCurrentProcessId;
 use windows::Win32::UI::WindowsAndMessaging::{
     CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GWLP_USERDATA, GetMessageW,
     GetWindowLongPtrW, MSG, PostMessageW, RegisterClassExW, SetWindowLongPtrW, TranslateMessage,
     WINDOW_EX_STYLE, WINDOW_STYLE, WM_USER, WNDCLASSEXW,
 };
 
+use super::remote_desktop::RemoteDesktopTracker;
 use crate::AppState;
 
 const WM_WTSSESSION_CHANGE: u32 = 0x02B1;
+const WTS_CONSOLE_CONNECT: usize = 0x1;
+const WTS_SESSION_LOGOFF: usize = 0x6;
 const WTS_SESSION_LOCK: usize = 0x7;
 const WTS_SESSION_UNLOCK: usize = 0x8;
-const NOTIFY_FOR_THIS_SESSION: u32 = 0;
+const NOTIFY_FOR_ALL_SESSIONS: u32 = 1;
+
+/// Re-read the sessions this often even without a notification, in case one
+/// was missed (or `remote_desktop` was just turned on).
+const REMOTE_REFRESH: Duration = Duration::from_secs(60);
 
-/// Lock/unlock event carried from the message pump to the async publisher.
+/// Session event carried from the message pump to the async publisher.
 #[derive(Debug, Clone, Copy)]
 enum SessionEvent {
     Locked,
     Unlocked,
+    /// A console / remote connect or disconnect, logon or logoff, in any session
+    Changed,
 }
 
 /// Stored in the window's user data so `wnd_proc` can forward events.
 struct WndProcContext {
     event_tx: mpsc::Sender<SessionEvent>,
+    /// Our own session: lock/unlock of other sessions is ignored.
+    session_id: u32,
 }
 
 pub struct SessionSensor {
@@ -85,6 +101,10 @@ impl SessionSensor {
         // Skip duplicate publishes (e.g. a brief double-registration on rapid
         // re-enable emitting the same lock state twice).
         let mut prev: Option<&'static str> = None;
+        let mut remote = RemoteDesktopTracker::default();
+        let mut refresh = interval(REMOTE_REFRESH);
+        refresh.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
 
         loop {
             tokio::select! {
@@ -99,12 +119,29 @@ impl SessionSensor {
                     }
                     break;
                 }
+                Ok(()) = reconnect_rx.recv() => {
+                    remote.reset();
+                }
+                // First tick fires immediately: the initial read.
+                _ = refresh.tick() => {
+                    if self.state.config.read().await.features.remote_desktop {
+                        remote.refresh(&self.state).await;
+                    }
+                }
                 Some(event) = event_rx.recv() => {
                     let value = match event {
                         SessionEvent::Locked => "locked",
                         SessionEvent::Unlocked => "unlocked",
+                        SessionEvent::Changed => {
+                            if self.state.config.read().await.features.remote_desktop {
+                                remote.refresh(&self.state).await;
+                            }
+                            continue;
+                        }
                     };
-                    if prev == Some(value) {
+                    if !self.state.config.read().await.features.session_state
+                        || prev == Some(value)
+                    {
                         continue;
                     }
                     prev = Some(value);
@@ -154,13 +191,18 @@ impl SessionSensor {
                 }
             };
 
-            if let Err(e) = WTSRegisterSessionNotification(hwnd, NOTIFY_FOR_THIS_SESSION) {
+            if let Err(e) = WTSRegisterSessionNotification(hwnd, NOTIFY_FOR_ALL_SESSIONS) {
                 error!("Failed to register session notifications: {:?}", e);
             } else {
-                info!("Registered for session lock/unlock notifications");
+                info!("Registered for session notifications");
             }
 
-            let ctx = Box::new(WndProcContext { event_tx });
+            let mut session_id = 0u32;
+            let _ = ProcessIdToSessionId(GetCurrentProcessId(), &raw mut session_id);
+            let ctx = Box::new(WndProcContext {
+                event_tx,
+                session_id,
+            });
             let ctx_ptr = Box::into_raw(ctx);
             SetWindowLongPtrW(hwnd, GWLP_USERDATA, ctx_ptr as isize);
 
@@ -196,13 +238,19 @@ impl SessionSensor {
                 let ctx_ptr = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const WndProcContext;
                 if !ctx_ptr.is_null() {
                     let ctx = &*ctx_ptr;
+                    // lparam is the session the notification is about.
+                    let own = lparam.0 as u32 == ctx.session_id;
                     match wparam.0 {
-                        WTS_SESSION_LOCK => {
+                        WTS_SESSION_LOCK if own => {
                             let _ = ctx.event_tx.blocking_send(SessionEvent::Locked);
                         }
-                        WTS_SESSION_UNLOCK => {
+                        WTS_SESSION_UNLOCK if own => {
                             let _ = ctx.event_tx.blocking_send(SessionEvent::Unlocked);
                         }
+                        // A full queue already holds a re-read.
+                        WTS_CONSOLE_CONNECT..=WTS_SESSION_LOGOFF => {
+                            let _ = ctx.event_tx.try_send(SessionEvent::Changed);
+                        }
                         _ => {}
                     }
                 }
@@ -2,13 +2,15 @@
 //!
 //! Polls logind's `LockedHint` for the current session and publishes
 //! "locked"/"unlocked" to the `session` sensor. Mirrors the Windows WTS-based
-//! `SessionSensor`; Linux has no cheap event, so it polls.
+//! `SessionSensor`; Linux has no cheap event, so it polls. The same poll feeds
+//! the `remote_desktop` tracker.
 
 use log::{debug, info};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::time::{Duration, MissedTickBehavior, interval};
 
+use super::remote_desktop::RemoteDesktopTracker;
 use crate::AppState;
 
 static LOGINCTL_WARNED: AtomicBool = AtomicBool::new(false);
@@ -28,6 +30,7 @@ impl SessionSensor {
         let mut shutdown_rx = shutdown.subscribe();
         let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
         let mut prev: &'static str = "";
+        let mut remote = RemoteDesktopTracker::default();
 
         info!("Session sensor started (Linux logind, polled every 5s)");
 
@@ -40,8 +43,19 @@ impl SessionSensor {
                 }
                 Ok(()) = reconnect_rx.recv() => {
                     prev = "";
+                    remote.reset();
                 }
                 _ = tick.tick() => {
+                    let (session_state, remote_desktop) = {
+                        let f = &self.state.config.read().await.features;
+                        (f.session_state, f.remote_desktop)
+                    };
+                    if remote_desktop {
+                        remote.refresh(&self.state).await;
+                    }
+                    if !session_state {
+                        continue;
+                    }
                     // Fall back to "unlocked" (not "unknown") so the value vocab
                     // matches the Windows producer, which only emits locked/unlocked.
                     let value = tokio::task::spawn_blocking(read_locked)
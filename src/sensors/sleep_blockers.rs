@@ -0,0 +1,108 @@
+//! Sleep blockers sensor: what's keeping the PC awake
+//!
+//! `sleep_blockers` is the number of active power requests that keep the PC
+//! from sleeping on its own, polled every `intervals.sleep_blockers`. The
+//! `blockers` attribute names them ("chrome.exe (Audio Stream)") and
+//! `requests` lists every request, display ones included, with its `kind`,
+//! `requester` and `reason`. See `power::requests` for the sources; on
+//! Windows powercfg needs an elevated agent, else the sensor is unavailable.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::debug;
+
+use super::runner::{Reading, Sensor};
+use crate::AppState;
+use crate::config::Config;
+use crate::power::requests::{self, PowerRequest};
+
+pub struct SleepBlockersSensor {
+    state: Arc<AppState>,
+}
+
+impl SleepBlockersSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        if !self.state.config.read().await.features.sleep_blockers {
+            return;
+        }
+        super::runner::run(Arc::clone(&self.state), self).await;
+    }
+}
+
+fn reading(requests: Option<Vec<PowerRequest>>) -> Reading {
+    let Some(requests) = requests else {
+        return Reading::new("sleep_blockers", "unavailable");
+    };
+    let blockers: Vec<String> = requests
+        .iter()
+        .filter(|r| r.blocks_sleep)
+        .map(PowerRequest::describe)
+        .collect();
+    let listed: Vec<serde_json::Value> = requests
+        .iter()
+        .map(|r| {
+            serde_json::json!({
+                "kind": r.kind,
+                "requester": r.requester,
+                "reason": r.reason,
+            })
+        })
+        .collect();
+    Reading::new("sleep_blockers", blockers.len().to_string()).with_attributes(serde_json::json!({
+        "blockers": blockers,
+        "requests": listed,
+    }))
+}
+
+impl Sensor for SleepBlockersSensor {
+    const NAME: &'static str = "Sleep blockers";
+
+    fn interval(config: &Config) -> Option<Duration> {
+        Some(Duration::from_secs(config.intervals.sleep_blockers.max(5)))
+    }
+
+    async fn poll(&mut self) -> Vec<Reading> {
+        let requests = requests::active().await;
+        if requests.is_none() {
+            debug!("Sleep blockers: power requests can't be read");
+        }
+        vec![reading(requests)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reading() {
+        let requests = vec![
+            PowerRequest {
+                kind: "display".to_string(),
+                requester: "chrome.exe".to_string(),
+                reason: Some("Video Wake Lock".to_string()),
+                blocks_sleep: false,
+            },
+            PowerRequest {
+                kind: "system".to_string(),
+                requester: "chrome.exe".to_string(),
+                reason: Some("Audio Stream".to_string()),
+                blocks_sleep: true,
+            },
+        ];
+        let expected = Reading::new("sleep_blockers", "1").with_attributes(serde_json::json!({
+            "blockers": ["chrome.exe (Audio Stream)"],
+            "requests": [
+                { "kind": "display", "requester": "chrome.exe", "reason": "Video Wake Lock" },
+                { "kind": "system", "requester": "chrome.exe", "reason": "Audio Stream" },
+            ],
+        }));
+        assert_eq!(reading(Some(requests)), expected);
+        assert_eq!(reading(None), Reading::new("sleep_blockers", "unavailable"));
+    }
+}
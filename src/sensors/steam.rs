@@ -2,17 +2,19 @@
 //!
 //! Uses filesystem watcher (`notify` crate) for instant detection of ACF manifest changes.
 //! Falls back to periodic polling if the watcher fails.
+//!
+//! The `activity` attribute tells a download apart from installing and from
+//! shader pre-caching (Steam's `fossilize_replay` workers, which run after an
+//! update while `steam_updating` is already off). Download speed comes from
+//! how fast the manifests' `BytesDownloaded` grows, or else from the last rate
+//! in Steam's `logs/content_log.txt`.
 
 use log::{debug, error, info, warn};
 use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, LazyLock};
+use std::sync::Arc;
 use tokio::sync::mpsc;
-
-/// Pre-built JSON attributes for the idle (no updates) state.
-/// Avoids re-creating the same `serde_json::Value` every publish cycle.
-static IDLE_STEAM_ATTRS: LazyLock<serde_json::Value> =
-    LazyLock::new(|| serde_json::json!({"updating_games": [], "count": 0}));
 use tokio::time::{Duration, Instant};
 
 use crate::AppState;
@@ -45,18 +47,39 @@ const STATE_UPDATE_MASK: u32 = 0x2      // UpdateRequired
     | 0x20_0000 // Staging
     | 0x40_0000; // Committing
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 struct GameUpdateState {
     app_id: String,
     name: String,
     state_flags: u32,
     manifest_path: PathBuf,
+    /// Download progress; Steam rewrites these while it works, 0 if absent
+    bytes_downloaded: u64,
+    bytes_to_download: u64,
+    bytes_staged: u64,
+    bytes_to_stage: u64,
+}
+
+/// An app's download rate, from how fast its `BytesDownloaded` grows
+struct DownloadRate {
+    /// When `bytes` was last seen to change
+    at: Instant,
+    bytes: u64,
+    /// Bytes/s between the last two changes
+    rate: Option<f64>,
 }
 
 pub struct SteamSensor {
     state: Arc<AppState>,
     library_folders: Vec<PathBuf>,
+    /// Steam's `logs/content_log.txt`, for the download rate fallback
+    content_log: Option<PathBuf>,
     updating_games: HashMap<String, GameUpdateState>,
+    rates: HashMap<String, DownloadRate>,
+    /// Whether shader pre-caching was running at the last publish
+    shaders: bool,
+    /// State and attributes last published, so progress polls only publish changes
+    last_published: Option<(&'static str, serde_json::Value)>,
     last_full_scan: Instant,
     /// Cache of ACF file paths → (mtime, parsed state) to skip unchanged files
     acf_cache: HashMap<PathBuf, (std::time::SystemTime, Option<GameUpdateState>)>,
@@ -67,7 +90,11 @@ impl SteamSensor {
         Self {
             state,
             library_folders: Vec::new(),
+            content_log: None,
             updating_games: HashMap::new(),
+            rates: HashMap::new(),
+            shaders: false,
+            last_published: None,
             last_full_scan: Instant::now(),
             acf_cache: HashMap::new(),
         }
@@ -81,9 +108,13 @@ impl SteamSensor {
         let mut shutdown_rx = self.state.shutdown_tx.subscribe();
 
         // Discover Steam library folders (blocking registry/file I/O - off-runtime)
-        self.library_folders = tokio::task::spawn_blocking(discover_library_folders_blocking)
-            .await
-            .unwrap_or_default();
+        (self.library_folders, self.content_log) = tokio::task::spawn_blocking(|| {
+            let content_log = crate::steam::find_steam_path()
+                .map(|steam| steam.join("logs").join("content_log.txt"));
+            (discover_library_folders_blocking(), content_log)
+        })
+        .await
+        .unwrap_or_default();
 
         if self.library_folders.is_empty() {
             warn!("No Steam library folders found, steam sensor disabled");
@@ -117,8 +148,9 @@ impl SteamSensor {
 
         loop {
             // When updates are active, also poll at 5s for progress tracking
-            // (ACF files may not trigger fs events on every byte written)
-            let sleep_duration = if !self.updating_games.is_empty() {
+            // (ACF files may not trigger fs events on every byte written), and
+            // for the end of shader pre-caching (no ACF changes at all)
+            let sleep_duration = if !self.updating_games.is_empty() || self.shaders {
                 Duration::from_secs(5)
             } else if using_watcher {
                 // With fs watcher, only do periodic reconciliation scans
@@ -141,7 +173,13 @@ impl SteamSensor {
                     self.last_full_scan = Instant::now();
                 }
                 () = tokio::time::sleep(sleep_duration) => {
-                    if self.updating_games.is_empty() {
+                    if self.updating_games.is_empty()
+                        && self.shaders
+                        && self.last_full_scan.elapsed() < Duration::from_secs(base_interval)
+                    {
+                        // Only the shader workers can have changed
+                        self.publish_state(false).await;
+                    } else if self.updating_games.is_empty() {
                         self.do_full_scan().await;
                         self.last_full_scan = Instant::now();
                     } else {
@@ -291,7 +329,7 @@ impl SteamSensor {
         }
 
         self.updating_games = new_updating;
-        self.publish_state().await;
+        self.publish_state(true).await;
     }
 
     async fn do_targeted_scan(&mut self) {
@@ -321,15 +359,38 @@ impl SteamSensor {
             HashMap::default()
         });
 
-        let changed = still_updating.len() != self.updating_games.len();
         self.updating_games = still_updating;
+        self.publish_state(false).await;
+    }
 
-        if changed {
-            self.publish_state().await;
+    /// Whether Steam is pre-compiling shaders: it runs them through Fossilize's
+    /// `fossilize_replay` workers, on Windows and Linux alike.
+    async fn shader_precaching(&self) -> bool {
+        #[cfg(windows)]
+        {
+            let procs = self.state.process_watcher.state();
+            let procs = procs.read().await;
+            procs.names().iter().any(|name| is_shader_worker(name))
+        }
+        #[cfg(unix)]
+        {
+            tokio::task::spawn_blocking(|| {
+                super::current_process_names()
+                    .iter()
+                    .any(|name| is_shader_worker(name))
+            })
+            .await
+            .unwrap_or(false)
         }
     }
 }
 
+fn is_shader_worker(process_name: &str) -> bool {
+    process_name
+        .get(..16)
+        .is_some_and(|p| p.eq_ignore_ascii_case("fossilize_replay"))
+}
+
 /// Parse an ACF manifest file into a GameUpdateState
 fn parse_acf_file(path: &Path) -> Option<GameUpdateState> {
     let content = match std::fs::read_to_string(path) {
@@ -344,6 +405,8 @@ fn parse_acf_content(content: &str, manifest_path: &Path) -> Option<GameUpdateSt
     let mut app_id = String::new();
     let mut name = String::new();
     let mut state_flags: u32 = 0;
+    let (mut bytes_downloaded, mut bytes_to_download) = (0, 0);
+    let (mut bytes_staged, mut bytes_to_stage) = (0, 0);
 
     for line in content.lines() {
         let trimmed = line.trim();
@@ -360,6 +423,18 @@ fn parse_acf_content(content: &str, manifest_path: &Path) -> Option<GameUpdateSt
             && let Some(val) = extract_vdf_value(trimmed)
         {
             state_flags = val.parse().unwrap_or(0);
+        } else if let Some(field) = [
+            ("\"BytesDownloaded\"", &mut bytes_downloaded),
+            ("\"BytesToDownload\"", &mut bytes_to_download),
+            ("\"BytesStaged\"", &mut bytes_staged),
+            ("\"BytesToStage\"", &mut bytes_to_stage),
+        ]
+        .into_iter()
+        .find_map(|(key, field)| trimmed.starts_with(key).then_some(field))
+        {
+            *field = extract_vdf_value(trimmed)
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
         }
     }
 
@@ -372,6 +447,10 @@ fn parse_acf_content(content: &str, manifest_path: &Path) -> Option<GameUpdateSt
         name,
         state_flags,
         manifest_path: manifest_path.to_path_buf(),
+        bytes_downloaded,
+        bytes_to_download,
+        bytes_staged,
+        bytes_to_stage,
     })
 }
 
@@ -391,37 +470,171 @@ fn is_updating(game: &GameUpdateState) -> bool {
     game.state_flags & STATE_UPDATE_MASK != 0
 }
 
+/// What one updating app is doing: "paused", "downloading", "installing"
+/// (staging/committing the downloaded files) or just "updating" (queued,
+/// validating, ...)
+fn game_activity(game: &GameUpdateState) -> &'static str {
+    if game.state_flags & STATE_UPDATE_PAUSED != 0 {
+        "paused"
+    } else if game.bytes_downloaded < game.bytes_to_download {
+        "downloading"
+    } else if game.bytes_staged < game.bytes_to_stage {
+        "installing"
+    } else {
+        "updating"
+    }
+}
+
+/// What Steam is busy with overall, most telling first; "shader_precaching"
+/// only once no update is left, "idle" when nothing at all
+fn activity<'a>(games: impl Iterator<Item = &'a GameUpdateState>, shaders: bool) -> &'static str {
+    const ORDER: [&str; 4] = ["downloading", "installing", "updating", "paused"];
+    games
+        .map(game_activity)
+        .min_by_key(|a| ORDER.iter().position(|o| o == a))
+        .unwrap_or(if shaders { "shader_precaching" } else { "idle" })
+}
+
+/// Fold the latest manifests into the per-app download rates (`now` is the
+/// scan time). Apps no longer updating are dropped.
+fn update_rates(
+    rates: &mut HashMap<String, DownloadRate>,
+    games: &HashMap<String, GameUpdateState>,
+    now: Instant,
+) {
+    rates.retain(|app_id, _| games.contains_key(app_id));
+    for (app_id, game) in games {
+        let bytes = game.bytes_downloaded;
+        match rates.get_mut(app_id) {
+            Some(r) if bytes > r.bytes => {
+                let secs = now.duration_since(r.at).as_secs_f64();
+                if secs > 0.0 {
+                    r.rate = Some((bytes - r.bytes) as f64 / secs);
+                }
+                r.at = now;
+                r.bytes = bytes;
+            }
+            // Unchanged since the last manifest write: keep the last rate
+            Some(r) if bytes == r.bytes => {}
+            // New app, or a restarted download
+            _ => {
+                rates.insert(
+                    app_id.clone(),
+                    DownloadRate {
+                        at: now,
+                        bytes,
+                        rate: None,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Combined bytes/s of the apps downloading right now, if any is known
+fn download_speed(
+    games: &HashMap<String, GameUpdateState>,
+    rates: &HashMap<String, DownloadRate>,
+) -> Option<f64> {
+    games
+        .iter()
+        .filter(|(_, g)| game_activity(g) == "downloading")
+        .filter_map(|(id, _)| rates.get(id)?.rate)
+        .reduce(|a, b| a + b)
+}
+
+/// The rate in the last `Current download rate: 123.4 Mbps` line of a
+/// content_log.txt tail, in bytes/s
+fn parse_download_rate(log: &str) -> Option<f64> {
+    let line = log
+        .lines()
+        .rev()
+        .find(|l| l.contains("Current download rate:"))?;
+    let (_, rate) = line.split_once("Current download rate:")?;
+    let mbps: f64 = rate.split_whitespace().next()?.parse().ok()?;
+    (mbps.is_finite() && mbps >= 0.0).then_some(mbps * 1_000_000.0 / 8.0)
+}
+
+/// The last 16 KB of a file - content_log.txt grows for the life of Steam.
+fn read_tail(path: &Path) -> Option<String> {
+    const TAIL: u64 = 16 * 1024;
+    let mut file = std::fs::File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL))).ok()?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail).ok()?;
+    Some(String::from_utf8_lossy(&tail).into_owned())
+}
+
+/// The `steam_updating` attributes. `updating_games`/`count` as always, plus
+/// `activity` and the combined progress of the downloading apps. Speed and
+/// ETA are null until a rate is known.
+fn attributes(
+    games: &HashMap<String, GameUpdateState>,
+    activity: &str,
+    speed: Option<f64>,
+) -> serde_json::Value {
+    let names: Vec<&str> = games.values().map(|g| g.name.as_str()).collect();
+    let (downloaded, to_download) = games
+        .values()
+        .filter(|g| game_activity(g) == "downloading")
+        .fold((0u64, 0u64), |(d, t), g| {
+            (d + g.bytes_downloaded, t + g.bytes_to_download)
+        });
+    let progress =
+        (to_download > 0).then(|| (downloaded as f64 * 1000.0 / to_download as f64).round() / 10.0);
+    let speed = speed.filter(|s| *s > 0.0 && to_download > 0);
+    let eta = speed.map(|s| ((to_download - downloaded) as f64 / s).round() as u64);
+    serde_json::json!({
+        "updating_games": names,
+        "count": games.len(),
+        "activity": activity,
+        "bytes_downloaded": downloaded,
+        "bytes_to_download": to_download,
+        "progress": progress,
+        "download_speed": speed.map(|s| s.round() as u64),
+        "eta": eta,
+    })
+}
+
 impl SteamSensor {
-    async fn publish_state(&self) {
-        let is_updating = !self.updating_games.is_empty();
-        let state_str = if is_updating { "on" } else { "off" };
+    /// Publish state + attributes. Unless `force`, only when they changed
+    /// since the last publish (the 5s progress polls).
+    async fn publish_state(&mut self, force: bool) {
+        self.shaders = self.shader_precaching().await;
+        update_rates(&mut self.rates, &self.updating_games, Instant::now());
+        let activity = activity(self.updating_games.values(), self.shaders);
+        let mut speed = download_speed(&self.updating_games, &self.rates);
+        if speed.is_none()
+            && activity == "downloading"
+            && let Some(log) = self.content_log.clone()
+        {
+            speed = tokio::task::spawn_blocking(move || parse_download_rate(&read_tail(&log)?))
+                .await
+                .ok()
+                .flatten();
+        }
+
+        let state_str = if self.updating_games.is_empty() {
+            "off"
+        } else {
+            "on"
+        };
+        let attrs = attributes(&self.updating_games, activity, speed);
+        let published = (state_str, attrs);
+        if !force && self.last_published.as_ref() == Some(&published) {
+            return;
+        }
 
         self.state
             .mqtt
             .publish_sensor_retained("steam_updating", state_str)
             .await;
-
-        // Publish attributes with game names
-        if is_updating {
-            let names: Vec<&str> = self
-                .updating_games
-                .values()
-                .map(|g| g.name.as_str())
-                .collect();
-            let attrs = serde_json::json!({
-                "updating_games": names,
-                "count": self.updating_games.len()
-            });
-            self.state
-                .mqtt
-                .publish_sensor_attributes("steam_updating", &attrs)
-                .await;
-        } else {
-            self.state
-                .mqtt
-                .publish_sensor_attributes("steam_updating", &IDLE_STEAM_ATTRS)
-                .await;
-        }
+        self.state
+            .mqtt
+            .publish_sensor_attributes("steam_updating", &published.1)
+            .await;
+        self.last_published = Some(published);
     }
 }
 
@@ -515,6 +728,7 @@ mod tests {
             name: "Test Game".to_string(),
             state_flags: flags,
             manifest_path: PathBuf::from("/tmp/test.acf"),
+            ..Default::default()
         }
     }
 
@@ -661,19 +875,21 @@ mod tests {
 
     #[test]
     fn test_steam_updating_attributes_json_with_games() {
-        // Exact JSON published to homeassistant/sensor/{device}/steam_updating/attributes
+        // Exact JSON published to pc-bridge/{device}/steam_updating/attributes
         let games = [
             GameUpdateState {
                 app_id: "730".to_string(),
                 name: "Counter-Strike 2".to_string(),
                 state_flags: STATE_UPDATE_RUNNING,
                 manifest_path: PathBuf::from("/tmp/appmanifest_730.acf"),
+                ..Default::default()
             },
             GameUpdateState {
                 app_id: "440".to_string(),
                 name: "Team Fortress 2".to_string(),
                 state_flags: STATE_DOWNLOADING,
                 manifest_path: PathBuf::from("/tmp/appmanifest_440.acf"),
+                ..Default::default()
             },
         ];
         let names: Vec<&str> = games.iter().map(|g| g.name.as_str()).collect();
@@ -725,6 +941,7 @@ mod tests {
                     name: "HELLDIVERS 2".to_string(),
                     state_flags: STATE_DOWNLOADING,
                     manifest_path: PathBuf::from("/tmp/appmanifest_553850.acf"),
+                    ..Default::default()
                 },
             );
             m
@@ -732,7 +949,7 @@ mod tests {
 
         let is_updating = !updating_games.is_empty();
         let state_str = if is_updating { "on" } else { "off" };
-        // This exact string goes to: homeassistant/sensor/{device}/steam_updating/state
+        // This exact string goes to: pc-bridge/{device}/steam_updating/state
         assert_eq!(state_str, "on");
 
         // 2. Attributes topic payload (retained)
@@ -741,8 +958,146 @@ mod tests {
             "updating_games": names,
             "count": updating_games.len()
         });
-        // This exact JSON goes to: homeassistant/sensor/{device}/steam_updating/attributes
+        // This exact JSON goes to: pc-bridge/{device}/steam_updating/attributes
         assert_eq!(attrs["count"], 1);
         assert_eq!(attrs["updating_games"][0], "HELLDIVERS 2");
     }
+
+    // ===== Download progress, speed and activity =====
+
+    fn downloading(app_id: &str, downloaded: u64, to_download: u64) -> GameUpdateState {
+        GameUpdateState {
+            app_id: app_id.to_string(),
+            name: format!("Game {app_id}"),
+            state_flags: STATE_DOWNLOADING,
+            bytes_downloaded: downloaded,
+            bytes_to_download: to_download,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_acf_content_download_progress() {
+        let content = r#"
+"AppState"
+{
+	"appid"		"730"
+	"name"		"Counter-Strike 2"
+	"StateFlags"		"1026"
+	"BytesToDownload"		"2000000"
+	"BytesDownloaded"		"500000"
+	"BytesToStage"		"3000000"
+	"BytesStaged"		"not a number"
+}"#;
+        let game = parse_acf_content(content, Path::new("/tmp/appmanifest_730.acf")).unwrap();
+        assert_eq!(game.bytes_downloaded, 500_000);
+        assert_eq!(game.bytes_to_download, 2_000_000);
+        assert_eq!(game.bytes_to_stage, 3_000_000);
+        assert_eq!(game.bytes_staged, 0);
+        assert_eq!(game_activity(&game), "downloading");
+    }
+
+    #[test]
+    fn test_activity() {
+        let paused = GameUpdateState {
+            state_flags: STATE_UPDATE_PAUSED,
+            ..downloading("1", 5, 10)
+        };
+        let installing = GameUpdateState {
+            bytes_staged: 5,
+            bytes_to_stage: 10,
+            ..downloading("2", 10, 10)
+        };
+        let queued = make_game(0x2);
+        assert_eq!(game_activity(&paused), "paused");
+        assert_eq!(game_activity(&installing), "installing");
+        assert_eq!(game_activity(&queued), "updating");
+
+        let all = [paused.clone(), installing.clone(), downloading("3", 1, 10)];
+        assert_eq!(activity(all.iter(), true), "downloading");
+        assert_eq!(
+            activity([&paused, &installing].into_iter(), false),
+            "installing"
+        );
+        assert_eq!(activity([&paused].into_iter(), true), "paused");
+        assert_eq!(activity(std::iter::empty(), true), "shader_precaching");
+        assert_eq!(activity(std::iter::empty(), false), "idle");
+    }
+
+    #[test]
+    fn test_update_rates() {
+        let start = Instant::now();
+        let mut rates = HashMap::new();
+        let mut games = HashMap::from([("730".to_string(), downloading("730", 0, 1000))]);
+        update_rates(&mut rates, &games, start);
+        assert!(rates["730"].rate.is_none());
+
+        games.insert("730".to_string(), downloading("730", 400, 1000));
+        update_rates(&mut rates, &games, start + Duration::from_secs(4));
+        assert_eq!(rates["730"].rate, Some(100.0));
+        // No manifest write in between: the last rate stands.
+        update_rates(&mut rates, &games, start + Duration::from_secs(9));
+        assert_eq!(download_speed(&games, &rates), Some(100.0));
+
+        // A restarted download starts over.
+        games.insert("730".to_string(), downloading("730", 10, 5000));
+        update_rates(&mut rates, &games, start + Duration::from_secs(10));
+        assert!(download_speed(&games, &rates).is_none());
+
+        update_rates(&mut rates, &HashMap::new(), start + Duration::from_secs(11));
+        assert!(rates.is_empty());
+    }
+
+    #[test]
+    fn test_parse_download_rate() {
+        let log = "[2026-10-15 14:00:00] Current download rate: 80.000 Mbps\n\
+                   [2026-10-15 14:00:05] AppID 730 state changed : Update Running,\n\
+                   [2026-10-15 14:00:10] Current download rate: 240.000 Mbps\n\
+                   [2026-10-15 14:00:11] AppID 730 scheduler update : download 5/10";
+        assert_eq!(parse_download_rate(log), Some(30_000_000.0));
+        assert!(parse_download_rate("[x] AppID 730 update started").is_none());
+        assert!(parse_download_rate("Current download rate: fast").is_none());
+    }
+
+    #[test]
+    fn test_is_shader_worker() {
+        assert!(is_shader_worker("fossilize_replay.exe"));
+        assert!(is_shader_worker("fossilize_replay"));
+        assert!(!is_shader_worker("fossilize"));
+        assert!(!is_shader_worker("steam.exe"));
+    }
+
+    #[test]
+    fn test_steam_updating_attributes_progress() {
+        let games = HashMap::from([
+            ("1".to_string(), downloading("1", 250, 1000)),
+            ("2".to_string(), downloading("2", 0, 1000)),
+        ]);
+        let attrs = attributes(&games, "downloading", Some(350.0));
+        assert_eq!(attrs["count"], 2);
+        assert_eq!(attrs["activity"], "downloading");
+        assert_eq!(attrs["bytes_downloaded"], 250);
+        assert_eq!(attrs["bytes_to_download"], 2000);
+        assert_eq!(attrs["progress"], 12.5);
+        assert_eq!(attrs["download_speed"], 350);
+        assert_eq!(attrs["eta"], 5); // 1750 bytes left at 350/s
+
+        // No rate yet, or nothing downloading: unknown rather than 0.
+        let attrs = attributes(&games, "downloading", None);
+        assert!(attrs["download_speed"].is_null() && attrs["eta"].is_null());
+        let idle = attributes(&HashMap::new(), "shader_precaching", Some(1.0));
+        assert_eq!(
+            idle,
+            serde_json::json!({
+                "updating_games": [],
+                "count": 0,
+                "activity": "shader_precaching",
+                "bytes_downloaded": 0,
+                "bytes_to_download": 0,
+                "progress": null,
+                "download_speed": null,
+                "eta": null,
+            })
+        );
+    }
 }
@@ -1,44 +1,32 @@
 //! System sensors - CPU, memory, battery, active window
 //!
 //! - CPU/memory: polled (inherently sampled metrics)
-//! - Battery: event-driven via RegisterPowerSettingNotification (instant on plug/unplug/level change)
-//! - Active window: event-driven via SetWinEventHook(EVENT_SYSTEM_FOREGROUND) (instant on focus change)
+//! - Battery: event-driven via RegisterPowerSettingNotification (instant on plug/unplug/level change),
+//!   with time remaining, health and power draw as `battery_level` attributes
+//! - Active window: event-driven via SetWinEventHook(EVENT_SYSTEM_FOREGROUND) (instant on focus change),
+//!   plus its recent history (`window_history`)
 
 #[cfg(windows)]
 use log::error;
 use log::{debug, info};
+use serde::Serialize;
 use std::sync::Arc;
+use std::time::Instant;
+use time::OffsetDateTime;
 use tokio::sync::mpsc;
-use tokio::time::{Duration, MissedTickBehavior, interval};
+use tokio::time::Duration;
 
+use super::history::History;
+use super::runner::{Published, Reading};
+use super::window_history::WindowHistory;
 use crate::AppState;
+use crate::reporting::Reported;
 
 /// System sensor that reports CPU, memory, battery, and active window
 pub struct SystemSensor {
     state: Arc<AppState>,
 }
 
-/// Tracks previous sensor values to skip duplicate MQTT publishes
-struct PrevSystemValues {
-    cpu: String,
-    mem: String,
-    battery_level: String,
-    battery_charging: String,
-    health_uptime: u64,
-}
-
-impl PrevSystemValues {
-    fn new() -> Self {
-        Self {
-            cpu: String::new(),
-            mem: String::new(),
-            battery_level: String::new(),
-            battery_charging: String::new(),
-            health_uptime: 0,
-        }
-    }
-}
-
 /// Events from background threads monitoring window focus and battery state
 enum SystemEvent {
     /// Foreground window changed
@@ -55,26 +43,32 @@ impl SystemSensor {
     pub async fn run(self, shutdown: tokio::sync::broadcast::Sender<()>) {
         // cpu and memory are independently polled + gated (they used to share one
         // timer/interval). active_window/battery are event-driven (below).
-        let (cpu_secs, mem_secs, mut cpu_on, mut mem_on) = {
+        // Both slow to `idle_poll` while the user is away and stop while perf
+        // mode pauses them (crate::activity).
+        let mut published = Published::default();
+        let (mut cpu_secs, mut mem_secs, mut idle_secs, mut cpu_on, mut mem_on) = {
             let config = self.state.config.read().await;
+            published.configure(&config);
             (
                 config.intervals.cpu.max(1),
                 config.intervals.memory.max(1),
+                config.intervals.idle_poll,
                 config.features.cpu_sensor,
                 config.features.memory_sensor,
             )
         };
 
-        let mut cpu_tick = interval(Duration::from_secs(cpu_secs));
-        let mut mem_tick = interval(Duration::from_secs(mem_secs));
-        cpu_tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
-        mem_tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut activity_rx = crate::activity::subscribe();
+        let mut activity = *activity_rx.borrow_and_update();
+        let mut cpu_tick = crate::activity::ticker(cpu_secs, idle_secs, activity);
+        let mut mem_tick = crate::activity::ticker(mem_secs, idle_secs, activity);
         let mut shutdown_rx = shutdown.subscribe();
         let mut config_rx = self.state.config_generation.subscribe();
 
         // CPU calculation needs previous sample
         let mut prev_cpu = get_cpu_times();
-        let mut prev_vals = PrevSystemValues::new();
+        let mut cpu_history = History::default();
+        let mut mem_history = History::default();
 
         // Channel for receiving events from background threads
         let (event_tx, mut event_rx) = mpsc::channel::<SystemEvent>(8);
@@ -112,8 +106,14 @@ impl SystemSensor {
         // Suppress unused warning when neither platform uses event_tx further
         drop(event_tx);
 
-        // Initial publish (force all by using empty prev_vals)
-        self.publish_all(&mut prev_cpu, &mut prev_vals).await;
+        // Initial publish (everything, as nothing was published yet)
+        self.publish_all(
+            &mut prev_cpu,
+            &mut cpu_history,
+            &mut mem_history,
+            &mut published,
+        )
+        .await;
 
         // Track health publish separately (once per ~60s)
         let mut last_health_publish = tokio::time::Instant::now();
@@ -148,20 +148,39 @@ impl SystemSensor {
                         continue;
                     }
                     let config = self.state.config.read().await;
-                    let c = config.intervals.cpu.max(1);
-                    let m = config.intervals.memory.max(1);
+                    cpu_secs = config.intervals.cpu.max(1);
+                    mem_secs = config.intervals.memory.max(1);
+                    idle_secs = config.intervals.idle_poll;
                     cpu_on = config.features.cpu_sensor;
                     mem_on = config.features.memory_sensor;
+                    published.configure(&config);
                     drop(config);
-                    cpu_tick = interval(Duration::from_secs(c));
-                    mem_tick = interval(Duration::from_secs(m));
-                    cpu_tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
-                    mem_tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
-                    debug!("System sensor: cpu={c}s memory={m}s");
+                    cpu_tick = crate::activity::ticker(cpu_secs, idle_secs, activity);
+                    mem_tick = crate::activity::ticker(mem_secs, idle_secs, activity);
+                    debug!("System sensor: cpu={cpu_secs}s memory={mem_secs}s");
+                }
+                // Away, paused or back: swap in the matching rates. The new
+                // intervals tick at once, so coming back refreshes both
+                // immediately.
+                Ok(()) = activity_rx.changed() => {
+                    let was_paused = activity.is_paused();
+                    activity = *activity_rx.borrow_and_update();
+                    cpu_tick = crate::activity::ticker(cpu_secs, idle_secs, activity);
+                    mem_tick = crate::activity::ticker(mem_secs, idle_secs, activity);
+                    if was_paused && !activity.is_paused() {
+                        // A CPU delta spanning the whole pause would report the
+                        // game's load: restart it and sample one period out.
+                        prev_cpu = get_cpu_times();
+                        cpu_tick.reset();
+                    }
+                    debug!("System sensor: {activity:?} polling");
                 }
                 _ = cpu_tick.tick() => {
+                    if activity.is_paused() {
+                        continue;
+                    }
                     if cpu_on {
-                        self.publish_cpu(&mut prev_cpu, &mut prev_vals).await;
+                        self.publish_cpu(&mut prev_cpu, &mut cpu_history, &mut published).await;
                     } else {
                         // Keep prev_cpu fresh so re-enabling doesn't compute a CPU
                         // delta spanning the whole disabled gap.
@@ -170,114 +189,128 @@ impl SystemSensor {
                     // Bridge health diagnostics (~every 60s), driven off this tick.
                     if last_health_publish.elapsed() >= Duration::from_mins(1) {
                         last_health_publish = tokio::time::Instant::now();
-                        self.publish_health(&mut prev_vals).await;
+                        self.publish_health(&mut published).await;
                     }
                 }
                 _ = mem_tick.tick() => {
-                    if mem_on {
-                        self.publish_memory(&mut prev_vals).await;
+                    if mem_on && !activity.is_paused() {
+                        self.publish_memory(&mut mem_history, &mut published).await;
                     }
                 }
                 Some(event) = event_rx.recv() => {
                     match event {
                         // active_window is handled by ActiveWindowSensor now.
                         SystemEvent::WindowFocusChanged => {}
-                        SystemEvent::BatteryChanged => {
-                            if let Some((percent, charging)) = get_battery_status() {
-                                let level_str = percent.to_string();
-                                let charging_str = if charging { "true" } else { "false" };
-                                if level_str != prev_vals.battery_level {
-                                    self.state.mqtt.publish_sensor("battery_level", &level_str).await;
-                                    prev_vals.battery_level = level_str;
-                                }
-                                if charging_str != prev_vals.battery_charging {
-                                    self.state.mqtt.publish_sensor("battery_charging", charging_str).await;
-                                    prev_vals.battery_charging = charging_str.to_string();
-                                }
-                            }
-                        }
+                        SystemEvent::BatteryChanged => self.publish_battery(&mut published).await,
                     }
                 }
             }
         }
     }
 
-    async fn publish_cpu(&self, prev_cpu: &mut CpuTimes, prev: &mut PrevSystemValues) {
-        let cpu = calculate_cpu_usage(prev_cpu);
-        let cpu_str = format!("{cpu:.1}");
-        if cpu_str != prev.cpu {
-            self.state.mqtt.publish_sensor("cpu_usage", &cpu_str).await;
-            prev.cpu = cpu_str;
-        }
+    async fn publish_cpu(
+        &self,
+        prev_cpu: &mut CpuTimes,
+        history: &mut History,
+        published: &mut Published,
+    ) {
+        let cpu = format!("{:.1}", calculate_cpu_usage(prev_cpu));
+        published
+            .publish(&self.state, with_history(history, "cpu_usage", cpu))
+            .await;
     }
 
-    async fn publish_memory(&self, prev: &mut PrevSystemValues) {
+    async fn publish_memory(&self, history: &mut History, published: &mut Published) {
         // Memory usage (percentage); unavailable on a read/parse failure rather
         // than a misleading 0% or 100%.
         let mem_str = match get_memory_percent() {
             Some(m) => format!("{m:.1}"),
             None => "unavailable".to_string(),
         };
-        if mem_str != prev.mem {
-            self.state
-                .mqtt
-                .publish_sensor("memory_usage", &mem_str)
-                .await;
-            prev.mem = mem_str;
-        }
+        published
+            .publish(&self.state, with_history(history, "memory_usage", mem_str))
+            .await;
     }
 
-    async fn publish_all(&self, prev_cpu: &mut CpuTimes, prev: &mut PrevSystemValues) {
+    async fn publish_all(
+        &self,
+        prev_cpu: &mut CpuTimes,
+        cpu_history: &mut History,
+        mem_history: &mut History,
+        published: &mut Published,
+    ) {
         // CPU and memory (polled metrics)
-        self.publish_cpu(prev_cpu, prev).await;
-        self.publish_memory(prev).await;
+        self.publish_cpu(prev_cpu, cpu_history, published).await;
+        self.publish_memory(mem_history, published).await;
 
         // Bridge health (initial publish)
-        self.publish_health(prev).await;
+        self.publish_health(published).await;
 
         // Battery (event-driven, but publish initial state)
-        if let Some((percent, charging)) = get_battery_status() {
-            let level_str = percent.to_string();
-            let charging_str = if charging { "true" } else { "false" };
-            if level_str != prev.battery_level {
-                self.state
-                    .mqtt
-                    .publish_sensor("battery_level", &level_str)
-                    .await;
-                prev.battery_level = level_str;
-            }
-            if charging_str != prev.battery_charging {
-                self.state
-                    .mqtt
-                    .publish_sensor("battery_charging", charging_str)
-                    .await;
-                prev.battery_charging = charging_str.to_string();
-            }
-        }
+        self.publish_battery(published).await;
         // active_window's initial publish now lives in ActiveWindowSensor.
     }
 
+    async fn publish_battery(&self, published: &mut Published) {
+        let Some((percent, charging)) = get_battery_status() else {
+            return;
+        };
+        // The WMI queries behind these take tens of ms; keep them off the runtime.
+        let details = tokio::task::spawn_blocking(get_battery_details)
+            .await
+            .unwrap_or_default();
+        let attrs = serde_json::to_value(&details).unwrap_or_default();
+        published
+            .publish_all(
+                &self.state,
+                vec![
+                    Reading::new("battery_level", percent.to_string()).with_attributes(attrs),
+                    Reading::new("battery_charging", if charging { "true" } else { "false" }),
+                ],
+            )
+            .await;
+    }
+
     /// Publish bridge health diagnostics (uptime, version)
-    async fn publish_health(&self, prev: &mut PrevSystemValues) {
+    async fn publish_health(&self, published: &mut Published) {
         let uptime_secs = self.state.start_time.elapsed().as_secs();
-        // Only publish when uptime actually changed (avoids duplicate on rapid calls)
-        if uptime_secs != prev.health_uptime {
-            self.state
-                .mqtt
-                .publish_sensor("bridge_health", &uptime_secs.to_string())
-                .await;
-            let attrs = serde_json::json!({
-                "version": env!("CARGO_PKG_VERSION"),
-            });
-            self.state
-                .mqtt
-                .publish_sensor_attributes("bridge_health", &attrs)
-                .await;
-            prev.health_uptime = uptime_secs;
+        let mut attrs = serde_json::json!({
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+        if let Some(crash) = crate::crash::last() {
+            attrs["last_crash"] = crash.summary();
         }
+        // Null until a heartbeat comes back, or when the last one didn't.
+        attrs["broker_rtt_ms"] = self
+            .state
+            .mqtt
+            .broker_rtt()
+            .map(|rtt| (rtt.as_secs_f64() * 10_000.0).round() / 10.0)
+            .into();
+        #[cfg(windows)]
+        if let Some(mode) = self.state.process_watcher.mode() {
+            attrs["process_watcher"] = mode.into();
+            attrs["process_watcher_resubscribes"] =
+                self.state.process_watcher.resubscribes().into();
+        }
+        published
+            .publish(
+                &self.state,
+                Reading::new("bridge_health", uptime_secs.to_string()).with_attributes(attrs),
+            )
+            .await;
     }
 }
 
+/// A percentage reading with its `history` (updated with it) as attributes
+pub(super) fn with_history(history: &mut History, name: &'static str, value: String) -> Reading {
+    let now = tokio::time::Instant::now();
+    history.record(now, &value);
+    Reading::new(name, value)
+        .with_attributes(history.attributes(now))
+        .attributes_with_value()
+}
+
 /// Active window sensor. Split out of SystemSensor into its own supervised task so
 /// enabling/disabling it at runtime starts/stops its focus-hook thread directly,
 /// instead of leaving the thread running while SystemSensor stays up for cpu/memory.
@@ -307,13 +340,31 @@ impl ActiveWindowSensor {
 
         info!("Active window sensor started (event-driven)");
 
+        let mut config_rx = self.state.config_generation.subscribe();
+        let (mut history_on, size, mut history_period) =
+            history_settings(&*self.state.config.read().await);
+        let mut history = WindowHistory::new(size);
+        let mut history_tick = history_ticker(history_period);
+        let mut published = Published::default();
+
         // Publish the current title once at startup (the monitor only fires on
         // change, so without this the sensor would read empty until the first switch).
         #[cfg(windows)]
         let mut prev = get_active_window_title();
         #[cfg(unix)]
         let mut prev = get_active_window_title_async().await;
-        self.state.mqtt.publish_sensor("active_window", &prev).await;
+        // The report_active_window switch; while off the title reads "paused".
+        let mut reporting_rx = crate::reporting::subscribe();
+        let mut paused = crate::reporting::is_paused(Reported::ActiveWindow);
+        let shown = if paused {
+            crate::reporting::PAUSED
+        } else {
+            prev.as_str()
+        };
+        self.state.mqtt.publish_sensor("active_window", shown).await;
+        if !paused {
+            history.focus(&prev, OffsetDateTime::now_utc(), Instant::now());
+        }
 
         loop {
             tokio::select! {
@@ -340,16 +391,57 @@ impl ActiveWindowSensor {
                         #[cfg(unix)]
                         let title = get_active_window_title_async().await;
                         if title != prev {
-                            self.state.mqtt.publish_sensor("active_window", &title).await;
+                            if !paused {
+                                self.state.mqtt.publish_sensor("active_window", &title).await;
+                                history.focus(&title, OffsetDateTime::now_utc(), Instant::now());
+                            }
                             prev = title;
                         }
                     }
                 }
+                Ok(()) = reporting_rx.changed() => {
+                    let now_paused = reporting_rx.borrow_and_update().contains(&Reported::ActiveWindow);
+                    if now_paused != paused {
+                        paused = now_paused;
+                        let shown = if paused { crate::reporting::PAUSED } else { prev.as_str() };
+                        self.state.mqtt.publish_sensor("active_window", shown).await;
+                        // Paused time isn't recorded: the window before ends here.
+                        let focused = if paused { "" } else { prev.as_str() };
+                        history.focus(focused, OffsetDateTime::now_utc(), Instant::now());
+                    }
+                }
+                Ok(()) = config_rx.recv() => {
+                    let (on, size, period) = history_settings(&*self.state.config.read().await);
+                    history_on = on;
+                    history.resize(size);
+                    if period != history_period {
+                        history_period = period;
+                        history_tick = history_ticker(period);
+                    }
+                }
+                _ = history_tick.tick(), if history_on && !paused => {
+                    published.publish(&self.state, history.reading(Instant::now())).await;
+                }
             }
         }
     }
 }
 
+/// `window_history` on, its size, and its publish period
+fn history_settings(config: &crate::config::Config) -> (bool, usize, Duration) {
+    (
+        config.features.window_history,
+        config.window_history_size,
+        Duration::from_secs(config.intervals.window_history.max(10)),
+    )
+}
+
+fn history_ticker(period: Duration) -> tokio::time::Interval {
+    let mut tick = tokio::time::interval(period);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    tick
+}
+
 // ============================================================================
 // Active Window Monitor - Event-driven via SetWinEventHook
 // ============================================================================
@@ -908,6 +1000,170 @@ fn get_battery_status() -> Option<(u8, bool)> {
     None
 }
 
+/// Extra battery readings, published as `battery_level` attributes. Each is
+/// omitted when the platform/driver doesn't report it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+struct BatteryDetails {
+    /// Estimated seconds until empty (discharging) or full (charging, Linux)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_remaining_secs: Option<u64>,
+    /// Full-charge capacity as a percentage of design capacity (100 = new)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health_pct: Option<f64>,
+    /// Current charge or discharge rate in watts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    power_w: Option<f64>,
+}
+
+/// Health as a 0.1-rounded percentage. Capped at 100: new cells often exceed
+/// their design capacity slightly, which would read as "better than new".
+fn battery_health_pct(full: u64, design: u64) -> Option<f64> {
+    if full == 0 || design == 0 {
+        return None;
+    }
+    let pct = (full as f64 / design as f64 * 100.0).min(100.0);
+    Some((pct * 10.0).round() / 10.0)
+}
+
+/// Milli-units (mW) to watts, rounded to 0.1; 0 means "not reported".
+fn milli_to_watts(milli: u64) -> Option<f64> {
+    (milli > 0).then(|| (milli as f64 / 100.0).round() / 10.0)
+}
+
+#[cfg(windows)]
+fn get_battery_details() -> BatteryDetails {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    // BatteryLifeTime is only known on battery; u32::MAX = unknown.
+    let time_remaining_secs = unsafe {
+        let mut status = SYSTEM_POWER_STATUS::default();
+        GetSystemPowerStatus(&raw mut status)
+            .ok()
+            .filter(|()| status.BatteryLifeTime != u32::MAX)
+            .map(|()| u64::from(status.BatteryLifeTime))
+    };
+    let (health_pct, power_w) = battery_wmi::query().unwrap_or_else(|e| {
+        debug!("Battery WMI query failed: {e}");
+        (None, None)
+    });
+    BatteryDetails {
+        time_remaining_secs,
+        health_pct,
+        power_w,
+    }
+}
+
+/// Capacity and rate come from the battery miniport's root\WMI classes;
+/// SYSTEM_POWER_STATUS has neither. First battery only, like the level.
+#[cfg(windows)]
+mod battery_wmi {
+    use serde::Deserialize;
+    use wmi::{COMLibrary, WMIConnection};
+
+    #[derive(Deserialize)]
+    #[serde(rename = "BatteryFullChargedCapacity", rename_all = "PascalCase")]
+    struct FullChargedCapacity {
+        full_charged_capacity: u32,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "BatteryStaticData", rename_all = "PascalCase")]
+    struct StaticData {
+        designed_capacity: u32,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "BatteryStatus", rename_all = "PascalCase")]
+    struct Status {
+        charge_rate: i32,
+        discharge_rate: i32,
+    }
+
+    pub(super) fn query() -> Result<(Option<f64>, Option<f64>), wmi::WMIError> {
+        let wmi = WMIConnection::with_namespace_path("ROOT\\WMI", COMLibrary::new()?)?;
+        let full = wmi.query::<FullChargedCapacity>()?;
+        let design = wmi.query::<StaticData>()?;
+        let health = full.first().zip(design.first()).and_then(|(f, d)| {
+            super::battery_health_pct(
+                u64::from(f.full_charged_capacity),
+                u64::from(d.designed_capacity),
+            )
+        });
+        // Rates are mW; whichever direction is active is non-zero.
+        let power = wmi.query::<Status>()?.first().and_then(|s| {
+            let rate = s.discharge_rate.max(s.charge_rate).max(0);
+            super::milli_to_watts(rate.unsigned_abs().into())
+        });
+        Ok((health, power))
+    }
+}
+
+#[cfg(unix)]
+fn get_battery_details() -> BatteryDetails {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+        return BatteryDetails::default();
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with("BAT") || name.starts_with("CMB") {
+            let path = entry.path();
+            return battery_details_from_sysfs(|attr| {
+                std::fs::read_to_string(path.join(attr)).ok()
+            });
+        }
+    }
+    BatteryDetails::default()
+}
+
+/// Drivers report either energy_* (µWh) with power_now (µW), or charge_*
+/// (µAh) with current_now (µA); time remaining works with either pair.
+#[cfg(unix)]
+fn battery_details_from_sysfs(read: impl Fn(&str) -> Option<String>) -> BatteryDetails {
+    let num = |attr: &str| read(attr).and_then(|s| s.trim().parse::<u64>().ok());
+    let status = read("status").unwrap_or_default();
+    let status = status.trim();
+
+    let (now, full, design, rate) = if num("energy_full").is_some() {
+        (
+            num("energy_now"),
+            num("energy_full"),
+            num("energy_full_design"),
+            num("power_now"),
+        )
+    } else {
+        (
+            num("charge_now"),
+            num("charge_full"),
+            num("charge_full_design"),
+            num("current_now"),
+        )
+    };
+    let rate = rate.filter(|r| *r > 0);
+    let time_remaining_secs = match (now, full, rate) {
+        (Some(now), _, Some(rate)) if status.eq_ignore_ascii_case("Discharging") => {
+            Some(now * 3600 / rate)
+        }
+        (Some(now), Some(full), Some(rate)) if status.eq_ignore_ascii_case("Charging") => {
+            Some(full.saturating_sub(now) * 3600 / rate)
+        }
+        _ => None,
+    };
+    // power_now is µW; otherwise derive from current (µA) x voltage (µV).
+    let power_uw = num("power_now").or_else(|| {
+        let amps = num("current_now")?;
+        let volts = num("voltage_now")?;
+        Some(amps * volts / 1_000_000)
+    });
+    BatteryDetails {
+        time_remaining_secs,
+        health_pct: full
+            .zip(design)
+            .and_then(|(full, design)| battery_health_pct(full, design)),
+        power_w: power_uw.and_then(|uw| milli_to_watts(uw / 1000)),
+    }
+}
+
 // ============================================================================
 // Active Window Title - Native via GetForegroundWindow
 // ============================================================================
@@ -1172,6 +1428,67 @@ mod tests {
         assert!(out.ends_with('\u{2026}'));
     }
 
+    #[test]
+    fn test_battery_health_pct() {
+        assert_eq!(battery_health_pct(42_000, 50_000), Some(84.0));
+        assert_eq!(battery_health_pct(45_123, 50_000), Some(90.2));
+        // Fresh cells can exceed design capacity.
+        assert_eq!(battery_health_pct(51_000, 50_000), Some(100.0));
+        assert_eq!(battery_health_pct(0, 50_000), None);
+        assert_eq!(battery_health_pct(42_000, 0), None);
+        assert_eq!(milli_to_watts(15_360), Some(15.4));
+        assert_eq!(milli_to_watts(0), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_battery_details_from_sysfs() {
+        let energy = |status: &'static str| {
+            move |attr: &str| {
+                match attr {
+                    "status" => Some(status),
+                    "energy_now" => Some("30000000"),
+                    "energy_full" => Some("45000000"),
+                    "energy_full_design" => Some("50000000"),
+                    "power_now" => Some("15000000\n"),
+                    _ => None,
+                }
+                .map(String::from)
+            }
+        };
+        let details = battery_details_from_sysfs(energy("Discharging"));
+        assert_eq!(details.time_remaining_secs, Some(7200));
+        assert_eq!(details.health_pct, Some(90.0));
+        assert_eq!(details.power_w, Some(15.0));
+        // Charging: time until full.
+        let details = battery_details_from_sysfs(energy("Charging"));
+        assert_eq!(details.time_remaining_secs, Some(3600));
+        assert_eq!(
+            battery_details_from_sysfs(energy("Full")).time_remaining_secs,
+            None
+        );
+
+        // charge_* driver: power from current x voltage.
+        let details = battery_details_from_sysfs(|attr| {
+            match attr {
+                "status" => Some("Discharging"),
+                "charge_now" => Some("2000000"),
+                "charge_full" => Some("4000000"),
+                "charge_full_design" => Some("5000000"),
+                "current_now" => Some("1000000"),
+                "voltage_now" => Some("12000000"),
+                _ => None,
+            }
+            .map(String::from)
+        });
+        assert_eq!(details.time_remaining_secs, Some(7200));
+        assert_eq!(details.health_pct, Some(80.0));
+        assert_eq!(details.power_w, Some(12.0));
+
+        let empty = serde_json::to_value(battery_details_from_sysfs(|_| None)).unwrap();
+        assert_eq!(empty, serde_json::json!({}));
+    }
+
     #[cfg(unix)]
     #[test]
     fn test_parse_meminfo_value_kb_unit() {
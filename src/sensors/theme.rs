@@ -0,0 +1,254 @@
+//! Light / dark theme select
+//!
+//! Publishes "light" or "dark" as the state of the `theme` select, polled every
+//! `intervals.theme`. Switching is the `Theme` command, which the select sends
+//! with the chosen option; `toggle` flips it.
+//! - Windows: `AppsUseLightTheme` / `SystemUsesLightTheme` under
+//!   `HKCU\...\Themes\Personalize` (the state is the app theme; both are set,
+//!   and `apps` / `system` attributes carry each)
+//! - Linux: KDE Plasma's color scheme (`plasma-apply-colorscheme`, Breeze
+//!   Light / Dark), else GNOME's `color-scheme` (GNOME, Cinnamon, Budgie); the
+//!   `scheme` attribute is the raw value
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use log::{info, warn};
+
+use tokio::sync::Notify;
+
+use super::runner::{Reading, Sensor};
+use crate::AppState;
+use crate::config::Config;
+
+/// Select options
+pub(crate) const OPTIONS: &[&str] = &["light", "dark"];
+
+/// Poked after a switch so the select catches up right away.
+static REFRESH: Notify = Notify::const_new();
+
+#[derive(Debug, Clone, PartialEq)]
+struct Theme {
+    dark: bool,
+    attributes: serde_json::Value,
+}
+
+pub struct ThemeSensor {
+    state: Arc<AppState>,
+}
+
+impl ThemeSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        if !self.state.config.read().await.features.theme {
+            return;
+        }
+        super::runner::run(Arc::clone(&self.state), self).await;
+    }
+}
+
+fn reading(theme: Option<Theme>) -> Reading {
+    match theme {
+        Some(theme) => Reading::new("theme", if theme.dark { "dark" } else { "light" })
+            .retained()
+            .with_attributes(theme.attributes),
+        None => Reading::new("theme", "unavailable").retained(),
+    }
+}
+
+impl Sensor for ThemeSensor {
+    const NAME: &'static str = "Theme";
+
+    fn interval(config: &Config) -> Option<Duration> {
+        Some(Duration::from_secs(config.intervals.theme.max(1)))
+    }
+
+    async fn event(&mut self) -> Option<()> {
+        REFRESH.notified().await;
+        Some(())
+    }
+
+    async fn poll(&mut self) -> Vec<Reading> {
+        vec![reading(
+            tokio::task::spawn_blocking(current).await.ok().flatten(),
+        )]
+    }
+}
+
+/// The `Theme` command: `light`, `dark` or `toggle`.
+pub(crate) async fn set_theme(payload: &str) {
+    let payload = payload.trim().to_ascii_lowercase();
+    let result = tokio::task::spawn_blocking(move || {
+        let dark = match payload.as_str() {
+            "dark" => true,
+            "light" => false,
+            "toggle" | "press" | "" => current().is_none_or(|theme| !theme.dark),
+            other => bail!("unknown theme '{other}' (light, dark or toggle)"),
+        };
+        apply(dark).map(|()| dark)
+    })
+    .await;
+    match result {
+        Ok(Ok(dark)) => info!("Theme set to {}", if dark { "dark" } else { "light" }),
+        Ok(Err(e)) => warn!("Theme: {e:#}"),
+        Err(e) => warn!("Theme task failed: {e}"),
+    }
+    REFRESH.notify_one();
+}
+
+#[cfg(windows)]
+const PERSONALIZE: &str = r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+
+#[cfg(windows)]
+fn current() -> Option<Theme> {
+    use winreg::RegKey;
+    use winreg::enums::HKEY_CURRENT_USER;
+
+    let key = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(PERSONALIZE)
+        .ok()?;
+    // Absent values mean light, Windows' default.
+    let light = |name: &str| key.get_value::<u32, _>(name).ok() != Some(0);
+    let (apps, system) = (light("AppsUseLightTheme"), light("SystemUsesLightTheme"));
+    let name = |light: bool| if light { "light" } else { "dark" };
+    Some(Theme {
+        dark: !apps,
+        attributes: serde_json::json!({ "apps": name(apps), "system": name(system) }),
+    })
+}
+
+/// Set the app and system theme, then tell open windows so the taskbar and
+/// apps repaint without a sign-out.
+#[cfg(windows)]
+fn apply(dark: bool) -> Result<()> {
+    use anyhow::Context;
+    use windows::Win32::Foundation::{LPARAM, WPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        HWND_BROADCAST, SMTO_ABORTIFHUNG, SendMessageTimeoutW, WM_SETTINGCHANGE,
+    };
+    use winreg::RegKey;
+    use winreg::enums::HKEY_CURRENT_USER;
+
+    let (key, _) = RegKey::predef(HKEY_CURRENT_USER)
+        .create_subkey(PERSONALIZE)
+        .context("can't open the theme settings")?;
+    let light = u32::from(!dark);
+    for name in ["AppsUseLightTheme", "SystemUsesLightTheme"] {
+        key.set_value(name, &light)
+            .with_context(|| format!("can't set {name}"))?;
+    }
+    let area = windows::core::w!("ImmersiveColorSet");
+    // Timeout-bounded broadcast: a hung window must not park the caller.
+    unsafe {
+        SendMessageTimeoutW(
+            HWND_BROADCAST,
+            WM_SETTINGCHANGE,
+            WPARAM(0),
+            LPARAM(area.as_ptr() as isize),
+            SMTO_ABORTIFHUNG,
+            2000,
+            None,
+        );
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_kde() -> bool {
+    std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+        .contains("kde")
+}
+
+/// Output of a command that succeeded, trimmed.
+#[cfg(unix)]
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let out = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+#[cfg(unix)]
+fn current() -> Option<Theme> {
+    let scheme = if is_kde() {
+        let args = [
+            "--file",
+            "kdeglobals",
+            "--group",
+            "General",
+            "--key",
+            "ColorScheme",
+        ];
+        output("kreadconfig6", &args).or_else(|| output("kreadconfig5", &args))?
+    } else {
+        output(
+            "gsettings",
+            &["get", "org.gnome.desktop.interface", "color-scheme"],
+        )?
+    };
+    Some(Theme {
+        dark: is_dark_scheme(&scheme),
+        attributes: serde_json::json!({ "scheme": scheme.trim_matches('\'') }),
+    })
+}
+
+/// A GNOME `color-scheme` (`'prefer-dark'`) or KDE color scheme name
+/// (`BreezeDark`) that means dark.
+#[cfg_attr(windows, allow(dead_code))]
+fn is_dark_scheme(scheme: &str) -> bool {
+    scheme.to_ascii_lowercase().contains("dark")
+}
+
+#[cfg(unix)]
+fn apply(dark: bool) -> Result<()> {
+    let (program, args): (&str, &[&str]) = match (is_kde(), dark) {
+        (true, true) => ("plasma-apply-colorscheme", &["BreezeDark"]),
+        (true, false) => ("plasma-apply-colorscheme", &["BreezeLight"]),
+        (false, true) => (
+            "gsettings",
+            &[
+                "set",
+                "org.gnome.desktop.interface",
+                "color-scheme",
+                "prefer-dark",
+            ],
+        ),
+        (false, false) => (
+            "gsettings",
+            &[
+                "set",
+                "org.gnome.desktop.interface",
+                "color-scheme",
+                "default",
+            ],
+        ),
+    };
+    match std::process::Command::new(program).args(args).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => bail!("{program} exited with {status}"),
+        Err(e) => bail!("{program}: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_dark_scheme() {
+        assert!(is_dark_scheme("'prefer-dark'"));
+        assert!(is_dark_scheme("BreezeDark"));
+        assert!(!is_dark_scheme("'default'"));
+        assert!(!is_dark_scheme("'prefer-light'"));
+        assert!(!is_dark_scheme("BreezeLight"));
+    }
+}
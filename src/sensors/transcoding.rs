@@ -0,0 +1,452 @@
+//! Hardware video-encode sensor
+//!
+//! `transcoding` is "on" while the GPU's video encoder (NVENC, AMD VCE/VCN,
+//! Intel QuickSync) is busy, polled every `intervals.transcoding`: a Plex or
+//! Jellyfin transcode, OBS, Steam Remote Play. The `processes` attribute
+//! names what is encoding, `sessions` is NVIDIA's encoder session count.
+//! - Windows: `\GPU Engine(*engtype_VideoEncode)\Utilization Percentage`,
+//!   the per-process Video Encode engines Task Manager shows, any vendor
+//! - Linux: growth of the encode engines' busy time in the DRM fdinfo of
+//!   every process with a GPU open (amdgpu `enc`; i915 `video`, which decodes
+//!   too), plus `nvidia-smi` encoder sessions. Another user's processes (a
+//!   `plex` service account) only show up when the agent runs as root.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(unix)]
+use std::collections::HashMap;
+
+use log::info;
+
+use super::runner::{Reading, Sensor};
+use crate::AppState;
+use crate::config::Config;
+
+/// Encode activity seen in one poll
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Encoding {
+    /// Names of the processes encoding, sorted
+    processes: Vec<String>,
+    /// NVIDIA encoder sessions (nvidia-smi, Linux)
+    sessions: Option<u32>,
+}
+
+impl Encoding {
+    fn active(&self) -> bool {
+        !self.processes.is_empty() || self.sessions.is_some_and(|n| n > 0)
+    }
+
+    fn reading(&self) -> Reading {
+        Reading::new("transcoding", if self.active() { "on" } else { "off" })
+            .retained()
+            .with_attributes(serde_json::json!({
+                "processes": self.processes,
+                "sessions": self.sessions,
+            }))
+    }
+}
+
+pub struct TranscodingSensor {
+    state: Arc<AppState>,
+    /// Encode busy time (ns) per DRM client as of the last poll
+    #[cfg(unix)]
+    clients: HashMap<String, u64>,
+    was_active: bool,
+}
+
+impl TranscodingSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self {
+            state,
+            #[cfg(unix)]
+            clients: HashMap::new(),
+            was_active: false,
+        }
+    }
+
+    pub async fn run(self) {
+        if !self.state.config.read().await.features.transcoding {
+            return;
+        }
+        super::runner::run(Arc::clone(&self.state), self).await;
+    }
+
+    #[cfg(windows)]
+    async fn encoding(&mut self) -> Encoding {
+        let pids = tokio::task::spawn_blocking(encoding_pids)
+            .await
+            .unwrap_or_default();
+        let watcher = self.state.process_watcher.state();
+        let watcher = watcher.read().await;
+        let mut processes: Vec<String> = watcher
+            .processes()
+            .filter(|(pid, _)| pids.contains(pid))
+            .map(|(_, name)| name.to_string())
+            .collect();
+        // Short-lived or not yet tracked: still encoding, name unknown.
+        if processes.is_empty() && !pids.is_empty() {
+            processes = pids.iter().map(|pid| format!("pid {pid}")).collect();
+        }
+        processes.sort_unstable();
+        processes.dedup();
+        Encoding {
+            processes,
+            sessions: None,
+        }
+    }
+
+    #[cfg(unix)]
+    async fn encoding(&mut self) -> Encoding {
+        let prev = std::mem::take(&mut self.clients);
+        let Ok((encoding, clients)) = tokio::task::spawn_blocking(move || {
+            let clients = drm_encode_busy();
+            let mut processes: Vec<String> = clients
+                .iter()
+                .filter(|(client, (_, busy))| prev.get(*client).is_some_and(|p| busy > p))
+                .filter_map(|(_, (pid, _))| {
+                    std::fs::read_to_string(format!("/proc/{pid}/comm"))
+                        .ok()
+                        .map(|comm| comm.trim().to_string())
+                })
+                .collect();
+            processes.sort_unstable();
+            processes.dedup();
+            let encoding = Encoding {
+                processes,
+                sessions: nvidia_sessions(),
+            };
+            let clients = clients
+                .into_iter()
+                .map(|(client, (_, busy))| (client, busy))
+                .collect();
+            (encoding, clients)
+        })
+        .await
+        else {
+            return Encoding::default();
+        };
+        self.clients = clients;
+        encoding
+    }
+}
+
+impl Sensor for TranscodingSensor {
+    const NAME: &'static str = "Transcoding";
+
+    fn interval(config: &Config) -> Option<Duration> {
+        Some(Duration::from_secs(config.intervals.transcoding.max(1)))
+    }
+
+    async fn poll(&mut self) -> Vec<Reading> {
+        let encoding = self.encoding().await;
+        if encoding.active() != self.was_active {
+            if encoding.active() {
+                info!("Video encoding started ({})", encoding.processes.join(", "));
+            } else {
+                info!("Video encoding stopped");
+            }
+            self.was_active = encoding.active();
+        }
+        vec![encoding.reading()]
+    }
+}
+
+/// PIDs with a Video Encode engine busy since the last call. The first call
+/// only takes PDH's baseline sample.
+#[cfg(windows)]
+fn encoding_pids() -> Vec<u32> {
+    use log::warn;
+    use std::sync::Mutex;
+    use windows::Win32::System::Performance::{
+        PDH_CSTATUS_NEW_DATA, PDH_CSTATUS_VALID_DATA, PDH_FMT_COUNTERVALUE_ITEM_W, PDH_FMT_DOUBLE,
+        PdhAddEnglishCounterW, PdhCloseQuery, PdhCollectQueryData, PdhGetFormattedCounterArrayW,
+        PdhOpenQueryW,
+    };
+
+    // PDH "more data" - returned by the array getter's first (sizing) call.
+    const PDH_MORE_DATA: u32 = 0x8000_07D2;
+
+    struct PdhState {
+        query: isize,
+        counter: isize,
+    }
+
+    // SAFETY: raw PDH handles, only touched under the Mutex below; PDH query
+    // handles aren't thread-bound (see the GPU sensor).
+    unsafe impl Send for PdhState {}
+
+    static PDH: Mutex<Option<PdhState>> = Mutex::new(None);
+
+    let mut guard = PDH.lock().unwrap_or_else(|e| e.into_inner());
+    unsafe {
+        if guard.is_none() {
+            // Not set up yet (or the provider wasn't ready last time): open
+            // the query and take the baseline sample.
+            let mut query: isize = 0;
+            let status = PdhOpenQueryW(None, 0, &raw mut query);
+            if status != 0 {
+                warn!("Transcoding: PdhOpenQueryW failed: 0x{status:08x}");
+                return Vec::new();
+            }
+            let path =
+                windows::core::w!("\\GPU Engine(*engtype_VideoEncode)\\Utilization Percentage");
+            let mut counter: isize = 0;
+            let status = PdhAddEnglishCounterW(query, path, 0, &raw mut counter);
+            if status != 0 {
+                warn!("Transcoding: PdhAddEnglishCounterW failed: 0x{status:08x}");
+                let _ = PdhCloseQuery(query);
+                return Vec::new();
+            }
+            let _ = PdhCollectQueryData(query);
+            *guard = Some(PdhState { query, counter });
+            return Vec::new();
+        }
+        let Some(pdh) = guard.as_ref() else {
+            return Vec::new();
+        };
+
+        if PdhCollectQueryData(pdh.query) != 0 {
+            return Vec::new();
+        }
+        let mut buf_size: u32 = 0;
+        let mut item_count: u32 = 0;
+        let status = PdhGetFormattedCounterArrayW(
+            pdh.counter,
+            PDH_FMT_DOUBLE,
+            &raw mut buf_size,
+            &raw mut item_count,
+            None,
+        );
+        if status != PDH_MORE_DATA || buf_size == 0 {
+            return Vec::new();
+        }
+        let elem = std::mem::size_of::<PDH_FMT_COUNTERVALUE_ITEM_W>();
+        let mut buffer: Vec<PDH_FMT_COUNTERVALUE_ITEM_W> =
+            Vec::with_capacity((buf_size as usize).div_ceil(elem).max(1));
+        let status = PdhGetFormattedCounterArrayW(
+            pdh.counter,
+            PDH_FMT_DOUBLE,
+            &raw mut buf_size,
+            &raw mut item_count,
+            Some(buffer.as_mut_ptr()),
+        );
+        if status != 0 {
+            return Vec::new();
+        }
+        let items = std::slice::from_raw_parts(buffer.as_ptr(), item_count as usize);
+        let mut pids: Vec<u32> = items
+            .iter()
+            .filter(|item| {
+                matches!(
+                    item.FmtValue.CStatus,
+                    PDH_CSTATUS_VALID_DATA | PDH_CSTATUS_NEW_DATA
+                ) && item.FmtValue.Anonymous.doubleValue > 0.0
+            })
+            .filter_map(|item| instance_pid(&item.szName.to_string().unwrap_or_default()))
+            .collect();
+        pids.sort_unstable();
+        pids.dedup();
+        pids
+    }
+}
+
+/// The process of a `\GPU Engine` instance
+/// (`pid_1234_luid_0x..._phys_0_eng_2_engtype_VideoEncode`).
+#[cfg_attr(unix, allow(dead_code))]
+fn instance_pid(name: &str) -> Option<u32> {
+    name.to_ascii_lowercase()
+        .strip_prefix("pid_")?
+        .split('_')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Encode busy time (ns) per DRM client (`<pdev>/<client id>`), with the
+/// pid holding it. A client opened on several fds is counted once.
+#[cfg(unix)]
+fn drm_encode_busy() -> HashMap<String, (u32, u64)> {
+    let mut clients = HashMap::new();
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return clients;
+    };
+    for entry in procs.flatten() {
+        let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|s| s.parse::<u32>().ok())
+        else {
+            continue;
+        };
+        // Unreadable for other users' processes unless running as root.
+        let Ok(fds) = std::fs::read_dir(entry.path().join("fd")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let is_gpu =
+                std::fs::read_link(fd.path()).is_ok_and(|target| target.starts_with("/dev/dri/"));
+            if !is_gpu {
+                continue;
+            }
+            let fdinfo = entry.path().join("fdinfo").join(fd.file_name());
+            if let Some((client, busy)) = std::fs::read_to_string(fdinfo)
+                .ok()
+                .as_deref()
+                .and_then(parse_fdinfo)
+            {
+                clients.entry(client).or_insert((pid, busy));
+            }
+        }
+    }
+    clients
+}
+
+/// `(client, encode busy ns)` from a DRM fdinfo file, if its driver reports
+/// an encode engine.
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_fdinfo(text: &str) -> Option<(String, u64)> {
+    let mut driver = None;
+    let mut pdev = "";
+    let mut client = None;
+    let mut busy: Option<u64> = None;
+    for line in text.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "drm-driver" => driver = Some(value),
+            "drm-pdev" => pdev = value,
+            "drm-client-id" => client = Some(value),
+            _ => {
+                let Some(engine) = key.strip_prefix("drm-engine-") else {
+                    continue;
+                };
+                let encodes = match driver {
+                    Some("amdgpu") => engine == "enc" || engine.starts_with("enc_"),
+                    Some("i915") => engine == "video",
+                    _ => false,
+                };
+                if encodes
+                    && let Some(ns) = value
+                        .split_whitespace()
+                        .next()
+                        .and_then(|n| n.parse::<u64>().ok())
+                {
+                    *busy.get_or_insert(0) += ns;
+                }
+            }
+        }
+    }
+    Some((format!("{pdev}/{}", client?), busy?))
+}
+
+/// Active NVENC sessions across NVIDIA GPUs; None without nvidia-smi.
+#[cfg(unix)]
+fn nvidia_sessions() -> Option<u32> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    // Once nvidia-smi turns out not to be installed, stop forking it.
+    static NVIDIA_ABSENT: AtomicBool = AtomicBool::new(false);
+
+    if NVIDIA_ABSENT.load(Ordering::Relaxed) {
+        return None;
+    }
+    match std::process::Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=encoder.stats.sessionCount",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            parse_nvidia_sessions(&String::from_utf8_lossy(&output.stdout))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            NVIDIA_ABSENT.store(true, Ordering::Relaxed);
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Sum of the per-GPU session counts nvidia-smi prints, one line each.
+#[cfg_attr(windows, allow(dead_code))]
+fn parse_nvidia_sessions(output: &str) -> Option<u32> {
+    output
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.trim().parse::<u32>().ok())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instance_pid() {
+        assert_eq!(
+            instance_pid("pid_4120_luid_0x00000000_0x0000D1A4_phys_0_eng_2_engtype_VideoEncode"),
+            Some(4120)
+        );
+        assert_eq!(
+            instance_pid("luid_0x0_phys_0_eng_2_engtype_VideoEncode"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_fdinfo() {
+        let amd = "pos:\t0\nflags:\t02100002\ndrm-driver:\tamdgpu\ndrm-pdev:\t0000:03:00.0\n\
+                   drm-client-id:\t17\ndrm-engine-gfx:\t9000 ns\ndrm-engine-enc:\t1200 ns\n\
+                   drm-engine-enc_1:\t300 ns\ndrm-engine-dec:\t5000 ns\n";
+        assert_eq!(
+            parse_fdinfo(amd),
+            Some(("0000:03:00.0/17".to_string(), 1500))
+        );
+
+        let intel = "drm-driver:\ti915\ndrm-pdev:\t0000:00:02.0\ndrm-client-id:\t4\n\
+                     drm-engine-render:\t100 ns\ndrm-engine-video:\t250 ns\n";
+        assert_eq!(
+            parse_fdinfo(intel),
+            Some(("0000:00:02.0/4".to_string(), 250))
+        );
+
+        // No encode engine reported, or not a DRM client at all
+        let render_only = "drm-driver:\ti915\ndrm-client-id:\t5\ndrm-engine-render:\t1 ns\n";
+        assert_eq!(parse_fdinfo(render_only), None);
+        assert_eq!(parse_fdinfo("pos:\t0\nflags:\t02\n"), None);
+    }
+
+    #[test]
+    fn test_parse_nvidia_sessions() {
+        assert_eq!(parse_nvidia_sessions("2\n"), Some(2));
+        assert_eq!(parse_nvidia_sessions("1\n0\n"), Some(1));
+        assert_eq!(parse_nvidia_sessions("[N/A]\n"), None);
+    }
+
+    #[test]
+    fn test_encoding_state() {
+        assert!(!Encoding::default().active());
+        let idle_nvidia = Encoding {
+            processes: Vec::new(),
+            sessions: Some(0),
+        };
+        assert!(!idle_nvidia.active());
+        let plex = Encoding {
+            processes: vec!["Plex Transcoder".to_string()],
+            sessions: None,
+        };
+        assert!(plex.active());
+        assert_eq!(
+            plex.reading(),
+            Reading::new("transcoding", "on")
+                .retained()
+                .with_attributes(serde_json::json!({
+                    "processes": ["Plex Transcoder"],
+                    "sessions": null,
+                }))
+        );
+    }
+}
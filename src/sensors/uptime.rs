@@ -1,15 +1,23 @@
 //! System uptime sensor
 //!
-//! Reports the system (OS) uptime in seconds.
+//! Reports the system (OS) uptime in seconds (`system_uptime`) and the boot
+//! time derived from it (`last_boot`), unrelated to the agent's own uptime
+//! in `bridge_health`.
 //! - Windows: GetTickCount64
 //! - Linux: /proc/uptime
 
 use log::{debug, info};
 use std::sync::Arc;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
 use tokio::time::{Duration, MissedTickBehavior, interval};
 
 use crate::AppState;
 
+/// `last_boot` is now - uptime, so it jitters by a second between polls;
+/// only a shift beyond this (wall-clock change, NTP step) is republished.
+const BOOT_DRIFT_SECS: i64 = 30;
+
 pub struct UptimeSensor {
     state: Arc<AppState>,
 }
@@ -32,6 +40,7 @@ impl UptimeSensor {
         let mut shutdown_rx = self.state.shutdown_tx.subscribe();
         let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
         let mut prev_uptime = String::new();
+        let mut prev_boot: Option<OffsetDateTime> = None;
 
         info!("Uptime sensor started (polled every 60s)");
 
@@ -44,11 +53,13 @@ impl UptimeSensor {
                 }
                 Ok(()) = reconnect_rx.recv() => {
                     prev_uptime.clear();
+                    prev_boot = None;
                 }
                 _ = tick.tick() => {
                     // Report unavailable on a read failure rather than 0, which
                     // would look like a fresh boot and fire "PC rebooted" automations.
-                    let uptime_str = match get_system_uptime() {
+                    let uptime = get_system_uptime();
+                    let uptime_str = match uptime {
                         Some(secs) => secs.to_string(),
                         None => "unavailable".to_string(),
                     };
@@ -56,12 +67,29 @@ impl UptimeSensor {
                         self.state.mqtt.publish_sensor("system_uptime", &uptime_str).await;
                         prev_uptime = uptime_str;
                     }
+                    if let Some(boot) = uptime.and_then(|secs| boot_time(OffsetDateTime::now_utc(), secs))
+                        && boot_moved(prev_boot, boot)
+                    {
+                        let value = boot.format(&Rfc3339).unwrap_or_else(|_| boot.to_string());
+                        self.state.mqtt.publish_sensor("last_boot", &value).await;
+                        prev_boot = Some(boot);
+                    }
                 }
             }
         }
     }
 }
 
+/// Boot time from the current time and uptime, truncated to whole seconds.
+fn boot_time(now: OffsetDateTime, uptime_secs: u64) -> Option<OffsetDateTime> {
+    let now = now.replace_nanosecond(0).ok()?;
+    now.checked_sub(time::Duration::seconds(i64::try_from(uptime_secs).ok()?))
+}
+
+fn boot_moved(prev: Option<OffsetDateTime>, boot: OffsetDateTime) -> bool {
+    prev.is_none_or(|prev| (boot - prev).whole_seconds().abs() > BOOT_DRIFT_SECS)
+}
+
 #[cfg(windows)]
 fn get_system_uptime() -> Option<u64> {
     // GetTickCount64 returns milliseconds since system boot
@@ -84,9 +112,25 @@ fn parse_proc_uptime(content: &str) -> Option<u64> {
 
 #[cfg(test)]
 mod tests {
-    #[cfg(unix)]
     use super::*;
 
+    #[test]
+    fn test_boot_time() {
+        let now = OffsetDateTime::parse("2026-10-15T12:00:00.750Z", &Rfc3339).unwrap();
+        let boot = boot_time(now, 3 * 86_400 + 90).unwrap();
+        assert_eq!(boot.format(&Rfc3339).unwrap(), "2026-10-12T11:58:30Z");
+        assert_eq!(boot_time(now, u64::MAX), None);
+    }
+
+    #[test]
+    fn test_boot_moved_ignores_poll_jitter() {
+        let boot = OffsetDateTime::parse("2026-10-12T11:58:30Z", &Rfc3339).unwrap();
+        assert!(boot_moved(None, boot));
+        assert!(!boot_moved(Some(boot), boot + time::Duration::seconds(1)));
+        assert!(!boot_moved(Some(boot), boot - time::Duration::seconds(1)));
+        assert!(boot_moved(Some(boot), boot + time::Duration::hours(1)));
+    }
+
     #[cfg(unix)]
     #[test]
     fn test_parse_proc_uptime_typical() {
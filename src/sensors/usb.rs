@@ -0,0 +1,599 @@
+//! USB plug / unplug events and presence sensors
+//!
+//! Publishes `usb_connected` / `usb_disconnected` events (vid, pid, name) for
+//! any USB device, and a `usb_<slug>` plug binary sensor per `usb_devices`
+//! entry: on while a device with its vendor (and product) ID is attached.
+//! - Windows: WM_DEVICECHANGE on a hidden window, then a SetupAPI walk of the
+//!   USB enumerator
+//! - Linux: kernel uevents over netlink, then `/sys/bus/usb/devices`
+//!
+//! A notification only says something changed; the device list is re-read
+//! and diffed, so a missed or duplicate message can't leave a sensor wrong.
+//! The list is also re-read every `intervals.usb_devices` in case a
+//! notification never arrives (no window messages for a service, say).
+
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use super::device_battery::slug;
+use crate::AppState;
+use crate::config::UsbDevice;
+use crate::events::usb_transitions;
+use crate::feature_state::FeatureState;
+use crate::mqtt::usb_device_id;
+
+/// Quiet time after a notification before re-reading: one plug-in is a burst
+/// of messages, one per interface.
+const SETTLE: Duration = Duration::from_millis(500);
+
+/// An attached device: (key, vid, pid, name). The key (instance ID / sysfs
+/// port path) tells apart two devices with the same IDs.
+type Attached = (String, u16, u16, String);
+
+pub struct UsbSensor {
+    state: Arc<AppState>,
+}
+
+impl UsbSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.usb_devices {
+            return;
+        }
+        let poll_secs = config.intervals.usb_devices.max(10);
+        let watched: Vec<(UsbDevice, String)> = config
+            .usb_devices
+            .iter()
+            .map(|d| (d.clone(), slug(&d.name)))
+            .collect();
+        drop(config);
+
+        // Dropping the receiver (shutdown, task cancelled) stops the watcher.
+        let (changed_tx, mut changed_rx) = mpsc::channel::<()>(1);
+        watch::spawn(changed_tx);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut attached: Option<Vec<Attached>> = None;
+        let mut registered = false;
+        let mut published: HashMap<String, Option<Attached>> = HashMap::new();
+
+        info!(
+            "USB sensor started ({} watched devices, rescanned every {poll_secs}s)",
+            watched.len()
+        );
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("USB sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => {
+                    registered = false;
+                    published.clear();
+                }
+                Some(()) = changed_rx.recv() => {
+                    tokio::time::sleep(SETTLE).await;
+                    while changed_rx.try_recv().is_ok() {}
+                }
+                _ = tick.tick() => {}
+            }
+
+            let now = tokio::task::spawn_blocking(enumerate)
+                .await
+                .unwrap_or_default();
+            // The first read is the baseline: what was plugged in before the
+            // agent started isn't an event.
+            if let Some(prev) = &attached {
+                for event in usb_transitions(prev, &now) {
+                    info!("USB: {event:?}");
+                    self.state.mqtt.publish_event(&event).await;
+                }
+            }
+            attached = Some(now);
+
+            if !registered {
+                self.register(&watched).await;
+                registered = true;
+            }
+            let now = attached.as_deref().unwrap_or_default();
+            for (device, slug) in &watched {
+                let found = now
+                    .iter()
+                    .find(|(_, vid, pid, _)| device.matches(*vid, *pid))
+                    .cloned();
+                // A different port or instance of the same device isn't a change.
+                if published
+                    .get(slug)
+                    .is_some_and(|prev| same_device(prev.as_ref(), found.as_ref()))
+                {
+                    continue;
+                }
+                let id = usb_device_id(slug);
+                let mqtt = &self.state.mqtt;
+                mqtt.publish_sensor(&id, if found.is_some() { "on" } else { "off" })
+                    .await;
+                let attributes = match &found {
+                    Some((_, vid, pid, name)) => serde_json::json!({
+                        "vid": format!("{vid:04x}"),
+                        "pid": format!("{pid:04x}"),
+                        "product": name,
+                    }),
+                    None => serde_json::json!({}),
+                };
+                mqtt.publish_sensor_attributes(&id, &attributes).await;
+                published.insert(slug.clone(), found);
+            }
+        }
+    }
+
+    /// Register each watched device's sensor and record it in
+    /// feature_state.json, so it's removed when `usb_devices` is turned off.
+    async fn register(&self, watched: &[(UsbDevice, String)]) {
+        let config = self.state.config.read().await;
+        for (device, slug) in watched {
+            self.state
+                .mqtt
+                .register_usb_device(slug, &device.name, &config)
+                .await;
+        }
+        drop(config);
+        let mut state = FeatureState::load();
+        let mut changed = false;
+        for (_, slug) in watched {
+            changed |= state.remember_usb_device(slug);
+        }
+        if changed && let Err(e) = state.save() {
+            warn!("USB: failed to record devices in feature_state.json: {e}");
+        }
+    }
+}
+
+/// Same presence, IDs and name, wherever it's plugged in.
+fn same_device(a: Option<&Attached>, b: Option<&Attached>) -> bool {
+    match (a, b) {
+        (Some((_, va, pa, na)), Some((_, vb, pb, nb))) => (va, pa, na) == (vb, pb, nb),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+/// Attached USB devices (not hubs' root ports or interfaces).
+#[cfg(unix)]
+fn enumerate() -> Vec<Attached> {
+    let Ok(entries) = std::fs::read_dir("/sys/bus/usb/devices") else {
+        return Vec::new();
+    };
+    let mut devices = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let read = |file: &str| {
+            std::fs::read_to_string(path.join(file))
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        // Interfaces ("1-2:1.0") have no idVendor.
+        let (Some(vid), Some(pid)) = (read("idVendor"), read("idProduct")) else {
+            continue;
+        };
+        let (Ok(vid), Ok(pid)) = (u16::from_str_radix(&vid, 16), u16::from_str_radix(&pid, 16))
+        else {
+            continue;
+        };
+        // Linux Foundation: the root hubs.
+        if vid == 0x1d6b {
+            continue;
+        }
+        let name = read("product").unwrap_or_else(|| format!("{vid:04x}:{pid:04x}"));
+        devices.push((
+            entry.file_name().to_string_lossy().into_owned(),
+            vid,
+            pid,
+            name,
+        ));
+    }
+    devices.sort();
+    devices
+}
+
+/// Whether a kernel uevent (NUL-separated `KEY=value` after an
+/// `action@devpath` header) is a whole USB device being added or removed.
+#[cfg_attr(windows, allow(dead_code))]
+fn is_usb_device_uevent(msg: &[u8]) -> bool {
+    let mut fields = msg.split(|&b| b == 0);
+    let header = fields.next().unwrap_or_default();
+    if !(header.starts_with(b"add@") || header.starts_with(b"remove@")) {
+        return false;
+    }
+    let (mut usb, mut device) = (false, false);
+    for field in fields {
+        usb |= field == b"SUBSYSTEM=usb";
+        device |= field == b"DEVTYPE=usb_device";
+    }
+    usb && device
+}
+
+/// Vendor and product ID from a USB instance ID, `USB\VID_04E8&PID_6860\...`.
+/// Interfaces of composite devices (`&MI_01`) and root hubs give None.
+#[cfg_attr(unix, allow(dead_code))]
+fn parse_instance_id(id: &str) -> Option<(u16, u16)> {
+    let hardware = id.split('\\').nth(1)?.to_ascii_uppercase();
+    if hardware.contains("&MI_") {
+        return None;
+    }
+    let (vid, pid) = hardware.strip_prefix("VID_")?.split_once("&PID_")?;
+    let pid = pid.get(..4)?;
+    Some((
+        u16::from_str_radix(vid, 16).ok()?,
+        u16::from_str_radix(pid, 16).ok()?,
+    ))
+}
+
+#[cfg(unix)]
+mod watch {
+    use log::warn;
+    use std::os::fd::{FromRawFd, OwnedFd};
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+
+    use super::is_usb_device_uevent;
+
+    /// Listen for kernel uevents on a thread, sending on `changed` for each
+    /// USB device added or removed.
+    pub(super) fn spawn(changed: mpsc::Sender<()>) {
+        let sock = match open() {
+            Ok(sock) => sock,
+            Err(e) => {
+                warn!("USB: no uevent socket ({e}); rescanning on the interval only");
+                return;
+            }
+        };
+        let spawned = std::thread::Builder::new()
+            .name("usb-uevents".into())
+            .stack_size(128 * 1024)
+            .spawn(move || {
+                let mut buf = vec![0u8; 8192];
+                while !changed.is_closed() {
+                    // Times out every second to notice the receiver going away.
+                    let Ok(n) = sock.recv(&mut buf) else {
+                        continue;
+                    };
+                    if is_usb_device_uevent(&buf[..n]) {
+                        let _ = changed.try_send(());
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            warn!("USB: failed to spawn the uevent thread: {e}");
+        }
+    }
+
+    /// A NETLINK_KOBJECT_UEVENT socket on the kernel's broadcast group, which
+    /// needs no privileges.
+    fn open() -> std::io::Result<std::net::UdpSocket> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = 1;
+        let bound = unsafe {
+            libc::bind(
+                std::os::fd::AsRawFd::as_raw_fd(&fd),
+                (&raw const addr).cast(),
+                size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if bound < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        // Datagram socket semantics are all std needs for recv and timeouts.
+        let sock = std::net::UdpSocket::from(fd);
+        sock.set_read_timeout(Some(Duration::from_secs(1)))?;
+        Ok(sock)
+    }
+}
+
+/// Attached USB devices, from a SetupAPI walk of the USB enumerator.
+/// Declared locally like device_battery's SetupAPI calls.
+#[cfg(windows)]
+fn enumerate() -> Vec<Attached> {
+    pnp::read()
+}
+
+#[cfg(windows)]
+mod pnp {
+    use super::{Attached, parse_instance_id};
+    use core::ffi::c_void;
+
+    #[repr(C)]
+    struct SpDevinfoData {
+        cb_size: u32,
+        class_guid: [u8; 16],
+        dev_inst: u32,
+        reserved: usize,
+    }
+
+    #[link(name = "setupapi")]
+    unsafe extern "system" {
+        fn SetupDiGetClassDevsW(
+            class_guid: *const c_void,
+            enumerator: *const u16,
+            hwnd_parent: *mut c_void,
+            flags: u32,
+        ) -> *mut c_void;
+        fn SetupDiEnumDeviceInfo(
+            device_info_set: *mut c_void,
+            member_index: u32,
+            device_info_data: *mut SpDevinfoData,
+        ) -> i32;
+        fn SetupDiGetDeviceInstanceIdW(
+            device_info_set: *mut c_void,
+            device_info_data: *const SpDevinfoData,
+            buffer: *mut u16,
+            buffer_size: u32,
+            required_size: *mut u32,
+        ) -> i32;
+        fn SetupDiGetDeviceRegistryPropertyW(
+            device_info_set: *mut c_void,
+            device_info_data: *const SpDevinfoData,
+            property: u32,
+            property_reg_type: *mut u32,
+            buffer: *mut u8,
+            buffer_size: u32,
+            required_size: *mut u32,
+        ) -> i32;
+        fn SetupDiDestroyDeviceInfoList(device_info_set: *mut c_void) -> i32;
+    }
+
+    const DIGCF_PRESENT: u32 = 0x2;
+    const DIGCF_ALLCLASSES: u32 = 0x4;
+    const SPDRP_DEVICEDESC: u32 = 0x0;
+    const SPDRP_FRIENDLYNAME: u32 = 0xC;
+
+    pub(super) fn read() -> Vec<Attached> {
+        let enumerator: Vec<u16> = "USB\0".encode_utf16().collect();
+        let set = unsafe {
+            SetupDiGetClassDevsW(
+                std::ptr::null(),
+                enumerator.as_ptr(),
+                std::ptr::null_mut(),
+                DIGCF_PRESENT | DIGCF_ALLCLASSES,
+            )
+        };
+        // INVALID_HANDLE_VALUE
+        if set as isize == -1 {
+            return Vec::new();
+        }
+        let mut devices = Vec::new();
+        for index in 0u32.. {
+            let mut info = SpDevinfoData {
+                cb_size: size_of::<SpDevinfoData>() as u32,
+                class_guid: [0; 16],
+                dev_inst: 0,
+                reserved: 0,
+            };
+            if unsafe { SetupDiEnumDeviceInfo(set, index, &raw mut info) } == 0 {
+                break; // ERROR_NO_MORE_ITEMS
+            }
+            let mut buf = [0u16; 256];
+            let mut len = 0u32;
+            let ok = unsafe {
+                SetupDiGetDeviceInstanceIdW(
+                    set,
+                    &raw const info,
+                    buf.as_mut_ptr(),
+                    buf.len() as u32,
+                    &raw mut len,
+                )
+            };
+            if ok == 0 {
+                continue;
+            }
+            let end = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+            let instance_id = String::from_utf16_lossy(&buf[..end]);
+            let Some((vid, pid)) = parse_instance_id(&instance_id) else {
+                continue;
+            };
+            let name = string_property(set, &info, SPDRP_FRIENDLYNAME)
+                .or_else(|| string_property(set, &info, SPDRP_DEVICEDESC))
+                .unwrap_or_else(|| format!("{vid:04x}:{pid:04x}"));
+            devices.push((instance_id, vid, pid, name));
+        }
+        unsafe { SetupDiDestroyDeviceInfoList(set) };
+        devices.sort();
+        devices
+    }
+
+    fn string_property(set: *mut c_void, info: &SpDevinfoData, property: u32) -> Option<String> {
+        let mut buf = [0u8; 512];
+        let mut reg_type = 0u32;
+        let mut size = 0u32;
+        let ok = unsafe {
+            SetupDiGetDeviceRegistryPropertyW(
+                set,
+                info,
+                property,
+                &raw mut reg_type,
+                buf.as_mut_ptr(),
+                buf.len() as u32,
+                &raw mut size,
+            )
+        };
+        if ok == 0 {
+            return None;
+        }
+        let len = (size as usize).min(buf.len());
+        let wide: Vec<u16> = buf[..len]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|&c| c != 0)
+            .collect();
+        let name = String::from_utf16_lossy(&wide).trim().to_string();
+        (!name.is_empty()).then_some(name)
+    }
+}
+
+#[cfg(windows)]
+mod watch {
+    use log::{error, warn};
+    use tokio::sync::mpsc;
+    use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DefWindowProcW, DestroyWindow, DispatchMessageW, GWLP_USERDATA,
+        GetMessageW, GetWindowLongPtrW, MSG, RegisterClassExW, SetTimer, SetWindowLongPtrW,
+        WINDOW_EX_STYLE, WINDOW_STYLE, WNDCLASSEXW,
+    };
+
+    const WM_DEVICECHANGE: u32 = 0x0219;
+    const DBT_DEVNODES_CHANGED: usize = 0x0007;
+    const DBT_DEVICEARRIVAL: usize = 0x8000;
+    const DBT_DEVICEREMOVECOMPLETE: usize = 0x8004;
+
+    /// Pump a hidden top-level window's messages on a thread (device changes
+    /// are broadcast to those), sending on `changed` for each device change.
+    pub(super) fn spawn(changed: mpsc::Sender<()>) {
+        let spawned = std::thread::Builder::new()
+            .name("usb-events".into())
+            .stack_size(256 * 1024)
+            .spawn(move || message_pump(changed));
+        if let Err(e) = spawned {
+            warn!("USB: failed to spawn the device events thread: {e}");
+        }
+    }
+
+    fn message_pump(changed: mpsc::Sender<()>) {
+        unsafe {
+            let class_name = windows::core::w!("PCBridgeUsbMonitor");
+            let wc = WNDCLASSEXW {
+                cbSize: size_of::<WNDCLASSEXW>() as u32,
+                lpfnWndProc: Some(wnd_proc),
+                lpszClassName: class_name,
+                ..Default::default()
+            };
+            RegisterClassExW(&raw const wc);
+            let hwnd = match CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                class_name,
+                windows::core::w!("PC Bridge USB Monitor"),
+                WINDOW_STYLE::default(),
+                0,
+                0,
+                0,
+                0,
+                None,
+                None,
+                None,
+                None,
+            ) {
+                Ok(h) => h,
+                Err(e) => {
+                    error!("Failed to create USB monitor window: {e:?}");
+                    return;
+                }
+            };
+            let ctx = Box::into_raw(Box::new(changed));
+            SetWindowLongPtrW(hwnd, GWLP_USERDATA, ctx as isize);
+            // Wakes GetMessageW every second to notice the receiver going away.
+            SetTimer(hwnd, 1, 1000, None);
+
+            let mut msg = MSG::default();
+            while !(*ctx).is_closed() {
+                let ret = GetMessageW(&raw mut msg, None, 0, 0);
+                if !ret.as_bool() || ret.0 == -1 {
+                    break;
+                }
+                DispatchMessageW(&raw const msg);
+            }
+
+            SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0);
+            let _ = Box::from_raw(ctx);
+            let _ = DestroyWindow(hwnd);
+        }
+    }
+
+    unsafe extern "system" fn wnd_proc(
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        unsafe {
+            if msg == WM_DEVICECHANGE
+                && matches!(
+                    wparam.0,
+                    DBT_DEVNODES_CHANGED | DBT_DEVICEARRIVAL | DBT_DEVICEREMOVECOMPLETE
+                )
+            {
+                let ctx = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const mpsc::Sender<()>;
+                if !ctx.is_null() {
+                    let _ = (*ctx).try_send(());
+                }
+                return LRESULT(1);
+            }
+            DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_usb_device_uevent() {
+        let add = b"add@/devices/pci0000:00/0000:00:14.0/usb1/1-3\0ACTION=add\0\
+                    DEVPATH=/devices/pci0000:00/0000:00:14.0/usb1/1-3\0SUBSYSTEM=usb\0\
+                    DEVTYPE=usb_device\0PRODUCT=4e8/6860/400\0";
+        assert!(is_usb_device_uevent(add));
+        let remove =
+            b"remove@/devices/x/usb1/1-3\0ACTION=remove\0SUBSYSTEM=usb\0DEVTYPE=usb_device\0";
+        assert!(is_usb_device_uevent(remove));
+        // An interface of the device, a bind, and another subsystem.
+        let interface = b"add@/devices/x/usb1/1-3/1-3:1.0\0ACTION=add\0SUBSYSTEM=usb\0\
+                          DEVTYPE=usb_interface\0";
+        assert!(!is_usb_device_uevent(interface));
+        let bind = b"bind@/devices/x/usb1/1-3\0ACTION=bind\0SUBSYSTEM=usb\0DEVTYPE=usb_device\0";
+        assert!(!is_usb_device_uevent(bind));
+        assert!(!is_usb_device_uevent(
+            b"add@/devices/virtual/net/tun0\0SUBSYSTEM=net\0"
+        ));
+    }
+
+    #[test]
+    fn test_parse_instance_id() {
+        assert_eq!(
+            parse_instance_id(r"USB\VID_04E8&PID_6860\R58N1234ABC"),
+            Some((0x04e8, 0x6860))
+        );
+        assert_eq!(
+            parse_instance_id(r"USB\VID_046D&PID_C52B\5&2A1B3C4D&0&2"),
+            Some((0x046d, 0xc52b))
+        );
+        // A composite device's interface, and a root hub.
+        assert_eq!(
+            parse_instance_id(r"USB\VID_046D&PID_C52B&MI_01\6&1234&0&0001"),
+            None
+        );
+        assert_eq!(parse_instance_id(r"USB\ROOT_HUB30\4&1234&0&0"), None);
+    }
+}
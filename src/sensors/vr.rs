@@ -0,0 +1,198 @@
+//! VR session sensor
+//!
+//! `vr_active` is "on" while a VR runtime has a session up, polled every
+//! `intervals.vr`. A session is the runtime's compositor, not its always-on
+//! service: SteamVR's `vrcompositor`, Oculus/Meta's `OculusDash` (started
+//! when the headset or Link comes up), Monado's `monado-service` and WiVRn's
+//! `wivrn-server` on Linux. Attributes name the runtime and the running
+//! title: Steam's running app (`RunningAppID`), else the game sensor's
+//! current game for non-Steam titles.
+
+use log::{debug, info};
+use std::sync::Arc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::AppState;
+
+/// Session process (lowercase, no `.exe`) -> runtime name. Earlier wins when
+/// several run (SteamVR on top of Oculus Link reports SteamVR).
+const RUNTIMES: &[(&str, &str)] = &[
+    ("vrcompositor", "SteamVR"),
+    ("oculusdash", "Oculus"),
+    ("monado-service", "Monado"),
+    ("wivrn-server", "WiVRn"),
+];
+
+/// SteamVR's own app id. Steam reports it as running while only SteamVR is.
+const STEAMVR_APP_ID: u32 = 250_820;
+
+/// The runtime whose session process is among `names`.
+fn detect_runtime<'a>(names: impl IntoIterator<Item = &'a str>) -> Option<&'static str> {
+    let keys: Vec<String> = names
+        .into_iter()
+        .map(|n| {
+            let n = n.to_ascii_lowercase();
+            n.strip_suffix(".exe").map(str::to_string).unwrap_or(n)
+        })
+        .collect();
+    RUNTIMES
+        .iter()
+        .find(|(process, _)| keys.iter().any(|k| k == process))
+        .map(|(_, runtime)| *runtime)
+}
+
+/// What's published: runtime and title while a session is up.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Session {
+    runtime: Option<&'static str>,
+    app_id: Option<u32>,
+    title: Option<String>,
+}
+
+impl Session {
+    fn state(&self) -> &'static str {
+        if self.runtime.is_some() { "on" } else { "off" }
+    }
+
+    fn attributes(&self) -> serde_json::Value {
+        serde_json::json!({
+            "runtime": self.runtime,
+            "title": self.title,
+            "app_id": self.app_id,
+        })
+    }
+}
+
+pub struct VrSensor {
+    state: Arc<AppState>,
+}
+
+impl VrSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.vr {
+            return;
+        }
+        let poll_secs = config.intervals.vr.max(1);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut prev: Option<Session> = None;
+
+        info!("VR sensor started (polled every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("VR sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => prev = None,
+                _ = tick.tick() => {
+                    let session = self.session(prev.as_ref()).await;
+                    if prev.as_ref() == Some(&session) {
+                        continue;
+                    }
+                    if prev.as_ref().map(Session::state) != Some(session.state()) {
+                        match session.runtime {
+                            Some(runtime) => info!("VR session started ({runtime})"),
+                            None => info!("VR session ended"),
+                        }
+                    }
+                    self.state.mqtt.publish_sensor("vr_active", session.state()).await;
+                    self.state
+                        .mqtt
+                        .publish_sensor_attributes("vr_active", &session.attributes())
+                        .await;
+                    prev = Some(session);
+                }
+            }
+        }
+    }
+
+    async fn session(&self, prev: Option<&Session>) -> Session {
+        let Some(runtime) = self.runtime().await else {
+            return Session::default();
+        };
+        let app_id = tokio::task::spawn_blocking(crate::steam::running_app_id)
+            .await
+            .ok()
+            .flatten()
+            .filter(|&id| id != STEAMVR_APP_ID);
+        let title = match app_id {
+            // Same app as last poll: skip the appmanifest lookup.
+            Some(id) if prev.is_some_and(|p| p.app_id == Some(id)) => {
+                prev.and_then(|p| p.title.clone())
+            }
+            Some(id) => tokio::task::spawn_blocking(move || crate::steam::app_name(id))
+                .await
+                .ok()
+                .flatten(),
+            None => None,
+        };
+        let title = title.or_else(|| {
+            self.state
+                .mqtt
+                .sensor_value("runninggames")
+                .filter(|v| v != "none")
+        });
+        Session {
+            runtime: Some(runtime),
+            app_id,
+            title,
+        }
+    }
+
+    #[cfg(windows)]
+    async fn runtime(&self) -> Option<&'static str> {
+        let names = self.state.process_watcher.get_names().await;
+        detect_runtime(names.iter().map(AsRef::as_ref))
+    }
+
+    #[cfg(unix)]
+    async fn runtime(&self) -> Option<&'static str> {
+        let names = tokio::task::spawn_blocking(super::current_process_names)
+            .await
+            .unwrap_or_default();
+        detect_runtime(names.iter().map(String::as_str))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_runtime() {
+        assert_eq!(detect_runtime(["explorer.exe", "steam.exe"]), None);
+        // The always-running services alone aren't a session.
+        assert_eq!(detect_runtime(["OVRServer_x64.exe", "vrserver.exe"]), None);
+        assert_eq!(detect_runtime(["OculusDash.exe"]), Some("Oculus"));
+        assert_eq!(
+            detect_runtime(["OculusDash.exe", "vrcompositor.exe"]),
+            Some("SteamVR")
+        );
+        assert_eq!(detect_runtime(["monado-service"]), Some("Monado"));
+    }
+
+    #[test]
+    fn test_session_attributes() {
+        let session = Session {
+            runtime: Some("SteamVR"),
+            app_id: Some(546_560),
+            title: Some("Half-Life: Alyx".to_string()),
+        };
+        assert_eq!(session.state(), "on");
+        assert_eq!(session.attributes()["title"], "Half-Life: Alyx");
+        assert_eq!(Session::default().state(), "off");
+        assert!(Session::default().attributes()["runtime"].is_null());
+    }
+}
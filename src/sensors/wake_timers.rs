@@ -0,0 +1,209 @@
+//! Next scheduled wake sensor
+//!
+//! `next_wake` is a timestamp: when the PC is next set to wake itself, e.g.
+//! for Windows Update's reboot or maintenance tasks, or unknown while nothing
+//! is scheduled. Attributes name what set it (`source`) and list the upcoming
+//! `timers`.
+//! - Windows: scheduled tasks with "Wake the computer to run this task"
+//!   (where `powercfg /waketimers` gets most of its entries), read with
+//!   Get-ScheduledTask. Running as a service also sees the system's own tasks.
+//! - Linux: the RTC wake alarm (`/sys/class/rtc/rtc0/wakealarm`), set by
+//!   systemd timers with `WakeSystem=` or rtcwake.
+
+use log::{debug, info};
+use std::sync::Arc;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::AppState;
+
+/// Timers listed in the `timers` attribute
+const MAX_LISTED: usize = 10;
+
+/// A scheduled wake
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WakeTimer {
+    at: OffsetDateTime,
+    source: String,
+}
+
+pub struct WakeTimerSensor {
+    state: Arc<AppState>,
+}
+
+impl WakeTimerSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.wake_timers {
+            return;
+        }
+        let poll_secs = config.intervals.wake_timers.max(60);
+        drop(config);
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let mut prev: Option<(String, serde_json::Value)> = None;
+
+        info!("Wake timer sensor started (polled every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Wake timer sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => {
+                    prev = None;
+                }
+                _ = tick.tick() => {
+                    let timers = match read_timers().await {
+                        Ok(timers) => timers,
+                        Err(e) => {
+                            debug!("Wake timers: {e}");
+                            continue;
+                        }
+                    };
+                    let reading = reading(timers, OffsetDateTime::now_utc());
+                    if prev.as_ref() == Some(&reading) {
+                        continue;
+                    }
+                    let mqtt = &self.state.mqtt;
+                    mqtt.publish_sensor("next_wake", &reading.0).await;
+                    mqtt.publish_sensor_attributes("next_wake", &reading.1).await;
+                    prev = Some(reading);
+                }
+            }
+        }
+    }
+}
+
+/// `next_wake` state and attributes from the timers still ahead of `now`.
+/// "None" (HA shows unknown) when there are none.
+fn reading(mut timers: Vec<WakeTimer>, now: OffsetDateTime) -> (String, serde_json::Value) {
+    timers.retain(|t| t.at > now);
+    timers.sort_by_key(|t| t.at);
+    let format = |at: OffsetDateTime| at.format(&Rfc3339).unwrap_or_default();
+    let listed: Vec<serde_json::Value> = timers
+        .iter()
+        .take(MAX_LISTED)
+        .map(|t| serde_json::json!({ "at": format(t.at), "source": t.source }))
+        .collect();
+    match timers.first() {
+        Some(next) => (
+            format(next.at),
+            serde_json::json!({ "source": next.source, "timers": listed }),
+        ),
+        None => (
+            "None".to_string(),
+            serde_json::json!({ "source": null, "timers": [] }),
+        ),
+    }
+}
+
+#[cfg(windows)]
+async fn read_timers() -> Result<Vec<WakeTimer>, String> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    // One "<UTC time>\t<task path>" line per enabled wake-to-run task with a
+    // next run time.
+    const SCRIPT: &str = "Get-ScheduledTask | \
+        Where-Object { $_.Settings.WakeToRun -and $_.State -ne 'Disabled' } | \
+        ForEach-Object { $i = $_ | Get-ScheduledTaskInfo -ErrorAction SilentlyContinue; \
+        if ($i.NextRunTime) { $i.NextRunTime.ToUniversalTime().ToString('yyyy-MM-ddTHH:mm:ssZ', [cultureinfo]::InvariantCulture) + \"`t\" + $_.TaskPath + $_.TaskName } }";
+
+    // kill_on_drop so a disabled sensor or the timeout doesn't leave
+    // powershell.exe behind.
+    let mut cmd = tokio::process::Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .creation_flags(CREATE_NO_WINDOW)
+        .kill_on_drop(true);
+    match tokio::time::timeout(Duration::from_mins(1), cmd.output()).await {
+        Ok(Ok(out)) if out.status.success() => {
+            Ok(parse_scheduled_tasks(&String::from_utf8_lossy(&out.stdout)))
+        }
+        Ok(Ok(out)) => Err(String::from_utf8_lossy(&out.stderr).trim().to_string()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("Get-ScheduledTask timed out".to_string()),
+    }
+}
+
+#[cfg_attr(unix, allow(dead_code))]
+fn parse_scheduled_tasks(output: &str) -> Vec<WakeTimer> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (at, task) = line.trim().split_once('\t')?;
+            Some(WakeTimer {
+                at: OffsetDateTime::parse(at, &Rfc3339).ok()?,
+                source: task.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+async fn read_timers() -> Result<Vec<WakeTimer>, String> {
+    // sysfs: answers from memory, fine on the runtime
+    let alarm = std::fs::read_to_string("/sys/class/rtc/rtc0/wakealarm")
+        .map_err(|e| format!("reading the RTC wake alarm: {e}"))?;
+    Ok(parse_wakealarm(&alarm).into_iter().collect())
+}
+
+/// `wakealarm` holds the alarm as Unix seconds, or nothing when unset.
+#[cfg(unix)]
+fn parse_wakealarm(content: &str) -> Option<WakeTimer> {
+    let secs = content.trim().parse::<i64>().ok()?;
+    Some(WakeTimer {
+        at: OffsetDateTime::from_unix_timestamp(secs).ok()?,
+        source: "rtc".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> OffsetDateTime {
+        OffsetDateTime::parse(s, &Rfc3339).unwrap()
+    }
+
+    #[test]
+    fn test_reading_picks_next_future_timer() {
+        let timers = parse_scheduled_tasks(
+            "2026-10-16T03:00:00Z\t\\Microsoft\\Windows\\UpdateOrchestrator\\Reboot_AC\r\n\
+             2026-10-15T01:00:00Z\t\\Backup\r\n\
+             2026-10-15T22:30:00Z\t\\Microsoft\\Windows\\UpdateOrchestrator\\Universal Orchestrator Start\r\n\
+             garbage\r\n",
+        );
+        assert_eq!(timers.len(), 3);
+        let (state, attrs) = reading(timers, at("2026-10-15T12:00:00Z"));
+        assert_eq!(state, "2026-10-15T22:30:00Z");
+        assert_eq!(
+            attrs["source"],
+            "\\Microsoft\\Windows\\UpdateOrchestrator\\Universal Orchestrator Start"
+        );
+        // The past one is dropped
+        assert_eq!(attrs["timers"].as_array().unwrap().len(), 2);
+
+        let (state, attrs) = reading(Vec::new(), at("2026-10-15T12:00:00Z"));
+        assert_eq!(state, "None");
+        assert!(attrs["source"].is_null());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_parse_wakealarm() {
+        assert_eq!(parse_wakealarm("\n"), None);
+        let timer = parse_wakealarm("1791864000\n").unwrap();
+        assert_eq!(timer.at, at("2026-10-13T04:00:00Z"));
+        assert_eq!(timer.source, "rtc");
+    }
+}
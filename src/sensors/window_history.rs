@@ -0,0 +1,128 @@
+//! Active window history
+//!
+//! With `window_history`, the active window sensor also keeps its last
+//! `window_history_size` changes - title, when it got focus and for how long -
+//! and publishes them every `intervals.window_history` seconds as the
+//! `entries` attribute of `window_history` (newest first; the state is how
+//! many there are). Enough for a time-tracking card in HA without a separate
+//! tool. Nothing is recorded while the `report_active_window` switch is off.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use serde::Serialize;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use super::runner::Reading;
+
+/// One stretch of a window in the foreground
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct Entry {
+    title: String,
+    /// RFC 3339, UTC
+    start: String,
+    seconds: u64,
+}
+
+/// The window in the foreground now, not yet an entry
+struct Current {
+    title: String,
+    start: OffsetDateTime,
+    since: Instant,
+}
+
+impl Current {
+    fn entry(&self, now: Instant) -> Entry {
+        Entry {
+            title: self.title.clone(),
+            start: self.start.format(&Rfc3339).unwrap_or_default(),
+            seconds: now.saturating_duration_since(self.since).as_secs(),
+        }
+    }
+}
+
+pub(super) struct WindowHistory {
+    size: usize,
+    /// Newest first
+    entries: VecDeque<Entry>,
+    current: Option<Current>,
+}
+
+impl WindowHistory {
+    pub(super) fn new(size: usize) -> Self {
+        Self {
+            size: size.max(1),
+            entries: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    pub(super) fn resize(&mut self, size: usize) {
+        self.size = size.max(1);
+        self.entries.truncate(self.size);
+    }
+
+    /// `title` got focus (empty: no window, e.g. the desktop). The window
+    /// before it becomes an entry.
+    pub(super) fn focus(&mut self, title: &str, start: OffsetDateTime, now: Instant) {
+        if let Some(current) = self.current.take() {
+            self.entries.push_front(current.entry(now));
+            self.entries.truncate(self.size);
+        }
+        if !title.is_empty() {
+            self.current = Some(Current {
+                title: title.to_string(),
+                start: start.replace_nanosecond(0).unwrap_or(start),
+                since: now,
+            });
+        }
+    }
+
+    /// The `window_history` reading, with the current window's time so far
+    /// as the first entry.
+    pub(super) fn reading(&self, now: Instant) -> Reading {
+        let entries: Vec<Entry> = self
+            .current
+            .iter()
+            .map(|current| current.entry(now))
+            .chain(self.entries.iter().cloned())
+            .take(self.size)
+            .collect();
+        Reading::new("window_history", entries.len().to_string())
+            .with_attributes(serde_json::json!({ "entries": entries }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_history_keeps_newest_with_durations() {
+        let t0 = Instant::now();
+        let start = OffsetDateTime::parse("2026-10-15T09:00:00.250Z", &Rfc3339).unwrap();
+        let mut history = WindowHistory::new(2);
+        history.focus("Editor", start, t0);
+        history.focus("Browser", start, t0 + Duration::from_secs(90));
+        history.focus("", start, t0 + Duration::from_secs(100));
+        history.focus("Terminal", start, t0 + Duration::from_secs(130));
+
+        let reading = history.reading(t0 + Duration::from_secs(145));
+        assert_eq!(
+            reading,
+            Reading::new("window_history", "2").with_attributes(serde_json::json!({
+                "entries": [
+                    { "title": "Terminal", "start": "2026-10-15T09:00:00Z", "seconds": 15 },
+                    { "title": "Browser", "start": "2026-10-15T09:00:00Z", "seconds": 10 },
+                ]
+            }))
+        );
+        assert_eq!(history.entries.len(), 2);
+        assert_eq!(history.entries[1].seconds, 90);
+
+        history.resize(1);
+        assert_eq!(history.entries.len(), 1);
+    }
+}
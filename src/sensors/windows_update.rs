@@ -0,0 +1,351 @@
+//! Windows Update status sensor
+//!
+//! Reports the number of pending (not installed, not hidden) software updates,
+//! with the titles and a reboot-required flag as attributes, polled every
+//! `intervals.windows_update`.
+//! - Windows Update Agent COM API (IUpdateSearcher + ISystemInformation)
+//! - Falls back to the PSWindowsUpdate PowerShell module when the COM search
+//!   fails (e.g. blocked by policy for a non-elevated agent)
+//!
+//! Installing is the separate, opt-in `WindowsUpdateInstall` command.
+
+use log::{debug, info};
+use std::sync::Arc;
+use tokio::time::{Duration, MissedTickBehavior, interval};
+
+use crate::AppState;
+
+/// Titles listed in the attributes; a fresh install can have 100+ pending.
+const MAX_LISTED: usize = 25;
+
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct UpdateStatus {
+    pub titles: Vec<String>,
+    pub reboot_required: bool,
+    /// "wua" or "pswindowsupdate"
+    pub source: &'static str,
+}
+
+impl UpdateStatus {
+    fn attributes(&self) -> serde_json::Value {
+        serde_json::json!({
+            "reboot_required": self.reboot_required,
+            "updates": self.titles.iter().take(MAX_LISTED).collect::<Vec<_>>(),
+            "source": self.source,
+        })
+    }
+}
+
+pub struct WindowsUpdateSensor {
+    state: Arc<AppState>,
+}
+
+impl WindowsUpdateSensor {
+    pub fn new(state: Arc<AppState>) -> Self {
+        Self { state }
+    }
+
+    pub async fn run(self) {
+        let config = self.state.config.read().await;
+        if !config.features.windows_update {
+            return;
+        }
+        let poll_secs = config.intervals.windows_update.max(60);
+        drop(config);
+
+        if cfg!(not(windows)) {
+            info!("Windows Update sensor enabled but this isn't Windows - skipping");
+            return;
+        }
+
+        let mut tick = interval(Duration::from_secs(poll_secs));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let mut shutdown_rx = self.state.shutdown_tx.subscribe();
+        let mut reconnect_rx = self.state.mqtt.subscribe_reconnect();
+        let activity_rx = crate::activity::subscribe();
+        let mut last: Option<(String, serde_json::Value)> = None;
+
+        info!("Windows Update sensor started (searched every {poll_secs}s)");
+
+        loop {
+            tokio::select! {
+                biased;
+                _ = shutdown_rx.recv() => {
+                    debug!("Windows Update sensor shutting down");
+                    break;
+                }
+                Ok(()) = reconnect_rx.recv() => {
+                    // Searching is expensive; republish the cached result instead.
+                    if let Some((state, attrs)) = &last {
+                        self.publish(state, attrs).await;
+                    }
+                }
+                _ = tick.tick() => {
+                    // A search is seconds to minutes of COM work: not mid-game.
+                    if activity_rx.borrow().is_paused() {
+                        continue;
+                    }
+                    let (state, attrs) = check().await;
+                    self.publish(&state, &attrs).await;
+                    last = Some((state, attrs));
+                }
+            }
+        }
+    }
+
+    async fn publish(&self, state: &str, attrs: &serde_json::Value) {
+        self.state
+            .mqtt
+            .publish_sensor("windows_updates", state)
+            .await;
+        self.state
+            .mqtt
+            .publish_sensor_attributes("windows_updates", attrs)
+            .await;
+    }
+}
+
+/// Search now and publish the result. Used after `WindowsUpdateInstall` so HA
+/// sees the new count without waiting for the next poll.
+#[cfg(windows)]
+pub(crate) async fn refresh(state: &AppState) {
+    if !state.config.read().await.features.windows_update {
+        return;
+    }
+    let (value, attrs) = check().await;
+    state.mqtt.publish_sensor("windows_updates", &value).await;
+    state
+        .mqtt
+        .publish_sensor_attributes("windows_updates", &attrs)
+        .await;
+}
+
+/// (state, attributes) for the sensor; "unavailable" if neither source works,
+/// rather than a misleading 0 pending.
+async fn check() -> (String, serde_json::Value) {
+    match query().await {
+        Some(status) => (status.titles.len().to_string(), status.attributes()),
+        None => ("unavailable".to_string(), serde_json::json!({})),
+    }
+}
+
+#[cfg(windows)]
+async fn query() -> Option<UpdateStatus> {
+    // A search can take minutes on a slow update server; keep it off the runtime.
+    match tokio::task::spawn_blocking(wua::search).await {
+        Ok(Ok(status)) => return Some(status),
+        Ok(Err(e)) => debug!("Windows Update Agent search failed ({e}) - trying PSWindowsUpdate"),
+        Err(e) => debug!("Windows Update search task failed: {e}"),
+    }
+    match query_pswindowsupdate().await {
+        Ok(status) => Some(status),
+        Err(e) => {
+            log::warn!("Windows Update status unavailable: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn query() -> Option<UpdateStatus> {
+    None
+}
+
+/// PSWindowsUpdate fallback. Prints one `U <title>` line per pending update
+/// and `REBOOT` if a reboot is pending.
+#[cfg(windows)]
+async fn query_pswindowsupdate() -> Result<UpdateStatus, String> {
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    const SCRIPT: &str = "Import-Module PSWindowsUpdate -ErrorAction Stop; \
+        Get-WindowsUpdate -ErrorAction Stop | ForEach-Object { 'U ' + $_.Title }; \
+        if (Get-WURebootStatus -Silent) { 'REBOOT' }";
+
+    // Same pattern as custom PowerShell sensors: kill_on_drop so a disabled
+    // sensor or the timeout doesn't leave powershell.exe behind.
+    let mut cmd = tokio::process::Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .creation_flags(CREATE_NO_WINDOW)
+        .kill_on_drop(true);
+    match tokio::time::timeout(Duration::from_mins(5), cmd.output()).await {
+        Ok(Ok(out)) if out.status.success() => {
+            Ok(parse_pswindowsupdate(&String::from_utf8_lossy(&out.stdout)))
+        }
+        Ok(Ok(out)) => Err(String::from_utf8_lossy(&out.stderr).trim().to_string()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("PSWindowsUpdate timed out".to_string()),
+    }
+}
+
+#[cfg_attr(unix, allow(dead_code))]
+fn parse_pswindowsupdate(output: &str) -> UpdateStatus {
+    let mut status = UpdateStatus {
+        source: "pswindowsupdate",
+        ..UpdateStatus::default()
+    };
+    for line in output.lines().map(str::trim) {
+        if let Some(title) = line.strip_prefix("U ") {
+            status.titles.push(title.trim().to_string());
+        } else if line == "REBOOT" {
+            status.reboot_required = true;
+        }
+    }
+    status
+}
+
+/// Windows Update Agent COM calls. Every function here blocks (searches and
+/// installs take minutes) and must run on a blocking thread.
+#[cfg(windows)]
+pub(crate) mod wua {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use windows::Win32::System::Com::{
+        CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED, CoCreateInstance, CoInitializeEx,
+    };
+    use windows::Win32::System::UpdateAgent::{
+        ISystemInformation, IUpdateCollection, IUpdateSession, SystemInformation, UpdateCollection,
+        UpdateSession,
+    };
+    use windows::core::BSTR;
+
+    use super::UpdateStatus;
+
+    const CRITERIA: &str = "IsInstalled=0 and IsHidden=0 and Type='Software'";
+    /// `OperationResultCode` values (wuapi.h)
+    const ORC_SUCCEEDED: i32 = 2;
+    const ORC_SUCCEEDED_WITH_ERRORS: i32 = 3;
+
+    /// Only one install at a time; a second press while one runs is refused.
+    static INSTALLING: AtomicBool = AtomicBool::new(false);
+
+    fn session() -> windows::core::Result<IUpdateSession> {
+        unsafe {
+            // MTA like the audio module; S_FALSE on repeat calls is fine.
+            let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+            let session: IUpdateSession =
+                CoCreateInstance(&UpdateSession, None, CLSCTX_INPROC_SERVER)?;
+            session.SetClientApplicationID(&BSTR::from("PC Bridge"))?;
+            Ok(session)
+        }
+    }
+
+    fn pending(session: &IUpdateSession) -> windows::core::Result<IUpdateCollection> {
+        unsafe {
+            session
+                .CreateUpdateSearcher()?
+                .Search(&BSTR::from(CRITERIA))?
+                .Updates()
+        }
+    }
+
+    fn reboot_required() -> bool {
+        unsafe {
+            CoCreateInstance::<_, ISystemInformation>(
+                &SystemInformation,
+                None,
+                CLSCTX_INPROC_SERVER,
+            )
+            .and_then(|info| info.RebootRequired())
+            .is_ok_and(|b| b.0 != 0)
+        }
+    }
+
+    pub(super) fn search() -> windows::core::Result<UpdateStatus> {
+        let updates = pending(&session()?)?;
+        let mut titles = Vec::new();
+        unsafe {
+            for i in 0..updates.Count()? {
+                titles.push(updates.get_Item(i)?.Title()?.to_string());
+            }
+        }
+        Ok(UpdateStatus {
+            titles,
+            reboot_required: reboot_required(),
+            source: "wua",
+        })
+    }
+
+    /// Download and install every pending update. Never reboots; the result
+    /// says whether one is needed. Needs an elevated agent.
+    pub(crate) fn install() -> Result<String, String> {
+        if INSTALLING.swap(true, Ordering::SeqCst) {
+            return Err("an install is already running".to_string());
+        }
+        let result = install_inner().map_err(|e| e.to_string());
+        INSTALLING.store(false, Ordering::SeqCst);
+        result
+    }
+
+    fn install_inner() -> windows::core::Result<String> {
+        let session = session()?;
+        let found = pending(&session)?;
+        unsafe {
+            let selected: IUpdateCollection =
+                CoCreateInstance(&UpdateCollection, None, CLSCTX_INPROC_SERVER)?;
+            for i in 0..found.Count()? {
+                let update = found.get_Item(i)?;
+                // Same as the Settings app: installing implies accepting.
+                if update.EulaAccepted()?.0 == 0 {
+                    update.AcceptEula()?;
+                }
+                selected.Add(&update)?;
+            }
+            let count = selected.Count()?;
+            if count == 0 {
+                return Ok("no pending updates".to_string());
+            }
+
+            let downloader = session.CreateUpdateDownloader()?;
+            downloader.SetUpdates(&selected)?;
+            downloader.Download()?;
+
+            let installer = session.CreateUpdateInstaller()?;
+            installer.SetUpdates(&selected)?;
+            let result = installer.Install()?;
+            let code = result.ResultCode()?.0;
+            let reboot = result.RebootRequired()?.0 != 0;
+            let outcome = match code {
+                ORC_SUCCEEDED => "installed",
+                ORC_SUCCEEDED_WITH_ERRORS => "installed with errors",
+                _ => "failed",
+            };
+            Ok(format!(
+                "{count} update(s) {outcome}{}",
+                if reboot { ", reboot required" } else { "" }
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pswindowsupdate() {
+        let status = parse_pswindowsupdate(
+            "U 2026-10 Cumulative Update for Windows 11 (KB5044284)\r\n\
+             U Security Intelligence Update for Microsoft Defender\r\n\
+             REBOOT\r\n",
+        );
+        assert_eq!(status.titles.len(), 2);
+        assert!(status.titles[0].ends_with("(KB5044284)"));
+        assert!(status.reboot_required);
+        assert_eq!(status.source, "pswindowsupdate");
+
+        let none = parse_pswindowsupdate("");
+        assert!(none.titles.is_empty() && !none.reboot_required);
+    }
+
+    #[test]
+    fn test_attributes_cap_listed_titles() {
+        let status = UpdateStatus {
+            titles: (0..40).map(|i| format!("Update {i}")).collect(),
+            reboot_required: true,
+            source: "wua",
+        };
+        let attrs = status.attributes();
+        assert_eq!(attrs["updates"].as_array().unwrap().len(), MAX_LISTED);
+        assert_eq!(attrs["reboot_required"], true);
+        assert_eq!(attrs["source"], "wua");
+    }
+}
@@ -6,9 +6,12 @@ use log::info;
 use std::io::{self, Write};
 use std::path::PathBuf;
 
+use crate::i18n::tr;
+
 /// Configuration collected from setup wizard
 #[derive(Debug)]
 pub struct SetupConfig {
+    pub language: String,
     pub device_name: String,
     pub mqtt_broker: String,
     pub mqtt_user: String,
@@ -27,6 +30,7 @@ pub struct SetupConfig {
 impl Default for SetupConfig {
     fn default() -> Self {
         Self {
+            language: crate::config::default_language(),
             device_name: get_default_device_name(),
             mqtt_broker: "tcp://homeassistant.local:1883".to_string(),
             mqtt_user: String::new(),
@@ -158,54 +162,78 @@ pub fn run_setup_wizard(existing_config: bool) -> Option<SetupConfig> {
 fn run_wizard_flow(existing_config: bool) -> Option<SetupConfig> {
     let mut config = SetupConfig::default();
 
+    // Language first: everything after it is shown in the chosen one
+    clear_screen();
+    print_header("Language");
+    let languages: Vec<_> = crate::i18n::languages().collect();
+    for (i, (_, name)) in languages.iter().enumerate() {
+        println!("  [{}] {name}", i + 1);
+    }
+    println!();
+    let input = read_input("  Language [1]: ");
+    if let Some((code, _)) = input
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| languages.get(n.checked_sub(1)?))
+    {
+        config.language = (*code).to_string();
+    }
+    crate::i18n::set_language(&config.language);
+
     // Warn if overwriting an existing configuration
     if existing_config {
         clear_screen();
-        print_header("Existing Config Found");
-        println!("  A configuration already exists.");
+        print_header(&tr("Existing Config Found"));
+        println!("  {}", tr("A configuration already exists."));
         println!();
-        println!("  Running setup will REPLACE your current");
-        println!("  settings including MQTT credentials,");
-        println!("  features, and device name.");
+        println!("  {}", tr("Running setup will REPLACE your current"));
+        println!("  {}", tr("settings including MQTT credentials,"));
+        println!("  {}", tr("features, and device name."));
         println!();
-        println!("  Game mappings and custom commands will");
-        println!("  be reset to defaults.");
+        println!("  {}", tr("Game mappings and custom commands will"));
+        println!("  {}", tr("be reset to defaults."));
         println!();
-        let input = read_input("  Continue? [y/N]: ");
+        let input = read_input(&format!("  {} [y/N]: ", tr("Continue?")));
         if !input.to_lowercase().starts_with('y') {
-            println!("\n  Setup cancelled.");
+            println!("\n  {}", tr("Setup cancelled."));
             return None;
         }
     }
 
     // Welcome
     clear_screen();
-    print_header("PC Bridge Setup");
-    println!("  Welcome! This wizard will configure your");
-    println!("  connection to Home Assistant via MQTT.");
+    print_header(&tr("PC Bridge Setup"));
+    println!("  {}", tr("Welcome! This wizard will configure your"));
+    println!("  {}", tr("connection to Home Assistant via MQTT."));
     println!();
-    println!("  Press Enter to continue...");
+    println!("  {}", tr("Press Enter to continue..."));
     read_input("");
 
     // Device name
     loop {
         clear_screen();
-        print_header("Device Name");
-        println!("  Enter a unique name for this PC.");
-        println!("  Used as the MQTT client ID and entity prefix.");
+        print_header(&tr("Device Name"));
+        println!("  {}", tr("Enter a unique name for this PC."));
+        println!("  {}", tr("Used as the MQTT client ID and entity prefix."));
         println!();
-        println!("  • Letters, digits, '.', '_', '-' only (no spaces)");
-        println!("  • Lowercase recommended");
+        println!(
+            "  {}",
+            tr("• Letters, digits, '.', '_', '-' only (no spaces)")
+        );
+        println!("  {}", tr("• Lowercase recommended"));
         println!();
-        let input = read_input(&format!("  Name [{}]: ", config.device_name));
+        let input = read_input(&format!("  {} [{}]: ", tr("Name"), config.device_name));
 
         if input.is_empty() {
             break; // Use default
         } else if !input.chars().all(is_valid_device_char) {
             // Match Config::validate so the wizard can't produce a name that
             // then fails at save time (losing everything the user entered).
-            println!("\n  Name may only contain letters, digits, '.', '_', and '-'!");
-            read_input("  Press Enter to try again...");
+            println!(
+                "\n  {}",
+                tr("Name may only contain letters, digits, '.', '_', and '-'!")
+            );
+            read_input(&format!("  {}", tr("Press Enter to try again...")));
         } else {
             config.device_name = input;
             break;
@@ -214,94 +242,103 @@ fn run_wizard_flow(existing_config: bool) -> Option<SetupConfig> {
 
     // MQTT Broker
     clear_screen();
-    print_header("MQTT Broker");
-    println!("  Enter your MQTT broker address.");
+    print_header(&tr("MQTT Broker"));
+    println!("  {}", tr("Enter your MQTT broker address."));
     println!();
-    println!("  Examples:");
+    println!("  {}", tr("Examples:"));
     println!("    tcp://homeassistant.local:1883");
     println!("    tcp://192.168.1.100:1883");
     println!();
-    let input = read_input(&format!("  Broker [{}]: ", config.mqtt_broker));
+    let input = read_input(&format!("  {} [{}]: ", tr("Broker"), config.mqtt_broker));
     if !input.is_empty() {
         config.mqtt_broker = input;
     }
 
     // MQTT Username
     clear_screen();
-    print_header("MQTT Authentication");
-    println!("  Enter MQTT credentials if required.");
-    println!("  Leave blank for anonymous access.");
+    print_header(&tr("MQTT Authentication"));
+    println!("  {}", tr("Enter MQTT credentials if required."));
+    println!("  {}", tr("Leave blank for anonymous access."));
     println!();
-    let input = read_input("  Username: ");
+    let input = read_input(&format!("  {}: ", tr("Username")));
     config.mqtt_user = input;
 
     // MQTT Password (only if username provided)
     if !config.mqtt_user.is_empty() {
         println!();
-        println!("  (input is hidden)");
-        match rpassword::prompt_password("  Password: ") {
+        println!("  {}", tr("(input is hidden)"));
+        let prompt = format!("  {}: ", tr("Password"));
+        match rpassword::prompt_password(&prompt) {
             Ok(pass) => config.mqtt_pass = pass,
-            Err(_) => config.mqtt_pass = read_input("  Password: "),
+            Err(_) => config.mqtt_pass = read_input(&prompt),
         }
     }
 
     // Feature selection with toggle menu
     loop {
         clear_screen();
-        print_header("Features");
-        println!("  Select which features to enable.");
-        println!("  Type a number to toggle, Enter when done.");
+        print_header(&tr("Features"));
+        println!("  {}", tr("Select which features to enable."));
+        println!("  {}", tr("Type a number to toggle, Enter when done."));
         println!();
         println!(
-            "  [1] [{}] Game Detection",
-            if config.game_detection { "*" } else { " " }
+            "  [1] [{}] {}",
+            if config.game_detection { "*" } else { " " },
+            tr("Game Detection")
         );
-        println!("      Detect running games, report to HA");
+        println!("      {}", tr("Detect running games, report to HA"));
         println!();
         println!(
-            "  [2] [{}] Idle Tracking",
-            if config.idle_tracking { "*" } else { " " }
+            "  [2] [{}] {}",
+            if config.idle_tracking { "*" } else { " " },
+            tr("Idle Tracking")
         );
-        println!("      Track keyboard/mouse activity");
+        println!("      {}", tr("Track keyboard/mouse activity"));
         println!();
         println!(
-            "  [3] [{}] Power Events",
-            if config.power_events { "*" } else { " " }
+            "  [3] [{}] {}",
+            if config.power_events { "*" } else { " " },
+            tr("Power Events")
         );
-        println!("      Report sleep/wake/shutdown");
+        println!("      {}", tr("Report sleep/wake/shutdown"));
         println!();
         println!(
-            "  [4] [{}] Notifications",
-            if config.notifications { "*" } else { " " }
+            "  [4] [{}] {}",
+            if config.notifications { "*" } else { " " },
+            tr("Notifications")
         );
-        println!("      Receive toast notifications from HA");
+        println!("      {}", tr("Receive toast notifications from HA"));
         println!();
         println!(
-            "  [5] [{}] System Sensors",
-            if config.system_sensors { "*" } else { " " }
+            "  [5] [{}] {}",
+            if config.system_sensors { "*" } else { " " },
+            tr("System Sensors")
         );
-        println!("      CPU, memory, battery, active window");
+        println!("      {}", tr("CPU, memory, battery, active window"));
         println!();
         println!(
-            "  [6] [{}] Audio Control",
-            if config.audio_control { "*" } else { " " }
+            "  [6] [{}] {}",
+            if config.audio_control { "*" } else { " " },
+            tr("Audio Control")
         );
-        println!("      Volume, mute, media keys");
+        println!("      {}", tr("Volume, mute, media keys"));
         println!();
         println!(
-            "  [7] [{}] Steam Updates",
-            if config.steam_updates { "*" } else { " " }
+            "  [7] [{}] {}",
+            if config.steam_updates { "*" } else { " " },
+            tr("Steam Updates")
         );
-        println!("      Detect when Steam games are updating");
+        println!("      {}", tr("Detect when Steam games are updating"));
         println!();
         println!(
-            "  [8] [{}] Discord Integration",
-            if config.discord { "*" } else { " " }
+            "  [8] [{}] {}",
+            if config.discord { "*" } else { " " },
+            tr("Discord Integration")
         );
-        println!("      Leave voice channel via HA command");
+        println!("      {}", tr("Leave voice channel via HA command"));
         println!();
 
-        let input = read_input("  Toggle (1-8) or Enter to continue: ");
+        let input = read_input(&format!("  {} ", tr("Toggle (1-8) or Enter to continue:")));
 
         match input.as_str() {
             "1" => config.game_detection = !config.game_detection,
@@ -320,13 +357,16 @@ fn run_wizard_flow(existing_config: bool) -> Option<SetupConfig> {
     // Discord keybind prompt (only if discord was enabled)
     if config.discord {
         clear_screen();
-        print_header("Discord Keybind");
-        println!("  Enter the keybind for leaving a voice channel.");
-        println!("  This must match your Discord keybind setting.");
+        print_header(&tr("Discord Keybind"));
+        println!("  {}", tr("Enter the keybind for leaving a voice channel."));
+        println!("  {}", tr("This must match your Discord keybind setting."));
         println!();
-        println!("  Format: modifier+key (e.g. ctrl+f6, ctrl+shift+m)");
+        println!(
+            "  {}",
+            tr("Format: modifier+key (e.g. ctrl+f6, ctrl+shift+m)")
+        );
         println!();
-        let input = read_input("  Keybind [ctrl+f6]: ");
+        let input = read_input(&format!("  {} [ctrl+f6]: ", tr("Keybind")));
         if !input.is_empty() {
             config.discord_keybind = input.to_lowercase();
         }
@@ -334,54 +374,64 @@ fn run_wizard_flow(existing_config: bool) -> Option<SetupConfig> {
 
     // Confirmation
     clear_screen();
-    print_header("Confirm Configuration");
-    println!("  Device:   {}", config.device_name);
-    println!("  Broker:   {}", config.mqtt_broker);
+    print_header(&tr("Confirm Configuration"));
+    let label = |text: &str| format!("{}:", tr(text));
+    println!("  {:<10}{}", label("Device"), config.device_name);
+    println!("  {:<10}{}", label("Broker"), config.mqtt_broker);
     println!(
-        "  Auth:     {}",
-        if config.mqtt_user.is_empty() {
+        "  {:<10}{}",
+        label("Auth"),
+        tr(if config.mqtt_user.is_empty() {
             "none"
         } else {
             "configured"
-        }
+        })
     );
     println!();
-    println!("  Features:");
+    println!("  {}", tr("Features:"));
     println!(
-        "    [{}] Game Detection",
-        if config.game_detection { "x" } else { " " }
+        "    [{}] {}",
+        if config.game_detection { "x" } else { " " },
+        tr("Game Detection")
     );
     println!(
-        "    [{}] Idle Tracking",
-        if config.idle_tracking { "x" } else { " " }
+        "    [{}] {}",
+        if config.idle_tracking { "x" } else { " " },
+        tr("Idle Tracking")
     );
     println!(
-        "    [{}] Power Events",
-        if config.power_events { "x" } else { " " }
+        "    [{}] {}",
+        if config.power_events { "x" } else { " " },
+        tr("Power Events")
     );
     println!(
-        "    [{}] Notifications",
-        if config.notifications { "x" } else { " " }
+        "    [{}] {}",
+        if config.notifications { "x" } else { " " },
+        tr("Notifications")
     );
     println!(
-        "    [{}] System Sensors",
-        if config.system_sensors { "x" } else { " " }
+        "    [{}] {}",
+        if config.system_sensors { "x" } else { " " },
+        tr("System Sensors")
     );
     println!(
-        "    [{}] Audio Control",
-        if config.audio_control { "x" } else { " " }
+        "    [{}] {}",
+        if config.audio_control { "x" } else { " " },
+        tr("Audio Control")
     );
     println!(
-        "    [{}] Steam Updates",
-        if config.steam_updates { "x" } else { " " }
+        "    [{}] {}",
+        if config.steam_updates { "x" } else { " " },
+        tr("Steam Updates")
     );
     println!(
-        "    [{}] Discord Integration",
-        if config.discord { "x" } else { " " }
+        "    [{}] {}",
+        if config.discord { "x" } else { " " },
+        tr("Discord Integration")
     );
     println!();
 
-    let input = read_input("  Save configuration? [Y/n]: ");
+    let input = read_input(&format!("  {} [Y/n]: ", tr("Save configuration?")));
     let confirmed = input.is_empty() || input.to_lowercase().starts_with('y');
 
     if confirmed { Some(config) } else { None }
@@ -389,17 +439,42 @@ fn run_wizard_flow(existing_config: bool) -> Option<SetupConfig> {
 
 /// Save the setup configuration to disk
 pub fn save_setup_config(config: &SetupConfig) -> std::io::Result<PathBuf> {
-    use crate::config::{Config, FeatureConfig, IntervalConfig, MqttConfig};
+    use crate::config::{
+        CommandQueueConfig, Config, DockerConfig, FeatureConfig, FocusConfig, GameLimitsConfig,
+        HaApiConfig, HttpApiConfig, IdleConfig, IntervalConfig, MqttConfig, NotificationConfig,
+        OpenRgbConfig, ShutdownGuardConfig, ThermalGuardConfig,
+    };
     use std::collections::HashMap;
 
     let full_config = Config {
         device_name: config.device_name.clone(),
+        suggested_area: None,
+        group: None,
+        language: config.language.clone(),
         mqtt: MqttConfig {
             broker: config.mqtt_broker.clone(),
             user: config.mqtt_user.clone(),
             pass: config.mqtt_pass.clone(),
             client_id: None,
+            protocol_version: 4,
+            session_expiry_secs: 3600,
+            availability_expiry_secs: 0,
+            keep_alive_secs: 30,
+            connect_timeout_secs: 5,
+            tcp_nodelay: false,
+            bundled_state: false,
         },
+        ha_api: HaApiConfig::default(),
+        http_api: HttpApiConfig::default(),
+        openrgb: OpenRgbConfig::default(),
+        command_queue: CommandQueueConfig::default(),
+        notifications: NotificationConfig::default(),
+        idle: IdleConfig::default(),
+        shutdown_guard: ShutdownGuardConfig::default(),
+        game_limits: GameLimitsConfig::default(),
+        thermal_guard: ThermalGuardConfig::default(),
+        focus: FocusConfig::default(),
+        window_history_size: 20,
         intervals: IntervalConfig::default(),
         features: FeatureConfig {
             running_game: config.game_detection,
@@ -421,6 +496,7 @@ pub fn save_setup_config(config: &SetupConfig) -> std::io::Result<PathBuf> {
             memory_sensor: config.system_sensors,
             active_window: config.system_sensors,
             session_state: false,
+            remote_desktop: false,
             audio_device: false,
             mic: false,
             webcam: false,
@@ -434,11 +510,43 @@ pub fn save_setup_config(config: &SetupConfig) -> std::io::Result<PathBuf> {
             disk_sensor: false,
             uptime_sensor: false,
             hwinfo_sensor: false,
+            windows_update: false,
+            cmd_windows_update_install: false,
+            wake_timers: false,
+            sleep_blockers: false,
+            power_plan: false,
+            device_batteries: false,
+            gamepad: false,
+            vr: false,
+            transcoding: false,
+            network_connection: false,
+            disk_health: false,
+            latency: false,
+            public_ip: false,
+            printers: false,
+            usb_devices: false,
+            docker: false,
+            media_playing: false,
+            display_mode: false,
+            game_art: false,
+            theme: false,
+            openrgb: false,
+            perf_mode: false,
+            window_history: false,
+            focus_mode: false,
+            cmd_speedtest: false,
+            cmd_disk_cleanup: false,
+            cmd_install_update: false,
+            cmd_config_backup: false,
+            cmd_file_transfer: false,
+            cmd_wallpaper: false,
         },
         games: HashMap::new(),
+        emulators: HashMap::new(),
         custom_sensors_enabled: false,
         custom_commands_enabled: false,
         custom_command_privileges_allowed: false,
+        scripts_enabled: false,
         allow_raw_commands: false,
         allow_global_launch: true,
         allow_global_close: false,
@@ -450,7 +558,21 @@ pub fn save_setup_config(config: &SetupConfig) -> std::io::Result<PathBuf> {
         },
         custom_sensors: Vec::new(),
         custom_commands: Vec::new(),
+        process_alerts: Vec::new(),
+        process_sensors: Vec::new(),
+        automations: Vec::new(),
+        wol_relay: Vec::new(),
+        display_modes: Vec::new(),
+        speedtest_command: Vec::new(),
+        printers: Vec::new(),
+        usb_devices: Vec::new(),
+        docker: DockerConfig::default(),
+        plugins: Vec::new(),
+        entity_overrides: HashMap::new(),
+        deadbands: HashMap::new(),
         update_channel: crate::config::default_update_channel(),
+        auto_update: true,
+        crash_notify: false,
         disk_sensor_paths: Vec::new(),
     };
 
@@ -0,0 +1,267 @@
+//! On-demand speed test
+//!
+//! The `Speedtest` command measures download, upload and ping and publishes
+//! them to `speedtest_download` / `speedtest_upload` (Mbit/s) and
+//! `speedtest_ping` (ms); the download sensor's attributes name the server.
+//! A failed test publishes them unavailable. Built in: HTTP against
+//! Cloudflare's speed test endpoints - ping is the fastest of a few empty
+//! requests on a warm connection, then a 25 MB download and a 10 MB upload.
+//! With `speedtest_command` set the agent runs that CLI instead and reads its
+//! JSON (Ookla `speedtest --format=json` or speedtest-cli `--json`).
+
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::{info, warn};
+
+use crate::AppState;
+
+const DOWN_URL: &str = "https://speed.cloudflare.com/__down";
+const UP_URL: &str = "https://speed.cloudflare.com/__up";
+const DOWNLOAD_BYTES: u64 = 25_000_000;
+const UPLOAD_BYTES: usize = 10_000_000;
+const PINGS: usize = 5;
+
+/// The download stops here on slow links; the rate is from what arrived.
+const DOWNLOAD_FOR: Duration = Duration::from_secs(15);
+
+/// Bound on a whole test, built-in or CLI.
+const TIMEOUT: Duration = Duration::from_mins(2);
+
+/// A test is in flight: a second press is ignored rather than halving both.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, PartialEq)]
+struct Speed {
+    download_mbps: f64,
+    upload_mbps: f64,
+    ping_ms: f64,
+    server: String,
+}
+
+/// The `Speedtest` command. Runs to completion (tens of seconds), so the
+/// executor spawns it detached.
+pub(crate) async fn run(state: &AppState) {
+    if RUNNING.swap(true, Ordering::AcqRel) {
+        warn!("Speedtest: a test is already running");
+        return;
+    }
+    let command = state.config.read().await.speedtest_command.clone();
+    info!("Speedtest started");
+    let result = if command.is_empty() {
+        tokio::task::spawn_blocking(built_in)
+            .await
+            .unwrap_or_else(|e| Err(anyhow!("test task failed: {e}")))
+    } else {
+        run_cli(&command).await
+    };
+    RUNNING.store(false, Ordering::Release);
+
+    let mqtt = &state.mqtt;
+    match result {
+        Ok(speed) => {
+            info!(
+                "Speedtest: {:.1} down / {:.1} up Mbit/s, {:.1} ms ({})",
+                speed.download_mbps, speed.upload_mbps, speed.ping_ms, speed.server
+            );
+            mqtt.publish_sensor("speedtest_download", &format!("{:.1}", speed.download_mbps))
+                .await;
+            mqtt.publish_sensor("speedtest_upload", &format!("{:.1}", speed.upload_mbps))
+                .await;
+            mqtt.publish_sensor("speedtest_ping", &format!("{:.1}", speed.ping_ms))
+                .await;
+            mqtt.publish_sensor_attributes(
+                "speedtest_download",
+                &serde_json::json!({
+                    "server": speed.server,
+                    "method": if command.is_empty() { "built-in" } else { "command" },
+                }),
+            )
+            .await;
+        }
+        Err(e) => {
+            warn!("Speedtest failed: {e:#}");
+            for name in ["speedtest_download", "speedtest_upload", "speedtest_ping"] {
+                mqtt.publish_sensor(name, "unavailable").await;
+            }
+        }
+    }
+}
+
+fn http_agent() -> ureq::Agent {
+    use ureq::tls::{RootCerts, TlsConfig, TlsProvider};
+
+    let tls = TlsConfig::builder()
+        .provider(TlsProvider::NativeTls)
+        .root_certs(RootCerts::PlatformVerifier)
+        .build();
+    let config = ureq::Agent::config_builder()
+        .tls_config(tls)
+        .timeout_global(Some(TIMEOUT))
+        .build();
+    ureq::Agent::new_with_config(config)
+}
+
+/// Megabits per second for `bytes` moved in `elapsed`.
+fn mbps(bytes: u64, elapsed: Duration) -> f64 {
+    bytes as f64 * 8.0 / elapsed.as_secs_f64().max(1e-3) / 1e6
+}
+
+fn built_in() -> Result<Speed> {
+    let agent = http_agent();
+    let empty = format!("{DOWN_URL}?bytes=0");
+
+    // The first request pays for DNS, TCP and TLS; the rest reuse it.
+    let mut first = agent.get(&empty).call().context("ping")?;
+    first.body_mut().read_to_vec().context("ping")?;
+    let server = first
+        .headers()
+        .get("cf-ray")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|ray| ray.rsplit_once('-'))
+        .map(|(_, colo)| format!("Cloudflare {colo}"))
+        .unwrap_or_else(|| "Cloudflare".to_string());
+    let mut ping = Duration::MAX;
+    for _ in 0..PINGS {
+        let start = Instant::now();
+        agent
+            .get(&empty)
+            .call()
+            .context("ping")?
+            .body_mut()
+            .read_to_vec()
+            .context("ping")?;
+        ping = ping.min(start.elapsed());
+    }
+
+    let start = Instant::now();
+    let mut response = agent
+        .get(&format!("{DOWN_URL}?bytes={DOWNLOAD_BYTES}"))
+        .call()
+        .context("download")?;
+    let mut reader = response.body_mut().as_reader();
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut received = 0u64;
+    while start.elapsed() < DOWNLOAD_FOR {
+        match reader.read(&mut buf).context("download")? {
+            0 => break,
+            n => received += n as u64,
+        }
+    }
+    if received == 0 {
+        bail!("download: empty response");
+    }
+    let download_mbps = mbps(received, start.elapsed());
+
+    let payload = vec![0u8; UPLOAD_BYTES];
+    let start = Instant::now();
+    agent
+        .post(UP_URL)
+        .header("Content-Type", "application/octet-stream")
+        .send(&payload[..])
+        .context("upload")?;
+    let upload_mbps = mbps(UPLOAD_BYTES as u64, start.elapsed());
+
+    Ok(Speed {
+        download_mbps,
+        upload_mbps,
+        ping_ms: ping.as_secs_f64() * 1000.0,
+        server,
+    })
+}
+
+async fn run_cli(command: &[String]) -> Result<Speed> {
+    let mut cmd = tokio::process::Command::new(&command[0]);
+    cmd.args(&command[1..]).kill_on_drop(true);
+    #[cfg(windows)]
+    {
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    let out = tokio::time::timeout(TIMEOUT, cmd.output())
+        .await
+        .map_err(|_| anyhow!("'{}' timed out", command[0]))?
+        .with_context(|| format!("running '{}'", command[0]))?;
+    if !out.status.success() {
+        bail!(
+            "'{}' failed: {}",
+            command[0],
+            String::from_utf8_lossy(&out.stderr).trim()
+        );
+    }
+    parse_cli_output(&String::from_utf8_lossy(&out.stdout))
+        .ok_or_else(|| anyhow!("'{}' printed no speed test JSON", command[0]))
+}
+
+/// The result line of an Ookla or speedtest-cli JSON run. Ookla reports
+/// bytes/s under `download.bandwidth`, speedtest-cli bits/s as `download`.
+fn parse_cli_output(stdout: &str) -> Option<Speed> {
+    let v: serde_json::Value = stdout
+        .lines()
+        .rev()
+        .map(str::trim)
+        .filter(|line| line.starts_with('{'))
+        .find_map(|line| serde_json::from_str(line).ok())?;
+    if let Some(down) = v["download"]["bandwidth"].as_f64() {
+        return Some(Speed {
+            download_mbps: down * 8.0 / 1e6,
+            upload_mbps: v["upload"]["bandwidth"].as_f64()? * 8.0 / 1e6,
+            ping_ms: v["ping"]["latency"].as_f64()?,
+            server: v["server"]["name"].as_str().unwrap_or_default().to_string(),
+        });
+    }
+    Some(Speed {
+        download_mbps: v["download"].as_f64()? / 1e6,
+        upload_mbps: v["upload"].as_f64()? / 1e6,
+        ping_ms: v["ping"].as_f64()?,
+        server: v["server"]["sponsor"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn test_parse_ookla() {
+        let out = r#"{"type":"result","ping":{"jitter":0.5,"latency":11.2},"download":{"bandwidth":12500000,"bytes":150000000},"upload":{"bandwidth":2500000},"server":{"id":1,"name":"Example ISP"}}"#;
+        let speed = parse_cli_output(out).unwrap();
+        assert!(close(speed.download_mbps, 100.0));
+        assert!(close(speed.upload_mbps, 20.0));
+        assert!(close(speed.ping_ms, 11.2));
+        assert_eq!(speed.server, "Example ISP");
+    }
+
+    #[test]
+    fn test_parse_speedtest_cli() {
+        let out = "Retrieving speedtest.net configuration...\n\
+                   {\"download\": 93500000.0, \"upload\": 18000000.0, \"ping\": 14.7, \"server\": {\"sponsor\": \"Example\"}}\n";
+        let speed = parse_cli_output(out).unwrap();
+        assert!(close(speed.download_mbps, 93.5));
+        assert!(close(speed.upload_mbps, 18.0));
+        assert!(close(speed.ping_ms, 14.7));
+        assert_eq!(speed.server, "Example");
+    }
+
+    #[test]
+    fn test_parse_cli_rejects_other_output() {
+        assert!(parse_cli_output("").is_none());
+        assert!(parse_cli_output("Speedtest by Ookla\n  Download: 100 Mbps").is_none());
+        assert!(parse_cli_output(r#"{"download": "fast"}"#).is_none());
+    }
+
+    #[test]
+    fn test_mbps() {
+        assert!(close(mbps(12_500_000, Duration::from_secs(1)), 100.0));
+        assert!(close(mbps(25_000_000, Duration::from_secs(4)), 50.0));
+    }
+}
@@ -2,17 +2,29 @@
 //!
 //! Memory-mapped, indexed access to Steam's app metadata cache.
 //! Only parses entries we actually need (installed games).
+//!
+//! Valve bumps the format every year or two. Versions 27-29 are known; a newer
+//! one is read with the latest known layout, and the layout is checked against
+//! the first entry (its VDF must open with the root "appinfo" block) so a
+//! shifted field shows up in the log instead of as silently empty lookups.
 
 #![allow(dead_code)]
 
+use log::{debug, info, warn};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, Read, Seek, SeekFrom};
 use std::path::Path;
 
-/// appinfo.vdf header magic
+/// appinfo.vdf header magic: the version in the low byte over `DV\x07`
+const APPINFO_MAGIC_V27: u32 = 0x07564427; // "'DV\x07" - version 27
 const APPINFO_MAGIC_V28: u32 = 0x07564428; // '(DV\x07' - version 28
-const APPINFO_MAGIC_V29: u32 = 0x07564429; // '(DV\x07' - version 29
+const APPINFO_MAGIC_V29: u32 = 0x07564429; // ')DV\x07' - version 29
+const APPINFO_MAGIC_BASE: u32 = 0x07564400;
+
+/// Oldest and newest formats whose layout is known
+const OLDEST_VERSION: u32 = 27;
+const LATEST_VERSION: u32 = 29;
 
 /// Binary VDF type markers
 const TYPE_BLOCK_START: u8 = 0x00;
@@ -27,11 +39,58 @@ pub struct AppInfoEntry {
     pub size: u32,
 }
 
+/// How an app entry is laid out in one format version
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Layout {
+    /// Fixed fields between an entry's size and its binary VDF:
+    ///   infoState(4) lastUpdated(4) picsToken(8) textVdfSha1(20) changeNumber(4) = 40
+    /// and v28+ adds a 20-byte binary-VDF SHA-1 -> 60
+    metadata: usize,
+    /// v29+: the header has a string-table offset and VDF keys are u32 indices
+    /// into that table; before, keys are inline null-terminated strings
+    indexed_keys: bool,
+}
+
+impl Layout {
+    fn for_version(version: u32) -> Self {
+        Self {
+            metadata: if version >= 28 { 60 } else { 40 },
+            indexed_keys: version >= 29,
+        }
+    }
+
+    /// magic(4) + universe(4), plus the i64 string-table offset on v29+
+    fn header_size(self) -> u64 {
+        if self.indexed_keys { 16 } else { 8 }
+    }
+}
+
+/// The format version in an appinfo.vdf magic. The low byte counts up from
+/// 0x27 = v27 (0x29 is v29, 0x2a would be v30). A version newer than
+/// [`LATEST_VERSION`] is accepted (and read as the latest); anything else is
+/// not an appinfo.vdf we can read.
+fn sniff_version(magic: u32) -> io::Result<u32> {
+    if magic & !0xff != APPINFO_MAGIC_BASE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("not an appinfo.vdf (magic {magic:#010x})"),
+        ));
+    }
+    match magic.checked_sub(APPINFO_MAGIC_V27) {
+        Some(newer) => Ok(OLDEST_VERSION + newer),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("appinfo.vdf magic {magic:#010x} is older than v{OLDEST_VERSION}"),
+        )),
+    }
+}
+
 /// Indexed appinfo.vdf reader
 pub struct AppInfoReader {
     file: File,
     index: HashMap<u32, AppInfoEntry>,
     version: u32,
+    layout: Layout,
     /// v29 string table (binary-VDF key names). Empty on v28, where keys are
     /// stored inline as null-terminated strings.
     string_table: Vec<String>,
@@ -49,17 +108,14 @@ impl AppInfoReader {
         let mut header = [0u8; 4];
         file.read_exact(&mut header)?;
 
-        let magic = u32::from_le_bytes(header);
-        let version = match magic {
-            APPINFO_MAGIC_V28 => 28,
-            APPINFO_MAGIC_V29 => 29,
-            _ => {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "Unknown appinfo.vdf version",
-                ));
-            }
-        };
+        let version = sniff_version(u32::from_le_bytes(header))?;
+        if version > LATEST_VERSION {
+            warn!(
+                "Steam: appinfo.vdf version {version} is newer than the latest known \
+                 ({LATEST_VERSION}); reading it with the v{LATEST_VERSION} layout"
+            );
+        }
+        let mut layout = Layout::for_version(version.min(LATEST_VERSION));
 
         // Universe (4 bytes).
         file.seek(SeekFrom::Current(4))?;
@@ -67,7 +123,7 @@ impl AppInfoReader {
         // v29 added an i64 string-table offset right after universe, and moved
         // binary-VDF key names into a string table at that offset (each key is
         // now a u32 index instead of an inline string). v28 has neither.
-        let string_table = if version >= 29 {
+        let string_table = if layout.indexed_keys {
             let mut buf8 = [0u8; 8];
             file.read_exact(&mut buf8)?;
             let st_offset = i64::from_le_bytes(buf8);
@@ -80,17 +136,64 @@ impl AppInfoReader {
             Vec::new()
         };
 
-        let index = Self::build_index(&mut file, version)?;
+        let index = Self::build_index(&mut file, version, layout)?;
+        if let Some(found) = Self::sniff_layout(&mut file, &index, layout, &string_table) {
+            if found != layout {
+                warn!(
+                    "Steam: appinfo.vdf v{version} entries have {} bytes of metadata, not the \
+                     expected {}; using {}",
+                    found.metadata, layout.metadata, found.metadata
+                );
+            }
+            layout = found;
+        } else if !index.is_empty() {
+            warn!(
+                "Steam: can't find the app data in appinfo.vdf v{version} entries; game names \
+                 and executables will come from the appmanifests"
+            );
+        }
 
         Ok(Self {
             file,
             index,
             version,
+            layout,
             string_table,
             read_buf: Vec::new(),
         })
     }
 
+    /// The layout whose VDF offset lands on the root "appinfo" block in the
+    /// first entry: the expected one, else the other known metadata size.
+    /// None when neither does (or the file has no entries).
+    fn sniff_layout(
+        file: &mut File,
+        index: &HashMap<u32, AppInfoEntry>,
+        expected: Layout,
+        string_table: &[String],
+    ) -> Option<Layout> {
+        let entry = index.values().min_by_key(|e| e.offset)?;
+        let mut data = vec![0u8; (entry.size as usize).min(256)];
+        file.seek(SeekFrom::Start(entry.offset)).ok()?;
+        file.read_exact(&mut data).ok()?;
+        [expected.metadata, 60, 40]
+            .into_iter()
+            .map(|metadata| Layout {
+                metadata,
+                ..expected
+            })
+            .find(|layout| {
+                data.get(layout.metadata..).is_some_and(|vdf| {
+                    let mut reader = BinaryVdfReader::new(vdf, string_table, layout.indexed_keys);
+                    matches!(
+                        reader.next_kv(),
+                        Some(("appinfo", BinaryVdfValue::BlockStart))
+                    )
+                })
+            })
+            .inspect(|layout| debug!("Steam: appinfo.vdf layout {layout:?}"))
+    }
+
     /// Read the v29 string table at `offset`: a u32 count followed by that many
     /// null-terminated UTF-8 strings, running to end of file.
     fn read_string_table(file: &mut File, offset: u64) -> io::Result<Vec<String>> {
@@ -124,14 +227,15 @@ impl AppInfoReader {
     ///
     /// This is the hot path. We read sequentially (cache-friendly) and only
     /// store app_id -> offset mapping. No parsing of actual content yet.
-    fn build_index(file: &mut File, version: u32) -> io::Result<HashMap<u32, AppInfoEntry>> {
-        use log::info;
-
+    fn build_index(
+        file: &mut File,
+        version: u32,
+        layout: Layout,
+    ) -> io::Result<HashMap<u32, AppInfoEntry>> {
         let file_size = file.seek(SeekFrom::End(0))?;
         // Header is magic(4)+universe(4)=8 on v28; v29 adds an i64 string-table
         // offset -> 16. Seeking to the wrong one reads app entries mid-field.
-        let header_size: u64 = if version >= 29 { 16 } else { 8 };
-        file.seek(SeekFrom::Start(header_size))?;
+        file.seek(SeekFrom::Start(layout.header_size()))?;
         info!(
             "Steam: appinfo.vdf size={} bytes, version={}",
             file_size, version
@@ -189,7 +293,7 @@ impl AppInfoReader {
 
             // Log first few entries for debugging
             if index.len() <= 5 {
-                info!("  app_id={} offset={} size={}", app_id, entry_start, size);
+                debug!("  app_id={} offset={} size={}", app_id, entry_start, size);
             }
 
             // Skip to next entry (size bytes after the size field)
@@ -218,11 +322,10 @@ impl AppInfoReader {
         };
 
         // `offset` already points past app_id+size (build_index stored
-        // entry_start+8). Between it and the binary VDF is the fixed metadata:
-        //   infoState(4) lastUpdated(4) picsToken(8) textVdfSha1(20) changeNumber(4) = 40
-        // and v29+ adds a 20-byte binary-VDF hash -> 60. (No extra +8 here: that
-        // was double-counting the app_id+size already baked into `offset`.)
-        let metadata = if self.version >= 29 { 60 } else { 40 };
+        // entry_start+8); the fixed metadata (see `Layout`) follows. (No extra
+        // +8 here: that was double-counting the app_id+size already baked
+        // into `offset`.)
+        let metadata = self.layout.metadata;
         if size <= metadata {
             return None;
         }
@@ -238,7 +341,7 @@ impl AppInfoReader {
         Self::parse_game_info(
             &self.read_buf[..vdf_len],
             &self.string_table,
-            self.version >= 29,
+            self.layout.indexed_keys,
         )
     }
 
@@ -419,28 +522,12 @@ impl<'a> BinaryVdfReader<'a> {
                 self.pos += 4;
                 self.string_table.get(idx).map(String::as_str)?
             } else {
-                let key_start = self.pos;
-                while self.pos < self.data.len() && self.data[self.pos] != 0 {
-                    self.pos += 1;
-                }
-                let k = std::str::from_utf8(&self.data[key_start..self.pos]).ok()?;
-                self.pos += 1; // Skip null terminator
-                k
+                self.read_cstr()?
             };
 
             let value = match type_byte {
                 TYPE_BLOCK_START => BinaryVdfValue::BlockStart,
-                TYPE_STRING => {
-                    let str_start = self.pos;
-                    while self.pos < self.data.len() && self.data[self.pos] != 0 {
-                        self.pos += 1;
-                    }
-                    let s = std::str::from_utf8(&self.data[str_start..self.pos])
-                        .ok()?
-                        .to_string();
-                    self.pos += 1;
-                    BinaryVdfValue::String(s)
-                }
+                TYPE_STRING => BinaryVdfValue::String(self.read_cstr()?.to_string()),
                 TYPE_INT32 => {
                     if self.pos + 4 > self.data.len() {
                         return None;
@@ -476,6 +563,16 @@ impl<'a> BinaryVdfReader<'a> {
         }
     }
 
+    /// A null-terminated UTF-8 string at the current position. None (the
+    /// stream is unusable) if it's unterminated or not UTF-8.
+    fn read_cstr(&mut self) -> Option<&'a str> {
+        let rest = self.data.get(self.pos..)?;
+        let len = rest.iter().position(|&b| b == 0)?;
+        let s = std::str::from_utf8(&rest[..len]).ok()?;
+        self.pos += len + 1;
+        Some(s)
+    }
+
     fn find_block(&mut self, name: &str) -> bool {
         while let Some((key, value)) = self.next_kv() {
             if let BinaryVdfValue::BlockStart = value {
@@ -525,6 +622,365 @@ mod tests {
         assert_eq!(APPINFO_MAGIC_V29, 0x07564429);
     }
 
+    #[test]
+    fn test_sniff_version() {
+        assert_eq!(sniff_version(APPINFO_MAGIC_V27).unwrap(), 27);
+        assert_eq!(sniff_version(APPINFO_MAGIC_V29).unwrap(), 29);
+        // A future version is still read (with the latest layout).
+        assert_eq!(sniff_version(0x0756442a).unwrap(), 30);
+        assert_eq!(sniff_version(APPINFO_MAGIC_V28).unwrap(), 28);
+        assert!(sniff_version(0x07564426).is_err());
+        assert!(sniff_version(0x12345678).is_err());
+        assert!(sniff_version(0).is_err());
+    }
+
+    #[test]
+    fn test_layout_for_version() {
+        assert_eq!(Layout::for_version(27).metadata, 40);
+        assert_eq!(Layout::for_version(28).metadata, 60);
+        assert!(!Layout::for_version(28).indexed_keys);
+        assert!(Layout::for_version(29).indexed_keys);
+        assert_eq!(Layout::for_version(28).header_size(), 8);
+        assert_eq!(Layout::for_version(29).header_size(), 16);
+    }
+
+    // ===== Whole files =====
+
+    const KEYS: &[&str] = &[
+        "appinfo",
+        "common",
+        "name",
+        "config",
+        "launch",
+        "0",
+        "executable",
+        "oslist",
+        "type",
+        "gameid",
+    ];
+
+    /// A binary VDF node, encodable with inline or indexed (`KEYS`) keys
+    #[derive(Clone, Debug)]
+    enum Node {
+        Block(&'static str, Vec<Node>),
+        Str(&'static str, String),
+        Int(&'static str, i32),
+        /// A value type the reader consumes but doesn't return: (type, width)
+        Skipped(&'static str, u8, usize),
+    }
+
+    fn encode(nodes: &[Node], indexed: bool, out: &mut Vec<u8>) {
+        let key = |out: &mut Vec<u8>, k: &str| {
+            if indexed {
+                let idx = KEYS.iter().position(|x| *x == k).unwrap() as u32;
+                out.extend_from_slice(&idx.to_le_bytes());
+            } else {
+                out.extend_from_slice(k.as_bytes());
+                out.push(0);
+            }
+        };
+        for node in nodes {
+            match node {
+                Node::Block(k, children) => {
+                    out.push(TYPE_BLOCK_START);
+                    key(out, k);
+                    encode(children, indexed, out);
+                    out.push(TYPE_BLOCK_END);
+                }
+                Node::Str(k, v) => {
+                    out.push(TYPE_STRING);
+                    key(out, k);
+                    out.extend_from_slice(v.as_bytes());
+                    out.push(0);
+                }
+                Node::Int(k, v) => {
+                    out.push(TYPE_INT32);
+                    key(out, k);
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+                Node::Skipped(k, ty, width) => {
+                    out.push(*ty);
+                    key(out, k);
+                    out.extend(std::iter::repeat_n(0xab, *width));
+                }
+            }
+        }
+    }
+
+    fn table() -> Vec<String> {
+        KEYS.iter().map(|k| (*k).to_string()).collect()
+    }
+
+    fn game_blob() -> Vec<Node> {
+        vec![Node::Block(
+            "appinfo",
+            vec![
+                Node::Block(
+                    "common",
+                    vec![
+                        Node::Int("gameid", 440),
+                        Node::Str("name", "TestGame".into()),
+                    ],
+                ),
+                Node::Block(
+                    "config",
+                    vec![Node::Block(
+                        "launch",
+                        vec![Node::Block(
+                            "0",
+                            vec![
+                                Node::Str("executable", "game.exe".into()),
+                                Node::Str("oslist", "windows".into()),
+                            ],
+                        )],
+                    )],
+                ),
+            ],
+        )]
+    }
+
+    /// An appinfo.vdf of `version` with one entry per app, each holding
+    /// `metadata` bytes of fixed fields before its VDF.
+    fn appinfo_file(version: u32, metadata: usize, apps: &[(u32, &[Node])]) -> Vec<u8> {
+        let indexed = version >= 29;
+        let mut file = (APPINFO_MAGIC_V27 + version - 27).to_le_bytes().to_vec();
+        file.extend_from_slice(&1u32.to_le_bytes()); // universe
+        let table_offset_at = file.len();
+        if indexed {
+            file.extend_from_slice(&0i64.to_le_bytes()); // patched below
+        }
+        for (app_id, nodes) in apps {
+            let mut vdf = vec![0u8; metadata];
+            encode(nodes, indexed, &mut vdf);
+            file.extend_from_slice(&app_id.to_le_bytes());
+            file.extend_from_slice(&(vdf.len() as u32).to_le_bytes());
+            file.extend_from_slice(&vdf);
+        }
+        file.extend_from_slice(&0u32.to_le_bytes()); // end of entries
+        if indexed {
+            let offset = file.len() as i64;
+            file[table_offset_at..table_offset_at + 8].copy_from_slice(&offset.to_le_bytes());
+            file.extend_from_slice(&(KEYS.len() as u32).to_le_bytes());
+            for key in KEYS {
+                file.extend_from_slice(key.as_bytes());
+                file.push(0);
+            }
+        }
+        file
+    }
+
+    fn open(bytes: &[u8]) -> io::Result<AppInfoReader> {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        std::io::Write::write_all(&mut file, bytes).unwrap();
+        AppInfoReader::open(file.path())
+    }
+
+    #[test]
+    fn test_open_each_version() {
+        let blob = game_blob();
+        for version in [27, 28, 29, 30] {
+            let metadata = Layout::for_version(version.min(LATEST_VERSION)).metadata;
+            let bytes = appinfo_file(version, metadata, &[(10, &blob), (440, &blob)]);
+            let mut reader = open(&bytes).unwrap();
+            assert_eq!(reader.app_count(), 2, "v{version}");
+            assert_eq!(
+                reader.get_game_info(440),
+                Some(("TestGame".to_string(), "game.exe".to_string())),
+                "v{version}"
+            );
+            assert!(reader.get_game_info(999).is_none());
+        }
+    }
+
+    #[test]
+    fn test_open_sniffs_shifted_metadata() {
+        // A v28 file whose entries lack the binary-VDF hash is still read.
+        let blob = game_blob();
+        let bytes = appinfo_file(28, 40, &[(440, &blob)]);
+        let mut reader = open(&bytes).unwrap();
+        assert_eq!(reader.layout.metadata, 40);
+        assert_eq!(reader.get_executable(440).as_deref(), Some("game.exe"));
+    }
+
+    #[test]
+    fn test_open_rejects_other_files() {
+        assert!(open(b"").is_err());
+        assert!(open(b"\"appinfo\" {}").is_err());
+        assert!(open(&APPINFO_MAGIC_BASE.to_le_bytes()).is_err()); // version 0
+    }
+
+    // ===== Property tests: seeded random inputs =====
+
+    /// What a reader walk returns for `nodes`, in the form `walk` reports it
+    fn expected_events(nodes: &[Node], out: &mut Vec<String>) {
+        for node in nodes {
+            match node {
+                Node::Block(k, children) => {
+                    out.push(format!("{k}{{"));
+                    expected_events(children, out);
+                    out.push("}".into());
+                }
+                Node::Str(k, v) => out.push(format!("{k}={v}")),
+                Node::Int(k, v) => out.push(format!("{k}#{v}")),
+                Node::Skipped(..) => {}
+            }
+        }
+    }
+
+    /// Every key/value the reader returns, bounded so a bug can't loop forever
+    fn walk(data: &[u8], table: &[String], indexed: bool) -> Vec<String> {
+        let mut reader = BinaryVdfReader::new(data, table, indexed);
+        let mut events = Vec::new();
+        while let Some((key, value)) = reader.next_kv() {
+            events.push(match value {
+                BinaryVdfValue::BlockStart => format!("{key}{{"),
+                BinaryVdfValue::BlockEnd => "}".into(),
+                BinaryVdfValue::String(s) => format!("{key}={s}"),
+                BinaryVdfValue::Int32(i) => format!("{key}#{i}"),
+            });
+            assert!(events.len() <= data.len(), "reader made no progress");
+        }
+        events
+    }
+
+    fn random_string(rng: &mut fastrand::Rng) -> String {
+        const CHARS: &[char] = &['a', 'Z', '0', ' ', '.', '\\', 'é', '日', '🎮'];
+        (0..rng.usize(0..12))
+            .map(|_| CHARS[rng.usize(..CHARS.len())])
+            .collect()
+    }
+
+    fn random_nodes(rng: &mut fastrand::Rng, depth: usize) -> Vec<Node> {
+        (0..rng.usize(0..6))
+            .map(|_| {
+                let key = KEYS[rng.usize(..KEYS.len())];
+                match rng.u8(0..5) {
+                    0 if depth < 4 => Node::Block(key, random_nodes(rng, depth + 1)),
+                    1 => Node::Int(key, rng.i32(..)),
+                    2 => Node::Skipped(key, [0x03, 0x04, 0x06][rng.usize(..3)], 4),
+                    3 => Node::Skipped(key, [0x07, 0x0a][rng.usize(..2)], 8),
+                    _ => Node::Str(key, random_string(rng)),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prop_random_trees_round_trip() {
+        let table = table();
+        for seed in 0..500 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let nodes = random_nodes(&mut rng, 0);
+            let mut expected = Vec::new();
+            expected_events(&nodes, &mut expected);
+            for indexed in [false, true] {
+                let mut data = Vec::new();
+                encode(&nodes, indexed, &mut data);
+                assert_eq!(
+                    walk(&data, &table, indexed),
+                    expected,
+                    "seed {seed}, indexed {indexed}: {nodes:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn prop_game_info_found_among_random_siblings() {
+        let table = table();
+        for seed in 0..200 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let Node::Block(_, mut children) = game_blob().remove(0) else {
+                unreachable!()
+            };
+            // Unrelated blocks and values before, between and after.
+            for _ in 0..3 {
+                let at = rng.usize(..=children.len());
+                let noise = Node::Block("gameid", random_nodes(&mut rng, 1));
+                children.insert(at, noise);
+            }
+            let nodes = [Node::Block("appinfo", children)];
+            for indexed in [false, true] {
+                let mut data = Vec::new();
+                encode(&nodes, indexed, &mut data);
+                assert_eq!(
+                    AppInfoReader::parse_game_info(&data, &table, indexed),
+                    Some(("TestGame".to_string(), "game.exe".to_string())),
+                    "seed {seed}, indexed {indexed}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn prop_corrupt_vdf_never_panics() {
+        let table = table();
+        let mut valid = Vec::new();
+        encode(&game_blob(), false, &mut valid);
+        let mut valid_indexed = Vec::new();
+        encode(&game_blob(), true, &mut valid_indexed);
+
+        for seed in 0..2000 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let indexed = rng.bool();
+            let mut data = match rng.u8(0..3) {
+                // Pure noise
+                0 => (0..rng.usize(0..256)).map(|_| rng.u8(..)).collect(),
+                // A real blob with some bytes flipped
+                1 => {
+                    let mut d = if indexed {
+                        valid_indexed.clone()
+                    } else {
+                        valid.clone()
+                    };
+                    for _ in 0..rng.usize(1..8) {
+                        let at = rng.usize(..d.len());
+                        d[at] = rng.u8(..);
+                    }
+                    d
+                }
+                // A real blob cut short
+                _ => {
+                    let d = if indexed { &valid_indexed } else { &valid };
+                    d[..rng.usize(..d.len())].to_vec()
+                }
+            };
+            if rng.bool() {
+                data.truncate(rng.usize(..=data.len()));
+            }
+            // Must return (anything) rather than panic or hang.
+            let _ = walk(&data, &table, indexed);
+            let _ = AppInfoReader::parse_game_info(&data, &table, indexed);
+            let mut reader = BinaryVdfReader::new(&data, &table, indexed);
+            let _ = reader.find_nested_block(&["config", "launch"]);
+        }
+    }
+
+    #[test]
+    fn prop_corrupt_files_never_panic() {
+        let blob = game_blob();
+        let valid = [
+            appinfo_file(28, 60, &[(440, &blob), (10, &blob)]),
+            appinfo_file(29, 60, &[(440, &blob), (10, &blob)]),
+        ];
+        for seed in 0..200 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let mut bytes = valid[rng.usize(..valid.len())].clone();
+            // Keep the magic so the mangled body is actually parsed.
+            for _ in 0..rng.usize(1..6) {
+                let at = rng.usize(4..bytes.len());
+                bytes[at] = rng.u8(..);
+            }
+            if rng.bool() {
+                bytes.truncate(rng.usize(4..=bytes.len()));
+            }
+            if let Ok(mut reader) = open(&bytes) {
+                let _ = reader.get_game_info(440);
+                let _ = reader.get_game_info(10);
+            }
+        }
+    }
+
     #[test]
     fn test_v29_indexed_keys() {
         // v29: keys are u32 indices into the string table; string values inline.
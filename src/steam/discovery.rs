@@ -17,7 +17,7 @@ use super::vdf;
 
 /// Cache file magic + version
 const CACHE_MAGIC: u32 = 0x50435354; // "PCST"
-const CACHE_VERSION: u32 = 1;
+const CACHE_VERSION: u32 = 2;
 
 /// Safety limits for cache deserialization to prevent memory exhaustion
 /// from malformed or tampered cache files.
@@ -7,7 +7,7 @@ mod appinfo;
 mod discovery;
 pub(crate) mod vdf;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub use discovery::SteamGameDiscovery;
 
@@ -84,3 +84,111 @@ pub fn find_steam_path() -> Option<PathBuf> {
         .into_iter()
         .find(|p| p.join("steamapps").is_dir())
 }
+
+/// App id Steam currently reports as running (`RunningAppID`), if any.
+#[cfg(windows)]
+pub fn running_app_id() -> Option<u32> {
+    use winreg::RegKey;
+    use winreg::enums::HKEY_CURRENT_USER;
+
+    let id: u32 = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey("Software\\Valve\\Steam")
+        .ok()?
+        .get_value("RunningAppID")
+        .ok()?;
+    (id != 0).then_some(id)
+}
+
+/// App id Steam currently reports as running (`RunningAppID` in
+/// `registry.vdf`), if any.
+#[cfg(unix)]
+pub fn running_app_id() -> Option<u32> {
+    let home = PathBuf::from(std::env::var("HOME").ok()?);
+    [
+        home.join(".steam/registry.vdf"),
+        home.join(".var/app/com.valvesoftware.Steam/.steam/registry.vdf"),
+    ]
+    .iter()
+    .find_map(|p| std::fs::read_to_string(p).ok())
+    .and_then(|content| vdf::extract_running_app_id(&content))
+    .filter(|&id| id != 0)
+}
+
+/// Display name of an installed Steam app, from its library's appmanifest.
+pub fn app_name(app_id: u32) -> Option<String> {
+    let steam = find_steam_path()?;
+    let folders = std::fs::read_to_string(steam.join("steamapps/libraryfolders.vdf")).ok()?;
+    vdf::extract_library_info(&folders)
+        .into_iter()
+        .filter(|(_, apps)| apps.contains(&app_id))
+        .find_map(|(path, _)| {
+            let manifest = PathBuf::from(path)
+                .join("steamapps")
+                .join(format!("appmanifest_{app_id}.acf"));
+            let content = std::fs::read_to_string(manifest).ok()?;
+            vdf::extract_appmanifest_fields(&content).map(|(_, name, _)| name)
+        })
+}
+
+/// Steam's locally cached artwork for `app_id`: the store header, else the
+/// 600x900 library capsule.
+pub fn library_art(app_id: u32) -> Option<PathBuf> {
+    find_library_art(
+        &find_steam_path()?.join("appcache").join("librarycache"),
+        app_id,
+    )
+}
+
+/// `<id>_header.jpg` in older clients; `<id>/header.jpg` in newer ones, which
+/// may nest it one (hash-named) directory deeper.
+fn find_library_art(cache: &Path, app_id: u32) -> Option<PathBuf> {
+    let dir = cache.join(app_id.to_string());
+    ["header.jpg", "library_600x900.jpg"]
+        .iter()
+        .find_map(|name| {
+            [cache.join(format!("{app_id}_{name}")), dir.join(name)]
+                .into_iter()
+                .find(|p| p.is_file())
+                .or_else(|| {
+                    std::fs::read_dir(&dir)
+                        .ok()?
+                        .flatten()
+                        .map(|entry| entry.path().join(name))
+                        .find(|p| p.is_file())
+                })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_library_art_layouts() {
+        let cache = tempfile::tempdir().unwrap();
+        let cache = cache.path();
+        assert_eq!(find_library_art(cache, 730), None);
+
+        // Newer client: nested under a hash directory, capsule only
+        let nested = cache.join("730").join("4f1c2d");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(nested.join("library_600x900.jpg"), b"x").unwrap();
+        assert_eq!(
+            find_library_art(cache, 730),
+            Some(nested.join("library_600x900.jpg"))
+        );
+
+        // The header wins over the capsule, whichever layout it's in
+        std::fs::write(cache.join("730").join("header.jpg"), b"x").unwrap();
+        assert_eq!(
+            find_library_art(cache, 730),
+            Some(cache.join("730").join("header.jpg"))
+        );
+        std::fs::write(cache.join("730_header.jpg"), b"x").unwrap();
+        assert_eq!(
+            find_library_art(cache, 730),
+            Some(cache.join("730_header.jpg"))
+        );
+        assert_eq!(find_library_art(cache, 570), None);
+    }
+}
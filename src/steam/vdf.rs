@@ -28,6 +28,20 @@ pub fn extract_appmanifest_fields(content: &str) -> Option<(u32, String, String)
     Some((appid?.parse().ok()?, name?, installdir?))
 }
 
+/// `RunningAppID` from Steam's `registry.vdf` (Linux), the app Steam
+/// currently has running (0 = none)
+pub fn extract_running_app_id(content: &str) -> Option<u32> {
+    content
+        .lines()
+        .map(str::trim)
+        .find(|line| {
+            line.get(..14)
+                .is_some_and(|key| key.eq_ignore_ascii_case("\"RunningAppID\""))
+        })
+        .and_then(extract_quoted_value)
+        .and_then(|v| v.parse().ok())
+}
+
 /// Extract library paths from libraryfolders.vdf
 #[cfg(test)]
 pub fn extract_library_paths(content: &str) -> Vec<String> {
@@ -326,5 +340,34 @@ mod tests {
         assert!(extract_appmanifest_fields("").is_none());
         assert!(extract_library_paths("").is_empty());
         assert!(extract_library_info("").is_empty());
+        assert!(extract_running_app_id("").is_none());
+    }
+
+    #[test]
+    fn test_extract_running_app_id() {
+        let content = r#"
+"Registry"
+{
+	"HKCU"
+	{
+		"Software"
+		{
+			"Valve"
+			{
+				"Steam"
+				{
+					"RunningAppID"		"546560"
+					"SourceModInstallPath"		"/home/u/.steam/steam/steamapps/sourcemods"
+				}
+			}
+		}
+	}
+}
+"#;
+        assert_eq!(extract_running_app_id(content), Some(546560));
+        assert_eq!(
+            extract_running_app_id("\t\"runningappid\"\t\t\"0\""),
+            Some(0)
+        );
     }
 }
@@ -3,9 +3,10 @@
 //!
 //! Two kinds of supervised task:
 //! - Pure-async polling sensors (gpu, network, disk, uptime, games, custom,
-//!   steam, idle, volume, audio_device, capture) hold no per-task OS thread, so
-//!   they're cancelled by dropping their future (`cancelable` selects the run()
-//!   future against a per-task cancel) - zero changes to those sensors.
+//!   process_alerts, process_sensors, steam, idle, volume, audio_device, notifications,
+//!   automations, game_limits, thermal_guard, focus, capture) hold no per-task OS thread, so
+//!   they're cancelled by dropping their future (`cancelable` selects the run() future against a
+//!   per-task cancel) - zero changes to those sensors.
 //! - Thread-holding sensors (system, session, now_playing, power) take the
 //!   per-task shutdown SENDER into run() and use it (loop + their OS threads) in
 //!   place of the global shutdown, so firing it stops them and their threads.
@@ -26,9 +27,13 @@ use crate::AppState;
 use crate::config::Config;
 use crate::power::PowerEventListener;
 use crate::sensors::{
-    ActiveWindowSensor, AudioDeviceSensor, CaptureSensor, CustomSensorManager, DiskSensor,
-    GameSensor, GpuSensor, IdleSensor, NetworkSensor, NowPlayingSensor, SessionSensor, SteamSensor,
-    SystemSensor, UptimeSensor, VolumeSensor,
+    ActiveWindowSensor, AudioDeviceSensor, CaptureSensor, CustomSensorManager, DeviceBatterySensor,
+    DiskHealthSensor, DiskSensor, DisplayModeSensor, DockerSensor, FocusSensor, GameArtSensor,
+    GameSensor, GamepadSensor, GpuSensor, IdleSensor, LatencySensor, MediaPlayingSensor,
+    NetworkConnectionSensor, NetworkSensor, NowPlayingSensor, PerfModeSensor, PowerPlanSensor,
+    PrinterSensor, ProcessAlertSensor, ProcessPresenceSensor, PublicIpSensor, ReportingSensor,
+    SessionSensor, SleepBlockersSensor, SteamSensor, SystemSensor, ThemeSensor, TranscodingSensor,
+    UptimeSensor, UsbSensor, VolumeSensor, VrSensor, WakeTimerSensor, WindowsUpdateSensor,
 };
 
 /// Run `fut` until it finishes on its own (global shutdown, handled inside the
@@ -75,16 +80,151 @@ const TASKS: &[TaskDef] = &[
         enabled: |c| c.features.uptime_sensor,
         spawn: |s, c| tokio::spawn(cancelable(UptimeSensor::new(s).run(), c.subscribe())),
     },
+    TaskDef {
+        name: "power_plan",
+        enabled: |c| c.features.power_plan,
+        spawn: |s, c| tokio::spawn(cancelable(PowerPlanSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "display_mode",
+        enabled: |c| c.features.display_mode,
+        spawn: |s, c| tokio::spawn(cancelable(DisplayModeSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "device_batteries",
+        enabled: |c| c.features.device_batteries,
+        spawn: |s, c| tokio::spawn(cancelable(DeviceBatterySensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "gamepad",
+        enabled: |c| c.features.gamepad,
+        spawn: |s, c| tokio::spawn(cancelable(GamepadSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "network_connection",
+        enabled: |c| c.features.network_connection,
+        spawn: |s, c| {
+            tokio::spawn(cancelable(
+                NetworkConnectionSensor::new(s).run(),
+                c.subscribe(),
+            ))
+        },
+    },
+    TaskDef {
+        name: "disk_health",
+        enabled: |c| c.features.disk_health,
+        spawn: |s, c| tokio::spawn(cancelable(DiskHealthSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "printers",
+        enabled: |c| c.features.printers,
+        spawn: |s, c| tokio::spawn(cancelable(PrinterSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "usb_devices",
+        enabled: |c| c.features.usb_devices,
+        spawn: |s, c| tokio::spawn(cancelable(UsbSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "wake_timers",
+        enabled: |c| c.features.wake_timers,
+        spawn: |s, c| tokio::spawn(cancelable(WakeTimerSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "sleep_blockers",
+        enabled: |c| c.features.sleep_blockers,
+        spawn: |s, c| tokio::spawn(cancelable(SleepBlockersSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "theme",
+        enabled: |c| c.features.theme,
+        spawn: |s, c| tokio::spawn(cancelable(ThemeSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "docker",
+        enabled: |c| c.features.docker,
+        spawn: |s, c| tokio::spawn(cancelable(DockerSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "plugins",
+        enabled: |c| !c.plugins.is_empty(),
+        spawn: |s, c| tokio::spawn(cancelable(crate::plugins::run(s), c.subscribe())),
+    },
+    TaskDef {
+        name: "latency",
+        enabled: |c| c.features.latency,
+        spawn: |s, c| tokio::spawn(cancelable(LatencySensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "public_ip",
+        enabled: |c| c.features.public_ip,
+        spawn: |s, c| tokio::spawn(cancelable(PublicIpSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "media_playing",
+        enabled: |c| c.features.media_playing,
+        spawn: |s, c| tokio::spawn(cancelable(MediaPlayingSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "vr",
+        enabled: |c| c.features.vr,
+        spawn: |s, c| tokio::spawn(cancelable(VrSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "transcoding",
+        enabled: |c| c.features.transcoding,
+        spawn: |s, c| tokio::spawn(cancelable(TranscodingSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "perf_mode",
+        enabled: |c| c.features.perf_mode,
+        spawn: |s, c| tokio::spawn(cancelable(PerfModeSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "focus",
+        enabled: |c| c.features.focus_mode,
+        spawn: |s, c| tokio::spawn(cancelable(FocusSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "reporting",
+        enabled: |c| c.features.active_window || c.features.running_game,
+        spawn: |s, c| tokio::spawn(cancelable(ReportingSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "windows_update",
+        enabled: |c| c.features.windows_update,
+        spawn: |s, c| tokio::spawn(cancelable(WindowsUpdateSensor::new(s).run(), c.subscribe())),
+    },
     TaskDef {
         name: "games",
         enabled: |c| c.features.running_game || c.features.game_catalog,
         spawn: |s, c| tokio::spawn(cancelable(GameSensor::new(s).run(), c.subscribe())),
     },
+    TaskDef {
+        name: "game_art",
+        enabled: |c| c.features.game_art,
+        spawn: |s, c| tokio::spawn(cancelable(GameArtSensor::new(s).run(), c.subscribe())),
+    },
     TaskDef {
         name: "custom_sensors",
         enabled: |c| c.custom_sensors_enabled && !c.custom_sensors.is_empty(),
         spawn: |s, c| tokio::spawn(cancelable(CustomSensorManager::new(s).run(), c.subscribe())),
     },
+    TaskDef {
+        name: "process_alerts",
+        enabled: |c| !c.process_alerts.is_empty(),
+        spawn: |s, c| tokio::spawn(cancelable(ProcessAlertSensor::new(s).run(), c.subscribe())),
+    },
+    TaskDef {
+        name: "process_sensors",
+        enabled: |c| !c.process_sensors.is_empty(),
+        spawn: |s, c| {
+            tokio::spawn(cancelable(
+                ProcessPresenceSensor::new(s).run(),
+                c.subscribe(),
+            ))
+        },
+    },
     // These hold no per-task OS thread either: steam's fs-watcher is dropped with
     // the future; volume/audio_device/capture/idle poll via spawn_blocking. (Their
     // process-wide COM listener / ext-idle-notify helper is idempotent and
@@ -109,6 +249,31 @@ const TASKS: &[TaskDef] = &[
         enabled: |c| c.features.audio_device,
         spawn: |s, c| tokio::spawn(cancelable(AudioDeviceSensor::new(s).run(), c.subscribe())),
     },
+    TaskDef {
+        name: "notifications",
+        enabled: |c| c.features.notifications,
+        spawn: |s, c| tokio::spawn(cancelable(crate::notification::run(s), c.subscribe())),
+    },
+    TaskDef {
+        name: "automations",
+        enabled: |c| !c.automations.is_empty(),
+        spawn: |s, c| tokio::spawn(cancelable(crate::automations::run(s), c.subscribe())),
+    },
+    TaskDef {
+        name: "game_limits",
+        enabled: |c| c.game_limits.enabled && c.features.running_game,
+        spawn: |s, c| tokio::spawn(cancelable(crate::game_limits::run(s), c.subscribe())),
+    },
+    TaskDef {
+        name: "thermal_guard",
+        enabled: |c| c.thermal_guard.enabled,
+        spawn: |s, c| tokio::spawn(cancelable(crate::thermal::run(s), c.subscribe())),
+    },
+    TaskDef {
+        name: "scripts",
+        enabled: |c| c.scripts_enabled,
+        spawn: |s, c| tokio::spawn(cancelable(crate::scripting::run(s), c.subscribe())),
+    },
     TaskDef {
         name: "capture",
         enabled: |c| c.features.mic || c.features.webcam,
@@ -128,7 +293,7 @@ const TASKS: &[TaskDef] = &[
     },
     TaskDef {
         name: "session",
-        enabled: |c| c.features.session_state,
+        enabled: |c| c.features.session_state || c.features.remote_desktop,
         spawn: |s, c| tokio::spawn(SessionSensor::new(s).run(c)),
     },
     TaskDef {
@@ -0,0 +1,25 @@
+//! Small string helpers.
+
+/// `s` cut to at most `max` characters, the last one an ellipsis when cut.
+/// Counts chars, not bytes, so multi-byte characters are never split (HA
+/// rejects states over 255 characters).
+pub(crate) fn truncate_chars(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max.saturating_sub(1)) {
+        Some((i, _)) if s.len() > i + 1 => format!("{}\u{2026}", &s[..i]),
+        _ => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("short", 10), "short");
+        assert_eq!(truncate_chars("exactly", 7), "exactly");
+        assert_eq!(truncate_chars("abcdef", 4), "abc\u{2026}");
+        // Multi-byte characters are never split.
+        assert_eq!(truncate_chars("äöüäöü", 3), "äö\u{2026}");
+    }
+}
@@ -0,0 +1,373 @@
+//! Thermal guard: emergency actions when the CPU or GPU overheats
+//!
+//! With `thermal_guard.enabled`, the CPU package and GPU core temperatures are
+//! checked every few seconds against `cpu_max_c` / `gpu_max_c`. Once one stays
+//! above its limit for `seconds`, a toast warns, a `temperature_critical`
+//! event goes out and `action` runs (close the game, or a forced shutdown). It
+//! trips once per overheat, re-arming after the temperature drops
+//! `REARM_MARGIN` below the limit. Runs in the agent, so it works without HA.
+//!
+//! Temperatures: on Windows the HWiNFO sensor hands over its
+//! `cpu_package_temp` / `gpu_temp` readings (`record`); on Linux they're read
+//! here from hwmon (k10temp / coretemp / zenpower, amdgpu / nouveau / radeon),
+//! with nvidia-smi for NVIDIA cards.
+
+use log::{debug, info, warn};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::{MissedTickBehavior, interval};
+
+use crate::AppState;
+use crate::config::{ThermalAction, ThermalGuardConfig};
+use crate::events::BridgeEvent;
+use crate::i18n::{tr, tr_args};
+use crate::mqtt::Command;
+
+/// How often temperatures are checked
+const TICK: Duration = Duration::from_secs(5);
+
+/// Readings older than this are ignored (HWiNFO closed, say), so a stale hot
+/// value can't trip the guard.
+const MAX_AGE: Duration = Duration::from_secs(30);
+
+/// °C below the limit a temperature must drop before the guard re-arms
+const REARM_MARGIN: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sensor {
+    Cpu,
+    Gpu,
+}
+
+impl Sensor {
+    const ALL: [Self; 2] = [Self::Cpu, Self::Gpu];
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::Cpu => "cpu",
+            Self::Gpu => "gpu",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Cpu => "CPU",
+            Self::Gpu => "GPU",
+        }
+    }
+
+    fn limit(self, guard: &ThermalGuardConfig) -> Option<u32> {
+        match self {
+            Self::Cpu => guard.cpu_max_c,
+            Self::Gpu => guard.gpu_max_c,
+        }
+    }
+}
+
+/// Latest `(°C, when)` per sensor, indexed like `Sensor::ALL`
+static LATEST: Mutex<[Option<(f64, Instant)>; 2]> = Mutex::new([None; 2]);
+
+/// Hand over a temperature reading by sensor key. Called by the HWiNFO sensor
+/// for every matched reading; keys other than the CPU package and GPU core
+/// temperatures are ignored.
+pub(crate) fn record(key: &str, celsius: f64) {
+    let index = match key {
+        "cpu_package_temp" => 0,
+        "gpu_temp" => 1,
+        _ => return,
+    };
+    LATEST.lock().unwrap_or_else(|e| e.into_inner())[index] = Some((celsius, Instant::now()));
+}
+
+/// Recent readings, indexed like `Sensor::ALL`.
+fn latest(now: Instant) -> [Option<f64>; 2] {
+    LATEST
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .map(|r| r.and_then(|(c, at)| (now.duration_since(at) <= MAX_AGE).then_some(c)))
+}
+
+/// A limit the guard just tripped on
+#[derive(Debug, Clone, PartialEq)]
+struct Trip {
+    sensor: Sensor,
+    celsius: f64,
+    limit_c: u32,
+}
+
+/// Tracks how long each sensor has been over its limit; trips once per spell.
+struct Guard {
+    limits: ThermalGuardConfig,
+    /// Per sensor: since when it's been above its limit
+    over_since: [Option<Instant>; 2],
+    /// Per sensor: tripped and not yet cooled down
+    tripped: [bool; 2],
+}
+
+impl Guard {
+    fn new(limits: ThermalGuardConfig) -> Self {
+        Self {
+            limits,
+            over_since: [None; 2],
+            tripped: [false; 2],
+        }
+    }
+
+    /// Check `temps` (indexed like `Sensor::ALL`, None = no reading) taken at
+    /// `now`, and return the limits that just tripped.
+    fn tick(&mut self, now: Instant, temps: [Option<f64>; 2]) -> Vec<Trip> {
+        let hold = Duration::from_secs(u64::from(self.limits.seconds));
+        let mut trips = Vec::new();
+        for (i, sensor) in Sensor::ALL.into_iter().enumerate() {
+            let Some(limit_c) = sensor.limit(&self.limits) else {
+                continue;
+            };
+            let Some(celsius) = temps[i] else {
+                // A gap in readings restarts the count.
+                self.over_since[i] = None;
+                continue;
+            };
+            let limit = f64::from(limit_c);
+            if celsius <= limit {
+                self.over_since[i] = None;
+                if celsius <= limit - REARM_MARGIN {
+                    self.tripped[i] = false;
+                }
+                continue;
+            }
+            let since = *self.over_since[i].get_or_insert(now);
+            if !self.tripped[i] && now.duration_since(since) >= hold {
+                self.tripped[i] = true;
+                trips.push(Trip {
+                    sensor,
+                    celsius,
+                    limit_c,
+                });
+            }
+        }
+        trips
+    }
+}
+
+/// CPU package and GPU core temperature from hwmon, nvidia-smi for NVIDIA.
+#[cfg(unix)]
+fn read_temps() -> [Option<f64>; 2] {
+    let mut temps = [None; 2];
+    if let Ok(entries) = std::fs::read_dir("/sys/class/hwmon") {
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            let Ok(name) = std::fs::read_to_string(dir.join("name")) else {
+                continue;
+            };
+            let index = match name.trim() {
+                "k10temp" | "coretemp" | "zenpower" => 0,
+                "amdgpu" | "nouveau" | "radeon" => 1,
+                _ => continue,
+            };
+            // temp1 is Tctl / Package id 0 / edge on these drivers
+            let celsius = std::fs::read_to_string(dir.join("temp1_input"))
+                .ok()
+                .and_then(|v| v.trim().parse::<f64>().ok())
+                .map(|milli| milli / 1000.0);
+            temps[index] = temps[index].or(celsius);
+        }
+    }
+    if temps[1].is_none() {
+        temps[1] = nvidia_smi_temp();
+    }
+    temps
+}
+
+#[cfg(unix)]
+fn nvidia_smi_temp() -> Option<f64> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    // Once we learn nvidia-smi isn't installed, stop forking it every tick.
+    static NVIDIA_ABSENT: AtomicBool = AtomicBool::new(false);
+    if NVIDIA_ABSENT.load(Ordering::Relaxed) {
+        return None;
+    }
+    match std::process::Command::new("nvidia-smi")
+        .args([
+            "--query-gpu=temperature.gpu",
+            "--format=csv,noheader,nounits",
+        ])
+        .output()
+    {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|l| l.trim().parse::<f64>().ok())
+            .reduce(f64::max),
+        Ok(_) => None,
+        Err(_) => {
+            NVIDIA_ABSENT.store(true, Ordering::Relaxed);
+            None
+        }
+    }
+}
+
+fn toast(title: &str, message: String) {
+    let payload = serde_json::json!({ "title": title, "message": message }).to_string();
+    tokio::spawn(async move {
+        match tokio::task::spawn_blocking(move || crate::notification::show_toast(&payload, ""))
+            .await
+        {
+            Ok(Err(e)) => warn!("Thermal guard toast failed: {e}"),
+            Err(e) => warn!("Thermal guard toast task failed: {e}"),
+            Ok(Ok(())) => {}
+        }
+    });
+}
+
+/// Toast, publish and act on a trip.
+async fn handle(state: &AppState, guard: &ThermalGuardConfig, trip: Trip) {
+    let Trip {
+        sensor,
+        celsius,
+        limit_c,
+    } = trip;
+    let action = guard.action;
+    let label = sensor.label();
+    warn!(
+        "Thermal guard: {label} at {celsius:.0} °C, above {limit_c} °C for {}s ({action:?})",
+        guard.seconds
+    );
+    let then = match action {
+        ThermalAction::None => None,
+        ThermalAction::CloseGame => Some("Games will be closed."),
+        ThermalAction::Shutdown => Some("The PC will shut down."),
+    };
+    let mut message = tr_args(
+        "{label} is at {celsius} °C (limit {limit} °C).",
+        &[
+            ("label", label),
+            ("celsius", &format!("{celsius:.0}")),
+            ("limit", &limit_c.to_string()),
+        ],
+    );
+    if let Some(then) = then {
+        message = format!("{message} {}", tr(then));
+    }
+    toast(
+        &tr_args("{label} overheating", &[("label", label)]),
+        message,
+    );
+    state
+        .mqtt
+        .publish_event(&BridgeEvent::TemperatureCritical {
+            sensor: sensor.key().to_string(),
+            celsius: (celsius * 10.0).round() / 10.0,
+            limit_c,
+            seconds: guard.seconds,
+            action,
+        })
+        .await;
+    match action {
+        ThermalAction::None => {}
+        ThermalAction::CloseGame => {
+            crate::commands::stop_game::close(None).await;
+        }
+        ThermalAction::Shutdown => {
+            // Forced: the shutdown guard mustn't hold this off for a game.
+            let cmd = Command {
+                name: "Shutdown".to_string(),
+                payload: "force:true".to_string(),
+            };
+            if state.mqtt.command_sender().send(cmd).await.is_err() {
+                warn!("Thermal guard: command executor is gone");
+            }
+        }
+    }
+}
+
+/// Supervised while `thermal_guard` is enabled.
+pub(crate) async fn run(state: Arc<AppState>) {
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    let mut config_rx = state.config_generation.subscribe();
+    let mut guard = Guard::new(state.config.read().await.thermal_guard.clone());
+    let mut tick = interval(TICK);
+    tick.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    info!("Thermal guard started");
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown_rx.recv() => break,
+            Ok(()) = config_rx.recv() => {
+                guard.limits = state.config.read().await.thermal_guard.clone();
+                debug!("Thermal guard reloaded");
+            }
+            _ = tick.tick() => {
+                #[cfg(unix)]
+                if let Ok(temps) = tokio::task::spawn_blocking(read_temps).await {
+                    for (key, celsius) in ["cpu_package_temp", "gpu_temp"].into_iter().zip(temps) {
+                        if let Some(celsius) = celsius {
+                            record(key, celsius);
+                        }
+                    }
+                }
+                let now = Instant::now();
+                for trip in guard.tick(now, latest(now)) {
+                    handle(&state, &guard.limits, trip).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    fn guard(cpu: Option<u32>, gpu: Option<u32>) -> Guard {
+        Guard::new(ThermalGuardConfig {
+            enabled: true,
+            cpu_max_c: cpu,
+            gpu_max_c: gpu,
+            seconds: 30,
+            action: ThermalAction::None,
+        })
+    }
+
+    #[test]
+    fn test_trips_after_hold_once_per_overheat() {
+        let mut guard = guard(Some(90), None);
+        let start = Instant::now();
+        assert!(guard.tick(start, [Some(95.0), None]).is_empty());
+        assert!(guard.tick(start + 29 * SEC, [Some(96.0), None]).is_empty());
+        assert_eq!(
+            guard.tick(start + 30 * SEC, [Some(97.0), None]),
+            vec![Trip {
+                sensor: Sensor::Cpu,
+                celsius: 97.0,
+                limit_c: 90
+            }]
+        );
+        assert!(guard.tick(start + 60 * SEC, [Some(97.0), None]).is_empty());
+        // Dipping just under the limit doesn't re-arm it...
+        assert!(guard.tick(start + 65 * SEC, [Some(88.0), None]).is_empty());
+        assert!(guard.tick(start + 70 * SEC, [Some(91.0), None]).is_empty());
+        assert!(guard.tick(start + 100 * SEC, [Some(91.0), None]).is_empty());
+        // ...cooling down does.
+        assert!(guard.tick(start + 105 * SEC, [Some(80.0), None]).is_empty());
+        assert!(guard.tick(start + 110 * SEC, [Some(92.0), None]).is_empty());
+        assert_eq!(guard.tick(start + 140 * SEC, [Some(92.0), None]).len(), 1);
+    }
+
+    #[test]
+    fn test_dip_or_gap_restarts_the_count() {
+        let mut guard = guard(None, Some(85));
+        let start = Instant::now();
+        assert!(guard.tick(start, [None, Some(90.0)]).is_empty());
+        assert!(guard.tick(start + 20 * SEC, [None, Some(84.0)]).is_empty());
+        assert!(guard.tick(start + 25 * SEC, [None, Some(90.0)]).is_empty());
+        assert!(guard.tick(start + 40 * SEC, [None, None]).is_empty());
+        assert!(guard.tick(start + 45 * SEC, [None, Some(90.0)]).is_empty());
+        assert!(guard.tick(start + 70 * SEC, [None, Some(90.0)]).is_empty());
+        let trips = guard.tick(start + 75 * SEC, [Some(99.0), Some(90.0)]);
+        // The CPU has no limit set.
+        assert_eq!(trips.len(), 1);
+        assert_eq!(trips[0].sensor, Sensor::Gpu);
+    }
+}
@@ -0,0 +1,139 @@
+//! App identity Windows shows toasts under
+//!
+//! Unpackaged apps only get their own name and icon on a toast when their
+//! AppUserModelID is registered: `HKCU\Software\Classes\AppUserModelId\<id>`
+//! (display name, icon), plus a Start menu shortcut carrying the id, which
+//! older Windows 10 builds still look for. Both are (re)written at startup so
+//! a moved exe or a changed `notifications.app_name` is picked up. A payload's
+//! `app_name` gets an id of its own, registered the first time it's used.
+//! Until registration succeeds, toasts borrow PowerShell's identity.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+
+/// Id of the `notifications.app_name` identity
+pub const AUMID: &str = "dank0i.PCBridge";
+
+/// Registered by every Windows install, so always shows
+const POWERSHELL_AUMID: &str =
+    "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\WindowsPowerShell\\v1.0\\powershell.exe";
+
+const ICON: &[u8] = include_bytes!("../assets/icon.ico");
+
+/// Display name -> registered id, once registration worked
+static REGISTERED: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+/// Id of a payload's own `app_name`; None if it has nothing to build one from
+fn alias_id(app_name: &str) -> Option<String> {
+    let slug: String = app_name
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    (!slug.is_empty()).then(|| format!("{AUMID}.{slug}"))
+}
+
+/// `icon.ico` next to userConfig.json, for `IconUri`
+fn icon_path() -> Result<PathBuf> {
+    let path = crate::config::Config::config_path()?.with_file_name("icon.ico");
+    if std::fs::read(&path).ok().as_deref() != Some(ICON) {
+        std::fs::write(&path, ICON).with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(path)
+}
+
+fn register_id(id: &str, app_name: &str, icon: &std::path::Path) -> Result<()> {
+    use winreg::RegKey;
+    use winreg::enums::HKEY_CURRENT_USER;
+
+    let (key, _) = RegKey::predef(HKEY_CURRENT_USER)
+        .create_subkey(format!(r"Software\Classes\AppUserModelId\{id}"))
+        .with_context(|| format!("registering {id}"))?;
+    key.set_value("DisplayName", &app_name)?;
+    key.set_value("IconUri", &icon.to_string_lossy().into_owned())?;
+    Ok(())
+}
+
+/// `PC Bridge.lnk` in the user's Start menu, launching this exe and tagged
+/// with `AUMID`
+fn write_shortcut() -> Result<()> {
+    use windows::Win32::Storage::EnhancedStorage::PKEY_AppUserModel_ID;
+    use windows::Win32::System::Com::{
+        CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED, CoCreateInstance, CoInitializeEx,
+        IPersistFile,
+    };
+    use windows::Win32::UI::Shell::PropertiesSystem::IPropertyStore;
+    use windows::Win32::UI::Shell::{IShellLinkW, ShellLink};
+    use windows::core::{HSTRING, Interface};
+    use windows_core::PROPVARIANT;
+
+    let exe = std::env::current_exe()?;
+    let programs = std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .context("APPDATA not set")?
+        .join(r"Microsoft\Windows\Start Menu\Programs");
+    let lnk = programs.join("PC Bridge.lnk");
+
+    // SAFETY: COM is initialized on this (blocking pool) thread first; the
+    // interfaces are released when they drop, before the thread moves on.
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        let link: IShellLinkW = CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER)?;
+        link.SetPath(&HSTRING::from(exe.as_os_str()))?;
+        if let Some(dir) = exe.parent() {
+            link.SetWorkingDirectory(&HSTRING::from(dir.as_os_str()))?;
+        }
+        let store: IPropertyStore = link.cast()?;
+        store.SetValue(&PKEY_AppUserModel_ID, &PROPVARIANT::from(AUMID))?;
+        store.Commit()?;
+        let file: IPersistFile = link.cast()?;
+        file.Save(&HSTRING::from(lnk.as_os_str()), true)?;
+    }
+    debug!("Start menu shortcut written: {}", lnk.display());
+    Ok(())
+}
+
+/// Register `app_name` as the identity toasts show under. Blocking (registry,
+/// files, COM).
+pub fn register(app_name: &str) {
+    match icon_path().and_then(|icon| register_id(AUMID, app_name, &icon)) {
+        Ok(()) => {
+            // The registry entry alone is enough on current Windows; without
+            // the shortcut only older builds fall back to PowerShell's name.
+            if let Err(e) = write_shortcut() {
+                warn!("Toast identity: Start menu shortcut failed: {e:#}");
+            }
+            info!("Toasts show as \"{app_name}\"");
+            *REGISTERED.lock().unwrap_or_else(|e| e.into_inner()) =
+                Some(HashMap::from([(app_name.to_string(), AUMID.to_string())]));
+        }
+        Err(e) => warn!("Toast identity not registered, using PowerShell's: {e:#}"),
+    }
+}
+
+/// Id to show a toast from `app_name` under, registering it on first use.
+pub fn aumid_for(app_name: &str) -> String {
+    let mut registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(ids) = registered.as_mut() else {
+        return POWERSHELL_AUMID.to_string();
+    };
+    if let Some(id) = ids.get(app_name) {
+        return id.clone();
+    }
+    let Some(id) = alias_id(app_name) else {
+        return AUMID.to_string();
+    };
+    match icon_path().and_then(|icon| register_id(&id, app_name, &icon)) {
+        Ok(()) => {
+            ids.insert(app_name.to_string(), id.clone());
+            id
+        }
+        Err(e) => {
+            warn!("Toast identity for \"{app_name}\" not registered: {e:#}");
+            AUMID.to_string()
+        }
+    }
+}
@@ -0,0 +1,585 @@
+//! File drop: small files (wallpapers, notification sounds) sent from HA
+//!
+//! - `FileDownload` fetches a URL: `{"url": "http://ha:8123/local/sunset.jpg"}`,
+//!   optionally with a `"name"` to save it as (default: the URL's file name);
+//!   a bare URL works too
+//! - `FileChunk` carries a file in base64 pieces, for when the PC can't reach
+//!   a URL: `{"name": "sunset.jpg", "chunk": 0, "chunks": 12, "data": "..."}`.
+//!   Chunks may arrive in any order; the file is written once all are in.
+//!   One transfer at a time: a chunk for another name starts over
+//! - `SetWallpaper` sets the desktop wallpaper: a received image by name, an
+//!   image on a local disk by absolute path (no UNC share or device path),
+//!   or a URL, downloaded into the files folder first (with the same limits)
+//!
+//! Files only ever land in the `files` folder next to userConfig.json, under
+//! a plain file name (no paths). They must be at most `MAX_BYTES`, have an
+//! image or sound extension, and start with that format's signature. Each
+//! command reports to the command results topic.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use log::{info, warn};
+
+use crate::AppState;
+use crate::config::Config;
+
+/// Largest file accepted, either way in
+pub(crate) const MAX_BYTES: usize = 10 * 1024 * 1024;
+
+/// Most chunks a file may be sent in
+const MAX_CHUNKS: usize = 1024;
+
+/// A chunked transfer with no new chunk for this long is dropped.
+const CHUNK_TIMEOUT: Duration = Duration::from_mins(5);
+
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_mins(1);
+
+/// What a file may be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Image,
+    Sound,
+}
+
+/// An accepted extension, its kind, and a check of the file's first bytes
+type FileType = (&'static str, Kind, fn(&[u8]) -> bool);
+
+const TYPES: &[FileType] = &[
+    ("jpg", Kind::Image, |b| b.starts_with(&[0xFF, 0xD8, 0xFF])),
+    ("jpeg", Kind::Image, |b| b.starts_with(&[0xFF, 0xD8, 0xFF])),
+    ("png", Kind::Image, |b| b.starts_with(b"\x89PNG\r\n\x1a\n")),
+    ("bmp", Kind::Image, |b| b.starts_with(b"BM")),
+    ("gif", Kind::Image, |b| b.starts_with(b"GIF8")),
+    ("webp", Kind::Image, |b| {
+        b.starts_with(b"RIFF") && b.get(8..12) == Some(b"WEBP")
+    }),
+    ("wav", Kind::Sound, |b| {
+        b.starts_with(b"RIFF") && b.get(8..12) == Some(b"WAVE")
+    }),
+    ("ogg", Kind::Sound, |b| b.starts_with(b"OggS")),
+    ("mp3", Kind::Sound, |b| {
+        b.starts_with(b"ID3") || (b.len() > 1 && b[0] == 0xFF && b[1] & 0xE0 == 0xE0)
+    }),
+];
+
+/// The chunks received so far of the one transfer in progress.
+struct Pending {
+    name: String,
+    chunks: Vec<Option<Vec<u8>>>,
+    last: Instant,
+}
+
+static PENDING: Mutex<Option<Pending>> = Mutex::new(None);
+
+#[derive(serde::Deserialize)]
+struct DownloadRequest {
+    url: String,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// A `FileChunk` payload
+#[derive(serde::Deserialize)]
+struct Piece {
+    name: String,
+    chunk: usize,
+    chunks: usize,
+    data: String,
+}
+
+/// The `FileDownload` command.
+pub(crate) async fn download(payload: &str, state: &AppState) {
+    let result = match download_request(payload) {
+        Ok((url, name)) => tokio::task::spawn_blocking(move || {
+            let bytes = fetch(&url)?;
+            save(&name, &bytes)
+        })
+        .await
+        .unwrap_or_else(|e| Err(anyhow::anyhow!("download task failed: {e}"))),
+        Err(e) => Err(e),
+    };
+    report("FileDownload", result, state).await;
+}
+
+/// The `FileChunk` command. Only the last chunk of a file reports.
+pub(crate) async fn chunk(payload: &str, state: &AppState) {
+    let result = match serde_json::from_str::<Piece>(payload) {
+        Ok(chunk) => add_chunk(chunk, Instant::now()),
+        Err(e) => Err(anyhow::anyhow!("payload is not a file chunk: {e}")),
+    };
+    let result = match result {
+        Ok(Some((name, bytes))) => tokio::task::spawn_blocking(move || save(&name, &bytes))
+            .await
+            .unwrap_or_else(|e| Err(anyhow::anyhow!("write task failed: {e}"))),
+        Ok(None) => return,
+        Err(e) => Err(e),
+    };
+    report("FileChunk", result, state).await;
+}
+
+/// The `SetWallpaper` command; `payload` is a received image's name, a local
+/// path or a URL.
+pub(crate) async fn set_wallpaper(payload: &str, state: &AppState) {
+    let target = payload.trim().to_string();
+    let result = tokio::task::spawn_blocking(move || {
+        let path = wallpaper_image(&target)?;
+        apply_wallpaper(&path)?;
+        Ok(path)
+    })
+    .await
+    .unwrap_or_else(|e| Err(anyhow::anyhow!("wallpaper task failed: {e}")));
+    if let Ok(path) = &result {
+        info!("Wallpaper set to {}", path.display());
+    }
+    report("SetWallpaper", result, state).await;
+}
+
+async fn report(command: &str, result: Result<PathBuf>, state: &AppState) {
+    let result = match result {
+        Ok(path) => serde_json::json!({
+            "command": command,
+            "result": "success",
+            "file": path.file_name().map(|n| n.to_string_lossy()),
+        }),
+        Err(e) => {
+            warn!("{command}: {e:#}");
+            serde_json::json!({ "command": command, "result": "failed", "error": format!("{e:#}") })
+        }
+    };
+    state.mqtt.publish_command_result(&result).await;
+}
+
+/// URL and file name of a `FileDownload` payload.
+fn download_request(payload: &str) -> Result<(String, String)> {
+    let payload = payload.trim();
+    let request = if payload.starts_with('{') {
+        serde_json::from_str(payload).context("payload is not a download request")?
+    } else {
+        DownloadRequest {
+            url: payload.to_string(),
+            name: None,
+        }
+    };
+    let url = request.url.trim();
+    if !is_url(url) {
+        bail!("only http:// and https:// URLs can be downloaded");
+    }
+    let name = match request.name {
+        Some(name) => name,
+        None => {
+            let path = url.split(['?', '#']).next().unwrap_or_default();
+            path.rsplit('/').next().unwrap_or_default().to_string()
+        }
+    };
+    Ok((url.to_string(), name))
+}
+
+/// Body of `url`, refusing anything over `MAX_BYTES`.
+fn fetch(url: &str) -> Result<Vec<u8>> {
+    use ureq::tls::{RootCerts, TlsConfig, TlsProvider};
+
+    let tls = TlsConfig::builder()
+        .provider(TlsProvider::NativeTls)
+        .root_certs(RootCerts::PlatformVerifier)
+        .build();
+    let agent = ureq::Agent::new_with_config(
+        ureq::Agent::config_builder()
+            .tls_config(tls)
+            .timeout_global(Some(DOWNLOAD_TIMEOUT))
+            .build(),
+    );
+    let mut response = agent.get(url).call().context("download failed")?;
+    response
+        .body_mut()
+        .with_config()
+        .limit(MAX_BYTES as u64)
+        .read_to_vec()
+        .with_context(|| format!("download failed (files are limited to {MAX_BYTES} bytes)"))
+}
+
+/// Store a chunk; the whole file once it's complete.
+fn add_chunk(chunk: Piece, now: Instant) -> Result<Option<(String, Vec<u8>)>> {
+    if chunk.chunks == 0 || chunk.chunk >= chunk.chunks {
+        bail!("chunk {} of {} is out of range", chunk.chunk, chunk.chunks);
+    }
+    if chunk.chunks > MAX_CHUNKS {
+        bail!("files can be sent in at most {MAX_CHUNKS} chunks");
+    }
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(chunk.data.trim())
+        .context("chunk data is not base64")?;
+    let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+    let restart = pending.as_ref().is_none_or(|p| {
+        p.name != chunk.name
+            || p.chunks.len() != chunk.chunks
+            || now.duration_since(p.last) > CHUNK_TIMEOUT
+    });
+    if restart {
+        *pending = Some(Pending {
+            name: chunk.name.clone(),
+            chunks: vec![None; chunk.chunks],
+            last: now,
+        });
+    }
+    let Some(transfer) = pending.as_mut() else {
+        return Ok(None);
+    };
+    transfer.last = now;
+    transfer.chunks[chunk.chunk] = Some(data);
+    let bytes: usize = transfer.chunks.iter().flatten().map(Vec::len).sum();
+    if bytes > MAX_BYTES {
+        *pending = None;
+        bail!("file is over the {MAX_BYTES} byte limit");
+    }
+    if transfer.chunks.iter().any(Option::is_none) {
+        return Ok(None);
+    }
+    let Some(done) = pending.take() else {
+        return Ok(None);
+    };
+    let bytes = done.chunks.into_iter().flatten().flatten().collect();
+    Ok(Some((done.name, bytes)))
+}
+
+/// Check `bytes` and write them to the files folder as `name`.
+fn save(name: &str, bytes: &[u8]) -> Result<PathBuf> {
+    check(name, bytes)?;
+    let dir = Config::files_dir()?;
+    std::fs::create_dir_all(&dir).with_context(|| format!("can't create {}", dir.display()))?;
+    let path = dir.join(name);
+    crate::fsutil::write_atomic(&path, bytes, None)
+        .with_context(|| format!("can't write {}", path.display()))?;
+    info!("Received {} ({} bytes)", path.display(), bytes.len());
+    Ok(path)
+}
+
+/// The kind of file `name` / `bytes` is, or why it's refused.
+fn check(name: &str, bytes: &[u8]) -> Result<Kind> {
+    let (_, kind, signature) = file_type(name)?;
+    if bytes.len() > MAX_BYTES {
+        bail!("file is over the {MAX_BYTES} byte limit");
+    }
+    if !signature(bytes) {
+        bail!("'{name}' doesn't hold what its extension says");
+    }
+    Ok(*kind)
+}
+
+/// The `TYPES` entry for a file name's extension, if the name is a plain,
+/// acceptable file name.
+fn file_type(name: &str) -> Result<&'static FileType> {
+    let plain = !name.is_empty()
+        && name.len() <= 128
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | ' '));
+    if !plain {
+        bail!("'{name}' is not a plain file name");
+    }
+    let ext = Path::new(name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    TYPES.iter().find(|(e, ..)| *e == ext).with_context(|| {
+        let allowed: Vec<_> = TYPES.iter().map(|(e, ..)| *e).collect();
+        format!("'{name}': only {} files are accepted", allowed.join(", "))
+    })
+}
+
+/// The image a `SetWallpaper` payload points at, downloading a URL first.
+fn wallpaper_image(target: &str) -> Result<PathBuf> {
+    if is_url(target) {
+        let (url, name) = download_request(target)?;
+        let bytes = fetch(&url)?;
+        // No usable name in the URL (an image service's `/1920/1080`, say):
+        // name it after what it holds.
+        let name = match file_type(&name) {
+            Ok((_, Kind::Image, _)) => name,
+            _ => match TYPES
+                .iter()
+                .find(|(_, kind, signature)| *kind == Kind::Image && signature(&bytes))
+            {
+                Some((ext, ..)) => format!("wallpaper.{ext}"),
+                None => bail!("{url} is not an image"),
+            },
+        };
+        return save(&name, &bytes);
+    }
+    let path = Path::new(target);
+    if path.is_absolute() {
+        local_image(path)?;
+        return Ok(path.to_path_buf());
+    }
+    received(target, Kind::Image)
+}
+
+/// Check that an image outside the files folder is one, on a local disk.
+/// Every failure is the same error, so it doesn't tell whether a file exists.
+fn local_image(path: &Path) -> Result<()> {
+    use std::io::Read;
+
+    let is_image = || -> Option<()> {
+        // Before touching the path: a UNC one would already send the PC's
+        // credentials to that host.
+        if !is_local(path, false) {
+            return None;
+        }
+        let real = std::fs::canonicalize(path).ok()?;
+        if !is_local(&real, true) || !real.is_file() {
+            return None;
+        }
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        let (_, kind, signature) = TYPES
+            .iter()
+            .find(|(ext, ..)| name.ends_with(&format!(".{ext}")))?;
+        let mut header = Vec::with_capacity(16);
+        std::fs::File::open(&real)
+            .and_then(|f| f.take(16).read_to_end(&mut header))
+            .ok()?;
+        (*kind == Kind::Image && signature(&header)).then_some(())
+    };
+    is_image().with_context(|| format!("{} is not a readable local image", path.display()))
+}
+
+/// A drive-letter path (`C:\...`); not a UNC share, `\\?\` or `\\.\`
+/// device path. `canonical` also takes `\\?\C:\...`, which is how
+/// `canonicalize` spells a drive-letter path.
+#[cfg(windows)]
+fn is_local(path: &Path, canonical: bool) -> bool {
+    use std::path::{Component, Prefix};
+
+    path.has_root()
+        && match path.components().next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(_) => true,
+                Prefix::VerbatimDisk(_) => canonical,
+                _ => false,
+            },
+            _ => false,
+        }
+}
+
+/// An absolute path outside the kernel's device and process trees.
+#[cfg(not(windows))]
+fn is_local(path: &Path, _canonical: bool) -> bool {
+    path.is_absolute()
+        && !["/dev", "/proc", "/sys"]
+            .iter()
+            .any(|dir| path.starts_with(dir))
+}
+
+fn is_url(s: &str) -> bool {
+    let lower = s.to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://")
+}
+
+/// Path of a received file of `kind`.
+fn received(name: &str, want: Kind) -> Result<PathBuf> {
+    if file_type(name)?.1 != want {
+        bail!("'{name}' is not an image");
+    }
+    let path = Config::files_dir()?.join(name);
+    if !path.is_file() {
+        bail!("no file '{name}' has been received");
+    }
+    Ok(path)
+}
+
+#[cfg(windows)]
+fn apply_wallpaper(path: &Path) -> Result<()> {
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SPI_SETDESKWALLPAPER, SPIF_SENDCHANGE, SPIF_UPDATEINIFILE, SystemParametersInfoW,
+    };
+
+    use std::os::windows::ffi::OsStrExt;
+
+    let mut wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    // SAFETY: `wide` is a NUL-terminated path that outlives the call.
+    unsafe {
+        SystemParametersInfoW(
+            SPI_SETDESKWALLPAPER,
+            0,
+            Some(wide.as_mut_ptr().cast()),
+            SPIF_UPDATEINIFILE | SPIF_SENDCHANGE,
+        )
+    }
+    .context("SystemParametersInfo failed")
+}
+
+/// GNOME (and Cinnamon / Budgie, which read the same keys) via gsettings,
+/// KDE Plasma via `plasma-apply-wallpaperimage`, anything else via `feh`.
+#[cfg(unix)]
+fn apply_wallpaper(path: &Path) -> Result<()> {
+    use std::process::Command;
+
+    let file = path.to_string_lossy();
+    let uri = format!("file://{file}");
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let attempts: Vec<(&str, Vec<&str>)> = if desktop.contains("kde") {
+        vec![("plasma-apply-wallpaperimage", vec![&*file])]
+    } else {
+        vec![
+            (
+                "gsettings",
+                vec!["set", "org.gnome.desktop.background", "picture-uri", &uri],
+            ),
+            ("feh", vec!["--bg-fill", &*file]),
+        ]
+    };
+    let mut last = None;
+    for (program, args) in attempts {
+        match Command::new(program).args(&args).status() {
+            Ok(status) if status.success() => {
+                if program == "gsettings" {
+                    // The dark-style wallpaper, on GNOME 42+; absent before.
+                    let _ = Command::new("gsettings")
+                        .args([
+                            "set",
+                            "org.gnome.desktop.background",
+                            "picture-uri-dark",
+                            &uri,
+                        ])
+                        .status();
+                }
+                return Ok(());
+            }
+            Ok(status) => last = Some(format!("{program} exited with {status}")),
+            Err(e) => last = Some(format!("{program}: {e}")),
+        }
+    }
+    bail!(
+        "can't set the wallpaper: {}",
+        last.unwrap_or_else(|| "no wallpaper tool".to_string())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+    #[test]
+    fn test_check_file() {
+        assert_eq!(check("sunset.png", PNG).unwrap(), Kind::Image);
+        assert_eq!(
+            check("Chime.WAV", b"RIFF\0\0\0\0WAVEfmt ").unwrap(),
+            Kind::Sound
+        );
+        // Not what the extension says, not an accepted type, not a plain name.
+        assert!(check("sunset.jpg", PNG).is_err());
+        assert!(check("setup.exe", b"MZ").is_err());
+        for name in ["../sunset.png", "C:\\sunset.png", "a/b.png", ".png", ""] {
+            assert!(check(name, PNG).is_err(), "{name}");
+        }
+        assert!(check("big.png", &[PNG, &vec![0; MAX_BYTES]].concat()).is_err());
+    }
+
+    #[test]
+    fn test_local_wallpaper_must_be_an_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = dir.path().join("Movie Night.PNG");
+        std::fs::write(&png, PNG).unwrap();
+        let renamed = dir.path().join("notes.jpg");
+        std::fs::write(&renamed, PNG).unwrap();
+        let sound = dir.path().join("chime.ogg");
+        std::fs::write(&sound, b"OggS\0\x02").unwrap();
+
+        assert_eq!(wallpaper_image(png.to_str().unwrap()).unwrap(), png);
+        for path in [&renamed, &sound, &dir.path().join("missing.png")] {
+            assert!(wallpaper_image(path.to_str().unwrap()).is_err(), "{path:?}");
+        }
+        // A relative name is a received file, never a path.
+        assert!(wallpaper_image("../Movie Night.PNG").is_err());
+        // Existing or not, a file that isn't a usable image gets one error.
+        let missing = dir.path().join("missing.png");
+        assert_eq!(
+            wallpaper_image(renamed.to_str().unwrap()).unwrap_err().to_string(),
+            format!("{} is not a readable local image", renamed.display())
+        );
+        assert_eq!(
+            wallpaper_image(missing.to_str().unwrap()).unwrap_err().to_string(),
+            format!("{} is not a readable local image", missing.display())
+        );
+    }
+
+    #[test]
+    fn test_is_local() {
+        #[cfg(windows)]
+        {
+            assert!(is_local(Path::new(r"C:\Users\me\dark.png"), false));
+            assert!(is_local(Path::new(r"\\?\C:\Users\me\dark.png"), true));
+            for path in [
+                r"\\attacker\share\x.png",
+                r"\\?\C:\Users\me\dark.png",
+                r"\\?\UNC\attacker\share\x.png",
+                r"\\.\PhysicalDrive0",
+                r"C:dark.png",
+            ] {
+                assert!(!is_local(Path::new(path), false), "{path}");
+            }
+        }
+        #[cfg(not(windows))]
+        {
+            assert!(is_local(Path::new("/home/me/dark.png"), false));
+            for path in ["/dev/zero", "/proc/self/environ", "/sys/x.png", "dark.png"] {
+                assert!(!is_local(Path::new(path), false), "{path}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_download_request() {
+        assert_eq!(
+            download_request("http://ha:8123/local/sunset.jpg?v=2").unwrap(),
+            (
+                "http://ha:8123/local/sunset.jpg?v=2".to_string(),
+                "sunset.jpg".to_string()
+            )
+        );
+        let (url, name) =
+            download_request(r#"{"url": "https://example.com/x", "name": "bg.png"}"#).unwrap();
+        assert_eq!(
+            (url.as_str(), name.as_str()),
+            ("https://example.com/x", "bg.png")
+        );
+        assert!(download_request("file:///etc/passwd").is_err());
+        assert!(download_request(r#"{"name": "bg.png"}"#).is_err());
+    }
+
+    #[test]
+    fn test_chunks_assemble_in_any_order() {
+        let b64 = |b: &[u8]| base64::engine::general_purpose::STANDARD.encode(b);
+        let part = |chunk, data: &[u8]| Piece {
+            name: "chime.ogg".to_string(),
+            chunk,
+            chunks: 3,
+            data: b64(data),
+        };
+        let now = Instant::now();
+        assert!(add_chunk(part(2, b"cc"), now).unwrap().is_none());
+        assert!(add_chunk(part(0, b"Og"), now).unwrap().is_none());
+        let (name, bytes) = add_chunk(part(1, b"gS"), now).unwrap().unwrap();
+        assert_eq!(name, "chime.ogg");
+        assert_eq!(bytes, b"OggScc");
+
+        assert!(add_chunk(part(3, b"xx"), now).is_err());
+        assert!(
+            add_chunk(
+                Piece {
+                    data: "not base64!".to_string(),
+                    ..part(0, b"")
+                },
+                now
+            )
+            .is_err()
+        );
+    }
+}
@@ -1,6 +1,6 @@
 //! System tray icon (Windows). A hidden message-only window on a dedicated thread
 //! owns a Shell_NotifyIcon tray entry with a right-click menu (Open Settings /
-//! Quit) and a double-click-to-open shortcut. Started/stopped live by the manager
+//! Send Button Press / Quit) and a double-click-to-open shortcut. Started/stopped live by the manager
 //! below as the `show_tray_icon` config flag changes, so it's fully toggleable.
 //!
 //! Mirrors the hidden-window + message-pump idiom used by the session/power
@@ -26,18 +26,23 @@ use windows::Win32::UI::WindowsAndMessaging::{
 };
 
 use crate::AppState;
+use crate::events::BridgeEvent;
 
 /// Message the tray icon posts to our window on mouse events.
 const WM_TRAYICON: u32 = WM_APP + 1;
 /// Menu command ids.
 const ID_OPEN: usize = 1;
 const ID_QUIT: usize = 2;
+const ID_BUTTON: usize = 3;
 /// Our single tray icon's id within the window.
 const TRAY_UID: u32 = 1;
 
 /// Per-window state handed to the wnd_proc via GWLP_USERDATA.
 struct TrayContext {
     shutdown_tx: broadcast::Sender<()>,
+    /// For publishing `button_pressed` from the (non-async) tray thread.
+    state: Arc<AppState>,
+    rt: tokio::runtime::Handle,
 }
 
 /// Async manager: create/destroy the tray as `show_tray_icon` changes, and tear it
@@ -50,7 +55,7 @@ pub async fn run_manager(state: Arc<AppState>) {
     loop {
         let want = state.config.read().await.show_tray_icon;
         match (want, current) {
-            (true, None) => current = spawn_tray(state.shutdown_tx.clone()).await,
+            (true, None) => current = spawn_tray(Arc::clone(&state)).await,
             (false, Some(hwnd)) => {
                 stop_tray(hwnd);
                 current = None;
@@ -76,12 +81,13 @@ pub async fn run_manager(state: Arc<AppState>) {
 }
 
 /// Spawn the tray thread and return its window handle once created.
-async fn spawn_tray(shutdown_tx: broadcast::Sender<()>) -> Option<isize> {
+async fn spawn_tray(state: Arc<AppState>) -> Option<isize> {
     let (hwnd_tx, hwnd_rx) = tokio::sync::oneshot::channel::<isize>();
+    let rt = tokio::runtime::Handle::current();
     if let Err(e) = std::thread::Builder::new()
         .name("tray".into())
         .stack_size(256 * 1024)
-        .spawn(move || tray_thread(&shutdown_tx, hwnd_tx))
+        .spawn(move || tray_thread(state, rt, hwnd_tx))
     {
         error!("Failed to spawn tray thread: {e}");
         return None;
@@ -96,7 +102,11 @@ fn stop_tray(hwnd: isize) {
     }
 }
 
-fn tray_thread(shutdown_tx: &broadcast::Sender<()>, hwnd_tx: tokio::sync::oneshot::Sender<isize>) {
+fn tray_thread(
+    state: Arc<AppState>,
+    rt: tokio::runtime::Handle,
+    hwnd_tx: tokio::sync::oneshot::Sender<isize>,
+) {
     unsafe {
         let class_name = windows::core::w!("PCAgentTray");
         let wc = WNDCLASSEXW {
@@ -128,9 +138,11 @@ fn tray_thread(shutdown_tx: &broadcast::Sender<()>, hwnd_tx: tokio::sync::onesho
             }
         };
 
-        // Stash the shutdown sender for the wnd_proc.
+        // Stash the shutdown sender and app state for the wnd_proc.
         let ctx = Box::new(TrayContext {
-            shutdown_tx: shutdown_tx.clone(),
+            shutdown_tx: state.shutdown_tx.clone(),
+            state,
+            rt,
         });
         let ctx_ptr = Box::into_raw(ctx);
         SetWindowLongPtrW(hwnd, GWLP_USERDATA, ctx_ptr as isize);
@@ -214,6 +226,12 @@ unsafe fn show_menu(hwnd: HWND) {
             return;
         };
         let _ = AppendMenuW(menu, MF_STRING, ID_OPEN, windows::core::w!("Open Settings"));
+        let _ = AppendMenuW(
+            menu,
+            MF_STRING,
+            ID_BUTTON,
+            windows::core::w!("Send Button Press"),
+        );
         let _ = AppendMenuW(
             menu,
             MF_STRING,
@@ -239,6 +257,15 @@ unsafe fn show_menu(hwnd: HWND) {
 
         match cmd.0 as usize {
             ID_OPEN => open_settings(),
+            ID_BUTTON => {
+                if let Some(ctx) = context(hwnd) {
+                    info!("Tray: Send Button Press selected");
+                    let state = Arc::clone(&ctx.state);
+                    ctx.rt.spawn(async move {
+                        state.mqtt.publish_event(&BridgeEvent::ButtonPressed).await;
+                    });
+                }
+            }
             ID_QUIT => {
                 if let Some(ctx) = context(hwnd) {
                     info!("Tray: Quit selected");
@@ -10,6 +10,7 @@ use egui::{Color32, RichText, Rounding};
 
 use crate::config::{
     Config, CustomCommand, CustomCommandType, CustomSensor, CustomSensorType, FeatureConfig,
+    FolderMetric,
 };
 
 use super::model::{
@@ -425,6 +426,24 @@ fn feature_interval_field(id: &str) -> Option<&'static str> {
         // (steam downloads is event-driven, interval == 0, so it never reaches
         // this mapping - there's deliberately no arm for it.)
         "running_game" | "game_catalog" => "game_sensor",
+        "windows_update" => "windows_update",
+        "wake_timers" => "wake_timers",
+        "sleep_blockers" => "sleep_blockers",
+        "power_plan" => "power_plan",
+        "device_batteries" => "device_batteries",
+        "gamepad" => "gamepad",
+        "vr" => "vr",
+        "transcoding" => "transcoding",
+        "display_mode" => "display_mode",
+        "theme" => "theme",
+        "media_playing" => "media_playing",
+        "network_connection" => "network_connection",
+        "disk_health" => "disk_health",
+        "latency" => "latency",
+        "public_ip" => "public_ip",
+        "printers" => "printers",
+        "docker" => "docker",
+        "usb_devices" => "usb_devices",
         _ => return None,
     })
 }
@@ -440,6 +459,24 @@ fn interval_field_get(iv: &crate::config::IntervalConfig, field: &str) -> u32 {
         "last_active" => iv.last_active,
         "steam_check" => iv.steam_check,
         "game_sensor" => iv.game_sensor,
+        "windows_update" => iv.windows_update,
+        "wake_timers" => iv.wake_timers,
+        "sleep_blockers" => iv.sleep_blockers,
+        "power_plan" => iv.power_plan,
+        "device_batteries" => iv.device_batteries,
+        "gamepad" => iv.gamepad,
+        "vr" => iv.vr,
+        "transcoding" => iv.transcoding,
+        "display_mode" => iv.display_mode,
+        "theme" => iv.theme,
+        "media_playing" => iv.media_playing,
+        "network_connection" => iv.network_connection,
+        "disk_health" => iv.disk_health,
+        "latency" => iv.latency,
+        "public_ip" => iv.public_ip,
+        "printers" => iv.printers,
+        "docker" => iv.docker,
+        "usb_devices" => iv.usb_devices,
         _ => 0,
     };
     v.min(u64::from(u32::MAX)) as u32
@@ -457,6 +494,24 @@ fn interval_field_set(iv: &mut crate::config::IntervalConfig, field: &str, v: u3
         "last_active" => iv.last_active = v,
         "steam_check" => iv.steam_check = v,
         "game_sensor" => iv.game_sensor = v,
+        "windows_update" => iv.windows_update = v,
+        "wake_timers" => iv.wake_timers = v,
+        "sleep_blockers" => iv.sleep_blockers = v,
+        "power_plan" => iv.power_plan = v,
+        "device_batteries" => iv.device_batteries = v,
+        "gamepad" => iv.gamepad = v,
+        "vr" => iv.vr = v,
+        "transcoding" => iv.transcoding = v,
+        "display_mode" => iv.display_mode = v,
+        "theme" => iv.theme = v,
+        "media_playing" => iv.media_playing = v,
+        "network_connection" => iv.network_connection = v,
+        "disk_health" => iv.disk_health = v,
+        "latency" => iv.latency = v,
+        "public_ip" => iv.public_ip = v,
+        "printers" => iv.printers = v,
+        "docker" => iv.docker = v,
+        "usb_devices" => iv.usb_devices = v,
         _ => {}
     }
 }
@@ -471,7 +526,9 @@ fn flag_get(f: &FeatureConfig, id: &str) -> Option<bool> {
         "cpu" => f.cpu_sensor,
         "memory" => f.memory_sensor,
         "active_window" => f.active_window,
+        "window_history" => f.window_history,
         "session" => f.session_state,
+        "remote_desktop" => f.remote_desktop,
         "audio_device" => f.audio_device,
         "mic" => f.mic,
         "webcam" => f.webcam,
@@ -479,6 +536,7 @@ fn flag_get(f: &FeatureConfig, id: &str) -> Option<bool> {
         "idle" => f.idle_tracking,
         "running_game" => f.running_game,
         "game_catalog" => f.game_catalog,
+        "game_art" => f.game_art,
         "steam_library" => f.steam_library,
         "launch_game" => f.launch_game,
         "close_game" => f.close_game,
@@ -494,6 +552,34 @@ fn flag_get(f: &FeatureConfig, id: &str) -> Option<bool> {
         "lock" => f.cmd_lock,
         "logoff" => f.cmd_logoff,
         "monitor" => f.cmd_monitor,
+        "windows_update" => f.windows_update,
+        "wake_timers" => f.wake_timers,
+        "sleep_blockers" => f.sleep_blockers,
+        "windows_update_install" => f.cmd_windows_update_install,
+        "power_plan" => f.power_plan,
+        "device_batteries" => f.device_batteries,
+        "gamepad" => f.gamepad,
+        "vr" => f.vr,
+        "transcoding" => f.transcoding,
+        "display_mode" => f.display_mode,
+        "theme" => f.theme,
+        "media_playing" => f.media_playing,
+        "network_connection" => f.network_connection,
+        "disk_health" => f.disk_health,
+        "latency" => f.latency,
+        "public_ip" => f.public_ip,
+        "printers" => f.printers,
+        "docker" => f.docker,
+        "usb_devices" => f.usb_devices,
+        "openrgb" => f.openrgb,
+        "perf_mode" => f.perf_mode,
+        "focus_mode" => f.focus_mode,
+        "speedtest" => f.cmd_speedtest,
+        "disk_cleanup" => f.cmd_disk_cleanup,
+        "install_update" => f.cmd_install_update,
+        "config_backup" => f.cmd_config_backup,
+        "file_transfer" => f.cmd_file_transfer,
+        "wallpaper" => f.cmd_wallpaper,
         _ => return None,
     })
 }
@@ -508,7 +594,9 @@ fn flag_set(f: &mut FeatureConfig, id: &str, v: bool) {
         "cpu" => f.cpu_sensor = v,
         "memory" => f.memory_sensor = v,
         "active_window" => f.active_window = v,
+        "window_history" => f.window_history = v,
         "session" => f.session_state = v,
+        "remote_desktop" => f.remote_desktop = v,
         "audio_device" => f.audio_device = v,
         "mic" => f.mic = v,
         "webcam" => f.webcam = v,
@@ -516,6 +604,7 @@ fn flag_set(f: &mut FeatureConfig, id: &str, v: bool) {
         "idle" => f.idle_tracking = v,
         "running_game" => f.running_game = v,
         "game_catalog" => f.game_catalog = v,
+        "game_art" => f.game_art = v,
         "steam_library" => f.steam_library = v,
         "launch_game" => f.launch_game = v,
         "close_game" => f.close_game = v,
@@ -531,6 +620,34 @@ fn flag_set(f: &mut FeatureConfig, id: &str, v: bool) {
         "lock" => f.cmd_lock = v,
         "logoff" => f.cmd_logoff = v,
         "monitor" => f.cmd_monitor = v,
+        "windows_update" => f.windows_update = v,
+        "wake_timers" => f.wake_timers = v,
+        "sleep_blockers" => f.sleep_blockers = v,
+        "windows_update_install" => f.cmd_windows_update_install = v,
+        "power_plan" => f.power_plan = v,
+        "device_batteries" => f.device_batteries = v,
+        "gamepad" => f.gamepad = v,
+        "vr" => f.vr = v,
+        "transcoding" => f.transcoding = v,
+        "display_mode" => f.display_mode = v,
+        "theme" => f.theme = v,
+        "media_playing" => f.media_playing = v,
+        "network_connection" => f.network_connection = v,
+        "disk_health" => f.disk_health = v,
+        "latency" => f.latency = v,
+        "public_ip" => f.public_ip = v,
+        "printers" => f.printers = v,
+        "docker" => f.docker = v,
+        "usb_devices" => f.usb_devices = v,
+        "openrgb" => f.openrgb = v,
+        "perf_mode" => f.perf_mode = v,
+        "focus_mode" => f.focus_mode = v,
+        "speedtest" => f.cmd_speedtest = v,
+        "disk_cleanup" => f.cmd_disk_cleanup = v,
+        "install_update" => f.cmd_install_update = v,
+        "config_backup" => f.cmd_config_backup = v,
+        "file_transfer" => f.cmd_file_transfer = v,
+        "wallpaper" => f.cmd_wallpaper = v,
         _ => {}
     }
 }
@@ -687,7 +804,7 @@ fn top_bar(app: &App, ctx: &egui::Context) {
                     // can't know the broker connection - but it CAN tell whether the
                     // background agent is running (via the singleton probe). Show that
                     // instead of a fake connection status.
-                    let (txt, col) = if crate::instance_already_running() {
+                    let (txt, col) = if crate::instance::is_running() {
                         ("Agent running", GREEN)
                     } else {
                         ("Agent stopped", GREY)
@@ -1024,6 +1141,12 @@ fn custom_view(app: &mut App, ui: &mut egui::Ui, master_on: bool, ct: Kind) {
                         file_path: None,
                         registry_key: None,
                         registry_value: None,
+                        pattern: None,
+                        folder: None,
+                        metric: FolderMetric::Count,
+                        host: None,
+                        timeout_ms: None,
+                        service: None,
                     });
                 }
                 if let Some(i) = remove {
@@ -1050,6 +1173,8 @@ fn custom_view(app: &mut App, ui: &mut egui::Ui, master_on: bool, ct: Kind) {
                         path: None,
                         args: None,
                         command: None,
+                        service: None,
+                        sandbox: None,
                     });
                 }
                 if let Some(i) = remove {
@@ -1119,6 +1244,10 @@ fn custom_sensor_row(ui: &mut egui::Ui, s: &mut CustomSensor, master_on: bool, i
                             CustomSensorType::ProcessExists,
                             CustomSensorType::FileContents,
                             CustomSensorType::Registry,
+                            CustomSensorType::LogTail,
+                            CustomSensorType::Folder,
+                            CustomSensorType::Ping,
+                            CustomSensorType::Service,
                         ] {
                             let lbl = sensor_type_label(&t);
                             ui.selectable_value(&mut s.sensor_type, t, lbl);
@@ -1142,6 +1271,38 @@ fn custom_sensor_row(ui: &mut egui::Ui, s: &mut CustomSensor, master_on: bool, i
                     ui.add_space(TIGHT);
                     opt_field(ui, "Value name", &mut s.registry_value, 220.0);
                 }
+                CustomSensorType::LogTail => {
+                    opt_field(ui, "File path", &mut s.file_path, 300.0);
+                    ui.add_space(TIGHT);
+                    opt_field(ui, "Pattern", &mut s.pattern, 300.0);
+                }
+                CustomSensorType::Ping => {
+                    opt_field(ui, "Host", &mut s.host, 220.0);
+                }
+                CustomSensorType::Service => {
+                    opt_field(ui, "Service", &mut s.service, 220.0);
+                }
+                CustomSensorType::Folder => {
+                    opt_field(ui, "Folder", &mut s.folder, 300.0);
+                    ui.add_space(TIGHT);
+                    ui.horizontal(|ui| {
+                        ui.add_sized(
+                            [108.0, 18.0],
+                            egui::Label::new(RichText::new("Report").size(12.0).color(GREY)),
+                        );
+                        egui::ComboBox::from_id_salt(("cs_metric", idx))
+                            .selected_text(folder_metric_label(s.metric))
+                            .show_ui(ui, |ui| {
+                                for m in [
+                                    FolderMetric::Count,
+                                    FolderMetric::Size,
+                                    FolderMetric::Newest,
+                                ] {
+                                    ui.selectable_value(&mut s.metric, m, folder_metric_label(m));
+                                }
+                            });
+                    });
+                }
             }
             ui.add_space(TIGHT);
             ui.horizontal(|ui| {
@@ -1212,6 +1373,9 @@ fn custom_command_row(
                             CustomCommandType::Shell,
                             CustomCommandType::Powershell,
                             CustomCommandType::Executable,
+                            CustomCommandType::ServiceStart,
+                            CustomCommandType::ServiceStop,
+                            CustomCommandType::ServiceRestart,
                         ] {
                             let lbl = command_type_label(&t);
                             ui.selectable_value(&mut c.command_type, t, lbl);
@@ -1229,6 +1393,11 @@ fn custom_command_row(
                 CustomCommandType::Executable => {
                     opt_field(ui, "Path", &mut c.path, 300.0);
                 }
+                CustomCommandType::ServiceStart
+                | CustomCommandType::ServiceStop
+                | CustomCommandType::ServiceRestart => {
+                    opt_field(ui, "Service", &mut c.service, 220.0);
+                }
             }
             ui.add_space(TIGHT);
             ui.horizontal(|ui| {
@@ -1248,6 +1417,18 @@ fn sensor_type_label(t: &CustomSensorType) -> &'static str {
         CustomSensorType::ProcessExists => "Process running",
         CustomSensorType::FileContents => "File contents",
         CustomSensorType::Registry => "Registry value",
+        CustomSensorType::LogTail => "Log tail",
+        CustomSensorType::Folder => "Folder",
+        CustomSensorType::Ping => "Ping",
+        CustomSensorType::Service => "Service running",
+    }
+}
+
+fn folder_metric_label(m: FolderMetric) -> &'static str {
+    match m {
+        FolderMetric::Count => "File count",
+        FolderMetric::Size => "Total size (MB)",
+        FolderMetric::Newest => "Newest file",
     }
 }
 
@@ -1256,6 +1437,9 @@ fn command_type_label(t: &CustomCommandType) -> &'static str {
         CustomCommandType::Shell => "Shell command",
         CustomCommandType::Powershell => "PowerShell script",
         CustomCommandType::Executable => "Executable",
+        CustomCommandType::ServiceStart => "Start service",
+        CustomCommandType::ServiceStop => "Stop service",
+        CustomCommandType::ServiceRestart => "Restart service",
     }
 }
 
@@ -54,28 +54,32 @@ pub fn start(cfg: &Config) -> LiveView {
 
 fn sub_topics(dev: &str) -> [String; 3] {
     [
-        format!("homeassistant/sensor/{dev}/availability"),
-        format!("homeassistant/sensor/{dev}/runninggames/state"),
-        format!("homeassistant/sensor/{dev}/steam_updating/attributes"),
+        format!("pc-bridge/{dev}/availability"),
+        format!("pc-bridge/{dev}/runninggames/state"),
+        format!("pc-bridge/{dev}/steam_updating/attributes"),
     ]
 }
 
 fn run(broker: String, user: String, pass: String, dev: String, state: Arc<Mutex<LiveState>>) {
-    // No broker configured (first run / load error): nothing to connect to.
-    if broker.trim().is_empty() {
+    // No broker configured (first run / load error) or one that doesn't
+    // parse: nothing to connect to.
+    let Ok(broker) = crate::mqtt::BrokerUrl::parse(&broker) else {
         if let Ok(mut s) = state.lock() {
             s.attempted = true;
         }
         return;
-    }
-    let (host, port, tls) = crate::power::sync_mqtt::parse_broker_url(&broker);
+    };
     // Distinct client id so we don't clash with the agent's session on the broker.
-    let mut opts = MqttOptions::new(format!("pc-bridge-ui-{}", std::process::id()), host, port);
+    let mut opts = MqttOptions::new(
+        format!("pc-bridge-ui-{}", std::process::id()),
+        broker.connect_host(),
+        broker.port,
+    );
     opts.set_keep_alive(Duration::from_secs(30));
     if !user.is_empty() {
         opts.set_credentials(user, pass);
     }
-    if tls {
+    if broker.tls {
         opts.set_transport(rumqttc::Transport::tls_with_config(
             rumqttc::TlsConfiguration::Native,
         ));
@@ -212,6 +212,19 @@ pub fn registry(device_id: &str) -> Vec<Feature> {
             "",
             "Rebuilt when the library changes",
         ),
+        s(
+            "game_art",
+            "Game Art",
+            "Box art of the game you're playing, for dashboard picture cards.",
+            Games,
+            false,
+            Running,
+            "header.jpg",
+            0,
+            "image.dank0i_pc_game_art",
+            "Running Game",
+            "Steam's local library cache",
+        ),
         s(
             "steam_library",
             "Steam Library Sync",
@@ -262,6 +275,18 @@ pub fn registry(device_id: &str) -> Vec<Feature> {
             "",
             "PowerShell CloseMainWindow()",
         ),
+        a(
+            "perf_mode",
+            "Perf Mode",
+            "Pauses system and custom sensors while you play.",
+            Games,
+            false,
+            false,
+            "paused: game",
+            "switch.dank0i_pc_perf_mode",
+            "",
+            "Follows the running game; the switch pauses by hand",
+        ),
         // Hardware (polled telemetry)
         s(
             "gpu",
@@ -315,6 +340,19 @@ pub fn registry(device_id: &str) -> Vec<Feature> {
             "",
             "",
         ),
+        s(
+            "disk_health",
+            "Disk Health",
+            "SMART status, temperature and reallocated sectors per disk.",
+            Hardware,
+            false,
+            Running,
+            "2 disks OK",
+            600,
+            "binary_sensor.dank0i_pc_disk_health_*",
+            "smartmontools (root on Linux)",
+            "smartctl / Storage Management WMI",
+        ),
         s(
             "network",
             "Network",
@@ -328,10 +366,100 @@ pub fn registry(device_id: &str) -> Vec<Feature> {
             "",
             "NIC counters",
         ),
+        s(
+            "network_connection",
+            "Network Connection",
+            "Connected network, whether it's metered, and whether a VPN is up.",
+            Hardware,
+            false,
+            Running,
+            "HomeWiFi",
+            30,
+            "sensor.dank0i_pc_network_connection",
+            "",
+            "Connection profile (Windows) / NetworkManager (Linux)",
+        ),
+        s(
+            "latency",
+            "Latency",
+            "Round trip to the MQTT broker (or HA host).",
+            Hardware,
+            false,
+            Running,
+            "3.2 ms",
+            60,
+            "sensor.dank0i_pc_latency",
+            "",
+            "ICMP echo, TCP connect fallback",
+        ),
+        s(
+            "public_ip",
+            "Public IP",
+            "External IP address, checked rarely.",
+            Hardware,
+            false,
+            Running,
+            "203.0.113.7",
+            3600,
+            "sensor.dank0i_pc_public_ip",
+            "",
+            "api.ipify.org",
+        ),
+        s(
+            "printers",
+            "Printers",
+            "Status and queued jobs of the configured printers, plus a Print command.",
+            Hardware,
+            false,
+            Running,
+            "idle · 0 jobs",
+            30,
+            "sensor.dank0i_pc_printer_*",
+            "printers list in config",
+            "Print spooler (Windows) / CUPS lpstat (Linux)",
+        ),
+        s(
+            "docker",
+            "Docker",
+            "Running containers, plus state and start / stop buttons for the configured ones.",
+            Hardware,
+            false,
+            Running,
+            "2 running",
+            30,
+            "binary_sensor.dank0i_pc_docker_*",
+            "docker.containers list in config",
+            "Docker / Podman API socket",
+        ),
+        s(
+            "usb_devices",
+            "USB Devices",
+            "Plug / unplug events, and presence of the configured USB devices.",
+            Hardware,
+            false,
+            Running,
+            "Phone on",
+            60,
+            "binary_sensor.dank0i_pc_usb_*",
+            "usb_devices list in config",
+            "WM_DEVICECHANGE + SetupAPI (Windows) / udev uevents + sysfs (Linux)",
+        ),
+        a(
+            "speedtest",
+            "Speed Test",
+            "Measure download, upload and ping on demand.",
+            Hardware,
+            false,
+            false,
+            "run a speed test",
+            "button.dank0i_pc_speedtest",
+            "",
+            "Cloudflare HTTP test, or speedtest_command",
+        ),
         s(
             "uptime",
             "Uptime",
-            "Time since last boot.",
+            "Time since last boot, and the boot timestamp.",
             Hardware,
             true,
             Running,
@@ -341,6 +469,31 @@ pub fn registry(device_id: &str) -> Vec<Feature> {
             "",
             "",
         ),
+        s(
+            "device_batteries",
+            "Device Batteries",
+            "Battery level of connected controllers, headsets and mice.",
+            Hardware,
+            false,
+            Running,
+            "DualSense 15%",
+            300,
+            "sensor.dank0i_pc_device_battery_*",
+            "",
+            "Bluetooth battery level from PnP (Windows) / HID power supplies (Linux)",
+        ),
+        a(
+            "openrgb",
+            "RGB Lighting",
+            "Case, keyboard and peripheral RGB as a Home Assistant light.",
+            Hardware,
+            false,
+            false,
+            "color / brightness / off",
+            "light.dank0i_pc_rgb",
+            "OpenRGB running with its SDK server on",
+            "OpenRGB SDK (openrgb.host:port)",
+        ),
         s(
             "hwinfo",
             "HWiNFO Bridge",
@@ -394,6 +547,19 @@ pub fn registry(device_id: &str) -> Vec<Feature> {
             "",
             "System media transport (GSMTC)",
         ),
+        s(
+            "media_playing",
+            "Audio Playing",
+            "Whether sound is coming out of the speakers, and from which app.",
+            Audio,
+            false,
+            Running,
+            "on (Spotify.exe)",
+            2,
+            "binary_sensor.dank0i_pc_media_playing",
+            "",
+            "Output peak meter (Windows) / parec on the sink monitor (Linux)",
+        ),
         s(
             "mic",
             "Microphone In Use",
@@ -459,6 +625,19 @@ pub fn registry(device_id: &str) -> Vec<Feature> {
             "",
             "Foreground-window change events",
         ),
+        s(
+            "window_history",
+            "Window History",
+            "Recent foreground windows and how long each had focus.",
+            Presence,
+            false,
+            Running,
+            "20",
+            60,
+            "sensor.dank0i_pc_window_history",
+            "Active Window",
+            "Records focus changes; entries attribute",
+        ),
         s(
             "session",
             "Session State",
@@ -472,11 +651,75 @@ pub fn registry(device_id: &str) -> Vec<Feature> {
             "",
             "Session notifications",
         ),
+        s(
+            "remote_desktop",
+            "Remote Desktop",
+            "Someone connected over Remote Desktop.",
+            Presence,
+            false,
+            Running,
+            "off",
+            0,
+            "binary_sensor.dank0i_pc_remote_desktop",
+            "",
+            "Session notifications (Windows) / logind remote sessions (Linux)",
+        ),
+        s(
+            "gamepad",
+            "Gamepad",
+            "Whether a controller is connected and being played.",
+            Presence,
+            false,
+            Running,
+            "active",
+            2,
+            "binary_sensor.dank0i_pc_gamepad_active",
+            "",
+            "XInput (Windows) / evdev (Linux)",
+        ),
+        s(
+            "vr",
+            "VR Session",
+            "Whether a VR headset session is running, and the VR title.",
+            Presence,
+            false,
+            Running,
+            "on (Half-Life: Alyx)",
+            5,
+            "binary_sensor.dank0i_pc_vr_active",
+            "",
+            "VR compositor process (SteamVR, Oculus, Monado, WiVRn) + Steam's running app",
+        ),
+        s(
+            "transcoding",
+            "Transcoding",
+            "Whether the GPU is encoding video (Plex / Jellyfin transcodes, OBS), e.g. to hold off sleep.",
+            Presence,
+            false,
+            Running,
+            "on (Plex Transcoder)",
+            10,
+            "binary_sensor.dank0i_pc_transcoding",
+            "",
+            "GPU Video Encode engine counters (Windows) / DRM fdinfo, nvidia-smi (Linux)",
+        ),
+        a(
+            "focus_mode",
+            "Focus Mode",
+            "Timed focus sessions that close distracting apps and hold back notifications.",
+            Presence,
+            false,
+            false,
+            "25 min left",
+            "switch.dank0i_pc_focus_mode",
+            "",
+            "Closes focus.blocked_apps while a session runs",
+        ),
         // Power (event-driven state + actions)
         s(
             "sleep_wake",
             "Sleep / Wake",
-            "Sleep state and wake events.",
+            "Sleep state, on / sleep / off PC state and wake events.",
             Power,
             true,
             Running,
@@ -571,6 +814,156 @@ pub fn registry(device_id: &str) -> Vec<Feature> {
             "",
             "Monitor power message",
         ),
+        s(
+            "windows_update",
+            "Windows Update",
+            "Pending updates and whether a reboot is needed.",
+            Power,
+            false,
+            Running,
+            "2 pending",
+            3600,
+            "sensor.dank0i_pc_windows_updates",
+            "",
+            "Windows Update Agent search (PSWindowsUpdate fallback)",
+        ),
+        s(
+            "wake_timers",
+            "Wake Timers",
+            "When the PC next wakes itself, e.g. for Windows Update.",
+            Power,
+            false,
+            Running,
+            "03:00",
+            300,
+            "sensor.dank0i_pc_next_wake",
+            "",
+            "Wake-to-run scheduled tasks (Windows) / RTC wake alarm (Linux)",
+        ),
+        s(
+            "sleep_blockers",
+            "Sleep Blockers",
+            "What's keeping the PC awake, e.g. a browser tab playing audio.",
+            Power,
+            false,
+            Running,
+            "1 (chrome.exe)",
+            30,
+            "sensor.dank0i_pc_sleep_blockers",
+            "",
+            "powercfg /requests, elevated agent (Windows) / logind inhibitors (Linux)",
+        ),
+        a(
+            "disk_cleanup",
+            "Disk Cleanup",
+            "Empty the recycle bin and temp files older than a day.",
+            Power,
+            false,
+            false,
+            "free up space",
+            "button.dank0i_pc_disk_cleanup",
+            "",
+            "SHEmptyRecycleBin + %TEMP% (Windows) / XDG Trash + /tmp (Linux)",
+        ),
+        a(
+            "windows_update_install",
+            "Install Windows Updates",
+            "Download and install pending updates (no automatic reboot).",
+            Power,
+            true,
+            false,
+            "install pending updates",
+            "button.dank0i_pc_windows_update_install",
+            "Agent running elevated (e.g. as a service)",
+            "Windows Update Agent installer",
+        ),
+        s(
+            "power_plan",
+            "Power Plan",
+            "Active power plan (switchable from HA) and AC power source.",
+            Power,
+            false,
+            Running,
+            "Balanced",
+            10,
+            "select.dank0i_pc_power_plan",
+            "",
+            "powercfg (Windows) / power-profiles-daemon (Linux)",
+        ),
+        s(
+            "display_mode",
+            "Display Mode",
+            "Switch resolution / refresh rate between configured modes (e.g. 4K60, 1440p144).",
+            Hardware,
+            false,
+            Running,
+            "1440p144",
+            10,
+            "select.dank0i_pc_display_mode",
+            "display_modes configured",
+            "ChangeDisplaySettingsEx (Windows) / xrandr (Linux, X11)",
+        ),
+        s(
+            "theme",
+            "Theme",
+            "Switch between light and dark, e.g. from a sunset automation.",
+            Hardware,
+            false,
+            Running,
+            "dark",
+            30,
+            "select.dank0i_pc_theme",
+            "",
+            "Personalize registry values (Windows) / GNOME color-scheme, KDE color scheme (Linux)",
+        ),
+        a(
+            "install_update",
+            "Install Update",
+            "Install the pending PC Bridge release from HA's update entity.",
+            Power,
+            true,
+            false,
+            "update and restart agent",
+            "update.dank0i_pc_update_available",
+            "update_channel not \"disabled\"",
+            "Signed GitHub release (minisign + SHA-256)",
+        ),
+        a(
+            "config_backup",
+            "Config Backup / Restore",
+            "Publish the config (secrets redacted) and restore one sent from HA.",
+            Power,
+            false,
+            false,
+            "back up or replace userConfig.json",
+            "button.dank0i_pc_backupconfig",
+            "",
+            "pc-bridge/config/<device> + RestoreConfig payload",
+        ),
+        a(
+            "file_transfer",
+            "File Drop",
+            "Receive wallpapers and sounds from HA.",
+            Power,
+            false,
+            false,
+            "write images and sounds to the files folder",
+            "",
+            "",
+            "FileDownload / FileChunk payloads",
+        ),
+        a(
+            "wallpaper",
+            "Set Wallpaper",
+            "Change the desktop wallpaper from HA scenes.",
+            Power,
+            false,
+            false,
+            "change the desktop wallpaper",
+            "",
+            "",
+            "SystemParametersInfo (Windows) / gsettings, Plasma or feh (Linux)",
+        ),
         // Notifications
         a(
             "notifications",
@@ -736,7 +1129,8 @@ pub fn games_to_library(games: &HashMap<String, GameConfig>) -> Vec<Game> {
 /// Fold the edited library back into a `games` config map. Entries with a blank
 /// process name are dropped (incomplete rows). `prev` preserves the
 /// `auto_discovered` flag for games already known to Steam discovery so a UI
-/// save does not make them look manually added.
+/// save does not make them look manually added, and the window-title / AUMID
+/// detection settings the UI doesn't edit.
 pub fn library_to_games(
     library: &[Game],
     prev: &HashMap<String, GameConfig>,
@@ -753,10 +1147,8 @@ pub fn library_to_games(
                 // The UI shows the bare path; re-apply the lnk:/exe:/url: scheme.
                 Some(add_launch_scheme(g.path.trim()))
             };
-            let auto_discovered = prev
-                .get(&g.process)
-                .map(GameConfig::is_auto_discovered)
-                .unwrap_or(false);
+            let prev = prev.get(&g.process);
+            let auto_discovered = prev.is_some_and(GameConfig::is_auto_discovered);
             let gc = GameConfig::Full {
                 game_id: game_id_from_name(&g.name),
                 app_id: if g.appid != 0 { Some(g.appid) } else { None },
@@ -764,6 +1156,9 @@ pub fn library_to_games(
                 launch_command,
                 auto_discovered,
                 exposed: g.exposed,
+                // Not editable in the UI; keep whatever the config file has.
+                window_title: prev.and_then(GameConfig::window_title).map(str::to_string),
+                aumid: prev.and_then(GameConfig::aumid).map(str::to_string),
             };
             (g.process.trim().to_string(), gc)
         })
@@ -800,6 +1195,8 @@ mod tests {
                 launch_command: Some("exe:C:/game.exe".into()),
                 auto_discovered: false,
                 exposed: false,
+                window_title: Some("My Game -".into()),
+                aumid: None,
             },
         );
 
@@ -824,6 +1221,11 @@ mod tests {
             back.get("manual.exe").unwrap().launch_command(),
             Some("exe:C:/game.exe".to_string())
         );
+        // Detection settings the UI doesn't show survive the save.
+        assert_eq!(
+            back.get("manual.exe").unwrap().window_title(),
+            Some("My Game -")
+        );
     }
 
     #[test]
@@ -1,15 +1,33 @@
-//! Auto-updater - checks GitHub releases on launch
+//! Auto-updater - checks GitHub releases on launch and every
+//! `intervals.update_check`
+//!
+//! Each check publishes the HA update entity (`update_available`: installed vs
+//! latest version, release notes); with `auto_update` the update is installed
+//! right away, otherwise it waits for the `InstallUpdate` command.
 //!
 //! Uses the "rename trick" on Windows: a running exe can be renamed but not
 //! deleted. So we rename ourselves to `.old`, move the update into place,
-//! spawn the new exe, and exit. The new instance cleans up `.old` on startup.
+//! spawn the new exe, and exit. The new instance keeps `.old` until it has
+//! run long enough to be trusted, and rolls back to it otherwise.
+//!
+//! Where the release publishes a patch from the running version
+//! (`<asset>.from-<version>.patch.gz`, a gzipped bsdiff patch CI builds from
+//! the last three releases and minisign-signs), only the patch is downloaded;
+//! any problem with it falls back to the full binary. Either way the result
+//! must match the signed manifest's SHA-256.
 
-use log::{info, warn};
+use log::{debug, info, warn};
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use ureq::tls::{RootCerts, TlsConfig, TlsProvider};
 
+use crate::AppState;
+use crate::textutil::truncate_chars;
+
 const GITHUB_OWNER: &str = "dank0i";
 const GITHUB_REPO: &str = "pc-bridge";
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -31,7 +49,8 @@ const USER_AGENT: &str = concat!("pc-bridge/", env!("CARGO_PKG_VERSION"));
 /// binary, so it can't serve an old validly-signed build under a newer tag), then
 /// authenticates the downloaded binary by SHA-256 against the manifest hash. That
 /// hash chain makes a separate per-binary signature (or a standalone `.sha256`)
-/// redundant, so neither is shipped.
+/// redundant, so neither is shipped. Delta patches (`.patch.gz`) come with a
+/// `.minisig` each, so an unsigned one is never even decompressed.
 ///
 /// While this is empty, updates are refused (there is no signed manifest to trust).
 /// Once set, a missing/invalid signature or manifest aborts the update.
@@ -40,6 +59,23 @@ const UPDATE_PUBLIC_KEY: &str = "RWSE5vU+bgt+LQ0szYtrcZL3qLst9oXVEdB/fptCnHUivRl
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
 
+/// HA caps an update entity's `release_summary` at 255 characters; the full
+/// notes go in the `changelog` attribute (capped to keep the payload small).
+const SUMMARY_MAX: usize = 255;
+const CHANGELOG_MAX: usize = 8 * 1024;
+
+/// A newer release found by the last check.
+#[derive(Debug, Clone, PartialEq)]
+struct Release {
+    version: String,
+    notes: String,
+    url: String,
+}
+
+/// Result of the last check, for `InstallUpdate`. `None` = up to date (or
+/// not checked yet).
+static LATEST: Mutex<Option<Release>> = Mutex::new(None);
+
 /// GitHub release info (minimal fields we need). Only used by the beta
 /// channel, which still queries the API; the stable channel resolves the
 /// version from a CDN redirect and never deserializes this. Extra JSON fields
@@ -50,6 +86,11 @@ struct GitHubRelease {
     #[serde(default)]
     #[allow(dead_code)]
     prerelease: bool,
+    /// Release notes (markdown)
+    #[serde(default)]
+    body: String,
+    #[serde(default)]
+    html_url: String,
 }
 
 /// Create an HTTP agent configured to use native-tls (OS TLS stack).
@@ -124,20 +165,24 @@ fn download_base_for(version: &str) -> String {
     )
 }
 
-/// Clean up leftover files from a previous update.
-/// Called on startup before the update check.
-pub fn cleanup_old_files() {
+/// Clean up leftover files from a previous update. The previous binary is
+/// kept while an update is still unconfirmed - it's the rollback copy.
+fn cleanup_old_files(keep_previous: bool) {
     let current_exe = match std::env::current_exe() {
         Ok(p) => p,
         Err(_) => return,
     };
 
-    // Windows: pc-bridge.exe.old
-    let old_path = current_exe.with_extension("exe.old");
-    if old_path.exists() {
-        match std::fs::remove_file(&old_path) {
-            Ok(()) => info!("Cleaned up old update file: {:?}", old_path),
-            Err(e) => warn!("Failed to clean up {:?}: {}", old_path, e),
+    let mut leftovers = vec![sibling(&current_exe, "failed")];
+    if !keep_previous {
+        leftovers.push(sibling(&current_exe, "old"));
+    }
+    for old_path in leftovers {
+        if old_path.exists() {
+            match std::fs::remove_file(&old_path) {
+                Ok(()) => info!("Cleaned up old update file: {:?}", old_path),
+                Err(e) => warn!("Failed to clean up {:?}: {}", old_path, e),
+            }
         }
     }
 
@@ -155,45 +200,368 @@ pub fn cleanup_old_files() {
     }
 }
 
-/// Check for updates and download if available.
-/// `update_channel`: "stable" (default), "beta", or "disabled".
-pub async fn check_for_updates(update_channel: String) {
-    if update_channel == "disabled" {
-        info!("Update checking disabled by config");
+/// `pc-bridge.exe` -> `pc-bridge.exe.<suffix>` (`pc-bridge` -> `pc-bridge.<suffix>`
+/// on Unix): the previous binary (`old`) and a rolled-back one (`failed`).
+fn sibling(exe: &Path, suffix: &str) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    exe.with_file_name(name)
+}
+
+/// An update counts as good once its first launch has run this long or shut
+/// down cleanly (Quit, Ctrl+C, an OS shutdown, a `--replace` takeover); a
+/// launch that dies sooner makes the next start roll back.
+const ROLLBACK_WINDOW: Duration = Duration::from_mins(1);
+
+/// This launch is an update still awaiting confirmation
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Written next to userConfig.json just before an update swaps the binary;
+/// removed once the new version has run for [`ROLLBACK_WINDOW`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+struct UpdateMarker {
+    from_version: String,
+    to_version: String,
+    /// Launches of `to_version` so far; one that never got confirmed means
+    /// it crashed.
+    #[serde(default)]
+    launches: u32,
+}
+
+impl UpdateMarker {
+    fn path() -> anyhow::Result<PathBuf> {
+        Ok(crate::config::Config::config_path()?.with_file_name("update_pending.json"))
+    }
+
+    fn load() -> Option<Self> {
+        let content = std::fs::read_to_string(Self::path().ok()?).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        crate::fsutil::write_atomic(&Self::path()?, content.as_bytes(), None)?;
+        Ok(())
+    }
+
+    fn remove() {
+        if let Ok(path) = Self::path() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum StartupAction {
+    /// No update in flight
+    None,
+    /// First launch of a fresh update: record it and start the window
+    FirstLaunch,
+    /// A launch of this version never confirmed: go back to the previous binary
+    RollBack,
+    /// Marker for another version (install failed before the swap, or manual
+    /// downgrade): drop it
+    Discard,
+}
+
+fn startup_action(marker: Option<&UpdateMarker>, current: &str) -> StartupAction {
+    match marker {
+        None => StartupAction::None,
+        Some(m) if m.to_version != current => StartupAction::Discard,
+        Some(m) if m.launches == 0 => StartupAction::FirstLaunch,
+        Some(_) => StartupAction::RollBack,
+    }
+}
+
+/// Startup half of update rollback, run before anything else touches the
+/// network. Rolls back (relaunching the previous binary; doesn't return) if
+/// the last launch of a fresh update didn't survive [`ROLLBACK_WINDOW`], then
+/// cleans up leftovers. Returns true while an update awaits confirmation -
+/// pass that to [`confirm_update`].
+pub fn startup_check() -> bool {
+    let marker = UpdateMarker::load();
+    let pending = match startup_action(marker.as_ref(), CURRENT_VERSION) {
+        StartupAction::None => false,
+        StartupAction::Discard => {
+            UpdateMarker::remove();
+            false
+        }
+        StartupAction::FirstLaunch => {
+            if let Some(mut marker) = marker {
+                marker.launches += 1;
+                if let Err(e) = marker.save() {
+                    warn!("Could not record update launch ({e}) - rollback disabled");
+                }
+            }
+            true
+        }
+        StartupAction::RollBack => {
+            let from = marker.map(|m| m.from_version).unwrap_or_default();
+            warn!(
+                "v{CURRENT_VERSION} didn't run {}s after updating - rolling back to v{from}",
+                ROLLBACK_WINDOW.as_secs()
+            );
+            let mut state = crate::feature_state::FeatureState::load();
+            state.rolled_back_version = Some(CURRENT_VERSION.to_string());
+            if let Err(e) = state.save() {
+                warn!("Could not record rolled-back version: {e}");
+            }
+            UpdateMarker::remove();
+            roll_back();
+            // Only reached if restoring failed; keep running the new version.
+            false
+        }
+    };
+    cleanup_old_files(pending);
+    PENDING.store(pending, Ordering::SeqCst);
+    pending
+}
+
+/// Confirm a pending update once this launch has run for [`ROLLBACK_WINDOW`].
+pub async fn confirm_update(pending: bool) {
+    if !pending {
+        return;
+    }
+    tokio::time::sleep(ROLLBACK_WINDOW).await;
+    confirm_pending_update();
+}
+
+/// Confirm the update this launch is, if any: drop the marker and the
+/// previous binary. Also called on every graceful shutdown, since a clean
+/// exit inside the window isn't a crash. A marker for another version (an
+/// install started since) is left alone.
+pub fn confirm_pending_update() {
+    if !PENDING.swap(false, Ordering::SeqCst) {
         return;
     }
+    if UpdateMarker::load().is_some_and(|m| m.to_version == CURRENT_VERSION) {
+        UpdateMarker::remove();
+    }
+    cleanup_old_files(false);
+    info!("Update to v{CURRENT_VERSION} confirmed");
+}
 
+/// Put the previous binary back and relaunch it. Returns only on failure.
+fn roll_back() {
+    let Ok(current_exe) = std::env::current_exe() else {
+        return;
+    };
+    let previous = sibling(&current_exe, "old");
+    if !previous.exists() {
+        warn!("No previous binary at {previous:?} - cannot roll back");
+        return;
+    }
+    // Windows can rename but not overwrite a running exe; Unix replaces it
+    // in place, but moving it aside keeps the two paths identical.
+    let failed = sibling(&current_exe, "failed");
+    let _ = std::fs::remove_file(&failed);
+    if let Err(e) = std::fs::rename(&current_exe, &failed) {
+        warn!("Rollback: failed to move the current binary aside: {e}");
+        return;
+    }
+    if let Err(e) = std::fs::rename(&previous, &current_exe) {
+        warn!("Rollback: failed to restore the previous binary: {e}");
+        let _ = std::fs::rename(&failed, &current_exe);
+        return;
+    }
+    relaunch(&current_exe);
+}
+
+/// Start `exe` with our own arguments plus `--replace` (so it takes over from
+/// this still-running instance) and exit. Returns only if the spawn failed.
+fn relaunch(exe: &Path) {
+    let mut cmd = std::process::Command::new(exe);
+    cmd.args(std::env::args_os().skip(1).filter(|a| a != "--replace"));
+    cmd.arg("--replace");
+    #[cfg(windows)]
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    if let Err(e) = cmd.spawn() {
+        warn!("Failed to start {exe:?}: {e}");
+        return;
+    }
+    std::process::exit(0);
+}
+
+/// Update task: check on launch, then every `intervals.update_check`, and
+/// publish the result. Channel, interval and `auto_update` are re-read each
+/// round, so hot-reloaded changes apply from the next check.
+pub async fn run(state: Arc<AppState>) {
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+    loop {
+        let (channel, auto_update, interval) = {
+            let config = state.config.read().await;
+            (
+                config.update_channel.clone(),
+                config.auto_update,
+                config.intervals.update_check.max(600),
+            )
+        };
+        if channel == "disabled" {
+            info!("Update checking disabled by config");
+        } else {
+            match check(&channel).await {
+                Ok(latest) => {
+                    set_latest(latest.clone());
+                    publish(&state, latest.as_ref(), false).await;
+                    if let Some(release) = latest
+                        && auto_update
+                    {
+                        if is_rolled_back(&release.version) {
+                            info!(
+                                "Not auto-installing {} - it was rolled back after failing to start",
+                                release.version
+                            );
+                        } else {
+                            install_release(&state, &release).await;
+                        }
+                    }
+                }
+                Err(e) => warn!("Failed to check for updates: {e}"),
+            }
+        }
+        tokio::select! {
+            _ = shutdown_rx.recv() => break,
+            () = tokio::time::sleep(Duration::from_secs(interval)) => {}
+        }
+    }
+}
+
+/// `InstallUpdate` command: install the release the last check found (or
+/// check now if there is none). Returns only if the update didn't happen.
+pub async fn install_latest(state: &AppState) {
+    let channel = state.config.read().await.update_channel.clone();
+    if channel == "disabled" {
+        warn!("InstallUpdate ignored - update_channel is \"disabled\"");
+        return;
+    }
+    let cached = LATEST.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    let release = match cached {
+        Some(release) => release,
+        None => match check(&channel).await {
+            Ok(Some(release)) => {
+                set_latest(Some(release.clone()));
+                release
+            }
+            Ok(None) => {
+                info!("InstallUpdate: already up to date (v{CURRENT_VERSION})");
+                return;
+            }
+            Err(e) => {
+                warn!("InstallUpdate: update check failed: {e}");
+                return;
+            }
+        },
+    };
+    install_release(state, &release).await;
+}
+
+fn is_rolled_back(version: &str) -> bool {
+    crate::feature_state::FeatureState::load()
+        .rolled_back_version
+        .as_deref()
+        == Some(version)
+}
+
+fn set_latest(latest: Option<Release>) {
+    *LATEST.lock().unwrap_or_else(|e| e.into_inner()) = latest;
+}
+
+/// Publish the HA update entity state (JSON) and the changelog attribute.
+async fn publish(state: &AppState, latest: Option<&Release>, in_progress: bool) {
+    let (payload, attrs) = update_payload(latest, in_progress);
+    state
+        .mqtt
+        .publish_sensor_retained("update_available", &payload.to_string())
+        .await;
+    state
+        .mqtt
+        .publish_sensor_attributes("update_available", &attrs)
+        .await;
+}
+
+/// (state, attributes) for the HA update entity. Up to date = latest equals
+/// installed, which HA shows as "no update".
+fn update_payload(
+    latest: Option<&Release>,
+    in_progress: bool,
+) -> (serde_json::Value, serde_json::Value) {
+    let latest_version = latest.map_or(CURRENT_VERSION, |r| r.version.as_str());
+    let notes = latest.map_or("", |r| r.notes.as_str());
+    let mut state = serde_json::json!({
+        "installed_version": CURRENT_VERSION,
+        "latest_version": latest_version,
+        "title": format!("PC Bridge {latest_version}"),
+        "in_progress": in_progress,
+    });
+    if let Some(release) = latest {
+        state["release_summary"] = truncate_chars(&release.notes, SUMMARY_MAX).into();
+        if !release.url.is_empty() {
+            state["release_url"] = release.url.clone().into();
+        }
+    }
+    let attrs = serde_json::json!({
+        "changelog": truncate_chars(notes, CHANGELOG_MAX),
+    });
+    (state, attrs)
+}
+
+/// Resolve the newest release on `channel`; `None` if not newer than this build.
+async fn check(channel: &str) -> anyhow::Result<Option<Release>> {
     // Stable resolves the version via the CDN-served redirect (no API limit).
     // Beta still needs the API, since there's no static URL for "latest
     // prerelease" - that path can hit the unauthenticated rate limit.
-    let remote_version = if update_channel == "beta" {
-        resolve_latest_via_api().await
+    let (version, notes) = if channel == "beta" {
+        let release = latest_release_via_api().await?;
+        (
+            release.tag_name.trim_start_matches('v').to_string(),
+            Some(release),
+        )
     } else {
-        resolve_latest_via_redirect().await
-    };
-
-    let remote_version = match remote_version {
-        Ok(v) => v,
-        Err(e) => {
-            warn!("Failed to check for updates: {}", e);
-            return;
-        }
+        (resolve_latest_via_redirect().await?, None)
     };
 
     // Cheap unsigned pre-check (the tag is attacker-controllable, so this is only
     // a "maybe there's an update" gate, not the authoritative decision).
-    if !is_newer_version(&remote_version, CURRENT_VERSION) {
+    if !is_newer_version(&version, CURRENT_VERSION) {
         info!("Already up to date (v{})", CURRENT_VERSION);
-        return;
+        return Ok(None);
     }
-
-    info!(
-        "Update available: {} -> {}",
-        CURRENT_VERSION, remote_version
+    info!("Update available: {} -> {}", CURRENT_VERSION, version);
+
+    // Stable only learned the version; fetch its notes (best effort - one API
+    // call, and only when there is an update).
+    let release = match notes {
+        Some(release) => Some(release),
+        None => release_by_tag(&version)
+            .await
+            .inspect_err(|e| debug!("Could not fetch release notes: {e}"))
+            .ok(),
+    };
+    let (notes, url) = release.map_or_else(
+        || (String::new(), release_page_for(&version)),
+        |r| (r.body, r.html_url),
     );
+    Ok(Some(Release {
+        version,
+        notes,
+        url,
+    }))
+}
 
+/// Verify and install `release`; on success the process restarts and this
+/// never returns.
+async fn install_release(state: &AppState, release: &Release) {
+    publish(state, Some(release), true).await;
+    if let Err(e) = download_and_install(&release.version).await {
+        warn!("Update to {} failed: {e}", release.version);
+        publish(state, Some(release), false).await;
+    }
+}
+
+async fn download_and_install(remote_version: &str) -> anyhow::Result<()> {
     let asset_name = platform_asset_name();
-    let base = download_base_for(&remote_version);
+    let base = download_base_for(remote_version);
     let asset_url = format!("{}/{}", base, asset_name);
 
     // Anti-rollback: the AUTHORITATIVE version + binary hash come from the SIGNED
@@ -204,42 +572,46 @@ pub async fn check_for_updates(update_channel: String) {
     let expected_sha256 = if UPDATE_PUBLIC_KEY.is_empty() {
         None
     } else {
-        match tokio::task::spawn_blocking({
+        let manifest = tokio::task::spawn_blocking({
             let base = base.clone();
             move || fetch_verified_manifest(&base, asset_name)
         })
-        .await
-        {
-            Ok(Ok(m)) => {
-                if !is_newer_version(&m.version, CURRENT_VERSION) {
-                    warn!(
-                        "Signed manifest version {} is not newer than {} - refusing (possible rollback)",
-                        m.version, CURRENT_VERSION
-                    );
-                    return;
-                }
-                Some(m.sha256)
-            }
-            Ok(Err(e)) => {
-                warn!("Could not verify update manifest: {e} - refusing to install");
-                return;
-            }
-            Err(e) => {
-                warn!("Manifest verification task failed: {e}");
-                return;
-            }
+        .await?
+        .map_err(|e| anyhow::anyhow!("could not verify update manifest: {e}"))?;
+        if !is_newer_version(&manifest.version, CURRENT_VERSION) {
+            anyhow::bail!(
+                "signed manifest version {} is not newer than {} - refusing (possible rollback)",
+                manifest.version,
+                CURRENT_VERSION
+            );
         }
+        Some(manifest.sha256)
     };
 
-    match download_update(&asset_url, asset_name, expected_sha256.as_deref()).await {
-        Ok(update_path) => {
-            info!("Update downloaded, installing...");
-            install_and_restart(&update_path);
-        }
-        Err(e) => {
-            warn!("Failed to download update: {}", e);
-        }
+    let delta = match expected_sha256.as_deref() {
+        Some(sha) => download_delta(&base, asset_name, sha)
+            .await
+            .inspect_err(|e| info!("No delta update ({e}) - downloading the full binary"))
+            .ok(),
+        None => None,
+    };
+    let update_path = match delta {
+        Some(path) => path,
+        None => download_update(&asset_url, asset_name, expected_sha256.as_deref()).await?,
+    };
+    info!("Update downloaded, installing...");
+    // Marker first: if the new binary dies before confirming itself, its next
+    // start rolls back to the copy install_and_restart keeps.
+    UpdateMarker {
+        from_version: CURRENT_VERSION.to_string(),
+        to_version: remote_version.to_string(),
+        launches: 0,
     }
+    .save()
+    .map_err(|e| anyhow::anyhow!("could not write update marker: {e}"))?;
+    install_and_restart(&update_path);
+    UpdateMarker::remove();
+    anyhow::bail!("install did not complete (see log above)")
 }
 
 /// Resolve the latest STABLE version by following the `releases/latest`
@@ -275,15 +647,31 @@ async fn resolve_latest_via_redirect() -> anyhow::Result<String> {
         .ok_or_else(|| anyhow::anyhow!("could not parse version from redirect: {location}"))
 }
 
-/// Resolve the latest version (including prereleases) via the GitHub API.
-/// Only used for the beta channel.
-async fn resolve_latest_via_api() -> anyhow::Result<String> {
+/// Latest release (including prereleases) via the GitHub API. Only used for
+/// the beta channel.
+async fn latest_release_via_api() -> anyhow::Result<GitHubRelease> {
     let url = format!(
         "https://api.github.com/repos/{}/{}/releases?per_page=1",
         GITHUB_OWNER, GITHUB_REPO
     );
+    let releases: Vec<GitHubRelease> = serde_json::from_str(&api_get(url).await?)?;
+    releases
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("No releases found"))
+}
 
-    let response = tokio::task::spawn_blocking(move || -> anyhow::Result<String> {
+/// Release metadata (notes) for a known version.
+async fn release_by_tag(version: &str) -> anyhow::Result<GitHubRelease> {
+    let url = format!(
+        "https://api.github.com/repos/{}/{}/releases/tags/v{}",
+        GITHUB_OWNER, GITHUB_REPO, version
+    );
+    Ok(serde_json::from_str(&api_get(url).await?)?)
+}
+
+async fn api_get(url: String) -> anyhow::Result<String> {
+    tokio::task::spawn_blocking(move || -> anyhow::Result<String> {
         let body = http_agent()
             .get(&url)
             .header("User-Agent", USER_AGENT)
@@ -292,27 +680,32 @@ async fn resolve_latest_via_api() -> anyhow::Result<String> {
             .read_to_string()?;
         Ok(body)
     })
-    .await??;
+    .await?
+}
 
-    let releases: Vec<GitHubRelease> = serde_json::from_str(&response)?;
-    let release = releases
-        .into_iter()
-        .next()
-        .ok_or_else(|| anyhow::anyhow!("No releases found"))?;
-    Ok(release.tag_name.trim_start_matches('v').to_string())
+fn release_page_for(version: &str) -> String {
+    format!(
+        "https://github.com/{}/{}/releases/tag/v{}",
+        GITHUB_OWNER, GITHUB_REPO, version
+    )
 }
 
-async fn download_update(
-    url: &str,
-    filename: &str,
-    expected_sha256: Option<&str>,
-) -> anyhow::Result<PathBuf> {
+/// Where a downloaded update is staged (next to the exe, so the final rename
+/// stays on one filesystem).
+fn staged_update_path(filename: &str) -> anyhow::Result<PathBuf> {
     let exe_dir = std::env::current_exe()?
         .parent()
         .ok_or_else(|| anyhow::anyhow!("No parent dir"))?
         .to_path_buf();
+    Ok(exe_dir.join(format!("{}.update", filename)))
+}
 
-    let update_path = exe_dir.join(format!("{}.update", filename));
+async fn download_update(
+    url: &str,
+    filename: &str,
+    expected_sha256: Option<&str>,
+) -> anyhow::Result<PathBuf> {
+    let update_path = staged_update_path(filename)?;
 
     let url = url.to_string();
     let expected_sha256 = expected_sha256.map(String::from);
@@ -352,6 +745,123 @@ async fn download_update(
     Ok(update_path)
 }
 
+/// Upper bound for a patched binary and a compressed patch; a corrupt or
+/// hostile patch can't make us allocate more than this.
+const MAX_BINARY_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Rebuild the new binary from the running one plus the release's patch for
+/// this version, staged like a full download. Only used with a signed
+/// manifest hash, which the result must match; the patch's own signature is
+/// checked before it's decompressed.
+async fn download_delta(
+    base: &str,
+    filename: &str,
+    expected_sha256: &str,
+) -> anyhow::Result<PathBuf> {
+    let url = format!("{base}/{filename}.from-{CURRENT_VERSION}.patch.gz");
+    let update_path = staged_update_path(filename)?;
+    let expected_sha256 = expected_sha256.to_string();
+    let path = update_path.clone();
+
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        use std::io::Read;
+
+        let mut compressed = Vec::new();
+        http_agent()
+            .get(&url)
+            .header("User-Agent", USER_AGENT)
+            .call()?
+            .into_body()
+            .as_reader()
+            .take(MAX_BINARY_BYTES)
+            .read_to_end(&mut compressed)?;
+        verify_signature_bytes(&compressed, &format!("{url}.minisig"))?;
+        let mut raw_patch = Vec::new();
+        flate2::read::GzDecoder::new(compressed.as_slice())
+            .take(MAX_BINARY_BYTES)
+            .read_to_end(&mut raw_patch)?;
+
+        let old = std::fs::read(std::env::current_exe()?)?;
+        let new = apply_patch(&old, &raw_patch)?;
+        std::fs::write(&path, &new)?;
+        verify_sha256_hex(&path, &expected_sha256)?;
+        info!(
+            "Delta update applied: {} KiB downloaded instead of {} KiB",
+            compressed.len() / 1024,
+            new.len() / 1024
+        );
+        Ok(())
+    })
+    .await??;
+    Ok(update_path)
+}
+
+/// Apply a raw bsdiff patch (as produced by the `bsdiff` crate, before
+/// compression). The patch is a sequence of chunks, each:
+/// - three 8-byte integers (sign-magnitude, little-endian): `mix_len`,
+///   `copy_len`, `seek`
+/// - `mix_len` bytes added bytewise (wrapping) to the old file at the cursor
+/// - `copy_len` bytes copied verbatim
+///
+/// after which the old-file cursor moves by `seek`.
+fn apply_patch(old: &[u8], patch: &[u8]) -> anyhow::Result<Vec<u8>> {
+    fn offtin(bytes: &[u8]) -> i64 {
+        let raw = u64::from_le_bytes(bytes.try_into().unwrap_or([0; 8]));
+        let magnitude = (raw & !(1 << 63)) as i64;
+        if raw & (1 << 63) == 0 {
+            magnitude
+        } else {
+            -magnitude
+        }
+    }
+    fn take<'a>(patch: &'a [u8], pos: &mut usize, len: usize) -> anyhow::Result<&'a [u8]> {
+        let end = pos
+            .checked_add(len)
+            .filter(|end| *end <= patch.len())
+            .ok_or_else(|| anyhow::anyhow!("truncated patch"))?;
+        let slice = &patch[*pos..end];
+        *pos = end;
+        Ok(slice)
+    }
+
+    let mut new = Vec::with_capacity(old.len());
+    let mut pos = 0;
+    let mut old_pos: i64 = 0;
+    while pos < patch.len() {
+        let header = take(patch, &mut pos, 24)?;
+        let (mix_len, copy_len, seek) = (
+            offtin(&header[..8]),
+            offtin(&header[8..16]),
+            offtin(&header[16..]),
+        );
+        let (Ok(mix_len), Ok(copy_len)) = (usize::try_from(mix_len), usize::try_from(copy_len))
+        else {
+            anyhow::bail!("corrupt patch: negative length");
+        };
+        if new.len() as u64 + mix_len as u64 + copy_len as u64 > MAX_BINARY_BYTES {
+            anyhow::bail!("corrupt patch: output too large");
+        }
+
+        let mix = take(patch, &mut pos, mix_len)?;
+        let start = usize::try_from(old_pos)
+            .ok()
+            .filter(|start| start.saturating_add(mix_len) <= old.len())
+            .ok_or_else(|| anyhow::anyhow!("patch does not match the installed binary"))?;
+        new.extend(
+            old[start..start + mix_len]
+                .iter()
+                .zip(mix)
+                .map(|(o, d)| o.wrapping_add(*d)),
+        );
+        new.extend_from_slice(take(patch, &mut pos, copy_len)?);
+        old_pos = old_pos
+            .checked_add(mix_len as i64)
+            .and_then(|p| p.checked_add(seek))
+            .ok_or_else(|| anyhow::anyhow!("corrupt patch: bad seek"))?;
+    }
+    Ok(new)
+}
+
 /// Verify a minisign signature (fetched from `sig_url`) over `bytes` using the
 /// embedded [`UPDATE_PUBLIC_KEY`].
 fn verify_signature_bytes(bytes: &[u8], sig_url: &str) -> anyhow::Result<()> {
@@ -483,11 +993,10 @@ fn prerelease_gt(a: &str, b: &str) -> bool {
 /// 1. Rename `pc-bridge.exe` → `pc-bridge.exe.old`
 /// 2. Rename `pc-bridge.exe.update` → `pc-bridge.exe`
 /// 3. Spawn the new `pc-bridge.exe`
-/// 4. Exit - new instance cleans up `.old` on startup via `cleanup_old_files()`
+/// 4. Exit - the new instance keeps `.old` as its rollback copy until the
+///    update is confirmed (see [`startup_check`])
 #[cfg(windows)]
 fn install_and_restart(update_path: &Path) {
-    use std::process::Command;
-
     let current_exe = match std::env::current_exe() {
         Ok(p) => p,
         Err(e) => {
@@ -496,7 +1005,7 @@ fn install_and_restart(update_path: &Path) {
         }
     };
 
-    let old_path = current_exe.with_extension("exe.old");
+    let old_path = sibling(&current_exe, "old");
 
     // Remove any leftover .old file from a previous update
     if old_path.exists()
@@ -527,32 +1036,21 @@ fn install_and_restart(update_path: &Path) {
 
     info!("Update installed, starting new version...");
 
-    // Step 3: Spawn the new exe, forwarding the CLI args we were started with
+    // Steps 3 + 4: spawn the new exe, forwarding the CLI args we were started with
     // (e.g. --config-dir / service flags) so the restart preserves them. `--replace`
     // tells the new process this is an update takeover, so it replaces us instead of
     // treating the still-running old instance as "already running" and opening the UI.
-    let mut cmd = Command::new(&current_exe);
-    cmd.args(std::env::args_os().skip(1));
-    cmd.arg("--replace");
-    cmd.creation_flags(CREATE_NO_WINDOW);
-
-    if let Err(e) = cmd.spawn() {
-        warn!("Failed to start updated exe: {}", e);
-        return;
-    }
-
-    // Step 4: Exit so the new instance takes over
-    std::process::exit(0);
+    relaunch(&current_exe);
 }
 
 /// Install update and restart (Unix).
 ///
 /// On Unix, a running binary can be replaced directly (the OS keeps the old
-/// inode open until the process exits). So this is even simpler than Windows.
+/// inode open until the process exits). So this is even simpler than Windows;
+/// the previous binary is copied to `<exe>.old` first for rollback.
 #[cfg(unix)]
 fn install_and_restart(update_path: &Path) {
     use std::os::unix::fs::PermissionsExt;
-    use std::process::Command;
 
     let current_exe = match std::env::current_exe() {
         Ok(p) => p,
@@ -567,6 +1065,11 @@ fn install_and_restart(update_path: &Path) {
         warn!("Failed to set permissions: {}", e);
     }
 
+    if let Err(e) = std::fs::copy(&current_exe, sibling(&current_exe, "old")) {
+        warn!("Failed to keep the previous binary for rollback: {}", e);
+        return;
+    }
+
     // Replace in place - Unix allows this while running
     if let Err(e) = std::fs::rename(update_path, &current_exe) {
         warn!("Failed to replace binary: {}", e);
@@ -574,17 +1077,7 @@ fn install_and_restart(update_path: &Path) {
     }
 
     info!("Update installed, starting new version...");
-
-    if let Err(e) = Command::new(&current_exe)
-        .args(std::env::args_os().skip(1))
-        .arg("--replace")
-        .spawn()
-    {
-        warn!("Failed to start updated binary: {}", e);
-        return;
-    }
-
-    std::process::exit(0);
+    relaunch(&current_exe);
 }
 
 #[cfg(test)]
@@ -771,4 +1264,111 @@ cS8yVMu/941m9iEXeSa/T6K+kerWtz+h/yjhdVGQMs4MccQmzxW1km/6/aOXbo4G0D5dvSk0AgzfVD8j
             assert!(part.parse::<u64>().is_ok(), "Non-numeric segment: {part}");
         }
     }
+
+    #[test]
+    fn test_update_payload() {
+        let (state, attrs) = update_payload(None, false);
+        assert_eq!(state["installed_version"], CURRENT_VERSION);
+        assert_eq!(state["latest_version"], CURRENT_VERSION);
+        assert!(state.get("release_summary").is_none());
+        assert_eq!(attrs["changelog"], "");
+
+        let release = Release {
+            version: "99.0.0".to_string(),
+            notes: "x".repeat(600),
+            url: "https://github.com/dank0i/pc-bridge/releases/tag/v99.0.0".to_string(),
+        };
+        let (state, attrs) = update_payload(Some(&release), true);
+        assert_eq!(state["latest_version"], "99.0.0");
+        assert_eq!(state["title"], "PC Bridge 99.0.0");
+        assert_eq!(state["in_progress"], true);
+        assert_eq!(
+            state["release_summary"].as_str().unwrap().chars().count(),
+            SUMMARY_MAX
+        );
+        assert_eq!(state["release_url"], release.url);
+        assert_eq!(attrs["changelog"], release.notes);
+    }
+
+    #[test]
+    fn test_startup_action() {
+        let marker = |to: &str, launches| UpdateMarker {
+            from_version: "1.0.0".to_string(),
+            to_version: to.to_string(),
+            launches,
+        };
+        assert_eq!(startup_action(None, "2.0.0"), StartupAction::None);
+        assert_eq!(
+            startup_action(Some(&marker("2.0.0", 0)), "2.0.0"),
+            StartupAction::FirstLaunch
+        );
+        // The first launch never confirmed: it crashed.
+        assert_eq!(
+            startup_action(Some(&marker("2.0.0", 1)), "2.0.0"),
+            StartupAction::RollBack
+        );
+        // Still the old version: the swap never happened.
+        assert_eq!(
+            startup_action(Some(&marker("2.0.0", 0)), "1.0.0"),
+            StartupAction::Discard
+        );
+    }
+
+    #[test]
+    fn test_sibling_paths() {
+        let exe = Path::new("/opt/pc-bridge/pc-bridge");
+        assert_eq!(
+            sibling(exe, "old"),
+            Path::new("/opt/pc-bridge/pc-bridge.old")
+        );
+        // Same name the Windows rename trick has always used.
+        let exe = Path::new("pc-bridge.exe");
+        assert_eq!(sibling(exe, "old"), exe.with_extension("exe.old"));
+    }
+
+    /// Encode one bsdiff chunk (sign-magnitude header) for the patch tests.
+    fn chunk(mix: &[u8], copy: &[u8], seek: i64) -> Vec<u8> {
+        let offtout = |x: i64| {
+            if x >= 0 {
+                x.to_le_bytes()
+            } else {
+                (x.unsigned_abs() | (1 << 63)).to_le_bytes()
+            }
+        };
+        let mut out = Vec::new();
+        out.extend(offtout(mix.len() as i64));
+        out.extend(offtout(copy.len() as i64));
+        out.extend(offtout(seek));
+        out.extend(mix);
+        out.extend(copy);
+        out
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let old = b"hello world";
+        // "hello " unchanged, "W" = 'w' + 0xE0 (wrapping), new tail copied,
+        // then seek back to reuse "hello" again.
+        let mut patch = chunk(&[0; 6], b"", 0);
+        patch.extend(chunk(&[0xE0], b"ORLD!", -7));
+        patch.extend(chunk(&[0; 5], b"", 0));
+        assert_eq!(apply_patch(old, &patch).unwrap(), b"hello WORLD!hello");
+        assert_eq!(apply_patch(old, &[]).unwrap(), b"");
+    }
+
+    #[test]
+    fn test_apply_patch_rejects_bad_input() {
+        let old = b"hello";
+        // Truncated header / body.
+        assert!(apply_patch(old, &[0; 10]).is_err());
+        let mut truncated = chunk(&[0; 2], b"abc", 0);
+        truncated.pop();
+        assert!(apply_patch(old, &truncated).is_err());
+        // Reads past the end of the installed binary (wrong base version).
+        assert!(apply_patch(old, &chunk(&[0; 6], b"", 0)).is_err());
+        // Seeks before the start.
+        let mut before = chunk(&[0; 1], b"", -5);
+        before.extend(chunk(&[0; 1], b"", 0));
+        assert!(apply_patch(old, &before).is_err());
+    }
 }
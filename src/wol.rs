@@ -0,0 +1,103 @@
+//! Wake-on-LAN relay
+//!
+//! A sleeping PC can't receive MQTT, so its own `Wake` button can't wake it.
+//! With `wol_relay` targets configured, an always-on pc-bridge subscribes to
+//! wake requests for those devices - `pc-bridge/wol/<device_name>` (the
+//! relay's "Wake <device>" button) and the target's own `Wake` button topic -
+//! and sends the magic packet itself. Both arrive as the `WakeOnLan` command
+//! with the target's device_name as payload.
+
+use std::net::UdpSocket;
+
+use anyhow::{Context, Result, bail};
+use log::{info, warn};
+
+use crate::AppState;
+use crate::config::WolTarget;
+
+/// Relay topic for one target: `pc-bridge/wol/<device_name>`.
+pub fn relay_topic(device_name: &str) -> String {
+    format!("pc-bridge/wol/{device_name}")
+}
+
+/// `AA:BB:CC:DD:EE:FF` (also `-`-separated or bare hex) to bytes.
+pub fn parse_mac(mac: &str) -> Result<[u8; 6]> {
+    let hex: String = mac
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .collect();
+    if hex.len() != 12 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("'{mac}' is not a MAC address");
+    }
+    let mut bytes = [0u8; 6];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)?;
+    }
+    Ok(bytes)
+}
+
+/// Six 0xFF bytes, then the MAC sixteen times.
+fn magic_packet(mac: [u8; 6]) -> [u8; 102] {
+    let mut packet = [0xFF; 102];
+    for chunk in packet[6..].chunks_exact_mut(6) {
+        chunk.copy_from_slice(&mac);
+    }
+    packet
+}
+
+/// The `WakeOnLan` command: wake the relay target named by `payload`.
+pub(crate) async fn run(payload: &str, state: &AppState) {
+    let target = state
+        .config
+        .read()
+        .await
+        .wol_relay
+        .iter()
+        .find(|t| t.device_name == payload)
+        .cloned();
+    let Some(target) = target else {
+        warn!("WakeOnLan: '{payload}' is not a wol_relay target");
+        return;
+    };
+    if let Ok(Err(e)) = tokio::task::spawn_blocking(move || wake(&target)).await {
+        warn!("WakeOnLan: {e:#}");
+    }
+}
+
+/// Send the magic packet for `target`. Blocking (one UDP send).
+pub fn wake(target: &WolTarget) -> Result<()> {
+    let packet = magic_packet(parse_mac(&target.mac)?);
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).context("bind UDP socket")?;
+    socket.set_broadcast(true).context("enable broadcast")?;
+    socket
+        .send_to(&packet, (target.broadcast.as_str(), target.port))
+        .with_context(|| format!("send to {}:{}", target.broadcast, target.port))?;
+    info!(
+        "Wake-on-LAN: sent magic packet for {} ({}) to {}:{}",
+        target.device_name, target.mac, target.broadcast, target.port
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mac() {
+        let expected = [0xAA, 0xBB, 0xCC, 0x01, 0x02, 0x03];
+        assert_eq!(parse_mac("AA:BB:CC:01:02:03").unwrap(), expected);
+        assert_eq!(parse_mac("aa-bb-cc-01-02-03").unwrap(), expected);
+        assert_eq!(parse_mac("aabbcc010203").unwrap(), expected);
+        assert!(parse_mac("AA:BB:CC:01:02").is_err());
+        assert!(parse_mac("GG:BB:CC:01:02:03").is_err());
+    }
+
+    #[test]
+    fn test_magic_packet() {
+        let mac = [1, 2, 3, 4, 5, 6];
+        let packet = magic_packet(mac);
+        assert!(packet[..6].iter().all(|&b| b == 0xFF));
+        assert!(packet[6..].chunks(6).all(|c| c == mac));
+    }
+}
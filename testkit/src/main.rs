@@ -264,7 +264,7 @@ async fn connect(
     opts.set_keep_alive(Duration::from_secs(30));
     let (client, mut eventloop) = AsyncClient::new(opts, 128);
 
-    let avail_topic = format!("homeassistant/sensor/{device}/availability");
+    let avail_topic = format!("pc-bridge/{device}/availability");
     let test_topic = format!("pc-bridge/test/executed/{device}");
     client.subscribe(&avail_topic, QoS::AtLeastOnce).await?;
     client.subscribe(&test_topic, QoS::AtLeastOnce).await?;